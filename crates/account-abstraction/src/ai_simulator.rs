@@ -1,338 +1,242 @@
 use ethereum_types::{H256, U256, Address};
 use ethereum_core::Transaction;
-use candle_core::{Device, Tensor};
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{info, debug};
+use ethereum_evm::execution::{BlockContext, ExecutionStatus, Log};
+use ethereum_evm::state::StateDB;
+use ethereum_evm::{decode_revert_reason, run_interpreter, ExecutionContext, JournaledState};
+use ethereum_state::{transfer, CallState, StateDbProvider, TX_BASE_GAS};
+use std::collections::HashSet;
 
 use crate::{Result, AccountAbstractionError};
 
-/// AI-powered transaction simulator
-pub struct AITransactionSimulator {
-    model: Arc<SimulationModel>,
-    cache: Arc<RwLock<SimulationCache>>,
-    device: Device,
+/// Outbound native transfers from this much on are flagged (10 ETH)
+pub const DEFAULT_LARGE_TRANSFER: u128 = 10_000_000_000_000_000_000;
+
+/// Call to simulate
+///
+/// A user operation maps onto this as a call from the entry point to the
+/// smart account with the operation's call data and call gas limit.
+#[derive(Debug, Clone)]
+pub struct SimulationRequest {
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    pub data: Vec<u8>,
+    pub gas_limit: u64,
 }
 
-impl AITransactionSimulator {
-    pub fn new() -> Result<Self> {
-        let device = Device::cuda_if_available(0).unwrap_or(Device::Cpu);
-        
-        Ok(Self {
-            model: Arc::new(SimulationModel::load()?),
-            cache: Arc::new(RwLock::new(SimulationCache::new(1000))),
-            device,
+impl SimulationRequest {
+    /// Request for a transaction, `None` for contract creations
+    pub fn from_transaction(tx: &Transaction) -> Option<Self> {
+        Some(Self {
+            from: tx.from(),
+            to: tx.to()?,
+            value: tx.value(),
+            data: tx.data().as_slice().to_vec(),
+            gas_limit: tx.gas_limit().min(U256::from(u64::MAX)).as_u64(),
         })
     }
-    
-    /// Simulate a transaction using AI model
-    pub async fn simulate(&self, tx: &Transaction) -> Result<SimulationResult> {
-        info!("AI-powered simulation for transaction: {:?}", tx.hash());
-        
-        // Check cache
-        if let Some(cached) = self.cache.read().await.get(&tx.hash()) {
-            return Ok(cached.clone());
+}
+
+/// Pre-flight simulator: executes a call against the current state without
+/// committing anything and flags patterns users commonly get drained by
+///
+/// The flags are heuristics, a call without flags is not necessarily safe.
+pub struct AITransactionSimulator {
+    block: BlockContext,
+    verified_contracts: HashSet<Address>,
+    large_transfer: U256,
+}
+
+impl AITransactionSimulator {
+    pub fn new(block: BlockContext) -> Self {
+        Self {
+            block,
+            verified_contracts: HashSet::new(),
+            large_transfer: U256::from(DEFAULT_LARGE_TRANSFER),
         }
-        
-        // Extract features from transaction
-        let features = self.extract_features(tx)?;
-        
-        // Run AI model inference
-        let prediction = self.model.predict(&features, &self.device).await?;
-        
-        // Interpret prediction
-        let result = self.interpret_prediction(prediction, tx)?;
-        
-        // Cache result
-        self.cache.write().await.insert(tx.hash(), result.clone());
-        
-        Ok(result)
-    }
-    
-    /// Extract features from transaction for AI model
-    fn extract_features(&self, tx: &Transaction) -> Result<TransactionFeatures> {
-        Ok(TransactionFeatures {
-            gas_price: tx.gas_price.as_u64() as f32 / 1e9,
-            gas_limit: tx.gas_limit.as_u64() as f32,
-            value: tx.value.as_u64() as f32 / 1e18,
-            nonce: tx.nonce.as_u64() as f32,
-            data_size: tx.data.len() as f32,
-            is_contract_creation: tx.to.is_none(),
-            function_selector: self.extract_function_selector(&tx.data),
-            historical_success_rate: self.get_historical_success_rate(&tx.from),
-            network_congestion: self.estimate_network_congestion(),
-            mev_risk_score: self.calculate_mev_risk(tx),
-        })
     }
-    
-    fn extract_function_selector(&self, data: &[u8]) -> [f32; 4] {
-        if data.len() >= 4 {
-            [
-                data[0] as f32 / 255.0,
-                data[1] as f32 / 255.0,
-                data[2] as f32 / 255.0,
-                data[3] as f32 / 255.0,
-            ]
-        } else {
-            [0.0; 4]
-        }
+
+    /// Contracts with verified source, calls to any other contract are flagged
+    pub fn with_verified_contracts(mut self, contracts: impl IntoIterator<Item = Address>) -> Self {
+        self.verified_contracts.extend(contracts);
+        self
     }
-    
-    fn get_historical_success_rate(&self, address: &Address) -> f32 {
-        // Would query historical data
-        0.95 // Mock 95% success rate
+
+    /// Smallest outbound native transfer flagged as large
+    pub fn with_large_transfer_threshold(mut self, threshold: U256) -> Self {
+        self.large_transfer = threshold;
+        self
     }
-    
-    fn estimate_network_congestion(&self) -> f32 {
-        // Would query network state
-        0.3 // Mock 30% congestion
+
+    pub fn simulate_transaction<S: StateDB + Sync>(&self, state: &S, tx: &Transaction) -> Result<SimulationResult> {
+        let request = SimulationRequest::from_transaction(tx).ok_or_else(|| {
+            AccountAbstractionError::SimulationFailed("contract creation is not simulated".to_string())
+        })?;
+        self.simulate(state, &request)
     }
-    
-    fn calculate_mev_risk(&self, tx: &Transaction) -> f32 {
-        // Analyze MEV vulnerability
-        if tx.value > U256::from(10).pow(U256::from(18)) {
-            0.7 // High value = higher MEV risk
+
+    /// Execute `request` on an overlay of `state`
+    ///
+    /// Balance changes cover value moved by the call, gas fees are not
+    /// charged. A failed call reports no balance or storage changes.
+    pub fn simulate<S: StateDB + Sync>(&self, state: &S, request: &SimulationRequest) -> Result<SimulationResult> {
+        let provider = StateDbProvider(state);
+        let mut overlay = CallState::new(&provider, H256::zero());
+
+        let balance = overlay.get_account(&request.from).map(|acc| acc.balance).unwrap_or_default();
+        if balance < request.value {
+            return Err(AccountAbstractionError::SimulationFailed(
+                "insufficient funds for value transfer".to_string()
+            ));
+        }
+        let gas_limit = request.gas_limit.checked_sub(TX_BASE_GAS).ok_or_else(|| {
+            AccountAbstractionError::SimulationFailed(format!("gas limit below {}", TX_BASE_GAS))
+        })?;
+
+        let code = overlay.get_account(&request.to).map(|acc| acc.code).unwrap_or_default();
+        let mut context = ExecutionContext::new(
+            request.from,
+            request.to,
+            request.value,
+            code.clone(),
+            request.data.clone(),
+            gas_limit,
+            self.block.clone(),
+        );
+        context.gas_price = self.block.base_fee.unwrap_or_default();
+
+        transfer(&mut overlay, request.from, request.to, request.value);
+        let result = if code.is_empty() {
+            ethereum_evm::ExecutionResult::success(Vec::new(), 0)
         } else {
-            0.2
+            let mut journaled = JournaledState::new(&mut overlay);
+            run_interpreter(context, &mut journaled)
+        };
+
+        let success = result.status == ExecutionStatus::Success;
+        if !success {
+            overlay = CallState::new(&provider, H256::zero());
         }
+
+        let mut simulation = SimulationResult {
+            success,
+            gas_used: TX_BASE_GAS + result.gas_used,
+            revert_reason: match result.status {
+                ExecutionStatus::Revert => decode_revert_reason(&result.return_data),
+                _ => None,
+            },
+            return_data: result.return_data,
+            balance_changes: balance_changes(state, &overlay),
+            state_changes: storage_changes(state, &overlay),
+            events: result.logs,
+            risk_flags: Vec::new(),
+        };
+        simulation.risk_flags = self.risk_flags(request, &code, &simulation);
+
+        Ok(simulation)
     }
-    
-    /// Interpret AI model prediction
-    fn interpret_prediction(
-        &self,
-        prediction: ModelPrediction,
-        tx: &Transaction,
-    ) -> Result<SimulationResult> {
-        Ok(SimulationResult {
-            success_probability: prediction.success_probability,
-            estimated_gas_used: U256::from((prediction.gas_estimate * 1e6) as u64),
-            revert_reason: prediction.revert_reason,
-            warnings: prediction.warnings,
-            optimization_suggestions: self.generate_optimizations(&prediction, tx),
-            mev_protection_needed: prediction.mev_risk > 0.5,
-            recommended_gas_price: self.calculate_optimal_gas_price(&prediction),
-            execution_path: prediction.execution_path,
-            state_changes: prediction.state_changes,
-        })
-    }
-    
-    fn generate_optimizations(
-        &self,
-        prediction: &ModelPrediction,
-        tx: &Transaction,
-    ) -> Vec<OptimizationSuggestion> {
-        let mut suggestions = Vec::new();
-        
-        if prediction.gas_estimate > tx.gas_limit.as_u64() as f32 * 0.8 {
-            suggestions.push(OptimizationSuggestion {
-                type_: OptimizationType::GasLimit,
-                description: "Consider increasing gas limit".to_string(),
-                impact: ImpactLevel::High,
-            });
+
+    fn risk_flags(&self, request: &SimulationRequest, code: &[u8], simulation: &SimulationResult) -> Vec<RiskFlag> {
+        let mut flags = Vec::new();
+
+        if !code.is_empty() && !self.verified_contracts.contains(&request.to) {
+            flags.push(RiskFlag::UnverifiedContract { address: request.to });
         }
-        
-        if prediction.mev_risk > 0.5 {
-            suggestions.push(OptimizationSuggestion {
-                type_: OptimizationType::MEVProtection,
-                description: "Use flashbots or private mempool".to_string(),
-                impact: ImpactLevel::Critical,
-            });
+
+        let outflow = simulation.balance_changes.iter()
+            .find(|change| change.address == request.from)
+            .map(|change| change.before.saturating_sub(change.after))
+            .unwrap_or_default();
+        if outflow >= self.large_transfer {
+            flags.push(RiskFlag::LargeTransfer { amount: outflow });
         }
-        
-        suggestions
-    }
-    
-    fn calculate_optimal_gas_price(&self, prediction: &ModelPrediction) -> U256 {
-        let base = 30_000_000_000u64; // 30 gwei
-        let multiplier = (1.0 + prediction.urgency_score) as u64;
-        U256::from(base * multiplier)
-    }
-    
-    /// Train the model with new data
-    pub async fn train(&mut self, training_data: Vec<TrainingExample>) -> Result<()> {
-        info!("Training AI model with {} examples", training_data.len());
-        
-        // Convert to tensors
-        let inputs = self.prepare_training_inputs(&training_data)?;
-        let targets = self.prepare_training_targets(&training_data)?;
-        
-        // Train model
-        self.model.train(&inputs, &targets, &self.device).await?;
-        
-        Ok(())
-    }
-    
-    fn prepare_training_inputs(&self, data: &[TrainingExample]) -> Result<Tensor> {
-        // Convert training data to tensors
-        let features: Vec<f32> = data.iter()
-            .flat_map(|ex| ex.features.to_vec())
-            .collect();
-        
-        Ok(Tensor::from_vec(
-            features,
-            &[data.len(), TransactionFeatures::SIZE],
-            &self.device,
-        ).map_err(|e| AccountAbstractionError::SimulationFailed(e.to_string()))?)
-    }
-    
-    fn prepare_training_targets(&self, data: &[TrainingExample]) -> Result<Tensor> {
-        let targets: Vec<f32> = data.iter()
-            .map(|ex| if ex.successful { 1.0 } else { 0.0 })
-            .collect();
-        
-        Ok(Tensor::from_vec(
-            targets,
-            &[data.len(), 1],
-            &self.device,
-        ).map_err(|e| AccountAbstractionError::SimulationFailed(e.to_string()))?)
+
+        let approval = approval_topic();
+        let owner = address_topic(&request.from);
+        for event in &simulation.events {
+            if event.topics.len() == 3 && event.topics[0] == approval && event.topics[1] == owner {
+                let amount = U256::from_big_endian(&event.data[..event.data.len().min(32)]);
+                if amount >= unlimited_allowance() {
+                    flags.push(RiskFlag::UnlimitedApproval {
+                        token: event.address,
+                        spender: Address::from_slice(&event.topics[2].as_bytes()[12..]).unwrap_or_default(),
+                    });
+                }
+            }
+        }
+
+        flags
     }
 }
 
-/// Transaction features for AI model
-#[derive(Debug, Clone)]
-struct TransactionFeatures {
-    gas_price: f32,
-    gas_limit: f32,
-    value: f32,
-    nonce: f32,
-    data_size: f32,
-    is_contract_creation: bool,
-    function_selector: [f32; 4],
-    historical_success_rate: f32,
-    network_congestion: f32,
-    mev_risk_score: f32,
+/// Allowances from half of uint256 on are how "infinite" approvals are
+/// usually encoded
+fn unlimited_allowance() -> U256 {
+    U256::one() << 255
 }
 
-impl TransactionFeatures {
-    const SIZE: usize = 14;
-    
-    fn to_vec(&self) -> Vec<f32> {
-        vec![
-            self.gas_price,
-            self.gas_limit,
-            self.value,
-            self.nonce,
-            self.data_size,
-            if self.is_contract_creation { 1.0 } else { 0.0 },
-            self.function_selector[0],
-            self.function_selector[1],
-            self.function_selector[2],
-            self.function_selector[3],
-            self.historical_success_rate,
-            self.network_congestion,
-            self.mev_risk_score,
-            0.0, // Padding
-        ]
-    }
+/// topic0 of the ERC-20 `Approval(address,address,uint256)` event
+fn approval_topic() -> H256 {
+    ethereum_crypto::keccak256(b"Approval(address,address,uint256)")
 }
 
-/// AI model for transaction simulation
-struct SimulationModel {
-    weights: Vec<Tensor>,
+fn address_topic(address: &Address) -> H256 {
+    let mut topic = [0u8; 32];
+    topic[12..].copy_from_slice(address.as_bytes());
+    H256::from(topic)
 }
 
-impl SimulationModel {
-    fn load() -> Result<Self> {
-        // Load pre-trained model
-        Ok(Self {
-            weights: Vec::new(),
-        })
-    }
-    
-    async fn predict(
-        &self,
-        features: &TransactionFeatures,
-        device: &Device,
-    ) -> Result<ModelPrediction> {
-        // Run inference
-        let input = Tensor::from_vec(
-            features.to_vec(),
-            &[1, TransactionFeatures::SIZE],
-            device,
-        ).map_err(|e| AccountAbstractionError::SimulationFailed(e.to_string()))?;
-        
-        // Mock prediction
-        Ok(ModelPrediction {
-            success_probability: 0.95,
-            gas_estimate: 100000.0,
-            revert_reason: None,
-            warnings: Vec::new(),
-            mev_risk: features.mev_risk_score,
-            urgency_score: 0.5,
-            execution_path: Vec::new(),
-            state_changes: Vec::new(),
+/// Balances the overlay changed, against the ones in `base`
+fn balance_changes<S: StateDB>(base: &S, overlay: &CallState<'_>) -> Vec<BalanceChange> {
+    let mut changes: Vec<_> = overlay.changed_accounts()
+        .filter_map(|(address, account)| {
+            let before = base.get_account(address).map(|acc| acc.balance).unwrap_or_default();
+            let after = account.map(|acc| acc.balance).unwrap_or_default();
+            (before != after).then_some(BalanceChange { address: *address, before, after })
         })
-    }
-    
-    async fn train(
-        &mut self,
-        inputs: &Tensor,
-        targets: &Tensor,
-        device: &Device,
-    ) -> Result<()> {
-        // Training logic
-        Ok(())
-    }
+        .collect();
+    changes.sort_by_key(|change| change.address);
+    changes
 }
 
-/// Model prediction result
-struct ModelPrediction {
-    success_probability: f64,
-    gas_estimate: f32,
-    revert_reason: Option<String>,
-    warnings: Vec<String>,
-    mev_risk: f32,
-    urgency_score: f32,
-    execution_path: Vec<ExecutionStep>,
-    state_changes: Vec<StateChange>,
+/// Storage slots the overlay changed, against the ones in `base`
+fn storage_changes<S: StateDB>(base: &S, overlay: &CallState<'_>) -> Vec<StateChange> {
+    let mut changes: Vec<_> = overlay.changed_storage()
+        .filter_map(|(address, slot, value)| {
+            let old_value = base.get_storage(address, slot);
+            (old_value != *value).then_some(StateChange {
+                address: *address,
+                slot: *slot,
+                old_value,
+                new_value: *value,
+            })
+        })
+        .collect();
+    changes.sort_by_key(|change| (change.address, change.slot));
+    changes
 }
 
-/// Simulation result
+/// Outcome of a simulated call
 #[derive(Debug, Clone)]
 pub struct SimulationResult {
-    pub success_probability: f64,
-    pub estimated_gas_used: U256,
+    pub success: bool,
+    pub gas_used: u64,
+    pub return_data: Vec<u8>,
     pub revert_reason: Option<String>,
-    pub warnings: Vec<String>,
-    pub optimization_suggestions: Vec<OptimizationSuggestion>,
-    pub mev_protection_needed: bool,
-    pub recommended_gas_price: U256,
-    pub execution_path: Vec<ExecutionStep>,
+    pub balance_changes: Vec<BalanceChange>,
     pub state_changes: Vec<StateChange>,
+    pub events: Vec<Log>,
+    pub risk_flags: Vec<RiskFlag>,
 }
 
-#[derive(Debug, Clone)]
-pub struct OptimizationSuggestion {
-    pub type_: OptimizationType,
-    pub description: String,
-    pub impact: ImpactLevel,
-}
-
-#[derive(Debug, Clone)]
-pub enum OptimizationType {
-    GasLimit,
-    GasPrice,
-    MEVProtection,
-    Batching,
-    Timing,
-}
-
-#[derive(Debug, Clone)]
-pub enum ImpactLevel {
-    Low,
-    Medium,
-    High,
-    Critical,
-}
-
-#[derive(Debug, Clone)]
-pub struct ExecutionStep {
-    pub opcode: String,
-    pub gas_cost: u64,
-    pub stack_depth: usize,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub address: Address,
+    pub before: U256,
+    pub after: U256,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StateChange {
     pub address: Address,
     pub slot: H256,
@@ -340,38 +244,115 @@ pub struct StateChange {
     pub new_value: H256,
 }
 
-/// Training example for model
-pub struct TrainingExample {
-    pub features: TransactionFeatures,
-    pub successful: bool,
-    pub gas_used: u64,
+/// Heuristic warning about a simulated call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RiskFlag {
+    /// The sender grants `spender` an effectively unlimited allowance
+    UnlimitedApproval { token: Address, spender: Address },
+    /// The call targets a contract without verified source
+    UnverifiedContract { address: Address },
+    /// The sender's balance drops by at least the configured threshold
+    LargeTransfer { amount: U256 },
 }
 
-/// Simulation cache
-struct SimulationCache {
-    cache: std::collections::HashMap<H256, SimulationResult>,
-    max_size: usize,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_evm::Account;
+    use std::collections::HashMap;
 
-impl SimulationCache {
-    fn new(max_size: usize) -> Self {
-        Self {
-            cache: std::collections::HashMap::new(),
-            max_size,
+    fn block() -> BlockContext {
+        BlockContext {
+            coinbase: Address::zero(),
+            number: U256::from(1),
+            timestamp: U256::from(1_000),
+            difficulty: U256::zero(),
+            prev_randao: H256::zero(),
+            gas_limit: U256::from(30_000_000),
+            base_fee: Some(U256::from(7)),
+            blob_base_fee: None,
+            chain_id: U256::from(1),
+            block_hashes: Vec::new(),
         }
     }
-    
-    fn get(&self, hash: &H256) -> Option<SimulationResult> {
-        self.cache.get(hash).cloned()
+
+    fn eth(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(18)
     }
-    
-    fn insert(&mut self, hash: H256, result: SimulationResult) {
-        if self.cache.len() >= self.max_size {
-            // Remove oldest entry (simplified)
-            if let Some(key) = self.cache.keys().next().cloned() {
-                self.cache.remove(&key);
-            }
-        }
-        self.cache.insert(hash, result);
+
+    /// `approve(spender, amount)` that only emits `Approval(caller, spender, amount)`
+    fn approve_only_token() -> Vec<u8> {
+        let mut code = vec![
+            0x60, 0x24, 0x35, 0x60, 0x00, 0x52, // mstore(0, calldataload(36))
+            0x60, 0x04, 0x35,                   // calldataload(4)
+            0x33,                               // caller
+            0x7f,                               // push32 topic0
+        ];
+        code.extend_from_slice(approval_topic().as_bytes());
+        code.extend_from_slice(&[
+            0x60, 0x20, 0x60, 0x00, 0xa3,       // log3(0, 32, topic0, caller, spender)
+            0x60, 0x01, 0x60, 0x00, 0x52,       // mstore(0, 1)
+            0x60, 0x20, 0x60, 0x00, 0xf3,       // return(0, 32)
+        ]);
+        code
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_simulate_transfer() {
+        let (alice, bob) = (Address::from_bytes([0xaa; 20]), Address::from_bytes([0xbb; 20]));
+        let mut state: HashMap<Address, Account> = HashMap::new();
+        state.insert(alice, Account { balance: eth(100), ..Default::default() });
+
+        let simulator = AITransactionSimulator::new(block());
+        let request = SimulationRequest { from: alice, to: bob, value: eth(50), data: Vec::new(), gas_limit: 21_000 };
+        let result = simulator.simulate(&state, &request).unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.gas_used, 21_000);
+        assert_eq!(result.balance_changes, vec![
+            BalanceChange { address: alice, before: eth(100), after: eth(50) },
+            BalanceChange { address: bob, before: U256::zero(), after: eth(50) },
+        ]);
+        assert_eq!(result.risk_flags, vec![RiskFlag::LargeTransfer { amount: eth(50) }]);
+
+        // Nothing was committed
+        assert_eq!(state[&alice].balance, eth(100));
+        assert!(!state.contains_key(&bob));
+
+        // Small transfers aren't flagged
+        let request = SimulationRequest { value: eth(1), ..request };
+        assert!(simulator.simulate(&state, &request).unwrap().risk_flags.is_empty());
+    }
+
+    #[test]
+    fn test_simulate_unlimited_approval() {
+        let owner = Address::from_bytes([0xaa; 20]);
+        let token = Address::from_bytes([0x70; 20]);
+        let spender = Address::from_bytes([0x5e; 20]);
+        let mut state: HashMap<Address, Account> = HashMap::new();
+        state.insert(owner, Account { balance: eth(1), ..Default::default() });
+        state.insert(token, Account { code: approve_only_token(), ..Default::default() });
+
+        let approve = |amount: U256| {
+            let mut data = vec![0x09, 0x5e, 0xa7, 0xb3];
+            data.extend_from_slice(address_topic(&spender).as_bytes());
+            let mut word = [0u8; 32];
+            amount.to_big_endian(&mut word);
+            data.extend_from_slice(&word);
+            SimulationRequest { from: owner, to: token, value: U256::zero(), data, gas_limit: 100_000 }
+        };
+
+        let simulator = AITransactionSimulator::new(block()).with_verified_contracts([token]);
+        let result = simulator.simulate(&state, &approve(U256::MAX)).unwrap();
+        assert!(result.success);
+        assert_eq!(result.events.len(), 1);
+        assert_eq!(result.risk_flags, vec![RiskFlag::UnlimitedApproval { token, spender }]);
+
+        let result = simulator.simulate(&state, &approve(U256::from(1_000))).unwrap();
+        assert!(result.risk_flags.is_empty());
+
+        // The same call to a contract nobody verified
+        let result = AITransactionSimulator::new(block()).simulate(&state, &approve(U256::from(1_000))).unwrap();
+        assert_eq!(result.risk_flags, vec![RiskFlag::UnverifiedContract { address: token }]);
+    }
+}
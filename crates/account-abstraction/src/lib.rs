@@ -8,7 +8,9 @@ pub mod bundler;
 pub mod paymaster;
 
 pub use smart_account::{SmartAccount, AccountFactory, AccountConfig};
-pub use ai_simulator::{AITransactionSimulator, SimulationResult};
+pub use ai_simulator::{
+    AITransactionSimulator, BalanceChange, RiskFlag, SimulationRequest, SimulationResult, StateChange,
+};
 pub use quantum_signatures::{QuantumSigner, PostQuantumAlgorithm};
 pub use session_keys::{SessionKey, SessionKeyManager};
 pub use biometric_auth::{BiometricAuthenticator, BiometricType};
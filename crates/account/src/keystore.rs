@@ -5,13 +5,16 @@ use ethereum_types::Address;
 use secp256k1::SecretKey;
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
-use aes::cipher::{KeyIvInit, StreamCipher};
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit, StreamCipher};
 use scrypt::{scrypt, Params as ScryptParams};
 use pbkdf2::pbkdf2_hmac;
 use sha2::Sha256;
 use rand::Rng;
 
-use crate::{Account, AccountError, Result};
+use crate::{Account, AccountError, HDWallet, Result};
+
+/// Scrypt cost of new keyfiles, N = 2^14
+const SCRYPT_LOG_N: u8 = 14;
 
 /// Keystore for managing encrypted keys
 pub struct KeyStore {
@@ -103,6 +106,22 @@ impl KeyStore {
         Ok(keyfile)
     }
     
+    /// Import a presale wallet, storing it under the same password
+    pub async fn import_presale(&mut self, path: &Path, password: &str) -> Result<Account> {
+        let wallet: PresaleWallet = read_json(path)?;
+        let account = wallet.decrypt(password)?;
+        self.store_account(&account, password).await?;
+        Ok(account)
+    }
+    
+    /// Import the account of an encrypted mnemonic, storing it under the same password
+    pub async fn import_encrypted_mnemonic(&mut self, path: &Path, password: &str) -> Result<Account> {
+        let mnemonic: EncryptedMnemonic = read_json(path)?;
+        let account = mnemonic.decrypt(password)?;
+        self.store_account(&account, password).await?;
+        Ok(account)
+    }
+    
     /// Check if account exists
     pub fn has_account(&self, address: Address) -> bool {
         self.accounts.contains_key(&address)
@@ -147,6 +166,12 @@ impl KeyStore {
     }
 }
 
+/// Parse a wallet file, any malformed content is `InvalidKeyFile`
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|_| AccountError::InvalidKeyFile)
+}
+
 /// Keyfile format (Web3 Secret Storage Definition)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyFile {
@@ -192,6 +217,33 @@ pub enum KdfParams {
 impl KeyFile {
     /// Encrypt account to keyfile
     pub fn encrypt(account: &Account, password: &str) -> Result<Self> {
+        let private_key = account.private_key().secret_bytes();
+        
+        Ok(KeyFile {
+            id: Uuid::new_v4().to_string(),
+            version: 3,
+            address: hex::encode(account.address().as_bytes()),
+            crypto: CryptoParams::encrypt(&private_key, password)?,
+        })
+    }
+    
+    /// Decrypt keyfile to account
+    pub fn decrypt(&self, password: &str) -> Result<Account> {
+        if self.version != 3 {
+            return Err(AccountError::InvalidKeyFile);
+        }
+        
+        let private_key = self.crypto.decrypt(password)?;
+        
+        // Create account from private key
+        let secret_key = SecretKey::from_slice(&private_key)?;
+        Account::from_private_key(secret_key)
+    }
+}
+
+impl CryptoParams {
+    /// Encrypt `plaintext` with aes-128-ctr under a scrypt derived key
+    pub fn encrypt(plaintext: &[u8], password: &str) -> Result<Self> {
         let mut rng = rand::thread_rng();
         
         // Generate random salt and IV
@@ -202,7 +254,7 @@ impl KeyFile {
         
         // Derive key using scrypt
         let mut derived_key = [0u8; 32];
-        let params = ScryptParams::new(14, 8, 1, 32)
+        let params = ScryptParams::new(SCRYPT_LOG_N, 8, 1, 32)
             .map_err(|e| AccountError::KeystoreError(e.to_string()))?;
         
         scrypt(
@@ -212,9 +264,7 @@ impl KeyFile {
             &mut derived_key,
         ).map_err(|e| AccountError::KeystoreError(e.to_string()))?;
         
-        // Encrypt private key
-        let private_key = account.private_key().secret_bytes();
-        let mut ciphertext = private_key.to_vec();
+        let mut ciphertext = plaintext.to_vec();
         
         type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
         let mut cipher = Aes128Ctr::new((&derived_key[..16]).into(), (&iv[..]).into());
@@ -226,37 +276,28 @@ impl KeyFile {
         mac_data.extend_from_slice(&ciphertext);
         let mac = ethereum_crypto::keccak256(&mac_data);
         
-        Ok(KeyFile {
-            id: Uuid::new_v4().to_string(),
-            version: 3,
-            address: hex::encode(account.address().as_bytes()),
-            crypto: CryptoParams {
-                cipher: "aes-128-ctr".to_string(),
-                cipherparams: CipherParams {
-                    iv: hex::encode(iv),
-                },
-                ciphertext: hex::encode(ciphertext),
-                kdf: "scrypt".to_string(),
-                kdfparams: KdfParams::Scrypt {
-                    dklen: 32,
-                    n: 8192,
-                    p: 1,
-                    r: 8,
-                    salt: hex::encode(salt),
-                },
-                mac: hex::encode(mac),
+        Ok(CryptoParams {
+            cipher: "aes-128-ctr".to_string(),
+            cipherparams: CipherParams {
+                iv: hex::encode(iv),
+            },
+            ciphertext: hex::encode(ciphertext),
+            kdf: "scrypt".to_string(),
+            kdfparams: KdfParams::Scrypt {
+                dklen: 32,
+                n: 1 << SCRYPT_LOG_N,
+                p: 1,
+                r: 8,
+                salt: hex::encode(salt),
             },
+            mac: hex::encode(mac),
         })
     }
     
-    /// Decrypt keyfile to account
-    pub fn decrypt(&self, password: &str) -> Result<Account> {
-        if self.version != 3 {
-            return Err(AccountError::InvalidKeyFile);
-        }
-        
+    /// Decrypt the ciphertext, `InvalidPassword` if the MAC doesn't match
+    pub fn decrypt(&self, password: &str) -> Result<Vec<u8>> {
         // Derive key
-        let derived_key = match &self.crypto.kdfparams {
+        let derived_key = match &self.kdfparams {
             KdfParams::Scrypt { dklen, n, p, r, salt } => {
                 let salt = hex::decode(salt)
                     .map_err(|_| AccountError::InvalidKeyFile)?;
@@ -289,9 +330,12 @@ impl KeyFile {
                 derived_key
             }
         };
+        if derived_key.len() < 32 {
+            return Err(AccountError::InvalidKeyFile);
+        }
         
         // Verify MAC
-        let ciphertext = hex::decode(&self.crypto.ciphertext)
+        let ciphertext = hex::decode(&self.ciphertext)
             .map_err(|_| AccountError::InvalidKeyFile)?;
         
         let mut mac_data = Vec::new();
@@ -299,18 +343,18 @@ impl KeyFile {
         mac_data.extend_from_slice(&ciphertext);
         let mac = ethereum_crypto::keccak256(&mac_data);
         
-        let expected_mac = hex::decode(&self.crypto.mac)
+        let expected_mac = hex::decode(&self.mac)
             .map_err(|_| AccountError::InvalidKeyFile)?;
         
         if mac != expected_mac.as_slice() {
             return Err(AccountError::InvalidPassword);
         }
         
-        // Decrypt private key
-        let iv = hex::decode(&self.crypto.cipherparams.iv)
+        // Decrypt
+        let iv = hex::decode(&self.cipherparams.iv)
             .map_err(|_| AccountError::InvalidKeyFile)?;
         
-        let mut private_key = ciphertext;
+        let mut plaintext = ciphertext;
         
         type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
         let mut cipher = Aes128Ctr::new(
@@ -319,13 +363,154 @@ impl KeyFile {
             (&iv[..]).try_into()
                 .map_err(|_| AccountError::InvalidKeyFile)?,
         );
-        cipher.apply_keystream(&mut private_key);
+        cipher.apply_keystream(&mut plaintext);
         
-        // Create account from private key
-        let secret_key = SecretKey::from_slice(&private_key)?;
-        Account::from_private_key(secret_key)
+        Ok(plaintext)
+    }
+}
+
+/// Wallet from the 2014 ether presale
+///
+/// `encseed` is a 16 byte IV followed by the AES-128-CBC encrypted seed, the
+/// key being 2000 rounds of PBKDF2-HMAC-SHA256 with the password as both
+/// password and salt. The private key is the keccak256 of the seed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresaleWallet {
+    pub encseed: String,
+    pub ethaddr: String,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub btcaddr: String,
+}
+
+impl PresaleWallet {
+    /// Decrypt the seed, `InvalidPassword` unless it yields `ethaddr`
+    pub fn decrypt(&self, password: &str) -> Result<Account> {
+        let encseed = hex::decode(&self.encseed)
+            .map_err(|_| AccountError::InvalidKeyFile)?;
+        if encseed.len() < 32 || encseed.len() % 16 != 0 {
+            return Err(AccountError::InvalidKeyFile);
+        }
+        let address = hex::decode(self.ethaddr.trim_start_matches("0x"))
+            .map_err(|_| AccountError::InvalidKeyFile)
+            .and_then(|bytes| Address::from_slice(&bytes).map_err(|_| AccountError::InvalidKeyFile))?;
+        
+        let mut key = [0u8; 16];
+        pbkdf2_hmac::<Sha256>(password.as_bytes(), password.as_bytes(), 2000, &mut key);
+        
+        type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+        let (iv, ciphertext) = encseed.split_at(16);
+        let seed = Aes128CbcDec::new((&key).into(), iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+            .map_err(|_| AccountError::InvalidPassword)?;
+        
+        let private_key = ethereum_crypto::keccak256(&seed);
+        let secret_key = SecretKey::from_slice(private_key.as_bytes())
+            .map_err(|_| AccountError::InvalidPassword)?;
+        let account = Account::from_private_key(secret_key)?;
+        if account.address() != address {
+            return Err(AccountError::InvalidPassword);
+        }
+        
+        Ok(account)
+    }
+}
+
+/// BIP-39 mnemonic encrypted like a V3 keyfile
+///
+/// The account is derived from the phrase with an empty BIP-39 passphrase,
+/// at `derivation_path` or the first account of `m/44'/60'/0'/0`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedMnemonic {
+    pub id: String,
+    pub crypto: CryptoParams,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation_path: Option<String>,
+}
+
+impl EncryptedMnemonic {
+    /// Encrypt `phrase` with `password`
+    pub fn encrypt(phrase: &str, password: &str, derivation_path: Option<String>) -> Result<Self> {
+        Ok(Self {
+            id: Uuid::new_v4().to_string(),
+            crypto: CryptoParams::encrypt(phrase.as_bytes(), password)?,
+            derivation_path,
+        })
+    }
+    
+    /// Decrypt the phrase and derive the account from it
+    pub fn decrypt(&self, password: &str) -> Result<Account> {
+        let phrase = self.crypto.decrypt(password)?;
+        let phrase = String::from_utf8(phrase)
+            .map_err(|_| AccountError::InvalidKeyFile)?;
+        
+        let mut wallet = HDWallet::from_mnemonic_str(phrase.trim(), "")
+            .map_err(|_| AccountError::InvalidKeyFile)?;
+        let address = match &self.derivation_path {
+            Some(path) => wallet.derive_account_from_path(path, 0)
+                .map_err(|_| AccountError::InvalidKeyFile)?,
+            None => wallet.derive_account(0)?,
+        };
+        
+        wallet.get_account(address)
+            .ok_or(AccountError::AccountNotFound)?
+            .to_account()
     }
 }
 
 // Add chrono dependency for timestamp
-use chrono;
\ No newline at end of file
+use chrono;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// go-ethereum's presale test wallet, password "foo"
+    const PRESALE_WALLET: &str = r#"{
+        "encseed": "26d87f5f2bf9835f9a47eefae571bc09f9107bb13d54ff12a4ec095d01f83897494cf34f7bed2ed34126ecba9db7b62de56c9d7cd136520a0427bfb11b8954ba7ac39b90d4650d3448e31185affcd74226a68f1e94b1108e6e0a4a91cdd83eba",
+        "ethaddr": "d4584b5f6229b7be90727b0fc8c6b91bb427821f",
+        "email": "gustav.simonsson@gmail.com",
+        "btcaddr": "1EVknXyFC68kKNLkh6YnKzW41svSRoaAcx"
+    }"#;
+    
+    #[tokio::test]
+    async fn test_import_presale() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presale.wallet");
+        fs::write(&path, PRESALE_WALLET).unwrap();
+        let mut keystore = KeyStore::new(dir.path().join("keystore")).unwrap();
+        
+        let err = keystore.import_presale(&path, "bar").await.unwrap_err();
+        assert!(matches!(err, AccountError::InvalidPassword));
+        
+        let account = keystore.import_presale(&path, "foo").await.unwrap();
+        assert_eq!(hex::encode(account.address().as_bytes()), "d4584b5f6229b7be90727b0fc8c6b91bb427821f");
+        assert!(keystore.has_account(account.address()));
+        
+        fs::write(&path, r#"{"encseed": "0011", "ethaddr": "d4584b5f6229b7be90727b0fc8c6b91bb427821f"}"#).unwrap();
+        let err = keystore.import_presale(&path, "foo").await.unwrap_err();
+        assert!(matches!(err, AccountError::InvalidKeyFile));
+    }
+    
+    #[tokio::test]
+    async fn test_import_encrypted_mnemonic() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mnemonic.json");
+        let encrypted = EncryptedMnemonic::encrypt(phrase, "secret", None).unwrap();
+        fs::write(&path, serde_json::to_string(&encrypted).unwrap()).unwrap();
+        let mut keystore = KeyStore::new(dir.path().join("keystore")).unwrap();
+        
+        let err = keystore.import_encrypted_mnemonic(&path, "wrong").await.unwrap_err();
+        assert!(matches!(err, AccountError::InvalidPassword));
+        
+        // First account at m/44'/60'/0'/0/0
+        let account = keystore.import_encrypted_mnemonic(&path, "secret").await.unwrap();
+        assert_eq!(hex::encode(account.address().as_bytes()), "9858effd232b4033e47d90003d41ec34ecaeda94");
+        assert!(keystore.has_account(account.address()));
+        
+        let unlocked = keystore.unlock_account(account.address(), "secret").await.unwrap();
+        assert_eq!(unlocked.address(), account.address());
+    }
+}
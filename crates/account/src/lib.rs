@@ -9,9 +9,9 @@ pub mod keystore;
 pub mod wallet;
 pub mod signer;
 
-pub use keystore::{KeyStore, KeyFile, CryptoParams};
+pub use keystore::{KeyStore, KeyFile, CryptoParams, PresaleWallet, EncryptedMnemonic};
 pub use wallet::{Wallet, HDWallet};
-pub use signer::{Signer, TransactionSigner};
+pub use signer::{LocalSigner, Signer, TransactionSigner, TxBuildRequest};
 
 #[derive(Debug, Error)]
 pub enum AccountError {
@@ -30,6 +30,9 @@ pub enum AccountError {
     #[error("Signing error: {0}")]
     SigningError(String),
     
+    #[error("Invalid transaction request: {0}")]
+    InvalidTransactionRequest(String),
+    
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     
@@ -255,6 +258,17 @@ impl AccountManager {
         Ok(())
     }
     
+    /// Decrypt an account from the keystore without unlocking it
+    ///
+    /// Unlike `unlock_account` this always checks the password.
+    pub async fn account_with_password(
+        &self,
+        address: Address,
+        password: &str,
+    ) -> Result<Account> {
+        self.keystore.unlock_account(address, password).await
+    }
+    
     /// Lock account
     pub fn lock_account(&mut self, address: Address) {
         self.accounts.remove(&address);
@@ -1,5 +1,8 @@
-use ethereum_types::{H256, Address};
-use ethereum_core::Transaction;
+use ethereum_types::{H256, Address, Bytes, U256};
+use ethereum_core::{
+    AccessListItem, Authorization, Eip1559Transaction, Eip2930Transaction, Eip4844Transaction,
+    Eip7702Transaction, LegacyTransaction, Transaction,
+};
 use ethereum_crypto::Signature;
 use secp256k1::SecretKey;
 
@@ -18,6 +21,189 @@ pub trait Signer: Send + Sync {
     
     /// Get chain ID
     fn chain_id(&self) -> Option<u64>;
+    
+    /// Build a transaction from `request`, sign it and return it together
+    /// with its EIP-2718 envelope ready for `eth_sendRawTransaction`
+    fn build_and_sign(&self, request: TxBuildRequest) -> Result<(Transaction, Bytes)> {
+        // A legacy transaction only carries the chain id the signer signs
+        // it with (EIP-155), so a requested chain must be the signer's
+        if request.tx_type == 0 {
+            if let Some(requested) = request.chain_id {
+                match self.chain_id() {
+                    Some(own) if own == requested => {}
+                    Some(own) => {
+                        return Err(AccountError::InvalidTransactionRequest(format!(
+                            "legacy transaction for chain {} cannot be signed by a signer for chain {}",
+                            requested, own
+                        )));
+                    }
+                    None => {
+                        return Err(AccountError::InvalidTransactionRequest(format!(
+                            "legacy transaction for chain {} cannot be signed by a signer without a chain",
+                            requested
+                        )));
+                    }
+                }
+            }
+        }
+        
+        let tx = request.build(self.chain_id())?;
+        let signed = self.sign_transaction(&tx)?;
+        let raw = Bytes::from_vec(signed.encoded_2718());
+        
+        Ok((signed, raw))
+    }
+}
+
+/// High-level description of a transaction to be built and signed
+///
+/// `tx_type` follows EIP-2718 (0 = legacy, 1 = access list, 2 = dynamic fee,
+/// 3 = blob, 4 = set code). A missing `chain_id` falls back to the signer's.
+#[derive(Debug, Clone, Default)]
+pub struct TxBuildRequest {
+    pub tx_type: u8,
+    pub chain_id: Option<u64>,
+    pub nonce: U256,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Bytes,
+    pub gas_limit: U256,
+    pub gas_price: Option<U256>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub max_fee_per_blob_gas: Option<U256>,
+    pub access_list: Vec<AccessListItem>,
+    pub blob_versioned_hashes: Vec<H256>,
+    pub authorization_list: Vec<Authorization>,
+}
+
+impl TxBuildRequest {
+    /// Build the unsigned transaction, checking the fields required by its type
+    pub fn build(self, default_chain_id: Option<u64>) -> Result<Transaction> {
+        if self.gas_limit.is_zero() {
+            return Err(invalid_request("gas limit is required"));
+        }
+        if self.tx_type != 3 && !self.blob_versioned_hashes.is_empty() {
+            return Err(invalid_request("blob hashes are only allowed in blob transactions"));
+        }
+        if self.tx_type != 4 && !self.authorization_list.is_empty() {
+            return Err(invalid_request("authorizations are only allowed in set code transactions"));
+        }
+        
+        let chain_id = self.chain_id.or(default_chain_id);
+        
+        match self.tx_type {
+            0 => {
+                if !self.access_list.is_empty() {
+                    return Err(invalid_request("legacy transactions cannot carry an access list"));
+                }
+                Ok(Transaction::Legacy(LegacyTransaction {
+                    nonce: self.nonce,
+                    gas_price: required(self.gas_price, "gas_price")?,
+                    gas_limit: self.gas_limit,
+                    to: self.to,
+                    value: self.value,
+                    data: self.data,
+                    v: 0,
+                    r: U256::zero(),
+                    s: U256::zero(),
+                }))
+            }
+            1 => Ok(Transaction::Eip2930(Eip2930Transaction {
+                chain_id: required(chain_id, "chain_id")?,
+                nonce: self.nonce,
+                gas_price: required(self.gas_price, "gas_price")?,
+                gas_limit: self.gas_limit,
+                to: self.to,
+                value: self.value,
+                data: self.data,
+                access_list: self.access_list,
+                y_parity: false,
+                r: U256::zero(),
+                s: U256::zero(),
+            })),
+            2 => {
+                let (max_fee_per_gas, max_priority_fee_per_gas) = self.dynamic_fees()?;
+                Ok(Transaction::Eip1559(Eip1559Transaction {
+                    chain_id: required(chain_id, "chain_id")?,
+                    nonce: self.nonce,
+                    max_priority_fee_per_gas,
+                    max_fee_per_gas,
+                    gas_limit: self.gas_limit,
+                    to: self.to,
+                    value: self.value,
+                    data: self.data,
+                    access_list: self.access_list,
+                    y_parity: false,
+                    r: U256::zero(),
+                    s: U256::zero(),
+                }))
+            }
+            3 => {
+                let (max_fee_per_gas, max_priority_fee_per_gas) = self.dynamic_fees()?;
+                if self.blob_versioned_hashes.is_empty() {
+                    return Err(invalid_request("blob transactions require blob versioned hashes"));
+                }
+                Ok(Transaction::Eip4844(Eip4844Transaction {
+                    chain_id: required(chain_id, "chain_id")?,
+                    nonce: self.nonce,
+                    max_priority_fee_per_gas,
+                    max_fee_per_gas,
+                    gas_limit: self.gas_limit,
+                    to: required(self.to, "to")?,
+                    value: self.value,
+                    data: self.data,
+                    access_list: self.access_list,
+                    max_fee_per_blob_gas: required(self.max_fee_per_blob_gas, "max_fee_per_blob_gas")?,
+                    blob_versioned_hashes: self.blob_versioned_hashes,
+                    y_parity: false,
+                    r: U256::zero(),
+                    s: U256::zero(),
+                }))
+            }
+            4 => {
+                let (max_fee_per_gas, max_priority_fee_per_gas) = self.dynamic_fees()?;
+                if self.authorization_list.is_empty() {
+                    return Err(invalid_request("set code transactions require an authorization list"));
+                }
+                Ok(Transaction::Eip7702(Eip7702Transaction {
+                    chain_id: required(chain_id, "chain_id")?,
+                    nonce: self.nonce,
+                    max_priority_fee_per_gas,
+                    max_fee_per_gas,
+                    gas_limit: self.gas_limit,
+                    to: required(self.to, "to")?,
+                    value: self.value,
+                    data: self.data,
+                    access_list: self.access_list,
+                    authorization_list: self.authorization_list,
+                    y_parity: false,
+                    r: U256::zero(),
+                    s: U256::zero(),
+                }))
+            }
+            other => Err(invalid_request(&format!("unsupported transaction type {}", other))),
+        }
+    }
+    
+    fn dynamic_fees(&self) -> Result<(U256, U256)> {
+        let max_fee = required(self.max_fee_per_gas, "max_fee_per_gas")?;
+        let max_priority_fee = required(self.max_priority_fee_per_gas, "max_priority_fee_per_gas")?;
+        
+        if max_priority_fee > max_fee {
+            return Err(invalid_request("max_priority_fee_per_gas exceeds max_fee_per_gas"));
+        }
+        
+        Ok((max_fee, max_priority_fee))
+    }
+}
+
+fn required<T>(value: Option<T>, field: &str) -> Result<T> {
+    value.ok_or_else(|| invalid_request(&format!("missing {}", field)))
+}
+
+fn invalid_request(message: &str) -> AccountError {
+    AccountError::InvalidTransactionRequest(message.to_string())
 }
 
 /// Local signer using private key
@@ -45,6 +231,16 @@ impl LocalSigner {
         let account = Account::from_private_key_bytes(&key_bytes)?;
         Ok(Self { account, chain_id })
     }
+    
+    /// Sign a transaction signing hash, returning (y_parity, r, s)
+    fn sign_hash(&self, hash: &H256) -> Result<(bool, U256, U256)> {
+        let signature = self.account.sign_transaction_hash(hash)?;
+        Ok((
+            signature.v == 1,
+            U256::from_big_endian(signature.r.as_bytes()),
+            U256::from_big_endian(signature.s.as_bytes()),
+        ))
+    }
 }
 
 impl Signer for LocalSigner {
@@ -53,17 +249,34 @@ impl Signer for LocalSigner {
     }
     
     fn sign_transaction(&self, tx: &Transaction) -> Result<Transaction> {
-        // Calculate transaction hash for signing
-        let tx_hash = calculate_signing_hash(tx);
-        
-        // Sign the hash
-        let signature = self.account.sign_transaction_hash(&tx_hash)?;
+        let mut signed = tx.clone();
         
-        // Create signed transaction by applying signature
-        // This is simplified - in reality would need to properly encode based on transaction type
-        let signed_tx = tx.clone();
+        match &mut signed {
+            Transaction::Legacy(inner) => {
+                // EIP-155 replay protection whenever the signer knows its chain
+                let (y_parity, r, s) = self.sign_hash(&inner.signing_hash(self.chain_id))?;
+                inner.v = match self.chain_id {
+                    Some(chain_id) => chain_id * 2 + 35 + y_parity as u64,
+                    None => 27 + y_parity as u64,
+                };
+                inner.r = r;
+                inner.s = s;
+            }
+            Transaction::Eip2930(inner) => {
+                (inner.y_parity, inner.r, inner.s) = self.sign_hash(&inner.signing_hash())?;
+            }
+            Transaction::Eip1559(inner) => {
+                (inner.y_parity, inner.r, inner.s) = self.sign_hash(&inner.signing_hash())?;
+            }
+            Transaction::Eip4844(inner) => {
+                (inner.y_parity, inner.r, inner.s) = self.sign_hash(&inner.signing_hash())?;
+            }
+            Transaction::Eip7702(inner) => {
+                (inner.y_parity, inner.r, inner.s) = self.sign_hash(&inner.signing_hash())?;
+            }
+        }
         
-        Ok(signed_tx)
+        Ok(signed)
     }
     
     fn address(&self) -> Address {
@@ -160,38 +373,6 @@ impl TransactionSigner {
     }
 }
 
-/// Calculate signing hash for transaction (EIP-155)
-fn calculate_signing_hash(tx: &Transaction) -> H256 {
-    // This is a simplified version
-    // Real implementation would need to properly encode transaction based on type
-    let mut data = Vec::new();
-    
-    // Add transaction fields
-    data.extend_from_slice(&tx.nonce().to_le_bytes());
-    
-    if let Some(gas_price) = tx.gas_price() {
-        data.extend_from_slice(&gas_price.to_le_bytes());
-    }
-    
-    data.extend_from_slice(&tx.gas_limit().to_le_bytes());
-    
-    if let Some(to) = tx.to() {
-        data.extend_from_slice(to.as_bytes());
-    }
-    
-    data.extend_from_slice(&tx.value().to_le_bytes());
-    data.extend_from_slice(tx.data());
-    
-    // Add chain ID for EIP-155
-    if let Some(chain_id) = tx.chain_id() {
-        data.extend_from_slice(&chain_id.to_le_bytes());
-        data.extend_from_slice(&[0u8; 8]); // r = 0
-        data.extend_from_slice(&[0u8; 8]); // s = 0
-    }
-    
-    ethereum_crypto::keccak256(&data)
-}
-
 /// Hardware wallet signer (stub for future implementation)
 pub struct HardwareWalletSigner {
     address: Address,
@@ -258,4 +439,95 @@ mod tests {
         assert_eq!(signers[0], account1.address());
         assert_eq!(signers[1], account2.address());
     }
-}
\ No newline at end of file
+    
+    fn request(tx_type: u8) -> TxBuildRequest {
+        TxBuildRequest {
+            tx_type,
+            nonce: U256::from(7),
+            to: Some(Address::from_bytes([0x35; 20])),
+            value: U256::from(1_000_000_000u64),
+            gas_limit: U256::from(21_000),
+            gas_price: Some(U256::from(20_000_000_000u64)),
+            max_fee_per_gas: Some(U256::from(30_000_000_000u64)),
+            max_priority_fee_per_gas: Some(U256::from(1_000_000_000u64)),
+            ..Default::default()
+        }
+    }
+    
+    #[test]
+    fn test_build_and_sign_each_type() {
+        let account = Account::new().unwrap();
+        let signer = LocalSigner::new(account.clone(), Some(1));
+        
+        let mut blob = request(3);
+        blob.max_fee_per_blob_gas = Some(U256::from(1));
+        blob.blob_versioned_hashes = vec![H256::from_low_u64_be(1)];
+        
+        let mut set_code = request(4);
+        set_code.authorization_list = vec![Authorization::new(1, Address::from_bytes([0x42; 20]), U256::zero())];
+        
+        for req in vec![request(0), request(1), request(2), blob, set_code] {
+            let tx_type = req.tx_type;
+            let (tx, raw) = signer.build_and_sign(req).unwrap();
+            
+            assert_eq!(tx.tx_type(), tx_type);
+            assert_eq!(tx.sender().unwrap(), account.address());
+            assert_eq!(raw.as_slice(), &tx.encoded_2718()[..]);
+            if tx_type == 0 {
+                assert!(raw[0] >= 0xc0);
+            } else {
+                assert_eq!(raw[0], tx_type);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_build_and_sign_legacy_uses_eip155() {
+        let signer = LocalSigner::new(Account::new().unwrap(), Some(5));
+        
+        let (tx, _) = signer.build_and_sign(request(0)).unwrap();
+        match tx {
+            Transaction::Legacy(inner) => assert!(inner.v == 45 || inner.v == 46),
+            _ => panic!("expected legacy transaction"),
+        }
+        
+        let mut other_chain = request(0);
+        other_chain.chain_id = Some(1);
+        assert!(signer.build_and_sign(other_chain).is_err());
+        
+        // Without a chain of its own the signer can't protect it either
+        let unprotected = LocalSigner::new(Account::new().unwrap(), None);
+        let mut for_chain = request(0);
+        for_chain.chain_id = Some(5);
+        assert!(matches!(
+            unprotected.build_and_sign(for_chain),
+            Err(AccountError::InvalidTransactionRequest(_))
+        ));
+    }
+    
+    #[test]
+    fn test_build_request_validation() {
+        let signer = LocalSigner::new(Account::new().unwrap(), None);
+        
+        // Typed transactions need a chain id from the request or the signer
+        assert!(signer.build_and_sign(request(2)).is_err());
+        
+        let mut no_blobs = request(3);
+        no_blobs.chain_id = Some(1);
+        no_blobs.max_fee_per_blob_gas = Some(U256::from(1));
+        assert!(matches!(
+            signer.build_and_sign(no_blobs),
+            Err(AccountError::InvalidTransactionRequest(_))
+        ));
+        
+        let mut no_fee = request(2);
+        no_fee.chain_id = Some(1);
+        no_fee.max_fee_per_gas = None;
+        assert!(signer.build_and_sign(no_fee).is_err());
+        
+        let mut stray_blobs = request(2);
+        stray_blobs.chain_id = Some(1);
+        stray_blobs.blob_versioned_hashes = vec![H256::zero()];
+        assert!(signer.build_and_sign(stray_blobs).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use ethereum_types::H256;
+use ethereum_storage::Database;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{ConsensusError, Result};
+
+/// A block referenced by the finality gadget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub hash: H256,
+    pub number: u64,
+}
+
+impl Checkpoint {
+    pub fn new(hash: H256, number: u64) -> Self {
+        Self { hash, number }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointKind {
+    Finalized,
+    Justified,
+    Safe,
+}
+
+impl CheckpointKind {
+    fn key(&self) -> &'static [u8] {
+        match self {
+            CheckpointKind::Finalized => b"checkpoint:finalized",
+            CheckpointKind::Justified => b"checkpoint:justified",
+            CheckpointKind::Safe => b"checkpoint:safe",
+        }
+    }
+
+    /// Finalized and justified checkpoints only ever move forward, the safe
+    /// head follows the consensus client and may be reorged
+    fn is_monotonic(&self) -> bool {
+        !matches!(self, CheckpointKind::Safe)
+    }
+}
+
+/// Persistent finalized/justified/safe checkpoints shared by the consensus
+/// engine, the engine API forkchoice handler, sync and rpc
+pub struct CheckpointStore {
+    db: Arc<dyn Database>,
+}
+
+impl CheckpointStore {
+    pub fn new(db: Arc<dyn Database>) -> Self {
+        Self { db }
+    }
+
+    pub fn get(&self, kind: CheckpointKind) -> Result<Option<Checkpoint>> {
+        match self.db.get(kind.key())? {
+            Some(data) => bincode::deserialize(&data)
+                .map(Some)
+                .map_err(|e| ConsensusError::CheckpointError(format!("corrupt {:?} checkpoint: {}", kind, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Store a checkpoint, rejecting a finalized or justified block lower
+    /// than the one already recorded
+    pub fn set(&self, kind: CheckpointKind, checkpoint: Checkpoint) -> Result<()> {
+        if kind.is_monotonic() {
+            if let Some(current) = self.get(kind)? {
+                if checkpoint.number < current.number {
+                    return Err(ConsensusError::CheckpointError(format!(
+                        "{:?} checkpoint cannot move back from block {} to {}",
+                        kind, current.number, checkpoint.number
+                    )));
+                }
+            }
+        }
+
+        let data = bincode::serialize(&checkpoint)
+            .map_err(|e| ConsensusError::CheckpointError(e.to_string()))?;
+        self.db.put(kind.key(), &data)?;
+
+        Ok(())
+    }
+
+    pub fn finalized(&self) -> Result<Option<Checkpoint>> {
+        self.get(CheckpointKind::Finalized)
+    }
+
+    pub fn justified(&self) -> Result<Option<Checkpoint>> {
+        self.get(CheckpointKind::Justified)
+    }
+
+    pub fn safe(&self) -> Result<Option<Checkpoint>> {
+        self.get(CheckpointKind::Safe)
+    }
+
+    pub fn set_finalized(&self, checkpoint: Checkpoint) -> Result<()> {
+        self.set(CheckpointKind::Finalized, checkpoint)
+    }
+
+    pub fn set_justified(&self, checkpoint: Checkpoint) -> Result<()> {
+        self.set(CheckpointKind::Justified, checkpoint)
+    }
+
+    pub fn set_safe(&self, checkpoint: Checkpoint) -> Result<()> {
+        self.set(CheckpointKind::Safe, checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_storage::MemoryDatabase;
+
+    #[test]
+    fn test_finalized_checkpoint_is_monotonic() {
+        let store = CheckpointStore::new(Arc::new(MemoryDatabase::new()));
+        assert_eq!(store.finalized().unwrap(), None);
+
+        let finalized = Checkpoint::new(H256::from([0x80; 32]), 128);
+        store.set_finalized(finalized).unwrap();
+        assert_eq!(store.finalized().unwrap(), Some(finalized));
+
+        // A lower finalized block is rejected and leaves the store untouched
+        let lower = Checkpoint::new(H256::from([0x7f; 32]), 127);
+        assert!(store.set_finalized(lower).is_err());
+        assert_eq!(store.finalized().unwrap(), Some(finalized));
+
+        let higher = Checkpoint::new(H256::from([0xa0; 32]), 160);
+        store.set_finalized(higher).unwrap();
+        assert_eq!(store.finalized().unwrap(), Some(higher));
+
+        // The safe head may move backwards on a reorg
+        store.set_safe(Checkpoint::new(H256::from([0xb0; 32]), 170)).unwrap();
+        store.set_safe(Checkpoint::new(H256::from([0xa8; 32]), 168)).unwrap();
+        assert_eq!(store.safe().unwrap().unwrap().number, 168);
+    }
+}
@@ -1,11 +1,20 @@
 use ethereum_types::{H256, U256, Address};
 use ethereum_core::{Block, Header, Transaction};
-use ethereum_crypto::{Signature, recover_address};
+use ethereum_crypto::{Signature, recover_address, sign_message};
+use secp256k1::SecretKey;
 use async_trait::async_trait;
 use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
 
 use crate::{Result, ConsensusError, ConsensusConfig};
 use crate::engine::{ConsensusEngine, EngineError};
+use crate::producer::BlockAssembler;
+
+/// Fixed number of vanity bytes at the start of extraData
+pub const EXTRA_VANITY: usize = 32;
+
+/// Length of the signer's seal at the end of extraData
+pub const EXTRA_SEAL: usize = 65;
 
 /// Clique Proof of Authority consensus implementation
 pub struct Clique {
@@ -14,6 +23,8 @@ pub struct Clique {
     recent_signers: VecDeque<(U256, Address)>,
     proposals: HashMap<Address, bool>, // true = add, false = remove
     votes: HashMap<Address, HashMap<Address, bool>>,
+    assembler: Option<Arc<BlockAssembler>>,
+    signer_key: Option<SecretKey>,
 }
 
 impl Clique {
@@ -26,9 +37,23 @@ impl Clique {
             recent_signers: VecDeque::new(),
             proposals: HashMap::new(),
             votes: HashMap::new(),
+            assembler: None,
+            signer_key: None,
         }
     }
     
+    /// Execute produced blocks with `assembler`
+    pub fn with_assembler(mut self, assembler: Arc<BlockAssembler>) -> Self {
+        self.assembler = Some(assembler);
+        self
+    }
+    
+    /// Seal blocks with `key`, which should belong to one of the signers
+    pub fn with_signer_key(mut self, key: SecretKey) -> Self {
+        self.signer_key = Some(key);
+        self
+    }
+    
     /// Check if a signer is authorized
     fn is_authorized(&self, signer: &Address) -> bool {
         self.signers.contains(signer)
@@ -73,6 +98,73 @@ impl Clique {
         ethereum_crypto::keccak256(&bincode::serialize(&signing_header).unwrap())
     }
     
+    /// Whether `number` is an epoch transition, where votes reset and the
+    /// header checkpoints the signer list
+    fn is_epoch_block(&self, number: U256) -> bool {
+        self.config.epoch_length != 0 && (number % self.config.epoch_length).is_zero()
+    }
+    
+    /// Active signers in the order a checkpoint lists them
+    fn sorted_signers(&self) -> Vec<Address> {
+        let mut signers = self.signers.clone();
+        signers.sort();
+        signers
+    }
+    
+    /// Signer list carried between the vanity and the seal
+    fn checkpoint_signers(header: &Header) -> Result<Vec<Address>> {
+        let extra = &header.extra_data;
+        if extra.len() < EXTRA_VANITY + EXTRA_SEAL {
+            return Err(ConsensusError::InvalidBlock(
+                "Extra data shorter than vanity and seal".to_string()
+            ));
+        }
+        
+        let signers = &extra[EXTRA_VANITY..extra.len() - EXTRA_SEAL];
+        if signers.len() % 20 != 0 {
+            return Err(ConsensusError::InvalidBlock(
+                "Checkpoint signer list is not a whole number of addresses".to_string()
+            ));
+        }
+        
+        signers.chunks(20)
+            .map(|chunk| Address::from_slice(chunk)
+                .map_err(|e| ConsensusError::InvalidBlock(e.to_string())))
+            .collect()
+    }
+    
+    /// Epoch blocks must list exactly the active signers, other blocks none
+    fn verify_checkpoint(&self, header: &Header) -> Result<()> {
+        let signers = Self::checkpoint_signers(header)?;
+        
+        if !self.is_epoch_block(header.number) {
+            if !signers.is_empty() {
+                return Err(ConsensusError::InvalidBlock(
+                    format!("Non-checkpoint block {} lists signers", header.number)
+                ));
+            }
+            return Ok(());
+        }
+        
+        if signers != self.sorted_signers() {
+            return Err(ConsensusError::InvalidBlock(
+                format!("Checkpoint block {} has a mismatched signer list", header.number)
+            ));
+        }
+        Ok(())
+    }
+    
+    /// Unsealed extraData for block `number`, with the signer list on epoch blocks
+    fn extra_data_for(&self, number: U256) -> Vec<u8> {
+        let mut data = self.extra_data();
+        if self.is_epoch_block(number) {
+            for signer in self.sorted_signers() {
+                data.extend_from_slice(signer.as_bytes());
+            }
+        }
+        data
+    }
+    
     /// Check if a signer has signed recently
     fn has_signed_recently(&self, signer: &Address, block_number: U256) -> bool {
         let limit = (self.signers.len() / 2) as u64;
@@ -177,6 +269,8 @@ impl ConsensusEngine for Clique {
     fn validate_block(&self, block: &Block) -> Result<()> {
         let header = &block.header;
         
+        self.verify_checkpoint(header)?;
+        
         // Get block signer
         let signer = self.get_signer(header)?;
         
@@ -194,20 +288,6 @@ impl ConsensusEngine for Clique {
             ));
         }
         
-        // Validate timestamp
-        if header.number > U256::zero() {
-            let period = self.config.block_period;
-            
-            // Check minimum time between blocks
-            if let Ok(parent_header) = self.get_parent_header(header) {
-                if header.timestamp < parent_header.timestamp + period {
-                    return Err(ConsensusError::InvalidBlock(
-                        "Block too early".to_string()
-                    ));
-                }
-            }
-        }
-        
         // Validate difficulty (should be 1 or 2 in Clique)
         if header.difficulty != U256::from(1) && header.difficulty != U256::from(2) {
             return Err(ConsensusError::InvalidBlock(
@@ -239,41 +319,26 @@ impl ConsensusEngine for Clique {
             U256::from(1) // Out-of-turn
         };
         
-        let header = Header {
-            parent_hash: parent.hash(),
-            uncles_hash: H256::from([0x1d, 0xcc, 0x4d, 0xe8, 0xde, 0xc7, 0x5d, 0x7a,
-                                     0xab, 0x85, 0xb5, 0x67, 0xb6, 0xcc, 0xd4, 0x1a,
-                                     0xd3, 0x12, 0x45, 0x1b, 0x94, 0x8a, 0x74, 0x13,
-                                     0xf0, 0xa1, 0x42, 0xfd, 0x40, 0xd4, 0x93, 0x47]),
-            author: beneficiary,
-            state_root: H256::zero(),
-            transactions_root: H256::zero(),
-            receipts_root: H256::zero(),
-            bloom: Default::default(),
-            difficulty,
-            number: block_number,
-            gas_limit: parent.gas_limit,
-            gas_used: U256::zero(),
-            timestamp: self.calculate_next_timestamp(parent, &beneficiary),
-            extra_data: self.extra_data(),
-            mix_hash: H256::zero(),
-            nonce: 0,
-        };
-        
-        let body = ethereum_core::BlockBody {
-            transactions,
-            uncles: vec![], // No uncles in Clique
-        };
+        let mut header = Header::new();
+        header.beneficiary = beneficiary;
+        header.difficulty = difficulty;
+        header.timestamp = self.calculate_next_timestamp(parent, &beneficiary);
+        header.extra_data = self.extra_data_for(block_number);
         
-        Ok(Block { header, body })
+        // Without an executor there is no post-state to put in the header
+        let assembler = self.assembler.as_ref().ok_or(EngineError::NotReady)?;
+        assembler.assemble_without_withdrawals(parent, header, transactions)
     }
     
     async fn seal_block(&self, mut block: Block) -> Result<Block> {
-        // Sign the block
         let signing_hash = self.signing_hash(&block.header);
         
-        // In real implementation, would sign with signer's private key
-        let signature = Signature::default();
+        let signature = match &self.signer_key {
+            Some(key) => sign_message(&H256::from(signing_hash), key)
+                .map_err(|e| ConsensusError::InvalidSignature(e.to_string()))?,
+            // Nothing to sign with, the seal won't verify
+            None => Signature::default(),
+        };
         
         // Append signature to extra data
         block.header.extra_data.extend_from_slice(&signature.to_bytes());
@@ -304,9 +369,8 @@ impl ConsensusEngine for Clique {
     }
     
     fn extra_data(&self) -> Vec<u8> {
-        // Clique extra data format: vanity (32 bytes) + signers (for genesis) + signature (65 bytes)
-        let mut data = vec![0u8; 32]; // Vanity
-        data
+        // Clique extra data format: vanity (32 bytes) + signers (on epoch blocks) + signature (65 bytes)
+        vec![0u8; EXTRA_VANITY] // Vanity
     }
     
     fn calculate_difficulty(&self, parent: &Header, _timestamp: u64) -> U256 {
@@ -317,23 +381,48 @@ impl ConsensusEngine for Clique {
         // Without knowing the actual signer, default to out-of-turn
         U256::from(1)
     }
-}
-
-impl Clique {
-    /// Helper to get parent header
-    fn get_parent_header(&self, header: &Header) -> Result<Header> {
-        // In real implementation, would fetch from database
-        // For now, return a mock header
-        Ok(Header {
-            timestamp: header.timestamp.saturating_sub(self.config.block_period),
-            ..Default::default()
-        })
+    
+    fn min_block_period(&self) -> u64 {
+        self.config.block_period
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ethereum_crypto::{generate_private_key, public_key_to_address};
+    use secp256k1::{PublicKey, Secp256k1};
+    
+    fn address_of(key: &SecretKey) -> Address {
+        public_key_to_address(&PublicKey::from_secret_key(&Secp256k1::new(), key))
+    }
+    
+    /// Engine with two signers and four block epochs
+    fn epoch_clique(keys: &[SecretKey]) -> Clique {
+        Clique::new(ConsensusConfig {
+            engine_type: crate::EngineType::Clique,
+            epoch_length: 4,
+            block_period: 15,
+            validators: keys.iter().map(address_of).collect(),
+            genesis_validators: vec![],
+        })
+    }
+    
+    /// Block `number` with `signers` in its extraData, sealed by `key`
+    fn sealed_block(clique: &Clique, number: u64, signers: &[Address], key: &SecretKey) -> Block {
+        let mut header = Header::new();
+        header.number = U256::from(number);
+        header.difficulty = U256::from(2);
+        header.timestamp = 1_000;
+        header.extra_data = vec![0u8; EXTRA_VANITY];
+        for signer in signers {
+            header.extra_data.extend_from_slice(signer.as_bytes());
+        }
+        
+        let signature = sign_message(&H256::from(clique.signing_hash(&header)), key).unwrap();
+        header.extra_data.extend_from_slice(&signature.to_bytes());
+        Block { header, transactions: Vec::new(), ommers: Vec::new(), withdrawals: None }
+    }
     
     #[test]
     fn test_clique_initialization() {
@@ -355,4 +444,62 @@ mod tests {
         assert!(clique.is_authorized(&Address::from([2u8; 20])));
         assert!(!clique.is_authorized(&Address::from([3u8; 20])));
     }
+    
+    #[test]
+    fn test_epoch_checkpoint_lists_sorted_signers() {
+        let keys = [generate_private_key(), generate_private_key()];
+        let clique = epoch_clique(&keys);
+        let signers = clique.sorted_signers();
+        
+        let block = sealed_block(&clique, 8, &signers, &keys[0]);
+        clique.validate_block(&block).unwrap();
+        assert_eq!(Clique::checkpoint_signers(&block.header).unwrap(), signers);
+        
+        // Produced headers carry the list only on epoch transitions
+        assert_eq!(clique.extra_data_for(U256::from(8)).len(), EXTRA_VANITY + 2 * 20);
+        assert_eq!(clique.extra_data_for(U256::from(9)).len(), EXTRA_VANITY);
+    }
+    
+    #[test]
+    fn test_epoch_checkpoint_with_wrong_signers_rejected() {
+        let keys = [generate_private_key(), generate_private_key()];
+        let clique = epoch_clique(&keys);
+        let mut signers = clique.sorted_signers();
+        
+        // Unsorted
+        signers.reverse();
+        let block = sealed_block(&clique, 8, &signers, &keys[0]);
+        assert!(matches!(clique.validate_block(&block), Err(ConsensusError::InvalidBlock(_))));
+        
+        // Missing a signer
+        let block = sealed_block(&clique, 8, &signers[..1], &keys[0]);
+        assert!(matches!(clique.validate_block(&block), Err(ConsensusError::InvalidBlock(_))));
+        
+        // An unauthorized extra signer
+        signers.push(Address::from([3u8; 20]));
+        signers.sort();
+        let block = sealed_block(&clique, 8, &signers, &keys[0]);
+        assert!(matches!(clique.validate_block(&block), Err(ConsensusError::InvalidBlock(_))));
+        
+        // No list at all
+        let block = sealed_block(&clique, 8, &[], &keys[0]);
+        assert!(matches!(clique.validate_block(&block), Err(ConsensusError::InvalidBlock(_))));
+    }
+    
+    #[test]
+    fn test_non_epoch_block_with_signers_rejected() {
+        let keys = [generate_private_key(), generate_private_key()];
+        let clique = epoch_clique(&keys);
+        
+        let block = sealed_block(&clique, 9, &clique.sorted_signers(), &keys[0]);
+        assert!(matches!(clique.validate_block(&block), Err(ConsensusError::InvalidBlock(_))));
+        
+        let block = sealed_block(&clique, 9, &[], &keys[0]);
+        clique.validate_block(&block).unwrap();
+        
+        // A partial address is malformed wherever it appears
+        let mut block = sealed_block(&clique, 9, &[], &keys[0]);
+        block.header.extra_data.insert(EXTRA_VANITY, 0xff);
+        assert!(Clique::checkpoint_signers(&block.header).is_err());
+    }
 }
\ No newline at end of file
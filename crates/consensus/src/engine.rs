@@ -63,6 +63,11 @@ pub trait ConsensusEngine: Send + Sync {
     
     /// Calculate difficulty for next block
     fn calculate_difficulty(&self, parent: &Header, timestamp: u64) -> U256;
+    
+    /// Minimum number of seconds between a block and its parent
+    fn min_block_period(&self) -> u64 {
+        0
+    }
 }
 
 /// Consensus parameters for different networks
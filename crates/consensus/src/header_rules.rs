@@ -0,0 +1,106 @@
+use ethereum_core::Header;
+use ethereum_types::U256;
+
+use crate::{ConsensusError, Result};
+
+/// Lowest gas limit a block may have
+pub const MIN_GAS_LIMIT: u64 = 5000;
+
+/// The gas limit may move by less than `parent.gas_limit / GAS_LIMIT_BOUND_DIVISOR`
+/// from one block to the next
+pub const GAS_LIMIT_BOUND_DIVISOR: u64 = 1024;
+
+/// Header checks against the parent that hold for every engine
+///
+/// The timestamp must increase, by at least `min_period` seconds for engines
+/// with a fixed block period. The gas limit stays above `MIN_GAS_LIMIT` and
+/// changes by strictly less than a 1/1024th of the parent's, as in the yellow
+/// paper, so a change of exactly `parent / 1024` is rejected.
+pub fn validate_against_parent(header: &Header, parent: &Header, min_period: u64) -> Result<()> {
+    if header.timestamp <= parent.timestamp {
+        return Err(ConsensusError::InvalidBlock(format!(
+            "Timestamp {} not greater than parent timestamp {}",
+            header.timestamp, parent.timestamp
+        )));
+    }
+    if header.timestamp < parent.timestamp.saturating_add(min_period) {
+        return Err(ConsensusError::InvalidBlock(format!(
+            "Block too early: timestamp {} is less than {} seconds after parent timestamp {}",
+            header.timestamp, min_period, parent.timestamp
+        )));
+    }
+
+    if header.gas_limit < U256::from(MIN_GAS_LIMIT) {
+        return Err(ConsensusError::InvalidBlock(format!(
+            "Gas limit {} below minimum {}", header.gas_limit, MIN_GAS_LIMIT
+        )));
+    }
+    let delta = if header.gas_limit > parent.gas_limit {
+        header.gas_limit - parent.gas_limit
+    } else {
+        parent.gas_limit - header.gas_limit
+    };
+    let bound = parent.gas_limit / U256::from(GAS_LIMIT_BOUND_DIVISOR);
+    if delta >= bound {
+        return Err(ConsensusError::InvalidBlock(format!(
+            "Gas limit {} changed by {} from parent gas limit {}, bound is {}",
+            header.gas_limit, delta, parent.gas_limit, bound
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parent() -> Header {
+        let mut parent = Header::new();
+        parent.timestamp = 1_000;
+        parent.gas_limit = U256::from(30_000_000u64);
+        parent
+    }
+
+    fn child(timestamp: u64, gas_limit: U256) -> Header {
+        let mut header = Header::new();
+        header.timestamp = timestamp;
+        header.gas_limit = gas_limit;
+        header
+    }
+
+    #[test]
+    fn test_timestamp_must_increase() {
+        let parent = parent();
+        for timestamp in [999, 1_000] {
+            assert!(validate_against_parent(&child(timestamp, parent.gas_limit), &parent, 0).is_err());
+        }
+        assert!(validate_against_parent(&child(1_001, parent.gas_limit), &parent, 0).is_ok());
+
+        // Engines with a block period need at least that much in between
+        assert!(validate_against_parent(&child(1_014, parent.gas_limit), &parent, 15).is_err());
+        assert!(validate_against_parent(&child(1_015, parent.gas_limit), &parent, 15).is_ok());
+    }
+
+    #[test]
+    fn test_gas_limit_change_is_bounded() {
+        let parent = parent();
+        let bound = parent.gas_limit / U256::from(GAS_LIMIT_BOUND_DIVISOR);
+
+        for gas_limit in [parent.gas_limit + bound - 1, parent.gas_limit - bound + 1] {
+            assert!(validate_against_parent(&child(1_012, gas_limit), &parent, 0).is_ok());
+        }
+        for gas_limit in [parent.gas_limit + bound, parent.gas_limit - bound] {
+            assert!(validate_against_parent(&child(1_012, gas_limit), &parent, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn test_gas_limit_floor() {
+        let mut parent = parent();
+        parent.gas_limit = U256::from(MIN_GAS_LIMIT);
+
+        assert!(validate_against_parent(&child(1_012, U256::from(MIN_GAS_LIMIT)), &parent, 0).is_ok());
+        assert!(validate_against_parent(&child(1_012, U256::from(MIN_GAS_LIMIT - 1)), &parent, 0).is_err());
+    }
+}
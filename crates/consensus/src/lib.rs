@@ -12,6 +12,10 @@ pub mod pos;
 pub mod clique;
 pub mod eip7251;
 pub mod eip7002;
+pub mod checkpoint;
+pub mod producer;
+pub mod header_rules;
+pub mod slashing;
 
 pub use engine::{ConsensusEngine, EngineError};
 pub use validator::{BlockValidator, ValidationResult};
@@ -20,6 +24,9 @@ pub use pos::ProofOfStake;
 pub use clique::Clique;
 pub use eip7251::{ValidatorEip7251, ValidatorRegistry, ConsolidationRequest};
 pub use eip7002::{WithdrawalRequest, WithdrawalRequestContract, ExitQueueManager};
+pub use checkpoint::{Checkpoint, CheckpointKind, CheckpointStore};
+pub use slashing::{Attestation, AttestationCheckpoint, Proposal, SlashableOffense, SlashingDetector};
+pub use producer::{next_base_fee, select_transactions, BlockAssembler, BlockExecutor, BlockProducer};
 
 #[derive(Debug, Error)]
 pub enum ConsensusError {
@@ -35,6 +42,12 @@ pub enum ConsensusError {
     #[error("Fork choice error: {0}")]
     ForkChoiceError(String),
     
+    #[error("Checkpoint error: {0}")]
+    CheckpointError(String),
+    
+    #[error("Missed slot {0}")]
+    MissedSlot(u64),
+    
     #[error("Engine error: {0}")]
     EngineError(#[from] EngineError),
     
@@ -67,6 +80,7 @@ pub struct Consensus<D: Database> {
     validator: BlockValidator<D>,
     fork_choice: ForkChoice<D>,
     config: ConsensusConfig,
+    checkpoints: Arc<CheckpointStore>,
     db: Arc<D>,
 }
 
@@ -90,28 +104,45 @@ impl<D: Database + 'static> Consensus<D> {
         
         let validator = BlockValidator::new(db.clone());
         let fork_choice = ForkChoice::new(db.clone());
+        let checkpoints = Arc::new(CheckpointStore::new(db.clone()));
         
         Self {
             engine,
             validator,
             fork_choice,
             config,
+            checkpoints,
             db,
         }
     }
     
+    /// Replace the engine picked from the config, e.g. one set up with a
+    /// block assembler and signing key for production
+    pub fn with_engine(mut self, engine: Box<dyn ConsensusEngine>) -> Self {
+        self.engine = engine;
+        self
+    }
+    
     /// Validate a block according to consensus rules
     pub async fn validate_block(&self, block: &Block) -> Result<ValidationResult> {
         // Engine-specific validation
         self.engine.validate_block(block)?;
         
+        // Rules against the parent shared by every engine
+        if let Some(parent) = self.validator.parent_header(&block.header)? {
+            header_rules::validate_against_parent(&block.header, &parent, self.engine.min_block_period())?;
+        }
+        
         // General block validation
         let result = self.validator.validate(block).await?;
         
         Ok(result)
     }
     
-    /// Produce a new block
+    /// Produce a new sealed block
+    ///
+    /// The engine assembles and executes the block, its header is complete
+    /// before the seal is added.
     pub async fn produce_block(
         &self,
         parent: &Header,
@@ -124,7 +155,7 @@ impl<D: Database + 'static> Consensus<D> {
             beneficiary,
         ).await?;
         
-        Ok(block)
+        self.engine.seal_block(block).await
     }
     
     /// Apply fork choice rule to select canonical chain
@@ -146,16 +177,35 @@ impl<D: Database + 'static> Consensus<D> {
         self.engine.is_validator(address)
     }
     
+    /// Finalized/justified/safe checkpoints, shared with the engine API and sync
+    pub fn checkpoints(&self) -> Arc<CheckpointStore> {
+        self.checkpoints.clone()
+    }
+    
     /// Finalize a block
     pub async fn finalize_block(&self, block: &Block) -> Result<()> {
+        let hash = block.header.hash();
+        let checkpoint = Checkpoint::new(hash, block.header.number.as_u64());
+        
+        // Reject finality regressions before touching the engine
+        if let Some(current) = self.checkpoints.finalized()? {
+            if checkpoint.number < current.number {
+                return Err(ConsensusError::CheckpointError(format!(
+                    "block {} is below finalized block {}",
+                    checkpoint.number, current.number
+                )));
+            }
+        }
+        
         self.engine.finalize(block).await?;
         
         // Store finalized block
-        let key = format!("finalized:{}", hex::encode(block.header.hash()));
+        let key = format!("finalized:{}", hex::encode(hash));
         self.db.put(
             key.as_bytes(),
             &bincode::serialize(block).unwrap(),
         )?;
+        self.checkpoints.set_finalized(checkpoint)?;
         
         Ok(())
     }
@@ -174,9 +224,16 @@ impl<D: Database + 'static> Consensus<D> {
     }
     
     async fn get_finalized_block(&self) -> Result<Block> {
-        // Get latest finalized block from database
-        // For now, return a mock block
-        Ok(Block::default())
+        let Some(checkpoint) = self.checkpoints.finalized()? else {
+            return Ok(Block::default());
+        };
+        
+        let key = format!("finalized:{}", hex::encode(checkpoint.hash));
+        match self.db.get(key.as_bytes())? {
+            Some(data) => bincode::deserialize(&data)
+                .map_err(|e| ConsensusError::CheckpointError(e.to_string())),
+            None => Ok(Block::default()),
+        }
     }
     
     async fn get_justified_block(&self) -> Result<Block> {
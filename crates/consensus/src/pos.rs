@@ -3,11 +3,19 @@ use ethereum_core::{Block, Header, Transaction};
 use ethereum_crypto::{Signature, recover_address};
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::sync::Arc;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 
 use crate::{Result, ConsensusError, ConsensusConfig};
 use crate::engine::{ConsensusEngine, EngineError};
+use crate::producer::BlockAssembler;
+use crate::slashing::{Attestation, Proposal, SlashableOffense, SlashingDetector};
+
+/// Epochs of proposals and attestations kept for slashing detection
+///
+/// Evidence older than this can no longer get a validator slashed.
+const SLASHING_HISTORY_EPOCHS: u64 = 4096;
 
 /// Proof of Stake consensus implementation
 pub struct ProofOfStake {
@@ -17,22 +25,9 @@ pub struct ProofOfStake {
     epoch: u64,
     slot: u64,
     attestations: Vec<Attestation>,
-}
-
-#[derive(Debug, Clone)]
-struct Attestation {
-    slot: u64,
-    validator: Address,
-    beacon_block_root: H256,
-    source_checkpoint: Checkpoint,
-    target_checkpoint: Checkpoint,
-    signature: Signature,
-}
-
-#[derive(Debug, Clone)]
-struct Checkpoint {
-    epoch: u64,
-    root: H256,
+    slashing: SlashingDetector,
+    offenses: Vec<SlashableOffense>,
+    assembler: Option<Arc<BlockAssembler>>,
 }
 
 impl ProofOfStake {
@@ -52,9 +47,18 @@ impl ProofOfStake {
             epoch: 0,
             slot: 0,
             attestations: Vec::new(),
+            slashing: SlashingDetector::new(),
+            offenses: Vec::new(),
+            assembler: None,
         }
     }
     
+    /// Execute produced blocks with `assembler`
+    pub fn with_assembler(mut self, assembler: Arc<BlockAssembler>) -> Self {
+        self.assembler = Some(assembler);
+        self
+    }
+    
     /// Get proposer for a given slot
     fn get_proposer(&self, slot: u64) -> Address {
         if self.validators.is_empty() {
@@ -89,8 +93,8 @@ impl ProofOfStake {
         committee
     }
     
-    /// Verify block proposer signature
-    fn verify_proposer_signature(&self, block: &Block) -> Result<()> {
+    /// Verify block proposer signature, returning the signed proposal
+    fn verify_proposer_signature(&self, block: &Block) -> Result<Proposal> {
         let expected_proposer = self.get_proposer(self.slot);
         
         // Extract signature from block extra data
@@ -106,7 +110,7 @@ impl ProofOfStake {
         
         // Verify signature
         let block_hash = block.header.hash();
-        let recovered = recover_address(&block_hash.0, &signature)
+        let recovered = recover_address(&block_hash, &signature)
             .map_err(|_| ConsensusError::InvalidSignature("Failed to recover address".to_string()))?;
         
         if recovered != expected_proposer {
@@ -115,15 +119,56 @@ impl ProofOfStake {
             ));
         }
         
+        Ok(Proposal {
+            slot: self.slot,
+            proposer: recovered,
+            block_root: block_hash,
+            signature,
+        })
+    }
+    
+    /// Check that `header` was produced for the current slot
+    fn verify_slot(&self, header: &Header) -> Result<()> {
+        let expected_slot = header.timestamp / self.config.block_period;
+        if expected_slot != self.slot {
+            return Err(ConsensusError::InvalidBlock(
+                format!("Invalid slot: expected {}, got {}", self.slot, expected_slot)
+            ));
+        }
+        
+        Ok(())
+    }
+    
+    /// Process a block received for the current slot
+    ///
+    /// Its proposal is checked against the proposer's earlier blocks for the
+    /// slot.
+    pub fn process_block(&mut self, block: &Block) -> Result<()> {
+        let proposal = self.verify_proposer_signature(block)?;
+        self.verify_slot(&block.header)?;
+        
+        let epoch = proposal.slot / self.config.epoch_length;
+        if let Some(offense) = self.slashing.observe_proposal(proposal) {
+            tracing::warn!("Slashable proposal by {:?}", offense.offender());
+            self.offenses.push(offense);
+        }
+        self.prune_slashing_history(epoch);
+        
         Ok(())
     }
     
     /// Process attestations for finality
-    fn process_attestations(&mut self, attestations: Vec<Attestation>) {
+    pub fn process_attestations(&mut self, attestations: Vec<Attestation>) {
         for attestation in attestations {
             // Verify attestation signature
             if self.verify_attestation(&attestation).is_ok() {
+                let epoch = attestation.target.epoch;
+                for offense in self.slashing.observe_attestation(attestation.clone()) {
+                    tracing::warn!("Slashable attestation by {:?}", offense.offender());
+                    self.offenses.push(offense);
+                }
                 self.attestations.push(attestation);
+                self.prune_slashing_history(epoch);
             }
         }
         
@@ -131,6 +176,23 @@ impl ProofOfStake {
         self.check_finality();
     }
     
+    /// Drop slashing history more than `SLASHING_HISTORY_EPOCHS` behind the
+    /// newest epoch seen
+    fn prune_slashing_history(&mut self, epoch: u64) {
+        if epoch <= self.epoch {
+            return;
+        }
+        self.epoch = epoch;
+        
+        let oldest = epoch.saturating_sub(SLASHING_HISTORY_EPOCHS);
+        self.slashing.prune(oldest * self.config.epoch_length, oldest);
+    }
+    
+    /// Drain the slashable offenses observed in verified blocks and attestations
+    pub fn take_offenses(&mut self) -> Vec<SlashableOffense> {
+        std::mem::take(&mut self.offenses)
+    }
+    
     /// Verify attestation
     fn verify_attestation(&self, attestation: &Attestation) -> Result<()> {
         // Check validator is in committee
@@ -156,14 +218,14 @@ impl ProofOfStake {
     }
     
     /// Create attestation message for signing
-    fn attestation_message(&self, attestation: &Attestation) -> [u8; 32] {
+    fn attestation_message(&self, attestation: &Attestation) -> H256 {
         let mut data = Vec::new();
         data.extend_from_slice(&attestation.slot.to_le_bytes());
         data.extend_from_slice(&attestation.beacon_block_root.0);
-        data.extend_from_slice(&attestation.source_checkpoint.epoch.to_le_bytes());
-        data.extend_from_slice(&attestation.source_checkpoint.root.0);
-        data.extend_from_slice(&attestation.target_checkpoint.epoch.to_le_bytes());
-        data.extend_from_slice(&attestation.target_checkpoint.root.0);
+        data.extend_from_slice(&attestation.source.epoch.to_le_bytes());
+        data.extend_from_slice(&attestation.source.root.0);
+        data.extend_from_slice(&attestation.target.epoch.to_le_bytes());
+        data.extend_from_slice(&attestation.target.root.0);
         
         ethereum_crypto::keccak256(&data)
     }
@@ -176,8 +238,8 @@ impl ProofOfStake {
         let mut attestation_count: HashMap<H256, usize> = HashMap::new();
         
         for attestation in &self.attestations {
-            if attestation.target_checkpoint.epoch == current_epoch {
-                *attestation_count.entry(attestation.target_checkpoint.root)
+            if attestation.target.epoch == current_epoch {
+                *attestation_count.entry(attestation.target.root)
                     .or_insert(0) += 1;
             }
         }
@@ -213,14 +275,7 @@ impl ConsensusEngine for ProofOfStake {
         self.verify_proposer_signature(block)?;
         
         // Check slot timing
-        let expected_slot = block.header.timestamp / self.config.block_period;
-        if expected_slot != self.slot {
-            return Err(ConsensusError::InvalidBlock(
-                format!("Invalid slot: expected {}, got {}", self.slot, expected_slot)
-            ));
-        }
-        
-        Ok(())
+        self.verify_slot(&block.header)
     }
     
     fn verify_seal(&self, header: &Header) -> Result<()> {
@@ -240,34 +295,15 @@ impl ConsensusEngine for ProofOfStake {
         transactions: Vec<Transaction>,
         beneficiary: Address,
     ) -> Result<Block> {
-        let mut header = Header {
-            parent_hash: parent.hash(),
-            uncles_hash: H256::from([0x1d, 0xcc, 0x4d, 0xe8, 0xde, 0xc7, 0x5d, 0x7a,
-                                     0xab, 0x85, 0xb5, 0x67, 0xb6, 0xcc, 0xd4, 0x1a,
-                                     0xd3, 0x12, 0x45, 0x1b, 0x94, 0x8a, 0x74, 0x13,
-                                     0xf0, 0xa1, 0x42, 0xfd, 0x40, 0xd4, 0x93, 0x47]),
-            author: beneficiary,
-            state_root: H256::zero(), // Would be calculated after execution
-            transactions_root: H256::zero(), // Would be calculated from transactions
-            receipts_root: H256::zero(), // Would be calculated from receipts
-            bloom: Default::default(),
-            difficulty: U256::zero(), // No difficulty in PoS
-            number: parent.number + U256::one(),
-            gas_limit: parent.gas_limit,
-            gas_used: U256::zero(), // Would be calculated from execution
-            timestamp: self.slot * self.config.block_period,
-            extra_data: self.extra_data(),
-            mix_hash: H256::zero(),
-            nonce: 0,
-        };
+        let mut header = Header::new();
+        header.beneficiary = beneficiary;
+        header.difficulty = U256::zero(); // No difficulty in PoS
+        header.timestamp = self.slot * self.config.block_period;
+        header.extra_data = self.extra_data();
         
-        // Create block body
-        let body = ethereum_core::BlockBody {
-            transactions,
-            uncles: vec![], // No uncles in PoS
-        };
-        
-        Ok(Block { header, body })
+        // Without an executor there is no post-state to put in the header
+        let assembler = self.assembler.as_ref().ok_or(EngineError::NotReady)?;
+        assembler.assemble(parent, header, transactions)
     }
     
     async fn seal_block(&self, mut block: Block) -> Result<Block> {
@@ -345,4 +381,48 @@ mod tests {
         assert_eq!(proposer2, Address::from([3u8; 20]));
         assert_eq!(proposer3, Address::from([1u8; 20])); // Wraps around
     }
+    
+    #[test]
+    fn test_attestations_feed_slashing_detector() {
+        use crate::slashing::AttestationCheckpoint;
+        use ethereum_crypto::{generate_private_key, public_key_to_address, sign_message};
+        
+        let key = generate_private_key();
+        let secp = secp256k1::Secp256k1::new();
+        let validator = public_key_to_address(&secp256k1::PublicKey::from_secret_key(&secp, &key));
+        let config = ConsensusConfig {
+            engine_type: crate::EngineType::ProofOfStake,
+            epoch_length: 32,
+            block_period: 12,
+            validators: vec![validator],
+            genesis_validators: vec![],
+        };
+        let mut pos = ProofOfStake::new(config);
+        
+        let attest = |pos: &ProofOfStake, target: u64, root: u8| {
+            let mut attestation = Attestation {
+                slot: target * 32,
+                validator,
+                beacon_block_root: H256::from([root; 32]),
+                source: AttestationCheckpoint::new(target - 1, H256::zero()),
+                target: AttestationCheckpoint::new(target, H256::from([root; 32])),
+                signature: Signature::default(),
+            };
+            attestation.signature = sign_message(&pos.attestation_message(&attestation), &key).unwrap();
+            attestation
+        };
+        
+        let (first, second) = (attest(&pos, 3, 0xaa), attest(&pos, 3, 0xbb));
+        pos.process_attestations(vec![first, second]);
+        let offenses = pos.take_offenses();
+        assert_eq!(offenses.len(), 1);
+        assert_eq!(offenses[0].offender(), validator);
+        
+        // Once the chain is far enough ahead the old votes are forgotten
+        let late = attest(&pos, 3 + SLASHING_HISTORY_EPOCHS + 1, 0xaa);
+        pos.process_attestations(vec![late]);
+        let conflicting = attest(&pos, 3, 0xcc);
+        pos.process_attestations(vec![conflicting]);
+        assert!(pos.take_offenses().is_empty());
+    }
 }
\ No newline at end of file
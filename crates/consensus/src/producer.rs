@@ -0,0 +1,611 @@
+use ethereum_types::{Address, U256};
+use ethereum_core::eip7691::calculate_excess_blob_gas;
+use ethereum_core::{Block, BlobGasConfig, BlobSchedule, Header, Transaction, Withdrawal};
+pub use ethereum_core::block::{next_base_fee, BASE_FEE_MAX_CHANGE_DENOMINATOR, ELASTICITY_MULTIPLIER};
+use ethereum_crypto::{keccak256, public_key_to_address, sign_message};
+use ethereum_txpool::TransactionPool;
+use parking_lot::{Mutex, RwLock};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::{ConsensusError, Result};
+
+/// Maximum number of pool transactions considered for a single block
+pub const MAX_TXS: usize = 1_000;
+
+/// Building must finish this long before the end of the slot so the block
+/// can still be propagated in time
+pub const EARLY_CUTOFF: Duration = Duration::from_millis(500);
+
+/// Executes a candidate block on top of its parent state
+pub trait BlockExecutor: Send + Sync {
+    /// Run the block's transactions, dropping the ones that fail, credit its
+    /// withdrawals and fill in the post-execution header fields (state,
+    /// transactions, receipts and withdrawals roots, bloom, gas used)
+    fn execute(&self, block: Block) -> Result<Block>;
+}
+
+/// Pick transactions for a block in the given order, returning them with
+/// the blob gas they use
+///
+/// Candidates come with their sender. Transactions that can't pay
+/// `base_fee` or no longer fit in the remaining gas or blob gas are skipped,
+/// smaller ones behind them may still be included. A skipped transaction
+/// also skips its sender's later nonces, which could no longer execute. At
+/// most `MAX_TXS` are taken.
+pub fn select_transactions(
+    candidates: impl IntoIterator<Item = (Address, Transaction)>,
+    gas_limit: U256,
+    base_fee: Option<U256>,
+    max_blob_gas: u64,
+) -> (Vec<Transaction>, u64) {
+    let mut selected = Vec::new();
+    let mut seen = HashSet::new();
+    // Lowest skipped nonce of each sender
+    let mut gaps: HashMap<Address, U256> = HashMap::new();
+    let mut gas_left = gas_limit;
+    let mut blob_gas_used = 0u64;
+
+    for (sender, tx) in candidates {
+        if selected.len() == MAX_TXS {
+            break;
+        }
+        if gaps.get(&sender).is_some_and(|gap| tx.nonce() > *gap) {
+            continue;
+        }
+        let underpriced = base_fee.is_some_and(|base_fee| tx.gas_price() < base_fee);
+        let fits = !underpriced
+            && tx.gas_limit() <= gas_left
+            && blob_gas_used + tx.blob_gas() <= max_blob_gas;
+        if !fits {
+            let gap = gaps.entry(sender).or_insert_with(|| tx.nonce());
+            *gap = (*gap).min(tx.nonce());
+            continue;
+        }
+        if !seen.insert(tx.hash()) {
+            continue;
+        }
+        gas_left -= tx.gas_limit();
+        blob_gas_used += tx.blob_gas();
+        selected.push(tx);
+    }
+
+    (selected, blob_gas_used)
+}
+
+/// Set the blob gas fields of a header built on `parent`, left empty when
+/// the block's fork has no blobs
+fn set_blob_gas(header: &mut Header, parent: &Header, config: Option<&BlobGasConfig>, blob_gas_used: u64) {
+    header.blob_gas_used = config.map(|_| blob_gas_used);
+    header.excess_blob_gas = config.map(|config| {
+        calculate_excess_blob_gas(
+            parent.excess_blob_gas.unwrap_or_default(),
+            parent.blob_gas_used.unwrap_or_default(),
+            config,
+        )
+    });
+}
+
+/// Builds executed, unsealed blocks for the consensus engines
+///
+/// Fills the execution fields of a header an engine prepared: the gas limit
+/// and base fee follow from the parent, transactions come from the caller
+/// first and then from the pool, queued withdrawals are attached and the
+/// executor computes the post-state fields.
+pub struct BlockAssembler {
+    executor: Arc<dyn BlockExecutor>,
+    pool: Option<Arc<TransactionPool>>,
+    withdrawals: Mutex<Vec<Withdrawal>>,
+    blob_schedule: BlobSchedule,
+}
+
+impl BlockAssembler {
+    pub fn new(executor: Arc<dyn BlockExecutor>) -> Self {
+        Self {
+            executor,
+            pool: None,
+            withdrawals: Mutex::new(Vec::new()),
+            blob_schedule: BlobSchedule::default(),
+        }
+    }
+
+    /// Fill blocks with executable transactions from `pool`
+    pub fn with_pool(mut self, pool: Arc<TransactionPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Forks that decide how much blob gas a block may use
+    pub fn with_blob_schedule(mut self, schedule: BlobSchedule) -> Self {
+        self.blob_schedule = schedule;
+        self
+    }
+
+    /// Withdrawals to include in the next assembled block
+    pub fn queue_withdrawals(&self, withdrawals: Vec<Withdrawal>) {
+        self.withdrawals.lock().extend(withdrawals);
+    }
+
+    /// Execute a block on top of `parent` with the queued withdrawals
+    ///
+    /// `header` carries the engine-specific fields (beneficiary, timestamp,
+    /// difficulty, extra data); the rest is overwritten here.
+    pub fn assemble(&self, parent: &Header, header: Header, transactions: Vec<Transaction>) -> Result<Block> {
+        self.build(parent, header, transactions, true)
+    }
+
+    /// Execute a block for an engine without a beacon chain, like Clique,
+    /// whose blocks carry no withdrawals
+    pub fn assemble_without_withdrawals(
+        &self,
+        parent: &Header,
+        header: Header,
+        transactions: Vec<Transaction>,
+    ) -> Result<Block> {
+        self.build(parent, header, transactions, false)
+    }
+
+    fn build(&self, parent: &Header, mut header: Header, transactions: Vec<Transaction>, withdrawals: bool) -> Result<Block> {
+        header.parent_hash = parent.hash();
+        header.ommers_hash = keccak256(&[0xc0]);
+        header.number = parent.number + U256::one();
+        header.gas_limit = parent.gas_limit;
+        header.gas_used = U256::zero();
+        header.base_fee_per_gas = next_base_fee(parent);
+
+        let blob_config = self.blob_schedule.config_at(header.timestamp);
+        let max_blob_gas = blob_config.as_ref().map_or(0, |config| config.max_blob_gas_per_block);
+        let base_fee = header.base_fee_per_gas.unwrap_or_default();
+        let pooled = self.pool.iter().flat_map(|pool| {
+            pool.get_transactions_for_block(parent.gas_limit, base_fee, max_blob_gas)
+                .0
+                .into_iter()
+                .map(|pooled| (pooled.from, pooled.tx))
+        });
+        let (transactions, blob_gas_used) = select_transactions(
+            transactions.into_iter().map(|tx| (tx.from(), tx)).chain(pooled),
+            header.gas_limit,
+            header.base_fee_per_gas,
+            max_blob_gas,
+        );
+        set_blob_gas(&mut header, parent, blob_config.as_ref(), blob_gas_used);
+
+        let withdrawals = withdrawals.then(|| self.withdrawals.lock().clone());
+        let gas_limit = header.gas_limit;
+        let block = self.executor.execute(Block {
+            header,
+            transactions,
+            ommers: Vec::new(),
+            withdrawals,
+        })?;
+
+        if block.header.gas_used > gas_limit {
+            return Err(ConsensusError::InvalidBlock(format!(
+                "executed block uses {} gas, limit is {}", block.header.gas_used, gas_limit
+            )));
+        }
+
+        // Withdrawals are consumed once they made it into a block
+        if let Some(included) = &block.withdrawals {
+            self.withdrawals.lock().retain(|w| !included.contains(w));
+        }
+
+        debug!(
+            "Assembled block {} with {} transactions using {} gas",
+            block.header.number,
+            block.transactions.len(),
+            block.header.gas_used
+        );
+
+        Ok(block)
+    }
+}
+
+/// Produces blocks for the slots this node proposes
+pub struct BlockProducer {
+    genesis_time: u64,
+    head: Arc<RwLock<Header>>,
+    withdrawals: Arc<Mutex<Vec<Withdrawal>>>,
+    executor: Arc<dyn BlockExecutor>,
+    blob_schedule: BlobSchedule,
+}
+
+impl BlockProducer {
+    pub fn new(genesis_time: u64, head: Header, executor: Arc<dyn BlockExecutor>) -> Self {
+        Self {
+            genesis_time,
+            head: Arc::new(RwLock::new(head)),
+            withdrawals: Arc::new(Mutex::new(Vec::new())),
+            executor,
+            blob_schedule: BlobSchedule::default(),
+        }
+    }
+
+    /// Forks that decide how much blob gas a block may use
+    pub fn with_blob_schedule(mut self, schedule: BlobSchedule) -> Self {
+        self.blob_schedule = schedule;
+        self
+    }
+
+    /// Update the parent the next block is built on
+    pub fn set_head(&self, head: Header) {
+        *self.head.write() = head;
+    }
+
+    /// Withdrawals to include in the next produced block
+    pub fn queue_withdrawals(&self, withdrawals: Vec<Withdrawal>) {
+        self.withdrawals.lock().extend(withdrawals);
+    }
+
+    /// Start time of `slot` in unix seconds
+    pub fn slot_start(&self, slot: u64, slot_duration: Duration) -> u64 {
+        self.genesis_time + slot * slot_duration.as_secs()
+    }
+
+    /// Schedule production of the block for `slot`
+    ///
+    /// The task sleeps until the slot starts, collects up to `MAX_TXS`
+    /// transactions from the pool, executes them together with the queued
+    /// withdrawals and seals the block with `proposer_key`. Building has to
+    /// finish `EARLY_CUTOFF` before the slot ends.
+    pub fn schedule(
+        &self,
+        slot: u64,
+        slot_duration: Duration,
+        proposer_key: &SecretKey,
+        pool: &Arc<TransactionPool>,
+    ) -> JoinHandle<Result<Block>> {
+        let produce_at = self.slot_start(slot, slot_duration);
+        let head = self.head.clone();
+        let withdrawals = self.withdrawals.clone();
+        let executor = self.executor.clone();
+        let proposer_key = *proposer_key;
+        let pool = pool.clone();
+        let blob_config = self.blob_schedule.config_at(produce_at);
+
+        tokio::spawn(async move {
+            let start = instant_at(produce_at);
+            let cutoff = (start + slot_duration)
+                .checked_sub(EARLY_CUTOFF)
+                .unwrap_or(start);
+
+            if Instant::now() >= cutoff {
+                return Err(ConsensusError::MissedSlot(slot));
+            }
+
+            tokio::time::sleep_until(start).await;
+            debug!("Producing block for slot {}", slot);
+
+            let parent = head.read().clone();
+            let beneficiary = proposer_address(&proposer_key);
+            let block = assemble_block(
+                &parent,
+                produce_at,
+                beneficiary,
+                &pool,
+                withdrawals.lock().clone(),
+                blob_config.as_ref(),
+            );
+
+            let executed = tokio::time::timeout_at(
+                cutoff,
+                tokio::task::spawn_blocking(move || executor.execute(block)),
+            )
+            .await
+            .map_err(|_| ConsensusError::MissedSlot(slot))?
+            .map_err(|e| ConsensusError::InvalidBlock(format!("block execution panicked: {}", e)))??;
+
+            let block = seal_block(executed, &proposer_key)?;
+
+            // Withdrawals are consumed once they made it into a block
+            if let Some(included) = &block.withdrawals {
+                withdrawals.lock().retain(|w| !included.contains(w));
+            }
+
+            info!(
+                "Produced block {} for slot {} with {} transactions",
+                block.header.number,
+                slot,
+                block.transactions.len()
+            );
+
+            Ok(block)
+        })
+    }
+}
+
+fn assemble_block(
+    parent: &Header,
+    timestamp: u64,
+    beneficiary: Address,
+    pool: &TransactionPool,
+    withdrawals: Vec<Withdrawal>,
+    blob_config: Option<&BlobGasConfig>,
+) -> Block {
+    let mut header = Header::new();
+    header.parent_hash = parent.hash();
+    // keccak256 of the RLP empty list, no ommers post-merge
+    header.ommers_hash = keccak256(&[0xc0]);
+    header.beneficiary = beneficiary;
+    header.number = parent.number + U256::one();
+    header.gas_limit = parent.gas_limit;
+    header.timestamp = timestamp;
+    header.base_fee_per_gas = next_base_fee(parent);
+
+    let max_blob_gas = blob_config.map_or(0, |config| config.max_blob_gas_per_block);
+    let (transactions, blob_gas_used) = select_transactions(
+        pool.get_transactions_for_block(
+            parent.gas_limit,
+            header.base_fee_per_gas.unwrap_or_default(),
+            max_blob_gas,
+        )
+            .0
+            .into_iter()
+            .map(|pooled| (pooled.from, pooled.tx)),
+        header.gas_limit,
+        header.base_fee_per_gas,
+        max_blob_gas,
+    );
+    set_blob_gas(&mut header, parent, blob_config, blob_gas_used);
+
+    Block {
+        header,
+        transactions,
+        ommers: Vec::new(),
+        withdrawals: Some(withdrawals),
+    }
+}
+
+/// Append the proposer's signature over the unsealed header hash to extra data
+fn seal_block(mut block: Block, proposer_key: &SecretKey) -> Result<Block> {
+    let signature = sign_message(&block.header.hash(), proposer_key)
+        .map_err(|e| ConsensusError::InvalidSignature(e.to_string()))?;
+    block.header.extra_data.extend_from_slice(&signature.to_bytes());
+    Ok(block)
+}
+
+fn proposer_address(key: &SecretKey) -> Address {
+    public_key_to_address(&PublicKey::from_secret_key(&Secp256k1::new(), key))
+}
+
+/// Map a unix timestamp onto the tokio clock
+fn instant_at(unix_secs: u64) -> Instant {
+    let target = UNIX_EPOCH + Duration::from_secs(unix_secs);
+    let now = SystemTime::now();
+    match target.duration_since(now) {
+        Ok(ahead) => Instant::now() + ahead,
+        Err(behind) => Instant::now()
+            .checked_sub(behind.duration())
+            .unwrap_or_else(Instant::now),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::eip7691::BLOB_GAS_PER_BLOB;
+    use ethereum_crypto::{generate_private_key, recover_address, Signature};
+    use ethereum_txpool::{MemoryNonceProvider, TxPoolConfig};
+
+    /// Executor that leaves the assembled block untouched
+    struct NoopExecutor;
+
+    impl BlockExecutor for NoopExecutor {
+        fn execute(&self, block: Block) -> Result<Block> {
+            Ok(block)
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn parent() -> Header {
+        let mut parent = Header::new();
+        parent.number = U256::from(41);
+        parent.gas_limit = U256::from(30_000_000);
+        parent
+    }
+
+    #[tokio::test]
+    async fn test_schedule_produces_sealed_block() {
+        let producer = BlockProducer::new(now(), parent(), Arc::new(NoopExecutor));
+        let withdrawal = Withdrawal {
+            index: 7,
+            validator_index: 3,
+            address: Address::from_bytes([0x11; 20]),
+            amount: 1_000_000_000,
+        };
+        producer.queue_withdrawals(vec![withdrawal.clone()]);
+
+        let key = generate_private_key();
+        let pool = Arc::new(TransactionPool::new(TxPoolConfig::default(), Arc::new(MemoryNonceProvider::new())));
+        let block = producer
+            .schedule(1, Duration::from_secs(1), &key, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(block.header.number, U256::from(42));
+        assert_eq!(block.header.parent_hash, parent().hash());
+        assert_eq!(block.header.timestamp, producer.slot_start(1, Duration::from_secs(1)));
+        assert_eq!(block.withdrawals, Some(vec![withdrawal]));
+
+        // The seal recovers to the proposer
+        let mut unsealed = block.header.clone();
+        let seal = unsealed.extra_data.split_off(unsealed.extra_data.len() - 65);
+        let signature = Signature::from_bytes(&seal).unwrap();
+        assert_eq!(recover_address(&unsealed.hash(), &signature).unwrap(), proposer_address(&key));
+        assert_eq!(block.header.beneficiary, proposer_address(&key));
+    }
+
+    fn legacy_tx(nonce: u64, gas_price: u64, gas_limit: u64) -> Transaction {
+        Transaction::Legacy(ethereum_core::LegacyTransaction {
+            nonce: U256::from(nonce),
+            gas_price: U256::from(gas_price),
+            gas_limit: U256::from(gas_limit),
+            to: Some(Address::from_bytes([0x22; 20])),
+            value: U256::zero(),
+            data: Default::default(),
+            v: 27,
+            r: U256::one(),
+            s: U256::one(),
+        })
+    }
+
+    #[test]
+    fn test_next_base_fee() {
+        let mut parent = parent();
+        assert_eq!(next_base_fee(&parent), None);
+
+        parent.base_fee_per_gas = Some(U256::from(1_000_000_000u64));
+        parent.gas_used = U256::from(15_000_000);
+        assert_eq!(next_base_fee(&parent), Some(U256::from(1_000_000_000u64)));
+
+        // A full block raises the base fee by an eighth, an empty one lowers it
+        parent.gas_used = U256::from(30_000_000);
+        assert_eq!(next_base_fee(&parent), Some(U256::from(1_125_000_000u64)));
+        parent.gas_used = U256::zero();
+        assert_eq!(next_base_fee(&parent), Some(U256::from(875_000_000u64)));
+    }
+
+    fn sender(byte: u8) -> Address {
+        Address::from_bytes([byte; 20])
+    }
+
+    #[test]
+    fn test_select_transactions() {
+        let underpriced = legacy_tx(0, 5, 21_000);
+        let large = legacy_tx(1, 20, 60_000);
+        let small = legacy_tx(2, 20, 21_000);
+        let fits = legacy_tx(3, 10, 50_000);
+
+        let (selected, blob_gas_used) = select_transactions(
+            vec![
+                (sender(1), underpriced),
+                (sender(2), fits.clone()),
+                (sender(3), large),
+                (sender(4), small.clone()),
+                (sender(2), fits.clone()),
+            ],
+            U256::from(100_000),
+            Some(U256::from(10)),
+            0,
+        );
+        assert_eq!(selected, vec![fits, small]);
+        assert_eq!(blob_gas_used, 0);
+    }
+
+    #[test]
+    fn test_skipped_nonce_skips_later_ones() {
+        let first = legacy_tx(0, 20, 21_000);
+        let large = legacy_tx(1, 20, 60_000);
+        let after_gap = legacy_tx(2, 20, 21_000);
+        let other = legacy_tx(5, 20, 21_000);
+
+        // Nonce 2 would fit, but nonce 1 didn't so it can't execute
+        let (selected, _) = select_transactions(
+            vec![
+                (sender(1), first.clone()),
+                (sender(1), large),
+                (sender(1), after_gap),
+                (sender(2), other.clone()),
+            ],
+            U256::from(50_000),
+            None,
+            0,
+        );
+        assert_eq!(selected, vec![first, other]);
+    }
+
+    fn blob_tx(nonce: u64, blobs: u8) -> Transaction {
+        Transaction::Eip4844(ethereum_core::Eip4844Transaction {
+            chain_id: 1,
+            nonce: U256::from(nonce),
+            max_priority_fee_per_gas: U256::one(),
+            max_fee_per_gas: U256::from(20),
+            gas_limit: U256::from(21_000),
+            to: Address::from_bytes([0x22; 20]),
+            value: U256::zero(),
+            data: Default::default(),
+            access_list: Vec::new(),
+            max_fee_per_blob_gas: U256::one(),
+            blob_versioned_hashes: (0..blobs).map(ethereum_types::H256::repeat_byte).collect(),
+            y_parity: false,
+            r: U256::one(),
+            s: U256::one(),
+        })
+    }
+
+    #[test]
+    fn test_assemble_budgets_blobs_by_fork() {
+        let blob_txs: Vec<_> = (0..4).map(|nonce| blob_tx(nonce, 2)).collect();
+        let mut parent = parent();
+        parent.blob_gas_used = Some(0);
+        parent.excess_blob_gas = Some(0);
+
+        // Cancun allows six blobs, so the fourth transaction is left out
+        let cancun = BlobSchedule { cancun_time: Some(0), prague_time: None };
+        let assembler = BlockAssembler::new(Arc::new(NoopExecutor)).with_blob_schedule(cancun);
+        let block = assembler.assemble(&parent, Header::new(), blob_txs.clone()).unwrap();
+        assert_eq!(block.transactions, blob_txs[..3].to_vec());
+        assert_eq!(block.header.blob_gas_used, Some(6 * BLOB_GAS_PER_BLOB));
+        assert_eq!(block.header.excess_blob_gas, Some(0));
+
+        // Prague raises the limit to nine
+        let block = BlockAssembler::new(Arc::new(NoopExecutor))
+            .assemble(&parent, Header::new(), blob_txs.clone())
+            .unwrap();
+        assert_eq!(block.transactions, blob_txs);
+        assert_eq!(block.header.blob_gas_used, Some(8 * BLOB_GAS_PER_BLOB));
+
+        // Before Cancun there are neither blobs nor blob gas fields
+        let london = BlobSchedule { cancun_time: None, prague_time: None };
+        let assembler = BlockAssembler::new(Arc::new(NoopExecutor)).with_blob_schedule(london);
+        let mut transactions = blob_txs;
+        transactions.push(legacy_tx(0, 20, 21_000));
+        let block = assembler.assemble(&parent, Header::new(), transactions).unwrap();
+        assert_eq!(block.transactions, vec![legacy_tx(0, 20, 21_000)]);
+        assert_eq!(block.header.blob_gas_used, None);
+        assert_eq!(block.header.excess_blob_gas, None);
+    }
+
+    #[test]
+    fn test_withdrawals_only_with_beacon_chain() {
+        let assembler = BlockAssembler::new(Arc::new(NoopExecutor));
+        let withdrawal = Withdrawal {
+            index: 1,
+            validator_index: 2,
+            address: sender(3),
+            amount: 4,
+        };
+        assembler.queue_withdrawals(vec![withdrawal.clone()]);
+
+        // A Clique block neither carries nor consumes them
+        let block = assembler.assemble_without_withdrawals(&parent(), Header::new(), Vec::new()).unwrap();
+        assert_eq!(block.withdrawals, None);
+
+        let block = assembler.assemble(&parent(), Header::new(), Vec::new()).unwrap();
+        assert_eq!(block.withdrawals, Some(vec![withdrawal]));
+        let block = assembler.assemble(&parent(), Header::new(), Vec::new()).unwrap();
+        assert_eq!(block.withdrawals, Some(Vec::new()));
+    }
+
+    #[tokio::test]
+    async fn test_past_slot_is_missed() {
+        let producer = BlockProducer::new(now() - 120, parent(), Arc::new(NoopExecutor));
+        let pool = Arc::new(TransactionPool::new(TxPoolConfig::default(), Arc::new(MemoryNonceProvider::new())));
+
+        let result = producer
+            .schedule(2, Duration::from_secs(12), &generate_private_key(), &pool)
+            .await
+            .unwrap();
+
+        assert!(matches!(result, Err(ConsensusError::MissedSlot(2))));
+    }
+}
@@ -0,0 +1,239 @@
+use ethereum_types::{H256, Address};
+use ethereum_crypto::Signature;
+use std::collections::HashMap;
+
+/// Source or target checkpoint an attestation votes for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttestationCheckpoint {
+    pub epoch: u64,
+    pub root: H256,
+}
+
+impl AttestationCheckpoint {
+    pub fn new(epoch: u64, root: H256) -> Self {
+        Self { epoch, root }
+    }
+}
+
+/// A validator's signed vote for a head block and a source to target link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attestation {
+    pub slot: u64,
+    pub validator: Address,
+    pub beacon_block_root: H256,
+    pub source: AttestationCheckpoint,
+    pub target: AttestationCheckpoint,
+    pub signature: Signature,
+}
+
+impl Attestation {
+    /// Same validator and target epoch but a different vote
+    pub fn is_double_vote(&self, other: &Attestation) -> bool {
+        self.validator == other.validator
+            && self.target.epoch == other.target.epoch
+            && self.vote() != other.vote()
+    }
+
+    /// Whether this vote's source to target span strictly encloses `other`'s
+    pub fn surrounds(&self, other: &Attestation) -> bool {
+        self.validator == other.validator
+            && self.source.epoch < other.source.epoch
+            && other.target.epoch < self.target.epoch
+    }
+
+    /// The signed part of the attestation
+    fn vote(&self) -> (u64, H256, AttestationCheckpoint, AttestationCheckpoint) {
+        (self.slot, self.beacon_block_root, self.source, self.target)
+    }
+}
+
+/// A proposer's signed block for a slot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proposal {
+    pub slot: u64,
+    pub proposer: Address,
+    pub block_root: H256,
+    pub signature: Signature,
+}
+
+/// Proof that a validator broke a slashing rule, carrying both signed
+/// messages that conflict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlashableOffense {
+    /// Two different blocks proposed for the same slot
+    DoubleProposal {
+        first: Proposal,
+        second: Proposal,
+    },
+    /// Two different attestations for the same target epoch
+    DoubleVote {
+        first: Attestation,
+        second: Attestation,
+    },
+    /// An attestation whose source to target span encloses another's
+    SurroundVote {
+        surrounding: Attestation,
+        surrounded: Attestation,
+    },
+}
+
+impl SlashableOffense {
+    /// Validator to slash
+    pub fn offender(&self) -> Address {
+        match self {
+            SlashableOffense::DoubleProposal { first, .. } => first.proposer,
+            SlashableOffense::DoubleVote { first, .. } => first.validator,
+            SlashableOffense::SurroundVote { surrounding, .. } => surrounding.validator,
+        }
+    }
+}
+
+/// Watches the proposals and attestations seen on the network for
+/// equivocations and surround votes
+///
+/// Every distinct message is kept per validator until `prune` drops the
+/// epochs that can no longer be slashed for.
+#[derive(Debug, Default)]
+pub struct SlashingDetector {
+    proposals: HashMap<(Address, u64), Proposal>,
+    attestations: HashMap<Address, Vec<Attestation>>,
+}
+
+impl SlashingDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `proposal`, returning the offense if its proposer already
+    /// proposed a different block for the slot
+    pub fn observe_proposal(&mut self, proposal: Proposal) -> Option<SlashableOffense> {
+        match self.proposals.get(&(proposal.proposer, proposal.slot)) {
+            Some(first) if first.block_root != proposal.block_root => Some(SlashableOffense::DoubleProposal {
+                first: first.clone(),
+                second: proposal,
+            }),
+            Some(_) => None,
+            None => {
+                self.proposals.insert((proposal.proposer, proposal.slot), proposal);
+                None
+            }
+        }
+    }
+
+    /// Record `attestation`, returning every offense it commits against the
+    /// validator's earlier attestations
+    pub fn observe_attestation(&mut self, attestation: Attestation) -> Vec<SlashableOffense> {
+        let history = self.attestations.entry(attestation.validator).or_default();
+        if history.contains(&attestation) {
+            return Vec::new();
+        }
+
+        let mut offenses = Vec::new();
+        for earlier in history.iter() {
+            if earlier.is_double_vote(&attestation) {
+                offenses.push(SlashableOffense::DoubleVote {
+                    first: earlier.clone(),
+                    second: attestation.clone(),
+                });
+            } else if earlier.surrounds(&attestation) {
+                offenses.push(SlashableOffense::SurroundVote {
+                    surrounding: earlier.clone(),
+                    surrounded: attestation.clone(),
+                });
+            } else if attestation.surrounds(earlier) {
+                offenses.push(SlashableOffense::SurroundVote {
+                    surrounding: attestation.clone(),
+                    surrounded: earlier.clone(),
+                });
+            }
+        }
+
+        history.push(attestation);
+        offenses
+    }
+
+    /// Forget proposals before `slot` and attestations targeting epochs
+    /// before `epoch`
+    pub fn prune(&mut self, slot: u64, epoch: u64) {
+        self.proposals.retain(|(_, proposal_slot), _| *proposal_slot >= slot);
+        self.attestations.retain(|_, history| {
+            history.retain(|attestation| attestation.target.epoch >= epoch);
+            !history.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attestation(validator: u8, source: u64, target: u64, root: u8) -> Attestation {
+        Attestation {
+            slot: target * 32,
+            validator: Address::from([validator; 20]),
+            beacon_block_root: H256::from([root; 32]),
+            source: AttestationCheckpoint::new(source, H256::from([source as u8; 32])),
+            target: AttestationCheckpoint::new(target, H256::from([root; 32])),
+            signature: Signature::new(H256::zero(), H256::zero(), 0),
+        }
+    }
+
+    #[test]
+    fn test_double_vote_detected() {
+        let mut detector = SlashingDetector::new();
+        let first = attestation(1, 2, 3, 0xaa);
+        let second = attestation(1, 2, 3, 0xbb);
+
+        assert!(detector.observe_attestation(first.clone()).is_empty());
+        let offenses = detector.observe_attestation(second.clone());
+        assert_eq!(offenses, vec![SlashableOffense::DoubleVote { first, second }]);
+        assert_eq!(offenses[0].offender(), Address::from([1u8; 20]));
+    }
+
+    #[test]
+    fn test_surround_vote_detected() {
+        let mut detector = SlashingDetector::new();
+        let inner = attestation(1, 3, 4, 0xaa);
+        let outer = attestation(1, 2, 5, 0xbb);
+
+        assert!(detector.observe_attestation(inner.clone()).is_empty());
+        assert_eq!(
+            detector.observe_attestation(outer.clone()),
+            vec![SlashableOffense::SurroundVote { surrounding: outer, surrounded: inner }]
+        );
+    }
+
+    #[test]
+    fn test_consistent_votes_are_not_slashable() {
+        let mut detector = SlashingDetector::new();
+
+        // Consecutive links, a repeated message and another validator's
+        // conflicting vote
+        assert!(detector.observe_attestation(attestation(1, 2, 3, 0xaa)).is_empty());
+        assert!(detector.observe_attestation(attestation(1, 3, 4, 0xaa)).is_empty());
+        assert!(detector.observe_attestation(attestation(1, 3, 4, 0xaa)).is_empty());
+        assert!(detector.observe_attestation(attestation(2, 2, 3, 0xbb)).is_empty());
+    }
+
+    #[test]
+    fn test_double_proposal_detected() {
+        let mut detector = SlashingDetector::new();
+        let proposal = |root: u8| Proposal {
+            slot: 7,
+            proposer: Address::from([1u8; 20]),
+            block_root: H256::from([root; 32]),
+            signature: Signature::new(H256::zero(), H256::zero(), 0),
+        };
+
+        assert!(detector.observe_proposal(proposal(0xaa)).is_none());
+        assert!(detector.observe_proposal(proposal(0xaa)).is_none());
+        assert_eq!(
+            detector.observe_proposal(proposal(0xbb)),
+            Some(SlashableOffense::DoubleProposal { first: proposal(0xaa), second: proposal(0xbb) })
+        );
+
+        // Pruned slots are forgotten
+        detector.prune(8, 0);
+        assert!(detector.observe_proposal(proposal(0xbb)).is_none());
+    }
+}
@@ -80,6 +80,23 @@ impl<D: Database> BlockValidator<D> {
         Ok(())
     }
     
+    /// Parent of `header` from the database, `None` for genesis
+    pub fn parent_header(&self, header: &Header) -> Result<Option<Header>> {
+        if header.number.is_zero() {
+            return Ok(None);
+        }
+        
+        let parent_key = format!("header:{}", hex::encode(header.parent_hash));
+        match self.db.get(parent_key.as_bytes())? {
+            Some(data) => bincode::deserialize(&data)
+                .map(Some)
+                .map_err(|e| ConsensusError::InvalidBlock(format!("Invalid parent header: {}", e))),
+            None => Err(ConsensusError::InvalidBlock(
+                "Parent block not found".to_string()
+            )),
+        }
+    }
+    
     /// Validate block body
     fn validate_body(&self, block: &Block) -> Result<()> {
         // Calculate transaction root
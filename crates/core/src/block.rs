@@ -4,6 +4,30 @@ use ethereum_types::{Address, Bloom, H256, U256};
 use ethereum_rlp::{Decode, Decoder, Encode, Encoder, RlpError};
 use serde::{Deserialize, Serialize};
 
+/// EIP-1559 bound on the base fee change between consecutive blocks
+pub const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+
+/// Gas target is the gas limit divided by this
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+
+/// Base fee of the block following `parent`, `None` before London
+pub fn next_base_fee(parent: &Header) -> Option<U256> {
+    let parent_base_fee = parent.base_fee_per_gas?;
+    let gas_target = parent.gas_limit / ELASTICITY_MULTIPLIER;
+    if gas_target.is_zero() || parent.gas_used == gas_target {
+        return Some(parent_base_fee);
+    }
+
+    let denominator = gas_target * BASE_FEE_MAX_CHANGE_DENOMINATOR;
+    if parent.gas_used > gas_target {
+        let delta = parent_base_fee * (parent.gas_used - gas_target) / denominator;
+        Some(parent_base_fee + delta.max(U256::one()))
+    } else {
+        let delta = parent_base_fee * (gas_target - parent.gas_used) / denominator;
+        Some(parent_base_fee.saturating_sub(delta))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Header {
     pub parent_hash: H256,
@@ -99,7 +123,8 @@ impl Encode for Header {
         self.timestamp.encode(&mut list_encoder);
         self.extra_data.encode(&mut list_encoder);
         self.mix_hash.encode(&mut list_encoder);
-        self.nonce.encode(&mut list_encoder);
+        // The seal nonce is a fixed 8-byte string, not a scalar
+        list_encoder.encode_bytes(&self.nonce.to_be_bytes());
         
         // Optional fields
         if let Some(base_fee) = &self.base_fee_per_gas {
@@ -124,36 +149,7 @@ impl Encode for Header {
         
         let list_bytes = list_encoder.finish();
         
-        // Encode as RLP list
-        match list_bytes.len() {
-            len if len < 56 => {
-                encoder.encode_bytes(&[0xc0 + len as u8]);
-                encoder.encode_bytes(&list_bytes);
-            }
-            len => {
-                let len_bytes = encode_length(len);
-                encoder.encode_bytes(&[0xf7 + len_bytes.len() as u8]);
-                encoder.encode_bytes(&len_bytes);
-                encoder.encode_bytes(&list_bytes);
-            }
-        }
-    }
-}
-
-fn encode_length(len: usize) -> Vec<u8> {
-    if len < 256 {
-        vec![len as u8]
-    } else if len < 65536 {
-        vec![(len >> 8) as u8, len as u8]
-    } else if len < 16777216 {
-        vec![(len >> 16) as u8, (len >> 8) as u8, len as u8]
-    } else {
-        vec![
-            (len >> 24) as u8,
-            (len >> 16) as u8,
-            (len >> 8) as u8,
-            len as u8,
-        ]
+        encoder.encode_list_payload(&list_bytes);
     }
 }
 
@@ -218,7 +214,15 @@ impl Decode for Header {
         let timestamp = list.decode()?;
         let extra_data = list.decode()?;
         let mix_hash = list.decode()?;
-        let nonce = list.decode()?;
+        let nonce_bytes: Vec<u8> = list.decode()?;
+        if nonce_bytes.len() > 8 {
+            return Err(RlpError::Decoder(ethereum_rlp::DecoderError::InvalidData(
+                "Header nonce longer than 8 bytes".to_string()
+            )));
+        }
+        let mut nonce_padded = [0u8; 8];
+        nonce_padded[8 - nonce_bytes.len()..].copy_from_slice(&nonce_bytes);
+        let nonce = u64::from_be_bytes(nonce_padded);
         
         let base_fee_per_gas = if !list.is_finished() {
             Some(list.decode()?)
@@ -302,19 +306,7 @@ impl Encode for Withdrawal {
         
         let list_bytes = list_encoder.finish();
         
-        // Encode as RLP list
-        match list_bytes.len() {
-            len if len < 56 => {
-                encoder.encode_bytes(&[0xc0 + len as u8]);
-                encoder.encode_bytes(&list_bytes);
-            }
-            len => {
-                let len_bytes = encode_length(len);
-                encoder.encode_bytes(&[0xf7 + len_bytes.len() as u8]);
-                encoder.encode_bytes(&len_bytes);
-                encoder.encode_bytes(&list_bytes);
-            }
-        }
+        encoder.encode_list_payload(&list_bytes);
     }
 }
 
@@ -386,19 +378,7 @@ impl Encode for Block {
         
         let list_bytes = list_encoder.finish();
         
-        // Encode as RLP list
-        match list_bytes.len() {
-            len if len < 56 => {
-                encoder.encode_bytes(&[0xc0 + len as u8]);
-                encoder.encode_bytes(&list_bytes);
-            }
-            len => {
-                let len_bytes = encode_length(len);
-                encoder.encode_bytes(&[0xf7 + len_bytes.len() as u8]);
-                encoder.encode_bytes(&len_bytes);
-                encoder.encode_bytes(&list_bytes);
-            }
-        }
+        encoder.encode_list_payload(&list_bytes);
     }
 }
 
@@ -410,18 +390,7 @@ fn encode_vec<T: Encode>(items: &[T], encoder: &mut Encoder) {
     }
     let list_bytes = list_encoder.finish();
     
-    match list_bytes.len() {
-        len if len < 56 => {
-            encoder.encode_bytes(&[0xc0 + len as u8]);
-            encoder.encode_bytes(&list_bytes);
-        }
-        len => {
-            let len_bytes = encode_length(len);
-            encoder.encode_bytes(&[0xf7 + len_bytes.len() as u8]);
-            encoder.encode_bytes(&len_bytes);
-            encoder.encode_bytes(&list_bytes);
-        }
-    }
+    encoder.encode_list_payload(&list_bytes);
 }
 
 impl Decode for Block {
@@ -520,18 +489,7 @@ fn encode_rlp_item(item: &ethereum_rlp::RlpItem, encoder: &mut Encoder) {
             }
             let list_bytes = list_encoder.finish();
             
-            match list_bytes.len() {
-                len if len < 56 => {
-                    encoder.encode_bytes(&[0xc0 + len as u8]);
-                    encoder.encode_bytes(&list_bytes);
-                }
-                len => {
-                    let len_bytes = encode_length(len);
-                    encoder.encode_bytes(&[0xf7 + len_bytes.len() as u8]);
-                    encoder.encode_bytes(&len_bytes);
-                    encoder.encode_bytes(&list_bytes);
-                }
-            }
+            encoder.encode_list_payload(&list_bytes);
         }
     }
 }
@@ -631,4 +589,19 @@ mod tests {
         
         assert_eq!(withdrawal, decoded);
     }
+    
+    #[test]
+    fn test_withdrawal_encoding() {
+        let withdrawal = Withdrawal {
+            index: 0,
+            validator_index: 1,
+            address: Address::from_bytes([0x11; 20]),
+            amount: 1_000_000_000,
+        };
+        
+        assert_eq!(
+            hex::encode(ethereum_rlp::encode(&withdrawal).as_slice()),
+            "dc8001941111111111111111111111111111111111111111843b9aca00"
+        );
+    }
 }
\ No newline at end of file
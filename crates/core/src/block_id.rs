@@ -0,0 +1,47 @@
+use ethereum_types::{H256, U256};
+use serde::{Deserialize, Serialize};
+
+/// Block number or tag, as the JSON-RPC APIs take it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BlockNumber {
+    #[default]
+    Latest,
+    Earliest,
+    Pending,
+    Finalized,
+    Safe,
+    Number(U256),
+}
+
+/// Block selector accepted by state-reading methods: a tag/number or an
+/// EIP-1898 `{ "blockHash": ..., "requireCanonical": ... }` object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BlockId {
+    #[serde(rename_all = "camelCase")]
+    Hash {
+        block_hash: H256,
+        #[serde(default)]
+        require_canonical: bool,
+    },
+    Number(BlockNumber),
+}
+
+impl Default for BlockId {
+    fn default() -> Self {
+        BlockId::Number(BlockNumber::Latest)
+    }
+}
+
+impl From<BlockNumber> for BlockId {
+    fn from(number: BlockNumber) -> Self {
+        BlockId::Number(number)
+    }
+}
+
+impl From<H256> for BlockId {
+    fn from(block_hash: H256) -> Self {
+        BlockId::Hash { block_hash, require_canonical: false }
+    }
+}
@@ -1,5 +1,7 @@
-use ethereum_types::{H256, U256, U64};
+use ethereum_types::{Bytes, H256, U256, U64};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use thiserror::Error;
 
 /// EIP-7691: Blob throughput increase
@@ -19,6 +21,13 @@ pub const MAX_BLOBS_PER_BLOCK: u64 = 9;
 // Blob pricing constants
 pub const MIN_BLOB_BASE_FEE: u64 = 1;
 pub const BLOB_BASE_FEE_UPDATE_FRACTION: u64 = 3338477;
+pub const BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE: u64 = 5007716;
+
+// Blob sidecar constants (EIP-4844)
+pub const BYTES_PER_BLOB: usize = 131072;              // 4096 field elements of 32 bytes
+pub const BYTES_PER_COMMITMENT: usize = 48;
+pub const BYTES_PER_PROOF: usize = 48;
+pub const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
 
 #[derive(Debug, Error)]
 pub enum Eip7691Error {
@@ -33,6 +42,12 @@ pub enum Eip7691Error {
     
     #[error("Invalid blob versioned hash")]
     InvalidVersionedHash,
+    
+    #[error("Invalid blob sidecar: {0}")]
+    InvalidSidecar(String),
+    
+    #[error("Blob transaction {0:?} is already pooled")]
+    AlreadyPooled(H256),
 }
 
 pub type Result<T> = std::result::Result<T, Eip7691Error>;
@@ -66,7 +81,7 @@ impl BlobGasConfig {
             max_blob_gas_per_block: MAX_BLOB_GAS_PER_BLOCK,
             blob_gas_per_blob: BLOB_GAS_PER_BLOB,
             min_blob_base_fee: MIN_BLOB_BASE_FEE,
-            blob_base_fee_update_fraction: BLOB_BASE_FEE_UPDATE_FRACTION,
+            blob_base_fee_update_fraction: BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE,
         }
     }
     
@@ -79,6 +94,39 @@ impl BlobGasConfig {
     }
 }
 
+/// Activation times of the forks that set the blob parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobSchedule {
+    /// EIP-4844 blobs, with the pre-EIP-7691 limits
+    pub cancun_time: Option<u64>,
+    /// EIP-7691 limits
+    pub prague_time: Option<u64>,
+}
+
+impl BlobSchedule {
+    /// Blob parameters of a block at `timestamp`, `None` before Cancun
+    pub fn config_at(&self, timestamp: u64) -> Option<BlobGasConfig> {
+        let active = |at: Option<u64>| at.map_or(false, |at| timestamp >= at);
+        if active(self.prague_time) {
+            Some(BlobGasConfig::post_7691())
+        } else if active(self.cancun_time) {
+            Some(BlobGasConfig::pre_7691())
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for BlobSchedule {
+    /// Every blob fork active from genesis
+    fn default() -> Self {
+        Self {
+            cancun_time: Some(0),
+            prague_time: Some(0),
+        }
+    }
+}
+
 /// Calculate excess blob gas for the next block
 pub fn calculate_excess_blob_gas(
     parent_excess_blob_gas: u64,
@@ -189,6 +237,82 @@ impl BlobTransactionData {
     }
 }
 
+/// Versioned hash committing to a KZG commitment:
+/// `VERSIONED_HASH_VERSION_KZG` followed by the tail of its SHA-256
+pub fn kzg_to_versioned_hash(commitment: &[u8]) -> H256 {
+    let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    H256::from(hash)
+}
+
+/// One blob with its KZG commitment and proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobAndProof {
+    pub blob: Bytes,
+    pub commitment: Bytes,
+    pub proof: Bytes,
+}
+
+/// Blobs of a 4844 transaction, gossiped and put in the payload alongside
+/// the transaction but not part of its signed body
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobSidecar {
+    pub blobs: Vec<Bytes>,
+    pub commitments: Vec<Bytes>,
+    pub proofs: Vec<Bytes>,
+}
+
+impl BlobSidecar {
+    /// Versioned hashes of the commitments, in order
+    pub fn versioned_hashes(&self) -> Vec<H256> {
+        self.commitments.iter().map(|commitment| kzg_to_versioned_hash(commitment)).collect()
+    }
+    
+    /// Check the sidecar holds one well-sized blob, commitment and proof per
+    /// versioned hash of the transaction and that each commitment hashes to
+    /// the versioned hash at its position
+    ///
+    /// The KZG proofs themselves are not verified here, that needs the
+    /// trusted setup.
+    pub fn validate(&self, versioned_hashes: &[H256]) -> Result<()> {
+        let count = versioned_hashes.len();
+        if self.blobs.len() != count || self.commitments.len() != count || self.proofs.len() != count {
+            return Err(Eip7691Error::InvalidSidecar(format!(
+                "{} blobs, {} commitments and {} proofs for {} versioned hashes",
+                self.blobs.len(), self.commitments.len(), self.proofs.len(), count
+            )));
+        }
+        
+        for (index, expected) in versioned_hashes.iter().enumerate() {
+            if self.blobs[index].len() != BYTES_PER_BLOB {
+                return Err(Eip7691Error::InvalidSidecar(format!(
+                    "blob {} is {} bytes", index, self.blobs[index].len()
+                )));
+            }
+            if self.commitments[index].len() != BYTES_PER_COMMITMENT || self.proofs[index].len() != BYTES_PER_PROOF {
+                return Err(Eip7691Error::InvalidSidecar(format!(
+                    "commitment or proof {} is not {} bytes", index, BYTES_PER_COMMITMENT
+                )));
+            }
+            if kzg_to_versioned_hash(&self.commitments[index]) != *expected {
+                return Err(Eip7691Error::InvalidSidecar(format!(
+                    "commitment {} does not match versioned hash {:?}", index, expected
+                )));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn into_entries(self) -> impl Iterator<Item = BlobAndProof> {
+        self.blobs
+            .into_iter()
+            .zip(self.commitments)
+            .zip(self.proofs)
+            .map(|((blob, commitment), proof)| BlobAndProof { blob, commitment, proof })
+    }
+}
+
 /// Block header extensions for EIP-7691
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BlobGasInfo {
@@ -232,8 +356,12 @@ impl BlobGasInfo {
 }
 
 /// Blob pool for managing pending blob transactions
+///
+/// Sidecars submitted with a transaction are kept per versioned hash until
+/// no transaction in the pool refers to the blob any more.
 pub struct BlobPool {
     transactions: Vec<(H256, BlobTransactionData, U256)>, // (tx_hash, blob_data, priority_fee)
+    sidecars: HashMap<H256, BlobAndProof>,
     config: BlobGasConfig,
     max_pool_size: usize,
 }
@@ -242,11 +370,63 @@ impl BlobPool {
     pub fn new(config: BlobGasConfig, max_pool_size: usize) -> Self {
         Self {
             transactions: Vec::new(),
+            sidecars: HashMap::new(),
             config,
             max_pool_size,
         }
     }
     
+    /// Add a transaction together with its sidecar, which has to match the
+    /// transaction's versioned hashes
+    ///
+    /// A transaction already in the pool is rejected, so of two imports of
+    /// the same transaction only one stores it. Blobs another pooled
+    /// transaction already brought are kept as they are.
+    pub fn add_transaction_with_sidecar(
+        &mut self,
+        tx_hash: H256,
+        blob_data: BlobTransactionData,
+        priority_fee: U256,
+        sidecar: BlobSidecar,
+    ) -> Result<()> {
+        sidecar.validate(&blob_data.blob_versioned_hashes)?;
+        if self.contains(&tx_hash) {
+            return Err(Eip7691Error::AlreadyPooled(tx_hash));
+        }
+        
+        let versioned_hashes = blob_data.blob_versioned_hashes.clone();
+        self.add_transaction(tx_hash, blob_data, priority_fee)?;
+        for (hash, entry) in versioned_hashes.into_iter().zip(sidecar.into_entries()) {
+            self.sidecars.entry(hash).or_insert(entry);
+        }
+        
+        Ok(())
+    }
+    
+    pub fn contains(&self, tx_hash: &H256) -> bool {
+        self.transactions.iter().any(|(h, _, _)| h == tx_hash)
+    }
+    
+    /// Blob, commitment and proof stored for `versioned_hash`
+    pub fn get_blob(&self, versioned_hash: &H256) -> Option<&BlobAndProof> {
+        self.sidecars.get(versioned_hash)
+    }
+    
+    /// Full sidecar of a pooled transaction, `None` unless every one of its
+    /// blobs is available
+    pub fn get_sidecar(&self, tx_hash: &H256) -> Option<BlobSidecar> {
+        let (_, blob_data, _) = self.transactions.iter().find(|(h, _, _)| h == tx_hash)?;
+        
+        let mut sidecar = BlobSidecar::default();
+        for hash in &blob_data.blob_versioned_hashes {
+            let entry = self.sidecars.get(hash)?;
+            sidecar.blobs.push(entry.blob.clone());
+            sidecar.commitments.push(entry.commitment.clone());
+            sidecar.proofs.push(entry.proof.clone());
+        }
+        Some(sidecar)
+    }
+    
     pub fn add_transaction(
         &mut self,
         tx_hash: H256,
@@ -294,19 +474,38 @@ impl BlobPool {
     pub fn remove_transaction(&mut self, tx_hash: &H256) -> Option<BlobTransactionData> {
         if let Some(pos) = self.transactions.iter().position(|(h, _, _)| h == tx_hash) {
             let (_, blob_data, _) = self.transactions.remove(pos);
+            self.release_sidecars(&blob_data);
             Some(blob_data)
         } else {
             None
         }
     }
     
+    pub fn clear(&mut self) {
+        self.transactions.clear();
+        self.sidecars.clear();
+    }
+    
+    /// Drop the blobs of a removed transaction that no other pooled
+    /// transaction refers to
+    fn release_sidecars(&mut self, blob_data: &BlobTransactionData) {
+        for hash in &blob_data.blob_versioned_hashes {
+            let shared = self.transactions
+                .iter()
+                .any(|(_, other, _)| other.blob_versioned_hashes.contains(hash));
+            if !shared {
+                self.sidecars.remove(hash);
+            }
+        }
+    }
+    
     fn sort_by_priority(&mut self) {
         self.transactions.sort_by(|a, b| b.2.cmp(&a.2));
     }
     
     fn evict_lowest_priority(&mut self) {
-        if !self.transactions.is_empty() {
-            self.transactions.pop();
+        if let Some((_, blob_data, _)) = self.transactions.pop() {
+            self.release_sidecars(&blob_data);
         }
     }
     
@@ -355,6 +554,15 @@ mod tests {
         assert_eq!(new_config.max_blobs_per_block(), 9);
     }
     
+    #[test]
+    fn test_blob_schedule() {
+        let schedule = BlobSchedule { cancun_time: Some(100), prague_time: Some(200) };
+        assert_eq!(schedule.config_at(99), None);
+        assert_eq!(schedule.config_at(100), Some(BlobGasConfig::pre_7691()));
+        assert_eq!(schedule.config_at(200), Some(BlobGasConfig::post_7691()));
+        assert_eq!(BlobSchedule::default().config_at(0), Some(BlobGasConfig::post_7691()));
+    }
+    
     #[test]
     fn test_excess_blob_gas_calculation() {
         let config = BlobGasConfig::post_7691();
@@ -405,6 +613,61 @@ mod tests {
         assert_eq!(pool.total_blobs(), 1);
     }
     
+    /// Sidecar of `count` zero blobs with distinct commitments
+    fn sidecar(count: u8) -> BlobSidecar {
+        BlobSidecar {
+            blobs: (0..count).map(|_| Bytes::from_vec(vec![0; BYTES_PER_BLOB])).collect(),
+            commitments: (0..count).map(|i| Bytes::from_vec(vec![0xc0 + i; BYTES_PER_COMMITMENT])).collect(),
+            proofs: (0..count).map(|_| Bytes::from_vec(vec![0xc0; BYTES_PER_PROOF])).collect(),
+        }
+    }
+    
+    #[test]
+    fn test_sidecar_validation() {
+        let sidecar = sidecar(2);
+        let hashes = sidecar.versioned_hashes();
+        assert!(hashes.iter().all(|hash| hash.as_bytes()[0] == VERSIONED_HASH_VERSION_KZG));
+        sidecar.validate(&hashes).unwrap();
+        
+        // Commitments out of order no longer match their versioned hashes
+        let swapped = vec![hashes[1], hashes[0]];
+        assert!(matches!(sidecar.validate(&swapped), Err(Eip7691Error::InvalidSidecar(_))));
+        assert!(matches!(sidecar.validate(&hashes[..1]), Err(Eip7691Error::InvalidSidecar(_))));
+        
+        let mut short_blob = sidecar.clone();
+        short_blob.blobs[0] = Bytes::from_vec(vec![0; BYTES_PER_BLOB - 1]);
+        assert!(matches!(short_blob.validate(&hashes), Err(Eip7691Error::InvalidSidecar(_))));
+    }
+    
+    #[test]
+    fn test_blob_pool_sidecars() {
+        let mut pool = BlobPool::new(BlobGasConfig::post_7691(), 100);
+        let sidecar = sidecar(2);
+        let hashes = sidecar.versioned_hashes();
+        let blob_data = BlobTransactionData::new(hashes.clone(), U256::from(1_000_000_000u64)).unwrap();
+        let tx_hash = H256::from([1u8; 32]);
+        
+        pool.add_transaction_with_sidecar(tx_hash, blob_data.clone(), U256::one(), sidecar.clone()).unwrap();
+        assert_eq!(pool.get_sidecar(&tx_hash), Some(sidecar.clone()));
+        assert_eq!(pool.get_blob(&hashes[1]).unwrap().commitment, sidecar.commitments[1]);
+        
+        // A sidecar not matching the transaction is rejected with it
+        let other = H256::from([2u8; 32]);
+        assert!(pool.add_transaction_with_sidecar(other, blob_data.clone(), U256::one(), BlobSidecar::default()).is_err());
+        assert_eq!(pool.size(), 1);
+        
+        // So is a second copy of a pooled transaction
+        assert!(matches!(
+            pool.add_transaction_with_sidecar(tx_hash, blob_data, U256::one(), sidecar),
+            Err(Eip7691Error::AlreadyPooled(_))
+        ));
+        assert_eq!(pool.size(), 1);
+        
+        pool.remove_transaction(&tx_hash).unwrap();
+        assert!(pool.get_blob(&hashes[0]).is_none());
+        assert!(pool.get_sidecar(&tx_hash).is_none());
+    }
+    
     #[test]
     fn test_migration() {
         let (new_excess, new_used) = migrate_blob_gas_at_fork(393216, 393216);
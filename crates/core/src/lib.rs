@@ -1,12 +1,18 @@
 pub mod block;
+pub mod receipt;
 pub mod transaction;
 pub mod eip7702;
 pub mod eip7691;
+pub mod rpc_transaction;
+pub mod block_id;
 
-pub use block::{Block, Header, Withdrawal};
+pub use block::{next_base_fee, Block, Header, Withdrawal};
+pub use receipt::{Log, Receipt};
 pub use transaction::{
     AccessListItem, Eip1559Transaction, Eip2930Transaction, Eip4844Transaction,
-    LegacyTransaction, Transaction, TransactionError,
+    LegacyTransaction, Transaction, TransactionError, MAX_INIT_CODE_SIZE,
 };
 pub use eip7702::{Authorization, Eip7702Transaction, DelegatedAccount};
-pub use eip7691::{BlobGasConfig, BlobGasInfo, BlobTransactionData, BlobPool};
+pub use rpc_transaction::{RpcAuthorization, RpcTransaction};
+pub use block_id::{BlockId, BlockNumber};
+pub use eip7691::{BlobAndProof, BlobGasConfig, BlobGasInfo, BlobPool, BlobSchedule, BlobSidecar, BlobTransactionData};
@@ -0,0 +1,244 @@
+use ethereum_types::{Address, Bloom, H256, U256};
+use ethereum_rlp::{Decode, Decoder, Encode, Encoder, RlpError, RlpItem};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Log {
+    pub address: Address,
+    pub topics: Vec<H256>,
+    pub data: Vec<u8>,
+    // Position of the log in the chain, filled in when served over RPC
+    pub block_hash: Option<H256>,
+    pub block_number: Option<U256>,
+    pub transaction_hash: Option<H256>,
+    pub transaction_index: Option<U256>,
+    pub log_index: Option<U256>,
+    /// Set when a reorg took the log's block out of the canonical chain
+    #[serde(default)]
+    pub removed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub tx_type: u8,
+    pub status: u8,
+    pub cumulative_gas_used: U256,
+    pub logs_bloom: Bloom,
+    pub logs: Vec<Log>,
+    pub gas_used: U256,
+    pub contract_address: Option<Address>,
+}
+
+impl Log {
+    pub fn new(address: Address, topics: Vec<H256>, data: Vec<u8>) -> Self {
+        Self {
+            address,
+            topics,
+            data,
+            ..Default::default()
+        }
+    }
+
+    fn to_rlp_item(&self) -> RlpItem {
+        RlpItem::List(vec![
+            RlpItem::String(self.address.as_bytes().to_vec()),
+            RlpItem::List(
+                self.topics
+                    .iter()
+                    .map(|topic| RlpItem::String(topic.as_bytes().to_vec()))
+                    .collect(),
+            ),
+            RlpItem::String(self.data.clone()),
+        ])
+    }
+
+    fn from_rlp_item(item: &RlpItem) -> Result<Self, RlpError> {
+        let fields = expect_list(item, 3, "log")?;
+
+        let address = Address::from_slice(expect_string(&fields[0])?)
+            .map_err(|_| invalid_data("Invalid log address"))?;
+        let topics = match &fields[1] {
+            RlpItem::List(items) => items
+                .iter()
+                .map(|topic| {
+                    let bytes = expect_string(topic)?;
+                    if bytes.len() != 32 {
+                        return Err(invalid_data("Invalid log topic length"));
+                    }
+                    Ok(H256::from_slice(bytes))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(invalid_data("Expected list for log topics")),
+        };
+        let data = expect_string(&fields[2])?.to_vec();
+
+        Ok(Log::new(address, topics, data))
+    }
+}
+
+impl Encode for Log {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.to_rlp_item().encode(encoder);
+    }
+}
+
+impl Decode for Log {
+    fn decode(decoder: &mut Decoder) -> Result<Self, RlpError> {
+        Log::from_rlp_item(&decoder.decode_item()?)
+    }
+}
+
+impl Receipt {
+    /// EIP-2718 receipt envelope: `rlp([status, cumulative_gas_used, logs_bloom, logs])`,
+    /// prefixed with the transaction type byte for typed transactions
+    pub fn encoded_2718(&self) -> Vec<u8> {
+        let payload = ethereum_rlp::encode(self);
+
+        if self.tx_type == 0 {
+            payload.as_slice().to_vec()
+        } else {
+            let mut encoded = Vec::with_capacity(payload.as_slice().len() + 1);
+            encoded.push(self.tx_type);
+            encoded.extend_from_slice(payload.as_slice());
+            encoded
+        }
+    }
+
+    /// Decode an EIP-2718 receipt envelope
+    pub fn decode_2718(data: &[u8]) -> Result<Self, RlpError> {
+        match data.first() {
+            None => Err(invalid_data("Empty receipt")),
+            // A legacy receipt always starts with a list prefix
+            Some(&first) if first >= 0xc0 => ethereum_rlp::decode(data),
+            Some(&tx_type) => {
+                let mut receipt: Receipt = ethereum_rlp::decode(&data[1..])?;
+                receipt.tx_type = tx_type;
+                Ok(receipt)
+            }
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == 1
+    }
+}
+
+impl Encode for Receipt {
+    fn encode(&self, encoder: &mut Encoder) {
+        RlpItem::List(vec![
+            uint_item(U256::from(self.status)),
+            uint_item(self.cumulative_gas_used),
+            RlpItem::String(self.logs_bloom.as_bytes().to_vec()),
+            RlpItem::List(self.logs.iter().map(Log::to_rlp_item).collect()),
+        ])
+        .encode(encoder);
+    }
+}
+
+impl Decode for Receipt {
+    fn decode(decoder: &mut Decoder) -> Result<Self, RlpError> {
+        let item = decoder.decode_item()?;
+        let fields = expect_list(&item, 4, "receipt")?;
+
+        let status: u8 = ethereum_rlp::decode(&ethereum_rlp::encode(&fields[0]))?;
+        let cumulative_gas_used: U256 = ethereum_rlp::decode(&ethereum_rlp::encode(&fields[1]))?;
+        let logs_bloom: Bloom = ethereum_rlp::decode(&ethereum_rlp::encode(&fields[2]))?;
+        let logs = match &fields[3] {
+            RlpItem::List(items) => items
+                .iter()
+                .map(Log::from_rlp_item)
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(invalid_data("Expected list for receipt logs")),
+        };
+
+        Ok(Receipt {
+            tx_type: 0,
+            status,
+            cumulative_gas_used,
+            logs_bloom,
+            logs,
+            gas_used: U256::zero(),
+            contract_address: None,
+        })
+    }
+}
+
+fn uint_item(value: U256) -> RlpItem {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    let first_non_zero = bytes.iter().position(|&b| b != 0).unwrap_or(32);
+    RlpItem::String(bytes[first_non_zero..].to_vec())
+}
+
+fn expect_list<'a>(item: &'a RlpItem, len: usize, what: &str) -> Result<&'a [RlpItem], RlpError> {
+    match item {
+        RlpItem::List(items) if items.len() == len => Ok(items),
+        _ => Err(invalid_data(&format!("Expected {}-item list for {}", len, what))),
+    }
+}
+
+fn expect_string(item: &RlpItem) -> Result<&[u8], RlpError> {
+    item.as_bytes().ok_or_else(|| invalid_data("Expected string"))
+}
+
+fn invalid_data(message: &str) -> RlpError {
+    RlpError::Decoder(ethereum_rlp::DecoderError::InvalidData(message.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_receipt(tx_type: u8) -> Receipt {
+        Receipt {
+            tx_type,
+            status: 1,
+            cumulative_gas_used: U256::from(21000),
+            logs_bloom: Bloom::ZERO,
+            logs: vec![],
+            gas_used: U256::from(21000),
+            contract_address: None,
+        }
+    }
+
+    #[test]
+    fn test_legacy_receipt_encoding() {
+        let encoded = sample_receipt(0).encoded_2718();
+
+        // list(status, cumulative gas, 256-byte bloom, empty logs) = 264 byte payload
+        assert_eq!(&encoded[..3], &[0xf9, 0x01, 0x08]);
+        assert_eq!(encoded[3], 0x01);
+        assert_eq!(&encoded[4..7], &[0x82, 0x52, 0x08]);
+        assert_eq!(&encoded[7..10], &[0xb9, 0x01, 0x00]);
+        assert_eq!(encoded[encoded.len() - 1], 0xc0);
+        assert_eq!(encoded.len(), 267);
+    }
+
+    #[test]
+    fn test_typed_receipt_encoding() {
+        let legacy = sample_receipt(0).encoded_2718();
+        let typed = sample_receipt(2).encoded_2718();
+
+        assert_eq!(typed[0], 0x02);
+        assert_eq!(&typed[1..], &legacy[..]);
+    }
+
+    #[test]
+    fn test_receipt_roundtrip() {
+        let mut receipt = sample_receipt(2);
+        receipt.logs.push(Log::new(
+            Address::from_bytes([0x11; 20]),
+            vec![H256::from_low_u64_be(1), H256::from_low_u64_be(2)],
+            vec![0xde, 0xad, 0xbe, 0xef],
+        ));
+
+        let decoded = Receipt::decode_2718(&receipt.encoded_2718()).unwrap();
+        assert_eq!(decoded.tx_type, 2);
+        assert_eq!(decoded.status, receipt.status);
+        assert_eq!(decoded.cumulative_gas_used, receipt.cumulative_gas_used);
+        assert_eq!(decoded.logs, receipt.logs);
+
+        let legacy = sample_receipt(0);
+        assert_eq!(Receipt::decode_2718(&legacy.encoded_2718()).unwrap().tx_type, 0);
+    }
+}
@@ -0,0 +1,516 @@
+use ethereum_types::{Address, Bytes, H256, U256};
+use serde::{Deserialize, Serialize};
+
+use crate::eip7702::{Authorization, Eip7702Transaction};
+use crate::transaction::{
+    AccessListItem, Eip1559Transaction, Eip2930Transaction, Eip4844Transaction,
+    LegacyTransaction, Result, Transaction, TransactionError,
+};
+
+/// Transaction object as served and accepted by the eth JSON-RPC API
+///
+/// `Transaction` derives serde for storage, which tags the variant and
+/// writes raw numbers; this is the flat object from the execution-apis
+/// spec, with `type` as a quantity and only the fields of that type set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTransaction {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub tx_type: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<U256>,
+    pub nonce: U256,
+    #[serde(default)]
+    pub to: Option<Address>,
+    pub gas: U256,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_price: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_blob_gas: Option<U256>,
+    #[serde(default)]
+    pub value: U256,
+    #[serde(alias = "data", default, with = "hex_bytes")]
+    pub input: Bytes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_list: Option<Vec<AccessListItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob_versioned_hashes: Option<Vec<H256>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization_list: Option<Vec<RpcAuthorization>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub v: Option<U256>,
+    pub r: U256,
+    pub s: U256,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y_parity: Option<U256>,
+}
+
+/// EIP-7702 authorization tuple as written in `authorizationList`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAuthorization {
+    pub chain_id: U256,
+    pub address: Address,
+    pub nonce: U256,
+    pub y_parity: U256,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl From<&Authorization> for RpcAuthorization {
+    fn from(auth: &Authorization) -> Self {
+        Self {
+            chain_id: U256::from(auth.chain_id),
+            address: auth.address,
+            nonce: auth.nonce,
+            y_parity: U256::from(auth.y_parity as u8),
+            r: auth.r,
+            s: auth.s,
+        }
+    }
+}
+
+impl TryFrom<RpcAuthorization> for Authorization {
+    type Error = TransactionError;
+
+    fn try_from(auth: RpcAuthorization) -> Result<Self> {
+        Ok(Self {
+            chain_id: to_chain_id(auth.chain_id)?,
+            address: auth.address,
+            nonce: auth.nonce,
+            y_parity: to_parity(auth.y_parity)?,
+            r: auth.r,
+            s: auth.s,
+        })
+    }
+}
+
+impl From<&Transaction> for RpcTransaction {
+    fn from(tx: &Transaction) -> Self {
+        let mut rpc = Self {
+            tx_type: Some(U256::from(tx.tx_type())),
+            hash: Some(tx.hash()),
+            from: tx.sender().ok(),
+            chain_id: None,
+            nonce: tx.nonce(),
+            to: tx.to(),
+            gas: tx.gas_limit(),
+            gas_price: None,
+            max_priority_fee_per_gas: None,
+            max_fee_per_gas: None,
+            max_fee_per_blob_gas: None,
+            value: tx.value(),
+            input: tx.data().clone(),
+            access_list: None,
+            blob_versioned_hashes: None,
+            authorization_list: None,
+            v: None,
+            r: U256::zero(),
+            s: U256::zero(),
+            y_parity: None,
+        };
+
+        match tx {
+            Transaction::Legacy(tx) => {
+                rpc.chain_id = tx.chain_id().map(U256::from);
+                rpc.gas_price = Some(tx.gas_price);
+                rpc.v = Some(U256::from(tx.v));
+                rpc.r = tx.r;
+                rpc.s = tx.s;
+            }
+            Transaction::Eip2930(tx) => {
+                rpc.gas_price = Some(tx.gas_price);
+                rpc.set_typed(tx.chain_id, &tx.access_list, tx.y_parity, tx.r, tx.s);
+            }
+            Transaction::Eip1559(tx) => {
+                rpc.set_fees(tx.max_priority_fee_per_gas, tx.max_fee_per_gas);
+                rpc.set_typed(tx.chain_id, &tx.access_list, tx.y_parity, tx.r, tx.s);
+            }
+            Transaction::Eip4844(tx) => {
+                rpc.set_fees(tx.max_priority_fee_per_gas, tx.max_fee_per_gas);
+                rpc.max_fee_per_blob_gas = Some(tx.max_fee_per_blob_gas);
+                rpc.blob_versioned_hashes = Some(tx.blob_versioned_hashes.clone());
+                rpc.set_typed(tx.chain_id, &tx.access_list, tx.y_parity, tx.r, tx.s);
+            }
+            Transaction::Eip7702(tx) => {
+                rpc.set_fees(tx.max_priority_fee_per_gas, tx.max_fee_per_gas);
+                rpc.authorization_list = Some(tx.authorization_list.iter().map(RpcAuthorization::from).collect());
+                rpc.set_typed(tx.chain_id, &tx.access_list, tx.y_parity, tx.r, tx.s);
+            }
+        }
+
+        rpc
+    }
+}
+
+impl From<Transaction> for RpcTransaction {
+    fn from(tx: Transaction) -> Self {
+        Self::from(&tx)
+    }
+}
+
+impl RpcTransaction {
+    fn set_fees(&mut self, max_priority_fee_per_gas: U256, max_fee_per_gas: U256) {
+        self.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+        self.max_fee_per_gas = Some(max_fee_per_gas);
+    }
+
+    /// Fields shared by all typed transactions; `v` repeats `yParity` as
+    /// clients still expect it
+    fn set_typed(&mut self, chain_id: u64, access_list: &[AccessListItem], y_parity: bool, r: U256, s: U256) {
+        self.chain_id = Some(U256::from(chain_id));
+        self.access_list = Some(access_list.to_vec());
+        self.y_parity = Some(U256::from(y_parity as u8));
+        self.v = self.y_parity;
+        self.r = r;
+        self.s = s;
+    }
+
+    /// Transaction type, from `type` or else inferred from the fee and list
+    /// fields present
+    pub fn transaction_type(&self) -> Result<u8> {
+        match self.tx_type {
+            Some(tx_type) if tx_type > U256::from(u8::MAX) => Err(TransactionError::InvalidTransactionType(u8::MAX)),
+            Some(tx_type) => Ok(tx_type.low_u64() as u8),
+            None if self.authorization_list.is_some() => Ok(0x04),
+            None if self.max_fee_per_blob_gas.is_some() || self.blob_versioned_hashes.is_some() => Ok(0x03),
+            None if self.max_fee_per_gas.is_some() => Ok(0x02),
+            None if self.access_list.is_some() => Ok(0x01),
+            None => Ok(0x00),
+        }
+    }
+
+    fn chain_id(&self) -> Result<u64> {
+        to_chain_id(self.chain_id.ok_or(TransactionError::MissingField("chainId"))?)
+    }
+
+    /// `yParity`, falling back to `v` for clients that only send that
+    fn parity(&self) -> Result<bool> {
+        to_parity(self.y_parity.or(self.v).ok_or(TransactionError::MissingField("yParity"))?)
+    }
+
+    fn required_to(&self) -> Result<Address> {
+        self.to.ok_or(TransactionError::MissingField("to"))
+    }
+}
+
+impl TryFrom<RpcTransaction> for Transaction {
+    type Error = TransactionError;
+
+    fn try_from(rpc: RpcTransaction) -> Result<Self> {
+        let field = |value: Option<U256>, name: &'static str| value.ok_or(TransactionError::MissingField(name));
+        let access_list = rpc.access_list.clone().unwrap_or_default();
+
+        let tx = match rpc.transaction_type()? {
+            0x00 => {
+                let v = field(rpc.v, "v")?;
+                if v > U256::from(u64::MAX) {
+                    return Err(TransactionError::InvalidSignature);
+                }
+                Transaction::Legacy(LegacyTransaction {
+                    nonce: rpc.nonce,
+                    gas_price: field(rpc.gas_price, "gasPrice")?,
+                    gas_limit: rpc.gas,
+                    to: rpc.to,
+                    value: rpc.value,
+                    data: rpc.input,
+                    v: v.low_u64(),
+                    r: rpc.r,
+                    s: rpc.s,
+                })
+            }
+            0x01 => Transaction::Eip2930(Eip2930Transaction {
+                chain_id: rpc.chain_id()?,
+                nonce: rpc.nonce,
+                gas_price: field(rpc.gas_price, "gasPrice")?,
+                gas_limit: rpc.gas,
+                to: rpc.to,
+                value: rpc.value,
+                data: rpc.input.clone(),
+                access_list,
+                y_parity: rpc.parity()?,
+                r: rpc.r,
+                s: rpc.s,
+            }),
+            0x02 => Transaction::Eip1559(Eip1559Transaction {
+                chain_id: rpc.chain_id()?,
+                nonce: rpc.nonce,
+                max_priority_fee_per_gas: field(rpc.max_priority_fee_per_gas, "maxPriorityFeePerGas")?,
+                max_fee_per_gas: field(rpc.max_fee_per_gas, "maxFeePerGas")?,
+                gas_limit: rpc.gas,
+                to: rpc.to,
+                value: rpc.value,
+                data: rpc.input.clone(),
+                access_list,
+                y_parity: rpc.parity()?,
+                r: rpc.r,
+                s: rpc.s,
+            }),
+            0x03 => Transaction::Eip4844(Eip4844Transaction {
+                chain_id: rpc.chain_id()?,
+                nonce: rpc.nonce,
+                max_priority_fee_per_gas: field(rpc.max_priority_fee_per_gas, "maxPriorityFeePerGas")?,
+                max_fee_per_gas: field(rpc.max_fee_per_gas, "maxFeePerGas")?,
+                gas_limit: rpc.gas,
+                to: rpc.required_to()?,
+                value: rpc.value,
+                data: rpc.input.clone(),
+                access_list,
+                max_fee_per_blob_gas: field(rpc.max_fee_per_blob_gas, "maxFeePerBlobGas")?,
+                blob_versioned_hashes: rpc.blob_versioned_hashes.clone()
+                    .ok_or(TransactionError::MissingField("blobVersionedHashes"))?,
+                y_parity: rpc.parity()?,
+                r: rpc.r,
+                s: rpc.s,
+            }),
+            0x04 => Transaction::Eip7702(Eip7702Transaction {
+                chain_id: rpc.chain_id()?,
+                nonce: rpc.nonce,
+                max_priority_fee_per_gas: field(rpc.max_priority_fee_per_gas, "maxPriorityFeePerGas")?,
+                max_fee_per_gas: field(rpc.max_fee_per_gas, "maxFeePerGas")?,
+                gas_limit: rpc.gas,
+                to: rpc.required_to()?,
+                value: rpc.value,
+                data: rpc.input.clone(),
+                access_list,
+                authorization_list: rpc.authorization_list.clone()
+                    .ok_or(TransactionError::MissingField("authorizationList"))?
+                    .into_iter()
+                    .map(Authorization::try_from)
+                    .collect::<Result<_>>()?,
+                y_parity: rpc.parity()?,
+                r: rpc.r,
+                s: rpc.s,
+            }),
+            other => return Err(TransactionError::InvalidTransactionType(other)),
+        };
+
+        Ok(tx)
+    }
+}
+
+fn to_chain_id(value: U256) -> Result<u64> {
+    if value > U256::from(u64::MAX) {
+        return Err(TransactionError::InvalidChainId);
+    }
+    Ok(value.low_u64())
+}
+
+fn to_parity(value: U256) -> Result<bool> {
+    match value.low_u64() {
+        0 if value.is_zero() => Ok(false),
+        1 if value == U256::one() => Ok(true),
+        _ => Err(TransactionError::InvalidSignature),
+    }
+}
+
+/// `0x`-prefixed hex for byte strings
+mod hex_bytes {
+    use ethereum_types::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Bytes, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(bytes.as_slice())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Bytes, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s.trim_start_matches("0x"))
+            .map(Bytes::from_vec)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const ADDRESS: &str = "0x095e7baea6a6c7c4c2dfeb977efac326af552d87";
+    const KEY: &str = "0x0000000000000000000000000000000000000000000000000000000000000003";
+    const BLOB_HASH: &str = "0x01a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8";
+    const R: &str = "0x48b55bfa915ac795c431978d8a6a992b628d557da5ff759b307d495a36649353";
+    const S: &str = "0x1fffd310ac743f371de3b9f7f9cb56c0b28ad43601b4ab949f53faa07bd2c804";
+
+    fn typed_fields(tx_type: &str) -> Value {
+        json!({
+            "type": tx_type,
+            "chainId": "0x1",
+            "nonce": "0x2a",
+            "to": ADDRESS,
+            "gas": "0x5208",
+            "value": "0xde0b6b3a7640000",
+            "input": "0x1234",
+            "accessList": [{ "address": ADDRESS, "storageKeys": [KEY] }],
+            "yParity": "0x1",
+            "v": "0x1",
+            "r": R,
+            "s": S,
+        })
+    }
+
+    fn with(mut base: Value, extra: Value) -> Value {
+        for (key, value) in extra.as_object().unwrap() {
+            base[key] = value.clone();
+        }
+        base
+    }
+
+    /// Decode `json`, check the variant and that re-encoding reproduces
+    /// every input field
+    fn assert_round_trip(json: Value, tx_type: u8) -> Transaction {
+        let rpc: RpcTransaction = serde_json::from_value(json.clone()).unwrap();
+        let tx = Transaction::try_from(rpc).unwrap();
+        assert_eq!(tx.tx_type(), tx_type);
+
+        let encoded = serde_json::to_value(RpcTransaction::from(&tx)).unwrap();
+        for (key, value) in json.as_object().unwrap() {
+            assert_eq!(&encoded[key], value, "field {}", key);
+        }
+        assert_eq!(encoded["hash"], json!(tx.hash()));
+        tx
+    }
+
+    #[test]
+    fn test_legacy_round_trip() {
+        let json = json!({
+            "type": "0x0",
+            "chainId": "0x1",
+            "nonce": "0x9",
+            "to": ADDRESS,
+            "gas": "0x5208",
+            "gasPrice": "0x4a817c800",
+            "value": "0xde0b6b3a7640000",
+            "input": "0x",
+            "v": "0x25",
+            "r": R,
+            "s": S,
+        });
+
+        match assert_round_trip(json, 0x00) {
+            Transaction::Legacy(tx) => {
+                assert_eq!(tx.v, 37);
+                assert_eq!(tx.chain_id(), Some(1));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_eip2930_round_trip() {
+        let json = with(typed_fields("0x1"), json!({ "gasPrice": "0x3b9aca00" }));
+
+        match assert_round_trip(json, 0x01) {
+            Transaction::Eip2930(tx) => {
+                assert_eq!(tx.access_list[0].storage_keys.len(), 1);
+                assert!(tx.y_parity);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_eip1559_round_trip() {
+        let json = with(typed_fields("0x2"), json!({
+            "maxPriorityFeePerGas": "0x3b9aca00",
+            "maxFeePerGas": "0x77359400",
+        }));
+
+        match assert_round_trip(json, 0x02) {
+            Transaction::Eip1559(tx) => assert_eq!(tx.max_fee_per_gas, U256::from(2_000_000_000u64)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_eip4844_round_trip() {
+        let json = with(typed_fields("0x3"), json!({
+            "maxPriorityFeePerGas": "0x3b9aca00",
+            "maxFeePerGas": "0x77359400",
+            "maxFeePerBlobGas": "0x1",
+            "blobVersionedHashes": [BLOB_HASH],
+        }));
+
+        match assert_round_trip(json, 0x03) {
+            Transaction::Eip4844(tx) => assert_eq!(tx.blob_versioned_hashes.len(), 1),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_eip7702_round_trip() {
+        let json = with(typed_fields("0x4"), json!({
+            "maxPriorityFeePerGas": "0x3b9aca00",
+            "maxFeePerGas": "0x77359400",
+            "authorizationList": [{
+                "chainId": "0x1",
+                "address": ADDRESS,
+                "nonce": "0x0",
+                "yParity": "0x0",
+                "r": R,
+                "s": S,
+            }],
+        }));
+
+        match assert_round_trip(json, 0x04) {
+            Transaction::Eip7702(tx) => {
+                assert_eq!(tx.authorization_list[0].chain_id, 1);
+                assert!(!tx.authorization_list[0].y_parity);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_untyped_request_params() {
+        // eth_sendTransaction style params: no type, `data` instead of `input`
+        let rpc: RpcTransaction = serde_json::from_value(json!({
+            "from": ADDRESS,
+            "to": null,
+            "gas": "0x76c0",
+            "maxFeePerGas": "0x9184e72a000",
+            "maxPriorityFeePerGas": "0x1",
+            "chainId": "0x1",
+            "nonce": "0x0",
+            "data": "0x6000",
+            "yParity": "0x0",
+            "r": "0x0",
+            "s": "0x0",
+        })).unwrap();
+        assert_eq!(rpc.transaction_type().unwrap(), 0x02);
+
+        let tx = Transaction::try_from(rpc).unwrap();
+        assert!(tx.is_create());
+        assert_eq!(tx.data().as_slice(), &[0x60, 0x00]);
+    }
+
+    #[test]
+    fn test_missing_and_invalid_fields() {
+        let mut json = typed_fields("0x3");
+        json["maxPriorityFeePerGas"] = json!("0x1");
+        json["maxFeePerGas"] = json!("0x1");
+        json["maxFeePerBlobGas"] = json!("0x1");
+        let rpc: RpcTransaction = serde_json::from_value(json.clone()).unwrap();
+        assert!(matches!(
+            Transaction::try_from(rpc),
+            Err(TransactionError::MissingField("blobVersionedHashes"))
+        ));
+
+        json["blobVersionedHashes"] = json!([BLOB_HASH]);
+        json["yParity"] = json!("0x2");
+        let rpc: RpcTransaction = serde_json::from_value(json.clone()).unwrap();
+        assert!(matches!(Transaction::try_from(rpc), Err(TransactionError::InvalidSignature)));
+
+        json["type"] = json!("0x7");
+        let rpc: RpcTransaction = serde_json::from_value(json).unwrap();
+        assert!(matches!(Transaction::try_from(rpc), Err(TransactionError::InvalidTransactionType(7))));
+    }
+}
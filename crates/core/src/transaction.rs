@@ -22,6 +22,9 @@ pub enum TransactionError {
 
 pub type Result<T> = std::result::Result<T, TransactionError>;
 
+/// Largest init code a creation transaction may carry (EIP-3860)
+pub const MAX_INIT_CODE_SIZE: usize = 2 * 24_576;
+
 fn encode_h256_list(list: &[H256]) -> ethereum_types::Bytes {
     let mut encoder = Encoder::new();
     encoder.encode_list(list);
@@ -57,6 +60,7 @@ pub struct LegacyTransaction {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct AccessListItem {
     pub address: Address,
     pub storage_keys: Vec<H256>,
@@ -122,6 +126,40 @@ impl Transaction {
         }
     }
 
+    /// EIP-2718 transaction type byte (0 for legacy)
+    pub fn tx_type(&self) -> u8 {
+        match self {
+            Transaction::Legacy(_) => 0x00,
+            Transaction::Eip2930(_) => 0x01,
+            Transaction::Eip1559(_) => 0x02,
+            Transaction::Eip4844(_) => 0x03,
+            Transaction::Eip7702(_) => 0x04,
+        }
+    }
+
+    /// EIP-2718 envelope (`type || rlp(payload)`, or plain rlp for legacy)
+    /// as accepted by `eth_sendRawTransaction`
+    pub fn encoded_2718(&self) -> Vec<u8> {
+        let payload = match self {
+            Transaction::Legacy(tx) => return ethereum_rlp::encode(tx)[..].to_vec(),
+            Transaction::Eip2930(tx) => ethereum_rlp::encode(tx),
+            Transaction::Eip1559(tx) => ethereum_rlp::encode(tx),
+            Transaction::Eip4844(tx) => ethereum_rlp::encode(tx),
+            Transaction::Eip7702(tx) => ethereum_rlp::encode(tx),
+        };
+        [&[self.tx_type()][..], &payload[..]].concat()
+    }
+
+    /// Length of the EIP-2718 envelope in bytes
+    pub fn size(&self) -> usize {
+        self.encoded_2718().len()
+    }
+
+    /// Whether the transaction deploys a contract rather than calling one
+    pub fn is_create(&self) -> bool {
+        self.to().is_none()
+    }
+
     pub fn sender(&self) -> Result<Address> {
         match self {
             Transaction::Legacy(tx) => tx.sender(),
@@ -201,6 +239,57 @@ impl Transaction {
             Transaction::Eip7702(tx) => &tx.data,
         }
     }
+
+    /// Most the sender pays the block producer per gas, the gas price for
+    /// legacy and access list transactions
+    pub fn max_priority_fee_per_gas(&self) -> U256 {
+        match self {
+            Transaction::Legacy(tx) => tx.gas_price,
+            Transaction::Eip2930(tx) => tx.gas_price,
+            Transaction::Eip1559(tx) => tx.max_priority_fee_per_gas,
+            Transaction::Eip4844(tx) => tx.max_priority_fee_per_gas,
+            Transaction::Eip7702(tx) => tx.max_priority_fee_per_gas,
+        }
+    }
+
+    /// Price per gas actually paid in a block with `base_fee`
+    ///
+    /// Fixed for legacy and access list transactions, otherwise the base fee
+    /// plus the priority fee, capped at the max fee.
+    pub fn effective_gas_price(&self, base_fee: U256) -> U256 {
+        match self {
+            Transaction::Legacy(tx) => tx.gas_price,
+            Transaction::Eip2930(tx) => tx.gas_price,
+            _ => self.gas_price().min(base_fee.saturating_add(self.max_priority_fee_per_gas())),
+        }
+    }
+
+    /// Part of the gas price that goes to the block producer, zero when the
+    /// transaction can't cover `base_fee`
+    pub fn effective_tip(&self, base_fee: U256) -> U256 {
+        self.effective_gas_price(base_fee).saturating_sub(base_fee)
+    }
+
+    /// Blob gas paid for by an EIP-4844 transaction, zero for other types
+    pub fn blob_gas(&self) -> u64 {
+        match self {
+            Transaction::Eip4844(tx) => tx.blob_versioned_hashes.len() as u64 * crate::eip7691::BLOB_GAS_PER_BLOB,
+            _ => 0,
+        }
+    }
+
+    /// Balance the sender needs up front: the value plus the gas limit and
+    /// any blob gas at their maximum prices
+    pub fn upfront_cost(&self) -> U256 {
+        let blob_cost = match self {
+            Transaction::Eip4844(tx) => U256::from(self.blob_gas()).saturating_mul(tx.max_fee_per_blob_gas),
+            _ => U256::zero(),
+        };
+
+        self.value()
+            .saturating_add(self.gas_limit().saturating_mul(self.gas_price()))
+            .saturating_add(blob_cost)
+    }
 }
 
 impl LegacyTransaction {
@@ -208,51 +297,46 @@ impl LegacyTransaction {
         keccak256(&ethereum_rlp::encode(self))
     }
 
+    /// Hash signed by the sender: the first six fields, followed by
+    /// `[chain_id, 0, 0]` for EIP-155 signatures
     pub fn signing_hash(&self, chain_id: Option<u64>) -> H256 {
+        let mut fields = Encoder::new();
+        self.nonce.encode(&mut fields);
+        self.gas_price.encode(&mut fields);
+        self.gas_limit.encode(&mut fields);
+        self.to.encode(&mut fields);
+        self.value.encode(&mut fields);
+        self.data.encode(&mut fields);
         if let Some(chain_id) = chain_id {
-            let mut tx = self.clone();
-            tx.v = chain_id;
-            tx.r = U256::zero();
-            tx.s = U256::zero();
-            let mut encoder = Encoder::new();
-            encoder.encode_list(&[
-                ethereum_rlp::encode(&tx.nonce),
-                ethereum_rlp::encode(&tx.gas_price),
-                ethereum_rlp::encode(&tx.gas_limit),
-                ethereum_rlp::encode(&tx.to),
-                ethereum_rlp::encode(&tx.value),
-                ethereum_rlp::encode(&tx.data),
-                ethereum_rlp::encode(&chain_id),
-                ethereum_rlp::encode(&0u8),
-                ethereum_rlp::encode(&0u8),
-            ]);
-            keccak256(&encoder.finish())
-        } else {
-            let mut encoder = Encoder::new();
-            encoder.encode_list(&[
-                ethereum_rlp::encode(&self.nonce),
-                ethereum_rlp::encode(&self.gas_price),
-                ethereum_rlp::encode(&self.gas_limit),
-                ethereum_rlp::encode(&self.to),
-                ethereum_rlp::encode(&self.value),
-                ethereum_rlp::encode(&self.data),
-            ]);
-            keccak256(&encoder.finish())
+            fields.encode_u64(chain_id);
+            fields.encode_u64(0);
+            fields.encode_u64(0);
         }
+
+        let mut encoder = Encoder::new();
+        encoder.encode_list_payload(&fields.finish());
+        keccak256(&encoder.finish())
     }
 
-    pub fn sender(&self) -> Result<Address> {
-        let chain_id = if self.v >= 35 {
-            Some((self.v - 35) / 2)
-        } else {
-            None
-        };
+    /// Chain the signature commits to, `None` for pre-EIP-155 signatures
+    pub fn chain_id(&self) -> Option<u64> {
+        self.v.checked_sub(35).map(|v| v / 2)
+    }
 
-        let v = if self.v >= 35 {
-            ((self.v - 35) % 2) as u8
-        } else {
-            (self.v - 27) as u8
-        };
+    /// Split `v` into the signed chain id and the recovery id
+    ///
+    /// Pre-EIP-155 signatures use 27 or 28, EIP-155 ones `chain_id * 2 + 35`
+    /// or `+ 36`. Anything else cannot come from a valid signature.
+    fn split_v(&self) -> Result<(Option<u64>, u8)> {
+        match self.v {
+            27 | 28 => Ok((None, (self.v - 27) as u8)),
+            v if v >= 35 => Ok((Some((v - 35) / 2), ((v - 35) % 2) as u8)),
+            _ => Err(TransactionError::InvalidSignature),
+        }
+    }
+
+    pub fn sender(&self) -> Result<Address> {
+        let (chain_id, v) = self.split_v()?;
 
         let mut r_bytes = [0u8; 32];
         self.r.to_big_endian(&mut r_bytes);
@@ -691,4 +775,188 @@ mod tests {
         let signing_hash = tx.signing_hash();
         assert_eq!(signing_hash.0.len(), 32);
     }
-}
\ No newline at end of file
+
+    fn fee_fixtures() -> Vec<Transaction> {
+        let to: Address = "0x3535353535353535353535353535353535353535".parse().unwrap();
+        let gwei = |n: u64| U256::from(n * 1_000_000_000);
+
+        vec![
+            Transaction::Legacy(LegacyTransaction {
+                nonce: U256::zero(),
+                gas_price: gwei(30),
+                gas_limit: U256::from(21_000),
+                to: Some(to),
+                value: U256::from(1_000),
+                data: Bytes::new(),
+                v: 27,
+                r: U256::one(),
+                s: U256::one(),
+            }),
+            Transaction::Eip2930(Eip2930Transaction {
+                chain_id: 1,
+                nonce: U256::zero(),
+                gas_price: gwei(25),
+                gas_limit: U256::from(30_000),
+                to: Some(to),
+                value: U256::from(1_000),
+                data: Bytes::new(),
+                access_list: vec![],
+                y_parity: false,
+                r: U256::one(),
+                s: U256::one(),
+            }),
+            Transaction::Eip1559(Eip1559Transaction {
+                chain_id: 1,
+                nonce: U256::zero(),
+                max_priority_fee_per_gas: gwei(2),
+                max_fee_per_gas: gwei(40),
+                gas_limit: U256::from(50_000),
+                to: Some(to),
+                value: U256::from(1_000),
+                data: Bytes::new(),
+                access_list: vec![],
+                y_parity: false,
+                r: U256::one(),
+                s: U256::one(),
+            }),
+            Transaction::Eip4844(Eip4844Transaction {
+                chain_id: 1,
+                nonce: U256::zero(),
+                max_priority_fee_per_gas: gwei(3),
+                max_fee_per_gas: gwei(21),
+                gas_limit: U256::from(60_000),
+                to,
+                value: U256::from(1_000),
+                data: Bytes::new(),
+                access_list: vec![],
+                max_fee_per_blob_gas: U256::from(7),
+                blob_versioned_hashes: vec![H256::repeat_byte(1), H256::repeat_byte(2)],
+                y_parity: false,
+                r: U256::one(),
+                s: U256::one(),
+            }),
+            Transaction::Eip7702(crate::eip7702::Eip7702Transaction {
+                chain_id: 1,
+                nonce: U256::zero(),
+                max_priority_fee_per_gas: gwei(5),
+                max_fee_per_gas: gwei(50),
+                gas_limit: U256::from(80_000),
+                to,
+                value: U256::from(1_000),
+                data: Bytes::new(),
+                access_list: vec![],
+                authorization_list: vec![],
+                y_parity: false,
+                r: U256::one(),
+                s: U256::one(),
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_effective_fees_under_base_fee() {
+        let gwei = |n: u64| U256::from(n * 1_000_000_000);
+        let base_fee = gwei(20);
+
+        // (effective gas price, effective tip) at a 20 gwei base fee
+        let expected = [
+            (gwei(30), gwei(10)), // legacy pays its gas price
+            (gwei(25), gwei(5)),  // access list pays its gas price
+            (gwei(22), gwei(2)),  // full priority fee fits under the max fee
+            (gwei(21), gwei(1)),  // priority fee capped by the max fee
+            (gwei(25), gwei(5)),
+        ];
+
+        for (tx, (price, tip)) in fee_fixtures().iter().zip(expected) {
+            assert_eq!(tx.effective_gas_price(base_fee), price, "type {}", tx.tx_type());
+            assert_eq!(tx.effective_tip(base_fee), tip, "type {}", tx.tx_type());
+        }
+
+        // Nothing is left for the producer once the base fee passes the max fee
+        for tx in fee_fixtures() {
+            assert_eq!(tx.effective_tip(gwei(100)), U256::zero());
+        }
+    }
+
+    #[test]
+    fn test_upfront_cost() {
+        let gwei = |n: u64| U256::from(n * 1_000_000_000);
+        let value = U256::from(1_000);
+
+        let expected = [
+            value + U256::from(21_000) * gwei(30),
+            value + U256::from(30_000) * gwei(25),
+            value + U256::from(50_000) * gwei(40),
+            // Two blobs of 131072 blob gas at 7 wei each
+            value + U256::from(60_000) * gwei(21) + U256::from(2 * 131_072 * 7),
+            value + U256::from(80_000) * gwei(50),
+        ];
+
+        for (tx, cost) in fee_fixtures().iter().zip(expected) {
+            assert_eq!(tx.upfront_cost(), cost, "type {}", tx.tx_type());
+        }
+        assert_eq!(fee_fixtures()[3].blob_gas(), 2 * 131_072);
+    }
+
+    fn eip155_example() -> LegacyTransaction {
+        LegacyTransaction {
+            nonce: U256::from(9),
+            gas_price: U256::from(20_000_000_000u64),
+            gas_limit: U256::from(21_000),
+            to: Some("0x3535353535353535353535353535353535353535".parse().unwrap()),
+            value: U256::from(1_000_000_000_000_000_000u64),
+            data: Bytes::new(),
+            v: 27,
+            r: U256::zero(),
+            s: U256::zero(),
+        }
+    }
+
+    #[test]
+    fn test_legacy_sender_eip155() {
+        // Example from EIP-155, signed with the key 0x4646...46
+        let mut tx = eip155_example();
+        tx.v = 37;
+        tx.r = U256::from_dec_str("18515461264373351373200002665853028612451056578545711640558177340181847433846").unwrap();
+        tx.s = U256::from_dec_str("46948507304638947509940763649030358759909902576025900602547168820602576006531").unwrap();
+
+        let sender: Address = "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f".parse().unwrap();
+        assert_eq!(tx.chain_id(), Some(1));
+        assert_eq!(tx.sender().unwrap(), sender);
+    }
+
+    #[test]
+    fn test_legacy_sender_pre_eip155() {
+        let key = ethereum_crypto::generate_private_key();
+        let sign = |chain_id: Option<u64>| {
+            let mut tx = eip155_example();
+            let signature = ethereum_crypto::sign_message(&tx.signing_hash(chain_id), &key).unwrap();
+            tx.v = match chain_id {
+                Some(chain_id) => chain_id * 2 + 35 + (signature.v - 27) as u64,
+                None => signature.v as u64,
+            };
+            tx.r = U256::from_big_endian(signature.r.as_bytes());
+            tx.s = U256::from_big_endian(signature.s.as_bytes());
+            tx
+        };
+
+        let unprotected = sign(None);
+        assert!(unprotected.v == 27 || unprotected.v == 28);
+        assert_eq!(unprotected.chain_id(), None);
+
+        // The same key recovers from either signature
+        let sender = sign(Some(1)).sender().unwrap();
+        assert_eq!(unprotected.sender().unwrap(), sender);
+    }
+
+    #[test]
+    fn test_legacy_sender_rejects_malformed_v() {
+        for v in [0, 1, 26, 29, 34] {
+            let mut tx = eip155_example();
+            tx.v = v;
+            tx.r = U256::one();
+            tx.s = U256::one();
+            assert!(matches!(tx.sender(), Err(TransactionError::InvalidSignature)), "v = {}", v);
+        }
+    }
+}
@@ -6,7 +6,7 @@ pub mod distribution;
 
 pub use sampling::{DataSampler, SampleRequest, SampleResponse};
 pub use reconstruction::{DataReconstructor, ReconstructionResult};
-pub use peer_das::{PeerDAS, DASConfig, DASStatus};
+pub use peer_das::{PeerDAS, DASConfig, DASStatus, SlotSampler, SlotSamplingOutcome};
 pub use erasure::{ErasureCoding, CodedData};
 pub use distribution::{DataDistributor, DistributionStrategy};
 
@@ -19,8 +19,12 @@ pub struct DASConfig {
     pub data_columns: usize,
     /// Number of redundancy columns (for erasure coding)
     pub redundancy_columns: usize,
-    /// Number of samples required for confirmation
+    /// Number of distinct columns sampled per slot
     pub samples_per_slot: usize,
+    /// Successful samples required to declare the data available
+    pub success_threshold: usize,
+    /// Extra attempts made for columns whose sample failed
+    pub max_sample_retries: usize,
     /// Sampling timeout
     pub sampling_timeout: Duration,
     /// Maximum concurrent sampling requests
@@ -35,6 +39,8 @@ impl Default for DASConfig {
             data_columns: 128,           // NUMBER_OF_COLUMNS
             redundancy_columns: 128,     // Same as data for 2x redundancy
             samples_per_slot: 75,        // SAMPLES_PER_SLOT
+            success_threshold: 50,       // 2/3 of the sampled columns
+            max_sample_retries: 2,
             sampling_timeout: Duration::from_secs(4),
             max_concurrent_samples: 16,
             custody_requirement: 4,      // CUSTODY_REQUIREMENT
@@ -238,63 +244,23 @@ impl PeerDAS {
     pub async fn sample_availability(&self, block_root: H256, blob_commitments: Vec<KzgCommitment>) -> Result<bool> {
         info!("Starting DAS for block {:?}", block_root);
         
-        *self.status.write().unwrap() = DASStatus::Sampling {
-            block_root,
-            progress: 0.0,
-        };
+        let slot_sampler = SlotSampler::new(self.config.clone(), self.sampler.clone(), self.status.clone());
+        let outcome = slot_sampler.sample_slot(block_root, &blob_commitments).await?;
         
         self.metrics.samples_requested.fetch_add(
-            self.config.samples_per_slot as u64,
+            outcome.requests as u64,
             std::sync::atomic::Ordering::Relaxed,
         );
-        
-        // Select random columns to sample
-        let column_indices = self.select_sample_columns(self.config.samples_per_slot);
-        
-        // Create sample requests
-        let mut sample_requests = Vec::new();
-        for column_index in column_indices {
-            let request = SampleRequest {
-                block_root,
-                column_index,
-                commitment: blob_commitments.get(column_index as usize).cloned(),
-            };
-            sample_requests.push(request);
-        }
-        
-        // Perform sampling
-        let results = self.sampler.sample_columns(sample_requests).await;
-        
-        // Check if we have enough successful samples
-        let successful_samples = results.iter().filter(|r| r.is_available).count();
-        let required_samples = (self.config.samples_per_slot * 2) / 3; // 2/3 threshold
-        
         self.metrics.samples_successful.fetch_add(
-            successful_samples as u64,
+            outcome.successful.len() as u64,
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        self.metrics.samples_failed.fetch_add(
+            outcome.failed.len() as u64,
             std::sync::atomic::Ordering::Relaxed,
         );
         
-        if successful_samples >= required_samples {
-            *self.status.write().unwrap() = DASStatus::Available;
-            info!("Data availability confirmed with {}/{} samples", successful_samples, self.config.samples_per_slot);
-            Ok(true)
-        } else {
-            *self.status.write().unwrap() = DASStatus::NotAvailable;
-            warn!("Insufficient samples: {}/{}", successful_samples, required_samples);
-            Ok(false)
-        }
-    }
-    
-    fn select_sample_columns(&self, count: usize) -> Vec<u64> {
-        use rand::seq::SliceRandom;
-        
-        let total_columns = self.config.data_columns + self.config.redundancy_columns;
-        let mut columns: Vec<u64> = (0..total_columns as u64).collect();
-        
-        let mut rng = rand::thread_rng();
-        columns.shuffle(&mut rng);
-        
-        columns.into_iter().take(count).collect()
+        Ok(outcome.available)
     }
     
     /// Store a data column locally
@@ -398,4 +364,226 @@ impl PeerDAS {
     pub fn get_status(&self) -> DASStatus {
         self.status.read().unwrap().clone()
     }
-}
\ No newline at end of file
+}
+
+/// Result of sampling the columns of one block
+#[derive(Debug, Clone)]
+pub struct SlotSamplingOutcome {
+    pub available: bool,
+    /// Columns whose sample was retrieved
+    pub successful: Vec<u64>,
+    /// Columns that still failed after all retries
+    pub failed: Vec<u64>,
+    /// Total sample requests issued, including retries
+    pub requests: usize,
+}
+
+/// Per-slot sampling loop
+///
+/// Picks `samples_per_slot` distinct random columns, requests them through
+/// the `DataSampler` and retries failed columns until `success_threshold`
+/// distinct columns have been retrieved or the retries run out.
+pub struct SlotSampler {
+    config: DASConfig,
+    sampler: Arc<DataSampler>,
+    status: Arc<RwLock<DASStatus>>,
+}
+
+impl SlotSampler {
+    pub fn new(config: DASConfig, sampler: Arc<DataSampler>, status: Arc<RwLock<DASStatus>>) -> Self {
+        Self { config, sampler, status }
+    }
+
+    pub fn status(&self) -> DASStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    pub async fn sample_slot(
+        &self,
+        block_root: H256,
+        blob_commitments: &[KzgCommitment],
+    ) -> Result<SlotSamplingOutcome> {
+        let total_columns = self.config.data_columns + self.config.redundancy_columns;
+        let sample_count = self.config.samples_per_slot.min(total_columns);
+        let threshold = self.config.success_threshold;
+
+        if threshold > sample_count {
+            return Err(DASError::InsufficientSamples(sample_count, threshold));
+        }
+
+        self.set_status(DASStatus::Sampling { block_root, progress: 0.0 });
+
+        let mut pending = self.select_columns(total_columns, sample_count);
+        let mut successful = HashSet::new();
+        let mut requests = 0;
+
+        for attempt in 0..=self.config.max_sample_retries {
+            if attempt > 0 {
+                debug!("Retrying {} failed columns for block {:?} (attempt {})", pending.len(), block_root, attempt);
+            }
+
+            let sample_requests: Vec<SampleRequest> = pending
+                .iter()
+                .map(|&column_index| SampleRequest {
+                    block_root,
+                    column_index,
+                    commitment: blob_commitments.get(column_index as usize).cloned(),
+                })
+                .collect();
+            requests += sample_requests.len();
+
+            let responses = self.sampler.sample_columns(sample_requests).await;
+            for response in responses.iter().filter(|r| r.is_available) {
+                successful.insert(response.column_index);
+            }
+            pending.retain(|column| !successful.contains(column));
+
+            self.set_status(DASStatus::Sampling {
+                block_root,
+                progress: (successful.len() as f64 / threshold.max(1) as f64).min(1.0),
+            });
+
+            // Done once enough columns succeeded, or when even recovering
+            // every pending column could not reach the threshold
+            if successful.len() >= threshold || successful.len() + pending.len() < threshold {
+                break;
+            }
+        }
+
+        let available = successful.len() >= threshold;
+        if available {
+            self.set_status(DASStatus::Available);
+            info!("Data availability confirmed with {}/{} samples", successful.len(), sample_count);
+        } else {
+            self.set_status(DASStatus::NotAvailable);
+            warn!("Insufficient samples for block {:?}: {}/{}", block_root, successful.len(), threshold);
+        }
+
+        let mut successful: Vec<u64> = successful.into_iter().collect();
+        successful.sort_unstable();
+
+        Ok(SlotSamplingOutcome {
+            available,
+            successful,
+            failed: pending,
+            requests,
+        })
+    }
+
+    fn select_columns(&self, total_columns: usize, count: usize) -> Vec<u64> {
+        use rand::seq::SliceRandom;
+
+        let mut columns: Vec<u64> = (0..total_columns as u64).collect();
+        columns.shuffle(&mut rand::thread_rng());
+        columns.truncate(count);
+        columns
+    }
+
+    fn set_status(&self, status: DASStatus) {
+        *self.status.write().unwrap() = status;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::SamplingNetwork;
+    use std::sync::Mutex;
+
+    /// Network where some columns never resolve and the rest fail a fixed
+    /// number of times before succeeding
+    struct MockNetwork {
+        unavailable: HashSet<u64>,
+        failures_before_success: usize,
+        attempts: Mutex<HashMap<u64, usize>>,
+    }
+
+    impl MockNetwork {
+        fn new(unavailable: HashSet<u64>, failures_before_success: usize) -> Self {
+            Self {
+                unavailable,
+                failures_before_success,
+                attempts: Mutex::new(HashMap::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl SamplingNetwork for MockNetwork {
+        async fn request_column(&self, _peer_id: &[u8; 32], column_index: u64, _block_root: H256) -> Result<Vec<u8>> {
+            let mut attempts = self.attempts.lock().unwrap();
+            let attempt = attempts.entry(column_index).or_insert(0);
+            *attempt += 1;
+
+            if self.unavailable.contains(&column_index) || *attempt <= self.failures_before_success {
+                Err(DASError::NetworkError(format!("column {} unavailable", column_index)))
+            } else {
+                Ok(vec![column_index as u8; 32])
+            }
+        }
+
+        async fn find_column_custodians(&self, _column_index: u64) -> Result<Vec<[u8; 32]>> {
+            Ok(vec![[1u8; 32]])
+        }
+    }
+
+    fn config(retries: usize) -> DASConfig {
+        DASConfig {
+            data_columns: 8,
+            redundancy_columns: 8,
+            samples_per_slot: 8,
+            success_threshold: 6,
+            max_sample_retries: retries,
+            ..Default::default()
+        }
+    }
+
+    fn slot_sampler(config: DASConfig, network: MockNetwork) -> SlotSampler {
+        let sampler = DataSampler::new(4).with_network(Arc::new(network));
+        SlotSampler::new(config, Arc::new(sampler), Arc::new(RwLock::new(DASStatus::Idle)))
+    }
+
+    #[tokio::test]
+    async fn test_available_when_threshold_met() {
+        let sampler = slot_sampler(config(0), MockNetwork::new(HashSet::new(), 0));
+
+        let outcome = sampler.sample_slot(H256::zero(), &[]).await.unwrap();
+
+        assert!(outcome.available);
+        assert_eq!(outcome.successful.len(), 8);
+        assert_eq!(outcome.requests, 8);
+        assert_eq!(sampler.status(), DASStatus::Available);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_when_too_many_fail() {
+        // Only 4 of the 16 columns can ever be served
+        let unavailable: HashSet<u64> = (4..16).collect();
+        let sampler = slot_sampler(config(2), MockNetwork::new(unavailable, 0));
+
+        let outcome = sampler.sample_slot(H256::zero(), &[]).await.unwrap();
+
+        assert!(!outcome.available);
+        assert!(outcome.successful.len() < 6);
+        assert_eq!(outcome.successful.len() + outcome.failed.len(), 8);
+        assert_eq!(sampler.status(), DASStatus::NotAvailable);
+    }
+
+    #[tokio::test]
+    async fn test_failed_columns_are_retried() {
+        // Every column fails once before it can be fetched
+        let sampler = slot_sampler(config(1), MockNetwork::new(HashSet::new(), 1));
+
+        let outcome = sampler.sample_slot(H256::zero(), &[]).await.unwrap();
+
+        assert!(outcome.available);
+        assert_eq!(outcome.successful.len(), 8);
+        assert_eq!(outcome.requests, 16);
+
+        // Without retries the same network never reaches the threshold
+        let sampler = slot_sampler(config(0), MockNetwork::new(HashSet::new(), 1));
+        let outcome = sampler.sample_slot(H256::zero(), &[]).await.unwrap();
+        assert!(!outcome.available);
+        assert_eq!(outcome.failed.len(), 8);
+    }
+}
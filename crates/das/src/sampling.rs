@@ -101,6 +101,7 @@ impl DataSampler {
             let network = self.network.clone();
             let metrics = self.metrics.clone();
             
+            let column_index = request.column_index;
             let handle = tokio::spawn(async move {
                 let start = Instant::now();
                 let result = Self::sample_single(
@@ -113,7 +114,7 @@ impl DataSampler {
                 drop(permit);
                 
                 SampleResponse {
-                    column_index: request.column_index,
+                    column_index,
                     is_available: result.is_ok(),
                     data: result.as_ref().ok().map(|r| r.0.clone()),
                     proof: result.ok().map(|r| r.1),
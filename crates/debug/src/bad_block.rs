@@ -0,0 +1,110 @@
+use ethereum_types::{H256, U256};
+use ethereum_core::{Block, Header, Receipt};
+use ethereum_trie::ordered_trie_root;
+use serde::{Serialize, Deserialize};
+
+use crate::{Result, TraceResult};
+
+/// Result of re-executing a block on its parent's state
+#[derive(Debug, Clone)]
+pub struct BlockReplay {
+    pub state_root: H256,
+    pub receipts: Vec<Receipt>,
+}
+
+impl BlockReplay {
+    pub fn gas_used(&self) -> U256 {
+        self.receipts.last().map(|receipt| receipt.cumulative_gas_used).unwrap_or_default()
+    }
+
+    pub fn receipts_root(&self) -> H256 {
+        ordered_trie_root(self.receipts.iter().map(|receipt| receipt.encoded_2718()))
+    }
+}
+
+/// Executes a block on top of its parent's state without importing it
+pub trait BlockExecutor: Send + Sync {
+    fn execute(&self, parent: &Block, block: &Block) -> Result<BlockReplay>;
+}
+
+/// Header commitment the re-execution disagrees with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Divergence {
+    GasUsed { expected: U256, actual: U256 },
+    ReceiptsRoot { expected: H256, actual: H256 },
+    StateRoot { expected: H256, actual: H256 },
+}
+
+/// Per-transaction traces of a bad block and where its replay diverged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BadBlockTrace {
+    pub block_hash: H256,
+    pub traces: Vec<TraceResult>,
+    pub divergence: Option<Divergence>,
+}
+
+/// First mismatch between `header` and the replay
+///
+/// Gas and receipts are compared before the state root: a receipt mismatch
+/// usually points at the transaction that caused the state to differ.
+pub fn find_divergence(header: &Header, replay: &BlockReplay) -> Option<Divergence> {
+    let gas_used = replay.gas_used();
+    if gas_used != header.gas_used {
+        return Some(Divergence::GasUsed { expected: header.gas_used, actual: gas_used });
+    }
+
+    let receipts_root = replay.receipts_root();
+    if receipts_root != header.receipts_root {
+        return Some(Divergence::ReceiptsRoot { expected: header.receipts_root, actual: receipts_root });
+    }
+
+    if replay.state_root != header.state_root {
+        return Some(Divergence::StateRoot { expected: header.state_root, actual: replay.state_root });
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_trie::empty_root;
+
+    fn receipt(cumulative_gas_used: u64) -> Receipt {
+        Receipt {
+            tx_type: 2,
+            status: 1,
+            cumulative_gas_used: U256::from(cumulative_gas_used),
+            logs_bloom: Default::default(),
+            logs: Vec::new(),
+            gas_used: U256::from(21_000),
+            contract_address: None,
+        }
+    }
+
+    #[test]
+    fn test_divergence_order() {
+        let replay = BlockReplay {
+            state_root: H256::repeat_byte(0xaa),
+            receipts: vec![receipt(21_000)],
+        };
+
+        let mut header = Header::new();
+        header.gas_used = U256::from(42_000);
+        header.receipts_root = replay.receipts_root();
+        header.state_root = replay.state_root;
+        assert!(matches!(find_divergence(&header, &replay), Some(Divergence::GasUsed { .. })));
+
+        header.gas_used = U256::from(21_000);
+        assert_eq!(find_divergence(&header, &replay), None);
+
+        header.receipts_root = empty_root();
+        header.state_root = H256::repeat_byte(0xbb);
+        assert_eq!(
+            find_divergence(&header, &replay),
+            Some(Divergence::ReceiptsRoot { expected: empty_root(), actual: replay.receipts_root() })
+        );
+    }
+}
@@ -1,11 +1,13 @@
 use ethereum_types::{H256, U256, Address};
 use ethereum_core::{Block, Transaction};
 use ethereum_storage::Database;
-use ethereum_evm::{EVM, Opcode};
+use ethereum_evm::execution::{BlockContext, Log};
+use ethereum_evm::{run_interpreter, Account, ChainConfig, Checkpoint, EvmError, Host, Step};
 use std::sync::Arc;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
+use crate::tracer::{apply_traced, delegate_host, Tracer};
 use crate::{Result, DebugError};
 
 /// Breakpoint for debugging
@@ -45,173 +47,208 @@ pub struct StepInfo {
     pub storage_changes: HashMap<H256, H256>,
 }
 
+/// Most steps a debugging session records
+const MAX_STEPS: usize = 10_000;
+
 /// Transaction debugger
 pub struct Debugger<D: Database> {
-    db: Arc<D>,
-    evm: Arc<EVM<D>>,
+    tracer: Tracer<D>,
 }
 
 impl<D: Database + 'static> Debugger<D> {
-    pub fn new(db: Arc<D>, evm: Arc<EVM<D>>) -> Self {
-        Self { db, evm }
+    pub fn new(db: Arc<D>) -> Self {
+        Self { tracer: Tracer::new(db) }
+    }
+    
+    /// Chain whose id debugged transactions see
+    pub fn with_chain_config(mut self, chain_config: ChainConfig) -> Self {
+        self.tracer = self.tracer.with_chain_config(chain_config);
+        self
     }
     
     /// Debug transaction with breakpoints
+    ///
+    /// The transaction is replayed on its parent block's state, the state
+    /// at the last breakpoint hit is kept along with every step it ran.
     pub async fn debug_transaction(
         &self,
         tx: &Transaction,
         block: &Block,
         breakpoints: Vec<Breakpoint>,
     ) -> Result<DebuggerState> {
-        let context = self.create_context(block);
-        let state = self.get_state_at_block(&block.header.parent_hash).await?;
-        
-        let mut debugger_state = DebuggerState {
-            pc: 0,
-            op: String::new(),
-            gas: tx.gas_limit,
-            gas_cost: U256::zero(),
-            memory: Vec::new(),
-            stack: Vec::new(),
+        self.tracer.replay(tx, block, |state, context| {
+            let mut debugged = None;
+            let result = apply_traced(state, context, tx, |frame, host| {
+                let mut debugger = BreakpointHost::new(host, &breakpoints, frame.gas_limit);
+                let result = run_interpreter(frame, &mut debugger);
+                debugged = Some(debugger.into_state());
+                result
+            })?;
+            
+            let mut debugger_state = debugged
+                .ok_or_else(|| DebugError::ExecutionError("transaction did not run".to_string()))?;
+            debugger_state.return_data = result.return_data;
+            Ok(debugger_state)
+        })
+    }
+}
+
+/// Host wrapper that records every step and stops the state at breakpoints
+struct BreakpointHost<'h, 'b, H: Host> {
+    inner: &'h mut H,
+    breakpoints: &'b [Breakpoint],
+    state: DebuggerState,
+    /// Storage written so far, keyed by slot
+    storage: HashMap<H256, H256>,
+    /// Whether each instruction still running hit a breakpoint, innermost
+    /// frame last
+    open: Vec<bool>,
+}
+
+impl<'h, 'b, H: Host> BreakpointHost<'h, 'b, H> {
+    fn new(inner: &'h mut H, breakpoints: &'b [Breakpoint], gas: u64) -> Self {
+        Self {
+            inner,
+            breakpoints,
+            state: DebuggerState {
+                pc: 0,
+                op: String::new(),
+                gas: U256::from(gas),
+                gas_cost: U256::zero(),
+                memory: Vec::new(),
+                stack: Vec::new(),
+                storage: HashMap::new(),
+                depth: 0,
+                return_data: Vec::new(),
+                breakpoints_hit: Vec::new(),
+                steps: Vec::new(),
+            },
             storage: HashMap::new(),
-            depth: 0,
-            return_data: Vec::new(),
-            breakpoints_hit: Vec::new(),
-            steps: Vec::new(),
-        };
-        
-        let mut step_count = 0;
-        let mut storage_snapshot = HashMap::new();
-        
-        // Execute with debugger
-        let result = self.evm.execute_transaction_with_tracer(
-            tx,
-            state,
-            &context,
-            |pc, op, stack, memory, storage| {
-                // Check breakpoints
-                for (i, bp) in breakpoints.iter().enumerate() {
-                    if self.should_break(bp, pc, op, stack, memory) {
-                        debugger_state.breakpoints_hit.push(i);
-                        
-                        // Update debugger state
-                        debugger_state.pc = pc as u64;
-                        debugger_state.op = format!("{:?}", op);
-                        debugger_state.gas = self.evm.get_gas_left();
-                        debugger_state.memory = memory.clone();
-                        debugger_state.stack = stack.clone();
-                        debugger_state.storage = storage.clone();
-                        debugger_state.depth = self.evm.get_call_depth();
-                    }
-                }
-                
-                // Track storage changes
-                let mut storage_changes = HashMap::new();
-                for (key, value) in storage {
-                    if storage_snapshot.get(key) != Some(value) {
-                        storage_changes.insert(*key, *value);
-                        storage_snapshot.insert(*key, *value);
-                    }
-                }
-                
-                // Record step
-                debugger_state.steps.push(StepInfo {
-                    pc: pc as u64,
-                    op: format!("{:?}", op),
-                    gas: self.evm.get_gas_left(),
-                    stack_size: stack.len(),
-                    memory_size: memory.len(),
-                    storage_changes,
-                });
-                
-                step_count += 1;
-                
-                // Limit steps to prevent excessive data
-                if step_count > 10000 {
-                    return;
-                }
-            }
-        ).await.map_err(|e| DebugError::EvmError(e.to_string()))?;
-        
-        debugger_state.return_data = result.return_data;
-        
-        Ok(debugger_state)
+            open: Vec::new(),
+        }
     }
     
-    /// Check if should break at current state
-    fn should_break(
-        &self,
-        breakpoint: &Breakpoint,
-        pc: usize,
-        op: Opcode,
-        stack: &[H256],
-        memory: &[u8],
-    ) -> bool {
-        // Check PC breakpoint
-        if let Some(bp_pc) = breakpoint.pc {
-            if pc as u64 != bp_pc {
-                return false;
-            }
+    fn into_state(self) -> DebuggerState {
+        self.state
+    }
+}
+
+impl<'h, 'b, H: Host> Host for BreakpointHost<'h, 'b, H> {
+    fn get_account(&self, address: &Address) -> Option<Account> {
+        self.inner.get_account(address)
+    }
+
+    fn set_account(&mut self, address: Address, account: Account) {
+        self.inner.set_account(address, account)
+    }
+
+    fn remove_account(&mut self, address: &Address) {
+        self.inner.remove_account(address)
+    }
+
+    fn is_empty(&self, address: &Address) -> bool {
+        self.inner.is_empty(address)
+    }
+
+    fn is_created(&self, address: &Address) -> bool {
+        self.inner.is_created(address)
+    }
+
+    fn get_storage(&self, address: &Address, key: &H256) -> H256 {
+        self.inner.get_storage(address, key)
+    }
+
+    fn set_storage(&mut self, address: Address, key: H256, value: H256) {
+        self.storage.insert(key, value);
+        if let Some(step) = self.state.steps.last_mut() {
+            step.storage_changes.insert(key, value);
         }
+        self.inner.set_storage(address, key, value)
+    }
+
+    delegate_host!(journal);
+
+    fn step(&mut self, step: &Step<'_>) {
+        let stack: Vec<H256> = step.stack.iter().map(|item| {
+            let mut word = [0u8; 32];
+            item.to_big_endian(&mut word);
+            H256::from(word)
+        }).collect();
         
-        // Check opcode breakpoint
-        if let Some(ref bp_op) = breakpoint.opcode {
-            if format!("{:?}", op) != *bp_op {
-                return false;
+        let mut hit = false;
+        for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+            if should_break(breakpoint, step, &stack) {
+                self.state.breakpoints_hit.push(index);
+                hit = true;
             }
         }
-        
-        // Check condition
-        if let Some(ref condition) = breakpoint.condition {
-            if !self.evaluate_condition(condition, stack, memory) {
-                return false;
-            }
+        if hit {
+            self.state.pc = step.pc as u64;
+            self.state.op = format!("{:?}", step.opcode);
+            self.state.gas = U256::from(step.gas);
+            self.state.memory = step.memory.to_vec();
+            self.state.stack = stack;
+            self.state.storage = self.storage.clone();
+            self.state.depth = step.depth as usize;
         }
+        self.open.push(hit);
         
-        true
+        if self.state.steps.len() < MAX_STEPS {
+            self.state.steps.push(StepInfo {
+                pc: step.pc as u64,
+                op: format!("{:?}", step.opcode),
+                gas: U256::from(step.gas),
+                stack_size: step.stack.len(),
+                memory_size: step.memory.len(),
+                storage_changes: HashMap::new(),
+            });
+        }
+    }
+
+    fn step_end(&mut self, gas_cost: u64, _error: Option<&EvmError>) {
+        if self.open.pop() == Some(true) {
+            self.state.gas_cost = U256::from(gas_cost);
+        }
+    }
+}
+
+/// Check if should break at `step`, `stack` being its stack as words
+fn should_break(breakpoint: &Breakpoint, step: &Step<'_>, stack: &[H256]) -> bool {
+    // Check PC breakpoint
+    if let Some(bp_pc) = breakpoint.pc {
+        if step.pc as u64 != bp_pc {
+            return false;
+        }
     }
     
-    /// Evaluate breakpoint condition
-    fn evaluate_condition(&self, condition: &str, stack: &[H256], memory: &[u8]) -> bool {
-        // Simple condition evaluation
-        // Real implementation would parse and evaluate complex expressions
-        
-        if condition.starts_with("stack[0] == ") {
-            if let Some(value_str) = condition.strip_prefix("stack[0] == ") {
-                if !stack.is_empty() {
-                    if let Ok(value) = U256::from_dec_str(value_str) {
-                        return U256::from(stack[0].as_bytes()) == value;
-                    }
-                }
-            }
+    // Check opcode breakpoint
+    if let Some(ref bp_op) = breakpoint.opcode {
+        if format!("{:?}", step.opcode) != *bp_op {
+            return false;
         }
-        
-        // Default to true if condition cannot be evaluated
-        true
     }
     
-    fn create_context(&self, block: &Block) -> ethereum_evm::Context {
-        ethereum_evm::Context {
-            block_number: block.header.number,
-            timestamp: block.header.timestamp,
-            gas_limit: block.header.gas_limit,
-            coinbase: block.header.author,
-            difficulty: block.header.difficulty,
-            chain_id: 1,
+    // Check condition
+    if let Some(ref condition) = breakpoint.condition {
+        if !evaluate_condition(condition, stack) {
+            return false;
         }
     }
     
-    async fn get_state_at_block(&self, block_hash: &H256) -> Result<ethereum_trie::PatriciaTrie<D>> {
-        let key = format!("block:{}", hex::encode(block_hash));
-        let block_data = self.db.get(key.as_bytes())?
-            .ok_or(DebugError::BlockNotFound)?;
-        
-        let block: Block = bincode::deserialize(&block_data)
-            .map_err(|e| DebugError::ExecutionError(e.to_string()))?;
-        
-        Ok(ethereum_trie::PatriciaTrie::new_with_root(
-            self.db.clone(),
-            block.header.state_root,
-        ))
+    true
+}
+
+/// Evaluate breakpoint condition, `stack[0]` being the top of the stack
+fn evaluate_condition(condition: &str, stack: &[H256]) -> bool {
+    // Simple condition evaluation
+    // Real implementation would parse and evaluate complex expressions
+    
+    if let Some(value_str) = condition.strip_prefix("stack[0] == ") {
+        if let (Some(top), Ok(value)) = (stack.last(), U256::from_dec_str(value_str)) {
+            return U256::from(top.as_bytes()) == value;
+        }
     }
-}
\ No newline at end of file
+    
+    // Default to true if condition cannot be evaluated
+    true
+}
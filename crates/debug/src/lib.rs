@@ -1,10 +1,9 @@
 use ethereum_types::{H256, U256, Address};
-use ethereum_core::{Block, Transaction, Receipt};
+use ethereum_core::{Block, BlockId, BlockNumber, Transaction, Receipt};
 use ethereum_storage::Database;
-use ethereum_evm::{EVM, ExecutionResult};
-use ethereum_trie::PatriciaTrie;
+use ethereum_consensus::CheckpointStore;
+use ethereum_state::{Call, CallState, StateError, StateOverride, TrieStateProvider};
 use std::sync::Arc;
-use std::collections::HashMap;
 use thiserror::Error;
 use serde::{Serialize, Deserialize};
 
@@ -12,11 +11,20 @@ pub mod tracer;
 pub mod debugger;
 pub mod profiler;
 pub mod state_diff;
+pub mod bad_block;
+pub mod prestate;
 
-pub use tracer::{Tracer, TraceConfig, TraceResult, CallTrace};
+pub use tracer::{
+    BuiltinTracer, CallTrace, CallTracer, FourByteTracer, FrameTracer, StructLog, StructLogger,
+    TraceConfig, TraceResult, TraceType, Tracer, TracerConfig,
+};
 pub use debugger::{Debugger, Breakpoint, DebuggerState};
 pub use profiler::{Profiler, GasProfile, OpcodeStats};
 pub use state_diff::{StateDiff, AccountDiff, StorageDiff};
+pub use bad_block::{BadBlockTrace, BlockExecutor, BlockReplay, Divergence};
+pub use ethereum_state::BadBlock;
+pub use prestate::{PrestateAccount, PrestateDiff, PrestateTracer};
+pub use ethereum_evm::ChainConfig;
 
 #[derive(Debug, Error)]
 pub enum DebugError {
@@ -41,28 +49,44 @@ pub enum DebugError {
 
 pub type Result<T> = std::result::Result<T, DebugError>;
 
+/// Key holding the hash of the current canonical head
+const HEAD_KEY: &[u8] = b"canonical:head";
+
 /// Debug API implementation
 pub struct DebugAPI<D: Database> {
     db: Arc<D>,
-    evm: Arc<EVM<D>>,
     tracer: Tracer<D>,
     debugger: Debugger<D>,
     profiler: Profiler,
+    executor: Option<Arc<dyn BlockExecutor>>,
+    checkpoints: Arc<CheckpointStore>,
 }
 
 impl<D: Database + 'static> DebugAPI<D> {
     pub fn new(db: Arc<D>) -> Self {
-        let evm = Arc::new(EVM::new(db.clone()));
-        
         Self {
             db: db.clone(),
-            evm: evm.clone(),
-            tracer: Tracer::new(db.clone(), evm.clone()),
-            debugger: Debugger::new(db.clone(), evm.clone()),
+            tracer: Tracer::new(db.clone()),
+            debugger: Debugger::new(db.clone()),
             profiler: Profiler::new(),
+            executor: None,
+            checkpoints: Arc::new(CheckpointStore::new(db.clone())),
         }
     }
     
+    /// Chain the traced transactions belong to, mainnet by default
+    pub fn with_chain_config(mut self, chain_config: ChainConfig) -> Self {
+        self.tracer = self.tracer.with_chain_config(chain_config.clone());
+        self.debugger = self.debugger.with_chain_config(chain_config);
+        self
+    }
+    
+    /// Executor used to replay bad blocks against their parent state
+    pub fn with_block_executor(mut self, executor: Arc<dyn BlockExecutor>) -> Self {
+        self.executor = Some(executor);
+        self
+    }
+    
     /// Trace transaction execution
     pub async fn trace_transaction(
         &self,
@@ -98,24 +122,41 @@ impl<D: Database + 'static> DebugAPI<D> {
         self.trace_block(block_hash, config).await
     }
     
-    /// Trace call
+    /// Trace a call on top of block `block_number`, with `state_override`
+    /// layered over that block's state first
     pub async fn trace_call(
         &self,
         call: CallRequest,
         block_number: Option<U256>,
+        state_override: Option<StateOverride>,
         config: Option<TraceConfig>,
     ) -> Result<TraceResult> {
-        let block_num = block_number.unwrap_or_else(|| self.get_latest_block_number());
-        
-        // Create transaction from call request
-        let tx = self.call_to_transaction(call);
-        
-        // Get block for context
+        let config = config.unwrap_or_default();
+        let frame_tracer = FrameTracer::from_config(&config);
+        let block_num = match block_number {
+            Some(number) => number,
+            None => self.get_latest_block_number().await?,
+        };
         let block_hash = self.get_block_hash_by_number(block_num).await?;
         let block = self.get_block(block_hash).await?;
         
-        // Trace call
-        self.tracer.trace_transaction(&tx, &block, config).await
+        let provider = TrieStateProvider::new(self.db.clone());
+        let mut state = CallState::new(&provider, block.header.state_root);
+        if let Some(overrides) = &state_override {
+            ethereum_state::apply_state_override(&mut state, overrides).map_err(state_error)?;
+        }
+        
+        let context = self.tracer.block_context(&block)?;
+        let mut trace = None;
+        let call: ethereum_state::Call = call.into();
+        let before = tracer::accounts_before(&state, &context, call.from, call.to);
+        ethereum_state::execute_call_with(&mut state, &context, &call, |frame, host| {
+            let (result, traced) = frame_tracer.trace_from(frame, call.to.is_none(), host, &config, &before);
+            trace = Some(traced);
+            result
+        }).map_err(state_error)?;
+        
+        trace.ok_or_else(|| DebugError::ExecutionError("call did not run".to_string()))
     }
     
     /// Get transaction trace
@@ -146,34 +187,16 @@ impl<D: Database + 'static> DebugAPI<D> {
         position: H256,
         block_number: Option<U256>,
     ) -> Result<H256> {
-        let block_num = block_number.unwrap_or_else(|| self.get_latest_block_number());
+        let block_num = match block_number {
+            Some(number) => number,
+            None => self.get_latest_block_number().await?,
+        };
         
-        // Get state at block
+        // Same trie read as eth_getStorageAt
         let state_root = self.get_state_root_at_block(block_num).await?;
-        let state = PatriciaTrie::new_with_root(self.db.clone(), state_root);
-        
-        // Get account storage
-        let account_key = address.as_bytes();
-        let account_data = state.get(account_key).await
-            .map_err(|e| DebugError::ExecutionError(e.to_string()))?;
-        
-        if let Some(data) = account_data {
-            let account: ethereum_core::Account = bincode::deserialize(&data)
-                .map_err(|e| DebugError::ExecutionError(e.to_string()))?;
-            
-            // Get storage value
-            let storage_trie = PatriciaTrie::new_with_root(self.db.clone(), account.storage_root);
-            let value = storage_trie.get(position.as_bytes()).await
-                .map_err(|e| DebugError::ExecutionError(e.to_string()))?;
-            
-            if let Some(v) = value {
-                let bytes: [u8; 32] = v.try_into()
-                    .map_err(|_| DebugError::ExecutionError("Invalid storage value".to_string()))?;
-                return Ok(H256::from(bytes));
-            }
-        }
-        
-        Ok(H256::zero())
+        TrieStateProvider::new(self.db.clone())
+            .storage_at(state_root, &address, &position)
+            .map_err(|e| DebugError::ExecutionError(e.to_string()))
     }
     
     /// Get state diff for a transaction
@@ -182,10 +205,9 @@ impl<D: Database + 'static> DebugAPI<D> {
         
         // Execute transaction and track state changes
         let state_diff = state_diff::compute_state_diff(
+            &self.tracer,
             &tx,
             &block,
-            self.db.clone(),
-            self.evm.clone(),
         ).await?;
         
         Ok(state_diff)
@@ -195,7 +217,7 @@ impl<D: Database + 'static> DebugAPI<D> {
     pub async fn profile_transaction(&self, tx_hash: H256) -> Result<GasProfile> {
         let (tx, block) = self.get_transaction_and_block(tx_hash).await?;
         
-        let profile = self.profiler.profile_transaction(&tx, &block, self.evm.clone()).await?;
+        let profile = self.profiler.profile_transaction(&self.tracer, &tx, &block).await?;
         
         Ok(profile)
     }
@@ -204,35 +226,85 @@ impl<D: Database + 'static> DebugAPI<D> {
     pub async fn get_opcode_stats(&self, tx_hash: H256) -> Result<OpcodeStats> {
         let (tx, block) = self.get_transaction_and_block(tx_hash).await?;
         
-        let stats = self.profiler.get_opcode_stats(&tx, &block, self.evm.clone()).await?;
+        let stats = self.profiler.get_opcode_stats(&self.tracer, &tx, &block).await?;
         
         Ok(stats)
     }
     
-    /// Get bad blocks (blocks that failed validation)
-    pub async fn get_bad_blocks(&self) -> Result<Vec<Block>> {
-        let mut bad_blocks = Vec::new();
+    /// Get bad blocks (blocks that failed validation) with why they were rejected
+    pub async fn get_bad_blocks(&self) -> Result<Vec<BadBlock>> {
+        ethereum_state::bad_blocks(&*self.db).map_err(state_error)
+    }
+    
+    /// Record a block that failed validation, `reason` being the consensus
+    /// or state-root mismatch it was rejected for
+    pub fn record_bad_block(&self, block: &Block, reason: impl Into<String>) -> Result<()> {
+        ethereum_state::store_bad_block(&*self.db, block, reason).map_err(state_error)
+    }
+    
+    /// Re-trace a stored bad block on its parent state
+    ///
+    /// Returns the per-transaction traces together with the first header
+    /// commitment the re-execution disagrees with.
+    pub async fn trace_bad_block(
+        &self,
+        block_hash: H256,
+        config: Option<TraceConfig>,
+    ) -> Result<BadBlockTrace> {
+        let block = ethereum_state::load_bad_block(&*self.db, &block_hash)
+            .map_err(state_error)?
+            .ok_or(DebugError::BlockNotFound)?
+            .block;
+        let parent = self.get_block(block.header.parent_hash).await?;
         
-        // Iterate through bad blocks storage
-        let prefix = b"bad_block:";
-        let iter = self.db.iter_prefix(prefix)?;
+        let executor = self.executor.as_ref()
+            .ok_or_else(|| DebugError::ExecutionError("No block executor configured".to_string()))?;
         
-        for (_, value) in iter {
-            if let Ok(block) = bincode::deserialize::<Block>(&value) {
-                bad_blocks.push(block);
-            }
-        }
+        let traces = self.tracer.trace_block(&block, config).await?;
+        let replay = executor.execute(&parent, &block)?;
         
-        Ok(bad_blocks)
+        Ok(BadBlockTrace {
+            block_hash,
+            traces,
+            divergence: bad_block::find_divergence(&block.header, &replay),
+        })
     }
     
     /// Get block RLP
     pub async fn get_block_rlp(&self, block_hash: H256) -> Result<Vec<u8>> {
         let block = self.get_block(block_hash).await?;
         
-        // Serialize block to RLP
-        bincode::serialize(&block)
-            .map_err(|e| DebugError::ExecutionError(e.to_string()))
+        Ok(ethereum_rlp::encode(&block).as_slice().to_vec())
+    }
+    
+    /// debug_getRawHeader: hex-encoded RLP of the block header
+    pub async fn get_raw_header(&self, block_id: BlockId) -> Result<String> {
+        let block = self.resolve_block(block_id).await?;
+        Ok(to_hex(ethereum_rlp::encode(&block.header).as_slice()))
+    }
+    
+    /// debug_getRawBlock: hex-encoded RLP of the whole block
+    pub async fn get_raw_block(&self, block_id: BlockId) -> Result<String> {
+        let block = self.resolve_block(block_id).await?;
+        Ok(to_hex(ethereum_rlp::encode(&block).as_slice()))
+    }
+    
+    /// debug_getRawReceipts: EIP-2718 encoded receipts of the block, in order
+    pub async fn get_raw_receipts(&self, block_id: BlockId) -> Result<Vec<String>> {
+        let block_hash = self.resolve_block_hash(&block_id).await?;
+        
+        let key = format!("receipts:{}", hex::encode(block_hash));
+        let receipts: Vec<Receipt> = match self.db.get(key.as_bytes())? {
+            Some(data) => bincode::deserialize(&data)
+                .map_err(|e| DebugError::ExecutionError(e.to_string()))?,
+            None => {
+                // A block without stored receipts must still exist
+                self.get_block(block_hash).await?;
+                Vec::new()
+            }
+        };
+        
+        Ok(receipts.iter().map(|receipt| to_hex(&receipt.encoded_2718())).collect())
     }
     
     /// Print block
@@ -246,20 +318,7 @@ impl<D: Database + 'static> DebugAPI<D> {
     
     /// Get chain config
     pub async fn get_chain_config(&self) -> ChainConfig {
-        ChainConfig {
-            chain_id: 1,
-            homestead_block: Some(U256::from(1_150_000)),
-            dao_fork_block: Some(U256::from(1_920_000)),
-            eip150_block: Some(U256::from(2_463_000)),
-            eip158_block: Some(U256::from(2_675_000)),
-            byzantium_block: Some(U256::from(4_370_000)),
-            constantinople_block: Some(U256::from(7_280_000)),
-            petersburg_block: Some(U256::from(7_280_000)),
-            istanbul_block: Some(U256::from(9_069_000)),
-            berlin_block: Some(U256::from(12_244_000)),
-            london_block: Some(U256::from(12_965_000)),
-            merge_block: Some(U256::from(15_537_394)),
-        }
+        self.tracer.chain_config().clone()
     }
     
     // Helper methods
@@ -293,17 +352,64 @@ impl<D: Database + 'static> DebugAPI<D> {
             .map_err(|e| DebugError::ExecutionError(e.to_string()))
     }
     
+    async fn resolve_block(&self, block_id: BlockId) -> Result<Block> {
+        let block_hash = self.resolve_block_hash(&block_id).await?;
+        self.get_block(block_hash).await
+    }
+    
+    /// Hash of the block `block_id` refers to, "pending" being the head
+    async fn resolve_block_hash(&self, block_id: &BlockId) -> Result<H256> {
+        let number = match block_id {
+            BlockId::Hash { block_hash, require_canonical } => {
+                if *require_canonical {
+                    let block = self.get_block(*block_hash).await?;
+                    if self.get_block_hash_by_number(block.header.number).await? != *block_hash {
+                        return Err(DebugError::BlockNotFound);
+                    }
+                }
+                return Ok(*block_hash);
+            }
+            BlockId::Number(number) => number,
+        };
+        
+        let checkpoint = match number {
+            BlockNumber::Latest | BlockNumber::Pending => None,
+            BlockNumber::Finalized => Some(self.checkpoints.finalized()),
+            BlockNumber::Safe => Some(self.checkpoints.safe()),
+            BlockNumber::Earliest => return self.get_block_hash_by_number(U256::zero()).await,
+            BlockNumber::Number(number) => return self.get_block_hash_by_number(*number).await,
+        };
+        if let Some(checkpoint) = checkpoint {
+            return checkpoint
+                .map_err(|e| DebugError::ExecutionError(e.to_string()))?
+                .map(|checkpoint| checkpoint.hash)
+                .ok_or(DebugError::BlockNotFound);
+        }
+        let data = self.db.get(HEAD_KEY)?
+            .ok_or(DebugError::BlockNotFound)?;
+        if data.len() < 32 {
+            return Err(DebugError::ExecutionError("corrupt block hash entry".to_string()));
+        }
+        Ok(H256::from_slice(&data[..32]))
+    }
+    
     async fn get_block_hash_by_number(&self, block_number: U256) -> Result<H256> {
-        let key = format!("block:number:{}", block_number);
+        let key = format!("number:{}", block_number);
         let data = self.db.get(key.as_bytes())?
             .ok_or(DebugError::BlockNotFound)?;
         
         Ok(H256::from_slice(&data))
     }
     
-    fn get_latest_block_number(&self) -> U256 {
-        // Get from database
-        U256::zero()
+    async fn get_latest_block_number(&self) -> Result<U256> {
+        let data = self.db.get(HEAD_KEY)?
+            .ok_or(DebugError::BlockNotFound)?;
+        if data.len() < 32 {
+            return Err(DebugError::ExecutionError("corrupt head hash entry".to_string()));
+        }
+        
+        let head = self.get_block(H256::from_slice(&data[..32])).await?;
+        Ok(head.header.number)
     }
     
     async fn get_state_root_at_block(&self, block_number: U256) -> Result<H256> {
@@ -311,23 +417,14 @@ impl<D: Database + 'static> DebugAPI<D> {
         let block = self.get_block(block_hash).await?;
         Ok(block.header.state_root)
     }
-    
-    fn call_to_transaction(&self, call: CallRequest) -> Transaction {
-        Transaction {
-            nonce: 0,
-            gas_price: call.gas_price,
-            gas_limit: call.gas.unwrap_or(U256::from(8_000_000)),
-            to: call.to,
-            value: call.value.unwrap_or_else(U256::zero),
-            input: call.data.unwrap_or_default(),
-            signature: Default::default(),
-            transaction_type: 0,
-            chain_id: Some(1),
-            access_list: None,
-            max_fee_per_gas: None,
-            max_priority_fee_per_gas: None,
-        }
-    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    format!("0x{}", hex::encode(data))
+}
+
+fn state_error(error: StateError) -> DebugError {
+    DebugError::ExecutionError(error.to_string())
 }
 
 /// Call request for debug_traceCall
@@ -341,45 +438,295 @@ pub struct CallRequest {
     pub data: Option<Vec<u8>>,
 }
 
-/// Chain configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ChainConfig {
-    pub chain_id: u64,
-    pub homestead_block: Option<U256>,
-    pub dao_fork_block: Option<U256>,
-    pub eip150_block: Option<U256>,
-    pub eip158_block: Option<U256>,
-    pub byzantium_block: Option<U256>,
-    pub constantinople_block: Option<U256>,
-    pub petersburg_block: Option<U256>,
-    pub istanbul_block: Option<U256>,
-    pub berlin_block: Option<U256>,
-    pub london_block: Option<U256>,
-    pub merge_block: Option<U256>,
+impl From<CallRequest> for Call {
+    fn from(call: CallRequest) -> Self {
+        Call {
+            from: call.from.unwrap_or_default(),
+            to: call.to,
+            gas: call.gas,
+            gas_price: call.gas_price.unwrap_or_default(),
+            value: call.value.unwrap_or_default(),
+            data: call.data.unwrap_or_default(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ethereum_core::Header;
+    use ethereum_state::AccountOverride;
+    use ethereum_types::H160;
+    use ethereum_storage::MemoryDatabase;
+    use std::collections::HashMap;
+    
+    #[tokio::test]
+    async fn test_raw_header_roundtrip() {
+        let db = Arc::new(MemoryDatabase::new());
+        
+        let mut header = Header::new();
+        header.number = U256::from(7);
+        header.gas_limit = U256::from(30_000_000);
+        header.timestamp = 1_700_000_000;
+        header.nonce = 0x42;
+        header.base_fee_per_gas = Some(U256::from(1_000_000_000u64));
+        let block = Block::new(header.clone());
+        let hash = block.hash();
+        
+        db.put(format!("block:{}", hex::encode(hash)).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+        db.put(format!("number:{}", header.number).as_bytes(), hash.as_bytes()).unwrap();
+        
+        let api = DebugAPI::new(db.clone());
+        
+        db.put(HEAD_KEY, hash.as_bytes()).unwrap();
+        
+        let block_ids = [
+            BlockId::from(hash),
+            BlockId::from(BlockNumber::Number(header.number)),
+            BlockId::from(BlockNumber::Latest),
+        ];
+        for block_id in block_ids {
+            let raw = api.get_raw_header(block_id).await.unwrap();
+            let bytes = hex::decode(raw.trim_start_matches("0x")).unwrap();
+            
+            let decoded: Header = ethereum_rlp::decode(&bytes).unwrap();
+            assert_eq!(decoded, header);
+            assert_eq!(ethereum_crypto::keccak256(&bytes), hash);
+        }
+        
+        assert!(api.get_raw_receipts(BlockId::from(hash)).await.unwrap().is_empty());
+        assert!(matches!(
+            api.get_raw_header(BlockId::from(BlockNumber::Number(U256::from(8)))).await,
+            Err(DebugError::BlockNotFound)
+        ));
+    }
     
-    #[test]
-    fn test_chain_config() {
-        let config = ChainConfig {
-            chain_id: 1,
-            homestead_block: Some(U256::from(1_150_000)),
-            dao_fork_block: None,
-            eip150_block: None,
-            eip158_block: None,
-            byzantium_block: None,
-            constantinople_block: None,
-            petersburg_block: None,
-            istanbul_block: None,
-            berlin_block: None,
-            london_block: None,
-            merge_block: None,
+    #[tokio::test]
+    async fn test_latest_block_number_follows_head() {
+        let db = Arc::new(MemoryDatabase::new());
+        let api = DebugAPI::new(db.clone());
+        assert!(matches!(api.get_latest_block_number().await, Err(DebugError::BlockNotFound)));
+        
+        let mut hashes = Vec::new();
+        for number in 0..3u64 {
+            let mut header = Header::new();
+            header.number = U256::from(number);
+            let block = Block::new(header);
+            let hash = block.hash();
+            db.put(format!("block:{}", hex::encode(hash)).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+            db.put(format!("number:{}", number).as_bytes(), hash.as_bytes()).unwrap();
+            hashes.push(hash);
+        }
+        
+        db.put(HEAD_KEY, hashes[2].as_bytes()).unwrap();
+        assert_eq!(api.get_latest_block_number().await.unwrap(), U256::from(2));
+        
+        // A reorg back to an earlier block moves the head with it
+        db.put(HEAD_KEY, hashes[1].as_bytes()).unwrap();
+        assert_eq!(api.get_latest_block_number().await.unwrap(), U256::one());
+    }
+    
+    /// Empty-state block 0 stored as the canonical head
+    fn store_genesis(db: &MemoryDatabase) {
+        let mut header = Header::new();
+        header.state_root = ethereum_trie::empty_root();
+        header.gas_limit = U256::from(30_000_000);
+        let block = Block::new(header);
+        let hash = block.hash();
+        db.put(format!("block:{}", hex::encode(hash)).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+        db.put(b"number:0", hash.as_bytes()).unwrap();
+        db.put(HEAD_KEY, hash.as_bytes()).unwrap();
+    }
+    
+    fn call_to(contract: Address) -> CallRequest {
+        CallRequest {
+            from: Some(Address::from_bytes([0x01; 20])),
+            to: Some(contract),
+            gas: Some(U256::from(100_000)),
+            gas_price: None,
+            value: None,
+            data: None,
+        }
+    }
+    
+    fn code_override(code: &[u8]) -> AccountOverride {
+        AccountOverride { code: Some(to_hex(code)), ..Default::default() }
+    }
+    
+    #[tokio::test]
+    async fn test_trace_call_runs_overridden_code() {
+        let db = Arc::new(MemoryDatabase::new());
+        store_genesis(&db);
+        let api = DebugAPI::new(db);
+        let contract = Address::from_bytes([0x02; 20]);
+        let key = H160::from(contract.to_bytes());
+        
+        // Nothing is deployed at the contract, so the call runs no code
+        let trace = api.trace_call(call_to(contract), None, None, None).await.unwrap();
+        let TraceResult::StructLogs(logs) = trace else { panic!("expected struct logs") };
+        assert!(logs.struct_logs.is_empty());
+        
+        // PUSH1 0x2a, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, RETURN
+        let code = [0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+        let overrides = StateOverride::from([(key, code_override(&code))]);
+        let trace = api.trace_call(call_to(contract), None, Some(overrides), None).await.unwrap();
+        let TraceResult::StructLogs(logs) = trace else { panic!("expected struct logs") };
+        let ops: Vec<_> = logs.struct_logs.iter().map(|log| log.op.as_str()).collect();
+        assert_eq!(ops, vec!["PUSH1", "PUSH1", "MSTORE", "PUSH1", "PUSH1", "RETURN"]);
+        assert_eq!(U256::from(&logs.return_value[..]), U256::from(0x2a));
+    }
+    
+    #[tokio::test]
+    async fn test_trace_call_sees_configured_chain_id() {
+        let db = Arc::new(MemoryDatabase::new());
+        store_genesis(&db);
+        let sepolia = ChainConfig { chain_id: 11155111, ..ChainConfig::mainnet() };
+        let api = DebugAPI::new(db).with_chain_config(sepolia);
+        let contract = Address::from_bytes([0x02; 20]);
+        
+        // CHAINID, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, RETURN
+        let code = [0x46, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+        let overrides = StateOverride::from([(H160::from(contract.to_bytes()), code_override(&code))]);
+        let trace = api.trace_call(call_to(contract), None, Some(overrides), None).await.unwrap();
+        let TraceResult::StructLogs(logs) = trace else { panic!("expected struct logs") };
+        assert_eq!(U256::from(&logs.return_value[..]), U256::from(11155111));
+        assert_eq!(api.get_chain_config().await.chain_id, 11155111);
+    }
+    
+    #[tokio::test]
+    async fn test_trace_call_state_diff_override() {
+        let db = Arc::new(MemoryDatabase::new());
+        store_genesis(&db);
+        let api = DebugAPI::new(db);
+        let contract = Address::from_bytes([0x02; 20]);
+        
+        // PUSH1 0x01, SLOAD, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, RETURN
+        let mut account = code_override(&[0x60, 0x01, 0x54, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3]);
+        account.state_diff = Some(HashMap::from([(H256::from_low_u64_be(1), H256::from_low_u64_be(9))]));
+        let overrides = StateOverride::from([(H160::from(contract.to_bytes()), account)]);
+        
+        let config = TraceConfig { tracer: Some(BuiltinTracer::PrestateTracer), ..Default::default() };
+        let trace = api.trace_call(call_to(contract), None, Some(overrides), Some(config)).await.unwrap();
+        let TraceResult::Custom(prestate) = trace else { panic!("expected a prestate") };
+        let key = serde_json::to_value(contract).unwrap();
+        let storage = &prestate[key.as_str().unwrap()]["storage"];
+        let slot = serde_json::to_value(H256::from_low_u64_be(1)).unwrap();
+        assert_eq!(storage[slot.as_str().unwrap()], serde_json::to_value(H256::from_low_u64_be(9)).unwrap());
+
+    }
+
+    #[tokio::test]
+    async fn test_trace_call_prestate_before_value_transfer() {
+        let db = Arc::new(MemoryDatabase::new());
+        store_genesis(&db);
+        let api = DebugAPI::new(db);
+        let sender = Address::from_bytes([0x01; 20]);
+        let contract = Address::from_bytes([0x02; 20]);
+
+        let overrides = StateOverride::from([
+            (H160::from(sender.to_bytes()), AccountOverride { balance: Some(U256::from(1000)), ..Default::default() }),
+            (H160::from(contract.to_bytes()), AccountOverride { balance: Some(U256::from(5)), ..code_override(&[0x00]) }),
+        ]);
+        let call = CallRequest { value: Some(U256::from(10)), ..call_to(contract) };
+        let config = TraceConfig { tracer: Some(BuiltinTracer::PrestateTracer), ..Default::default() };
+        let trace = api.trace_call(call, None, Some(overrides), Some(config)).await.unwrap();
+        let TraceResult::Custom(prestate) = trace else { panic!("expected a prestate") };
+
+        let balance = |address: Address| {
+            let key = serde_json::to_value(address).unwrap();
+            prestate[key.as_str().unwrap()]["balance"].clone()
         };
+        assert_eq!(balance(sender), serde_json::to_value(U256::from(1000)).unwrap());
+        assert_eq!(balance(contract), serde_json::to_value(U256::from(5)).unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_trace_call_with_call_tracer() {
+        let db = Arc::new(MemoryDatabase::new());
+        store_genesis(&db);
+        let api = DebugAPI::new(db);
+        let contract = Address::from_bytes([0x02; 20]);
+        
+        // PUSH1 0x00, PUSH1 0x00, REVERT
+        let overrides = StateOverride::from([(H160::from(contract.to_bytes()), code_override(&[0x60, 0x00, 0x60, 0x00, 0xfd]))]);
+        let config = TraceConfig { tracer: Some(BuiltinTracer::CallTracer), ..Default::default() };
+        let trace = api.trace_call(call_to(contract), None, Some(overrides), Some(config)).await.unwrap();
+        let TraceResult::CallTrace(trace) = trace else { panic!("expected a call trace") };
+        assert_eq!(trace.trace_type, TraceType::Call);
+        assert_eq!(trace.from, Address::from_bytes([0x01; 20]));
+        assert_eq!(trace.to, Some(contract));
+        assert_eq!(trace.gas_used, U256::from(6));
+        assert_eq!(trace.error.as_deref(), Some("execution reverted"));
+        assert!(trace.calls.is_empty());
+    }
+    
+    /// Replays every block to a fixed state root with no receipts
+    struct FixedRootExecutor(H256);
+    
+    impl BlockExecutor for FixedRootExecutor {
+        fn execute(&self, _parent: &Block, _block: &Block) -> Result<BlockReplay> {
+            Ok(BlockReplay { state_root: self.0, receipts: Vec::new() })
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_trace_bad_block_reports_state_root_mismatch() {
+        let db = Arc::new(MemoryDatabase::new());
+        
+        let parent = Block::new(Header::new());
+        let parent_hash = parent.hash();
+        db.put(format!("block:{}", hex::encode(parent_hash)).as_bytes(), &bincode::serialize(&parent).unwrap()).unwrap();
+        
+        let mut header = Header::new();
+        header.parent_hash = parent_hash;
+        header.number = U256::one();
+        header.receipts_root = ethereum_trie::empty_root();
+        header.state_root = H256::repeat_byte(0x01);
+        let bad = Block::new(header);
+        ethereum_state::store_bad_block(&*db, &bad, "invalid state root").unwrap();
+        
+        let actual = H256::repeat_byte(0x02);
+        let api = DebugAPI::new(db).with_block_executor(Arc::new(FixedRootExecutor(actual)));
+        
+        let trace = api.trace_bad_block(bad.hash(), None).await.unwrap();
+        assert_eq!(trace.block_hash, bad.hash());
+        assert!(trace.traces.is_empty());
+        assert_eq!(trace.divergence, Some(Divergence::StateRoot {
+            expected: H256::repeat_byte(0x01),
+            actual,
+        }));
+        
+        assert!(matches!(
+            api.trace_bad_block(parent_hash, None).await,
+            Err(DebugError::BlockNotFound)
+        ));
+    }
+    
+    #[tokio::test]
+    async fn test_bad_blocks_keep_their_reason() {
+        let db = Arc::new(MemoryDatabase::new());
+        let api = DebugAPI::new(db);
+        assert!(api.get_bad_blocks().await.unwrap().is_empty());
+        
+        let mut header = Header::new();
+        header.number = U256::from(9);
+        let block = Block::new(header);
+        let reason = "state root mismatch: expected 0x01.., got 0x02..";
+        api.record_bad_block(&block, reason).unwrap();
+        
+        let bad_blocks = api.get_bad_blocks().await.unwrap();
+        assert_eq!(bad_blocks.len(), 1);
+        assert_eq!(bad_blocks[0].block.hash(), block.hash());
+        assert_eq!(bad_blocks[0].reason, reason);
+        assert!(bad_blocks[0].rejected_at > 0);
+    }
+    
+    #[tokio::test]
+    async fn test_chain_config() {
+        let api = DebugAPI::new(Arc::new(MemoryDatabase::new()));
+        let config = api.get_chain_config().await;
         
         assert_eq!(config.chain_id, 1);
+        assert_eq!(config.homestead_block, Some(1_150_000));
     }
 }
\ No newline at end of file
@@ -0,0 +1,304 @@
+use ethereum_types::{H256, U256, Address};
+use ethereum_evm::execution::{BlockContext, Log};
+use ethereum_evm::{Account, Checkpoint, Host};
+use crate::tracer::delegate_host;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use serde::{Serialize, Serializer};
+
+/// Account as the prestate tracer reports it, only the storage slots the
+/// transaction touched are included
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PrestateAccount {
+    pub balance: U256,
+    pub nonce: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty", serialize_with = "serialize_hex")]
+    pub code: Vec<u8>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<H256, H256>,
+}
+
+impl PrestateAccount {
+    fn from_account(account: Account) -> Self {
+        Self {
+            balance: account.balance,
+            nonce: account.nonce,
+            code: account.code,
+            storage: BTreeMap::new(),
+        }
+    }
+}
+
+/// Accounts a transaction changed, before and after it ran
+///
+/// Only changed slots are listed. Accounts the transaction created are
+/// missing from `pre`, the ones it destroyed are missing from `post`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PrestateDiff {
+    pub pre: BTreeMap<Address, PrestateAccount>,
+    pub post: BTreeMap<Address, PrestateAccount>,
+}
+
+fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+}
+
+/// Host wrapper that records every account and storage slot a transaction
+/// reads or writes, at the value it had before the transaction
+///
+/// Writes record the value they replace before going through, so whatever is
+/// recorded first for an entry is its original value even if it is later
+/// changed and reverted.
+pub struct PrestateTracer<'h, H: Host> {
+    inner: &'h mut H,
+    pre: RefCell<BTreeMap<Address, PrestateAccount>>,
+    /// Touched accounts that did not exist yet
+    missing: RefCell<BTreeSet<Address>>,
+}
+
+impl<'h, H: Host> PrestateTracer<'h, H> {
+    pub fn new(inner: &'h mut H) -> Self {
+        Self {
+            inner,
+            pre: RefCell::new(BTreeMap::new()),
+            missing: RefCell::new(BTreeSet::new()),
+        }
+    }
+
+    /// Include `address` whether or not execution reads it, like the sender
+    /// or the block's coinbase
+    pub fn touch(&self, address: &Address) {
+        if !self.pre.borrow().contains_key(address) {
+            self.record(*address, self.inner.get_account(address));
+        }
+    }
+
+    /// Include `address` as `account`, read before the transaction moved
+    /// anything, `None` if it did not exist yet
+    pub fn record(&self, address: Address, account: Option<Account>) {
+        if self.pre.borrow().contains_key(&address) {
+            return;
+        }
+        let account = match account {
+            Some(account) => PrestateAccount::from_account(account),
+            None => {
+                self.missing.borrow_mut().insert(address);
+                PrestateAccount::default()
+            }
+        };
+        self.pre.borrow_mut().insert(address, account);
+    }
+
+    fn touch_slot(&self, address: &Address, key: &H256) {
+        self.touch(address);
+        let mut pre = self.pre.borrow_mut();
+        if let Some(account) = pre.get_mut(address) {
+            if !account.storage.contains_key(key) {
+                account.storage.insert(*key, self.inner.get_storage(address, key));
+            }
+        }
+    }
+
+    /// Every touched account at its value before the transaction
+    pub fn into_prestate(self) -> BTreeMap<Address, PrestateAccount> {
+        self.pre.into_inner()
+    }
+
+    /// Touched accounts that the transaction changed, before and after
+    pub fn into_diff(self) -> PrestateDiff {
+        let missing = self.missing.into_inner();
+        let mut diff = PrestateDiff::default();
+
+        for (address, mut before) in self.pre.into_inner() {
+            let existed = !missing.contains(&address);
+            let Some(account) = self.inner.get_account(&address) else {
+                if existed {
+                    diff.pre.insert(address, before);
+                }
+                continue;
+            };
+
+            let mut after = PrestateAccount::from_account(account);
+            for (key, value) in &before.storage {
+                let current = self.inner.get_storage(&address, key);
+                if current != *value {
+                    after.storage.insert(*key, current);
+                }
+            }
+            before.storage.retain(|key, _| after.storage.contains_key(key));
+
+            let changed = !existed
+                || before.balance != after.balance
+                || before.nonce != after.nonce
+                || before.code != after.code
+                || !after.storage.is_empty();
+            if !changed {
+                continue;
+            }
+            if existed {
+                diff.pre.insert(address, before);
+            }
+            diff.post.insert(address, after);
+        }
+
+        diff
+    }
+}
+
+impl<'h, H: Host> Host for PrestateTracer<'h, H> {
+    fn get_account(&self, address: &Address) -> Option<Account> {
+        self.touch(address);
+        self.inner.get_account(address)
+    }
+
+    fn set_account(&mut self, address: Address, account: Account) {
+        self.touch(&address);
+        self.inner.set_account(address, account)
+    }
+
+    fn remove_account(&mut self, address: &Address) {
+        self.touch(address);
+        self.inner.remove_account(address)
+    }
+
+    fn is_empty(&self, address: &Address) -> bool {
+        self.touch(address);
+        self.inner.is_empty(address)
+    }
+
+    fn is_created(&self, address: &Address) -> bool {
+        self.inner.is_created(address)
+    }
+
+    fn get_storage(&self, address: &Address, key: &H256) -> H256 {
+        self.touch_slot(address, key);
+        self.inner.get_storage(address, key)
+    }
+
+    fn set_storage(&mut self, address: Address, key: H256, value: H256) {
+        self.touch_slot(&address, &key);
+        self.inner.set_storage(address, key, value)
+    }
+
+    delegate_host!(journal);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::Header;
+    use ethereum_evm::{ChainConfig, ExecutionContext, Interpreter, JournaledState};
+    use std::collections::HashMap;
+
+    const CONTRACT: [u8; 20] = [0x02; 20];
+
+    fn slot(n: u64) -> H256 {
+        H256::from_low_u64_be(n)
+    }
+
+    /// Contract whose slot 0 holds 7 and slot 1 holds 1, running `code`
+    fn run_traced<T>(
+        code: Vec<u8>,
+        finish: impl FnOnce(PrestateTracer<'_, JournaledState<'_, HashMap<Address, Account>>>) -> T,
+    ) -> T {
+        let contract = Address::from_bytes(CONTRACT);
+        let mut accounts = HashMap::new();
+        let mut account = Account {
+            balance: U256::from(100),
+            nonce: 1,
+            code: code.clone(),
+            ..Default::default()
+        };
+        account.storage.insert(slot(0), slot(7));
+        account.storage.insert(slot(1), slot(1));
+        accounts.insert(contract, account);
+
+        let block = BlockContext::from_header(&Header::new(), &ChainConfig::mainnet(), Vec::new());
+        let context = ExecutionContext::new(
+            Address::from_bytes([0x01; 20]),
+            contract,
+            U256::zero(),
+            code,
+            Vec::new(),
+            1_000_000,
+            block,
+        );
+
+        let mut state = JournaledState::new(&mut accounts);
+        let mut tracer = PrestateTracer::new(&mut state);
+        tracer.touch(&Address::from_bytes([0x01; 20]));
+        Interpreter::new(context, &mut tracer).run().unwrap();
+        finish(tracer)
+    }
+
+    // PUSH1 0x00, SLOAD, PUSH1 0x02, SSTORE, PUSH1 0x05, PUSH1 0x01, SSTORE, STOP
+    fn copy_and_write() -> Vec<u8> {
+        vec![0x60, 0x00, 0x54, 0x60, 0x02, 0x55, 0x60, 0x05, 0x60, 0x01, 0x55, 0x00]
+    }
+
+    #[test]
+    fn test_prestate_records_reads_and_writes_before_execution() {
+        let prestate = run_traced(copy_and_write(), |tracer| tracer.into_prestate());
+        let contract = Address::from_bytes(CONTRACT);
+
+        let account = &prestate[&contract];
+        assert_eq!(account.balance, U256::from(100));
+        assert_eq!(account.nonce, 1);
+        assert_eq!(account.code, copy_and_write());
+        let storage: Vec<_> = account.storage.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(storage, vec![(slot(0), slot(7)), (slot(1), slot(1)), (slot(2), H256::zero())]);
+
+        // The sender never existed but is still part of the prestate
+        let sender = &prestate[&Address::from_bytes([0x01; 20])];
+        assert_eq!(*sender, PrestateAccount::default());
+
+        let json = serde_json::to_value(&prestate).unwrap();
+        let key = serde_json::to_value(contract).unwrap();
+        let entry = &json[key.as_str().unwrap()];
+        assert_eq!(entry["balance"], "0x64");
+        assert_eq!(entry["nonce"], 1);
+        assert_eq!(entry["code"], format!("0x{}", hex::encode(copy_and_write())));
+        assert_eq!(entry["storage"].as_object().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_prestate_includes_slots_only_read() {
+        // PUSH1 0x01, SLOAD, PUSH1 0x09, SLOAD, STOP
+        let code = vec![0x60, 0x01, 0x54, 0x60, 0x09, 0x54, 0x00];
+        let prestate = run_traced(code, |tracer| tracer.into_prestate());
+
+        // Both slots appear at their original value, the unset one as zero
+        let storage: Vec<_> = prestate[&Address::from_bytes(CONTRACT)]
+            .storage
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect();
+        assert_eq!(storage, vec![(slot(1), slot(1)), (slot(9), H256::zero())]);
+
+        // Nothing was written, so there is nothing to diff
+        let code = vec![0x60, 0x01, 0x54, 0x60, 0x09, 0x54, 0x00];
+        assert_eq!(run_traced(code, |tracer| tracer.into_diff()), PrestateDiff::default());
+    }
+
+    #[test]
+    fn test_prestate_diff_lists_only_changes() {
+        let diff = run_traced(copy_and_write(), |tracer| tracer.into_diff());
+        let contract = Address::from_bytes(CONTRACT);
+
+        // Slot 0 was only read, the untouched sender is left out
+        assert_eq!(diff.pre.keys().collect::<Vec<_>>(), vec![&contract]);
+        assert_eq!(diff.post.keys().collect::<Vec<_>>(), vec![&contract]);
+        let before: Vec<_> = diff.pre[&contract].storage.iter().map(|(k, v)| (*k, *v)).collect();
+        let after: Vec<_> = diff.post[&contract].storage.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(before, vec![(slot(1), slot(1)), (slot(2), H256::zero())]);
+        assert_eq!(after, vec![(slot(1), slot(5)), (slot(2), slot(7))]);
+
+        // Writes that are reverted leave nothing to report
+        let mut reverting = copy_and_write();
+        // ..., PUSH1 0x00, PUSH1 0x00, REVERT
+        *reverting.last_mut().unwrap() = 0x60;
+        reverting.extend_from_slice(&[0x00, 0x60, 0x00, 0xfd]);
+        let diff = run_traced(reverting, |tracer| tracer.into_diff());
+        assert_eq!(diff, PrestateDiff::default());
+    }
+}
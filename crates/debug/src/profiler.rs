@@ -1,11 +1,14 @@
 use ethereum_types::{H256, U256, Address};
 use ethereum_core::{Block, Transaction};
-use ethereum_evm::{EVM, Opcode};
-use std::sync::Arc;
+use ethereum_storage::Database;
+use ethereum_evm::execution::{BlockContext, ExecutionResult, Log};
+use ethereum_evm::opcodes::Opcode;
+use ethereum_evm::{run_interpreter, Account, Checkpoint, EvmError, Frame, Host, Step};
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
-use crate::{Result, DebugError};
+use crate::tracer::{apply_traced, delegate_host, Tracer};
+use crate::Result;
 
 /// Gas profiling results
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,7 +35,7 @@ pub struct CallCost {
 }
 
 /// Storage operation costs
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StorageCosts {
     pub sload_count: u64,
@@ -72,6 +75,7 @@ pub struct HotSpot {
 }
 
 /// Gas profiler
+#[derive(Default)]
 pub struct Profiler;
 
 impl Profiler {
@@ -80,129 +84,41 @@ impl Profiler {
     }
     
     /// Profile transaction gas usage
-    pub async fn profile_transaction<D: ethereum_storage::Database>(
+    pub async fn profile_transaction<D: Database + 'static>(
         &self,
+        tracer: &Tracer<D>,
         tx: &Transaction,
         block: &Block,
-        evm: Arc<EVM<D>>,
     ) -> Result<GasProfile> {
-        let context = self.create_context(block);
-        
-        // Calculate intrinsic gas
-        let intrinsic_gas = self.calculate_intrinsic_gas(tx);
-        
-        let mut opcode_costs = HashMap::new();
-        let mut call_costs = Vec::new();
-        let mut storage_costs = StorageCosts {
-            sload_count: 0,
-            sload_gas: U256::zero(),
-            sstore_count: 0,
-            sstore_gas: U256::zero(),
-            storage_refund: U256::zero(),
-        };
-        
-        let mut last_gas = tx.gas_limit;
-        
-        // Execute transaction with profiling
-        let result = evm.execute_transaction_with_tracer(
-            tx,
-            self.get_state(block),
-            &context,
-            |pc, op, stack, memory, storage| {
-                let gas_cost = last_gas - evm.get_gas_left();
-                
-                // Track opcode costs
-                let op_str = format!("{:?}", op);
-                *opcode_costs.entry(op_str.clone()).or_insert(U256::zero()) += gas_cost;
-                
-                // Track storage operations
-                match op {
-                    Opcode::SLOAD => {
-                        storage_costs.sload_count += 1;
-                        storage_costs.sload_gas += gas_cost;
-                    }
-                    Opcode::SSTORE => {
-                        storage_costs.sstore_count += 1;
-                        storage_costs.sstore_gas += gas_cost;
-                    }
-                    _ => {}
-                }
-                
-                // Track calls
-                match op {
-                    Opcode::CALL | Opcode::CALLCODE | Opcode::DELEGATECALL | Opcode::STATICCALL => {
-                        if stack.len() >= 2 {
-                            let gas_provided = U256::from(stack[0].as_bytes());
-                            call_costs.push(CallCost {
-                                call_type: op_str,
-                                target: None, // Would extract from stack
-                                gas_provided,
-                                gas_used: gas_cost,
-                                depth: evm.get_call_depth(),
-                            });
-                        }
-                    }
-                    _ => {}
-                }
-                
-                last_gas = evm.get_gas_left();
-            }
-        ).await.map_err(|e| DebugError::EvmError(e.to_string()))?;
-        
-        let execution_gas = result.gas_used - intrinsic_gas;
+        let (result, profile) = run_profiled(tracer, tx, block)?;
+        let intrinsic_gas = U256::from(ethereum_state::intrinsic_gas(tx));
+        let execution_gas = U256::from(result.gas_used);
         
         Ok(GasProfile {
-            total_gas_used: result.gas_used,
+            total_gas_used: intrinsic_gas + execution_gas,
             execution_gas,
             intrinsic_gas,
-            refund: result.gas_refund,
-            opcode_costs,
-            call_costs,
-            storage_costs,
+            refund: U256::from(result.gas_refund),
+            opcode_costs: profile.opcode_gas,
+            call_costs: profile.call_costs,
+            storage_costs: StorageCosts {
+                storage_refund: U256::from(result.gas_refund),
+                ..profile.storage_costs
+            },
         })
     }
     
     /// Get opcode statistics
-    pub async fn get_opcode_stats<D: ethereum_storage::Database>(
+    pub async fn get_opcode_stats<D: Database + 'static>(
         &self,
+        tracer: &Tracer<D>,
         tx: &Transaction,
         block: &Block,
-        evm: Arc<EVM<D>>,
     ) -> Result<OpcodeStats> {
-        let context = self.create_context(block);
-        
-        let mut opcode_counts: HashMap<String, u64> = HashMap::new();
-        let mut opcode_gas: HashMap<String, U256> = HashMap::new();
-        let mut pc_costs: HashMap<u64, (String, U256, u64)> = HashMap::new();
-        
-        let mut last_gas = tx.gas_limit;
-        
-        // Execute transaction with statistics collection
-        let _result = evm.execute_transaction_with_tracer(
-            tx,
-            self.get_state(block),
-            &context,
-            |pc, op, _stack, _memory, _storage| {
-                let gas_cost = last_gas - evm.get_gas_left();
-                let op_str = format!("{:?}", op);
-                
-                // Count opcodes
-                *opcode_counts.entry(op_str.clone()).or_insert(0) += 1;
-                
-                // Sum gas per opcode
-                *opcode_gas.entry(op_str.clone()).or_insert(U256::zero()) += gas_cost;
-                
-                // Track per-PC costs
-                let entry = pc_costs.entry(pc as u64).or_insert((op_str, U256::zero(), 0));
-                entry.1 += gas_cost;
-                entry.2 += 1;
-                
-                last_gas = evm.get_gas_left();
-            }
-        ).await.map_err(|e| DebugError::EvmError(e.to_string()))?;
+        let (_, profile) = run_profiled(tracer, tx, block)?;
         
         // Find most expensive operations
-        let mut expensive_ops: Vec<ExpensiveOp> = pc_costs
+        let mut expensive_ops: Vec<ExpensiveOp> = profile.pc_costs
             .into_iter()
             .map(|(pc, (opcode, gas_cost, count))| ExpensiveOp {
                 pc,
@@ -212,42 +128,20 @@ impl Profiler {
             })
             .collect();
         
-        expensive_ops.sort_by(|a, b| b.gas_cost.cmp(&a.gas_cost));
+        expensive_ops.sort_by_key(|op| std::cmp::Reverse(op.gas_cost));
         expensive_ops.truncate(10);
         
         // Find hot spots (simplified - would need more sophisticated analysis)
         let hot_spots = self.find_hot_spots(&expensive_ops);
         
         Ok(OpcodeStats {
-            opcode_counts,
-            opcode_gas,
+            opcode_counts: profile.opcode_counts,
+            opcode_gas: profile.opcode_gas,
             most_expensive_ops: expensive_ops,
             hot_spots,
         })
     }
     
-    /// Calculate intrinsic gas for transaction
-    fn calculate_intrinsic_gas(&self, tx: &Transaction) -> U256 {
-        let mut gas = U256::from(21000); // Base transaction cost
-        
-        // Add data costs
-        for byte in &tx.input {
-            if *byte == 0 {
-                gas += U256::from(4); // Zero byte
-            } else {
-                gas += U256::from(16); // Non-zero byte
-            }
-        }
-        
-        // Add access list costs (EIP-2930)
-        if let Some(ref access_list) = tx.access_list {
-            gas += U256::from(access_list.len() * 2400); // Per address
-            // Would also add per-storage-key costs
-        }
-        
-        gas
-    }
-    
     /// Find hot spots in code
     fn find_hot_spots(&self, expensive_ops: &[ExpensiveOp]) -> Vec<HotSpot> {
         let mut hot_spots = Vec::new();
@@ -296,23 +190,100 @@ impl Profiler {
         
         hot_spots
     }
-    
-    fn create_context(&self, block: &Block) -> ethereum_evm::Context {
-        ethereum_evm::Context {
-            block_number: block.header.number,
-            timestamp: block.header.timestamp,
-            gas_limit: block.header.gas_limit,
-            coinbase: block.header.author,
-            difficulty: block.header.difficulty,
-            chain_id: 1,
+}
+
+/// Replay `tx` on its parent state with a `ProfilingHost` under it
+fn run_profiled<D: Database + 'static>(
+    tracer: &Tracer<D>,
+    tx: &Transaction,
+    block: &Block,
+) -> Result<(ExecutionResult, Profile)> {
+    tracer.replay(tx, block, |state, context| {
+        let mut profile = Profile::default();
+        let result = apply_traced(state, context, tx, |frame, host| {
+            let mut profiler = ProfilingHost::new(host, &mut profile);
+            run_interpreter(frame, &mut profiler)
+        })?;
+        Ok((result, profile))
+    })
+}
+
+/// Gas charged per instruction and call of one transaction
+#[derive(Default)]
+struct Profile {
+    opcode_counts: HashMap<String, u64>,
+    opcode_gas: HashMap<String, U256>,
+    /// Opcode, gas and executions per program counter
+    pc_costs: HashMap<u64, (String, U256, u64)>,
+    call_costs: Vec<CallCost>,
+    storage_costs: StorageCosts,
+}
+
+/// Host wrapper that adds up the gas every instruction and nested call of a
+/// transaction is charged
+struct ProfilingHost<'h, 'p, H: Host> {
+    inner: &'h mut H,
+    profile: &'p mut Profile,
+    /// Program counter and opcode of the instructions still running,
+    /// innermost frame last
+    open: Vec<(u64, Opcode)>,
+    /// Nested frames still running, innermost last
+    calls: Vec<CallCost>,
+}
+
+impl<'h, 'p, H: Host> ProfilingHost<'h, 'p, H> {
+    fn new(inner: &'h mut H, profile: &'p mut Profile) -> Self {
+        Self { inner, profile, open: Vec::new(), calls: Vec::new() }
+    }
+}
+
+impl<'h, 'p, H: Host> Host for ProfilingHost<'h, 'p, H> {
+    delegate_host!();
+
+    fn step(&mut self, step: &Step<'_>) {
+        self.open.push((step.pc as u64, step.opcode));
+    }
+
+    fn step_end(&mut self, gas_cost: u64, _error: Option<&EvmError>) {
+        let Some((pc, opcode)) = self.open.pop() else { return };
+        let gas_cost = U256::from(gas_cost);
+        let op = format!("{:?}", opcode);
+        
+        *self.profile.opcode_counts.entry(op.clone()).or_insert(0) += 1;
+        *self.profile.opcode_gas.entry(op.clone()).or_insert_with(U256::zero) += gas_cost;
+        let entry = self.profile.pc_costs.entry(pc).or_insert((op, U256::zero(), 0));
+        entry.1 += gas_cost;
+        entry.2 += 1;
+        
+        let storage = &mut self.profile.storage_costs;
+        match opcode {
+            Opcode::SLOAD => {
+                storage.sload_count += 1;
+                storage.sload_gas += gas_cost;
+            }
+            Opcode::SSTORE => {
+                storage.sstore_count += 1;
+                storage.sstore_gas += gas_cost;
+            }
+            _ => {}
         }
     }
-    
-    fn get_state<D: ethereum_storage::Database>(&self, block: &Block) -> ethereum_trie::PatriciaTrie<D> {
-        // Simplified - would get actual state
-        ethereum_trie::PatriciaTrie::new_with_root(
-            Arc::new(ethereum_storage::MemoryDatabase::new()),
-            block.header.state_root,
-        )
+
+    fn enter_frame(&mut self, frame: &Frame<'_>) {
+        self.calls.push(CallCost {
+            call_type: format!("{:?}", frame.opcode),
+            target: Some(frame.to),
+            gas_provided: U256::from(frame.gas),
+            gas_used: U256::zero(),
+            // The transaction's own frame is depth 1
+            depth: self.calls.len() + 2,
+        });
+    }
+
+    fn exit_frame(&mut self, result: &ExecutionResult) {
+        if let Some(mut call) = self.calls.pop() {
+            call.gas_used = U256::from(result.gas_used);
+            self.profile.call_costs.push(call);
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,19 +1,30 @@
 use ethereum_types::{H256, U256, Address};
 use ethereum_core::{Block, Transaction};
 use ethereum_storage::Database;
-use ethereum_evm::{EVM, Opcode};
+use ethereum_evm::opcodes::Opcode;
+use ethereum_evm::execution::{BlockContext, Log};
+use ethereum_evm::execution::{ExecutionStatus, HaltReason};
+use ethereum_evm::{Account, Checkpoint, EvmError, ExecutionContext, ExecutionResult, Frame, Host, JournaledState, Step};
+use ethereum_evm::{decode_revert_reason, run_interpreter, ChainConfig};
+use ethereum_evm::interpreter::create_address;
+use ethereum_state::{apply_transaction, apply_transaction_with, CallState, TrieStateProvider};
 use std::sync::Arc;
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
+use crate::prestate::PrestateTracer;
 use crate::{Result, DebugError};
 
 /// Trace configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TraceConfig {
+    /// Record an opcode-level `structLogs` trace
     #[serde(default)]
-    pub disable_memory: bool,
+    pub enable_struct_log: bool,
+    /// Include memory in struct logs, left out by default as it dominates the trace size
+    #[serde(default)]
+    pub enable_memory: bool,
     #[serde(default)]
     pub disable_stack: bool,
     #[serde(default)]
@@ -21,7 +32,10 @@ pub struct TraceConfig {
     #[serde(default)]
     pub disable_return_data: bool,
     #[serde(default)]
-    pub tracer: Option<String>,
+    pub tracer: Option<BuiltinTracer>,
+    /// Options of the selected tracer
+    #[serde(default)]
+    pub tracer_config: TracerConfig,
     #[serde(default)]
     pub timeout: Option<String>,
     #[serde(default)]
@@ -31,17 +45,39 @@ pub struct TraceConfig {
 impl Default for TraceConfig {
     fn default() -> Self {
         Self {
-            disable_memory: false,
+            enable_struct_log: false,
+            enable_memory: false,
             disable_stack: false,
             disable_storage: false,
             disable_return_data: false,
             tracer: None,
+            tracer_config: TracerConfig::default(),
             timeout: None,
             trace_call: true,
         }
     }
 }
 
+/// Native tracers selectable by name through `TraceConfig::tracer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BuiltinTracer {
+    #[serde(rename = "callTracer")]
+    CallTracer,
+    #[serde(rename = "prestateTracer")]
+    PrestateTracer,
+    #[serde(rename = "4byteTracer")]
+    FourByteTracer,
+}
+
+/// Options for the builtin tracers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TracerConfig {
+    /// Make the prestate tracer report `pre` and `post` for changed accounts
+    #[serde(default)]
+    pub diff_mode: bool,
+}
+
 /// Trace result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -51,27 +87,98 @@ pub enum TraceResult {
     Custom(serde_json::Value),
 }
 
-/// Call trace
+/// Call frame as geth's `callTracer` reports it, with the frames it called
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CallTrace {
+    #[serde(rename = "type")]
+    pub trace_type: TraceType,
     pub from: Address,
-    pub to: Option<Address>,
-    pub value: U256,
     pub gas: U256,
     pub gas_used: U256,
+    /// Left out for a creation that failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<Address>,
+    #[serde(with = "hex_bytes")]
     pub input: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "hex_bytes")]
     pub output: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub revert_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub calls: Vec<CallTrace>,
-    pub trace_type: TraceType,
+    /// Left out for a STATICCALL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<U256>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl CallTrace {
+    fn enter(frame: &Frame<'_>) -> Self {
+        let trace_type = TraceType::from_opcode(frame.opcode);
+        Self {
+            value: (frame.opcode != Opcode::STATICCALL).then_some(frame.value),
+            trace_type,
+            from: frame.from,
+            gas: U256::from(frame.gas),
+            gas_used: U256::zero(),
+            to: Some(frame.to),
+            input: frame.input.to_vec(),
+            output: Vec::new(),
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Record how the frame ended
+    fn exit(&mut self, result: &ExecutionResult) {
+        self.gas_used = U256::from(result.gas_used);
+        match &result.status {
+            ExecutionStatus::Success => self.output = result.return_data.clone(),
+            ExecutionStatus::Revert => {
+                self.error = Some("execution reverted".to_string());
+                self.revert_reason = decode_revert_reason(&result.return_data);
+                self.output = result.return_data.clone();
+            }
+            ExecutionStatus::Halt(reason) => {
+                // A halted frame hands none of its gas back, and no output
+                self.gas_used = self.gas;
+                self.error = Some(halt_error(reason));
+            }
+        }
+        if self.error.is_some() && matches!(self.trace_type, TraceType::Create | TraceType::Create2) {
+            self.to = None;
+        }
+    }
+}
+
+/// Error message geth reports for a frame that halted
+fn halt_error(reason: &HaltReason) -> String {
+    match reason {
+        HaltReason::OutOfGas => "out of gas".to_string(),
+        HaltReason::InvalidOpcode(0xfe) => "invalid opcode: INVALID".to_string(),
+        HaltReason::InvalidOpcode(op) => format!("invalid opcode: opcode {:#04x} not defined", op),
+        HaltReason::StackUnderflow => "stack underflow".to_string(),
+        HaltReason::StackOverflow => "stack limit reached 1024".to_string(),
+        HaltReason::InvalidJump => "invalid jump destination".to_string(),
+        HaltReason::CallDepthExceeded => "max call depth exceeded".to_string(),
+        HaltReason::CreateCollision => "contract address collision".to_string(),
+        HaltReason::CreateContractTooLarge => "max code size exceeded".to_string(),
+        HaltReason::PrecompileFailed => "precompiled contract failed".to_string(),
+        HaltReason::StateModificationInStatic => "write protection".to_string(),
+        HaltReason::InvalidCode => "invalid code: must not begin with 0xef".to_string(),
+        HaltReason::ReturnDataOutOfBounds => "return data out of bounds".to_string(),
+        HaltReason::StepLimit => "execution timeout".to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum TraceType {
     Call,
+    Callcode,
     Create,
     Create2,
     Delegatecall,
@@ -79,11 +186,57 @@ pub enum TraceType {
     Selfdestruct,
 }
 
+impl TraceType {
+    fn from_opcode(opcode: Opcode) -> Self {
+        match opcode {
+            Opcode::CALLCODE => TraceType::Callcode,
+            Opcode::DELEGATECALL => TraceType::Delegatecall,
+            Opcode::STATICCALL => TraceType::Staticcall,
+            Opcode::CREATE => TraceType::Create,
+            Opcode::CREATE2 => TraceType::Create2,
+            _ => TraceType::Call,
+        }
+    }
+}
+
+/// `0x`-prefixed hex for byte strings
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s.trim_start_matches("0x")).map_err(serde::de::Error::custom)
+    }
+}
+
+/// `0x`-prefixed hex for optional byte strings
+mod hex_bytes_opt {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => super::hex_bytes::serialize(bytes, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| hex::decode(s.trim_start_matches("0x")).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
 /// Structured logs
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StructLogs {
     pub gas: U256,
+    #[serde(with = "hex_bytes")]
     pub return_value: Vec<u8>,
     pub struct_logs: Vec<StructLog>,
 }
@@ -101,394 +254,851 @@ pub struct StructLog {
     pub error: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stack: Option<Vec<H256>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex_bytes_opt")]
     pub memory: Option<Vec<u8>>,
+    /// Slots of the running contract read or written so far, set on SLOAD
+    /// and SSTORE
     #[serde(skip_serializing_if = "Option::is_none")]
     pub storage: Option<HashMap<H256, H256>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex_bytes_opt")]
     pub return_data: Option<Vec<u8>>,
 }
 
-/// Transaction tracer
-pub struct Tracer<D: Database> {
-    db: Arc<D>,
-    evm: Arc<EVM<D>>,
+/// Forward every `Host` method a tracer does not observe to `self.inner`
+///
+/// `delegate_host!(journal)` forwards everything but the account and
+/// storage accessors, for tracers that watch state themselves.
+macro_rules! delegate_host {
+    () => {
+        delegate_host!(accounts);
+        delegate_host!(journal);
+    };
+    (accounts) => {
+        fn get_account(&self, address: &Address) -> Option<Account> {
+            self.inner.get_account(address)
+        }
+
+        fn set_account(&mut self, address: Address, account: Account) {
+            self.inner.set_account(address, account)
+        }
+
+        fn remove_account(&mut self, address: &Address) {
+            self.inner.remove_account(address)
+        }
+
+        fn is_empty(&self, address: &Address) -> bool {
+            self.inner.is_empty(address)
+        }
+
+        fn is_created(&self, address: &Address) -> bool {
+            self.inner.is_created(address)
+        }
+
+        fn get_storage(&self, address: &Address, key: &H256) -> H256 {
+            self.inner.get_storage(address, key)
+        }
+
+        fn set_storage(&mut self, address: Address, key: H256, value: H256) {
+            self.inner.set_storage(address, key, value)
+        }
+    };
+    (journal) => {
+        fn get_transient_storage(&self, address: &Address, key: &H256) -> H256 {
+            self.inner.get_transient_storage(address, key)
+        }
+
+        fn set_transient_storage(&mut self, address: Address, key: H256, value: H256) {
+            self.inner.set_transient_storage(address, key, value)
+        }
+
+        fn checkpoint(&mut self) -> Checkpoint {
+            self.inner.checkpoint()
+        }
+
+        fn revert_to(&mut self, checkpoint: Checkpoint) {
+            self.inner.revert_to(checkpoint)
+        }
+
+        fn commit(&mut self, checkpoint: Checkpoint) {
+            self.inner.commit(checkpoint)
+        }
+
+        fn block_hash(&self, block: &BlockContext, number: U256) -> H256 {
+            self.inner.block_hash(block, number)
+        }
+
+        fn log(&mut self, log: &Log) {
+            self.inner.log(log)
+        }
+    };
 }
+pub(crate) use delegate_host;
 
-impl<D: Database + 'static> Tracer<D> {
-    pub fn new(db: Arc<D>, evm: Arc<EVM<D>>) -> Self {
-        Self { db, evm }
+/// Host wrapper that records a `StructLog` for every instruction the
+/// interpreter runs, nested frames included
+pub struct StructLogger<'h, H: Host> {
+    inner: &'h mut H,
+    disable_stack: bool,
+    enable_memory: bool,
+    disable_storage: bool,
+    logs: Vec<StructLog>,
+    /// Entries of the instructions still running, innermost frame last
+    open: Vec<usize>,
+    /// Slots each contract read or wrote so far
+    storage: HashMap<Address, HashMap<H256, H256>>,
+}
+
+impl<'h, H: Host> StructLogger<'h, H> {
+    pub fn new(inner: &'h mut H, config: &TraceConfig) -> Self {
+        Self {
+            inner,
+            disable_stack: config.disable_stack,
+            enable_memory: config.enable_memory,
+            disable_storage: config.disable_storage,
+            logs: Vec::new(),
+            open: Vec::new(),
+            storage: HashMap::new(),
+        }
     }
     
-    /// Trace transaction execution
-    pub async fn trace_transaction(
-        &self,
-        tx: &Transaction,
-        block: &Block,
-        config: Option<TraceConfig>,
-    ) -> Result<TraceResult> {
-        let config = config.unwrap_or_default();
+    /// Record the slot an SLOAD or SSTORE is about to touch, returning the
+    /// contract's slots seen so far
+    fn record_storage(&mut self, step: &Step<'_>) -> Option<HashMap<H256, H256>> {
+        let word = |item: &U256| {
+            let mut word = [0u8; 32];
+            item.to_big_endian(&mut word);
+            H256::from(word)
+        };
+        let (key, value) = match (step.opcode, step.stack) {
+            (Opcode::SLOAD, [.., key]) => {
+                let key = word(key);
+                (key, self.inner.get_storage(&step.address, &key))
+            }
+            (Opcode::SSTORE, [.., value, key]) => (word(key), word(value)),
+            _ => return None,
+        };
         
-        // Check if custom tracer is specified
-        if let Some(ref tracer_name) = config.tracer {
-            return self.run_custom_tracer(tx, block, tracer_name).await;
+        let slots = self.storage.entry(step.address).or_default();
+        slots.insert(key, value);
+        Some(slots.clone())
+    }
+
+    pub fn into_logs(self) -> Vec<StructLog> {
+        self.logs
+    }
+}
+
+impl<'h, H: Host> Host for StructLogger<'h, H> {
+    delegate_host!();
+
+    fn step(&mut self, step: &Step<'_>) {
+        let stack = (!self.disable_stack).then(|| {
+            step.stack
+                .iter()
+                .map(|item| {
+                    let mut word = [0u8; 32];
+                    item.to_big_endian(&mut word);
+                    H256::from(word)
+                })
+                .collect()
+        });
+        let memory = self.enable_memory.then(|| step.memory.to_vec());
+        let storage = if self.disable_storage { None } else { self.record_storage(step) };
+
+        self.open.push(self.logs.len());
+        self.logs.push(StructLog {
+            pc: step.pc as u64,
+            op: format!("{:?}", step.opcode),
+            gas: U256::from(step.gas),
+            gas_cost: U256::zero(),
+            // Geth numbers the outermost frame 1
+            depth: step.depth as usize + 1,
+            error: None,
+            stack,
+            memory,
+            storage,
+            return_data: None,
+        });
+    }
+
+    fn step_end(&mut self, gas_cost: u64, error: Option<&EvmError>) {
+        // A CALL or CREATE ends after the steps of the frame it started
+        if let Some(index) = self.open.pop() {
+            let log = &mut self.logs[index];
+            log.gas_cost = U256::from(gas_cost);
+            log.error = error.map(|e| e.to_string());
         }
-        
-        // Run standard tracer
-        if config.trace_call {
-            let trace = self.trace_call(tx, block, &config).await?;
-            Ok(TraceResult::CallTrace(trace))
-        } else {
-            let logs = self.trace_struct_logs(tx, block, &config).await?;
-            Ok(TraceResult::StructLogs(logs))
+    }
+}
+
+/// Host wrapper that counts the selectors and argument sizes of the calls
+/// made by a transaction, keyed like `0xa9059cbb-64`
+pub struct FourByteTracer<'h, H: Host> {
+    inner: &'h mut H,
+    ids: HashMap<String, u64>,
+}
+
+impl<'h, H: Host> FourByteTracer<'h, H> {
+    pub fn new(inner: &'h mut H) -> Self {
+        Self { inner, ids: HashMap::new() }
+    }
+
+    /// Count `input` if it is long enough to carry a selector
+    pub fn record(&mut self, input: &[u8]) {
+        if input.len() >= 4 {
+            let id = format!("0x{}-{}", hex::encode(&input[..4]), input.len() - 4);
+            *self.ids.entry(id).or_insert(0) += 1;
         }
     }
-    
-    /// Trace block execution
-    pub async fn trace_block(
-        &self,
-        block: &Block,
-        config: Option<TraceConfig>,
-    ) -> Result<Vec<TraceResult>> {
-        let mut results = Vec::new();
-        
-        for tx in &block.body.transactions {
-            let result = self.trace_transaction(tx, block, config.clone()).await?;
-            results.push(result);
+
+    pub fn into_ids(self) -> HashMap<String, u64> {
+        self.ids
+    }
+}
+
+impl<'h, H: Host> Host for FourByteTracer<'h, H> {
+    delegate_host!();
+
+    fn enter_frame(&mut self, frame: &Frame<'_>) {
+        let creates = matches!(frame.opcode, Opcode::CREATE | Opcode::CREATE2);
+        if !creates && !frame.precompile {
+            self.record(frame.input);
         }
-        
-        Ok(results)
     }
-    
-    /// Trace call execution
-    async fn trace_call(
-        &self,
-        tx: &Transaction,
-        block: &Block,
-        config: &TraceConfig,
-    ) -> Result<CallTrace> {
-        // Create EVM context
-        let context = self.create_context(block);
-        
-        // Create state
-        let state = self.get_state_at_block(&block.header.parent_hash).await?;
-        
-        // Setup tracer hooks
-        let mut trace = CallTrace {
-            from: self.get_sender(tx)?,
-            to: tx.to,
-            value: tx.value,
-            gas: tx.gas_limit,
-            gas_used: U256::zero(),
-            input: tx.input.clone(),
-            output: Vec::new(),
-            error: None,
-            revert_reason: None,
-            calls: Vec::new(),
-            trace_type: if tx.to.is_none() {
-                TraceType::Create
-            } else {
-                TraceType::Call
-            },
-        };
-        
-        // Execute transaction with tracing
-        let result = self.evm.execute_transaction_with_tracer(
-            tx,
-            state,
-            &context,
-            |depth, op, stack, memory, storage| {
-                // Capture subcalls
-                match op {
-                    Opcode::CALL | Opcode::CALLCODE | Opcode::DELEGATECALL | Opcode::STATICCALL => {
-                        if let Some(subcall) = self.extract_subcall(op, stack, memory) {
-                            trace.calls.push(subcall);
-                        }
-                    }
-                    Opcode::CREATE | Opcode::CREATE2 => {
-                        if let Some(subcall) = self.extract_create(op, stack, memory) {
-                            trace.calls.push(subcall);
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        ).await.map_err(|e| DebugError::EvmError(e.to_string()))?;
-        
-        // Update trace with result
-        trace.gas_used = result.gas_used;
-        trace.output = result.return_data;
-        
-        if !result.success {
-            trace.error = Some(result.error.unwrap_or_else(|| "Execution failed".to_string()));
-            if let Some(revert_data) = result.revert_data {
-                trace.revert_reason = self.decode_revert_reason(&revert_data);
+}
+
+/// Host wrapper that builds the tree of calls made under a root frame, the
+/// way geth's `callTracer` does
+///
+/// Frames that revert or halt stay in the tree with their `error` set and
+/// the calls they made, even though none of their changes are kept.
+pub struct CallTracer<'h, H: Host> {
+    inner: &'h mut H,
+    /// Frames still running, the root first
+    open: Vec<CallTrace>,
+}
+
+impl<'h, H: Host> CallTracer<'h, H> {
+    /// Trace the calls made under `root`, which the caller runs itself
+    pub fn new(inner: &'h mut H, root: &Frame<'_>) -> Self {
+        Self { inner, open: vec![CallTrace::enter(root)] }
+    }
+
+    /// Tree of the root frame once it returned with `result`
+    pub fn finish(mut self, result: &ExecutionResult) -> CallTrace {
+        let mut root = self.open.swap_remove(0);
+        root.exit(result);
+        root
+    }
+}
+
+impl<'h, H: Host> Host for CallTracer<'h, H> {
+    delegate_host!();
+
+    fn enter_frame(&mut self, frame: &Frame<'_>) {
+        self.open.push(CallTrace::enter(frame));
+    }
+
+    fn exit_frame(&mut self, result: &ExecutionResult) {
+        // The root is only closed by `finish`
+        if self.open.len() < 2 {
+            return;
+        }
+        if let Some(mut call) = self.open.pop() {
+            call.exit(result);
+            if let Some(parent) = self.open.last_mut() {
+                parent.calls.push(call);
             }
         }
-        
-        Ok(trace)
+    }
+}
+
+/// Tracers that only observe the host a frame runs on, so they can trace a
+/// frame against any state, overridden or not
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameTracer {
+    StructLogs,
+    Call,
+    FourByte,
+    Prestate { diff_mode: bool },
+}
+
+impl FrameTracer {
+    /// Tracer `config` selects
+    pub fn from_config(config: &TraceConfig) -> Self {
+        match config.tracer {
+            None => FrameTracer::StructLogs,
+            Some(BuiltinTracer::CallTracer) => FrameTracer::Call,
+            Some(BuiltinTracer::FourByteTracer) => FrameTracer::FourByte,
+            Some(BuiltinTracer::PrestateTracer) => FrameTracer::Prestate {
+                diff_mode: config.tracer_config.diff_mode,
+            },
+        }
     }
     
-    /// Trace structured logs
-    async fn trace_struct_logs(
-        &self,
-        tx: &Transaction,
-        block: &Block,
+    /// Run `context` on `host` and trace it, `creates` when it runs the init
+    /// code of a transaction or call without a recipient
+    pub fn trace<H: Host>(
+        self,
+        context: ExecutionContext,
+        creates: bool,
+        host: &mut H,
         config: &TraceConfig,
-    ) -> Result<StructLogs> {
-        let context = self.create_context(block);
-        let state = self.get_state_at_block(&block.header.parent_hash).await?;
-        
-        let mut struct_logs = Vec::new();
-        let mut last_gas = tx.gas_limit;
-        
-        // Execute with step tracer
-        let result = self.evm.execute_transaction_with_tracer(
-            tx,
-            state,
-            &context,
-            |pc, op, stack, memory, storage| {
-                let gas_cost = last_gas - self.evm.get_gas_left();
-                
-                let mut log = StructLog {
-                    pc: pc as u64,
-                    op: format!("{:?}", op),
-                    gas: self.evm.get_gas_left(),
-                    gas_cost,
-                    depth: self.evm.get_call_depth(),
-                    error: None,
-                    stack: None,
-                    memory: None,
-                    storage: None,
-                    return_data: None,
+    ) -> (ExecutionResult, TraceResult) {
+        self.trace_from(context, creates, host, config, &[])
+    }
+    
+    /// Like `trace`, with `before` holding accounts as they were before the
+    /// frame's nonce bump and value transfer, see [`accounts_before`]
+    pub fn trace_from<H: Host>(
+        self,
+        context: ExecutionContext,
+        creates: bool,
+        host: &mut H,
+        config: &TraceConfig,
+        before: &[(Address, Option<Account>)],
+    ) -> (ExecutionResult, TraceResult) {
+        match self {
+            FrameTracer::StructLogs => {
+                let mut logger = StructLogger::new(host, config);
+                let result = run_interpreter(context, &mut logger);
+                let logs = StructLogs {
+                    gas: U256::from(result.gas_used),
+                    return_value: result.return_data.clone(),
+                    struct_logs: logger.into_logs(),
                 };
-                
-                // Add optional data based on config
-                if !config.disable_stack {
-                    log.stack = Some(stack.clone());
-                }
-                
-                if !config.disable_memory {
-                    log.memory = Some(memory.clone());
-                }
-                
-                if !config.disable_storage {
-                    log.storage = Some(storage.clone());
-                }
-                
-                if !config.disable_return_data {
-                    log.return_data = Some(self.evm.get_return_data());
+                (result, TraceResult::StructLogs(logs))
+            }
+            FrameTracer::Call => {
+                let root = Frame {
+                    opcode: if creates { Opcode::CREATE } else { Opcode::CALL },
+                    from: context.caller,
+                    to: context.address,
+                    value: context.value,
+                    gas: context.gas_limit,
+                    input: if creates { &context.code } else { &context.data },
+                    precompile: false,
+                };
+                let mut tracer = CallTracer::new(host, &root);
+                let result = run_interpreter(context, &mut tracer);
+                let trace = tracer.finish(&result);
+                (result, TraceResult::CallTrace(trace))
+            }
+            FrameTracer::FourByte => {
+                let mut tracer = FourByteTracer::new(host);
+                tracer.record(&context.data);
+                let result = run_interpreter(context, &mut tracer);
+                (result, TraceResult::Custom(serde_json::json!(tracer.into_ids())))
+            }
+            FrameTracer::Prestate { diff_mode } => {
+                let mut tracer = PrestateTracer::new(host);
+                for (address, account) in before {
+                    tracer.record(*address, account.clone());
                 }
-                
-                struct_logs.push(log);
-                last_gas = self.evm.get_gas_left();
+                tracer.touch(&context.caller);
+                tracer.touch(&context.address);
+                tracer.touch(&context.block.coinbase);
+                let result = run_interpreter(context, &mut tracer);
+                let trace = if diff_mode {
+                    serde_json::json!(tracer.into_diff())
+                } else {
+                    serde_json::json!(tracer.into_prestate())
+                };
+                (result, TraceResult::Custom(trace))
             }
-        ).await.map_err(|e| DebugError::EvmError(e.to_string()))?;
-        
-        Ok(StructLogs {
-            gas: result.gas_used,
-            return_value: result.return_data,
-            struct_logs,
-        })
+        }
+    }
+}
+
+/// Transaction tracer
+///
+/// Replays transactions on their parent block's state through the shared
+/// call overlay, with a [`FrameTracer`] between the interpreter and the state.
+pub struct Tracer<D: Database> {
+    db: Arc<D>,
+    chain_config: ChainConfig,
+}
+
+impl<D: Database + 'static> Tracer<D> {
+    pub fn new(db: Arc<D>) -> Self {
+        Self { db, chain_config: ChainConfig::mainnet() }
+    }
+    
+    /// Chain whose id traced transactions see, mainnet by default
+    pub fn with_chain_config(mut self, chain_config: ChainConfig) -> Self {
+        self.chain_config = chain_config;
+        self
+    }
+    
+    pub fn chain_config(&self) -> &ChainConfig {
+        &self.chain_config
     }
     
-    /// Run custom tracer
-    async fn run_custom_tracer(
+    /// Trace transaction execution
+    ///
+    /// The transactions before `tx` in `block` are applied untraced first.
+    pub async fn trace_transaction(
         &self,
         tx: &Transaction,
         block: &Block,
-        tracer_name: &str,
+        config: Option<TraceConfig>,
     ) -> Result<TraceResult> {
-        match tracer_name {
-            "callTracer" => {
-                let trace = self.trace_call(tx, block, &TraceConfig::default()).await?;
-                Ok(TraceResult::CallTrace(trace))
-            }
-            "prestateTracer" => {
-                let prestate = self.trace_prestate(tx, block).await?;
-                Ok(TraceResult::Custom(prestate))
-            }
-            "4byteTracer" => {
-                let fourbyte = self.trace_4byte(tx, block).await?;
-                Ok(TraceResult::Custom(fourbyte))
-            }
-            _ => Err(DebugError::InvalidTraceConfig),
-        }
+        let config = config.unwrap_or_default();
+        self.replay(tx, block, |state, context| trace_on(state, context, tx, &config))
     }
     
-    /// Trace prestate
-    async fn trace_prestate(
+    /// Run `run` on the state `tx` starts from in `block`, the transactions
+    /// before it applied untraced
+    pub(crate) fn replay<T>(
         &self,
         tx: &Transaction,
         block: &Block,
-    ) -> Result<serde_json::Value> {
-        // Get accounts touched by transaction
-        let mut prestate = serde_json::Map::new();
-        
-        // Add sender
-        let sender = self.get_sender(tx)?;
-        let sender_state = self.get_account_state(sender, &block.header.parent_hash).await?;
-        prestate.insert(format!("{:?}", sender), sender_state);
+        run: impl FnOnce(&mut CallState<'_>, &BlockContext) -> Result<T>,
+    ) -> Result<T> {
+        let provider = TrieStateProvider::new(self.db.clone());
+        let mut state = CallState::new(&provider, self.parent_state_root(block)?);
+        let context = self.block_context(block)?;
         
-        // Add recipient
-        if let Some(to) = tx.to {
-            let to_state = self.get_account_state(to, &block.header.parent_hash).await?;
-            prestate.insert(format!("{:?}", to), to_state);
+        let hash = tx.hash();
+        for earlier in block.transactions.iter().take_while(|earlier| earlier.hash() != hash) {
+            apply_transaction(&mut state, &context, sender_of(earlier)?, earlier);
         }
         
-        Ok(serde_json::Value::Object(prestate))
+        run(&mut state, &context)
     }
     
-    /// Trace 4byte signatures
-    async fn trace_4byte(
+    /// Trace block execution, each transaction on the state the previous
+    /// ones left
+    pub async fn trace_block(
         &self,
-        tx: &Transaction,
         block: &Block,
-    ) -> Result<serde_json::Value> {
-        let mut signatures = serde_json::Map::new();
-        
-        // Extract 4-byte signature from input
-        if tx.input.len() >= 4 {
-            let sig = hex::encode(&tx.input[..4]);
-            *signatures.entry(sig).or_insert(serde_json::Value::Number(0.into())) = 
-                serde_json::Value::Number(1.into());
-        }
+        config: Option<TraceConfig>,
+    ) -> Result<Vec<TraceResult>> {
+        let config = config.unwrap_or_default();
+        let provider = TrieStateProvider::new(self.db.clone());
+        let mut state = CallState::new(&provider, self.parent_state_root(block)?);
+        let context = self.block_context(block)?;
         
-        Ok(serde_json::Value::Object(signatures))
+        block.transactions.iter()
+            .map(|tx| trace_on(&mut state, &context, tx, &config))
+            .collect()
     }
     
-    // Helper methods
-    
-    fn create_context(&self, block: &Block) -> ethereum_evm::Context {
-        ethereum_evm::Context {
-            block_number: block.header.number,
-            timestamp: block.header.timestamp,
-            gas_limit: block.header.gas_limit,
-            coinbase: block.header.author,
-            difficulty: block.header.difficulty,
-            chain_id: 1,
+    /// Context `block`'s transactions run in, with the configured chain id
+    /// and the hashes of the up to 256 blocks before it
+    pub(crate) fn block_context(&self, block: &Block) -> Result<BlockContext> {
+        let mut hashes = Vec::with_capacity(256);
+        let mut next = (!block.header.number.is_zero()).then_some(block.header.parent_hash);
+        while let Some(hash) = next {
+            hashes.push(hash);
+            if hashes.len() == 256 {
+                break;
+            }
+            next = self.load_block(&hash)?
+                .filter(|ancestor| !ancestor.header.number.is_zero())
+                .map(|ancestor| ancestor.header.parent_hash);
         }
+        
+        Ok(BlockContext::from_header(&block.header, &self.chain_config, hashes))
     }
     
-    async fn get_state_at_block(&self, block_hash: &H256) -> Result<ethereum_trie::PatriciaTrie<D>> {
-        // Get block to get state root
-        let key = format!("block:{}", hex::encode(block_hash));
-        let block_data = self.db.get(key.as_bytes())?
+    /// State root the block's transactions run on
+    fn parent_state_root(&self, block: &Block) -> Result<H256> {
+        let parent = self.load_block(&block.header.parent_hash)?
             .ok_or(DebugError::BlockNotFound)?;
-        
-        let block: Block = bincode::deserialize(&block_data)
-            .map_err(|e| DebugError::ExecutionError(e.to_string()))?;
-        
-        Ok(ethereum_trie::PatriciaTrie::new_with_root(
-            self.db.clone(),
-            block.header.state_root,
-        ))
+        Ok(parent.header.state_root)
     }
     
-    fn get_sender(&self, tx: &Transaction) -> Result<Address> {
-        // Recover sender from signature
-        Ok(Address::from([1u8; 20])) // Simplified
+    fn load_block(&self, hash: &H256) -> Result<Option<Block>> {
+        let key = format!("block:{}", hex::encode(hash));
+        match self.db.get(key.as_bytes())? {
+            Some(data) => bincode::deserialize(&data)
+                .map(Some)
+                .map_err(|e| DebugError::ExecutionError(e.to_string())),
+            None => Ok(None),
+        }
     }
+}
+
+/// Frame tracer a transaction trace uses, struct logs unless a native
+/// tracer or the call tree is asked for
+fn transaction_tracer(config: &TraceConfig) -> FrameTracer {
+    match config.tracer {
+        None if !config.enable_struct_log && config.trace_call => FrameTracer::Call,
+        _ => FrameTracer::from_config(config),
+    }
+}
+
+/// Apply `tx` to `state`, tracing the frame it runs
+fn trace_on(
+    state: &mut CallState<'_>,
+    context: &BlockContext,
+    tx: &Transaction,
+    config: &TraceConfig,
+) -> Result<TraceResult> {
+    let tracer = transaction_tracer(config);
+    let before = accounts_before(state, context, sender_of(tx)?, tx.to());
+    let mut trace = None;
+    apply_traced(state, context, tx, |frame, host| {
+        let (result, traced) = tracer.trace_from(frame, tx.to().is_none(), host, config, &before);
+        trace = Some(traced);
+        result
+    })?;
+    trace.ok_or_else(|| DebugError::ExecutionError("transaction did not run".to_string()))
+}
+
+/// Apply `tx` to `state` with `run` running its frame, failing if the
+/// transaction can't be included
+pub(crate) fn apply_traced<'a, F>(
+    state: &mut CallState<'a>,
+    context: &BlockContext,
+    tx: &Transaction,
+    run: F,
+) -> Result<ExecutionResult>
+where
+    F: FnOnce(ExecutionContext, &mut JournaledState<'_, CallState<'a>>) -> ExecutionResult,
+{
+    let applied = apply_transaction_with(state, context, sender_of(tx)?, tx, run);
+    state.finish().map_err(|e| DebugError::ExecutionError(e.to_string()))?;
     
-    async fn get_account_state(
-        &self,
-        address: Address,
-        block_hash: &H256,
-    ) -> Result<serde_json::Value> {
-        let state = self.get_state_at_block(block_hash).await?;
-        
-        // Get account data
-        let account_data = state.get(address.as_bytes()).await
-            .map_err(|e| DebugError::ExecutionError(e.to_string()))?;
-        
-        if let Some(data) = account_data {
-            let account: ethereum_core::Account = bincode::deserialize(&data)
-                .map_err(|e| DebugError::ExecutionError(e.to_string()))?;
-            
-            Ok(serde_json::json!({
-                "balance": format!("{:#x}", account.balance),
-                "nonce": account.nonce,
-                "code": format!("0x{}", hex::encode(account.code)),
-                "storage": {}
-            }))
-        } else {
-            Ok(serde_json::json!({
-                "balance": "0x0",
-                "nonce": 0,
-                "code": "0x",
-                "storage": {}
-            }))
+    applied.ok_or_else(|| DebugError::ExecutionError(
+        format!("transaction {:?} can't be applied on its parent state", tx.hash())
+    ))
+}
+
+/// Sender, recipient and coinbase of a frame `from` sends to `to`, or to the
+/// address it creates, as they are in `state` before it is applied
+pub(crate) fn accounts_before(
+    state: &CallState<'_>,
+    context: &BlockContext,
+    from: Address,
+    to: Option<Address>,
+) -> Vec<(Address, Option<Account>)> {
+    let sender = state.get_account(&from);
+    let recipient = to.unwrap_or_else(|| {
+        create_address(&from, sender.as_ref().map(|acc| acc.nonce).unwrap_or_default())
+    });
+    
+    [from, recipient, context.coinbase]
+        .into_iter()
+        .map(|address| (address, state.get_account(&address)))
+        .collect()
+}
+
+pub(crate) fn sender_of(tx: &Transaction) -> Result<Address> {
+    tx.sender().map_err(|e| DebugError::ExecutionError(format!("invalid sender: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::Header;
+    use ethereum_evm::{ExecutionContext, Interpreter, JournaledState};
+
+    fn context(code: Vec<u8>) -> ExecutionContext {
+        let block = BlockContext::from_header(&Header::new(), &ChainConfig::mainnet(), Vec::new());
+        ExecutionContext::new(
+            Address::from_bytes([0x01; 20]),
+            Address::from_bytes([0x02; 20]),
+            U256::zero(),
+            code,
+            Vec::new(),
+            1_000_000,
+            block,
+        )
+    }
+
+    fn run_logged(
+        accounts: &mut HashMap<Address, Account>,
+        code: Vec<u8>,
+        config: &TraceConfig,
+    ) -> Vec<StructLog> {
+        let mut state = JournaledState::new(accounts);
+        let mut logger = StructLogger::new(&mut state, config);
+        Interpreter::new(context(code), &mut logger).run().unwrap();
+        logger.into_logs()
+    }
+
+    #[test]
+    fn test_struct_logs_capture_memory_only_when_enabled() {
+        // PUSH1 0x2a, PUSH1 0x00, MSTORE, STOP
+        let code = vec![0x60, 0x2a, 0x60, 0x00, 0x52, 0x00];
+
+        let config = TraceConfig { enable_struct_log: true, ..Default::default() };
+        let logs = run_logged(&mut HashMap::new(), code.clone(), &config);
+        let ops: Vec<_> = logs.iter().map(|l| (l.pc, l.op.as_str())).collect();
+        assert_eq!(ops, vec![(0, "PUSH1"), (2, "PUSH1"), (4, "MSTORE"), (5, "STOP")]);
+        assert!(logs.iter().all(|l| l.memory.is_none() && l.depth == 1));
+        assert_eq!(
+            logs[2].stack,
+            Some(vec![H256::from_low_u64_be(0x2a), H256::zero()])
+        );
+        assert_eq!(logs[0].gas, U256::from(1_000_000));
+        assert_eq!(logs[0].gas_cost, U256::from(3));
+
+        let config = TraceConfig { enable_struct_log: true, enable_memory: true, ..Default::default() };
+        let logs = run_logged(&mut HashMap::new(), code, &config);
+        assert_eq!(logs[2].memory, Some(Vec::new()));
+        let mut word = vec![0u8; 32];
+        word[31] = 0x2a;
+        assert_eq!(logs[3].memory, Some(word));
+    }
+
+    #[test]
+    fn test_struct_logs_record_storage_slots() {
+        let contract = Address::from_bytes([0x02; 20]);
+        let slot = |n: u64| H256::from_low_u64_be(n);
+        let mut accounts = HashMap::new();
+        let mut storage = HashMap::new();
+        storage.insert(slot(2), slot(7));
+        accounts.insert(contract, Account { storage, ..Default::default() });
+
+        // PUSH1 0x2a, PUSH1 0x01, SSTORE, PUSH1 0x02, SLOAD, STOP
+        let code = vec![0x60, 0x2a, 0x60, 0x01, 0x55, 0x60, 0x02, 0x54, 0x00];
+
+        let config = TraceConfig { enable_struct_log: true, ..Default::default() };
+        let logs = run_logged(&mut accounts.clone(), code.clone(), &config);
+        assert_eq!(logs[2].op, "SSTORE");
+        assert_eq!(logs[2].storage, Some(HashMap::from([(slot(1), slot(0x2a))])));
+        assert_eq!(logs[4].op, "SLOAD");
+        assert_eq!(logs[4].storage, Some(HashMap::from([(slot(1), slot(0x2a)), (slot(2), slot(7))])));
+        assert!(logs.iter().filter(|l| l.op != "SSTORE" && l.op != "SLOAD").all(|l| l.storage.is_none()));
+
+        let config = TraceConfig { enable_struct_log: true, disable_storage: true, ..Default::default() };
+        let logs = run_logged(&mut accounts, code, &config);
+        assert!(logs.iter().all(|l| l.storage.is_none()));
+    }
+
+    #[test]
+    fn test_block_context_uses_chain_and_recent_hashes() {
+        let db = Arc::new(ethereum_storage::MemoryDatabase::new());
+        let mut parent_hash = H256::zero();
+        let mut hashes = Vec::new();
+        let mut blocks = Vec::new();
+        for number in 0..4u64 {
+            let mut header = Header::new();
+            header.number = U256::from(number);
+            header.parent_hash = parent_hash;
+            let block = Block::new(header);
+            parent_hash = block.hash();
+            db.put(format!("block:{}", hex::encode(parent_hash)).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+            hashes.push(parent_hash);
+            blocks.push(block);
         }
+
+        let sepolia = ChainConfig { chain_id: 11155111, ..ChainConfig::mainnet() };
+        let tracer = Tracer::new(db).with_chain_config(sepolia);
+        let context = tracer.block_context(&blocks[3]).unwrap();
+        assert_eq!(context.chain_id, U256::from(11155111));
+        assert_eq!(context.block_hashes, vec![hashes[2], hashes[1], hashes[0]]);
+
+        assert!(tracer.block_context(&blocks[0]).unwrap().block_hashes.is_empty());
     }
-    
-    fn extract_subcall(
-        &self,
-        op: Opcode,
-        stack: &[H256],
-        memory: &[u8],
-    ) -> Option<CallTrace> {
-        // Extract call parameters from stack
-        // This is simplified - real implementation would properly decode
-        
-        Some(CallTrace {
-            from: Address::zero(),
-            to: Some(Address::zero()),
-            value: U256::zero(),
-            gas: U256::zero(),
-            gas_used: U256::zero(),
-            input: Vec::new(),
-            output: Vec::new(),
-            error: None,
-            revert_reason: None,
-            calls: Vec::new(),
-            trace_type: match op {
-                Opcode::DELEGATECALL => TraceType::Delegatecall,
-                Opcode::STATICCALL => TraceType::Staticcall,
-                _ => TraceType::Call,
-            },
-        })
+
+    #[test]
+    fn test_struct_logs_serialize_bytes_as_hex() {
+        let logs = StructLogs {
+            gas: U256::from(21_000),
+            return_value: vec![0xde, 0xad],
+            struct_logs: vec![StructLog {
+                pc: 0,
+                op: "STOP".to_string(),
+                gas: U256::from(100),
+                gas_cost: U256::zero(),
+                depth: 1,
+                error: None,
+                stack: None,
+                memory: Some(vec![0x00, 0x2a]),
+                storage: None,
+                return_data: None,
+            }],
+        };
+
+        let json = serde_json::to_value(&logs).unwrap();
+        assert_eq!(json["returnValue"], "0xdead");
+        assert_eq!(json["structLogs"][0]["memory"], "0x002a");
+        assert!(json["structLogs"][0].get("returnData").is_none());
+
+        let decoded: StructLogs = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.return_value, vec![0xde, 0xad]);
+        assert_eq!(decoded.struct_logs[0].memory, Some(vec![0x00, 0x2a]));
+        assert_eq!(decoded.struct_logs[0].return_data, None);
     }
-    
-    fn extract_create(
-        &self,
-        op: Opcode,
-        stack: &[H256],
-        memory: &[u8],
-    ) -> Option<CallTrace> {
-        Some(CallTrace {
-            from: Address::zero(),
-            to: None,
-            value: U256::zero(),
-            gas: U256::zero(),
-            gas_used: U256::zero(),
-            input: Vec::new(),
-            output: Vec::new(),
-            error: None,
-            revert_reason: None,
-            calls: Vec::new(),
-            trace_type: if op == Opcode::CREATE2 {
-                TraceType::Create2
-            } else {
-                TraceType::Create
-            },
-        })
+
+    #[test]
+    fn test_struct_logs_follow_nested_calls() {
+        let callee = Address::from_bytes([0x03; 20]);
+        let mut accounts = HashMap::new();
+        // PUSH1 0x01, STOP
+        accounts.insert(callee, Account { code: vec![0x60, 0x01, 0x00], ..Default::default() });
+
+        // Five zero PUSH1s for the call arguments, PUSH20 callee, GAS, CALL, STOP
+        let mut code = [0x60, 0x00].repeat(5);
+        code.push(0x73);
+        code.extend_from_slice(callee.as_bytes());
+        code.extend_from_slice(&[0x5a, 0xf1, 0x00]);
+
+        let config = TraceConfig { enable_struct_log: true, ..Default::default() };
+        let logs = run_logged(&mut accounts, code, &config);
+
+        let ops: Vec<_> = logs.iter().map(|l| (l.op.as_str(), l.depth)).collect();
+        let mut expected = vec![("PUSH1", 1); 5];
+        expected.extend([("PUSH20", 1), ("GAS", 1), ("CALL", 1), ("PUSH1", 2), ("STOP", 2), ("STOP", 1)]);
+        assert_eq!(ops, expected);
+
+        // The callee's steps are charged like the caller's
+        assert_eq!(logs[8].gas_cost, U256::from(3));
+        // The CALL is charged for everything its frame used
+        assert_eq!(logs[10].gas, logs[7].gas - logs[7].gas_cost);
+        assert!(logs.iter().all(|l| l.error.is_none()));
     }
-    
-    fn decode_revert_reason(&self, data: &[u8]) -> Option<String> {
-        // Decode revert reason from return data
-        // Standard format: 0x08c379a0 (Error(string)) followed by ABI-encoded string
-        
-        if data.len() < 4 {
-            return None;
+
+    #[test]
+    fn test_four_byte_tracer_counts_call_selectors() {
+        let token = Address::from_bytes([0x70; 20]);
+        let mut accounts = HashMap::new();
+        accounts.insert(token, Account { code: vec![0x00], ..Default::default() });
+
+        // transfer(address,uint256) calldata at 0x00, balanceOf(address) at 0x80
+        let mut code = vec![0x7f, 0xa9, 0x05, 0x9c, 0xbb];
+        code.extend_from_slice(&[0u8; 28]);
+        code.extend_from_slice(&[0x60, 0x00, 0x52]);
+        code.extend_from_slice(&[0x7f, 0x70, 0xa0, 0x82, 0x31]);
+        code.extend_from_slice(&[0u8; 28]);
+        code.extend_from_slice(&[0x60, 0x80, 0x52]);
+        for _ in 0..2 {
+            // CALL with retSize 0, retOffset 0, argsSize 0x44, argsOffset 0, value 0
+            code.extend_from_slice(&[0x60, 0x00, 0x60, 0x00, 0x60, 0x44, 0x60, 0x00, 0x60, 0x00, 0x73]);
+            code.extend_from_slice(token.as_bytes());
+            code.extend_from_slice(&[0x5a, 0xf1, 0x50]);
         }
-        
-        let selector = &data[..4];
-        if selector == [0x08, 0xc3, 0x79, 0xa0] {
-            // Try to decode string
-            // This is simplified - real implementation would use proper ABI decoding
-            Some("Execution reverted".to_string())
-        } else {
-            None
+        // STATICCALL with retSize 0, retOffset 0, argsSize 0x24, argsOffset 0x80
+        code.extend_from_slice(&[0x60, 0x00, 0x60, 0x00, 0x60, 0x24, 0x60, 0x80, 0x73]);
+        code.extend_from_slice(token.as_bytes());
+        code.extend_from_slice(&[0x5a, 0xfa, 0x50, 0x00]);
+
+        let mut state = JournaledState::new(&mut accounts);
+        let mut tracer = FourByteTracer::new(&mut state);
+        Interpreter::new(context(code), &mut tracer).run().unwrap();
+
+        let expected = HashMap::from([
+            ("0xa9059cbb-64".to_string(), 2),
+            ("0x70a08231-32".to_string(), 1),
+        ]);
+        assert_eq!(tracer.into_ids(), expected);
+    }
+
+    #[test]
+    fn test_call_tracer_builds_call_tree() {
+        let inner = Address::from_bytes([0x03; 20]);
+        let reverter = Address::from_bytes([0x04; 20]);
+        let invalid = Address::from_bytes([0x05; 20]);
+
+        // STATICCALL the reverter with no input, POP, STOP
+        let mut inner_code = [0x60, 0x00].repeat(4);
+        inner_code.push(0x73);
+        inner_code.extend_from_slice(reverter.as_bytes());
+        inner_code.extend_from_slice(&[0x5a, 0xfa, 0x50, 0x00]);
+
+        // CALL inner with all the gas, then invalid with 0x1000 gas
+        let mut code = Vec::new();
+        for (to, gas) in [(inner, vec![0x5a]), (invalid, vec![0x61, 0x10, 0x00])] {
+            code.extend_from_slice(&[0x60, 0x00].repeat(5));
+            code.push(0x73);
+            code.extend_from_slice(to.as_bytes());
+            code.extend_from_slice(&gas);
+            code.extend_from_slice(&[0xf1, 0x50]);
         }
+        code.push(0x00);
+
+        let mut accounts = HashMap::new();
+        accounts.insert(Address::from_bytes([0x02; 20]), Account { code: code.clone(), ..Default::default() });
+        accounts.insert(inner, Account { code: inner_code, ..Default::default() });
+        // PUSH1 0x2a, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, REVERT
+        let reverting = vec![0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xfd];
+        accounts.insert(reverter, Account { code: reverting, ..Default::default() });
+        accounts.insert(invalid, Account { code: vec![0xfe], ..Default::default() });
+
+        let mut state = JournaledState::new(&mut accounts);
+        let config = TraceConfig { tracer: Some(BuiltinTracer::CallTracer), ..Default::default() };
+        let (result, trace) = FrameTracer::from_config(&config).trace(context(code), false, &mut state, &config);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        let TraceResult::CallTrace(trace) = trace else { panic!("expected a call trace") };
+
+        assert_eq!(trace.trace_type, TraceType::Call);
+        assert_eq!(trace.gas, U256::from(1_000_000));
+        assert_eq!(trace.gas_used, U256::from(result.gas_used));
+        assert_eq!(trace.calls.len(), 2);
+
+        // The reverted call stays in the tree under the frame that made it
+        let call = &trace.calls[0];
+        assert_eq!((call.trace_type, call.from, call.to), (TraceType::Call, Address::from_bytes([0x02; 20]), Some(inner)));
+        assert!(call.error.is_none());
+        let reverted = &call.calls[0];
+        assert_eq!((reverted.trace_type, reverted.from, reverted.to), (TraceType::Staticcall, inner, Some(reverter)));
+        assert_eq!(reverted.gas_used, U256::from(18));
+        assert!(call.gas_used > reverted.gas_used);
+        assert!(reverted.gas > reverted.gas_used);
+
+        // A halted frame is charged all of its gas
+        let halted = &trace.calls[1];
+        assert_eq!(halted.gas, U256::from(0x1000));
+        assert_eq!(halted.gas_used, U256::from(0x1000));
+        assert_eq!(halted.error.as_deref(), Some("invalid opcode: INVALID"));
+        assert!(halted.output.is_empty());
+
+        let json = serde_json::to_value(&trace).unwrap();
+        assert_eq!(json["type"], "CALL");
+        assert_eq!(json["value"], "0x0");
+        assert_eq!(json["input"], "0x");
+        assert!(json.get("output").is_none() && json.get("error").is_none());
+        assert_eq!(json["calls"][1]["gasUsed"], "0x1000");
+        let reverted = &json["calls"][0]["calls"][0];
+        assert_eq!(reverted["type"], "STATICCALL");
+        assert_eq!(reverted["error"], "execution reverted");
+        assert_eq!(reverted["output"], format!("0x{}2a", "0".repeat(62)));
+        assert!(reverted.get("value").is_none() && reverted.get("calls").is_none());
+
+        let decoded: CallTrace = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.calls[0].calls[0].output, trace.calls[0].calls[0].output);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_call_tracer_charges_code_deposit() {
+        // Init code returning one byte:
+        // PUSH1 0x01, PUSH1 0x00, MSTORE8, PUSH1 0x01, PUSH1 0x00, RETURN
+        let init_code = [0x60, 0x01, 0x60, 0x00, 0x53, 0x60, 0x01, 0x60, 0x00, 0xf3];
+
+        // PUSH10 <init code>, PUSH1 0x00, MSTORE, CREATE(0, 22, 10), STOP
+        let mut code = vec![0x69];
+        code.extend_from_slice(&init_code);
+        code.extend_from_slice(&[0x60, 0x00, 0x52, 0x60, 0x0a, 0x60, 0x16, 0x60, 0x00, 0xf0, 0x00]);
+
+        let mut accounts = HashMap::new();
+        accounts.insert(Address::from_bytes([0x02; 20]), Account { code: code.clone(), ..Default::default() });
+        let mut state = JournaledState::new(&mut accounts);
+        let config = TraceConfig { tracer: Some(BuiltinTracer::CallTracer), ..Default::default() };
+        let (_, trace) = FrameTracer::Call.trace(context(code), false, &mut state, &config);
+        let TraceResult::CallTrace(trace) = trace else { panic!("expected a call trace") };
+
+        // Four pushes, the store with its word of memory and 200 for the deployed byte
+        let created = &trace.calls[0];
+        assert_eq!(created.trace_type, TraceType::Create);
+        assert_eq!(created.input, init_code.to_vec());
+        assert_eq!(created.gas_used, U256::from(4 * 3 + 6 + 200));
+
+        // The root's kind comes from the caller, not from the code it runs
+        let mut state = JournaledState::new(&mut accounts);
+        let (_, trace) = FrameTracer::Call.trace(context(init_code.to_vec()), true, &mut state, &config);
+        let TraceResult::CallTrace(trace) = trace else { panic!("expected a call trace") };
+        assert_eq!(trace.trace_type, TraceType::Create);
+        assert_eq!(trace.input, init_code.to_vec());
+    }
+
+    #[test]
+    fn test_builtin_tracer_names() {
+        let config: TraceConfig = serde_json::from_str(r#"{"tracer":"4byteTracer"}"#).unwrap();
+        assert_eq!(config.tracer, Some(BuiltinTracer::FourByteTracer));
+        assert!(serde_json::from_str::<TraceConfig>(r#"{"tracer":"jsTracer"}"#).is_err());
+    }
+}
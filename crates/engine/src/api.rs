@@ -3,7 +3,7 @@ use jsonrpsee::{core::RpcResult, proc_macros::rpc};
 use ethereum_types::{H256, U256};
 use ethereum_core::Block;
 use ethereum_storage::Storage;
-use ethereum_consensus::ConsensusEngine;
+use ethereum_consensus::{Checkpoint, CheckpointStore, ConsensusEngine};
 use ethereum_txpool::TxPool;
 use std::sync::Arc;
 use tracing::{info, warn, error};
@@ -100,6 +100,7 @@ pub struct EngineApiServer {
     jwt_auth: Arc<JwtAuth>,
     forkchoice: Arc<ForkChoiceStore>,
     payload_builder: Arc<PayloadBuilder>,
+    checkpoints: Arc<CheckpointStore>,
     chain_id: u64,
 }
 
@@ -109,6 +110,7 @@ impl EngineApiServer {
         consensus: Arc<dyn ConsensusEngine>,
         tx_pool: Arc<TxPool>,
         jwt_secret: JwtSecret,
+        checkpoints: Arc<CheckpointStore>,
         chain_id: u64,
     ) -> Self {
         Self {
@@ -118,10 +120,35 @@ impl EngineApiServer {
             jwt_auth: Arc::new(JwtAuth::new(jwt_secret)),
             forkchoice: Arc::new(ForkChoiceStore::new()),
             payload_builder: Arc::new(PayloadBuilder::new(tx_pool, chain_id)),
+            checkpoints,
             chain_id,
         }
     }
 
+    /// Record the finalized and safe blocks of an accepted forkchoice update
+    /// in the store shared with consensus and rpc
+    fn record_checkpoints(&self, state: &ForkchoiceStateV1) {
+        let lookup = |hash: H256| -> Option<Checkpoint> {
+            if hash == H256::zero() {
+                return None;
+            }
+            self.storage.get_block_by_hash(hash).ok().flatten()
+                .map(|block| Checkpoint::new(hash, block.header.number.as_u64()))
+        };
+
+        if let Some(finalized) = lookup(state.finalized_block_hash) {
+            if let Err(e) = self.checkpoints.set_finalized(finalized) {
+                warn!("Ignoring finalized checkpoint {:?}: {}", finalized.hash, e);
+            }
+        }
+
+        if let Some(safe) = lookup(state.safe_block_hash) {
+            if let Err(e) = self.checkpoints.set_safe(safe) {
+                warn!("Ignoring safe checkpoint {:?}: {}", safe.hash, e);
+            }
+        }
+    }
+
     async fn validate_and_import_payload(&self, block: Block) -> Result<PayloadStatusV1> {
         match self.consensus.validate_block(&block) {
             Ok(_) => {
@@ -278,6 +305,9 @@ impl EngineApi for EngineApiServer {
         payload_attributes: Option<PayloadAttributesV1>,
     ) -> RpcResult<ForkchoiceUpdatedResponseV1> {
         let status = self.forkchoice.update_forkchoice(forkchoice_state.clone())?;
+        if status.status == PayloadStatus::Valid {
+            self.record_checkpoints(&forkchoice_state);
+        }
         
         let payload_id = if let Some(attributes) = payload_attributes {
             let parent = self.storage
@@ -306,6 +336,9 @@ impl EngineApi for EngineApiServer {
         payload_attributes: Option<PayloadAttributesV2>,
     ) -> RpcResult<ForkchoiceUpdatedResponseV1> {
         let status = self.forkchoice.update_forkchoice(forkchoice_state.clone())?;
+        if status.status == PayloadStatus::Valid {
+            self.record_checkpoints(&forkchoice_state);
+        }
         
         let payload_id = if let Some(attributes) = payload_attributes {
             let parent = self.storage
@@ -334,6 +367,9 @@ impl EngineApi for EngineApiServer {
         payload_attributes: Option<PayloadAttributesV3>,
     ) -> RpcResult<ForkchoiceUpdatedResponseV1> {
         let status = self.forkchoice.update_forkchoice(forkchoice_state.clone())?;
+        if status.status == PayloadStatus::Valid {
+            self.record_checkpoints(&forkchoice_state);
+        }
         
         let payload_id = if let Some(attributes) = payload_attributes {
             let parent = self.storage
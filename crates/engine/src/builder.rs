@@ -282,6 +282,6 @@ impl LocalBuilder {
     }
 
     fn calculate_withdrawals_root(&self, withdrawals: &[crate::types::Withdrawal]) -> H256 {
-        H256::zero()
+        crate::types::withdrawals_root(withdrawals)
     }
 }
\ No newline at end of file
@@ -182,7 +182,7 @@ impl PayloadBuilder {
             mix_hash: attributes.prev_randao,
             nonce: [0u8; 8],
             base_fee_per_gas: Some(self.calculate_base_fee(parent)),
-            withdrawals_root: attributes.withdrawals.as_ref().map(|_| H256::zero()),
+            withdrawals_root: attributes.withdrawals.as_deref().map(crate::types::withdrawals_root),
             blob_gas_used: None,
             excess_blob_gas: None,
             parent_beacon_block_root: attributes.parent_beacon_block_root,
@@ -71,6 +71,23 @@ pub struct Withdrawal {
     pub amount: U64,
 }
 
+impl From<&Withdrawal> for ethereum_core::Withdrawal {
+    fn from(withdrawal: &Withdrawal) -> Self {
+        Self {
+            index: withdrawal.index.as_u64(),
+            validator_index: withdrawal.validator_index.as_u64(),
+            address: withdrawal.address,
+            amount: withdrawal.amount.as_u64(),
+        }
+    }
+}
+
+/// Withdrawals root of a payload's withdrawals list
+pub fn withdrawals_root(withdrawals: &[Withdrawal]) -> H256 {
+    let withdrawals: Vec<ethereum_core::Withdrawal> = withdrawals.iter().map(Into::into).collect();
+    ethereum_verification::compute_withdrawals_root(&withdrawals)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PayloadAttributesV1 {
@@ -0,0 +1,571 @@
+use crate::opcodes::Opcode;
+use thiserror::Error;
+
+/// First two bytes of every EOF container, 0xEF is banned as the first
+/// byte of legacy code since EIP-3541
+pub const MAGIC: [u8; 2] = [0xef, 0x00];
+
+/// The only container version defined so far
+pub const VERSION: u8 = 0x01;
+
+const KIND_TYPES: u8 = 0x01;
+const KIND_CODE: u8 = 0x02;
+const KIND_CONTAINER: u8 = 0x03;
+const KIND_DATA: u8 = 0xff;
+const TERMINATOR: u8 = 0x00;
+
+/// Most code sections a container may have
+pub const MAX_CODE_SECTIONS: usize = 1024;
+
+/// `outputs` of a code section that never returns to its caller
+pub const NON_RETURNING: u8 = 0x80;
+
+const MAX_STACK_HEIGHT: usize = 1024;
+
+const RJUMP: u8 = 0xe0;
+const RJUMPI: u8 = 0xe1;
+const RJUMPV: u8 = 0xe2;
+const CALLF: u8 = 0xe3;
+const RETF: u8 = 0xe4;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum EofError {
+    #[error("Invalid magic, expected 0xef00")]
+    InvalidMagic,
+
+    #[error("Unsupported EOF version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Container truncated in {0}")]
+    Truncated(&'static str),
+
+    #[error("Expected {0} section header")]
+    MissingSection(&'static str),
+
+    #[error("Unsupported section kind {0:#x}")]
+    UnsupportedSection(u8),
+
+    #[error("Missing header terminator")]
+    MissingTerminator,
+
+    #[error("Invalid number of code sections: {0}")]
+    InvalidCodeSectionCount(usize),
+
+    #[error("Code section {0} is empty")]
+    EmptyCodeSection(usize),
+
+    #[error("Type section of {0} bytes doesn't match {1} code sections")]
+    InvalidTypeSectionSize(usize, usize),
+
+    #[error("{0} bytes after the data section")]
+    TrailingBytes(usize),
+
+    #[error("Invalid type of code section {section}: {reason}")]
+    InvalidType { section: usize, reason: &'static str },
+
+    #[error("Invalid opcode {opcode:#x} at {pc} in code section {section}")]
+    InvalidOpcode { section: usize, pc: usize, opcode: u8 },
+
+    #[error("Truncated immediate at {pc} in code section {section}")]
+    TruncatedImmediate { section: usize, pc: usize },
+
+    #[error("Relative jump at {pc} in code section {section} doesn't land on an instruction")]
+    InvalidJumpTarget { section: usize, pc: usize },
+
+    #[error("CALLF at {pc} in code section {section} targets {target}: {reason}")]
+    InvalidCallTarget { section: usize, pc: usize, target: usize, reason: &'static str },
+
+    #[error("RETF at {pc} in non-returning code section {section}")]
+    InvalidReturn { section: usize, pc: usize },
+
+    #[error("Execution can run off the end of code section {0}")]
+    MissingTerminatingInstruction(usize),
+
+    #[error("Stack underflow at {pc} in code section {section}")]
+    StackUnderflow { section: usize, pc: usize },
+
+    #[error("Stack overflow at {pc} in code section {section}")]
+    StackOverflow { section: usize, pc: usize },
+
+    #[error("Instruction at {pc} in code section {section} is reached with different stack heights")]
+    StackHeightMismatch { section: usize, pc: usize },
+
+    #[error("Unreachable instruction at {pc} in code section {section}")]
+    UnreachableCode { section: usize, pc: usize },
+
+    #[error("Code section {section} declares a max stack height of {declared}, {computed} is needed")]
+    MaxStackHeightMismatch { section: usize, declared: u16, computed: usize },
+}
+
+pub type EofResult<T> = Result<T, EofError>;
+
+/// Inputs, outputs and stack needs of a code section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeSection {
+    pub inputs: u8,
+    /// `NON_RETURNING` for sections that never return
+    pub outputs: u8,
+    pub max_stack_height: u16,
+}
+
+impl TypeSection {
+    pub fn is_returning(&self) -> bool {
+        self.outputs != NON_RETURNING
+    }
+}
+
+/// An EOF container that passed validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatedCode {
+    pub version: u8,
+    pub types: Vec<TypeSection>,
+    pub code_sections: Vec<Vec<u8>>,
+    pub data: Vec<u8>,
+}
+
+/// Parse and validate an EOF v1 container
+///
+/// The header must list the type, code and data sections in that order
+/// (EIP-3540), subcontainers aren't supported. Every code section may only
+/// contain opcodes valid in EOF with complete immediates (EIP-3670),
+/// relative jumps must land on instructions (EIP-4200), CALLF and RETF
+/// must agree with the type section (EIP-4750), and every instruction must
+/// be reached with a single stack height that stays in bounds, the largest
+/// being the section's declared max stack height (EIP-5450).
+pub fn validate_container(bytes: &[u8]) -> EofResult<ValidatedCode> {
+    if bytes.len() < 2 || bytes[..2] != MAGIC {
+        return Err(EofError::InvalidMagic);
+    }
+    let mut reader = Reader { bytes, pos: 2 };
+
+    let version = reader.u8("version")?;
+    if version != VERSION {
+        return Err(EofError::UnsupportedVersion(version));
+    }
+
+    reader.section_kind(KIND_TYPES, "type")?;
+    let types_size = reader.u16("type section header")? as usize;
+
+    reader.section_kind(KIND_CODE, "code")?;
+    let code_count = reader.u16("code section header")? as usize;
+    if code_count == 0 || code_count > MAX_CODE_SECTIONS {
+        return Err(EofError::InvalidCodeSectionCount(code_count));
+    }
+    let mut code_sizes = Vec::with_capacity(code_count);
+    for section in 0..code_count {
+        let size = reader.u16("code section header")? as usize;
+        if size == 0 {
+            return Err(EofError::EmptyCodeSection(section));
+        }
+        code_sizes.push(size);
+    }
+
+    match reader.u8("data section header")? {
+        KIND_DATA => {}
+        KIND_CONTAINER => return Err(EofError::UnsupportedSection(KIND_CONTAINER)),
+        _ => return Err(EofError::MissingSection("data")),
+    }
+    let data_size = reader.u16("data section header")? as usize;
+    if reader.u8("header")? != TERMINATOR {
+        return Err(EofError::MissingTerminator);
+    }
+
+    if types_size != code_count * 4 {
+        return Err(EofError::InvalidTypeSectionSize(types_size, code_count));
+    }
+
+    let mut types = Vec::with_capacity(code_count);
+    for _ in 0..code_count {
+        types.push(TypeSection {
+            inputs: reader.u8("type section")?,
+            outputs: reader.u8("type section")?,
+            max_stack_height: reader.u16("type section")?,
+        });
+    }
+    let mut code_sections = Vec::with_capacity(code_count);
+    for size in code_sizes {
+        code_sections.push(reader.take(size, "code section")?.to_vec());
+    }
+    let data = reader.take(data_size, "data section")?.to_vec();
+    if reader.pos != bytes.len() {
+        return Err(EofError::TrailingBytes(bytes.len() - reader.pos));
+    }
+
+    validate_types(&types)?;
+    for (section, code) in code_sections.iter().enumerate() {
+        validate_code(section, code, &types)?;
+    }
+
+    Ok(ValidatedCode { version, types, code_sections, data })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize, what: &'static str) -> EofResult<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or(EofError::Truncated(what))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self, what: &'static str) -> EofResult<u8> {
+        Ok(self.take(1, what)?[0])
+    }
+
+    fn u16(&mut self, what: &'static str) -> EofResult<u16> {
+        let bytes = self.take(2, what)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn section_kind(&mut self, kind: u8, name: &'static str) -> EofResult<()> {
+        if self.u8(name)? != kind {
+            return Err(EofError::MissingSection(name));
+        }
+        Ok(())
+    }
+}
+
+fn validate_types(types: &[TypeSection]) -> EofResult<()> {
+    if types[0].inputs != 0 || types[0].is_returning() {
+        return Err(EofError::InvalidType {
+            section: 0,
+            reason: "the first section must take no inputs and not return",
+        });
+    }
+    for (section, ty) in types.iter().enumerate() {
+        if ty.inputs > 127 {
+            return Err(EofError::InvalidType { section, reason: "more than 127 inputs" });
+        }
+        if ty.is_returning() && ty.outputs > 127 {
+            return Err(EofError::InvalidType { section, reason: "more than 127 outputs" });
+        }
+        if ty.max_stack_height as usize > MAX_STACK_HEIGHT {
+            return Err(EofError::InvalidType { section, reason: "max stack height above 1024" });
+        }
+    }
+    Ok(())
+}
+
+/// How an instruction moves the stack and where execution continues
+struct Step {
+    inputs: usize,
+    outputs: usize,
+    /// Offset of the next instruction, `None` when it doesn't fall through
+    next: Option<usize>,
+    /// Relative jump targets
+    jumps: Vec<usize>,
+}
+
+/// Decode the instruction at `pc`, checking its opcode and immediates
+fn decode(section: usize, code: &[u8], pc: usize, types: &[TypeSection]) -> EofResult<Step> {
+    let opcode = code[pc];
+    let immediate = |len: usize| -> EofResult<&[u8]> {
+        code.get(pc + 1..pc + 1 + len).ok_or(EofError::TruncatedImmediate { section, pc })
+    };
+    let relative = |after: usize, offset: [u8; 2]| -> EofResult<usize> {
+        let target = after as isize + i16::from_be_bytes(offset) as isize;
+        if target < 0 || target as usize >= code.len() {
+            return Err(EofError::InvalidJumpTarget { section, pc });
+        }
+        Ok(target as usize)
+    };
+
+    let step = match opcode {
+        RJUMP => {
+            let offset = immediate(2)?;
+            let jump = relative(pc + 3, [offset[0], offset[1]])?;
+            Step { inputs: 0, outputs: 0, next: None, jumps: vec![jump] }
+        }
+        RJUMPI => {
+            let offset = immediate(2)?;
+            let jump = relative(pc + 3, [offset[0], offset[1]])?;
+            Step { inputs: 1, outputs: 0, next: Some(pc + 3), jumps: vec![jump] }
+        }
+        RJUMPV => {
+            let count = immediate(1)?[0] as usize + 1;
+            let table = immediate(1 + 2 * count)?[1..].to_vec();
+            let after = pc + 2 + 2 * count;
+            let jumps = table
+                .chunks(2)
+                .map(|offset| relative(after, [offset[0], offset[1]]))
+                .collect::<EofResult<Vec<_>>>()?;
+            Step { inputs: 1, outputs: 0, next: Some(after), jumps }
+        }
+        CALLF => {
+            let index = immediate(2)?;
+            let target = u16::from_be_bytes([index[0], index[1]]) as usize;
+            let callee = types.get(target).ok_or(EofError::InvalidCallTarget {
+                section,
+                pc,
+                target,
+                reason: "no such code section",
+            })?;
+            if !callee.is_returning() {
+                return Err(EofError::InvalidCallTarget { section, pc, target, reason: "the section never returns" });
+            }
+            Step {
+                inputs: callee.inputs as usize,
+                outputs: callee.outputs as usize,
+                next: Some(pc + 3),
+                jumps: Vec::new(),
+            }
+        }
+        RETF => {
+            if !types[section].is_returning() {
+                return Err(EofError::InvalidReturn { section, pc });
+            }
+            Step { inputs: types[section].outputs as usize, outputs: 0, next: None, jumps: Vec::new() }
+        }
+        _ => {
+            let op = Opcode::from_u8(opcode)
+                .filter(|op| valid_in_eof(*op))
+                .ok_or(EofError::InvalidOpcode { section, pc, opcode })?;
+            let size = op.push_bytes().unwrap_or(0);
+            immediate(size)?;
+            let terminates = matches!(op, Opcode::STOP | Opcode::RETURN | Opcode::REVERT | Opcode::INVALID);
+            Step {
+                inputs: op.stack_inputs(),
+                outputs: op.stack_outputs(),
+                next: (!terminates).then_some(pc + 1 + size),
+                jumps: Vec::new(),
+            }
+        }
+    };
+    Ok(step)
+}
+
+/// Legacy opcodes that inspect or jump within code, use gas introspection
+/// or are otherwise replaced are not allowed in EOF code
+fn valid_in_eof(op: Opcode) -> bool {
+    !matches!(
+        op,
+        Opcode::JUMP
+            | Opcode::JUMPI
+            | Opcode::PC
+            | Opcode::GAS
+            | Opcode::CODESIZE
+            | Opcode::CODECOPY
+            | Opcode::EXTCODESIZE
+            | Opcode::EXTCODECOPY
+            | Opcode::EXTCODEHASH
+            | Opcode::CREATE
+            | Opcode::CREATE2
+            | Opcode::CALL
+            | Opcode::CALLCODE
+            | Opcode::DELEGATECALL
+            | Opcode::STATICCALL
+            | Opcode::SELFDESTRUCT
+    )
+}
+
+fn validate_code(section: usize, code: &[u8], types: &[TypeSection]) -> EofResult<()> {
+    // Decode every instruction first so jumps can be checked against
+    // instruction boundaries
+    let mut steps: Vec<Option<Step>> = (0..code.len()).map(|_| None).collect();
+    let mut pc = 0;
+    while pc < code.len() {
+        let step = decode(section, code, pc, types)?;
+        let size = match code[pc] {
+            RJUMP | RJUMPI | CALLF => 3,
+            RJUMPV => 2 + 2 * (code[pc + 1] as usize + 1),
+            RETF => 1,
+            opcode => 1 + Opcode::from_u8(opcode).and_then(|op| op.push_bytes()).unwrap_or(0),
+        };
+        steps[pc] = Some(step);
+        pc += size;
+    }
+    for (pc, step) in steps.iter().enumerate() {
+        if let Some(step) = step {
+            if step.jumps.iter().any(|target| steps[*target].is_none()) {
+                return Err(EofError::InvalidJumpTarget { section, pc });
+            }
+        }
+    }
+
+    // Walk every path from the entry, each instruction must always be
+    // reached with the same stack height
+    let ty = types[section];
+    let mut heights: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending = vec![(0, ty.inputs as usize)];
+    let mut max_height = ty.inputs as usize;
+    while let Some((pc, height)) = pending.pop() {
+        match heights[pc] {
+            Some(seen) if seen == height => continue,
+            Some(_) => return Err(EofError::StackHeightMismatch { section, pc }),
+            None => heights[pc] = Some(height),
+        }
+
+        let step = steps[pc].as_ref().expect("paths only lead to instruction starts");
+        if height < step.inputs {
+            return Err(EofError::StackUnderflow { section, pc });
+        }
+        if code[pc] == RETF && height != step.inputs {
+            return Err(EofError::StackHeightMismatch { section, pc });
+        }
+        if code[pc] == CALLF {
+            let callee = types[u16::from_be_bytes([code[pc + 1], code[pc + 2]]) as usize];
+            let peak = height - step.inputs + callee.max_stack_height as usize;
+            if peak > MAX_STACK_HEIGHT {
+                return Err(EofError::StackOverflow { section, pc });
+            }
+        }
+        let after = height - step.inputs + step.outputs;
+        if after > MAX_STACK_HEIGHT {
+            return Err(EofError::StackOverflow { section, pc });
+        }
+        max_height = max_height.max(after);
+
+        if let Some(next) = step.next {
+            if next >= code.len() {
+                return Err(EofError::MissingTerminatingInstruction(section));
+            }
+            pending.push((next, after));
+        }
+        for target in &step.jumps {
+            pending.push((*target, after));
+        }
+    }
+
+    if let Some(pc) = (0..code.len()).find(|pc| steps[*pc].is_some() && heights[*pc].is_none()) {
+        return Err(EofError::UnreachableCode { section, pc });
+    }
+    if max_height != ty.max_stack_height as usize {
+        return Err(EofError::MaxStackHeightMismatch {
+            section,
+            declared: ty.max_stack_height,
+            computed: max_height,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Container with one type entry per section and an empty data section
+    fn container(sections: &[(TypeSection, &[u8])], data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0xef, 0x00, VERSION, KIND_TYPES];
+        bytes.extend_from_slice(&((sections.len() * 4) as u16).to_be_bytes());
+        bytes.push(KIND_CODE);
+        bytes.extend_from_slice(&(sections.len() as u16).to_be_bytes());
+        for (_, code) in sections {
+            bytes.extend_from_slice(&(code.len() as u16).to_be_bytes());
+        }
+        bytes.push(KIND_DATA);
+        bytes.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        bytes.push(TERMINATOR);
+        for (ty, _) in sections {
+            bytes.extend_from_slice(&[ty.inputs, ty.outputs]);
+            bytes.extend_from_slice(&ty.max_stack_height.to_be_bytes());
+        }
+        for (_, code) in sections {
+            bytes.extend_from_slice(code);
+        }
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn entry(max_stack_height: u16) -> TypeSection {
+        TypeSection { inputs: 0, outputs: NON_RETURNING, max_stack_height }
+    }
+
+    #[test]
+    fn test_minimal_container() {
+        let bytes = [
+            0xef, 0x00, 0x01, // magic, version
+            0x01, 0x00, 0x04, // one type entry
+            0x02, 0x00, 0x01, 0x00, 0x01, // one code section of one byte
+            0xff, 0x00, 0x00, // no data
+            0x00, // terminator
+            0x00, 0x80, 0x00, 0x00, // no inputs, non-returning, empty stack
+            0x00, // STOP
+        ];
+        let code = validate_container(&bytes).unwrap();
+        assert_eq!(code.types, vec![entry(0)]);
+        assert_eq!(code.code_sections, vec![vec![0x00]]);
+        assert!(code.data.is_empty());
+    }
+
+    #[test]
+    fn test_functions_and_relative_jumps() {
+        // PUSH1 1, RJUMPI +0, CALLF 1, STOP
+        let main: &[u8] = &[0x60, 0x01, RJUMPI, 0x00, 0x00, CALLF, 0x00, 0x01, 0x00];
+        // PUSH0, RETF
+        let function: &[u8] = &[0x5f, RETF];
+        let returning = TypeSection { inputs: 0, outputs: 1, max_stack_height: 1 };
+        let bytes = container(&[(entry(1), main), (returning, function)], &[0xaa, 0xbb]);
+
+        let code = validate_container(&bytes).unwrap();
+        assert_eq!(code.code_sections.len(), 2);
+        assert_eq!(code.data, vec![0xaa, 0xbb]);
+
+        // The declared max stack height has to be exact
+        let bytes = container(&[(entry(2), main), (returning, function)], &[]);
+        assert_eq!(
+            validate_container(&bytes),
+            Err(EofError::MaxStackHeightMismatch { section: 0, declared: 2, computed: 1 })
+        );
+    }
+
+    #[test]
+    fn test_malformed_headers() {
+        let valid = container(&[(entry(0), &[0x00])], &[]);
+
+        let mut bad_magic = valid.clone();
+        bad_magic[1] = 0x01;
+        assert_eq!(validate_container(&bad_magic), Err(EofError::InvalidMagic));
+        assert_eq!(validate_container(&[0xef]), Err(EofError::InvalidMagic));
+
+        let mut bad_version = valid.clone();
+        bad_version[2] = 0x02;
+        assert_eq!(validate_container(&bad_version), Err(EofError::UnsupportedVersion(2)));
+
+        assert_eq!(validate_container(&valid[..8]), Err(EofError::Truncated("code section header")));
+        assert_eq!(validate_container(&valid[..valid.len() - 1]), Err(EofError::Truncated("code section")));
+
+        let mut trailing = valid.clone();
+        trailing.push(0x00);
+        assert_eq!(validate_container(&trailing), Err(EofError::TrailingBytes(1)));
+
+        let mut no_terminator = valid;
+        no_terminator[14] = 0x01;
+        assert_eq!(validate_container(&no_terminator), Err(EofError::MissingTerminator));
+    }
+
+    #[test]
+    fn test_invalid_code() {
+        let validate = |code: &[u8], max_stack_height: u16| {
+            validate_container(&container(&[(entry(max_stack_height), code)], &[]))
+        };
+
+        // Undefined and legacy-only opcodes
+        assert_eq!(validate(&[0x0c, 0x00], 0), Err(EofError::InvalidOpcode { section: 0, pc: 0, opcode: 0x0c }));
+        assert_eq!(validate(&[0x5f, 0x56], 1), Err(EofError::InvalidOpcode { section: 0, pc: 1, opcode: 0x56 }));
+        // PUSH2 with a single byte left
+        assert_eq!(validate(&[0x61, 0x01], 1), Err(EofError::TruncatedImmediate { section: 0, pc: 0 }));
+        // RJUMP into the middle of PUSH1's immediate
+        assert_eq!(
+            validate(&[RJUMP, 0x00, 0x01, 0x60, 0x00, 0x00], 1),
+            Err(EofError::InvalidJumpTarget { section: 0, pc: 0 })
+        );
+        // ADD on an empty stack
+        assert_eq!(validate(&[0x01, 0x00], 1), Err(EofError::StackUnderflow { section: 0, pc: 0 }));
+        // PUSH0 falls off the end
+        assert_eq!(validate(&[0x5f], 1), Err(EofError::MissingTerminatingInstruction(0)));
+        // Nothing jumps to the second STOP
+        assert_eq!(validate(&[0x00, 0x00], 0), Err(EofError::UnreachableCode { section: 0, pc: 1 }));
+        // Looping back with one more item on the stack each time
+        assert_eq!(
+            validate(&[0x5f, RJUMP, 0xff, 0xfc], 1),
+            Err(EofError::StackHeightMismatch { section: 0, pc: 0 })
+        );
+        // RETF in the non-returning entry section
+        assert_eq!(validate(&[RETF], 0), Err(EofError::InvalidReturn { section: 0, pc: 0 }));
+    }
+}
@@ -1,6 +1,16 @@
 use ethereum_types::{Address, H256, U256};
+use ethereum_core::eip7691::calculate_blob_base_fee;
+use ethereum_core::Header;
+use crate::gas::GasCost;
+use crate::spec::{ChainConfig, Hardfork};
 use std::collections::HashSet;
 
+/// Maximum depth of nested CALL/CREATE frames
+pub const MAX_CALL_DEPTH: u32 = 1024;
+
+/// Largest code a create may deploy (EIP-170)
+pub const MAX_CODE_SIZE: usize = 24_576;
+
 #[derive(Debug, Clone)]
 pub struct ExecutionContext {
     pub caller: Address,
@@ -14,6 +24,16 @@ pub struct ExecutionContext {
     pub block: BlockContext,
     pub is_static: bool,
     pub depth: u32,
+    /// Fork whose opcode set and gas schedule apply
+    pub spec: Hardfork,
+    /// Most instructions the whole call tree may execute, independent of gas
+    pub max_steps: Option<u64>,
+    /// Serve the P256VERIFY precompile at 0x100 (RIP-7212)
+    pub p256_verify: bool,
+    /// Accounts and storage slots the transaction accessed before the frame
+    /// started, which cost the warm price (EIP-2929)
+    pub warm_addresses: HashSet<Address>,
+    pub warm_storage_keys: HashSet<(Address, H256)>,
 }
 
 #[derive(Debug, Clone)]
@@ -22,12 +42,40 @@ pub struct BlockContext {
     pub number: U256,
     pub timestamp: U256,
     pub difficulty: U256,
+    /// Beacon chain randomness, the header's mix hash since the merge
+    pub prev_randao: H256,
     pub gas_limit: U256,
     pub base_fee: Option<U256>,
+    pub blob_base_fee: Option<U256>,
     pub chain_id: U256,
+    /// Hashes of the blocks before this one, the parent first, at most 256
     pub block_hashes: Vec<H256>,
 }
 
+impl BlockContext {
+    /// Context of the block `header` belongs to on the chain `config` describes
+    pub fn from_header(header: &Header, config: &ChainConfig, block_hashes: Vec<H256>) -> Self {
+        // EIP-4844: blob base fee is derived from the header's excess blob gas,
+        // under the blob parameters of the fork active at its timestamp
+        let blob_base_fee = header.excess_blob_gas.map(|excess| {
+            calculate_blob_base_fee(excess, &config.blob_config(header.timestamp))
+        });
+
+        Self {
+            coinbase: header.beneficiary,
+            number: header.number,
+            timestamp: U256::from(header.timestamp),
+            difficulty: header.difficulty,
+            prev_randao: header.mix_hash,
+            gas_limit: header.gas_limit,
+            base_fee: header.base_fee_per_gas,
+            blob_base_fee,
+            chain_id: U256::from(config.chain_id),
+            block_hashes,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ExecutionResult {
     pub status: ExecutionStatus,
@@ -36,8 +84,16 @@ pub struct ExecutionResult {
     pub return_data: Vec<u8>,
     pub logs: Vec<Log>,
     pub created_address: Option<Address>,
+    /// Accounts and storage slots touched by the call tree (EIP-2929), not
+    /// counting the accounts that are warm from the start of the transaction
     pub accessed_addresses: HashSet<Address>,
     pub accessed_storage_keys: HashSet<(Address, H256)>,
+    /// Warm accounts and storage slots as the frame left them, which its
+    /// caller keeps only if the frame succeeded
+    pub warm_addresses: HashSet<Address>,
+    pub warm_storage_keys: HashSet<(Address, H256)>,
+    /// Instructions executed by the frame and its children
+    pub steps: u64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -60,6 +116,10 @@ pub enum HaltReason {
     PrecompileFailed,
     StateModificationInStatic,
     InvalidCode,
+    /// RETURNDATACOPY read past the output of the last call
+    ReturnDataOutOfBounds,
+    /// Ran into the context's `max_steps`
+    StepLimit,
 }
 
 #[derive(Debug, Clone)]
@@ -91,9 +151,22 @@ impl ExecutionContext {
             block,
             is_static: false,
             depth: 0,
+            spec: Hardfork::LATEST,
+            max_steps: None,
+            p256_verify: false,
+            warm_addresses: HashSet::new(),
+            warm_storage_keys: HashSet::new(),
         }
     }
 
+    /// Apply the rules of the fork `config` schedules for this block
+    pub fn with_chain_config(mut self, config: &ChainConfig) -> Self {
+        let timestamp = self.block.timestamp.low_u64();
+        self.spec = config.hardfork(self.block.number, timestamp);
+        self.p256_verify = config.rip7212_time.is_some_and(|at| timestamp >= at);
+        self
+    }
+
     pub fn is_create(&self) -> bool {
         self.address == Address::from_bytes([0u8; 20])
     }
@@ -104,6 +177,11 @@ impl ExecutionContext {
         ctx
     }
 
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
     pub fn with_depth(&self, depth: u32) -> Self {
         let mut ctx = self.clone();
         ctx.depth = depth;
@@ -122,6 +200,9 @@ impl Default for ExecutionResult {
             created_address: None,
             accessed_addresses: HashSet::new(),
             accessed_storage_keys: HashSet::new(),
+            warm_addresses: HashSet::new(),
+            warm_storage_keys: HashSet::new(),
+            steps: 0,
         }
     }
 }
@@ -152,4 +233,54 @@ impl ExecutionResult {
             ..Default::default()
         }
     }
+
+    /// Charge a successful create frame that was given `gas_limit` for the
+    /// code it returned
+    ///
+    /// The frame halts with all its gas used instead if it can't pay the
+    /// deposit, the code is over [`MAX_CODE_SIZE`] (EIP-170) or it starts
+    /// with 0xEF (EIP-3541).
+    pub fn deposit_code(&mut self, spec: Hardfork, gas_limit: u64) {
+        if self.status != ExecutionStatus::Success {
+            return;
+        }
+
+        let deposit_cost = GasCost::CODEDEPOSIT.saturating_mul(self.return_data.len() as u64);
+        let reason = if spec.is_enabled(Hardfork::SpuriousDragon) && self.return_data.len() > MAX_CODE_SIZE {
+            HaltReason::CreateContractTooLarge
+        } else if spec.is_enabled(Hardfork::London) && self.return_data.first() == Some(&0xef) {
+            HaltReason::InvalidCode
+        } else if self.gas_used.saturating_add(deposit_cost) > gas_limit {
+            HaltReason::OutOfGas
+        } else {
+            self.gas_used += deposit_cost;
+            return;
+        };
+
+        self.status = ExecutionStatus::Halt(reason);
+        self.gas_used = gas_limit;
+        self.return_data.clear();
+        self.logs.clear();
+    }
+}
+
+/// Selector of Solidity's `Error(string)`
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Reason string of ABI-encoded `Error(string)` revert output
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    let body = data.strip_prefix(&ERROR_SELECTOR[..])?;
+    let word = |at: usize| -> Option<usize> {
+        let word = body.get(at..at.checked_add(32)?)?;
+        if word[..24].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        Some(u64::from_be_bytes(word[24..].try_into().ok()?) as usize)
+    };
+
+    let offset = word(0)?;
+    let len = word(offset)?;
+    let start = offset.checked_add(32)?;
+    let bytes = body.get(start..start.checked_add(len)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
 }
\ No newline at end of file
@@ -1,4 +1,6 @@
 use crate::error::{EvmError, EvmResult};
+use crate::opcodes::Opcode;
+use crate::spec::Hardfork;
 use ethereum_types::U256;
 
 #[derive(Debug, Clone, Copy)]
@@ -93,37 +95,214 @@ impl GasCost {
     pub const COLD_ACCOUNT_ACCESS_COST: u64 = 2600;
     pub const WARM_STORAGE_WRITE_COST: u64 = 100;
 
+    /// Most of `gas_used` a transaction gets back from its refund counter,
+    /// half of it before London and a fifth from then on (EIP-3529)
+    pub fn max_refund(spec: Hardfork, gas_used: u64) -> u64 {
+        if spec.is_enabled(Hardfork::London) { gas_used / 5 } else { gas_used / 2 }
+    }
+
+    /// SLOAD: raised by EIP-150 and EIP-1884, then from Berlin cold on the
+    /// first read of a slot in a transaction and warm after that (EIP-2929)
+    pub fn sload(spec: Hardfork, warm: bool) -> u64 {
+        if spec.is_enabled(Hardfork::Berlin) {
+            if warm { Self::WARM_STORAGE_READ_COST } else { Self::COLD_SLOAD_COST }
+        } else if spec.is_enabled(Hardfork::Istanbul) {
+            800
+        } else if spec.is_enabled(Hardfork::TangerineWhistle) {
+            200
+        } else {
+            50
+        }
+    }
+
+    /// Account access by BALANCE, EXTCODE* and the CALL family
+    ///
+    /// From Berlin the first access to an account in a transaction is cold
+    /// and later ones are warm (EIP-2929). Before that each opcode has its
+    /// own price, raised by EIP-150 and, for BALANCE and EXTCODEHASH, by
+    /// EIP-1884.
+    pub fn account_access(spec: Hardfork, opcode: Opcode, warm: bool) -> u64 {
+        if spec.is_enabled(Hardfork::Berlin) {
+            return if warm { Self::WARM_STORAGE_READ_COST } else { Self::COLD_ACCOUNT_ACCESS_COST };
+        }
+
+        let istanbul = spec.is_enabled(Hardfork::Istanbul);
+        let tangerine_whistle = spec.is_enabled(Hardfork::TangerineWhistle);
+        match opcode {
+            Opcode::BALANCE | Opcode::EXTCODEHASH if istanbul => 700,
+            Opcode::BALANCE if tangerine_whistle => 400,
+            Opcode::BALANCE => 20,
+            Opcode::EXTCODEHASH => 400,
+            _ if tangerine_whistle => 700,
+            Opcode::EXTCODESIZE | Opcode::EXTCODECOPY => 20,
+            _ => 40,
+        }
+    }
+
+    /// SELFDESTRUCT: 5000 from EIP-150, plus the cold access of the
+    /// beneficiary from Berlin (EIP-2929)
+    pub fn selfdestruct(spec: Hardfork, warm: bool) -> u64 {
+        let mut cost = if spec.is_enabled(Hardfork::TangerineWhistle) { Self::SELFDESTRUCT } else { 0 };
+        if spec.is_enabled(Hardfork::Berlin) && !warm {
+            cost += Self::COLD_ACCOUNT_ACCESS_COST;
+        }
+        cost
+    }
+
+    /// EIP-150: a sub-call may receive at most all but one 64th of the available gas
+    pub fn max_call_gas(available: u64) -> u64 {
+        available - available / 64
+    }
+
+    /// Gas a sub-call asking for `requested` receives out of `available`
+    ///
+    /// From EIP-150 the request is capped at all but one 64th of what is
+    /// available. Before that the call gets what it asks for, and the caller
+    /// runs out of gas if it can't pay for it.
+    pub fn call_gas(spec: Hardfork, available: u64, requested: U256) -> u64 {
+        let requested = Self::saturating_u64(requested);
+        if spec.is_enabled(Hardfork::TangerineWhistle) {
+            requested.min(Self::max_call_gas(available))
+        } else {
+            requested
+        }
+    }
+
+    /// Total cost of a memory of `size` bytes: 3 * words + words^2 / 512
     pub fn memory_gas_cost(size: U256) -> u64 {
-        let size_u64 = size.as_u64();
-        let memory_size_word = (size_u64 + 31) / 32;
+        let memory_size_word = Self::word_count(size);
         
         let linear_cost = memory_size_word.saturating_mul(Self::MEMORY);
-        let quadratic_cost = memory_size_word.saturating_pow(2) / 512;
+        let quadratic_cost = memory_size_word.saturating_mul(memory_size_word) / 512;
         
         linear_cost.saturating_add(quadratic_cost)
     }
 
+    /// Cost of growing memory of `current_len` bytes so that `size` bytes at
+    /// `offset` are addressable. Zero-sized accesses never expand memory.
+    pub fn memory_expansion_cost(current_len: usize, offset: U256, size: U256) -> u64 {
+        if size.is_zero() {
+            return 0;
+        }
+        
+        let end = match offset.checked_add(size) {
+            Some(end) => end,
+            None => return u64::MAX,
+        };
+        
+        let current = U256::from(current_len);
+        if end <= current {
+            return 0;
+        }
+        
+        Self::memory_gas_cost(end).saturating_sub(Self::memory_gas_cost(current))
+    }
+
+    /// EXP: 10 + 50 * byte length of the exponent (EIP-160)
     pub fn exp_gas_cost(exponent: U256) -> u64 {
         let byte_size = (exponent.bits() + 7) / 8;
         Self::EXP.saturating_add(Self::EXPBYTE.saturating_mul(byte_size as u64))
     }
 
-    pub fn keccak256_gas_cost(data_size: U256) -> u64 {
-        let size_u64 = data_size.as_u64();
-        let word_size = (size_u64 + 31) / 32;
-        Self::KECCAK256.saturating_add(Self::KECCAK256WORD.saturating_mul(word_size))
+    /// KECCAK256: 30 + 6 * words + memory expansion
+    pub fn keccak256_gas_cost(data_size: U256, memory_expansion: u64) -> u64 {
+        Self::KECCAK256
+            .saturating_add(Self::KECCAK256WORD.saturating_mul(Self::word_count(data_size)))
+            .saturating_add(memory_expansion)
     }
 
-    pub fn copy_gas_cost(data_size: U256) -> u64 {
-        let size_u64 = data_size.as_u64();
-        let word_size = (size_u64 + 31) / 32;
-        Self::COPY.saturating_mul(word_size)
+    /// Dynamic part of the *COPY opcodes: 3 * words + memory expansion
+    pub fn copy_gas_cost(data_size: U256, memory_expansion: u64) -> u64 {
+        Self::COPY
+            .saturating_mul(Self::word_count(data_size))
+            .saturating_add(memory_expansion)
     }
 
-    pub fn log_gas_cost(topic_count: u8, data_size: U256) -> u64 {
-        let size_u64 = data_size.as_u64();
+    /// LOGn: 375 + 375 * topics + 8 * bytes + memory expansion
+    pub fn log_gas_cost(topic_count: u8, data_size: U256, memory_expansion: u64) -> u64 {
+        let size_u64 = Self::saturating_u64(data_size);
         Self::LOG
             .saturating_add(Self::LOGTOPIC.saturating_mul(topic_count as u64))
             .saturating_add(Self::LOGDATA.saturating_mul(size_u64))
+            .saturating_add(memory_expansion)
+    }
+
+    /// CREATE: 32000 + memory expansion, CREATE2 also 6 * words of init
+    /// code for hashing it into the address
+    pub fn create_gas_cost(hashes_init_code: bool, init_code_size: U256, memory_expansion: u64) -> u64 {
+        let hashing = if hashes_init_code {
+            Self::KECCAK256WORD.saturating_mul(Self::word_count(init_code_size))
+        } else {
+            0
+        };
+        Self::CREATE
+            .saturating_add(hashing)
+            .saturating_add(memory_expansion)
+    }
+
+    fn word_count(size: U256) -> u64 {
+        let size_u64 = Self::saturating_u64(size);
+        size_u64 / 32 + u64::from(size_u64 % 32 != 0)
     }
-}
\ No newline at end of file
+
+    fn saturating_u64(value: U256) -> u64 {
+        if value > U256::from(u64::MAX) {
+            u64::MAX
+        } else {
+            value.as_u64()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exp_gas_cost() {
+        assert_eq!(GasCost::exp_gas_cost(U256::zero()), 10);
+        assert_eq!(GasCost::exp_gas_cost(U256::from(0xff)), 60);
+        assert_eq!(GasCost::exp_gas_cost(U256::from(0x100)), 110);
+        assert_eq!(GasCost::exp_gas_cost(U256::MAX), 10 + 50 * 32);
+    }
+
+    #[test]
+    fn test_memory_expansion_cost() {
+        assert_eq!(GasCost::memory_gas_cost(U256::from(32)), 3);
+        // 32 words: 3 * 32 + 32^2 / 512
+        assert_eq!(GasCost::memory_gas_cost(U256::from(1024)), 98);
+
+        assert_eq!(GasCost::memory_expansion_cost(0, U256::zero(), U256::from(1024)), 98);
+        assert_eq!(GasCost::memory_expansion_cost(1024, U256::from(1000), U256::from(24)), 0);
+        assert_eq!(GasCost::memory_expansion_cost(32, U256::from(32), U256::from(32)), 3);
+        // Zero-sized access at a huge offset is free
+        assert_eq!(GasCost::memory_expansion_cost(0, U256::MAX, U256::zero()), 0);
+        assert_eq!(GasCost::memory_expansion_cost(0, U256::MAX, U256::one()), u64::MAX);
+    }
+
+    #[test]
+    fn test_keccak256_gas_cost() {
+        assert_eq!(GasCost::keccak256_gas_cost(U256::zero(), 0), 30);
+        let expansion = GasCost::memory_expansion_cost(0, U256::zero(), U256::from(32));
+        assert_eq!(GasCost::keccak256_gas_cost(U256::from(32), expansion), 39);
+        let expansion = GasCost::memory_expansion_cost(0, U256::zero(), U256::from(33));
+        assert_eq!(GasCost::keccak256_gas_cost(U256::from(33), expansion), 30 + 12 + 6);
+    }
+
+    #[test]
+    fn test_copy_gas_cost() {
+        assert_eq!(GasCost::copy_gas_cost(U256::zero(), 0), 0);
+        assert_eq!(GasCost::copy_gas_cost(U256::from(1), 0), 3);
+        let expansion = GasCost::memory_expansion_cost(0, U256::zero(), U256::from(33));
+        assert_eq!(GasCost::copy_gas_cost(U256::from(33), expansion), 12);
+        assert_eq!(GasCost::copy_gas_cost(U256::from(1024), 0), 96);
+    }
+
+    #[test]
+    fn test_log_gas_cost() {
+        assert_eq!(GasCost::log_gas_cost(0, U256::zero(), 0), 375);
+        let expansion = GasCost::memory_expansion_cost(0, U256::zero(), U256::from(64));
+        assert_eq!(GasCost::log_gas_cost(2, U256::from(64), expansion), 375 + 750 + 512 + 6);
+        assert_eq!(GasCost::log_gas_cost(4, U256::from(1), 0), 375 + 1500 + 8);
+    }
+}
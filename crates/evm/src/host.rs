@@ -0,0 +1,160 @@
+use crate::{
+    error::{EvmError, EvmResult},
+    execution::{BlockContext, ExecutionContext, ExecutionResult, Log},
+    interpreter::Interpreter,
+    state::{Checkpoint, StateDB},
+    opcodes::Opcode,
+    Account,
+};
+use ethereum_types::{Address, H256, U256};
+
+/// Interpreter state just before an instruction runs
+#[derive(Debug)]
+pub struct Step<'a> {
+    /// Account whose code runs and whose storage the instruction sees
+    pub address: Address,
+    pub pc: usize,
+    pub opcode: Opcode,
+    /// Gas left before the instruction is charged
+    pub gas: u64,
+    pub depth: u32,
+    /// Stack items, bottom first
+    pub stack: &'a [U256],
+    pub memory: &'a [u8],
+}
+
+/// Nested frame a CALL-family or CREATE opcode is about to run
+#[derive(Debug)]
+pub struct Frame<'a> {
+    pub opcode: Opcode,
+    /// Account executing the opcode
+    pub from: Address,
+    /// Callee, or the address of the contract being created
+    pub to: Address,
+    pub value: U256,
+    /// Gas handed to the frame, stipend included
+    pub gas: u64,
+    /// Calldata, or the init code of a creation
+    pub input: &'a [u8],
+    /// Whether `to` is a precompile, which runs without any steps
+    pub precompile: bool,
+}
+
+/// Environment an `Interpreter` runs against
+///
+/// Every account and storage access, block hash lookup, emitted log and
+/// nested frame of the interpreter goes through the host, so overrides and
+/// tracers can sit in between without re-implementing a whole `StateDB`.
+///
+/// Any `StateDB` is a host: block hashes come from the block context, logs
+/// are only collected in the result and nested frames run on a new
+/// interpreter over the same state.
+pub trait Host {
+    fn get_account(&self, address: &Address) -> Option<Account>;
+    fn set_account(&mut self, address: Address, account: Account);
+    fn remove_account(&mut self, address: &Address);
+
+    /// No balance, nonce or code (EIP-161)
+    fn is_empty(&self, address: &Address) -> bool;
+
+    /// Whether `address` did not exist before the current transaction
+    fn is_created(&self, address: &Address) -> bool;
+
+    fn get_storage(&self, address: &Address, key: &H256) -> H256;
+    fn set_storage(&mut self, address: Address, key: H256, value: H256);
+    fn get_transient_storage(&self, address: &Address, key: &H256) -> H256;
+    fn set_transient_storage(&mut self, address: Address, key: H256, value: H256);
+
+    /// Start a section of changes that can be rolled back as a unit
+    fn checkpoint(&mut self) -> Checkpoint;
+
+    /// Undo every change made since `checkpoint` was taken
+    fn revert_to(&mut self, checkpoint: Checkpoint);
+
+    /// Keep the changes made since `checkpoint`
+    fn commit(&mut self, checkpoint: Checkpoint);
+
+    /// Hash of block `number`, zero unless it is one of the 256 blocks before `block`
+    fn block_hash(&self, block: &BlockContext, number: U256) -> H256 {
+        if number >= block.number || block.number - number > U256::from(256) {
+            return H256::zero();
+        }
+        let age = (block.number - number).as_usize();
+        block.block_hashes.get(age - 1).copied().unwrap_or_default()
+    }
+
+    /// Called for every log as it is emitted, before it is known whether
+    /// the emitting frame is reverted
+    fn log(&mut self, _log: &Log) {}
+
+    /// Called before every instruction of every frame
+    fn step(&mut self, _step: &Step<'_>) {}
+
+    /// Called once the instruction announced by `step` has run, with the gas
+    /// it was charged and the error it failed with, if any
+    fn step_end(&mut self, _gas_cost: u64, _error: Option<&EvmError>) {}
+
+    /// Called when a CALL-family or CREATE opcode starts a nested frame
+    fn enter_frame(&mut self, _frame: &Frame<'_>) {}
+
+    /// Called when the frame announced by `enter_frame` returns
+    fn exit_frame(&mut self, _result: &ExecutionResult) {}
+
+    /// Run the nested frame of a CALL-family or CREATE opcode
+    fn call(&mut self, context: ExecutionContext) -> EvmResult<ExecutionResult>
+    where
+        Self: Sized,
+    {
+        Interpreter::new(context, self).run()
+    }
+}
+
+impl<S: StateDB> Host for S {
+    fn get_account(&self, address: &Address) -> Option<Account> {
+        StateDB::get_account(self, address)
+    }
+
+    fn set_account(&mut self, address: Address, account: Account) {
+        StateDB::set_account(self, address, account)
+    }
+
+    fn remove_account(&mut self, address: &Address) {
+        StateDB::remove_account(self, address)
+    }
+
+    fn is_empty(&self, address: &Address) -> bool {
+        StateDB::is_empty(self, address)
+    }
+
+    fn is_created(&self, address: &Address) -> bool {
+        StateDB::is_created(self, address)
+    }
+
+    fn get_storage(&self, address: &Address, key: &H256) -> H256 {
+        StateDB::get_storage(self, address, key)
+    }
+
+    fn set_storage(&mut self, address: Address, key: H256, value: H256) {
+        StateDB::set_storage(self, address, key, value)
+    }
+
+    fn get_transient_storage(&self, address: &Address, key: &H256) -> H256 {
+        StateDB::get_transient_storage(self, address, key)
+    }
+
+    fn set_transient_storage(&mut self, address: Address, key: H256, value: H256) {
+        StateDB::set_transient_storage(self, address, key, value)
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        StateDB::checkpoint(self)
+    }
+
+    fn revert_to(&mut self, checkpoint: Checkpoint) {
+        StateDB::revert_to(self, checkpoint)
+    }
+
+    fn commit(&mut self, checkpoint: Checkpoint) {
+        StateDB::commit(self, checkpoint)
+    }
+}
@@ -1,19 +1,23 @@
 use crate::{
     error::{EvmError, EvmResult},
-    execution::{ExecutionContext, ExecutionResult, HaltReason, Log},
+    execution::{ExecutionContext, ExecutionResult, ExecutionStatus, HaltReason, Log, MAX_CALL_DEPTH},
     gas::{Gas, GasCost},
     memory::Memory,
     opcodes::Opcode,
+    precompiled::{get_precompiled, PrecompiledContract},
+    spec::Hardfork,
     stack::Stack,
-    state::StateDB,
+    host::{Frame, Host, Step},
+    Account,
 };
 use ethereum_crypto::keccak256;
 use ethereum_types::{Address, H256, U256};
 use std::cmp::min;
+use std::collections::HashSet;
 
-pub struct Interpreter<'a, S: StateDB> {
+pub struct Interpreter<'a, H: Host> {
     context: ExecutionContext,
-    state: &'a mut S,
+    host: &'a mut H,
     stack: Stack,
     memory: Memory,
     gas: Gas,
@@ -21,14 +25,29 @@ pub struct Interpreter<'a, S: StateDB> {
     return_data: Vec<u8>,
     logs: Vec<Log>,
     result: Option<ExecutionResult>,
+    /// Instructions executed by this frame and its children
+    steps: u64,
+    /// Accounts and storage slots touched by this frame and its children (EIP-2929)
+    accessed_addresses: HashSet<Address>,
+    accessed_storage_keys: HashSet<(Address, H256)>,
 }
 
-impl<'a, S: StateDB> Interpreter<'a, S> {
-    pub fn new(context: ExecutionContext, state: &'a mut S) -> Self {
+impl<'a, H: Host> Interpreter<'a, H> {
+    pub fn new(mut context: ExecutionContext, host: &'a mut H) -> Self {
+        // The caller and the frame's own account are always warm, and a
+        // transaction starts with its origin and, from Shanghai, the coinbase
+        // warm as well (EIP-2929, EIP-3651)
+        context.warm_addresses.extend([context.caller, context.address]);
+        if context.depth == 0 {
+            context.warm_addresses.insert(context.origin);
+            if context.spec.is_enabled(Hardfork::Shanghai) {
+                context.warm_addresses.insert(context.block.coinbase);
+            }
+        }
         let gas = Gas::new(context.gas_limit);
         Self {
             context,
-            state,
+            host,
             stack: Stack::new(),
             memory: Memory::new(),
             gas,
@@ -36,24 +55,82 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
             return_data: Vec::new(),
             logs: Vec::new(),
             result: None,
+            steps: 0,
+            accessed_addresses: HashSet::new(),
+            accessed_storage_keys: HashSet::new(),
         }
     }
 
+    /// Run the frame, rolling back its state changes unless it succeeds
     pub fn run(&mut self) -> EvmResult<ExecutionResult> {
+        let checkpoint = self.host.checkpoint();
+        let mut result = self.execute();
+        // Touched entries are reported whatever the outcome, a failed frame still read them
+        result.accessed_addresses = std::mem::take(&mut self.accessed_addresses);
+        result.accessed_storage_keys = std::mem::take(&mut self.accessed_storage_keys);
+        result.warm_addresses = std::mem::take(&mut self.context.warm_addresses);
+        result.warm_storage_keys = std::mem::take(&mut self.context.warm_storage_keys);
+        result.steps = self.steps;
+
+        if result.status == ExecutionStatus::Success {
+            self.host.commit(checkpoint);
+        } else {
+            self.host.revert_to(checkpoint);
+        }
+        Ok(result)
+    }
+
+    /// Step budget left for a child frame, shared with the rest of the call tree
+    fn remaining_steps(&self) -> Option<u64> {
+        self.context.max_steps.map(|max| max.saturating_sub(self.steps))
+    }
+
+    fn run_child(&mut self, context: ExecutionContext) -> EvmResult<ExecutionResult> {
+        let result = self.host.call(context)?;
+        self.steps += result.steps;
+        self.accessed_addresses.extend(&result.accessed_addresses);
+        self.accessed_storage_keys.extend(&result.accessed_storage_keys);
+        // A frame that fails takes its accesses back with its other changes
+        if result.status == ExecutionStatus::Success {
+            self.context.warm_addresses.extend(&result.warm_addresses);
+            self.context.warm_storage_keys.extend(&result.warm_storage_keys);
+        }
+        Ok(result)
+    }
+
+    fn execute(&mut self) -> ExecutionResult {
         while self.pc < self.context.code.len() {
+            if self.context.max_steps.is_some_and(|max| self.steps >= max) {
+                return ExecutionResult::halt(HaltReason::StepLimit, self.gas.used());
+            }
+            self.steps += 1;
+
             let opcode_byte = self.context.code[self.pc];
             let opcode = match Opcode::from_u8(opcode_byte) {
-                Some(op) => op,
-                None => {
-                    return Ok(ExecutionResult::halt(
+                Some(op) if self.context.spec.supports(op) => op,
+                _ => {
+                    return ExecutionResult::halt(
                         HaltReason::InvalidOpcode(opcode_byte),
-                        self.gas.used(),
-                    ));
+                        self.gas.limit(),
+                    );
                 }
             };
 
-            if let Err(e) = self.execute_opcode(opcode) {
-                return Ok(self.handle_error(e));
+            let gas_before = self.gas.remaining();
+            self.host.step(&Step {
+                address: self.context.address,
+                pc: self.pc,
+                opcode,
+                gas: gas_before,
+                depth: self.context.depth,
+                stack: self.stack.data(),
+                memory: self.memory.data(),
+            });
+            let outcome = self.execute_opcode(opcode);
+            let gas_cost = gas_before.saturating_sub(self.gas.remaining());
+            self.host.step_end(gas_cost, outcome.as_ref().err());
+            if let Err(e) = outcome {
+                return self.handle_error(e);
             }
 
             if self.result.is_some() {
@@ -61,9 +138,13 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
             }
         }
 
-        Ok(self.result.take().unwrap_or_else(|| {
+        let mut result = self.result.take().unwrap_or_else(|| {
             ExecutionResult::success(Vec::new(), self.gas.used())
-        }))
+        });
+        if result.status == ExecutionStatus::Success {
+            result.logs = std::mem::take(&mut self.logs);
+        }
+        result
     }
 
     fn execute_opcode(&mut self, opcode: Opcode) -> EvmResult<()> {
@@ -323,7 +404,8 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
             Opcode::KECCAK256 => {
                 let offset = self.stack.pop()?;
                 let size = self.stack.pop()?;
-                self.gas.consume(GasCost::keccak256_gas_cost(size))?;
+                let expansion = self.memory_expansion(offset, size);
+                self.gas.consume(GasCost::keccak256_gas_cost(size, expansion))?;
                 let data = self.memory.get(offset.as_usize(), size.as_usize());
                 let hash = keccak256(&data);
                 self.stack.push(U256::from(hash.as_bytes()))?;
@@ -339,10 +421,11 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 Ok(())
             }
             Opcode::BALANCE => {
-                let address = self.stack.pop()?;
-                self.gas.consume(GasCost::BALANCE)?;
-                let balance = self.state
-                    .get_account(&address_from_u256(address))
+                let address = address_from_u256(self.stack.pop()?);
+                let warm = self.touch_address(address);
+                self.gas.consume(GasCost::account_access(self.context.spec, Opcode::BALANCE, warm))?;
+                let balance = self.host
+                    .get_account(&address)
                     .map(|acc| acc.balance)
                     .unwrap_or_default();
                 self.stack.push(balance)?;
@@ -385,7 +468,9 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 let mem_offset = self.stack.pop()?;
                 let data_offset = self.stack.pop()?;
                 let size = self.stack.pop()?;
-                self.gas.consume(GasCost::copy_gas_cost(size))?;
+                let expansion = self.memory_expansion(mem_offset, size);
+                self.gas.consume(GasCost::VERYLOW)?;
+                self.gas.consume(GasCost::copy_gas_cost(size, expansion))?;
                 let data = self.get_data(data_offset, size);
                 self.memory.set(mem_offset.as_usize(), &data)?;
                 self.pc += 1;
@@ -401,7 +486,9 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 let mem_offset = self.stack.pop()?;
                 let code_offset = self.stack.pop()?;
                 let size = self.stack.pop()?;
-                self.gas.consume(GasCost::copy_gas_cost(size))?;
+                let expansion = self.memory_expansion(mem_offset, size);
+                self.gas.consume(GasCost::VERYLOW)?;
+                self.gas.consume(GasCost::copy_gas_cost(size, expansion))?;
                 let code = self.get_code(code_offset, size);
                 self.memory.set(mem_offset.as_usize(), &code)?;
                 self.pc += 1;
@@ -414,10 +501,11 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 Ok(())
             }
             Opcode::EXTCODESIZE => {
-                let address = self.stack.pop()?;
-                self.gas.consume(GasCost::EXTCODESIZE)?;
-                let size = self.state
-                    .get_account(&address_from_u256(address))
+                let address = address_from_u256(self.stack.pop()?);
+                let warm = self.touch_address(address);
+                self.gas.consume(GasCost::account_access(self.context.spec, Opcode::EXTCODESIZE, warm))?;
+                let size = self.host
+                    .get_account(&address)
                     .map(|acc| acc.code.len())
                     .unwrap_or(0);
                 self.stack.push(U256::from(size))?;
@@ -425,15 +513,17 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 Ok(())
             }
             Opcode::EXTCODECOPY => {
-                let address = self.stack.pop()?;
+                let address = address_from_u256(self.stack.pop()?);
+                let warm = self.touch_address(address);
                 let mem_offset = self.stack.pop()?;
                 let code_offset = self.stack.pop()?;
                 let size = self.stack.pop()?;
-                self.gas.consume(GasCost::EXTCODECOPY)?;
-                self.gas.consume(GasCost::copy_gas_cost(size))?;
+                let expansion = self.memory_expansion(mem_offset, size);
+                self.gas.consume(GasCost::account_access(self.context.spec, Opcode::EXTCODECOPY, warm))?;
+                self.gas.consume(GasCost::copy_gas_cost(size, expansion))?;
                 
-                let code = self.state
-                    .get_account(&address_from_u256(address))
+                let code = self.host
+                    .get_account(&address)
                     .map(|acc| self.get_slice(&acc.code, code_offset, size))
                     .unwrap_or_else(|| vec![0; size.as_usize()]);
                 self.memory.set(mem_offset.as_usize(), &code)?;
@@ -450,7 +540,9 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 let mem_offset = self.stack.pop()?;
                 let data_offset = self.stack.pop()?;
                 let size = self.stack.pop()?;
-                self.gas.consume(GasCost::copy_gas_cost(size))?;
+                let expansion = self.memory_expansion(mem_offset, size);
+                self.gas.consume(GasCost::VERYLOW)?;
+                self.gas.consume(GasCost::copy_gas_cost(size, expansion))?;
                 
                 if data_offset.saturating_add(size) > U256::from(self.return_data.len()) {
                     return Err(EvmError::ReturnDataOutOfBounds);
@@ -462,10 +554,11 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 Ok(())
             }
             Opcode::EXTCODEHASH => {
-                let address = self.stack.pop()?;
-                self.gas.consume(GasCost::EXTCODEHASH)?;
-                let hash = self.state
-                    .get_account(&address_from_u256(address))
+                let address = address_from_u256(self.stack.pop()?);
+                let warm = self.touch_address(address);
+                self.gas.consume(GasCost::account_access(self.context.spec, Opcode::EXTCODEHASH, warm))?;
+                let hash = self.host
+                    .get_account(&address)
                     .map(|acc| {
                         if acc.code.is_empty() {
                             H256::zero()
@@ -483,13 +576,7 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
             Opcode::BLOCKHASH => {
                 let block_number = self.stack.pop()?;
                 self.gas.consume(GasCost::BLOCKHASH)?;
-                let hash = if block_number >= self.context.block.number || 
-                    self.context.block.number - block_number > U256::from(256) {
-                    H256::zero()
-                } else {
-                    let index = block_number.as_usize();
-                    self.context.block.block_hashes.get(index).copied().unwrap_or_default()
-                };
+                let hash = self.host.block_hash(&self.context.block, block_number);
                 self.stack.push(U256::from(hash.as_bytes()))?;
                 self.pc += 1;
                 Ok(())
@@ -514,7 +601,13 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
             }
             Opcode::DIFFICULTY => {
                 self.gas.consume(GasCost::BASE)?;
-                self.stack.push(self.context.block.difficulty)?;
+                // PREVRANDAO after the merge (EIP-4399)
+                let value = if self.context.spec.is_enabled(Hardfork::Merge) {
+                    U256::from_big_endian(self.context.block.prev_randao.as_bytes())
+                } else {
+                    self.context.block.difficulty
+                };
+                self.stack.push(value)?;
                 self.pc += 1;
                 Ok(())
             }
@@ -532,7 +625,7 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
             }
             Opcode::SELFBALANCE => {
                 self.gas.consume(GasCost::SELFBALANCE)?;
-                let balance = self.state
+                let balance = self.host
                     .get_account(&self.context.address)
                     .map(|acc| acc.balance)
                     .unwrap_or_default();
@@ -547,6 +640,13 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 self.pc += 1;
                 Ok(())
             }
+            Opcode::BLOBBASEFEE => {
+                self.gas.consume(GasCost::BASE)?;
+                let blob_base_fee = self.context.block.blob_base_fee.unwrap_or(U256::zero());
+                self.stack.push(blob_base_fee)?;
+                self.pc += 1;
+                Ok(())
+            }
 
             // Stack, Memory, Storage and Flow Operations
             Opcode::POP => {
@@ -556,35 +656,42 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 Ok(())
             }
             Opcode::MLOAD => {
-                self.gas.consume(GasCost::VERYLOW)?;
                 let offset = self.stack.pop()?;
+                let expansion = self.memory_expansion(offset, U256::from(32));
+                self.gas.consume(GasCost::VERYLOW.saturating_add(expansion))?;
+                // Reading touches memory like writing does
+                self.memory.resize(offset.as_usize() + 32);
                 let value = self.memory.get_u256(offset.as_usize());
                 self.stack.push(value)?;
                 self.pc += 1;
                 Ok(())
             }
             Opcode::MSTORE => {
-                self.gas.consume(GasCost::VERYLOW)?;
                 let offset = self.stack.pop()?;
                 let value = self.stack.pop()?;
+                let expansion = self.memory_expansion(offset, U256::from(32));
+                self.gas.consume(GasCost::VERYLOW.saturating_add(expansion))?;
                 self.memory.set_u256(offset.as_usize(), value)?;
                 self.pc += 1;
                 Ok(())
             }
             Opcode::MSTORE8 => {
-                self.gas.consume(GasCost::VERYLOW)?;
                 let offset = self.stack.pop()?;
                 let value = self.stack.pop()?;
-                self.memory.set_byte(offset.as_usize(), value.byte(31))?;
+                let expansion = self.memory_expansion(offset, U256::one());
+                self.gas.consume(GasCost::VERYLOW.saturating_add(expansion))?;
+                self.memory.set_byte(offset.as_usize(), value.byte(0))?;
                 self.pc += 1;
                 Ok(())
             }
             Opcode::SLOAD => {
                 let key = self.stack.pop()?;
-                self.gas.consume(GasCost::SLOAD)?;
                 let mut key_bytes = [0u8; 32];
                 key.to_big_endian(&mut key_bytes);
-                let value = self.state.get_storage(&self.context.address, &H256::from(key_bytes));
+                let key = H256::from(key_bytes);
+                let warm = self.touch_slot(key);
+                self.gas.consume(GasCost::sload(self.context.spec, warm))?;
+                let value = self.host.get_storage(&self.context.address, &key);
                 self.stack.push(U256::from(value.as_bytes()))?;
                 self.pc += 1;
                 Ok(())
@@ -598,10 +705,41 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 self.gas.consume(GasCost::SSET)?;
                 let mut key_bytes = [0u8; 32];
                 key.to_big_endian(&mut key_bytes);
+                let key = H256::from(key_bytes);
+                self.touch_slot(key);
                 let mut value_bytes = [0u8; 32];
                 value.to_big_endian(&mut value_bytes);
-                self.state.set_storage(
+                self.host.set_storage(
                     self.context.address, 
+                    key,
+                    H256::from(value_bytes)
+                );
+                self.pc += 1;
+                Ok(())
+            }
+            Opcode::TLOAD => {
+                let key = self.stack.pop()?;
+                self.gas.consume(GasCost::WARM_STORAGE_READ_COST)?;
+                let mut key_bytes = [0u8; 32];
+                key.to_big_endian(&mut key_bytes);
+                let value = self.host.get_transient_storage(&self.context.address, &H256::from(key_bytes));
+                self.stack.push(U256::from(value.as_bytes()))?;
+                self.pc += 1;
+                Ok(())
+            }
+            Opcode::TSTORE => {
+                if self.context.is_static {
+                    return Err(EvmError::StaticCallStateModification);
+                }
+                let key = self.stack.pop()?;
+                let value = self.stack.pop()?;
+                self.gas.consume(GasCost::WARM_STORAGE_WRITE_COST)?;
+                let mut key_bytes = [0u8; 32];
+                key.to_big_endian(&mut key_bytes);
+                let mut value_bytes = [0u8; 32];
+                value.to_big_endian(&mut value_bytes);
+                self.host.set_transient_storage(
+                    self.context.address,
                     H256::from(key_bytes),
                     H256::from(value_bytes)
                 );
@@ -704,20 +842,33 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                     topics.push(H256::from(topic_bytes));
                 }
                 
-                self.gas.consume(GasCost::log_gas_cost(topic_count, size))?;
+                let expansion = self.memory_expansion(offset, size);
+                self.gas.consume(GasCost::log_gas_cost(topic_count, size, expansion))?;
                 let data = self.memory.get(offset.as_usize(), size.as_usize());
                 
-                self.logs.push(Log {
+                let log = Log {
                     address: self.context.address,
                     topics,
                     data,
-                });
+                };
+                self.host.log(&log);
+                self.logs.push(log);
                 
                 self.pc += 1;
                 Ok(())
             }
 
             // System Operations
+            Opcode::CALL | Opcode::CALLCODE | Opcode::DELEGATECALL | Opcode::STATICCALL => {
+                self.call(opcode)?;
+                self.pc += 1;
+                Ok(())
+            }
+            Opcode::CREATE | Opcode::CREATE2 => {
+                self.create(opcode)?;
+                self.pc += 1;
+                Ok(())
+            }
             Opcode::RETURN => {
                 let offset = self.stack.pop()?;
                 let size = self.stack.pop()?;
@@ -732,6 +883,19 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
                 self.result = Some(ExecutionResult::revert(data, self.gas.used()));
                 Ok(())
             }
+            Opcode::SELFDESTRUCT => {
+                if self.context.is_static {
+                    return Err(EvmError::StaticCallStateModification);
+                }
+                let beneficiary = address_from_u256(self.stack.pop()?);
+                let warm = self.touch_address(beneficiary);
+                self.gas.consume(GasCost::selfdestruct(self.context.spec, warm))?;
+                self.self_destruct(beneficiary)?;
+                self.result = Some(ExecutionResult::success(Vec::new(), self.gas.used()));
+                Ok(())
+            }
+
+            Opcode::INVALID => Err(EvmError::InvalidOpcode(Opcode::INVALID as u8)),
 
             _ => {
                 self.pc += 1;
@@ -740,18 +904,335 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
         }
     }
 
+    /// An exceptional halt consumes all of the frame's gas
     fn handle_error(&self, error: EvmError) -> ExecutionResult {
-        match error {
-            EvmError::OutOfGas => ExecutionResult::halt(HaltReason::OutOfGas, self.gas.limit()),
-            EvmError::StackOverflow => ExecutionResult::halt(HaltReason::StackOverflow, self.gas.used()),
-            EvmError::StackUnderflow => ExecutionResult::halt(HaltReason::StackUnderflow, self.gas.used()),
-            EvmError::InvalidJump(_) => ExecutionResult::halt(HaltReason::InvalidJump, self.gas.used()),
-            EvmError::InvalidOpcode(op) => ExecutionResult::halt(HaltReason::InvalidOpcode(op), self.gas.used()),
-            EvmError::StaticCallStateModification => {
-                ExecutionResult::halt(HaltReason::StateModificationInStatic, self.gas.used())
+        let reason = match error {
+            EvmError::OutOfGas => HaltReason::OutOfGas,
+            EvmError::StackOverflow => HaltReason::StackOverflow,
+            EvmError::StackUnderflow => HaltReason::StackUnderflow,
+            EvmError::InvalidJump(_) => HaltReason::InvalidJump,
+            EvmError::InvalidOpcode(op) => HaltReason::InvalidOpcode(op),
+            EvmError::StaticCallStateModification => HaltReason::StateModificationInStatic,
+            EvmError::ReturnDataOutOfBounds => HaltReason::ReturnDataOutOfBounds,
+            _ => HaltReason::InvalidCode,
+        };
+        ExecutionResult::halt(reason, self.gas.limit())
+    }
+
+    fn call(&mut self, opcode: Opcode) -> EvmResult<()> {
+        let gas_requested = self.stack.pop()?;
+        let target = address_from_u256(self.stack.pop()?);
+        let warm = self.touch_address(target);
+        let value = match opcode {
+            Opcode::CALL | Opcode::CALLCODE => self.stack.pop()?,
+            Opcode::DELEGATECALL => self.context.value,
+            _ => U256::zero(),
+        };
+        if self.context.is_static && opcode == Opcode::CALL && !value.is_zero() {
+            return Err(EvmError::StaticCallStateModification);
+        }
+        let (in_offset, in_size) = memory_range(self.stack.pop()?, self.stack.pop()?)?;
+        let (out_offset, out_size) = memory_range(self.stack.pop()?, self.stack.pop()?)?;
+        // Memory grows to cover both the input and the output before the call
+        let memory_end = (in_offset + in_size).max(out_offset + out_size);
+
+        let has_value = matches!(opcode, Opcode::CALL | Opcode::CALLCODE) && !value.is_zero();
+        let mut cost = GasCost::account_access(self.context.spec, opcode, warm)
+            .saturating_add(self.memory_expansion(U256::zero(), U256::from(memory_end)));
+        if has_value {
+            cost += GasCost::CALLVALUE;
+        }
+        if opcode == Opcode::CALL && self.creates_account(&target, has_value) {
+            cost += GasCost::NEWACCOUNT;
+        }
+        self.gas.consume(cost)?;
+        self.memory.resize(memory_end);
+
+        let gas_limit = GasCost::call_gas(self.context.spec, self.gas.remaining(), gas_requested);
+        // The stipend comes on top of the forwarded gas at no cost to the caller
+        let callee_gas = if has_value { gas_limit + GasCost::CALLSTIPEND } else { gas_limit };
+
+        self.return_data.clear();
+
+        // The callee never runs past the depth limit, so none of its gas is consumed
+        if self.context.depth >= MAX_CALL_DEPTH {
+            self.stack.push(U256::zero())?;
+            return Ok(());
+        }
+
+        let transfers_value = matches!(opcode, Opcode::CALL) && !value.is_zero();
+        if transfers_value {
+            let balance = self.host
+                .get_account(&self.context.address)
+                .map(|acc| acc.balance)
+                .unwrap_or_default();
+            if balance < value {
+                self.stack.push(U256::zero())?;
+                return Ok(());
             }
-            _ => ExecutionResult::halt(HaltReason::InvalidCode, self.gas.used()),
         }
+
+        let input = self.memory.get(in_offset, in_size);
+        self.gas.consume(gas_limit)?;
+
+        let precompile = self.precompile(&target);
+        self.host.enter_frame(&Frame {
+            opcode,
+            from: self.context.address,
+            to: target,
+            value,
+            gas: callee_gas,
+            input: &input,
+            precompile: precompile.is_some(),
+        });
+
+        // The value transfer is undone together with the callee's changes
+        let checkpoint = self.host.checkpoint();
+        if transfers_value {
+            self.transfer(self.context.address, target, value);
+        }
+        let result = if let Some(precompile) = precompile {
+            match precompile.execute(&input, U256::from(callee_gas)) {
+                Ok((output, gas_used)) => ExecutionResult::success(output, gas_used.as_u64()),
+                Err(_) => ExecutionResult::halt(HaltReason::PrecompileFailed, callee_gas),
+            }
+        } else {
+            let mut context = self.context.clone();
+            match opcode {
+                Opcode::CALL | Opcode::STATICCALL => {
+                    context.caller = self.context.address;
+                    context.address = target;
+                }
+                Opcode::CALLCODE => {
+                    context.caller = self.context.address;
+                }
+                _ => {}
+            }
+            context.value = value;
+            context.code = self.host
+                .get_account(&target)
+                .map(|acc| acc.code)
+                .unwrap_or_default();
+            context.data = input;
+            context.gas_limit = callee_gas;
+            context.is_static = self.context.is_static || opcode == Opcode::STATICCALL;
+            context.depth = self.context.depth + 1;
+            context.max_steps = self.remaining_steps();
+
+            self.run_child(context)?
+        };
+        self.host.exit_frame(&result);
+
+        if result.status == ExecutionStatus::Success {
+            self.host.commit(checkpoint);
+        } else {
+            self.host.revert_to(checkpoint);
+        }
+
+        // Only successful or reverted frames hand back their unused gas,
+        // including whatever is left of the stipend
+        if !matches!(result.status, ExecutionStatus::Halt(_)) {
+            self.gas.refund(callee_gas.saturating_sub(result.gas_used));
+        }
+
+        let out_len = min(out_size, result.return_data.len());
+        self.memory.set(out_offset, &result.return_data[..out_len])?;
+
+        let success = result.status == ExecutionStatus::Success;
+        if success {
+            self.logs.extend(result.logs);
+        }
+        self.return_data = result.return_data;
+        self.stack.push(if success { U256::one() } else { U256::zero() })?;
+        Ok(())
+    }
+
+    fn create(&mut self, opcode: Opcode) -> EvmResult<()> {
+        if self.context.is_static {
+            return Err(EvmError::StaticCallStateModification);
+        }
+        let value = self.stack.pop()?;
+        let offset = self.stack.pop()?;
+        let size = self.stack.pop()?;
+        let salt = if opcode == Opcode::CREATE2 {
+            Some(self.stack.pop()?)
+        } else {
+            None
+        };
+
+        let (offset, size) = memory_range(offset, size)?;
+        let expansion = self.memory_expansion(U256::from(offset), U256::from(size));
+        self.gas.consume(GasCost::create_gas_cost(salt.is_some(), U256::from(size), expansion))?;
+        self.memory.resize(offset + size);
+        let init_code = self.memory.get(offset, size);
+
+        // The init code receives all of the remaining gas, or from EIP-150
+        // all but one 64th of it
+        let remaining = self.gas.remaining();
+        let gas_limit = GasCost::call_gas(self.context.spec, remaining, U256::from(remaining));
+
+        self.return_data.clear();
+
+        if self.context.depth >= MAX_CALL_DEPTH {
+            self.stack.push(U256::zero())?;
+            return Ok(());
+        }
+
+        let creator = self.host.get_account(&self.context.address).unwrap_or_default();
+        if creator.balance < value {
+            self.stack.push(U256::zero())?;
+            return Ok(());
+        }
+
+        if creator.nonce == u64::MAX {
+            self.stack.push(U256::zero())?;
+            return Ok(());
+        }
+
+        let address = match salt {
+            Some(salt) => create2_address(&self.context.address, salt, &init_code),
+            None => create_address(&self.context.address, creator.nonce),
+        };
+        self.touch_address(address);
+
+        // The creator's nonce stays bumped whatever happens to the new frame
+        let mut creator = creator;
+        creator.nonce += 1;
+        self.host.set_account(self.context.address, creator);
+
+        self.gas.consume(gas_limit)?;
+
+        // Collisions fail like a halted frame, keeping all the gas given to it
+        let existing = self.host.get_account(&address).unwrap_or_default();
+        if existing.nonce != 0 || !existing.code.is_empty() {
+            self.stack.push(U256::zero())?;
+            return Ok(());
+        }
+
+        self.host.enter_frame(&Frame {
+            opcode,
+            from: self.context.address,
+            to: address,
+            value,
+            gas: gas_limit,
+            input: &init_code,
+            precompile: false,
+        });
+
+        let checkpoint = self.host.checkpoint();
+        self.transfer(self.context.address, address, value);
+
+        // EIP-161: new contracts start at nonce 1
+        let mut account = self.host.get_account(&address).unwrap_or_default();
+        account.nonce = 1;
+        self.host.set_account(address, account);
+
+        let mut context = self.context.clone();
+        context.caller = self.context.address;
+        context.address = address;
+        context.value = value;
+        context.code = init_code;
+        context.data = Vec::new();
+        context.gas_limit = gas_limit;
+        context.depth = self.context.depth + 1;
+        context.max_steps = self.remaining_steps();
+
+        let mut result = self.run_child(context)?;
+        // The frame pays for storing the code it returned
+        result.deposit_code(self.context.spec, gas_limit);
+
+        if result.status == ExecutionStatus::Success {
+            self.host.exit_frame(&result);
+            self.gas.refund(gas_limit - result.gas_used);
+
+            let mut account = self.host.get_account(&address).unwrap_or_default();
+            account.code = result.return_data;
+            self.host.set_account(address, account);
+            self.host.commit(checkpoint);
+
+            self.logs.extend(result.logs);
+            self.stack.push(U256::from(address.as_bytes()))?;
+        } else {
+            self.host.exit_frame(&result);
+            self.host.revert_to(checkpoint);
+            if result.status == ExecutionStatus::Revert {
+                self.gas.refund(gas_limit.saturating_sub(result.gas_used));
+                self.return_data = result.return_data;
+            }
+            self.stack.push(U256::zero())?;
+        }
+        Ok(())
+    }
+
+    /// Precompile at `address` under the current context
+    fn precompile(&self, address: &Address) -> Option<Box<dyn PrecompiledContract>> {
+        get_precompiled(precompile_id(address)?, &self.context)
+    }
+
+    /// Add `address` to the accessed accounts, returning whether it was
+    /// warm already. Precompiles are always warm.
+    fn touch_address(&mut self, address: Address) -> bool {
+        self.accessed_addresses.insert(address);
+        let warm = !self.context.warm_addresses.insert(address);
+        warm || self.precompile(&address).is_some()
+    }
+
+    /// Add `key` of the executing account to the accessed storage slots,
+    /// returning whether it was warm already
+    fn touch_slot(&mut self, key: H256) -> bool {
+        let slot = (self.context.address, key);
+        self.accessed_storage_keys.insert(slot);
+        !self.context.warm_storage_keys.insert(slot)
+    }
+
+    /// Send the whole balance to `beneficiary`, removing the account only if
+    /// it was created in this transaction (EIP-6780)
+    fn self_destruct(&mut self, beneficiary: Address) -> EvmResult<()> {
+        let address = self.context.address;
+        let balance = self.host
+            .get_account(&address)
+            .map(|acc| acc.balance)
+            .unwrap_or_default();
+
+        // Before EIP-150 sending the balance to a new account was free
+        if self.context.spec.is_enabled(Hardfork::TangerineWhistle)
+            && self.creates_account(&beneficiary, !balance.is_zero())
+        {
+            self.gas.consume(GasCost::SELFDESTRUCT_NEWACCOUNT)?;
+        }
+
+        if beneficiary != address {
+            self.transfer(address, beneficiary, balance);
+        }
+        if self.host.is_created(&address) {
+            // Value sent to itself is burned along with the account
+            self.host.remove_account(&address);
+        }
+        Ok(())
+    }
+
+    /// Whether sending to `address` brings an account into existence: before
+    /// EIP-161 any missing account, from it only an empty one receiving value
+    fn creates_account(&self, address: &Address, has_value: bool) -> bool {
+        if self.context.spec.is_enabled(Hardfork::SpuriousDragon) {
+            has_value && self.host.is_empty(address)
+        } else {
+            self.host.get_account(address).is_none()
+        }
+    }
+
+    fn transfer(&mut self, from: Address, to: Address, value: U256) {
+        if value.is_zero() {
+            return;
+        }
+
+        let mut sender = self.host.get_account(&from).unwrap_or_default();
+        sender.balance = sender.balance.saturating_sub(value);
+        self.host.set_account(from, sender);
+
+        let mut recipient = self.host.get_account(&to).unwrap_or_else(Account::default);
+        recipient.balance = recipient.balance.saturating_add(value);
+        self.host.set_account(to, recipient);
     }
 
     fn jump(&mut self, dest: usize) -> EvmResult<()> {
@@ -763,6 +1244,11 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
         Ok(())
     }
 
+    /// Gas owed for growing memory to cover `size` bytes at `offset`
+    fn memory_expansion(&self, offset: U256, size: U256) -> u64 {
+        GasCost::memory_expansion_cost(self.memory.len(), offset, size)
+    }
+
     fn get_data(&self, offset: U256, size: U256) -> Vec<u8> {
         self.get_slice(&self.context.data, offset, size)
     }
@@ -873,8 +1359,83 @@ impl<'a, S: StateDB> Interpreter<'a, S> {
     }
 }
 
+/// Memory range of `size` bytes at `offset` as `(offset, size)`
+///
+/// An empty range starts at zero whatever its offset. A range past 4 GiB
+/// fails with out of gas, as no gas limit could pay for the expansion.
+fn memory_range(offset: U256, size: U256) -> EvmResult<(usize, usize)> {
+    if size.is_zero() {
+        return Ok((0, 0));
+    }
+    match offset.checked_add(size) {
+        Some(end) if end <= U256::from(u32::MAX) => Ok((offset.as_usize(), size.as_usize())),
+        _ => Err(EvmError::OutOfGas),
+    }
+}
+
 fn address_from_u256(value: U256) -> Address {
     let mut bytes = [0u8; 32];
     value.to_big_endian(&mut bytes);
     Address::from_slice(&bytes[12..]).unwrap_or_else(|_| Address::from_bytes([0u8; 20]))
-}
\ No newline at end of file
+}
+
+fn precompile_id(address: &Address) -> Option<u64> {
+    let bytes = address.as_bytes();
+    if bytes[..18].iter().any(|b| *b != 0) {
+        return None;
+    }
+    let id = u16::from_be_bytes([bytes[18], bytes[19]]) as u64;
+    (id != 0).then_some(id)
+}
+
+/// CREATE address: keccak256(rlp([sender, nonce]))[12..]
+pub fn create_address(sender: &Address, nonce: u64) -> Address {
+    let nonce_bytes: Vec<u8> = nonce
+        .to_be_bytes()
+        .iter()
+        .skip_while(|b| **b == 0)
+        .copied()
+        .collect();
+
+    let mut payload = Vec::with_capacity(30);
+    payload.push(0x80 + 20);
+    payload.extend_from_slice(sender.as_bytes());
+    match nonce_bytes.as_slice() {
+        [] => payload.push(0x80),
+        [b] if *b < 0x80 => payload.push(*b),
+        bytes => {
+            payload.push(0x80 + bytes.len() as u8);
+            payload.extend_from_slice(bytes);
+        }
+    }
+
+    let mut encoded = Vec::with_capacity(payload.len() + 1);
+    encoded.push(0xc0 + payload.len() as u8);
+    encoded.extend_from_slice(&payload);
+
+    let hash = keccak256(&encoded);
+    Address::from_slice(&hash.as_bytes()[12..]).unwrap_or_else(|_| Address::from_bytes([0u8; 20]))
+}
+
+/// CREATE2 address: keccak256(0xff ++ sender ++ salt ++ keccak256(init_code))[12..]
+pub fn create2_address(sender: &Address, salt: U256, init_code: &[u8]) -> Address {
+    let mut salt_bytes = [0u8; 32];
+    salt.to_big_endian(&mut salt_bytes);
+
+    let mut preimage = Vec::with_capacity(85);
+    preimage.push(0xff);
+    preimage.extend_from_slice(sender.as_bytes());
+    preimage.extend_from_slice(&salt_bytes);
+    preimage.extend_from_slice(keccak256(init_code).as_bytes());
+
+    let hash = keccak256(&preimage);
+    Address::from_slice(&hash.as_bytes()[12..]).unwrap_or_else(|_| Address::from_bytes([0u8; 20]))
+}
+
+/// Run a frame to completion, an aborted frame consumes all of its gas
+pub fn run_interpreter<H: Host>(context: ExecutionContext, host: &mut H) -> ExecutionResult {
+    let gas_limit = context.gas_limit;
+    Interpreter::new(context, host)
+        .run()
+        .unwrap_or_else(|_| ExecutionResult::halt(HaltReason::InvalidCode, gas_limit))
+}
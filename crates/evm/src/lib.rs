@@ -1,10 +1,14 @@
+#[cfg(feature = "eof")]
+pub mod eof;
 pub mod error;
 pub mod execution;
 pub mod gas;
+pub mod host;
 pub mod interpreter;
 pub mod memory;
 pub mod opcodes;
 pub mod precompiled;
+pub mod spec;
 pub mod stack;
 pub mod state;
 
@@ -12,9 +16,12 @@ pub mod state;
 mod tests;
 
 pub use error::{EvmError, EvmResult};
-pub use execution::{ExecutionContext, ExecutionResult};
-pub use interpreter::Interpreter;
+pub use execution::{decode_revert_reason, ExecutionContext, ExecutionResult};
+pub use host::{Frame, Host, Step};
+pub use interpreter::{run_interpreter, Interpreter};
 pub use precompiled::{PrecompiledContract, get_precompiled, is_precompiled};
+pub use spec::{ChainConfig, Hardfork};
+pub use state::{Checkpoint, JournaledState};
 
 use ethereum_types::{Address, H256, U256};
 use std::collections::HashMap;
@@ -43,7 +50,8 @@ impl Evm {
         &mut self,
         context: ExecutionContext,
     ) -> EvmResult<ExecutionResult> {
-        let mut interpreter = Interpreter::new(context, &mut self.state);
+        let mut state = JournaledState::new(&mut self.state);
+        let mut interpreter = Interpreter::new(context, &mut state);
         interpreter.run()
     }
 }
@@ -11,6 +11,10 @@ impl Memory {
         Self { data: Vec::new() }
     }
 
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
     pub fn resize(&mut self, new_size: usize) {
         if new_size > self.data.len() {
             self.data.resize(new_size, 0);
@@ -0,0 +1,237 @@
+use crate::opcodes::Opcode;
+use ethereum_core::{BlobGasConfig, BlobSchedule};
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+
+/// Protocol upgrades that changed EVM behaviour, in activation order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Hardfork {
+    Frontier,
+    Homestead,
+    /// EIP-150 gas cost changes for IO-heavy operations
+    TangerineWhistle,
+    /// EIP-158/161 state clearing
+    SpuriousDragon,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Merge,
+    Shanghai,
+    Cancun,
+}
+
+impl Hardfork {
+    /// Most recent fork the interpreter implements
+    pub const LATEST: Hardfork = Hardfork::Cancun;
+
+    /// Whether the rules of `fork` apply under this fork
+    pub fn is_enabled(self, fork: Hardfork) -> bool {
+        self >= fork
+    }
+
+    /// Fork that introduced `opcode`
+    pub fn introducing(opcode: Opcode) -> Hardfork {
+        match opcode {
+            Opcode::DELEGATECALL => Hardfork::Homestead,
+            Opcode::REVERT
+            | Opcode::RETURNDATASIZE
+            | Opcode::RETURNDATACOPY
+            | Opcode::STATICCALL => Hardfork::Byzantium,
+            Opcode::SHL
+            | Opcode::SHR
+            | Opcode::SAR
+            | Opcode::CREATE2
+            | Opcode::EXTCODEHASH => Hardfork::Constantinople,
+            Opcode::CHAINID | Opcode::SELFBALANCE => Hardfork::Istanbul,
+            Opcode::BASEFEE => Hardfork::London,
+            Opcode::PUSH0 => Hardfork::Shanghai,
+            Opcode::TLOAD
+            | Opcode::TSTORE
+            | Opcode::MCOPY
+            | Opcode::BLOBHASH
+            | Opcode::BLOBBASEFEE => Hardfork::Cancun,
+            _ => Hardfork::Frontier,
+        }
+    }
+
+    /// Whether `opcode` is defined under this fork
+    pub fn supports(self, opcode: Opcode) -> bool {
+        self.is_enabled(Self::introducing(opcode))
+    }
+}
+
+impl Default for Hardfork {
+    fn default() -> Self {
+        Self::LATEST
+    }
+}
+
+/// Fork activation points of a chain, in the layout of a genesis file's
+/// `config` section
+///
+/// Forks up to the merge activate at a block number, later ones at a
+/// timestamp. `None` means the fork never activates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    pub homestead_block: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dao_fork_block: Option<u64>,
+    pub eip150_block: Option<u64>,
+    pub eip155_block: Option<u64>,
+    pub eip158_block: Option<u64>,
+    pub byzantium_block: Option<u64>,
+    pub constantinople_block: Option<u64>,
+    pub petersburg_block: Option<u64>,
+    pub istanbul_block: Option<u64>,
+    pub berlin_block: Option<u64>,
+    pub london_block: Option<u64>,
+    pub arrow_glacier_block: Option<u64>,
+    pub gray_glacier_block: Option<u64>,
+    pub merge_netsplit_block: Option<u64>,
+    pub shanghai_time: Option<u64>,
+    pub cancun_time: Option<u64>,
+    /// Prague, which so far only raises the blob limits here (EIP-7691)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prague_time: Option<u64>,
+    pub terminal_total_difficulty: Option<String>,
+    pub terminal_total_difficulty_passed: Option<bool>,
+    /// Activation of the RIP-7212 P256VERIFY precompile, which rollups add
+    /// outside of the Ethereum forks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rip7212_time: Option<u64>,
+}
+
+impl ChainConfig {
+    /// Ethereum mainnet
+    pub fn mainnet() -> Self {
+        Self {
+            chain_id: 1,
+            homestead_block: Some(1_150_000),
+            dao_fork_block: Some(1_920_000),
+            eip150_block: Some(2_463_000),
+            eip155_block: Some(2_675_000),
+            eip158_block: Some(2_675_000),
+            byzantium_block: Some(4_370_000),
+            constantinople_block: Some(7_280_000),
+            petersburg_block: Some(7_280_000),
+            istanbul_block: Some(9_069_000),
+            berlin_block: Some(12_244_000),
+            london_block: Some(12_965_000),
+            arrow_glacier_block: Some(13_773_000),
+            gray_glacier_block: Some(15_050_000),
+            merge_netsplit_block: Some(15_537_394),
+            shanghai_time: Some(1_681_338_455),
+            cancun_time: Some(1_710_338_135),
+            prague_time: Some(1_746_612_311),
+            terminal_total_difficulty: Some("58750000000000000000000".to_string()),
+            terminal_total_difficulty_passed: Some(true),
+            rip7212_time: None,
+        }
+    }
+
+    /// Activation of the forks that set the blob parameters
+    pub fn blob_schedule(&self) -> BlobSchedule {
+        BlobSchedule { cancun_time: self.cancun_time, prague_time: self.prague_time }
+    }
+
+    /// Blob parameters of a block at `timestamp`
+    ///
+    /// Only blocks from Cancun on carry blob gas, so a timestamp before any
+    /// blob fork gets Cancun's parameters.
+    pub fn blob_config(&self, timestamp: u64) -> BlobGasConfig {
+        self.blob_schedule().config_at(timestamp).unwrap_or_else(BlobGasConfig::pre_7691)
+    }
+
+    /// Fork in effect for a block with the given number and timestamp
+    pub fn hardfork(&self, number: U256, timestamp: u64) -> Hardfork {
+        let by_time = [
+            (self.cancun_time, Hardfork::Cancun),
+            (self.shanghai_time, Hardfork::Shanghai),
+        ];
+        if let Some((_, fork)) = by_time.iter().find(|(at, _)| at.is_some_and(|at| timestamp >= at)) {
+            return *fork;
+        }
+
+        let by_number = [
+            (self.merge_netsplit_block, Hardfork::Merge),
+            (self.london_block, Hardfork::London),
+            (self.berlin_block, Hardfork::Berlin),
+            (self.istanbul_block, Hardfork::Istanbul),
+            (self.petersburg_block, Hardfork::Petersburg),
+            (self.constantinople_block, Hardfork::Constantinople),
+            (self.byzantium_block, Hardfork::Byzantium),
+            (self.eip158_block, Hardfork::SpuriousDragon),
+            (self.eip150_block, Hardfork::TangerineWhistle),
+            (self.homestead_block, Hardfork::Homestead),
+        ];
+        by_number
+            .iter()
+            .find(|(at, _)| at.is_some_and(|at| number >= U256::from(at)))
+            .map(|(_, fork)| *fork)
+            .unwrap_or(Hardfork::Frontier)
+    }
+}
+
+impl Default for ChainConfig {
+    /// Every fork active from genesis, as on a development chain
+    fn default() -> Self {
+        Self {
+            chain_id: 1337,
+            homestead_block: Some(0),
+            dao_fork_block: None,
+            eip150_block: Some(0),
+            eip155_block: Some(0),
+            eip158_block: Some(0),
+            byzantium_block: Some(0),
+            constantinople_block: Some(0),
+            petersburg_block: Some(0),
+            istanbul_block: Some(0),
+            berlin_block: Some(0),
+            london_block: Some(0),
+            arrow_glacier_block: None,
+            gray_glacier_block: None,
+            merge_netsplit_block: Some(0),
+            shanghai_time: Some(0),
+            cancun_time: Some(0),
+            prague_time: Some(0),
+            terminal_total_difficulty: Some("0".to_string()),
+            terminal_total_difficulty_passed: Some(true),
+            rip7212_time: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mainnet_hardforks() {
+        let config = ChainConfig::mainnet();
+
+        assert_eq!(config.hardfork(U256::zero(), 0), Hardfork::Frontier);
+        assert_eq!(config.hardfork(U256::from(2_463_000), 0), Hardfork::TangerineWhistle);
+        assert_eq!(config.hardfork(U256::from(2_675_000), 0), Hardfork::SpuriousDragon);
+        assert_eq!(config.hardfork(U256::from(9_069_000), 0), Hardfork::Istanbul);
+        assert_eq!(config.hardfork(U256::from(12_243_999), 0), Hardfork::Istanbul);
+        assert_eq!(config.hardfork(U256::from(12_244_000), 0), Hardfork::Berlin);
+        assert_eq!(config.hardfork(U256::from(17_034_870), 1_681_338_455), Hardfork::Shanghai);
+        assert_eq!(config.hardfork(U256::from(19_426_587), 1_710_338_135), Hardfork::Cancun);
+        assert_eq!(ChainConfig::default().hardfork(U256::zero(), 0), Hardfork::Cancun);
+    }
+
+    #[test]
+    fn test_opcode_availability() {
+        assert!(!Hardfork::Istanbul.supports(Opcode::PUSH0));
+        assert!(Hardfork::Shanghai.supports(Opcode::PUSH0));
+        assert!(!Hardfork::Shanghai.supports(Opcode::TLOAD));
+        assert!(Hardfork::Istanbul.supports(Opcode::CHAINID));
+        assert!(!Hardfork::Berlin.supports(Opcode::BASEFEE));
+        assert!(Hardfork::Frontier.supports(Opcode::ADD));
+    }
+}
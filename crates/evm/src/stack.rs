@@ -27,6 +27,11 @@ impl Stack {
         self.data.pop().ok_or(EvmError::StackUnderflow)
     }
 
+    /// Items from the bottom of the stack up
+    pub fn data(&self) -> &[U256] {
+        &self.data
+    }
+
     pub fn peek(&self, index: usize) -> EvmResult<&U256> {
         if index >= self.data.len() {
             return Err(EvmError::StackUnderflow);
@@ -1,6 +1,6 @@
 use crate::Account;
 use ethereum_types::{Address, H256, U256};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub trait StateDB {
     fn get_account(&self, address: &Address) -> Option<Account>;
@@ -10,6 +10,32 @@ pub trait StateDB {
     fn exists(&self, address: &Address) -> bool;
     fn is_empty(&self, address: &Address) -> bool;
     fn remove_account(&mut self, address: &Address);
+
+    /// Start a section of changes that can be rolled back as a unit
+    ///
+    /// Plain backends can't roll anything back, wrap them in a
+    /// `JournaledState` to get nested-call reverts.
+    fn checkpoint(&mut self) -> Checkpoint {
+        Checkpoint(0)
+    }
+
+    /// Undo every change made since `checkpoint` was taken
+    fn revert_to(&mut self, _checkpoint: Checkpoint) {}
+
+    /// Keep the changes made since `checkpoint`, the enclosing section can
+    /// still revert them
+    fn commit(&mut self, _checkpoint: Checkpoint) {}
+
+    fn get_transient_storage(&self, _address: &Address, _key: &H256) -> H256 {
+        H256::zero()
+    }
+
+    fn set_transient_storage(&mut self, _address: Address, _key: H256, _value: H256) {}
+
+    /// Whether `address` did not exist before the current transaction
+    fn is_created(&self, _address: &Address) -> bool {
+        false
+    }
 }
 
 impl StateDB for HashMap<Address, Account> {
@@ -54,6 +80,155 @@ impl StateDB for HashMap<Address, Account> {
     }
 }
 
+/// Position in a `JournaledState` to revert to or commit
+///
+/// Hosts that keep their own journal number their checkpoints themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(pub usize);
+
+/// Original value of something changed during the transaction
+#[derive(Debug, Clone)]
+enum JournalEntry {
+    AccountCreated { address: Address },
+    AccountChanged { address: Address, previous: Account },
+    AccountRemoved { address: Address, previous: Account },
+    StorageChanged { address: Address, key: H256, previous: H256 },
+    TransientStorageChanged { address: Address, key: H256, previous: H256 },
+}
+
+/// Journal over a state backend for the lifetime of one transaction
+///
+/// Changes go straight to the backend and the value they replaced is
+/// recorded, so reverting to a checkpoint restores exactly the state at that
+/// point: storage, balances, nonces and code, accounts created or removed
+/// since, and transient storage. Transient storage lives here and is dropped
+/// with the journal at the end of the transaction.
+pub struct JournaledState<'a, S: StateDB> {
+    inner: &'a mut S,
+    entries: Vec<JournalEntry>,
+    /// Journal length at each open checkpoint
+    checkpoints: Vec<usize>,
+    transient: HashMap<(Address, H256), H256>,
+    created: HashSet<Address>,
+}
+
+impl<'a, S: StateDB> JournaledState<'a, S> {
+    pub fn new(inner: &'a mut S) -> Self {
+        Self {
+            inner,
+            entries: Vec::new(),
+            checkpoints: Vec::new(),
+            transient: HashMap::new(),
+            created: HashSet::new(),
+        }
+    }
+
+    fn record_creation(&mut self, address: &Address) {
+        if !self.inner.exists(address) {
+            self.entries.push(JournalEntry::AccountCreated { address: *address });
+            self.created.insert(*address);
+        }
+    }
+
+    fn undo(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::AccountCreated { address } => {
+                self.inner.remove_account(&address);
+                self.created.remove(&address);
+            }
+            JournalEntry::AccountChanged { address, previous }
+            | JournalEntry::AccountRemoved { address, previous } => {
+                self.inner.set_account(address, previous);
+            }
+            JournalEntry::StorageChanged { address, key, previous } => {
+                self.inner.set_storage(address, key, previous);
+            }
+            JournalEntry::TransientStorageChanged { address, key, previous } => {
+                self.transient.insert((address, key), previous);
+            }
+        }
+    }
+}
+
+impl<'a, S: StateDB> StateDB for JournaledState<'a, S> {
+    fn get_account(&self, address: &Address) -> Option<Account> {
+        self.inner.get_account(address)
+    }
+
+    fn set_account(&mut self, address: Address, account: Account) {
+        match self.inner.get_account(&address) {
+            Some(previous) => self.entries.push(JournalEntry::AccountChanged { address, previous }),
+            None => self.record_creation(&address),
+        }
+        self.inner.set_account(address, account);
+    }
+
+    fn get_storage(&self, address: &Address, key: &H256) -> H256 {
+        self.inner.get_storage(address, key)
+    }
+
+    fn set_storage(&mut self, address: Address, key: H256, value: H256) {
+        self.record_creation(&address);
+        let previous = self.inner.get_storage(&address, &key);
+        self.entries.push(JournalEntry::StorageChanged { address, key, previous });
+        self.inner.set_storage(address, key, value);
+    }
+
+    fn exists(&self, address: &Address) -> bool {
+        self.inner.exists(address)
+    }
+
+    fn is_empty(&self, address: &Address) -> bool {
+        self.inner.is_empty(address)
+    }
+
+    fn remove_account(&mut self, address: &Address) {
+        if let Some(previous) = self.inner.get_account(address) {
+            self.entries.push(JournalEntry::AccountRemoved { address: *address, previous });
+            self.inner.remove_account(address);
+        }
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints.push(self.entries.len());
+        Checkpoint(self.checkpoints.len() - 1)
+    }
+
+    fn revert_to(&mut self, checkpoint: Checkpoint) {
+        let Some(&len) = self.checkpoints.get(checkpoint.0) else {
+            return;
+        };
+        self.checkpoints.truncate(checkpoint.0);
+
+        while self.entries.len() > len {
+            if let Some(entry) = self.entries.pop() {
+                self.undo(entry);
+            }
+        }
+    }
+
+    fn commit(&mut self, checkpoint: Checkpoint) {
+        self.checkpoints.truncate(checkpoint.0);
+        // Nothing can be reverted once the outermost section is kept
+        if self.checkpoints.is_empty() {
+            self.entries.clear();
+        }
+    }
+
+    fn get_transient_storage(&self, address: &Address, key: &H256) -> H256 {
+        self.transient.get(&(*address, *key)).copied().unwrap_or_default()
+    }
+
+    fn set_transient_storage(&mut self, address: Address, key: H256, value: H256) {
+        let previous = self.transient.insert((address, key), value).unwrap_or_default();
+        self.entries.push(JournalEntry::TransientStorageChanged { address, key, previous });
+    }
+
+    fn is_created(&self, address: &Address) -> bool {
+        self.created.contains(address)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AccountChange {
     pub address: Address,
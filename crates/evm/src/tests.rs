@@ -1,10 +1,18 @@
 #[cfg(test)]
 mod tests {
     use crate::{
-        execution::{BlockContext, ExecutionContext},
-        Evm,
+        execution::{BlockContext, ExecutionContext, ExecutionResult, ExecutionStatus, HaltReason},
+        host::{Frame, Step},
+        opcodes::Opcode,
+        gas::GasCost,
+        state::StateDB,
+        interpreter::{create2_address, create_address},
+        precompiled::{get_precompiled, p256_vector},
+        Account, ChainConfig, Checkpoint, Evm, Hardfork, Interpreter, JournaledState,
     };
-    use ethereum_types::{Address, U256};
+    use ethereum_core::Header;
+    use ethereum_types::{Address, H256, U256};
+    use std::collections::HashMap;
 
     fn create_test_context() -> ExecutionContext {
         let block = BlockContext {
@@ -12,8 +20,10 @@ mod tests {
             number: U256::from(1),
             timestamp: U256::from(1000),
             difficulty: U256::from(1000000),
+            prev_randao: H256::repeat_byte(0x5a),
             gas_limit: U256::from(10000000),
             base_fee: Some(U256::from(1000)),
+            blob_base_fee: None,
             chain_id: U256::from(1),
             block_hashes: vec![],
         };
@@ -150,4 +160,1197 @@ mod tests {
         assert_eq!(result.return_data.len(), 32);
         assert_eq!(U256::from(&result.return_data[..]), U256::from(3));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_blobbasefee() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+
+        // excess_blob_gas = 10 * BLOB_BASE_FEE_UPDATE_FRACTION gives roughly e^10
+        let mut header = Header::new();
+        header.excess_blob_gas = Some(10 * 3338477);
+        header.timestamp = 1_710_338_135;
+        context.block = BlockContext::from_header(&header, &ChainConfig::mainnet(), vec![]);
+        assert_eq!(context.block.blob_base_fee, Some(U256::from(22026)));
+
+        // Prague's larger update fraction (EIP-7691) prices the same excess lower
+        header.timestamp = 1_746_612_311;
+        let prague = BlockContext::from_header(&header, &ChainConfig::mainnet(), vec![]);
+        assert_eq!(prague.blob_base_fee, Some(U256::from(785)));
+
+        // BLOBBASEFEE, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, RETURN
+        context.code = vec![
+            0x4a,        // BLOBBASEFEE
+            0x60, 0x00,  // PUSH1 0x00
+            0x52,        // MSTORE
+            0x60, 0x20,  // PUSH1 0x20
+            0x60, 0x00,  // PUSH1 0x00
+            0xf3,        // RETURN
+        ];
+
+        let result = evm.execute(context).unwrap();
+        assert_eq!(U256::from(&result.return_data[..]), U256::from(22026));
+    }
+
+    #[test]
+    fn test_blobbasefee_without_blob_gas() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+
+        context.block = BlockContext::from_header(&Header::new(), &ChainConfig::mainnet(), vec![]);
+        assert_eq!(context.block.blob_base_fee, None);
+
+        context.code = vec![0x4a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+
+        let result = evm.execute(context).unwrap();
+        assert_eq!(U256::from(&result.return_data[..]), U256::zero());
+    }
+
+    #[test]
+    fn test_blockhash_reads_recent_hashes_parent_first() {
+        let mut context = create_test_context();
+        context.block.number = U256::from(1000);
+        context.block.block_hashes = (1..=256u64).map(H256::from_low_u64_be).collect();
+
+        let blockhash = |context: &ExecutionContext, number: u16| {
+            let mut context = context.clone();
+            // PUSH2 number, BLOCKHASH, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, RETURN
+            context.code = vec![0x61, (number >> 8) as u8, number as u8, 0x40, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+            let result = Evm::new().execute(context).unwrap();
+            H256::from_slice(&result.return_data)
+        };
+
+        assert_eq!(blockhash(&context, 999), H256::from_low_u64_be(1));
+        assert_eq!(blockhash(&context, 744), H256::from_low_u64_be(256));
+        assert_eq!(blockhash(&context, 743), H256::zero());
+        assert_eq!(blockhash(&context, 1000), H256::zero());
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        // The test context runs at 0x02, which a call would reach as the SHA-256 precompile
+        context.address = Address::from_bytes([0x0a; 20]);
+
+        // Calls itself, then records the CALL result at storage[++counter]
+        let code = vec![
+            0x60, 0x00,  // PUSH1 0x00 (retSize)
+            0x60, 0x00,  // PUSH1 0x00 (retOffset)
+            0x60, 0x00,  // PUSH1 0x00 (argsSize)
+            0x60, 0x00,  // PUSH1 0x00 (argsOffset)
+            0x60, 0x00,  // PUSH1 0x00 (value)
+            0x30,        // ADDRESS
+            0x5a,        // GAS
+            0xf1,        // CALL
+            0x60, 0x00,  // PUSH1 0x00
+            0x54,        // SLOAD
+            0x60, 0x01,  // PUSH1 0x01
+            0x01,        // ADD
+            0x80,        // DUP1
+            0x60, 0x00,  // PUSH1 0x00
+            0x55,        // SSTORE (counter)
+            0x55,        // SSTORE (storage[counter] = result)
+            0x00,        // STOP
+        ];
+        evm.state.insert(context.address, Account {
+            code: code.clone(),
+            ..Default::default()
+        });
+        context.code = code;
+        // Start close to the limit so the recursion is not cut short by gas
+        context.depth = 1020;
+        let address = context.address;
+
+        let result = evm.execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+
+        let storage = &evm.state[&address].storage;
+        let slot = |n: u64| storage.get(&H256::from_low_u64_be(n)).copied().unwrap_or_default();
+
+        // Frames at depths 1020..=1024 each ran once
+        assert_eq!(slot(0), H256::from_low_u64_be(5));
+        // The frame at depth 1024 could not call any deeper
+        assert_eq!(slot(1), H256::zero());
+        for n in 2..=5 {
+            assert_eq!(slot(n), H256::from_low_u64_be(1));
+        }
+    }
+
+    #[test]
+    fn test_call_forwards_all_but_one_64th() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+
+        // Callee returns the gas it sees: GAS, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, RETURN
+        let callee = Address::from_bytes([0x03; 20]);
+        evm.state.insert(callee, Account {
+            code: vec![0x5a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3],
+            ..Default::default()
+        });
+
+        let mut code = vec![
+            0x60, 0x20,  // PUSH1 0x20 (retSize)
+            0x60, 0x00,  // PUSH1 0x00 (retOffset)
+            0x60, 0x00,  // PUSH1 0x00 (argsSize)
+            0x60, 0x00,  // PUSH1 0x00 (argsOffset)
+            0x60, 0x00,  // PUSH1 0x00 (value)
+            0x73,        // PUSH20 callee
+        ];
+        code.extend_from_slice(callee.as_bytes());
+        code.extend_from_slice(&[
+            0x5a,        // GAS
+            0xf1,        // CALL
+            0x50,        // POP
+            0x60, 0x20,  // PUSH1 0x20
+            0x60, 0x00,  // PUSH1 0x00
+            0xf3,        // RETURN
+        ]);
+        context.code = code;
+
+        let result = evm.execute(context).unwrap();
+        let seen = U256::from(&result.return_data[..]).as_u64();
+
+        // 5 x PUSH1, PUSH20, GAS, the CALL base cost and a word of memory
+        // for the output are spent before forwarding
+        let available = 1_000_000 - 15 - 3 - 2 - 2600 - 3;
+        let forwarded = available - available / 64;
+        assert!(forwarded < available);
+        // The callee's own GAS opcode costs 2
+        assert_eq!(seen, forwarded - 2);
+    }
+
+    /// Calls `callee` with `value` wei and no gas of its own, then returns the first memory word
+    fn call_without_gas(callee: Address, value: u8) -> Vec<u8> {
+        let mut code = vec![
+            0x60, 0x20,  // PUSH1 0x20 (retSize)
+            0x60, 0x00,  // PUSH1 0x00 (retOffset)
+            0x60, 0x00,  // PUSH1 0x00 (argsSize)
+            0x60, 0x00,  // PUSH1 0x00 (argsOffset)
+            0x60, value, // PUSH1 value
+            0x73,        // PUSH20 callee
+        ];
+        code.extend_from_slice(callee.as_bytes());
+        code.extend_from_slice(&[
+            0x60, 0x00,  // PUSH1 0x00 (gas)
+            0xf1,        // CALL
+            0x50,        // POP
+            0x60, 0x20,  // PUSH1 0x20
+            0x60, 0x00,  // PUSH1 0x00
+            0xf3,        // RETURN
+        ]);
+        code
+    }
+
+    #[test]
+    fn test_value_call_grants_stipend() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        evm.state.insert(context.address, Account {
+            balance: U256::from(100),
+            ..Default::default()
+        });
+
+        // Callee returns the gas it sees
+        let callee = Address::from_bytes([0x03; 20]);
+        evm.state.insert(callee, Account {
+            code: vec![0x5a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3],
+            ..Default::default()
+        });
+
+        context.code = call_without_gas(callee, 5);
+        let result = evm.execute(context.clone()).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(U256::from(&result.return_data[..]).as_u64(), 2300 - 2);
+
+        // Without value the callee gets nothing and runs out of gas
+        context.code = call_without_gas(callee, 0);
+        let result = evm.execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(U256::from(&result.return_data[..]), U256::zero());
+    }
+
+    #[test]
+    fn test_value_call_to_empty_account_costs_more() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        let caller = context.address;
+        evm.state.insert(caller, Account {
+            balance: U256::from(100),
+            ..Default::default()
+        });
+        let empty = Address::from_bytes([0x04; 20]);
+
+        // 8 x PUSH1, PUSH20, POP, the cold account access and a word of
+        // memory for the output
+        let base = 8 * 3 + 3 + 2 + 2600 + 3;
+
+        // The unused stipend goes back to the caller
+        context.code = call_without_gas(empty, 5);
+        let result = evm.execute(context.clone()).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.gas_used, base + 9000 + 25000 - 2300);
+        assert_eq!(evm.state[&empty].balance, U256::from(5));
+
+        // A call without value leaves the empty account alone
+        let mut evm = Evm::new();
+        evm.state.insert(caller, Account {
+            balance: U256::from(100),
+            ..Default::default()
+        });
+        context.code = call_without_gas(empty, 0);
+        let result = evm.execute(context.clone()).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.gas_used, base);
+
+        // Before EIP-161 any call to a missing account paid for creating it
+        let mut evm = Evm::new();
+        evm.state.insert(caller, Account {
+            balance: U256::from(100),
+            ..Default::default()
+        });
+        context.spec = Hardfork::TangerineWhistle;
+        let result = evm.execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.gas_used, base - 2600 + 700 + 25000);
+    }
+
+    /// Stores 2 in slot 0, calls `callee` with 5 wei, then runs `tail`
+    fn call_with_value(callee: Address, tail: &[u8]) -> Vec<u8> {
+        let mut code = vec![
+            0x60, 0x02,  // PUSH1 0x02
+            0x60, 0x00,  // PUSH1 0x00
+            0x55,        // SSTORE
+            0x60, 0x00,  // PUSH1 0x00 (retSize)
+            0x60, 0x00,  // PUSH1 0x00 (retOffset)
+            0x60, 0x00,  // PUSH1 0x00 (argsSize)
+            0x60, 0x00,  // PUSH1 0x00 (argsOffset)
+            0x60, 0x05,  // PUSH1 0x05 (value)
+            0x73,        // PUSH20 callee
+        ];
+        code.extend_from_slice(callee.as_bytes());
+        code.extend_from_slice(&[
+            0x5a,        // GAS
+            0xf1,        // CALL
+        ]);
+        code.extend_from_slice(tail);
+        code
+    }
+
+    #[test]
+    fn test_outer_call_persists_after_inner_revert() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        let caller = context.address;
+
+        // Callee writes slot 0 and then reverts
+        let callee = Address::from_bytes([0x03; 20]);
+        evm.state.insert(callee, Account {
+            code: vec![
+                0x60, 0x01,  // PUSH1 0x01
+                0x60, 0x00,  // PUSH1 0x00
+                0x55,        // SSTORE
+                0x60, 0x00,  // PUSH1 0x00
+                0x60, 0x00,  // PUSH1 0x00
+                0xfd,        // REVERT
+            ],
+            ..Default::default()
+        });
+        evm.state.insert(caller, Account {
+            balance: U256::from(100),
+            ..Default::default()
+        });
+
+        // Record the CALL result in slot 1
+        context.code = call_with_value(callee, &[
+            0x60, 0x01,  // PUSH1 0x01
+            0x55,        // SSTORE
+            0x00,        // STOP
+        ]);
+
+        let result = evm.execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+
+        // The caller's own writes are kept
+        assert_eq!(evm.state.get_storage(&caller, &H256::zero()), H256::from_low_u64_be(2));
+        assert_eq!(evm.state.get_storage(&caller, &H256::from_low_u64_be(1)), H256::zero());
+        // The callee's write and the value sent to it are rolled back
+        assert_eq!(evm.state.get_storage(&callee, &H256::zero()), H256::zero());
+        assert_eq!(evm.state[&callee].balance, U256::zero());
+        assert_eq!(evm.state[&caller].balance, U256::from(100));
+    }
+
+    #[test]
+    fn test_outer_revert_undoes_everything() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        let caller = context.address;
+
+        // Callee writes slot 0 and succeeds
+        let callee = Address::from_bytes([0x03; 20]);
+        evm.state.insert(callee, Account {
+            code: vec![
+                0x60, 0x01,  // PUSH1 0x01
+                0x60, 0x00,  // PUSH1 0x00
+                0x55,        // SSTORE
+                0x00,        // STOP
+            ],
+            ..Default::default()
+        });
+        evm.state.insert(caller, Account {
+            balance: U256::from(100),
+            ..Default::default()
+        });
+
+        context.code = call_with_value(callee, &[
+            0x50,        // POP
+            0x60, 0x00,  // PUSH1 0x00
+            0x60, 0x00,  // PUSH1 0x00
+            0xfd,        // REVERT
+        ]);
+
+        let result = evm.execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Revert);
+
+        assert_eq!(evm.state.get_storage(&caller, &H256::zero()), H256::zero());
+        assert_eq!(evm.state.get_storage(&callee, &H256::zero()), H256::zero());
+        assert_eq!(evm.state[&callee].balance, U256::zero());
+        assert_eq!(evm.state[&caller].balance, U256::from(100));
+    }
+
+    #[test]
+    fn test_revert_restores_created_accounts_and_transient_storage() {
+        let mut accounts: HashMap<Address, Account> = HashMap::new();
+        let owner = Address::from_bytes([0x01; 20]);
+        let created = Address::from_bytes([0x02; 20]);
+        let key = H256::from_low_u64_be(1);
+
+        let mut state = JournaledState::new(&mut accounts);
+        let outer = state.checkpoint();
+        state.set_transient_storage(owner, key, H256::from_low_u64_be(7));
+
+        let inner = state.checkpoint();
+        state.set_account(created, Account {
+            balance: U256::from(1),
+            ..Default::default()
+        });
+        state.set_storage(created, key, H256::from_low_u64_be(3));
+        state.set_transient_storage(owner, key, H256::from_low_u64_be(8));
+        assert!(state.is_created(&created));
+
+        state.revert_to(inner);
+        assert!(!state.exists(&created));
+        assert!(!state.is_created(&created));
+        assert_eq!(state.get_transient_storage(&owner, &key), H256::from_low_u64_be(7));
+
+        state.commit(outer);
+        drop(state);
+        assert!(accounts.is_empty());
+    }
+
+    #[test]
+    fn test_push0_depends_on_hardfork() {
+        // PUSH0, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, RETURN
+        let code = vec![0x5f, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+
+        let mut context = create_test_context();
+        context.code = code.clone();
+        context.spec = Hardfork::Shanghai;
+        let result = Evm::new().execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(U256::from(&result.return_data[..]), U256::zero());
+
+        let mut context = create_test_context();
+        context.code = code;
+        context.block.number = U256::from(10_000_000);
+        let context = context.with_chain_config(&ChainConfig::mainnet());
+        assert_eq!(context.spec, Hardfork::Istanbul);
+        let result = Evm::new().execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::InvalidOpcode(0x5f)));
+    }
+
+    #[test]
+    fn test_difficulty_becomes_prevrandao_at_merge() {
+        // DIFFICULTY, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, RETURN
+        let code = vec![0x44, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+        let run = |spec| {
+            let mut context = create_test_context();
+            context.code = code.clone();
+            context.spec = spec;
+            let result = Evm::new().execute(context).unwrap();
+            assert_eq!(result.status, ExecutionStatus::Success);
+            result.return_data
+        };
+
+        assert_eq!(U256::from(&run(Hardfork::London)[..]), U256::from(1000000));
+        assert_eq!(run(Hardfork::Merge), H256::repeat_byte(0x5a).as_bytes().to_vec());
+        assert_eq!(run(Hardfork::Cancun), H256::repeat_byte(0x5a).as_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_sload_cost_depends_on_hardfork() {
+        // PUSH1 0x00, SLOAD, PUSH1 0x00, SLOAD
+        let code = vec![0x60, 0x00, 0x54, 0x60, 0x00, 0x54];
+        let gas_used = |spec| {
+            let mut context = create_test_context();
+            context.code = code.clone();
+            context.spec = spec;
+            Evm::new().execute(context).unwrap().gas_used
+        };
+
+        assert_eq!(gas_used(Hardfork::Frontier), 2 * (3 + 50));
+        assert_eq!(gas_used(Hardfork::TangerineWhistle), 2 * (3 + 200));
+        assert_eq!(gas_used(Hardfork::Istanbul), 2 * (3 + 800));
+        assert_eq!(gas_used(Hardfork::Berlin), (3 + 2100) + (3 + 100));
+    }
+
+    #[test]
+    fn test_account_access_cost_depends_on_hardfork() {
+        let other = Address::from_bytes([0x77; 20]);
+        // (PUSH20 other, BALANCE, POP) twice
+        let mut code = Vec::new();
+        for _ in 0..2 {
+            code.push(0x73);
+            code.extend_from_slice(other.as_bytes());
+            code.extend_from_slice(&[0x31, 0x50]);
+        }
+        let gas_used = |spec| {
+            let mut context = create_test_context();
+            context.code = code.clone();
+            context.spec = spec;
+            Evm::new().execute(context).unwrap().gas_used
+        };
+
+        assert_eq!(gas_used(Hardfork::Frontier), 2 * (3 + 20 + 2));
+        assert_eq!(gas_used(Hardfork::TangerineWhistle), 2 * (3 + 400 + 2));
+        assert_eq!(gas_used(Hardfork::Istanbul), 2 * (3 + 700 + 2));
+        // Cold the first time, warm after that
+        assert_eq!(gas_used(Hardfork::Berlin), (3 + 2600 + 2) + (3 + 100 + 2));
+
+        // Accounts on the access list start warm
+        let mut context = create_test_context();
+        context.code = code.clone();
+        context.warm_addresses.insert(other);
+        assert_eq!(Evm::new().execute(context).unwrap().gas_used, 2 * (3 + 100 + 2));
+    }
+
+    #[test]
+    fn test_result_reports_accessed_accounts_and_slots() {
+        let mut context = create_test_context();
+        let other = Address::from_bytes([0x77; 20]);
+        // PUSH1 0x01, SLOAD, PUSH1 0x02, SLOAD, PUSH1 0x01, SLOAD, PUSH20 other, BALANCE, STOP
+        context.code = vec![0x60, 0x01, 0x54, 0x60, 0x02, 0x54, 0x60, 0x01, 0x54, 0x73];
+        context.code.extend_from_slice(other.as_bytes());
+        context.code.extend_from_slice(&[0x31, 0x00]);
+        let contract = context.address;
+
+        let result = Evm::new().execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        // Slot 1 is warm the second time it is read
+        assert_eq!(result.gas_used, (3 + 2100) + (3 + 2100) + (3 + 100) + (3 + 2600));
+        assert_eq!(result.accessed_addresses, [other].into_iter().collect());
+        assert_eq!(
+            result.accessed_storage_keys,
+            [
+                (contract, H256::from_low_u64_be(1)),
+                (contract, H256::from_low_u64_be(2)),
+            ].into_iter().collect(),
+        );
+    }
+
+    /// Calls `callee` without forwarding its output to memory, then runs `tail`
+    fn call_discarding_output(callee: Address, tail: &[u8]) -> Vec<u8> {
+        let mut code = vec![
+            0x60, 0x00,  // PUSH1 0x00 (retSize)
+            0x60, 0x00,  // PUSH1 0x00 (retOffset)
+            0x60, 0x00,  // PUSH1 0x00 (argsSize)
+            0x60, 0x00,  // PUSH1 0x00 (argsOffset)
+            0x60, 0x00,  // PUSH1 0x00 (value)
+            0x73,        // PUSH20 callee
+        ];
+        code.extend_from_slice(callee.as_bytes());
+        code.extend_from_slice(&[
+            0x5a,        // GAS
+            0xf1,        // CALL
+            0x50,        // POP
+        ]);
+        code.extend_from_slice(tail);
+        code
+    }
+
+    /// Account at 0x03.. whose code returns the words 1 and 2
+    fn insert_two_word_callee(evm: &mut Evm) -> Address {
+        let callee = Address::from_bytes([0x03; 20]);
+        evm.state.insert(callee, Account {
+            code: vec![
+                0x60, 0x01,  // PUSH1 0x01
+                0x60, 0x00,  // PUSH1 0x00
+                0x52,        // MSTORE
+                0x60, 0x02,  // PUSH1 0x02
+                0x60, 0x20,  // PUSH1 0x20
+                0x52,        // MSTORE
+                0x60, 0x40,  // PUSH1 0x40
+                0x60, 0x00,  // PUSH1 0x00
+                0xf3,        // RETURN
+            ],
+            ..Default::default()
+        });
+        callee
+    }
+
+    #[test]
+    fn test_returndatacopy_reads_last_call_output() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        let callee = insert_two_word_callee(&mut evm);
+
+        // Copy the whole output into memory and append RETURNDATASIZE after it
+        context.code = call_discarding_output(callee, &[
+            0x60, 0x40,  // PUSH1 0x40 (size)
+            0x60, 0x00,  // PUSH1 0x00 (offset)
+            0x60, 0x00,  // PUSH1 0x00 (destOffset)
+            0x3e,        // RETURNDATACOPY
+            0x3d,        // RETURNDATASIZE
+            0x60, 0x40,  // PUSH1 0x40
+            0x52,        // MSTORE
+            0x60, 0x60,  // PUSH1 0x60
+            0x60, 0x00,  // PUSH1 0x00
+            0xf3,        // RETURN
+        ]);
+
+        let result = evm.execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.return_data.len(), 96);
+        assert_eq!(U256::from(&result.return_data[..32]), U256::from(1));
+        assert_eq!(U256::from(&result.return_data[32..64]), U256::from(2));
+        assert_eq!(U256::from(&result.return_data[64..]), U256::from(64));
+    }
+
+    #[test]
+    fn test_returndatacopy_past_output_halts() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        let callee = insert_two_word_callee(&mut evm);
+
+        // Reading 64 bytes from offset 32 runs past the 64 bytes returned
+        context.code = call_discarding_output(callee, &[
+            0x60, 0x40,  // PUSH1 0x40 (size)
+            0x60, 0x20,  // PUSH1 0x20 (offset)
+            0x60, 0x00,  // PUSH1 0x00 (destOffset)
+            0x3e,        // RETURNDATACOPY
+            0x00,        // STOP
+        ]);
+        let result = evm.execute(context.clone()).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::ReturnDataOutOfBounds));
+
+        // Without a call there is nothing to read at all
+        context.code = vec![
+            0x60, 0x01,  // PUSH1 0x01 (size)
+            0x60, 0x00,  // PUSH1 0x00 (offset)
+            0x60, 0x00,  // PUSH1 0x00 (destOffset)
+            0x3e,        // RETURNDATACOPY
+            0x00,        // STOP
+        ];
+        let result = evm.execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::ReturnDataOutOfBounds));
+    }
+
+    #[test]
+    fn test_call_charges_memory_expansion() {
+        let mut evm = Evm::new();
+        let callee = insert_two_word_callee(&mut evm);
+
+        // Calls `callee` with the given output size and input range, then runs `tail`
+        let call_code = |ret_size: u8, args_offset: U256, args_size: U256, tail: &[u8]| {
+            let word = |value: U256| {
+                let mut bytes = [0u8; 32];
+                value.to_big_endian(&mut bytes);
+                bytes
+            };
+            let mut code = vec![
+                0x60, ret_size,  // PUSH1 retSize
+                0x60, 0x00,      // PUSH1 0x00 (retOffset)
+                0x7f,            // PUSH32 argsSize
+            ];
+            code.extend_from_slice(&word(args_size));
+            code.push(0x7f);     // PUSH32 argsOffset
+            code.extend_from_slice(&word(args_offset));
+            code.extend_from_slice(&[0x60, 0x00, 0x73]);  // PUSH1 0x00 (value), PUSH20 callee
+            code.extend_from_slice(callee.as_bytes());
+            code.extend_from_slice(&[0x5a, 0xf1, 0x50]);  // GAS, CALL, POP
+            code.extend_from_slice(tail);
+            code
+        };
+        let run = |evm: &mut Evm, code: Vec<u8>| {
+            let mut context = create_test_context();
+            context.code = code;
+            evm.execute(context).unwrap()
+        };
+
+        // The output range grows memory before the call, an empty input
+        // range doesn't whatever its offset
+        let result = run(&mut evm, call_code(0x40, U256::MAX, U256::zero(), &[
+            0x59,        // MSIZE
+            0x60, 0x00,  // PUSH1 0x00
+            0x52,        // MSTORE
+            0x60, 0x20,  // PUSH1 0x20
+            0x60, 0x00,  // PUSH1 0x00
+            0xf3,        // RETURN
+        ]));
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(U256::from(&result.return_data[..]), U256::from(64));
+
+        // Two words of memory cost 6 gas
+        let with_output = run(&mut evm, call_code(0x40, U256::zero(), U256::zero(), &[0x00]));
+        let without_output = run(&mut evm, call_code(0x00, U256::zero(), U256::zero(), &[0x00]));
+        assert_eq!(with_output.gas_used - without_output.gas_used, 6);
+
+        // An input range no gas limit could pay for runs out of gas
+        let result = run(&mut evm, call_code(0x00, U256::zero(), U256::from(u64::MAX), &[0x00]));
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::OutOfGas));
+        let result = run(&mut evm, call_code(0x00, U256::MAX, U256::one(), &[0x00]));
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::OutOfGas));
+    }
+
+    /// Host where every account holds the same balance and nothing is stored
+    struct FixedBalanceHost(U256);
+
+    impl crate::Host for FixedBalanceHost {
+        fn get_account(&self, _address: &Address) -> Option<Account> {
+            Some(Account { balance: self.0, ..Default::default() })
+        }
+        fn set_account(&mut self, _address: Address, _account: Account) {}
+        fn remove_account(&mut self, _address: &Address) {}
+        fn is_empty(&self, _address: &Address) -> bool {
+            false
+        }
+        fn is_created(&self, _address: &Address) -> bool {
+            false
+        }
+        fn get_storage(&self, _address: &Address, _key: &H256) -> H256 {
+            H256::zero()
+        }
+        fn set_storage(&mut self, _address: Address, _key: H256, _value: H256) {}
+        fn get_transient_storage(&self, _address: &Address, _key: &H256) -> H256 {
+            H256::zero()
+        }
+        fn set_transient_storage(&mut self, _address: Address, _key: H256, _value: H256) {}
+        fn checkpoint(&mut self) -> Checkpoint {
+            Checkpoint(0)
+        }
+        fn revert_to(&mut self, _checkpoint: Checkpoint) {}
+        fn commit(&mut self, _checkpoint: Checkpoint) {}
+    }
+
+    #[test]
+    fn test_balance_reads_from_host() {
+        let mut context = create_test_context();
+        let other = Address::from_bytes([0x77; 20]);
+        // PUSH20 other, BALANCE, PUSH1 0x00, MSTORE, PUSH1 0x20, PUSH1 0x00, RETURN
+        context.code = vec![0x73];
+        context.code.extend_from_slice(other.as_bytes());
+        context.code.extend_from_slice(&[0x31, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3]);
+
+        let mut host = FixedBalanceHost(U256::from(123_456));
+        let result = Interpreter::new(context, &mut host).run().unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(U256::from(&result.return_data[..]), U256::from(123_456));
+    }
+
+    /// Empty host that records every step the interpreter reports
+    #[derive(Default)]
+    struct StepRecorder {
+        steps: Vec<(usize, Opcode, u64, u32, Vec<U256>, usize)>,
+        costs: Vec<u64>,
+        frames: Vec<(Opcode, Address, Vec<u8>, bool)>,
+        exits: Vec<ExecutionStatus>,
+    }
+
+    impl crate::Host for StepRecorder {
+        fn get_account(&self, _address: &Address) -> Option<Account> {
+            None
+        }
+        fn set_account(&mut self, _address: Address, _account: Account) {}
+        fn remove_account(&mut self, _address: &Address) {}
+        fn is_empty(&self, _address: &Address) -> bool {
+            true
+        }
+        fn is_created(&self, _address: &Address) -> bool {
+            false
+        }
+        fn get_storage(&self, _address: &Address, _key: &H256) -> H256 {
+            H256::zero()
+        }
+        fn set_storage(&mut self, _address: Address, _key: H256, _value: H256) {}
+        fn get_transient_storage(&self, _address: &Address, _key: &H256) -> H256 {
+            H256::zero()
+        }
+        fn set_transient_storage(&mut self, _address: Address, _key: H256, _value: H256) {}
+        fn checkpoint(&mut self) -> Checkpoint {
+            Checkpoint(0)
+        }
+        fn revert_to(&mut self, _checkpoint: Checkpoint) {}
+        fn commit(&mut self, _checkpoint: Checkpoint) {}
+
+        fn step(&mut self, step: &Step<'_>) {
+            self.steps.push((
+                step.pc,
+                step.opcode,
+                step.gas,
+                step.depth,
+                step.stack.to_vec(),
+                step.memory.len(),
+            ));
+        }
+
+        fn step_end(&mut self, gas_cost: u64, _error: Option<&crate::EvmError>) {
+            self.costs.push(gas_cost);
+        }
+
+        fn enter_frame(&mut self, frame: &Frame<'_>) {
+            self.frames.push((frame.opcode, frame.to, frame.input.to_vec(), frame.precompile));
+        }
+
+        fn exit_frame(&mut self, result: &ExecutionResult) {
+            self.exits.push(result.status.clone());
+        }
+    }
+
+    #[test]
+    fn test_host_observes_every_step() {
+        let mut context = create_test_context();
+        // PUSH1 0x02, PUSH1 0x03, ADD, PUSH1 0x00, MSTORE, STOP
+        context.code = vec![0x60, 0x02, 0x60, 0x03, 0x01, 0x60, 0x00, 0x52, 0x00];
+        let gas_limit = context.gas_limit;
+
+        let mut host = StepRecorder::default();
+        let result = Interpreter::new(context, &mut host).run().unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+
+        let pcs: Vec<_> = host.steps.iter().map(|s| (s.0, s.1)).collect();
+        assert_eq!(
+            pcs,
+            vec![
+                (0, Opcode::PUSH1),
+                (2, Opcode::PUSH1),
+                (4, Opcode::ADD),
+                (5, Opcode::PUSH1),
+                (7, Opcode::MSTORE),
+                (8, Opcode::STOP),
+            ]
+        );
+        // MSTORE pays 3 for the store and 3 to grow memory to one word
+        assert_eq!(host.costs, vec![3, 3, 3, 3, 6, 0]);
+
+        // State is reported as it was before the instruction ran
+        assert_eq!(host.steps[0].2, gas_limit);
+        assert_eq!(host.steps[2].2, gas_limit - 6);
+        assert_eq!(host.steps[2].4, vec![U256::from(2), U256::from(3)]);
+        assert_eq!(host.steps[4].4, vec![U256::from(5), U256::zero()]);
+        assert_eq!(host.steps[4].5, 0);
+        assert_eq!(host.steps[5].5, 32);
+        assert!(host.steps.iter().all(|s| s.3 == 0));
+    }
+
+    #[test]
+    fn test_host_observes_frames() {
+        let mut context = create_test_context();
+        let callee = Address::from_bytes([0x55; 20]);
+        // PUSH4 0xdeadbeef, PUSH1 0x00, MSTORE, then STATICCALL the identity
+        // precompile and `callee` with the last 4 bytes of the word as input
+        context.code = vec![0x63, 0xde, 0xad, 0xbe, 0xef, 0x60, 0x00, 0x52];
+        let mut identity = [0u8; 20];
+        identity[19] = 0x04;
+        for to in [Address::from_bytes(identity), callee] {
+            // PUSH1 0x00 (retSize), PUSH1 0x00 (retOffset), PUSH1 0x04 (argsSize), PUSH1 0x1c (argsOffset)
+            context.code.extend_from_slice(&[0x60, 0x00, 0x60, 0x00, 0x60, 0x04, 0x60, 0x1c, 0x73]);
+            context.code.extend_from_slice(to.as_bytes());
+            // GAS, STATICCALL, POP
+            context.code.extend_from_slice(&[0x5a, 0xfa, 0x50]);
+        }
+
+        let mut host = StepRecorder::default();
+        let result = Interpreter::new(context, &mut host).run().unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        let input = vec![0xde, 0xad, 0xbe, 0xef];
+        assert_eq!(
+            host.frames,
+            vec![
+                (Opcode::STATICCALL, Address::from_bytes(identity), input.clone(), true),
+                (Opcode::STATICCALL, callee, input, false),
+            ]
+        );
+        assert_eq!(host.exits, vec![ExecutionStatus::Success; 2]);
+    }
+
+    #[test]
+    fn test_call_transfers_value_to_precompile() {
+        let mut identity = [0u8; 20];
+        identity[19] = 0x04;
+        let identity = Address::from_bytes(identity);
+        let mut ripemd = [0u8; 20];
+        ripemd[19] = 0x03;
+        let ripemd = Address::from_bytes(ripemd);
+
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        evm.state.insert(context.address, Account {
+            balance: U256::from(100),
+            ..Default::default()
+        });
+        context.code = call_with_value(identity, &[]);
+        let result = evm.execute(context.clone()).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(evm.state[&identity].balance, U256::from(5));
+        assert_eq!(evm.state[&context.address].balance, U256::from(95));
+
+        // A precompile that fails takes the value transfer down with it
+        context.code = vec![
+            0x60, 0x00,        // PUSH1 0x00 (retSize)
+            0x60, 0x00,        // PUSH1 0x00 (retOffset)
+            0x61, 0x04, 0x00,  // PUSH2 0x0400 (argsSize)
+            0x60, 0x00,        // PUSH1 0x00 (argsOffset)
+            0x60, 0x05,        // PUSH1 0x05 (value)
+            0x60, 0x03,        // PUSH1 0x03 (RIPEMD160)
+            0x60, 0x00,        // PUSH1 0x00 (gas, only the stipend)
+            0xf1,              // CALL
+        ];
+        let result = evm.execute(context.clone()).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert!(evm.state.get(&ripemd).is_none_or(|account| account.balance.is_zero()));
+        assert_eq!(evm.state[&context.address].balance, U256::from(95));
+    }
+
+    #[test]
+    fn test_static_context_rejects_state_changes() {
+        let callee = Address::from_bytes([0x03; 20]);
+        let mut call = call_with_value(callee, &[0x00]);
+        // Skip the SSTORE so only the CALL can violate the static context
+        call.drain(..5);
+
+        let cases = vec![
+            // PUSH1 0x00, PUSH1 0x00, PUSH1 0x00, CREATE
+            vec![0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0xf0],
+            // PUSH1 0x00, PUSH1 0x00, PUSH1 0x00, PUSH1 0x00, CREATE2
+            vec![0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0xf5],
+            // PUSH1 0x00, SELFDESTRUCT
+            vec![0x60, 0x00, 0xff],
+            call,
+        ];
+
+        for code in cases {
+            let mut evm = Evm::new();
+            let mut context = create_test_context();
+            evm.state.insert(context.address, Account {
+                balance: U256::from(100),
+                ..Default::default()
+            });
+            context.code = code.clone();
+
+            let result = evm.execute(context.with_static()).unwrap();
+            assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::StateModificationInStatic));
+            assert_eq!(evm.state[&context.address].balance, U256::from(100));
+
+            // The same code is fine outside a static context
+            let result = evm.execute(context).unwrap();
+            assert_eq!(result.status, ExecutionStatus::Success);
+        }
+    }
+
+    #[test]
+    fn test_staticcall_makes_nested_calls_static() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        let caller = context.address;
+
+        // Middle frame forwards 5 wei to the callee with a plain CALL
+        let middle = Address::from_bytes([0x03; 20]);
+        let callee = Address::from_bytes([0x04; 20]);
+        let mut middle_code = call_with_value(callee, &[]);
+        middle_code.drain(..5);
+        middle_code.extend_from_slice(&[
+            0x60, 0x00,  // PUSH1 0x00
+            0x52,        // MSTORE
+            0x60, 0x20,  // PUSH1 0x20
+            0x60, 0x00,  // PUSH1 0x00
+            0xf3,        // RETURN
+        ]);
+        evm.state.insert(middle, Account {
+            balance: U256::from(100),
+            code: middle_code,
+            ..Default::default()
+        });
+
+        context.code = vec![
+            0x60, 0x00,  // PUSH1 0x00 (retSize)
+            0x60, 0x00,  // PUSH1 0x00 (retOffset)
+            0x60, 0x00,  // PUSH1 0x00 (argsSize)
+            0x60, 0x00,  // PUSH1 0x00 (argsOffset)
+            0x73,        // PUSH20 middle
+        ];
+        context.code.extend_from_slice(middle.as_bytes());
+        context.code.extend_from_slice(&[
+            0x5a,        // GAS
+            0xfa,        // STATICCALL
+            0x60, 0x00,  // PUSH1 0x00
+            0x52,        // MSTORE
+            0x60, 0x20,  // PUSH1 0x20
+            0x60, 0x00,  // PUSH1 0x00
+            0xf3,        // RETURN
+        ]);
+
+        let result = evm.execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        // The middle frame halts on its value-bearing CALL, failing the STATICCALL
+        assert_eq!(U256::from(&result.return_data[..]), U256::zero());
+        assert_eq!(evm.state[&middle].balance, U256::from(100));
+        assert!(evm.state.get(&callee).is_none());
+        assert!(evm.state.get(&caller).is_none());
+    }
+
+    #[test]
+    fn test_step_limit_halts_tight_loop() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        context.code = vec![
+            0x5b,        // JUMPDEST
+            0x60, 0x00,  // PUSH1 0x00
+            0x56,        // JUMP
+        ];
+
+        let result = evm.execute(context.clone().with_max_steps(100)).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::StepLimit));
+        // 100 steps cover 33 full iterations plus one JUMPDEST
+        assert_eq!(result.gas_used, 33 * (1 + 3 + 8) + 1);
+
+        // Without a step limit the loop only ends when gas runs out
+        let result = evm.execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::OutOfGas));
+    }
+
+    #[test]
+    fn test_step_limit_covers_nested_calls() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+
+        let callee = Address::from_bytes([0x03; 20]);
+        evm.state.insert(callee, Account {
+            code: vec![0x5b, 0x60, 0x00, 0x56],
+            ..Default::default()
+        });
+
+        context.code = vec![
+            0x60, 0x00,  // PUSH1 0x00 (retSize)
+            0x60, 0x00,  // PUSH1 0x00 (retOffset)
+            0x60, 0x00,  // PUSH1 0x00 (argsSize)
+            0x60, 0x00,  // PUSH1 0x00 (argsOffset)
+            0x60, 0x00,  // PUSH1 0x00 (value)
+            0x73,        // PUSH20 callee
+        ];
+        context.code.extend_from_slice(callee.as_bytes());
+        context.code.extend_from_slice(&[
+            0x5a,        // GAS
+            0xf1,        // CALL
+            0x00,        // STOP
+        ]);
+
+        // The callee uses up the budget shared with its caller, which halts after the CALL
+        let result = evm.execute(context.with_max_steps(1_000)).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::StepLimit));
+    }
+
+    /// Runs CREATE2 with salt 0 over the single-byte init code 0x00 (STOP),
+    /// then returns the word CREATE2 pushed
+    fn create2_stop_code() -> Vec<u8> {
+        vec![
+            0x60, 0x00,  // PUSH1 0x00 (salt)
+            0x60, 0x01,  // PUSH1 0x01 (size)
+            0x60, 0x00,  // PUSH1 0x00 (offset)
+            0x60, 0x00,  // PUSH1 0x00 (value)
+            0xf5,        // CREATE2
+            0x60, 0x00,  // PUSH1 0x00
+            0x52,        // MSTORE
+            0x60, 0x20,  // PUSH1 0x20
+            0x60, 0x00,  // PUSH1 0x00
+            0xf3,        // RETURN
+        ]
+    }
+
+    #[test]
+    fn test_create2_address_matches_eip1014() {
+        // Examples from EIP-1014
+        let expected = Address::from_bytes([
+            0x4d, 0x1a, 0x2e, 0x2b, 0xb4, 0xf8, 0x8f, 0x02, 0x50, 0xf2,
+            0x6f, 0xff, 0xf0, 0x98, 0xb0, 0xb3, 0x0b, 0x26, 0xbf, 0x38,
+        ]);
+        assert_eq!(create2_address(&Address::from_bytes([0u8; 20]), U256::zero(), &[0x00]), expected);
+
+        let mut sender = [0u8; 20];
+        sender[..4].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let expected = Address::from_bytes([
+            0xb9, 0x28, 0xf6, 0x9b, 0xb1, 0xd9, 0x1c, 0xd6, 0x52, 0x74,
+            0xe3, 0xc7, 0x9d, 0x89, 0x86, 0x36, 0x29, 0x84, 0xfd, 0xa3,
+        ]);
+        assert_eq!(create2_address(&Address::from_bytes(sender), U256::zero(), &[0x00]), expected);
+
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        context.code = create2_stop_code();
+
+        let result = evm.execute(context.clone()).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        let created = create2_address(&context.address, U256::zero(), &[0x00]);
+        assert_eq!(U256::from(&result.return_data[..]), U256::from(created.as_bytes()));
+        assert_eq!(evm.state[&created].nonce, 1);
+    }
+
+    #[test]
+    fn test_create_bumps_creator_nonce() {
+        let mut evm = Evm::new();
+        let mut context = create_test_context();
+        evm.state.insert(context.address, Account {
+            nonce: 5,
+            ..Default::default()
+        });
+        context.code = vec![
+            0x60, 0x00,  // PUSH1 0x00 (size)
+            0x60, 0x00,  // PUSH1 0x00 (offset)
+            0x60, 0x00,  // PUSH1 0x00 (value)
+            0xf0,        // CREATE
+            0x60, 0x00,  // PUSH1 0x00
+            0x52,        // MSTORE
+            0x60, 0x20,  // PUSH1 0x20
+            0x60, 0x00,  // PUSH1 0x00
+            0xf3,        // RETURN
+        ];
+
+        let result = evm.execute(context.clone()).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        let created = create_address(&context.address, 5);
+        assert_eq!(U256::from(&result.return_data[..]), U256::from(created.as_bytes()));
+        assert_eq!(evm.state[&context.address].nonce, 6);
+
+        // The next CREATE derives its address from the bumped nonce
+        let result = evm.execute(context.clone()).unwrap();
+        let created = create_address(&context.address, 6);
+        assert_eq!(U256::from(&result.return_data[..]), U256::from(created.as_bytes()));
+        assert_eq!(evm.state[&context.address].nonce, 7);
+    }
+
+    #[test]
+    fn test_create_collision_fails_and_consumes_gas() {
+        let mut context = create_test_context();
+        context.code = create2_stop_code();
+        let target = create2_address(&context.address, U256::zero(), &[0x00]);
+
+        let mut evm = Evm::new();
+        let deployed = evm.execute(context.clone()).unwrap();
+        assert!(deployed.gas_used < 100_000);
+
+        // Existing code at the target address is a collision
+        let mut evm = Evm::new();
+        evm.state.insert(target, Account {
+            code: vec![0x00],
+            ..Default::default()
+        });
+        let result = evm.execute(context.clone()).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert!(U256::from(&result.return_data[..]).is_zero());
+        assert!(result.gas_used > 900_000);
+        assert_eq!(evm.state[&target].code, vec![0x00]);
+        assert_eq!(evm.state[&context.address].nonce, 1);
+
+        // So is a nonzero nonce without code
+        let mut evm = Evm::new();
+        evm.state.insert(target, Account {
+            nonce: 1,
+            ..Default::default()
+        });
+        let result = evm.execute(context).unwrap();
+        assert!(U256::from(&result.return_data[..]).is_zero());
+        assert!(result.gas_used > 900_000);
+    }
+
+    #[test]
+    fn test_create_charges_init_code_memory_and_hashing() {
+        // CREATE or CREATE2 (salt 0) over a word of zeroed init code at 0
+        let create = |salted: bool| {
+            let mut code = Vec::new();
+            if salted {
+                code.extend_from_slice(&[0x60, 0x00]); // PUSH1 0x00 (salt)
+            }
+            code.extend_from_slice(&[
+                0x60, 0x20,  // PUSH1 0x20 (size)
+                0x60, 0x00,  // PUSH1 0x00 (offset)
+                0x60, 0x00,  // PUSH1 0x00 (value)
+                if salted { 0xf5 } else { 0xf0 },
+                0x59,        // MSIZE
+                0x60, 0x00,  // PUSH1 0x00
+                0x52,        // MSTORE
+                0x60, 0x20,  // PUSH1 0x20
+                0x60, 0x00,  // PUSH1 0x00
+                0xf3,        // RETURN
+            ]);
+            let mut context = create_test_context();
+            context.code = code;
+            Evm::new().execute(context).unwrap()
+        };
+
+        let plain = create(false);
+        let salted = create(true);
+        assert_eq!(plain.status, ExecutionStatus::Success);
+        assert_eq!(salted.status, ExecutionStatus::Success);
+        // Reading the init code grew memory to a word
+        assert_eq!(U256::from(&plain.return_data[..]), U256::from(32));
+        // CREATE2 pays for its salt push and for hashing one word
+        assert_eq!(salted.gas_used - plain.gas_used, GasCost::VERYLOW + GasCost::KECCAK256WORD);
+
+        // Init code out of any addressable range runs out of gas instead
+        let mut context = create_test_context();
+        context.code = vec![
+            0x60, 0x01,  // PUSH1 0x01 (size)
+            0x64, 0xff, 0xff, 0xff, 0xff, 0xff,  // PUSH5 (offset)
+            0x60, 0x00,  // PUSH1 0x00 (value)
+            0xf0,        // CREATE
+        ];
+        let result = Evm::new().execute(context).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::OutOfGas));
+    }
+
+    #[test]
+    fn test_p256_verify_behind_chain_config() {
+        let input = p256_vector();
+
+        let mut context = create_test_context();
+        context.data = input;
+        context.code = vec![
+            0x36,              // CALLDATASIZE
+            0x60, 0x00,        // PUSH1 0x00
+            0x60, 0x00,        // PUSH1 0x00
+            0x37,              // CALLDATACOPY
+            0x60, 0x20,        // PUSH1 0x20 (retSize)
+            0x60, 0x00,        // PUSH1 0x00 (retOffset)
+            0x60, 0xa0,        // PUSH1 0xa0 (argsSize)
+            0x60, 0x00,        // PUSH1 0x00 (argsOffset)
+            0x61, 0x01, 0x00,  // PUSH2 0x0100
+            0x5a,              // GAS
+            0xfa,              // STATICCALL
+            0x50,              // POP
+            0x60, 0x20,        // PUSH1 0x20
+            0x60, 0x00,        // PUSH1 0x00
+            0xf3,              // RETURN
+        ];
+
+        // Without RIP-7212, 0x100 is an empty account and the hash stays in memory
+        let before = context.clone().with_chain_config(&ChainConfig::default());
+        assert!(get_precompiled(0x100, &before).is_none());
+        let result = Evm::new().execute(before).unwrap();
+        assert_eq!(result.return_data, context.data[..32].to_vec());
+
+        let config = ChainConfig { rip7212_time: Some(0), ..ChainConfig::default() };
+        let after = context.with_chain_config(&config);
+        assert!(get_precompiled(0x100, &after).is_some());
+        let result = Evm::new().execute(after).unwrap();
+        assert_eq!(U256::from(&result.return_data[..]), U256::one());
+    }
+}
@@ -81,7 +81,7 @@ impl<D: Database> BlockFilter<D> {
     
     /// Get block hash by number
     async fn get_block_hash(&self, block_number: U256) -> Result<H256> {
-        let key = format!("block:number:{}", block_number);
+        let key = format!("number:{}", block_number);
         
         match self.db.get(key.as_bytes())? {
             Some(data) => {
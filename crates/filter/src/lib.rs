@@ -3,6 +3,8 @@ use ethereum_core::{Block, Transaction, Receipt, Log};
 use ethereum_storage::Database;
 use std::sync::Arc;
 use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::time::Instant;
 use tokio::sync::{RwLock, mpsc, broadcast};
 use thiserror::Error;
 use serde::{Serialize, Deserialize};
@@ -11,11 +13,16 @@ pub mod log_filter;
 pub mod block_filter;
 pub mod pending_tx_filter;
 pub mod subscription;
+pub mod log_index;
 
-pub use log_filter::{LogFilter, LogFilterBuilder};
+pub use log_filter::{resolve_block_number, LogFilter, LogFilterBuilder};
+pub use log_index::LogIndexer;
 pub use block_filter::BlockFilter;
 pub use pending_tx_filter::PendingTransactionFilter;
-pub use subscription::{Subscription, SubscriptionManager, SubscriptionType};
+pub use subscription::{
+    Subscription, SubscriptionData, SubscriptionId, SubscriptionManager, SubscriptionType,
+    TaggedEvent,
+};
 
 #[derive(Debug, Error)]
 pub enum FilterError {
@@ -33,6 +40,9 @@ pub enum FilterError {
     
     #[error("Filter expired")]
     FilterExpired,
+    
+    #[error("Block range too large, at most {0} blocks can be queried")]
+    BlockRangeTooLarge(u64),
 }
 
 pub type Result<T> = std::result::Result<T, FilterError>;
@@ -40,6 +50,15 @@ pub type Result<T> = std::result::Result<T, FilterError>;
 /// Filter ID type
 pub type FilterId = U256;
 
+/// Filters not polled for this long are uninstalled
+pub const DEFAULT_FILTER_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Most blocks a single log query may span
+pub const MAX_LOG_BLOCK_RANGE: u64 = 10_000;
+
+/// How often idle filters are looked for
+const FILTER_CLEANUP_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Filter criteria
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FilterCriteria {
@@ -49,6 +68,32 @@ pub struct FilterCriteria {
     pub topics: Vec<Option<Vec<H256>>>,
 }
 
+impl FilterCriteria {
+    /// Check a log against the address and topic filters
+    ///
+    /// Any listed address matches. Each topic position matches if it is
+    /// unset or any of its alternatives equals the log's topic there.
+    pub fn matches_log(&self, log: &Log) -> bool {
+        if let Some(ref addresses) = self.address {
+            if !addresses.is_empty() && !addresses.contains(&log.address) {
+                return false;
+            }
+        }
+        
+        for (i, topic_filter) in self.topics.iter().enumerate() {
+            if let Some(ref topics) = topic_filter {
+                if !topics.is_empty() {
+                    if i >= log.topics.len() || !topics.contains(&log.topics[i]) {
+                        return false;
+                    }
+                }
+            }
+        }
+        
+        true
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum BlockNumber {
@@ -61,10 +106,11 @@ pub enum BlockNumber {
 /// Main filter system
 pub struct FilterSystem<D: Database> {
     db: Arc<D>,
-    filters: Arc<RwLock<HashMap<FilterId, Filter>>>,
+    filters: Arc<RwLock<HashMap<FilterId, InstalledFilter>>>,
     subscriptions: Arc<SubscriptionManager>,
     next_filter_id: Arc<RwLock<U256>>,
     poll_interval: std::time::Duration,
+    filter_timeout: Duration,
 }
 
 /// Filter types
@@ -75,6 +121,19 @@ enum Filter {
     PendingTransaction(PendingTransactionFilter),
 }
 
+/// Filter together with the last time a client created or polled it
+#[derive(Debug, Clone)]
+struct InstalledFilter {
+    filter: Filter,
+    last_activity: Instant,
+}
+
+impl InstalledFilter {
+    fn new(filter: Filter) -> Self {
+        Self { filter, last_activity: Instant::now() }
+    }
+}
+
 impl<D: Database + 'static> FilterSystem<D> {
     pub fn new(db: Arc<D>) -> Self {
         let subscriptions = Arc::new(SubscriptionManager::new());
@@ -85,9 +144,16 @@ impl<D: Database + 'static> FilterSystem<D> {
             subscriptions,
             next_filter_id: Arc::new(RwLock::new(U256::one())),
             poll_interval: std::time::Duration::from_secs(1),
+            filter_timeout: DEFAULT_FILTER_TIMEOUT,
         }
     }
     
+    /// Uninstall filters after `timeout` without a poll
+    pub fn with_filter_timeout(mut self, timeout: Duration) -> Self {
+        self.filter_timeout = timeout;
+        self
+    }
+    
     /// Start the filter system
     pub async fn start(&self) {
         // Start subscription manager
@@ -95,6 +161,12 @@ impl<D: Database + 'static> FilterSystem<D> {
         
         // Start filter polling
         self.start_filter_polling().await;
+        
+        // Uninstall filters clients stopped polling
+        self.start_filter_cleanup();
+        
+        // Index the logs of blocks stored before the filter system started
+        self.start_log_indexing();
     }
     
     /// Create a new log filter
@@ -104,7 +176,7 @@ impl<D: Database + 'static> FilterSystem<D> {
         
         self.filters.write().await.insert(
             filter_id,
-            Filter::Log(filter),
+            InstalledFilter::new(Filter::Log(filter)),
         );
         
         Ok(filter_id)
@@ -117,7 +189,7 @@ impl<D: Database + 'static> FilterSystem<D> {
         
         self.filters.write().await.insert(
             filter_id,
-            Filter::Block(filter),
+            InstalledFilter::new(Filter::Block(filter)),
         );
         
         Ok(filter_id)
@@ -130,7 +202,7 @@ impl<D: Database + 'static> FilterSystem<D> {
         
         self.filters.write().await.insert(
             filter_id,
-            Filter::PendingTransaction(filter),
+            InstalledFilter::new(Filter::PendingTransaction(filter)),
         );
         
         Ok(filter_id)
@@ -140,10 +212,11 @@ impl<D: Database + 'static> FilterSystem<D> {
     pub async fn get_filter_changes(&self, filter_id: FilterId) -> Result<FilterChanges> {
         let mut filters = self.filters.write().await;
         
-        let filter = filters.get_mut(&filter_id)
+        let installed = filters.get_mut(&filter_id)
             .ok_or(FilterError::FilterNotFound)?;
+        installed.last_activity = Instant::now();
         
-        match filter {
+        match &mut installed.filter {
             Filter::Log(log_filter) => {
                 let logs = log_filter.get_changes().await?;
                 Ok(FilterChanges::Logs(logs))
@@ -161,12 +234,13 @@ impl<D: Database + 'static> FilterSystem<D> {
     
     /// Get all logs matching filter
     pub async fn get_filter_logs(&self, filter_id: FilterId) -> Result<Vec<Log>> {
-        let filters = self.filters.read().await;
+        let mut filters = self.filters.write().await;
         
-        let filter = filters.get(&filter_id)
+        let installed = filters.get_mut(&filter_id)
             .ok_or(FilterError::FilterNotFound)?;
+        installed.last_activity = Instant::now();
         
-        match filter {
+        match &installed.filter {
             Filter::Log(log_filter) => {
                 log_filter.get_all_logs().await
             }
@@ -199,10 +273,18 @@ impl<D: Database + 'static> FilterSystem<D> {
     
     /// Notify new block
     pub async fn notify_new_block(&self, block: Block) {
+        // Keep the log index in step with the canonical chain, blocks
+        // replacing reorged ones included
+        let indexed = log_filter::load_receipts(&*self.db, &block.header.hash())
+            .and_then(|receipts| LogIndexer::new(self.db.clone()).index_block(&block, &receipts));
+        if let Err(e) = indexed {
+            tracing::warn!("Failed to index logs of block {}: {}", block.header.number, e);
+        }
+        
         // Update block filters
         let filters = self.filters.read().await;
-        for filter in filters.values() {
-            if let Filter::Block(block_filter) = filter {
+        for installed in filters.values() {
+            if let Filter::Block(block_filter) = &installed.filter {
                 block_filter.add_block(block.header.hash()).await;
             }
         }
@@ -215,8 +297,8 @@ impl<D: Database + 'static> FilterSystem<D> {
     pub async fn notify_new_pending_transaction(&self, tx: Transaction) {
         // Update pending transaction filters
         let filters = self.filters.read().await;
-        for filter in filters.values() {
-            if let Filter::PendingTransaction(tx_filter) = filter {
+        for installed in filters.values() {
+            if let Filter::PendingTransaction(tx_filter) = &installed.filter {
                 tx_filter.add_transaction(tx.hash()).await;
             }
         }
@@ -229,8 +311,8 @@ impl<D: Database + 'static> FilterSystem<D> {
     pub async fn notify_new_logs(&self, logs: Vec<Log>) {
         // Update log filters
         let filters = self.filters.read().await;
-        for filter in filters.values() {
-            if let Filter::Log(log_filter) = filter {
+        for installed in filters.values() {
+            if let Filter::Log(log_filter) = &installed.filter {
                 for log in &logs {
                     if log_filter.matches(log) {
                         log_filter.add_log(log.clone()).await;
@@ -243,6 +325,31 @@ impl<D: Database + 'static> FilterSystem<D> {
         self.subscriptions.notify_new_logs(logs).await;
     }
     
+    /// Retract the logs of blocks a reorg took out of the canonical chain
+    ///
+    /// Each log already delivered for `reverted_blocks` goes out once more
+    /// with `removed` set, to log filters and logs subscriptions alike.
+    pub async fn notify_reorg(&self, reverted_blocks: Vec<Block>) -> Result<()> {
+        let mut seen = HashSet::new();
+        let mut removed_logs = Vec::new();
+        
+        for block in &reverted_blocks {
+            if !seen.insert(block.header.hash()) {
+                continue;
+            }
+            let receipts = log_filter::load_receipts(&*self.db, &block.header.hash())?;
+            removed_logs.extend(log_filter::block_logs(block, &receipts).into_iter().map(|mut log| {
+                log.removed = true;
+                log
+            }));
+        }
+        
+        if !removed_logs.is_empty() {
+            self.notify_new_logs(removed_logs).await;
+        }
+        Ok(())
+    }
+    
     /// Start filter polling
     async fn start_filter_polling(&self) {
         let filters = self.filters.clone();
@@ -257,8 +364,8 @@ impl<D: Database + 'static> FilterSystem<D> {
                 
                 // Poll for changes
                 let filters_guard = filters.read().await;
-                for (id, filter) in filters_guard.iter() {
-                    match filter {
+                for (id, installed) in filters_guard.iter() {
+                    match &installed.filter {
                         Filter::Log(log_filter) => {
                             if let Err(e) = log_filter.poll_for_changes().await {
                                 tracing::warn!("Failed to poll log filter {}: {}", id, e);
@@ -276,6 +383,46 @@ impl<D: Database + 'static> FilterSystem<D> {
         });
     }
     
+    /// Index stored blocks the log index doesn't cover yet, until it
+    /// reaches the latest block
+    fn start_log_indexing(&self) {
+        let indexer = LogIndexer::new(self.db.clone());
+        let interval = self.poll_interval;
+        
+        tokio::spawn(async move {
+            let mut interval_timer = tokio::time::interval(interval);
+            
+            loop {
+                interval_timer.tick().await;
+                
+                match indexer.catch_up() {
+                    Ok(0) => {}
+                    Ok(indexed) => tracing::debug!("Indexed logs of {} blocks", indexed),
+                    Err(e) => tracing::warn!("Failed to index logs: {}", e),
+                }
+            }
+        });
+    }
+    
+    /// Periodically uninstall filters idle for longer than the timeout
+    fn start_filter_cleanup(&self) {
+        let filters = self.filters.clone();
+        let timeout = self.filter_timeout;
+        
+        tokio::spawn(async move {
+            let mut interval_timer = tokio::time::interval(FILTER_CLEANUP_INTERVAL);
+            
+            loop {
+                interval_timer.tick().await;
+                
+                let removed = remove_idle_filters(&filters, timeout, Instant::now()).await;
+                if removed > 0 {
+                    tracing::debug!("Uninstalled {} idle filters", removed);
+                }
+            }
+        });
+    }
+    
     /// Get next filter ID
     async fn next_filter_id(&self) -> FilterId {
         let mut id = self.next_filter_id.write().await;
@@ -284,28 +431,24 @@ impl<D: Database + 'static> FilterSystem<D> {
         filter_id
     }
     
-    /// Clean up expired filters
-    pub async fn cleanup_expired_filters(&self) {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        
-        let mut filters = self.filters.write().await;
-        
-        // Remove filters older than 5 minutes
-        const FILTER_TIMEOUT: u64 = 300;
-        
-        filters.retain(|_, filter| {
-            match filter {
-                Filter::Log(f) => now - f.created_at() < FILTER_TIMEOUT,
-                Filter::Block(f) => now - f.created_at() < FILTER_TIMEOUT,
-                Filter::PendingTransaction(f) => now - f.created_at() < FILTER_TIMEOUT,
-            }
-        });
+    /// Uninstall filters that haven't been polled within the timeout,
+    /// returning how many were removed
+    pub async fn cleanup_expired_filters(&self) -> usize {
+        remove_idle_filters(&self.filters, self.filter_timeout, Instant::now()).await
     }
 }
 
+async fn remove_idle_filters(
+    filters: &RwLock<HashMap<FilterId, InstalledFilter>>,
+    timeout: Duration,
+    now: Instant,
+) -> usize {
+    let mut filters = filters.write().await;
+    let before = filters.len();
+    filters.retain(|_, installed| now.saturating_duration_since(installed.last_activity) < timeout);
+    before - filters.len()
+}
+
 /// Filter changes result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -335,6 +478,22 @@ impl BloomFilter {
         true
     }
     
+    /// Whether a block with `bloom` can hold logs matching `criteria`
+    ///
+    /// Like `FilterCriteria::matches_log`, any listed address and any
+    /// alternative of each topic position will do.
+    pub fn matches_criteria(bloom: &Bloom, criteria: &FilterCriteria) -> bool {
+        if let Some(ref addresses) = criteria.address {
+            if !addresses.is_empty() && !addresses.iter().any(|addr| Self::contains_address(bloom, addr)) {
+                return false;
+            }
+        }
+        
+        criteria.topics.iter().flatten().all(|topics| {
+            topics.is_empty() || topics.iter().any(|topic| Self::contains_topic(bloom, topic))
+        })
+    }
+    
     /// Check if bloom contains address
     pub fn contains_address(bloom: &Bloom, address: &Address) -> bool {
         let hash = ethereum_crypto::keccak256(address.as_bytes());
@@ -348,33 +507,33 @@ impl BloomFilter {
     
     /// Check if bloom contains hash
     fn contains_hash(bloom: &Bloom, hash: &[u8; 32]) -> bool {
-        for i in 0..3 {
-            let bit_index = (hash[i * 2] as usize) | ((hash[i * 2 + 1] as usize) << 8);
-            let byte_index = bit_index / 8;
-            let bit_mask = 1u8 << (bit_index % 8);
-            
-            if byte_index < bloom.0.len() && (bloom.0[byte_index] & bit_mask) == 0 {
-                return false;
-            }
-        }
-        
-        true
+        Self::bloom_bits(hash)
+            .iter()
+            .all(|&(byte_index, bit_mask)| bloom.0[byte_index] & bit_mask != 0)
     }
     
     /// Add to bloom filter
     pub fn add_to_bloom(bloom: &mut Bloom, data: &[u8]) {
         let hash = ethereum_crypto::keccak256(data);
         
-        for i in 0..3 {
-            let bit_index = (hash[i * 2] as usize) | ((hash[i * 2 + 1] as usize) << 8);
-            let byte_index = bit_index / 8;
-            let bit_mask = 1u8 << (bit_index % 8);
-            
-            if byte_index < bloom.0.len() {
-                bloom.0[byte_index] |= bit_mask;
-            }
+        for (byte_index, bit_mask) in Self::bloom_bits(&hash) {
+            bloom.0[byte_index] |= bit_mask;
         }
     }
+    
+    /// Byte and bit of the 2048-bit filter each of the three bits for `hash`
+    /// lands on (yellow paper M3:2048)
+    ///
+    /// Every bit index is the low 11 bits of a big-endian pair of hash bytes,
+    /// counted from the end of the filter.
+    fn bloom_bits(hash: &[u8; 32]) -> [(usize, u8); 3] {
+        let mut bits = [(0, 0); 3];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            let bit_index = (((hash[i * 2] as usize) << 8) | hash[i * 2 + 1] as usize) & 0x7ff;
+            *bit = (255 - bit_index / 8, 1u8 << (bit_index % 8));
+        }
+        bits
+    }
 }
 
 #[cfg(test)]
@@ -392,4 +551,119 @@ mod tests {
         let other_address = Address::from([2u8; 20]);
         // May or may not contain due to false positives
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_bloom_matches_mainnet_log() {
+        // Bloom of a mainnet receipt with a single log of `address` and `topic`
+        let known = "00000000000000000000000000000000000000001000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000002020000000000000000000000000000000000000000000008000000001000000000000000000000000000000000000000000000000000001000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+        let known = Bloom::from_slice(&hex::decode(known).unwrap()).unwrap();
+        let address: [u8; 20] = hex::decode("ef2d6d194084c2de36e0dabfce45d046b37d1106").unwrap().try_into().unwrap();
+        let address = Address::from(address);
+        let topic: [u8; 32] = hex::decode("02c69be41d0b7e40352fc85be1cd65eb03d40ef8427a0ca4596b1ead9a00e9fc").unwrap().try_into().unwrap();
+        let topic = H256::from(topic);
+        
+        let mut bloom = Bloom::default();
+        BloomFilter::add_to_bloom(&mut bloom, address.as_bytes());
+        BloomFilter::add_to_bloom(&mut bloom, topic.as_bytes());
+        assert_eq!(bloom, known);
+        
+        let log = Log::new(address, vec![topic], vec![]);
+        assert!(BloomFilter::matches(&known, &log));
+        assert!(!BloomFilter::contains_address(&known, &Address::from([0x11u8; 20])));
+        assert!(!BloomFilter::contains_topic(&known, &H256::from([0xaau8; 32])));
+        
+        let criteria = LogFilterBuilder::new().address(address).topic(0, H256::from([0xaau8; 32])).build();
+        assert!(!BloomFilter::matches_criteria(&known, &criteria));
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_filters_are_uninstalled() {
+        let db = Arc::new(ethereum_storage::MemoryDatabase::new());
+        let system = FilterSystem::new(db).with_filter_timeout(Duration::from_secs(300));
+        
+        let idle = system.new_block_filter().await.unwrap();
+        let polled = system.new_block_filter().await.unwrap();
+        
+        tokio::time::advance(Duration::from_secs(200)).await;
+        system.get_filter_changes(polled).await.unwrap();
+        assert_eq!(system.cleanup_expired_filters().await, 0);
+        
+        // Past the timeout for the idle filter, not for the polled one
+        tokio::time::advance(Duration::from_secs(200)).await;
+        assert_eq!(system.cleanup_expired_filters().await, 1);
+        
+        assert!(matches!(
+            system.get_filter_changes(idle).await,
+            Err(FilterError::FilterNotFound)
+        ));
+        assert!(system.get_filter_changes(polled).await.is_ok());
+        assert!(!system.uninstall_filter(idle).await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_reorg_delivers_removed_logs_once() {
+        use futures::StreamExt;
+        use ethereum_core::Header;
+        use ethereum_storage::MemoryDatabase;
+        use subscription::SubscriptionNotification;
+        
+        let token = Address::from([0x11u8; 20]);
+        let mut header = Header::new();
+        header.number = U256::from(5);
+        let block = Block::new(header);
+        let receipt = Receipt {
+            tx_type: 0,
+            status: 1,
+            cumulative_gas_used: U256::from(50_000),
+            logs_bloom: Bloom::default(),
+            logs: vec![
+                Log::new(Address::from([0x22u8; 20]), vec![], vec![]),
+                Log::new(token, vec![], vec![1]),
+            ],
+            gas_used: U256::from(50_000),
+            contract_address: None,
+        };
+        
+        let db = Arc::new(MemoryDatabase::new());
+        let key = format!("receipts:{}", hex::encode(block.header.hash()));
+        db.put(key.as_bytes(), &bincode::serialize(&vec![receipt.clone()]).unwrap()).unwrap();
+        
+        let system = FilterSystem::new(db);
+        system.subscriptions.start().await;
+        let criteria = FilterCriteria {
+            from_block: None,
+            to_block: None,
+            address: Some(vec![token]),
+            topics: vec![],
+        };
+        let filter = system.new_log_filter(criteria.clone()).await.unwrap();
+        let subscription = system.subscribe(SubscriptionType::NewLogs { criteria }).await.unwrap();
+        let mut stream = Box::pin(system.subscriptions.multiplex(vec![subscription.id]));
+        
+        system.notify_new_logs(log_filter::block_logs(&block, &[receipt])).await;
+        let delivered = match system.get_filter_changes(filter).await.unwrap() {
+            FilterChanges::Logs(logs) => logs,
+            other => panic!("unexpected changes: {:?}", other),
+        };
+        assert_eq!(delivered.len(), 1);
+        assert!(!delivered[0].removed);
+        assert_eq!(delivered[0].log_index, Some(U256::one()));
+        stream.next().await.unwrap();
+        
+        // The same block reported twice is only retracted once
+        system.notify_reorg(vec![block.clone(), block]).await.unwrap();
+        let mut removed = delivered[0].clone();
+        removed.removed = true;
+        
+        match system.get_filter_changes(filter).await.unwrap() {
+            FilterChanges::Logs(logs) => assert_eq!(logs, vec![removed.clone()]),
+            other => panic!("unexpected changes: {:?}", other),
+        }
+        let event = tokio::time::timeout(Duration::from_secs(1), stream.next()).await.unwrap().unwrap();
+        match event.data {
+            SubscriptionNotification::Log(log) => assert_eq!(log, removed),
+            other => panic!("unexpected notification: {:?}", other),
+        }
+        assert!(tokio::time::timeout(Duration::from_millis(100), stream.next()).await.is_err());
+    }
+}
@@ -2,10 +2,11 @@ use ethereum_types::{H256, U256, Address, Bloom};
 use ethereum_core::{Log, Receipt, Block};
 use ethereum_storage::Database;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use parking_lot::RwLock;
 use std::collections::VecDeque;
 
-use crate::{Result, FilterError, FilterCriteria, BlockNumber, BloomFilter};
+use crate::{Result, FilterError, FilterCriteria, BlockNumber, BloomFilter, LogIndexer, MAX_LOG_BLOCK_RANGE};
 
 /// Log filter for filtering event logs
 pub struct LogFilter<D: Database> {
@@ -14,6 +15,10 @@ pub struct LogFilter<D: Database> {
     pending_logs: Arc<RwLock<VecDeque<Log>>>,
     last_poll_block: Arc<RwLock<U256>>,
     created_at: u64,
+    /// Blocks read from the database so far
+    loaded_blocks: AtomicUsize,
+    /// Blocks whose receipts were read so far
+    loaded_receipts: AtomicUsize,
 }
 
 impl<D: Database> LogFilter<D> {
@@ -29,6 +34,8 @@ impl<D: Database> LogFilter<D> {
             pending_logs: Arc::new(RwLock::new(VecDeque::new())),
             last_poll_block: Arc::new(RwLock::new(U256::zero())),
             created_at,
+            loaded_blocks: AtomicUsize::new(0),
+            loaded_receipts: AtomicUsize::new(0),
         }
     }
     
@@ -37,27 +44,19 @@ impl<D: Database> LogFilter<D> {
         self.created_at
     }
     
+    /// Number of blocks this filter has read from the database
+    pub fn loaded_blocks(&self) -> usize {
+        self.loaded_blocks.load(Ordering::Relaxed)
+    }
+    
+    /// Number of blocks whose receipts this filter has read
+    pub fn loaded_receipts(&self) -> usize {
+        self.loaded_receipts.load(Ordering::Relaxed)
+    }
+    
     /// Check if a log matches the filter criteria
     pub fn matches(&self, log: &Log) -> bool {
-        // Check address filter
-        if let Some(ref addresses) = self.criteria.address {
-            if !addresses.is_empty() && !addresses.contains(&log.address) {
-                return false;
-            }
-        }
-        
-        // Check topics filter
-        for (i, topic_filter) in self.criteria.topics.iter().enumerate() {
-            if let Some(ref topics) = topic_filter {
-                if !topics.is_empty() {
-                    if i >= log.topics.len() || !topics.contains(&log.topics[i]) {
-                        return false;
-                    }
-                }
-            }
-        }
-        
-        true
+        self.criteria.matches_log(log)
     }
     
     /// Add a log to pending queue
@@ -73,56 +72,72 @@ impl<D: Database> LogFilter<D> {
     }
     
     /// Get all logs matching the filter
+    ///
+    /// Blocks covered by the log index are only read if the index lists
+    /// them for the filter's addresses and topics, the rest of the range is
+    /// scanned block by block with a bloom check. Ranges that run backwards
+    /// or span more than `MAX_LOG_BLOCK_RANGE` blocks are rejected.
     pub async fn get_all_logs(&self) -> Result<Vec<Log>> {
-        let from_block = self.resolve_block_number(&self.criteria.from_block).await?;
-        let to_block = self.resolve_block_number(&self.criteria.to_block).await?;
+        let head = self.get_latest_block_number().await?.as_u64();
+        let from_block = resolve_block_number(self.criteria.from_block.as_ref(), head);
+        let to_block = resolve_block_number(self.criteria.to_block.as_ref(), head);
+        
+        if from_block > to_block {
+            return Err(FilterError::InvalidCriteria);
+        }
+        if to_block - from_block >= MAX_LOG_BLOCK_RANGE {
+            return Err(FilterError::BlockRangeTooLarge(MAX_LOG_BLOCK_RANGE));
+        }
         
+        // Nothing past the head is stored yet, the pending block included
+        let to_block = to_block.min(head);
         let mut all_logs = Vec::new();
+        if from_block > to_block {
+            return Ok(all_logs);
+        }
         
-        // Iterate through blocks
-        for block_num in from_block.as_u64()..=to_block.as_u64() {
-            let block = self.get_block(U256::from(block_num)).await?;
-            
-            // Quick bloom filter check
-            if let Some(ref addresses) = self.criteria.address {
-                let mut matches_bloom = false;
-                for addr in addresses {
-                    if BloomFilter::contains_address(&block.header.bloom, addr) {
-                        matches_bloom = true;
-                        break;
+        let index = LogIndexer::new(self.db.clone());
+        let indexed_until = index.indexed_until()?.clamp(from_block, to_block.saturating_add(1));
+        if from_block < indexed_until {
+            match index.candidates(&self.criteria, from_block, indexed_until - 1)? {
+                Some(candidates) => {
+                    for block_num in candidates {
+                        self.collect_block_logs(block_num, &mut all_logs).await?;
                     }
                 }
-                
-                if !matches_bloom {
-                    continue; // Skip this block
-                }
-            }
-            
-            // Get receipts for block
-            let receipts = self.get_receipts(&block.header.hash()).await?;
-            
-            // Extract logs from receipts
-            for (tx_index, receipt) in receipts.iter().enumerate() {
-                for (log_index, log) in receipt.logs.iter().enumerate() {
-                    if self.matches(log) {
-                        let mut log_with_position = log.clone();
-                        log_with_position.block_hash = Some(block.header.hash());
-                        log_with_position.block_number = Some(block.header.number);
-                        log_with_position.transaction_hash = Some(
-                            block.body.transactions[tx_index].hash()
-                        );
-                        log_with_position.transaction_index = Some(U256::from(tx_index));
-                        log_with_position.log_index = Some(U256::from(log_index));
-                        
-                        all_logs.push(log_with_position);
+                None => {
+                    for block_num in from_block..indexed_until {
+                        self.collect_block_logs(block_num, &mut all_logs).await?;
                     }
                 }
             }
         }
         
+        // Iterate through the blocks not indexed yet
+        for block_num in indexed_until..=to_block {
+            self.collect_block_logs(block_num, &mut all_logs).await?;
+        }
+        
         Ok(all_logs)
     }
     
+    /// Append the matching logs of block `block_num` to `logs`
+    async fn collect_block_logs(&self, block_num: u64, logs: &mut Vec<Log>) -> Result<()> {
+        let block = self.get_block(U256::from(block_num)).await?;
+        
+        // Receipts are only read if the header's bloom may hold a match
+        if !BloomFilter::matches_criteria(&block.header.logs_bloom, &self.criteria) {
+            return Ok(());
+        }
+        
+        self.loaded_receipts.fetch_add(1, Ordering::Relaxed);
+        let receipts = self.get_receipts(&block.header.hash()).await?;
+        
+        // Extract logs from receipts
+        logs.extend(block_logs(&block, &receipts).into_iter().filter(|log| self.matches(log)));
+        Ok(())
+    }
+    
     /// Poll for changes in new blocks
     pub async fn poll_for_changes(&self) -> Result<()> {
         let current_block = self.get_latest_block_number().await?;
@@ -137,78 +152,108 @@ impl<D: Database> LogFilter<D> {
             let block = self.get_block(U256::from(block_num)).await?;
             let receipts = self.get_receipts(&block.header.hash()).await?;
             
-            for (tx_index, receipt) in receipts.iter().enumerate() {
-                for (log_index, log) in receipt.logs.iter().enumerate() {
-                    if self.matches(log) {
-                        let mut log_with_position = log.clone();
-                        log_with_position.block_hash = Some(block.header.hash());
-                        log_with_position.block_number = Some(block.header.number);
-                        log_with_position.transaction_hash = Some(
-                            block.body.transactions[tx_index].hash()
-                        );
-                        log_with_position.transaction_index = Some(U256::from(tx_index));
-                        log_with_position.log_index = Some(U256::from(log_index));
-                        
-                        self.pending_logs.write().push_back(log_with_position);
-                    }
-                }
-            }
+            let mut pending = self.pending_logs.write();
+            pending.extend(block_logs(&block, &receipts).into_iter().filter(|log| self.matches(log)));
         }
         
         *last_poll = current_block;
         Ok(())
     }
     
-    /// Resolve block number
-    async fn resolve_block_number(&self, block_num: &Option<BlockNumber>) -> Result<U256> {
-        match block_num {
-            Some(BlockNumber::Number(n)) => Ok(*n),
-            Some(BlockNumber::Latest) | None => self.get_latest_block_number().await,
-            Some(BlockNumber::Earliest) => Ok(U256::zero()),
-            Some(BlockNumber::Pending) => self.get_latest_block_number().await,
-        }
-    }
-    
     /// Get block by number
     async fn get_block(&self, block_number: U256) -> Result<Block> {
-        let key = format!("block:number:{}", block_number);
-        let block_hash = self.db.get(key.as_bytes())?
-            .ok_or(FilterError::InvalidCriteria)?;
-        
-        let block_key = format!("block:{}", hex::encode(block_hash));
-        let block_data = self.db.get(block_key.as_bytes())?
-            .ok_or(FilterError::InvalidCriteria)?;
-        
-        bincode::deserialize(&block_data)
-            .map_err(|_| FilterError::InvalidCriteria)
+        self.loaded_blocks.fetch_add(1, Ordering::Relaxed);
+        load_block_by_number(&*self.db, block_number.as_u64())?
+            .ok_or(FilterError::InvalidCriteria)
     }
     
     /// Get receipts for a block
     async fn get_receipts(&self, block_hash: &H256) -> Result<Vec<Receipt>> {
-        let key = format!("receipts:{}", hex::encode(block_hash));
-        
-        match self.db.get(key.as_bytes())? {
-            Some(data) => {
-                bincode::deserialize(&data)
-                    .map_err(|_| FilterError::InvalidCriteria)
-            }
-            None => Ok(Vec::new()),
-        }
+        load_receipts(&*self.db, block_hash)
     }
     
     /// Get latest block number
     async fn get_latest_block_number(&self) -> Result<U256> {
-        let key = b"latest_block";
-        
-        match self.db.get(key)? {
-            Some(data) => {
-                let bytes: [u8; 32] = data.try_into()
-                    .map_err(|_| FilterError::InvalidCriteria)?;
-                Ok(U256::from_big_endian(&bytes))
-            }
-            None => Ok(U256::zero()),
+        latest_block_number(&*self.db)
+    }
+}
+
+/// Concrete number of `block` when the canonical head is `head`, an unset
+/// bound meaning the head
+///
+/// The pending block is the one that will follow the head.
+pub fn resolve_block_number(block: Option<&BlockNumber>, head: u64) -> u64 {
+    match block {
+        Some(BlockNumber::Number(n)) => (*n).min(U256::from(u64::MAX)).as_u64(),
+        Some(BlockNumber::Earliest) => 0,
+        Some(BlockNumber::Latest) | None => head,
+        Some(BlockNumber::Pending) => head.saturating_add(1),
+    }
+}
+
+/// Canonical block `number`, `None` if it isn't stored
+pub(crate) fn load_block_by_number<D: Database + ?Sized>(db: &D, number: u64) -> Result<Option<Block>> {
+    let key = format!("number:{}", number);
+    let block_hash = match db.get(key.as_bytes())? {
+        Some(hash) => hash,
+        None => return Ok(None),
+    };
+    
+    let block_key = format!("block:{}", hex::encode(block_hash));
+    let block_data = db.get(block_key.as_bytes())?
+        .ok_or(FilterError::InvalidCriteria)?;
+    
+    bincode::deserialize(&block_data)
+        .map(Some)
+        .map_err(|_| FilterError::InvalidCriteria)
+}
+
+/// Number of the latest block, zero before any block is stored
+pub(crate) fn latest_block_number<D: Database + ?Sized>(db: &D) -> Result<U256> {
+    match db.get(b"latest_block")? {
+        Some(data) => {
+            let bytes: [u8; 32] = data.try_into()
+                .map_err(|_| FilterError::InvalidCriteria)?;
+            Ok(U256::from_big_endian(&bytes))
+        }
+        None => Ok(U256::zero()),
+    }
+}
+
+/// Receipts stored for a block, empty if there are none
+pub(crate) fn load_receipts<D: Database + ?Sized>(db: &D, block_hash: &H256) -> Result<Vec<Receipt>> {
+    let key = format!("receipts:{}", hex::encode(block_hash));
+    
+    match db.get(key.as_bytes())? {
+        Some(data) => {
+            bincode::deserialize(&data)
+                .map_err(|_| FilterError::InvalidCriteria)
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Logs of `block` with their position filled in, log indexes counting
+/// from the start of the block
+pub(crate) fn block_logs(block: &Block, receipts: &[Receipt]) -> Vec<Log> {
+    let block_hash = block.header.hash();
+    let mut logs = Vec::new();
+    
+    for (tx_index, receipt) in receipts.iter().enumerate() {
+        let transaction_hash = block.transactions.get(tx_index).map(|tx| tx.hash());
+        for log in &receipt.logs {
+            let mut log_with_position = log.clone();
+            log_with_position.block_hash = Some(block_hash);
+            log_with_position.block_number = Some(block.header.number);
+            log_with_position.transaction_hash = transaction_hash;
+            log_with_position.transaction_index = Some(U256::from(tx_index));
+            log_with_position.log_index = Some(U256::from(logs.len()));
+            
+            logs.push(log_with_position);
         }
     }
+    
+    logs
 }
 
 /// Log filter builder
@@ -0,0 +1,336 @@
+use ethereum_types::{H256, Address};
+use ethereum_core::{Block, Receipt};
+use ethereum_storage::Database;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::{Result, FilterError, FilterCriteria};
+use crate::log_filter::{latest_block_number, load_block_by_number, load_receipts};
+
+/// Key holding the number of the first block not indexed yet, every block
+/// below it is
+const INDEXED_KEY: &[u8] = b"logindex:next";
+
+/// Inverted index from log addresses and topics to the blocks logging them
+///
+/// Each block writes one empty entry per distinct address and topic of its
+/// logs, keyed `logindex:{kind}:{hex}:{number}` with a fixed-width number so
+/// that entries of one address or topic are ordered by block. Topics are
+/// indexed regardless of their position, and entries of blocks a reorg
+/// replaced are left behind: both only make for candidate blocks that turn
+/// out not to match once their logs are read.
+pub struct LogIndexer<D: Database> {
+    db: Arc<D>,
+}
+
+impl<D: Database> LogIndexer<D> {
+    pub fn new(db: Arc<D>) -> Self {
+        Self { db }
+    }
+
+    /// Number of the first block that isn't indexed, all earlier ones are
+    pub fn indexed_until(&self) -> Result<u64> {
+        match self.db.get(INDEXED_KEY)? {
+            Some(data) => {
+                let bytes: [u8; 8] = data.try_into()
+                    .map_err(|_| FilterError::InvalidCriteria)?;
+                Ok(u64::from_be_bytes(bytes))
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Write the index entries of `block`
+    ///
+    /// Called for every block made canonical, including ones replacing
+    /// already indexed blocks in a reorg. The indexed range only grows when
+    /// `block` is the next block to index.
+    pub fn index_block(&self, block: &Block, receipts: &[Receipt]) -> Result<()> {
+        let number = block.header.number.as_u64();
+        let mut addresses = BTreeSet::new();
+        let mut topics = BTreeSet::new();
+        for log in receipts.iter().flat_map(|receipt| &receipt.logs) {
+            addresses.insert(log.address);
+            topics.extend(log.topics.iter().copied());
+        }
+
+        let mut batch = self.db.batch();
+        for address in &addresses {
+            batch.put(address_key(address, number).as_bytes(), &[]);
+        }
+        for topic in &topics {
+            batch.put(topic_key(topic, number).as_bytes(), &[]);
+        }
+        if number == self.indexed_until()? {
+            batch.put(INDEXED_KEY, &(number + 1).to_be_bytes());
+        }
+        self.db.write_batch(batch)?;
+
+        Ok(())
+    }
+
+    /// Index the stored blocks from where indexing stopped up to the latest
+    /// one, returning how many were indexed
+    pub fn catch_up(&self) -> Result<usize> {
+        let latest = latest_block_number(&*self.db)?.as_u64();
+        let mut indexed = 0;
+
+        for number in self.indexed_until()?..=latest {
+            let block = match load_block_by_number(&*self.db, number)? {
+                Some(block) => block,
+                None => break,
+            };
+            let receipts = load_receipts(&*self.db, &block.header.hash())?;
+            self.index_block(&block, &receipts)?;
+            indexed += 1;
+        }
+
+        Ok(indexed)
+    }
+
+    /// Blocks within `[from, to]` that may hold logs matching `criteria`,
+    /// `None` if the criteria name no address or topic to narrow them down
+    ///
+    /// Only meaningful for indexed blocks, `to` must be below
+    /// `indexed_until`.
+    pub fn candidates(&self, criteria: &FilterCriteria, from: u64, to: u64) -> Result<Option<BTreeSet<u64>>> {
+        let mut candidates: Option<BTreeSet<u64>> = None;
+        let mut narrow = |blocks: BTreeSet<u64>| {
+            candidates = Some(match candidates.take() {
+                Some(current) => current.intersection(&blocks).copied().collect(),
+                None => blocks,
+            });
+        };
+
+        if let Some(addresses) = criteria.address.as_ref().filter(|addresses| !addresses.is_empty()) {
+            let mut blocks = BTreeSet::new();
+            for address in addresses {
+                blocks.extend(self.blocks_with(&address_prefix(address), from, to)?);
+            }
+            narrow(blocks);
+        }
+
+        for alternatives in criteria.topics.iter().flatten().filter(|topics| !topics.is_empty()) {
+            let mut blocks = BTreeSet::new();
+            for topic in alternatives {
+                blocks.extend(self.blocks_with(&topic_prefix(topic), from, to)?);
+            }
+            narrow(blocks);
+        }
+
+        Ok(candidates)
+    }
+
+    /// Numbers within `[from, to]` of the entries under `prefix`
+    fn blocks_with(&self, prefix: &str, from: u64, to: u64) -> Result<BTreeSet<u64>> {
+        let mut blocks = BTreeSet::new();
+        let mut iter = self.db.iter_from(format!("{}{:016x}", prefix, from).as_bytes());
+
+        while let Some(entry) = iter.next() {
+            let (key, _) = entry?;
+            let number = match key.strip_prefix(prefix.as_bytes()) {
+                Some(number) => std::str::from_utf8(number).ok()
+                    .and_then(|number| u64::from_str_radix(number, 16).ok())
+                    .ok_or(FilterError::InvalidCriteria)?,
+                None => break,
+            };
+            if number > to {
+                break;
+            }
+            blocks.insert(number);
+        }
+
+        Ok(blocks)
+    }
+}
+
+fn address_prefix(address: &Address) -> String {
+    format!("logindex:address:{}:", hex::encode(address.as_bytes()))
+}
+
+fn topic_prefix(topic: &H256) -> String {
+    format!("logindex:topic:{}:", hex::encode(topic.as_bytes()))
+}
+
+fn address_key(address: &Address, number: u64) -> String {
+    format!("{}{:016x}", address_prefix(address), number)
+}
+
+fn topic_key(topic: &H256, number: u64) -> String {
+    format!("{}{:016x}", topic_prefix(topic), number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BloomFilter, LogFilter, LogFilterBuilder, BlockNumber};
+    use ethereum_core::{Header, Log};
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_types::{Bloom, U256};
+
+    /// Store canonical block `number` with one receipt holding `logs`
+    fn store_block(db: &MemoryDatabase, number: u64, logs: Vec<Log>) {
+        let mut bloom = Bloom::default();
+        for log in &logs {
+            BloomFilter::add_to_bloom(&mut bloom, log.address.as_bytes());
+            for topic in &log.topics {
+                BloomFilter::add_to_bloom(&mut bloom, topic.as_bytes());
+            }
+        }
+        let mut header = Header::new();
+        header.number = U256::from(number);
+        header.logs_bloom = bloom;
+        let block = Block::new(header);
+        let hash = block.header.hash();
+        let receipt = Receipt {
+            tx_type: 0,
+            status: 1,
+            cumulative_gas_used: U256::from(21_000),
+            logs_bloom: bloom,
+            logs,
+            gas_used: U256::from(21_000),
+            contract_address: None,
+        };
+
+        db.put(format!("number:{}", number).as_bytes(), hash.as_bytes()).unwrap();
+        db.put(format!("block:{}", hex::encode(hash)).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+        db.put(format!("receipts:{}", hex::encode(hash)).as_bytes(), &bincode::serialize(&vec![receipt]).unwrap()).unwrap();
+        let mut latest = [0u8; 32];
+        U256::from(number).to_big_endian(&mut latest);
+        db.put(b"latest_block", &latest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_indexed_query_matches_full_scan() {
+        let db = Arc::new(MemoryDatabase::new());
+        let token = Address::from([0x11u8; 20]);
+        let other = Address::from([0x22u8; 20]);
+        let transfer = H256::from([0xaau8; 32]);
+        let approval = H256::from([0xbbu8; 32]);
+
+        for number in 0..100u64 {
+            let logs = match number {
+                10 | 50 => vec![Log::new(token, vec![transfer], vec![1])],
+                70 => vec![Log::new(token, vec![approval], vec![2])],
+                _ => vec![Log::new(other, vec![transfer], vec![])],
+            };
+            store_block(&db, number, logs);
+        }
+
+        let criteria = LogFilterBuilder::new()
+            .from_block(BlockNumber::Earliest)
+            .to_block(BlockNumber::Latest)
+            .address(token)
+            .topic(0, transfer)
+            .build();
+
+        // Nothing is indexed yet, every block gets read
+        let scan = LogFilter::new(criteria.clone(), db.clone());
+        let scanned = scan.get_all_logs().await.unwrap();
+        assert_eq!(scanned.len(), 2);
+        assert_eq!(scan.loaded_blocks(), 100);
+
+        let indexer = LogIndexer::new(db.clone());
+        assert_eq!(indexer.catch_up().unwrap(), 100);
+        assert_eq!(indexer.indexed_until().unwrap(), 100);
+        assert_eq!(indexer.catch_up().unwrap(), 0);
+
+        // Only blocks logging both the address and the topic are read
+        let indexed = LogFilter::new(criteria.clone(), db.clone());
+        assert_eq!(indexed.get_all_logs().await.unwrap(), scanned);
+        assert_eq!(indexed.loaded_blocks(), 2);
+
+        // Blocks past the index are still scanned
+        store_block(&db, 100, vec![Log::new(token, vec![transfer], vec![3])]);
+        let partial = LogFilter::new(criteria, db.clone());
+        let logs = partial.get_all_logs().await.unwrap();
+        assert_eq!(logs.len(), 3);
+        assert_eq!(logs[2].block_number, Some(U256::from(100)));
+        assert_eq!(partial.loaded_blocks(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_scan_reads_receipts_of_bloom_matches_only() {
+        let db = Arc::new(MemoryDatabase::new());
+        let token = Address::from([0x11u8; 20]);
+        let other = Address::from([0x22u8; 20]);
+        let transfer = H256::from([0xaau8; 32]);
+
+        for number in 0..20u64 {
+            let emitter = if number % 5 == 0 { token } else { other };
+            store_block(&db, number, vec![Log::new(emitter, vec![transfer], vec![])]);
+        }
+
+        let criteria = LogFilterBuilder::new()
+            .from_block(BlockNumber::Earliest)
+            .to_block(BlockNumber::Latest)
+            .address(token)
+            .build();
+        let filter = LogFilter::new(criteria, db.clone());
+        let logs = filter.get_all_logs().await.unwrap();
+
+        let numbers: Vec<_> = logs.iter().map(|log| log.block_number.unwrap().as_u64()).collect();
+        assert_eq!(numbers, vec![0, 5, 10, 15]);
+        assert_eq!(filter.loaded_blocks(), 20);
+        assert_eq!(filter.loaded_receipts(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_block_tags_resolve_against_head() {
+        let db = Arc::new(MemoryDatabase::new());
+        let token = Address::from([0x11u8; 20]);
+        for number in 0..5u64 {
+            store_block(&db, number, vec![Log::new(token, vec![], vec![number as u8])]);
+        }
+
+        let query = |from: BlockNumber, to: BlockNumber| {
+            let criteria = LogFilterBuilder::new().from_block(from).to_block(to).build();
+            LogFilter::new(criteria, db.clone())
+        };
+
+        // Only the head block is scanned
+        let latest = query(BlockNumber::Latest, BlockNumber::Latest);
+        let logs = latest.get_all_logs().await.unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].block_number, Some(U256::from(4)));
+        assert_eq!(latest.loaded_blocks(), 1);
+
+        let earliest = query(BlockNumber::Earliest, BlockNumber::Earliest);
+        let logs = earliest.get_all_logs().await.unwrap();
+        assert_eq!(logs[0].block_number, Some(U256::zero()));
+        assert_eq!(earliest.loaded_blocks(), 1);
+
+        // The pending block isn't stored yet, so there is nothing past the head
+        let pending = query(BlockNumber::Latest, BlockNumber::Pending);
+        assert_eq!(pending.get_all_logs().await.unwrap().len(), 1);
+        let pending = query(BlockNumber::Pending, BlockNumber::Pending);
+        assert!(pending.get_all_logs().await.unwrap().is_empty());
+        assert_eq!(pending.loaded_blocks(), 0);
+
+        assert_eq!(crate::resolve_block_number(Some(&BlockNumber::Pending), 4), 5);
+        assert_eq!(crate::resolve_block_number(None, 4), 4);
+    }
+
+    #[tokio::test]
+    async fn test_get_all_logs_rejects_bad_ranges() {
+        let db = Arc::new(MemoryDatabase::new());
+
+        let backwards = LogFilterBuilder::new()
+            .from_block(BlockNumber::Number(U256::from(5)))
+            .to_block(BlockNumber::Number(U256::from(4)))
+            .build();
+        assert!(matches!(
+            LogFilter::new(backwards, db.clone()).get_all_logs().await,
+            Err(FilterError::InvalidCriteria)
+        ));
+
+        let too_long = LogFilterBuilder::new()
+            .from_block(BlockNumber::Number(U256::zero()))
+            .to_block(BlockNumber::Number(U256::from(crate::MAX_LOG_BLOCK_RANGE)))
+            .build();
+        assert!(matches!(
+            LogFilter::new(too_long, db).get_all_logs().await,
+            Err(FilterError::BlockRangeTooLarge(_))
+        ));
+    }
+}
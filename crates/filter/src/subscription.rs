@@ -4,18 +4,26 @@ use std::sync::Arc;
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use tokio::sync::{mpsc, broadcast};
+use futures::stream::{self, BoxStream, SelectAll, Stream, StreamExt};
 use serde::{Serialize, Deserialize};
 use serde_json::Value;
 
 use crate::{FilterCriteria, FilterError, Result};
 
+/// Subscription ID type
+pub type SubscriptionId = U256;
+
+/// Payload delivered for a subscription
+pub type SubscriptionData = SubscriptionNotification;
+
 /// Subscription types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum SubscriptionType {
     NewHeads,
     NewPendingTransactions,
-    Logs(FilterCriteria),
+    #[serde(rename = "logs")]
+    NewLogs { criteria: FilterCriteria },
     Syncing,
 }
 
@@ -36,6 +44,14 @@ pub enum SubscriptionNotification {
     Syncing(SyncStatus),
 }
 
+/// Notification tagged with the subscription it was produced for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaggedEvent {
+    pub subscription_id: SubscriptionId,
+    pub data: SubscriptionData,
+}
+
 /// Block header for subscription
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -94,6 +110,7 @@ pub struct SyncStatus {
 /// Subscription manager
 pub struct SubscriptionManager {
     subscriptions: Arc<RwLock<HashMap<U256, Subscription>>>,
+    streams: Arc<RwLock<HashMap<SubscriptionId, broadcast::Sender<SubscriptionData>>>>,
     next_id: Arc<RwLock<U256>>,
     new_heads_broadcast: broadcast::Sender<Block>,
     new_pending_tx_broadcast: broadcast::Sender<Transaction>,
@@ -108,6 +125,7 @@ impl SubscriptionManager {
         
         Self {
             subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            streams: Arc::new(RwLock::new(HashMap::new())),
             next_id: Arc::new(RwLock::new(U256::one())),
             new_heads_broadcast: new_heads_tx,
             new_pending_tx_broadcast: new_pending_tx_tx,
@@ -136,6 +154,9 @@ impl SubscriptionManager {
         
         self.subscriptions.write().insert(id, subscription);
         
+        let (stream_tx, _) = broadcast::channel(100);
+        self.streams.write().insert(id, stream_tx);
+        
         // Return subscription with receiver
         Ok(Subscription {
             id,
@@ -145,10 +166,66 @@ impl SubscriptionManager {
     }
     
     /// Unsubscribe
+    ///
+    /// Dropping the stream sender closes the matching branch of any
+    /// multiplexed stream, so it is removed from the merged output.
     pub async fn unsubscribe(&self, subscription_id: U256) -> Result<bool> {
+        self.streams.write().remove(&subscription_id);
         Ok(self.subscriptions.write().remove(&subscription_id).is_some())
     }
     
+    /// Merge several subscriptions into a single stream of tagged events
+    pub fn multiplex(&self, ids: Vec<SubscriptionId>) -> impl Stream<Item = TaggedEvent> {
+        let streams = self.streams.read();
+        let mut merged = SelectAll::new();
+        
+        for id in ids {
+            match streams.get(&id) {
+                Some(sender) => merged.push(Self::tagged_stream(id, sender.subscribe())),
+                None => tracing::debug!("Skipping unknown subscription {} in multiplex", id),
+            }
+        }
+        
+        merged
+    }
+    
+    /// Turn a per-subscription receiver into a stream of tagged events
+    fn tagged_stream(
+        id: SubscriptionId,
+        receiver: broadcast::Receiver<SubscriptionData>,
+    ) -> BoxStream<'static, TaggedEvent> {
+        stream::unfold(receiver, move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(data) => {
+                        return Some((TaggedEvent { subscription_id: id, data }, receiver));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Subscription {} lagged, skipped {} events", id, skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+    
+    /// Forward a notification to the subscription's own channel and stream
+    fn deliver(
+        streams: &RwLock<HashMap<SubscriptionId, broadcast::Sender<SubscriptionData>>>,
+        sub: &Subscription,
+        notification: SubscriptionNotification,
+    ) {
+        if let Some(stream) = streams.read().get(&sub.id) {
+            // No receivers just means nobody multiplexes this subscription
+            let _ = stream.send(notification.clone());
+        }
+        
+        if let Err(e) = sub.sender.send(notification) {
+            tracing::warn!("Failed to send notification for subscription {}: {}", sub.id, e);
+        }
+    }
+    
     /// Notify new block
     pub async fn notify_new_block(&self, block: Block) {
         let _ = self.new_heads_broadcast.send(block);
@@ -167,6 +244,7 @@ impl SubscriptionManager {
     /// Start new heads notification handler
     fn start_new_heads_handler(&self) {
         let subscriptions = self.subscriptions.clone();
+        let streams = self.streams.clone();
         let mut receiver = self.new_heads_broadcast.subscribe();
         
         tokio::spawn(async move {
@@ -178,9 +256,7 @@ impl SubscriptionManager {
                         let header = BlockHeader::from(&block.header);
                         let notification = SubscriptionNotification::NewHead(header);
                         
-                        if let Err(e) = sub.sender.send(notification) {
-                            tracing::warn!("Failed to send new head notification: {}", e);
-                        }
+                        Self::deliver(&streams, sub, notification);
                     }
                 }
             }
@@ -190,6 +266,7 @@ impl SubscriptionManager {
     /// Start new pending transactions handler
     fn start_new_pending_tx_handler(&self) {
         let subscriptions = self.subscriptions.clone();
+        let streams = self.streams.clone();
         let mut receiver = self.new_pending_tx_broadcast.subscribe();
         
         tokio::spawn(async move {
@@ -200,9 +277,7 @@ impl SubscriptionManager {
                     if matches!(sub.subscription_type, SubscriptionType::NewPendingTransactions) {
                         let notification = SubscriptionNotification::NewPendingTransaction(tx.hash());
                         
-                        if let Err(e) = sub.sender.send(notification) {
-                            tracing::warn!("Failed to send pending tx notification: {}", e);
-                        }
+                        Self::deliver(&streams, sub, notification);
                     }
                 }
             }
@@ -212,6 +287,7 @@ impl SubscriptionManager {
     /// Start new logs handler
     fn start_new_logs_handler(&self) {
         let subscriptions = self.subscriptions.clone();
+        let streams = self.streams.clone();
         let mut receiver = self.new_logs_broadcast.subscribe();
         
         tokio::spawn(async move {
@@ -219,14 +295,12 @@ impl SubscriptionManager {
                 let subs = subscriptions.read();
                 
                 for sub in subs.values() {
-                    if let SubscriptionType::Logs(ref criteria) = sub.subscription_type {
+                    if let SubscriptionType::NewLogs { ref criteria } = sub.subscription_type {
                         for log in &logs {
-                            if Self::log_matches_criteria(log, criteria) {
+                            if criteria.matches_log(log) {
                                 let notification = SubscriptionNotification::Log(log.clone());
                                 
-                                if let Err(e) = sub.sender.send(notification) {
-                                    tracing::warn!("Failed to send log notification: {}", e);
-                                }
+                                Self::deliver(&streams, sub, notification);
                             }
                         }
                     }
@@ -235,29 +309,6 @@ impl SubscriptionManager {
         });
     }
     
-    /// Check if log matches criteria
-    fn log_matches_criteria(log: &Log, criteria: &FilterCriteria) -> bool {
-        // Check address filter
-        if let Some(ref addresses) = criteria.address {
-            if !addresses.is_empty() && !addresses.contains(&log.address) {
-                return false;
-            }
-        }
-        
-        // Check topics filter
-        for (i, topic_filter) in criteria.topics.iter().enumerate() {
-            if let Some(ref topics) = topic_filter {
-                if !topics.is_empty() {
-                    if i >= log.topics.len() || !topics.contains(&log.topics[i]) {
-                        return false;
-                    }
-                }
-            }
-        }
-        
-        true
-    }
-    
     /// Get next subscription ID
     async fn next_subscription_id(&self) -> U256 {
         let mut id = self.next_id.write();
@@ -274,10 +325,17 @@ impl SubscriptionManager {
     /// Clean up closed subscriptions
     pub async fn cleanup_closed_subscriptions(&self) {
         let mut subs = self.subscriptions.write();
+        let mut streams = self.streams.write();
         
         subs.retain(|id, sub| {
-            if sub.sender.is_closed() {
+            let multiplexed = streams
+                .get(id)
+                .map(|stream| stream.receiver_count() > 0)
+                .unwrap_or(false);
+            
+            if sub.sender.is_closed() && !multiplexed {
                 tracing::debug!("Removing closed subscription {}", id);
+                streams.remove(id);
                 false
             } else {
                 true
@@ -311,4 +369,101 @@ impl SubscriptionMessage {
             },
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::Header;
+    use std::time::Duration;
+    
+    fn empty_criteria() -> FilterCriteria {
+        FilterCriteria {
+            from_block: None,
+            to_block: None,
+            address: None,
+            topics: vec![],
+        }
+    }
+    
+    async fn next_event<S: Stream<Item = TaggedEvent> + Unpin>(stream: &mut S) -> TaggedEvent {
+        tokio::time::timeout(Duration::from_secs(1), stream.next())
+            .await
+            .expect("timed out waiting for event")
+            .expect("stream ended")
+    }
+    
+    #[tokio::test]
+    async fn test_multiplex_new_heads_and_logs() {
+        let manager = SubscriptionManager::new();
+        manager.start().await;
+        
+        let heads = manager.subscribe(SubscriptionType::NewHeads).await.unwrap();
+        let logs = manager.subscribe(SubscriptionType::NewLogs { criteria: empty_criteria() }).await.unwrap();
+        
+        let mut stream = Box::pin(manager.multiplex(vec![heads.id, logs.id]));
+        
+        manager.notify_new_block(Block::new(Header::new())).await;
+        manager.notify_new_logs(vec![Log::default()]).await;
+        
+        let mut saw_head = false;
+        let mut saw_log = false;
+        for _ in 0..2 {
+            let event = next_event(&mut stream).await;
+            match event.data {
+                SubscriptionNotification::NewHead(_) => {
+                    assert_eq!(event.subscription_id, heads.id);
+                    saw_head = true;
+                }
+                SubscriptionNotification::Log(_) => {
+                    assert_eq!(event.subscription_id, logs.id);
+                    saw_log = true;
+                }
+                other => panic!("unexpected notification: {:?}", other),
+            }
+        }
+        assert!(saw_head && saw_log);
+        
+        // Dropping the logs subscription leaves only the heads branch
+        assert!(manager.unsubscribe(logs.id).await.unwrap());
+        manager.notify_new_logs(vec![Log::default()]).await;
+        manager.notify_new_block(Block::new(Header::new())).await;
+        
+        let event = next_event(&mut stream).await;
+        assert_eq!(event.subscription_id, heads.id);
+        assert!(matches!(event.data, SubscriptionNotification::NewHead(_)));
+    }
+    
+    #[tokio::test]
+    async fn test_logs_subscription_filters_transfer_topic() {
+        let manager = SubscriptionManager::new();
+        manager.start().await;
+        
+        let transfer_topic = ethereum_crypto::keccak256(b"Transfer(address,address,uint256)");
+        let approval_topic = ethereum_crypto::keccak256(b"Approval(address,address,uint256)");
+        let token = Address::from_bytes([0xaa; 20]);
+        
+        let criteria = FilterCriteria {
+            address: Some(vec![token]),
+            topics: vec![Some(vec![transfer_topic])],
+            ..empty_criteria()
+        };
+        let logs = manager.subscribe(SubscriptionType::NewLogs { criteria }).await.unwrap();
+        let mut stream = Box::pin(manager.multiplex(vec![logs.id]));
+        
+        let transfer = Log::new(token, vec![transfer_topic, H256::zero(), H256::zero()], vec![]);
+        let approval = Log::new(token, vec![approval_topic, H256::zero(), H256::zero()], vec![]);
+        let other_token = Log::new(Address::from_bytes([0xbb; 20]), vec![transfer_topic], vec![]);
+        
+        manager.notify_new_logs(vec![approval, other_token, transfer.clone()]).await;
+        
+        let event = next_event(&mut stream).await;
+        match event.data {
+            SubscriptionNotification::Log(log) => assert_eq!(log, transfer),
+            other => panic!("unexpected notification: {:?}", other),
+        }
+        
+        // Nothing else matched the criteria
+        assert!(tokio::time::timeout(Duration::from_millis(100), stream.next()).await.is_err());
+    }
+}
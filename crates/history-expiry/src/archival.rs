@@ -1,5 +1,5 @@
 use ethereum_types::{H256, U256};
-use ethereum_core::Block;
+use ethereum_core::{Block, Receipt};
 use std::sync::{Arc, RwLock};
 use std::path::PathBuf;
 use tokio::fs;
@@ -19,6 +19,14 @@ pub trait ArchivalBackend: Send + Sync {
     /// Archive a batch of blocks
     async fn archive_blocks(&self, blocks: Vec<Block>) -> Result<String>;
     
+    /// Archive a batch of blocks with their receipts, `receipts[i]` being
+    /// those of `blocks[i]`
+    ///
+    /// Backends that only keep blocks drop the receipts.
+    async fn archive_blocks_with_receipts(&self, blocks: Vec<Block>, _receipts: Vec<Vec<Receipt>>) -> Result<String> {
+        self.archive_blocks(blocks).await
+    }
+    
     /// Retrieve archived blocks
     async fn retrieve_blocks(&self, archive_id: &str, block_range: std::ops::Range<u64>) -> Result<Vec<Block>>;
     
@@ -27,6 +35,16 @@ pub trait ArchivalBackend: Send + Sync {
     
     /// Verify archive integrity
     async fn verify_archive(&self, archive_id: &str) -> Result<bool>;
+    
+    /// Archive holding `block_number`, for backends that keep an index
+    async fn archive_containing(&self, _block_number: u64) -> Result<Option<String>> {
+        Ok(None)
+    }
+    
+    /// Archived block with `hash`, for backends that keep an index
+    async fn find_block(&self, _hash: H256) -> Result<Option<Block>> {
+        Ok(None)
+    }
 }
 
 #[derive(Debug, Clone)]
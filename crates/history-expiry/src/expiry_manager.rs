@@ -129,6 +129,18 @@ impl HistoryExpiryManager {
         })
     }
 
+    /// Sweep unreachable state trie nodes whenever expired history is pruned
+    pub fn with_state_pruner(
+        mut self,
+        db: Arc<dyn ethereum_storage::Database>,
+        pruner: Arc<std::sync::Mutex<ethereum_trie::TriePruner>>,
+    ) -> Result<Self> {
+        self.pruning_engine = Arc::new(
+            PruningEngine::new(self.storage.clone(), Default::default())?.with_state_pruner(db, pruner),
+        );
+        Ok(self)
+    }
+
     /// Start automatic history expiry
     pub async fn start(&self) -> Result<()> {
         if !self.config.auto_expiry {
@@ -394,14 +406,24 @@ impl HistoryExpiryManager {
 
         for chunk in block_numbers.chunks(100) {
             let mut blocks = Vec::new();
+            let mut receipts = Vec::new();
             
             for &block_num in chunk {
                 if let Ok(Some(block)) = self.storage.get_block_by_number(block_num) {
+                    // A block is only archived with its receipts
+                    match self.storage.get_receipts_by_number(block_num) {
+                        Ok(Some(block_receipts)) => receipts.push(block_receipts),
+                        Ok(None) if block.transactions.is_empty() => receipts.push(Vec::new()),
+                        _ => {
+                            warn!("Receipts of block {} are missing, not archiving it", block_num);
+                            continue;
+                        }
+                    }
                     blocks.push(block);
                 }
             }
 
-            match self.archival_backend.archive_blocks(blocks).await {
+            match self.archival_backend.archive_blocks_with_receipts(blocks, receipts).await {
                 Ok(_) => {
                     self.metrics.archival_success.fetch_add(
                         chunk.len() as u64,
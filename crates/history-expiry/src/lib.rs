@@ -3,12 +3,14 @@ pub mod archival;
 pub mod portal_integration;
 pub mod retrieval;
 pub mod pruning;
+pub mod segments;
 
 pub use expiry_manager::{HistoryExpiryManager, ExpiryConfig, ExpiryPolicy};
 pub use archival::{ArchivalBackend, ArchivalStrategy};
 pub use portal_integration::{PortalNetworkClient, HistoryDistribution};
 pub use retrieval::{HistoryRetriever, RetrievalStrategy};
 pub use pruning::{PruningEngine, PruningPolicy};
+pub use segments::{FileSystemStore, ObjectStore, S3Store, Segment, SegmentArchive};
 
 use thiserror::Error;
 
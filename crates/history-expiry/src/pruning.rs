@@ -1,6 +1,7 @@
 use ethereum_types::{H256, U256};
-use ethereum_storage::Storage;
-use std::sync::{Arc, RwLock};
+use ethereum_storage::{Database, Storage};
+use ethereum_trie::TriePruner;
+use std::sync::{Arc, Mutex, RwLock};
 use std::collections::HashSet;
 use tokio::sync::mpsc;
 use tracing::{info, debug, warn};
@@ -14,6 +15,13 @@ pub struct PruningEngine {
     policy: PruningPolicy,
     state: Arc<RwLock<PruningState>>,
     metrics: Arc<PruningMetrics>,
+    state_pruner: Option<StatePruner>,
+}
+
+/// State trie nodes swept along with expired history
+struct StatePruner {
+    db: Arc<dyn Database>,
+    pruner: Arc<Mutex<TriePruner>>,
 }
 
 #[derive(Debug, Clone)]
@@ -96,9 +104,21 @@ impl PruningEngine {
                 },
             })),
             metrics: Arc::new(PruningMetrics::new()),
+            state_pruner: None,
         })
     }
 
+    /// Also sweep the state trie nodes `pruner`'s retained roots no longer
+    /// reach each time history is pruned
+    ///
+    /// State commits record their roots with the same pruner through
+    /// `PatriciaTrie::commit_pruned`, so the sweep runs on this engine's
+    /// schedule instead of after every commit.
+    pub fn with_state_pruner(mut self, db: Arc<dyn Database>, pruner: Arc<Mutex<TriePruner>>) -> Self {
+        self.state_pruner = Some(StatePruner { db, pruner });
+        self
+    }
+
     /// Prune a list of blocks
     pub async fn prune_blocks(&self, block_numbers: Vec<u64>) -> Result<PruneResult> {
         // Check if already pruning
@@ -132,6 +152,11 @@ impl PruningEngine {
             }
         };
 
+        let result = match result {
+            Ok(res) => self.prune_state().map(|_| res),
+            Err(e) => Err(e),
+        };
+
         // Update state and metrics
         {
             let mut state = self.state.write().unwrap();
@@ -167,6 +192,21 @@ impl PruningEngine {
         result
     }
 
+    /// Sweep state trie nodes that are no longer reachable from a retained root
+    fn prune_state(&self) -> Result<usize> {
+        let state_pruner = match &self.state_pruner {
+            Some(state_pruner) => state_pruner,
+            None => return Ok(0),
+        };
+        let pruner = state_pruner.pruner.lock().unwrap();
+        let deleted = pruner.prune(&*state_pruner.db)
+            .map_err(|e| HistoryExpiryError::PruningError(format!("state trie: {}", e)))?;
+        if deleted > 0 {
+            info!("Pruned {} unreachable state trie nodes", deleted);
+        }
+        Ok(deleted)
+    }
+
     /// Conservative pruning with verification
     async fn prune_conservative(
         &self,
@@ -246,8 +246,7 @@ impl HistoryRetriever {
             let backend = self.archival_backend.clone();
             let hash = block_hash;
             tokio::spawn(async move {
-                // Simplified - would need proper archive ID lookup
-                backend.retrieve_blocks("archive_id", 0..1).await
+                backend.find_block(hash).await
             })
         };
         
@@ -263,9 +262,9 @@ impl HistoryRetriever {
         tokio::select! {
             archive_result = archive_handle => {
                 match archive_result {
-                    Ok(Ok(blocks)) if !blocks.is_empty() => {
+                    Ok(Ok(Some(block))) => {
                         self.metrics.archive_retrievals.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        Ok(Some(blocks.into_iter().next().unwrap()))
+                        Ok(Some(block))
                     }
                     _ => {
                         // Wait for network result
@@ -288,9 +287,9 @@ impl HistoryRetriever {
                     _ => {
                         // Wait for archive result
                         match archive_handle.await {
-                            Ok(Ok(blocks)) if !blocks.is_empty() => {
+                            Ok(Ok(Some(block))) => {
                                 self.metrics.archive_retrievals.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                Ok(Some(blocks.into_iter().next().unwrap()))
+                                Ok(Some(block))
                             }
                             _ => Ok(None)
                         }
@@ -327,11 +326,7 @@ impl HistoryRetriever {
 
     /// Retrieve from local archive
     async fn retrieve_from_archive(&self, block_hash: H256) -> Result<Option<Block>> {
-        // Simplified - would need proper archive ID and range lookup
-        match self.archival_backend.retrieve_blocks("archive_id", 0..1).await {
-            Ok(blocks) => Ok(blocks.into_iter().next()),
-            Err(e) => Err(e),
-        }
+        self.archival_backend.find_block(block_hash).await
     }
 
     /// Retrieve from Portal Network
@@ -341,8 +336,23 @@ impl HistoryRetriever {
 
     /// Retrieve range from archive
     async fn retrieve_range_from_archive(&self, start: u64, end: u64) -> Result<Vec<Block>> {
-        // Simplified - would need proper archive ID lookup
-        self.archival_backend.retrieve_blocks("archive_id", start..end).await
+        let mut blocks = Vec::new();
+        let mut next = start;
+        
+        // Walk the archives covering the range until one is missing
+        while next < end {
+            let Some(archive_id) = self.archival_backend.archive_containing(next).await? else {
+                break;
+            };
+            let archived = self.archival_backend.retrieve_blocks(&archive_id, next..end).await?;
+            match archived.last() {
+                Some(last) => next = last.header.number.as_u64() + 1,
+                None => break,
+            }
+            blocks.extend(archived);
+        }
+        
+        Ok(blocks)
     }
 
     /// Get block from cache
@@ -0,0 +1,545 @@
+use async_trait::async_trait;
+use ethereum_core::{Block, Receipt};
+use ethereum_crypto::keccak256;
+use ethereum_rlp::{Decoder, Encode, Encoder, RlpItem};
+use ethereum_types::H256;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::archival::{ArchivalBackend, ArchivalStats};
+use crate::{HistoryExpiryError, Result};
+
+/// Prefix of the index entries, one per segment numbered in archive order
+pub const INDEX_PREFIX: &str = "index/";
+
+/// Flat key-value storage that segments and their index are written to
+///
+/// Keys are generated by the archive and only contain ASCII letters, digits,
+/// `-`, `.` and `/`.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Store `data` under `key`, replacing what was there
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()>;
+
+    /// Object stored under `key`, `None` if there is none
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+}
+
+/// Objects as files below a root directory
+pub struct FileSystemStore {
+    root: PathBuf,
+}
+
+impl FileSystemStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for FileSystemStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let path = self.root.join(key);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await
+                .map_err(|e| HistoryExpiryError::ArchivalError(format!("Failed to create directory: {}", e)))?;
+        }
+
+        // Write next to the target and rename, a crash never leaves half an object behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, &data).await
+            .map_err(|e| HistoryExpiryError::ArchivalError(format!("Failed to write {}: {}", key, e)))?;
+        fs::rename(&tmp, &path).await
+            .map_err(|e| HistoryExpiryError::ArchivalError(format!("Failed to write {}: {}", key, e)))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.root.join(key)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(HistoryExpiryError::RetrievalError(format!("Failed to read {}: {}", key, e))),
+        }
+    }
+}
+
+/// Objects in a bucket of an S3-compatible service
+///
+/// Requests use path-style URLs (`<endpoint>/<bucket>/<key>`), which
+/// self-hosted services such as MinIO expect, and are signed with AWS
+/// signature version 4.
+pub struct S3Store {
+    client: reqwest::Client,
+    endpoint: String,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Store {
+    pub fn new(endpoint: String, bucket: String, region: String, access_key: String, secret_key: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket,
+            region,
+            access_key,
+            secret_key,
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, key: &str, payload: &[u8]) -> Result<reqwest::RequestBuilder> {
+        let path = format!("/{}/{}", self.bucket, key);
+        let url = reqwest::Url::parse(&format!("{}{}", self.endpoint, path))
+            .map_err(|e| HistoryExpiryError::ArchivalError(format!("Invalid S3 endpoint: {}", e)))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(HistoryExpiryError::ArchivalError("S3 endpoint has no host".into())),
+        };
+
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(payload));
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date, scope, hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut key = hmac_sha256(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        Ok(self.client
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.access_key, scope, signed_headers, signature
+                ),
+            ))
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[async_trait]
+impl ObjectStore for S3Store {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let response = self.request(reqwest::Method::PUT, key, &data)?
+            .body(data)
+            .send()
+            .await
+            .map_err(|e| HistoryExpiryError::ArchivalError(format!("Failed to upload {}: {}", key, e)))?;
+        if !response.status().is_success() {
+            return Err(HistoryExpiryError::ArchivalError(format!(
+                "Failed to upload {}: status {}", key, response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let response = self.request(reqwest::Method::GET, key, &[])?
+            .send()
+            .await
+            .map_err(|e| HistoryExpiryError::RetrievalError(format!("Failed to download {}: {}", key, e)))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(HistoryExpiryError::RetrievalError(format!(
+                "Failed to download {}: status {}", key, response.status()
+            )));
+        }
+        let data = response.bytes().await
+            .map_err(|e| HistoryExpiryError::RetrievalError(format!("Failed to download {}: {}", key, e)))?;
+        Ok(Some(data.to_vec()))
+    }
+}
+
+/// Consecutive blocks and their receipts, archived as one object
+///
+/// Encoded as `rlp([[block, [receipt, ...]], ...])` with every receipt in
+/// its EIP-2718 envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub blocks: Vec<Block>,
+    pub receipts: Vec<Vec<Receipt>>,
+}
+
+impl Segment {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut entries = Encoder::new();
+        for (block, receipts) in self.blocks.iter().zip(&self.receipts) {
+            let mut encoded_receipts = Encoder::new();
+            for receipt in receipts {
+                encoded_receipts.encode_bytes(&receipt.encoded_2718());
+            }
+
+            let mut entry = Encoder::new();
+            block.encode(&mut entry);
+            entry.encode_list_payload(&encoded_receipts.finish());
+            entries.encode_list_payload(&entry.finish());
+        }
+
+        let mut encoder = Encoder::new();
+        encoder.encode_list_payload(&entries.finish());
+        encoder.finish()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let invalid = |reason: String| HistoryExpiryError::RetrievalError(format!("Invalid segment: {}", reason));
+
+        let item = Decoder::new(data)
+            .map_err(|e| invalid(e.to_string()))?
+            .decode_item()
+            .map_err(|e| invalid(e.to_string()))?;
+        let entries = item.as_list().ok_or_else(|| invalid("expected a list".into()))?;
+
+        let mut segment = Segment { blocks: Vec::new(), receipts: Vec::new() };
+        for entry in entries {
+            let (block, receipts) = match entry.as_list() {
+                Some([block, RlpItem::List(receipts)]) => (block, receipts),
+                _ => return Err(invalid("expected [block, receipts]".into())),
+            };
+
+            segment.blocks.push(
+                ethereum_rlp::decode(&ethereum_rlp::encode(block)).map_err(|e| invalid(e.to_string()))?
+            );
+            segment.receipts.push(
+                receipts
+                    .iter()
+                    .map(|receipt| {
+                        let envelope = receipt.as_bytes().ok_or_else(|| invalid("expected a receipt envelope".into()))?;
+                        Receipt::decode_2718(envelope).map_err(|e| invalid(e.to_string()))
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            );
+        }
+        Ok(segment)
+    }
+}
+
+/// Where an archived segment lives and what it holds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentEntry {
+    pub key: String,
+    pub first: u64,
+    pub last: u64,
+    /// Keccak-256 of the stored object
+    pub checksum: H256,
+    pub size: u64,
+    /// Hashes of the blocks in order, `hashes[i]` is block `first + i`
+    pub hashes: Vec<H256>,
+}
+
+/// Archived segments by their first block
+#[derive(Debug, Clone, Default)]
+pub struct SegmentIndex {
+    segments: BTreeMap<u64, SegmentEntry>,
+    by_hash: HashMap<H256, u64>,
+}
+
+impl SegmentIndex {
+    fn insert(&mut self, entry: SegmentEntry) {
+        for (offset, hash) in entry.hashes.iter().enumerate() {
+            self.by_hash.insert(*hash, entry.first + offset as u64);
+        }
+        self.segments.insert(entry.first, entry);
+    }
+
+    /// Segment holding block `number`
+    pub fn find(&self, number: u64) -> Option<&SegmentEntry> {
+        self.segments
+            .range(..=number)
+            .next_back()
+            .map(|(_, entry)| entry)
+            .filter(|entry| entry.last >= number)
+    }
+
+    /// Number of the archived block with `hash`
+    pub fn number_of(&self, hash: &H256) -> Option<u64> {
+        self.by_hash.get(hash).copied()
+    }
+
+    fn overlaps(&self, first: u64, last: u64) -> bool {
+        self.segments
+            .range(..=last)
+            .next_back()
+            .map_or(false, |(_, entry)| entry.last >= first)
+    }
+}
+
+/// Archive of RLP segments in an object store
+///
+/// Every archived batch of blocks becomes one segment, the batch size is
+/// up to the caller. After every segment an index entry with its block
+/// range and checksum is added next to it, earlier entries are never
+/// rewritten, so a node can reopen the archive and find any archived block
+/// by number or hash. Segments are checked against the index checksum
+/// whenever they are read.
+pub struct SegmentArchive {
+    store: Arc<dyn ObjectStore>,
+    index: RwLock<SegmentIndex>,
+}
+
+impl SegmentArchive {
+    /// Open the archive in `store`, loading the index entries written so far
+    pub async fn open(store: Arc<dyn ObjectStore>) -> Result<Self> {
+        let mut index = SegmentIndex::default();
+        while let Some(data) = store.get(&index_key(index.segments.len())).await? {
+            let entry: SegmentEntry = bincode::deserialize(&data)
+                .map_err(|e| HistoryExpiryError::RetrievalError(format!("Invalid segment index entry: {}", e)))?;
+            index.insert(entry);
+        }
+        Ok(Self {
+            store,
+            index: RwLock::new(index),
+        })
+    }
+
+    /// Archive consecutive `blocks` with their `receipts` as one segment,
+    /// returning its key
+    pub async fn archive_segment(&self, blocks: Vec<Block>, receipts: Vec<Vec<Receipt>>) -> Result<String> {
+        let (first, last) = match (blocks.first(), blocks.last()) {
+            (Some(first), Some(last)) => (first.header.number.as_u64(), last.header.number.as_u64()),
+            _ => return Err(HistoryExpiryError::ArchivalError("No blocks to archive".into())),
+        };
+        if receipts.len() != blocks.len() {
+            return Err(HistoryExpiryError::ArchivalError(format!(
+                "{} blocks but {} receipt lists", blocks.len(), receipts.len()
+            )));
+        }
+        if blocks.iter().enumerate().any(|(i, block)| block.header.number.as_u64() != first + i as u64) {
+            return Err(HistoryExpiryError::ArchivalError("Blocks are not consecutive".into()));
+        }
+
+        let mut index = self.index.write().await;
+        if index.overlaps(first, last) {
+            return Err(HistoryExpiryError::ArchivalError(format!(
+                "Blocks {}-{} overlap an archived segment", first, last
+            )));
+        }
+
+        let hashes = blocks.iter().map(|block| block.header.hash()).collect();
+        let data = Segment { blocks, receipts }.encode();
+        let key = format!("segments/{:012}-{:012}.rlp", first, last);
+        let entry = SegmentEntry {
+            key: key.clone(),
+            first,
+            last,
+            checksum: H256(keccak256(&data)),
+            size: data.len() as u64,
+            hashes,
+        };
+        self.store.put(&key, data).await?;
+
+        // The segment is only reachable once the index mentions it
+        let encoded = bincode::serialize(&entry)
+            .map_err(|e| HistoryExpiryError::ArchivalError(format!("Serialization failed: {}", e)))?;
+        self.store.put(&index_key(index.segments.len()), encoded).await?;
+        index.insert(entry);
+
+        info!("Archived blocks {}-{} to {}", first, last, key);
+        Ok(key)
+    }
+
+    /// Archived block `number` with its receipts
+    pub async fn block(&self, number: u64) -> Result<Option<(Block, Vec<Receipt>)>> {
+        let Some(entry) = self.index.read().await.find(number).cloned() else {
+            return Ok(None);
+        };
+        let segment = self.read_segment(&entry).await?;
+
+        let offset = (number - entry.first) as usize;
+        Ok(segment.blocks.into_iter().zip(segment.receipts).nth(offset))
+    }
+
+    /// Read the segment behind `entry`, failing if it doesn't match its checksum
+    async fn read_segment(&self, entry: &SegmentEntry) -> Result<Segment> {
+        let data = self.store.get(&entry.key).await?.ok_or_else(|| {
+            HistoryExpiryError::RetrievalError(format!("Segment {} is missing", entry.key))
+        })?;
+        if H256(keccak256(&data)) != entry.checksum {
+            return Err(HistoryExpiryError::RetrievalError(format!("Checksum mismatch in {}", entry.key)));
+        }
+        Segment::decode(&data)
+    }
+
+    async fn entry(&self, archive_id: &str) -> Result<SegmentEntry> {
+        self.index.read().await
+            .segments
+            .values()
+            .find(|entry| entry.key == archive_id)
+            .cloned()
+            .ok_or_else(|| HistoryExpiryError::RetrievalError("Archive not found".into()))
+    }
+}
+
+fn index_key(position: usize) -> String {
+    format!("{}{:08}", INDEX_PREFIX, position)
+}
+
+#[async_trait]
+impl ArchivalBackend for SegmentArchive {
+    async fn archive_blocks(&self, _blocks: Vec<Block>) -> Result<String> {
+        Err(HistoryExpiryError::ArchivalError("Segments are archived with their receipts".into()))
+    }
+
+    async fn archive_blocks_with_receipts(&self, blocks: Vec<Block>, receipts: Vec<Vec<Receipt>>) -> Result<String> {
+        self.archive_segment(blocks, receipts).await
+    }
+
+    async fn retrieve_blocks(&self, archive_id: &str, block_range: std::ops::Range<u64>) -> Result<Vec<Block>> {
+        let segment = self.read_segment(&self.entry(archive_id).await?).await?;
+        Ok(segment.blocks
+            .into_iter()
+            .filter(|block| block_range.contains(&block.header.number.as_u64()))
+            .collect())
+    }
+
+    async fn get_stats(&self) -> Result<ArchivalStats> {
+        let index = self.index.read().await;
+        Ok(ArchivalStats {
+            total_archives: index.segments.len(),
+            total_blocks: index.segments.values().map(|entry| entry.last - entry.first + 1).sum(),
+            total_size: index.segments.values().map(|entry| entry.size).sum(),
+            compression_ratio: 1.0,
+            oldest_archive: None,
+            newest_archive: None,
+        })
+    }
+
+    async fn verify_archive(&self, archive_id: &str) -> Result<bool> {
+        let entry = self.entry(archive_id).await?;
+        Ok(match self.store.get(&entry.key).await? {
+            Some(data) => H256(keccak256(&data)) == entry.checksum,
+            None => false,
+        })
+    }
+
+    async fn archive_containing(&self, block_number: u64) -> Result<Option<String>> {
+        Ok(self.index.read().await.find(block_number).map(|entry| entry.key.clone()))
+    }
+
+    async fn find_block(&self, hash: H256) -> Result<Option<Block>> {
+        let Some(number) = self.index.read().await.number_of(&hash) else {
+            return Ok(None);
+        };
+        Ok(self.block(number).await?.map(|(block, _)| block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::{Header, Log};
+    use ethereum_types::{Address, Bloom, U256};
+
+    fn block(number: u64) -> Block {
+        let mut header = Header::new();
+        header.number = U256::from(number);
+        header.timestamp = 1_000 + number * 12;
+        Block::new(header)
+    }
+
+    fn receipts(number: u64) -> Vec<Receipt> {
+        vec![Receipt {
+            tx_type: 2,
+            status: 1,
+            cumulative_gas_used: U256::from(21_000 * number),
+            logs_bloom: Bloom::default(),
+            logs: vec![Log::new(Address::from_bytes([number as u8; 20]), vec![H256::repeat_byte(1)], vec![0xab])],
+            gas_used: U256::zero(),
+            contract_address: None,
+        }]
+    }
+
+    async fn archive_range(dir: &std::path::Path, range: std::ops::RangeInclusive<u64>) -> SegmentArchive {
+        let archive = SegmentArchive::open(Arc::new(FileSystemStore::new(dir))).await.unwrap();
+        archive
+            .archive_segment(range.clone().map(block).collect(), range.map(receipts).collect())
+            .await
+            .unwrap();
+        archive
+    }
+
+    #[tokio::test]
+    async fn test_archived_block_is_retrieved_from_reopened_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = archive_range(dir.path(), 100..=199).await.archive_containing(150).await.unwrap().unwrap();
+
+        let archive = SegmentArchive::open(Arc::new(FileSystemStore::new(dir.path()))).await.unwrap();
+        assert_eq!(archive.archive_containing(150).await.unwrap(), Some(key.clone()));
+        assert_eq!(archive.archive_containing(200).await.unwrap(), None);
+        assert!(archive.verify_archive(&key).await.unwrap());
+
+        let (archived, archived_receipts) = archive.block(150).await.unwrap().unwrap();
+        assert_eq!(archived, block(150));
+        assert_eq!(archived_receipts, receipts(150));
+        assert_eq!(archive.find_block(block(120).header.hash()).await.unwrap(), Some(block(120)));
+        assert_eq!(archive.retrieve_blocks(&key, 198..250).await.unwrap(), vec![block(198), block(199)]);
+
+        // Overlapping ranges are rejected, and so are blocks without receipts
+        let overlapping = archive.archive_blocks_with_receipts((190..210).map(block).collect(), (190..210).map(receipts).collect());
+        assert!(overlapping.await.is_err());
+        assert!(archive.archive_blocks((300..310).map(block).collect()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_index_is_appended_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = archive_range(dir.path(), 0..=9).await;
+        let first_entry = std::fs::read(dir.path().join(index_key(0))).unwrap();
+
+        archive.archive_blocks_with_receipts((10..20).map(block).collect(), (10..20).map(receipts).collect()).await.unwrap();
+        assert_eq!(std::fs::read(dir.path().join(index_key(0))).unwrap(), first_entry);
+        assert!(dir.path().join(index_key(1)).exists());
+
+        let reopened = SegmentArchive::open(Arc::new(FileSystemStore::new(dir.path()))).await.unwrap();
+        assert_eq!(reopened.block(5).await.unwrap().unwrap().1, receipts(5));
+        assert_eq!(reopened.block(15).await.unwrap().unwrap().1, receipts(15));
+        assert_eq!(reopened.get_stats().await.unwrap().total_archives, 2);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_segment_fails_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = archive_range(dir.path(), 0..=9).await;
+        let key = archive.archive_containing(5).await.unwrap().unwrap();
+
+        let path = dir.path().join(&key);
+        let mut data = std::fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        std::fs::write(&path, data).unwrap();
+
+        assert!(!archive.verify_archive(&key).await.unwrap());
+        assert!(matches!(archive.block(5).await, Err(HistoryExpiryError::RetrievalError(_))));
+    }
+}
@@ -115,13 +115,10 @@ impl BundleTransaction {
         self.transaction.gas_limit().as_u64()
     }
     
+    /// Tip paid to the builder per gas at `base_fee`, capped by the
+    /// transaction's max priority fee
     pub fn effective_tip(&self, base_fee: U256) -> U256 {
-        let max_fee = self.transaction.gas_price();
-        if max_fee > base_fee {
-            max_fee - base_fee
-        } else {
-            U256::zero()
-        }
+        self.transaction.effective_tip(base_fee)
     }
     
     pub fn sender(&self) -> Result<Address> {
@@ -217,7 +214,7 @@ impl BundlePool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ethereum_core::LegacyTransaction;
+    use ethereum_core::{Eip1559Transaction, LegacyTransaction};
     
     #[test]
     fn test_bundle_creation() {
@@ -241,6 +238,29 @@ mod tests {
         assert_eq!(bundle.max_block, 101);
     }
     
+    #[test]
+    fn test_effective_tip_is_capped_by_priority_fee() {
+        let tx = Transaction::Eip1559(Eip1559Transaction {
+            chain_id: 1,
+            nonce: U256::zero(),
+            max_priority_fee_per_gas: U256::from(2),
+            max_fee_per_gas: U256::from(100),
+            gas_limit: U256::from(21_000),
+            to: Some(Address::zero()),
+            value: U256::zero(),
+            data: ethereum_types::Bytes::new(),
+            access_list: Vec::new(),
+            y_parity: false,
+            r: U256::zero(),
+            s: U256::zero(),
+        });
+        let bundle_tx = BundleTransaction::new(tx);
+        
+        assert_eq!(bundle_tx.effective_tip(U256::from(10)), U256::from(2));
+        assert_eq!(bundle_tx.effective_tip(U256::from(99)), U256::one());
+        assert_eq!(bundle_tx.effective_tip(U256::from(120)), U256::zero());
+    }
+    
     #[test]
     fn test_bundle_validation() {
         let bundle = Bundle::new(vec![], 100);
@@ -4,6 +4,12 @@ use tokio::sync::RwLock;
 use tokio::time::{interval, Duration};
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::{debug, warn};
+
+use crate::MonitorError;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AlertConfig {
@@ -67,6 +73,9 @@ pub struct Alert {
     pub category: String,
     pub message: String,
     pub details: HashMap<String, String>,
+    /// Observed value that triggered the alert, if it is metric based
+    pub value: Option<f64>,
+    pub threshold: Option<f64>,
     pub timestamp: DateTime<Utc>,
     pub resolved: bool,
     pub resolved_at: Option<DateTime<Utc>>,
@@ -80,6 +89,8 @@ impl Alert {
             category,
             message,
             details: HashMap::new(),
+            value: None,
+            threshold: None,
             timestamp: Utc::now(),
             resolved: false,
             resolved_at: None,
@@ -91,12 +102,130 @@ impl Alert {
         self
     }
     
+    pub fn with_value(mut self, value: f64, threshold: f64) -> Self {
+        self.value = Some(value);
+        self.threshold = Some(threshold);
+        self
+    }
+    
     pub fn resolve(&mut self) {
         self.resolved = true;
         self.resolved_at = Some(Utc::now());
     }
 }
 
+/// Destination alerts are delivered to
+#[async_trait]
+pub trait AlertChannel: Send + Sync {
+    async fn send_alert(&self, alert: &Alert) -> crate::Result<()>;
+}
+
+/// Number of retries after the first failed delivery
+const WEBHOOK_MAX_RETRIES: u32 = 3;
+
+/// JSON body posted by `WebhookAlertChannel`
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    name: &'a str,
+    level: AlertLevel,
+    value: Option<f64>,
+    threshold: Option<f64>,
+    message: &'a str,
+    timestamp: String,
+}
+
+/// Posts alerts as JSON to an HTTP endpoint
+///
+/// When a secret is configured the body is signed with HMAC-SHA256 and the
+/// hex digest is sent in the `X-Alert-Signature` header. Failed deliveries
+/// are retried with exponential backoff (1s, 2s, 4s).
+pub struct WebhookAlertChannel {
+    pub url: String,
+    pub secret: Option<String>,
+    pub client: reqwest::Client,
+    retry_delay: Duration,
+}
+
+impl WebhookAlertChannel {
+    pub fn new(url: String, secret: Option<String>) -> Self {
+        Self {
+            url,
+            secret,
+            client: reqwest::Client::new(),
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+    
+    /// Override the initial backoff delay
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+    
+    fn signature(&self, body: &[u8]) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(body);
+        Some(hex::encode(mac.finalize().into_bytes()))
+    }
+    
+    async fn post(&self, body: &[u8], signature: Option<&str>) -> std::result::Result<(), String> {
+        let mut request = self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        if let Some(signature) = signature {
+            request = request.header("X-Alert-Signature", signature);
+        }
+        
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", response.status()))
+        }
+    }
+}
+
+#[async_trait]
+impl AlertChannel for WebhookAlertChannel {
+    async fn send_alert(&self, alert: &Alert) -> crate::Result<()> {
+        let payload = WebhookPayload {
+            name: &alert.category,
+            level: alert.level,
+            value: alert.value,
+            threshold: alert.threshold,
+            message: &alert.message,
+            timestamp: alert.timestamp.to_rfc3339(),
+        };
+        let body = serde_json::to_vec(&payload)
+            .map_err(|e| MonitorError::AlertDeliveryFailed(e.to_string()))?;
+        let signature = self.signature(&body);
+        
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match self.post(&body, signature.as_deref()).await {
+                Ok(()) => {
+                    debug!("Delivered alert {} to webhook {}", alert.id, self.url);
+                    return Ok(());
+                }
+                Err(e) if attempt < WEBHOOK_MAX_RETRIES => {
+                    warn!("Webhook {} failed for alert {}: {}, retrying in {:?}", self.url, alert.id, e, delay);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    warn!("Giving up on webhook {} for alert {}: {}", self.url, alert.id, e);
+                    return Err(MonitorError::AlertDeliveryFailed(format!("{}: {}", self.url, e)));
+                }
+            }
+        }
+    }
+}
+
 /// Alert manager for monitoring and sending alerts
 pub struct AlertManager {
     config: AlertConfig,
@@ -104,11 +233,18 @@ pub struct AlertManager {
     alert_history: Arc<RwLock<Vec<Alert>>>,
     last_alert_times: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
     check_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    channels: Vec<Arc<dyn AlertChannel>>,
 }
 
 impl AlertManager {
     pub fn new(config: AlertConfig) -> Self {
+        let channels = config.webhooks
+            .iter()
+            .map(|url| Arc::new(WebhookAlertChannel::new(url.clone(), None)) as Arc<dyn AlertChannel>)
+            .collect();
+        
         Self {
+            channels,
             config,
             active_alerts: Arc::new(RwLock::new(HashMap::new())),
             alert_history: Arc::new(RwLock::new(Vec::new())),
@@ -117,6 +253,11 @@ impl AlertManager {
         }
     }
     
+    /// Deliver alerts to an additional channel
+    pub fn add_channel(&mut self, channel: Arc<dyn AlertChannel>) {
+        self.channels.push(channel);
+    }
+    
     /// Start the alert manager
     pub async fn start(&self) -> crate::Result<()> {
         if !self.config.enabled {
@@ -213,9 +354,11 @@ impl AlertManager {
     
     /// Send alert notifications
     async fn send_notifications(&self, alert: &Alert) -> crate::Result<()> {
-        // Send webhook notifications
-        for webhook_url in &self.config.webhooks {
-            self.send_webhook(webhook_url, alert).await?;
+        // A failing channel must not keep the alert from the others
+        for channel in &self.channels {
+            if let Err(e) = channel.send_alert(alert).await {
+                warn!("Alert {} not delivered: {}", alert.id, e);
+            }
         }
         
         // Send email notifications (would need email service integration)
@@ -226,24 +369,6 @@ impl AlertManager {
         Ok(())
     }
     
-    /// Send webhook notification
-    async fn send_webhook(&self, url: &str, alert: &Alert) -> crate::Result<()> {
-        // Webhook payload
-        let payload = serde_json::json!({
-            "id": alert.id,
-            "level": alert.level,
-            "category": alert.category,
-            "message": alert.message,
-            "details": alert.details,
-            "timestamp": alert.timestamp.to_rfc3339(),
-        });
-        
-        // In production, would use reqwest or similar to send HTTP POST
-        tracing::info!("Would send webhook to {}: {}", url, payload);
-        
-        Ok(())
-    }
-    
     /// Resolve an alert
     pub async fn resolve_alert(&self, alert_id: &str) -> crate::Result<()> {
         let mut alerts = self.active_alerts.write().await;
@@ -282,7 +407,9 @@ impl AlertManager {
                 AlertLevel::Warning,
                 "system.cpu".to_string(),
                 format!("High CPU usage: {:.1}%", metrics.cpu_usage),
-            ).with_detail("cpu_usage".to_string(), format!("{:.1}", metrics.cpu_usage));
+            )
+            .with_detail("cpu_usage".to_string(), format!("{:.1}", metrics.cpu_usage))
+            .with_value(metrics.cpu_usage, self.config.thresholds.high_cpu_percent);
             
             let _ = self.trigger_alert(alert).await;
         }
@@ -294,7 +421,9 @@ impl AlertManager {
                 AlertLevel::Warning,
                 "system.memory".to_string(),
                 format!("High memory usage: {:.1}%", memory_percent),
-            ).with_detail("memory_percent".to_string(), format!("{:.1}", memory_percent));
+            )
+            .with_detail("memory_percent".to_string(), format!("{:.1}", memory_percent))
+            .with_value(memory_percent, self.config.thresholds.high_memory_percent);
             
             let _ = self.trigger_alert(alert).await;
         }
@@ -409,4 +538,76 @@ mod tests {
         let active = manager.get_active_alerts().await;
         assert_eq!(active.len(), 1);
     }
-}
\ No newline at end of file
+    
+    /// Minimal HTTP endpoint answering with the given status codes in order
+    /// and handing back every request it received
+    async fn webhook_server(statuses: Vec<u16>) -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some(header_end) = text.find("\r\n\r\n") {
+                        let length = text.lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if request.len() >= header_end + 4 + length {
+                            break;
+                        }
+                    }
+                }
+                tx.send(String::from_utf8_lossy(&request).to_string()).unwrap();
+                let response = format!("HTTP/1.1 {} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n", status);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        
+        (url, rx)
+    }
+    
+    #[tokio::test]
+    async fn test_webhook_posts_signed_payload() {
+        let (url, mut requests) = webhook_server(vec![200]).await;
+        let channel = WebhookAlertChannel::new(url, Some("topsecret".to_string()));
+        
+        let alert = Alert::new(AlertLevel::Critical, "system.cpu".to_string(), "High CPU usage: 97.0%".to_string())
+            .with_value(97.0, 90.0);
+        channel.send_alert(&alert).await.unwrap();
+        
+        let request = requests.recv().await.unwrap();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["name"], "system.cpu");
+        assert_eq!(payload["level"], "Critical");
+        assert_eq!(payload["value"], 97.0);
+        assert_eq!(payload["threshold"], 90.0);
+        assert_eq!(payload["message"], "High CPU usage: 97.0%");
+        
+        let expected = channel.signature(body.as_bytes()).unwrap();
+        assert!(head.to_ascii_lowercase().contains(&format!("x-alert-signature: {}", expected)));
+    }
+    
+    #[tokio::test]
+    async fn test_webhook_retries_with_backoff() {
+        let (url, mut requests) = webhook_server(vec![500, 503, 200]).await;
+        let channel = WebhookAlertChannel::new(url, None).with_retry_delay(Duration::from_millis(10));
+        
+        let alert = Alert::new(AlertLevel::Warning, "network.peers".to_string(), "Low peer count: 1".to_string());
+        channel.send_alert(&alert).await.unwrap();
+        
+        for _ in 0..3 {
+            let request = requests.recv().await.unwrap();
+            assert!(!request.to_ascii_lowercase().contains("x-alert-signature"));
+        }
+    }
+}
@@ -1,8 +1,9 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::task::JoinHandle;
 use tokio::time;
-use sysinfo::{System, SystemExt, ProcessExt, DiskExt, CpuExt};
+use sysinfo::{Disks, System};
 use chrono::{DateTime, Utc};
 
 use crate::{Metrics, Result, MonitorError};
@@ -49,6 +50,8 @@ pub struct MetricsCollector {
     metrics: Arc<Metrics>,
     system: System,
     collection_interval: Duration,
+    /// Directory whose size is reported as the datadir disk usage
+    datadir: Option<PathBuf>,
     collector_handle: Option<JoinHandle<()>>,
     shutdown_tx: Option<tokio::sync::oneshot::Sender<()>>,
 }
@@ -59,6 +62,7 @@ impl MetricsCollector {
             metrics,
             system: System::new_all(),
             collection_interval: Duration::from_secs(10),
+            datadir: None,
             collector_handle: None,
             shutdown_tx: None,
         }
@@ -68,12 +72,20 @@ impl MetricsCollector {
         Self {
             metrics,
             system: System::new_all(),
-            collection_interval: Duration::from_secs(interval_secs),
+            // A zero period would make the collection loop panic
+            collection_interval: Duration::from_secs(interval_secs.max(1)),
+            datadir: None,
             collector_handle: None,
             shutdown_tx: None,
         }
     }
     
+    /// Report the size of `datadir` along with the host resources
+    pub fn with_datadir(mut self, datadir: impl Into<PathBuf>) -> Self {
+        self.datadir = Some(datadir.into());
+        self
+    }
+    
     /// Start the metrics collection loop
     pub async fn start(&mut self) -> Result<()> {
         if self.collector_handle.is_some() {
@@ -85,6 +97,7 @@ impl MetricsCollector {
         
         let metrics = self.metrics.clone();
         let interval = self.collection_interval;
+        let datadir = self.datadir.clone();
         
         let handle = tokio::spawn(async move {
             let mut interval_timer = time::interval(interval);
@@ -93,7 +106,7 @@ impl MetricsCollector {
             loop {
                 tokio::select! {
                     _ = interval_timer.tick() => {
-                        Self::collect_metrics(&metrics, &mut system).await;
+                        Self::collect_metrics(&metrics, &mut system, datadir.as_deref());
                     }
                     _ = &mut shutdown_rx => {
                         break;
@@ -117,8 +130,16 @@ impl MetricsCollector {
         }
     }
     
+    /// Run one collection tick outside the background loop
+    pub fn collect_once(&mut self) {
+        Self::collect_metrics(&self.metrics, &mut self.system, self.datadir.as_deref());
+    }
+    
     /// Collect all metrics once
-    async fn collect_metrics(metrics: &Arc<Metrics>, system: &mut System) {
+    ///
+    /// A source that can't be sampled is logged and skipped, the gauges
+    /// keep their previous value until the next tick.
+    fn collect_metrics(metrics: &Arc<Metrics>, system: &mut System, datadir: Option<&Path>) {
         // Refresh system information
         system.refresh_all();
         
@@ -126,7 +147,16 @@ impl MetricsCollector {
         Self::collect_system_metrics(metrics, system);
         
         // Collect process metrics
-        Self::collect_process_metrics(metrics, system);
+        if let Err(e) = Self::collect_process_metrics(metrics, system) {
+            tracing::warn!("Failed to sample process metrics: {}", e);
+        }
+        
+        if let Some(datadir) = datadir {
+            match directory_size(datadir) {
+                Ok(size) => metrics.datadir_size.set(size as i64),
+                Err(e) => tracing::warn!("Failed to measure data directory {}: {}", datadir.display(), e),
+            }
+        }
     }
     
     /// Collect system-wide metrics
@@ -140,11 +170,9 @@ impl MetricsCollector {
         metrics.system_memory_usage.set(used_memory as i64);
         
         // Disk usage
-        for disk in system.disks() {
+        for disk in Disks::new_with_refreshed_list().list() {
             let mount_point = disk.mount_point().to_string_lossy();
-            let total_space = disk.total_space();
-            let available_space = disk.available_space();
-            let used_space = total_space - available_space;
+            let used_space = disk.total_space().saturating_sub(disk.available_space());
             
             metrics.system_disk_usage
                 .with_label_values(&[&mount_point])
@@ -153,31 +181,26 @@ impl MetricsCollector {
     }
     
     /// Collect process-specific metrics
-    fn collect_process_metrics(metrics: &Arc<Metrics>, system: &System) {
-        let pid = sysinfo::get_current_pid().ok();
+    fn collect_process_metrics(metrics: &Arc<Metrics>, system: &System) -> Result<()> {
+        let pid = sysinfo::get_current_pid()
+            .map_err(|e| MonitorError::MetricsError(e.to_string()))?;
+        let process = system.process(pid)
+            .ok_or_else(|| MonitorError::MetricsError(format!("process {} not found", pid)))?;
         
-        if let Some(pid) = pid {
-            if let Some(process) = system.process(pid) {
-                // CPU usage
-                metrics.process_cpu_usage.set(process.cpu_usage() as f64);
-                
-                // Memory usage
-                metrics.process_memory_usage.set(process.memory() as i64 * 1024);
-                
-                // Thread count
-                // Note: sysinfo doesn't directly provide thread count
-                // This would need platform-specific implementation
-                
-                // Open file descriptors (Linux-specific)
-                #[cfg(target_os = "linux")]
-                {
-                    if let Ok(fds) = std::fs::read_dir(format!("/proc/{}/fd", pid)) {
-                        let count = fds.count();
-                        metrics.process_open_fds.set(count as i64);
-                    }
-                }
-            }
+        // CPU usage, zero until a second refresh gives it a baseline
+        metrics.process_cpu_usage.set(process.cpu_usage() as f64);
+        
+        // Resident memory
+        metrics.process_memory_usage.set(process.memory() as i64);
+        
+        // Open file descriptors (Linux-specific)
+        #[cfg(target_os = "linux")]
+        {
+            let fds = std::fs::read_dir(format!("/proc/{}/fd", pid))?;
+            metrics.process_open_fds.set(fds.count() as i64);
         }
+        
+        Ok(())
     }
     
     /// Get current system metrics snapshot
@@ -188,12 +211,13 @@ impl MetricsCollector {
         let memory_used = self.system.used_memory();
         let memory_total = self.system.total_memory();
         
-        let disk_usage: Vec<DiskMetrics> = self.system.disks()
+        let disk_usage: Vec<DiskMetrics> = Disks::new_with_refreshed_list()
+            .list()
             .iter()
             .map(|disk| {
                 let total = disk.total_space();
                 let available = disk.available_space();
-                let used = total - available;
+                let used = total.saturating_sub(available);
                 DiskMetrics {
                     mount_point: disk.mount_point().to_string_lossy().to_string(),
                     used_bytes: used,
@@ -231,8 +255,8 @@ impl MetricsCollector {
                 
                 return ProcessMetrics {
                     cpu_usage: process.cpu_usage(),
-                    memory_bytes: process.memory() * 1024,
-                    virtual_memory_bytes: process.virtual_memory() * 1024,
+                    memory_bytes: process.memory(),
+                    virtual_memory_bytes: process.virtual_memory(),
                     thread_count: 0, // Would need platform-specific implementation
                     open_files,
                 };
@@ -312,4 +336,18 @@ impl MetricsCollector {
             self.metrics.evm_reverts.inc();
         }
     }
-}
\ No newline at end of file
+}
+
+/// Total size of the files under `path`, symlinks are not followed
+fn directory_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        size += directory_size(&entry?.path())?;
+    }
+    Ok(size)
+}
@@ -3,6 +3,7 @@ pub mod collector;
 pub mod server;
 pub mod health;
 pub mod alerts;
+pub mod log_metrics;
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -13,7 +14,8 @@ pub use metrics::{Metrics, MetricsConfig};
 pub use collector::{MetricsCollector, SystemMetrics};
 pub use server::{MetricsServer, MetricsServerConfig};
 pub use health::{HealthCheck, HealthStatus, ComponentHealth};
-pub use alerts::{AlertManager, Alert, AlertLevel};
+pub use alerts::{AlertManager, Alert, AlertLevel, AlertChannel, WebhookAlertChannel};
+pub use log_metrics::LogMetricsLayer;
 
 #[derive(Error, Debug)]
 pub enum MonitorError {
@@ -31,6 +33,9 @@ pub enum MonitorError {
     
     #[error("Health check failed: {0}")]
     HealthCheckFailed(String),
+    
+    #[error("Alert delivery failed: {0}")]
+    AlertDeliveryFailed(String),
 }
 
 pub type Result<T> = std::result::Result<T, MonitorError>;
@@ -41,6 +46,7 @@ pub struct Monitor {
     collector: Arc<RwLock<MetricsCollector>>,
     health_check: Arc<HealthCheck>,
     alert_manager: Arc<AlertManager>,
+    log_metrics: LogMetricsLayer,
     registry: Registry,
 }
 
@@ -48,15 +54,21 @@ impl Monitor {
     pub fn new(config: MetricsConfig) -> Result<Self> {
         let registry = Registry::new();
         let metrics = Arc::new(Metrics::new(&registry)?);
-        let collector = Arc::new(RwLock::new(MetricsCollector::new(metrics.clone())));
+        let mut collector = MetricsCollector::with_interval(metrics.clone(), config.collection_interval_secs);
+        if let Some(datadir) = config.datadir {
+            collector = collector.with_datadir(datadir);
+        }
+        let collector = Arc::new(RwLock::new(collector));
         let health_check = Arc::new(HealthCheck::new());
         let alert_manager = Arc::new(AlertManager::new(config.alert_config));
+        let log_metrics = LogMetricsLayer::new(&registry)?;
         
         Ok(Self {
             metrics,
             collector,
             health_check,
             alert_manager,
+            log_metrics,
             registry,
         })
     }
@@ -126,4 +138,10 @@ impl Monitor {
     pub fn alert_manager(&self) -> Arc<AlertManager> {
         self.alert_manager.clone()
     }
+    
+    /// Layer to install in the node's tracing subscriber so that logged
+    /// warnings and errors show up in this monitor's metrics
+    pub fn log_metrics_layer(&self) -> LogMetricsLayer {
+        self.log_metrics.clone()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,78 @@
+use prometheus::{IntCounterVec, Opts, Registry};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::Result;
+
+/// Tracing layer counting WARN and ERROR events per module
+///
+/// Every `warn!`/`error!` already in the code base increments
+/// `ethereum_log_events_total{target, level}`, so error rates can be
+/// graphed without instrumenting call sites by hand. Events below WARN pass
+/// through untouched.
+#[derive(Clone)]
+pub struct LogMetricsLayer {
+    events: IntCounterVec,
+}
+
+impl LogMetricsLayer {
+    pub fn new(registry: &Registry) -> Result<Self> {
+        let events = IntCounterVec::new(
+            Opts::new("ethereum_log_events_total", "Warnings and errors logged, by module"),
+            &["target", "level"]
+        )?;
+        registry.register(Box::new(events.clone()))?;
+
+        Ok(Self { events })
+    }
+
+    /// Events of `level` logged so far by `target`
+    pub fn count(&self, target: &str, level: Level) -> u64 {
+        self.events.with_label_values(&[target, level.as_str()]).get()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogMetricsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        // Levels compare by verbosity, so WARN and ERROR are the ones <= WARN
+        if *metadata.level() > Level::WARN {
+            return;
+        }
+
+        self.events
+            .with_label_values(&[metadata.target(), metadata.level().as_str()])
+            .inc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_counts_warnings_and_errors_per_module() {
+        let registry = Registry::new();
+        let layer = LogMetricsLayer::new(&registry).unwrap();
+
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!(target: "ethereum_sync::reorg", "missing parent");
+            tracing::error!(target: "ethereum_sync::reorg", "missing parent");
+            tracing::warn!(target: "ethereum_network", "peer timed out");
+            tracing::info!(target: "ethereum_network", "peer connected");
+            tracing::debug!(target: "ethereum_sync::reorg", "nothing to do");
+        });
+
+        // Exported through the registry it was created with, one series per
+        // module and level that logged
+        let families = registry.gather();
+        let family = families.iter().find(|family| family.get_name() == "ethereum_log_events_total").unwrap();
+        assert_eq!(family.get_metric().len(), 2);
+
+        assert_eq!(layer.count("ethereum_sync::reorg", Level::ERROR), 2);
+        assert_eq!(layer.count("ethereum_network", Level::WARN), 1);
+        assert_eq!(layer.count("ethereum_network", Level::INFO), 0);
+    }
+}
@@ -3,6 +3,7 @@ use prometheus::{
     HistogramOpts, Opts, Registry, IntCounter, IntGauge,
 };
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::RwLock;
 use serde::{Serialize, Deserialize};
 
@@ -14,6 +15,9 @@ pub struct MetricsConfig {
     pub enable_system_metrics: bool,
     pub collection_interval_secs: u64,
     pub retention_hours: u64,
+    /// Node data directory whose size is sampled with the host resources
+    #[serde(default)]
+    pub datadir: Option<PathBuf>,
     pub alert_config: crate::alerts::AlertConfig,
 }
 
@@ -24,6 +28,7 @@ impl Default for MetricsConfig {
             enable_system_metrics: true,
             collection_interval_secs: 10,
             retention_hours: 24,
+            datadir: None,
             alert_config: Default::default(),
         }
     }
@@ -92,6 +97,7 @@ pub struct Metrics {
     pub system_cpu_usage: Gauge,
     pub system_memory_usage: IntGauge,
     pub system_disk_usage: GaugeVec,
+    pub datadir_size: IntGauge,
     
     // Custom metrics
     custom_metrics: RwLock<HashMap<String, Gauge>>,
@@ -189,6 +195,7 @@ impl Metrics {
             Opts::new("ethereum_system_disk_usage_bytes", "Disk usage by mount point"),
             &["mount_point"]
         )?;
+        let datadir_size = IntGauge::new("ethereum_datadir_size_bytes", "Size of the node data directory")?;
         
         // Register all metrics
         registry.register(Box::new(peers_connected.clone()))?;
@@ -244,6 +251,7 @@ impl Metrics {
         registry.register(Box::new(system_cpu_usage.clone()))?;
         registry.register(Box::new(system_memory_usage.clone()))?;
         registry.register(Box::new(system_disk_usage.clone()))?;
+        registry.register(Box::new(datadir_size.clone()))?;
         
         Ok(Self {
             peers_connected,
@@ -292,6 +300,7 @@ impl Metrics {
             system_cpu_usage,
             system_memory_usage,
             system_disk_usage,
+            datadir_size,
             custom_metrics: RwLock::new(HashMap::new()),
         })
     }
@@ -2,7 +2,7 @@ use ethereum_types::{H256, H512};
 use secp256k1::{PublicKey, SecretKey, Secp256k1};
 use std::net::{SocketAddr, IpAddr};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use tokio::net::UdpSocket;
 use tokio::sync::RwLock;
@@ -13,12 +13,24 @@ use rand::Rng;
 
 use crate::{Result, NetworkError};
 
+pub mod enr_store;
+pub mod bootnodes;
+
+pub use enr_store::EnrStore;
+pub use bootnodes::{default_bootnodes, parse_bootnodes, parse_enode, node_id_to_public_key};
+
 const PROTOCOL_VERSION: u32 = 4;
 const BUCKET_SIZE: usize = 16;
 const ALPHA: usize = 3; // Concurrency parameter
 const MAX_NODES: usize = 10000;
 const PING_INTERVAL: Duration = Duration::from_secs(60);
 const EXPIRATION_TIME: Duration = Duration::from_secs(20);
+/// Time a lookup round waits for neighbors and the pongs of the nodes they name
+const LOOKUP_ROUND_TIMEOUT: Duration = Duration::from_millis(500);
+/// Upper bound on the rounds of one lookup
+const MAX_LOOKUP_ROUNDS: usize = 8;
+/// Neighbors sent per packet, keeping packets within the 1280 byte limit
+const NEIGHBORS_PER_PACKET: usize = 6;
 
 #[derive(Debug, Clone)]
 pub struct NodeId {
@@ -37,25 +49,35 @@ impl NodeId {
         }
     }
     
-    pub fn distance(&self, other: &H512) -> H512 {
-        let mut result = [0u8; 64];
-        for i in 0..64 {
-            result[i] = self.id.as_bytes()[i] ^ other.as_bytes()[i];
-        }
-        H512::from(result)
+    /// XOR distance between the Keccak hashes of the two ids
+    pub fn distance(&self, other: &H512) -> H256 {
+        distance(&self.id, other)
     }
     
     pub fn log_distance(&self, other: &H512) -> Option<usize> {
-        let distance = self.distance(other);
-        for i in 0..512 {
-            let byte_idx = i / 8;
-            let bit_idx = 7 - (i % 8);
-            if (distance.as_bytes()[byte_idx] >> bit_idx) & 1 == 1 {
-                return Some(511 - i);
-            }
+        log_distance(&self.id, other)
+    }
+}
+
+/// XOR distance between the Keccak hashes of two node ids, as discv4 defines it
+pub fn distance(a: &H512, b: &H512) -> H256 {
+    let (a, b) = (ethereum_crypto::keccak256(a.as_bytes()), ethereum_crypto::keccak256(b.as_bytes()));
+    let mut result = [0u8; 32];
+    for i in 0..32 {
+        result[i] = a[i] ^ b[i];
+    }
+    H256::from(result)
+}
+
+/// Index of the highest differing bit of the hashed ids, `None` for equal ids
+pub fn log_distance(a: &H512, b: &H512) -> Option<usize> {
+    let distance = distance(a, b);
+    for (byte_idx, byte) in distance.as_bytes().iter().enumerate() {
+        if *byte != 0 {
+            return Some(255 - byte_idx * 8 - byte.leading_zeros() as usize);
         }
-        None
     }
+    None
 }
 
 #[derive(Debug)]
@@ -136,13 +158,7 @@ impl RoutingTable {
     }
     
     pub async fn find_nearest(&self, target: &H512, count: usize) -> Vec<NodeId> {
-        let mut nodes = Vec::new();
-        
-        // Collect nodes from all buckets
-        for bucket in &self.buckets {
-            let bucket = bucket.read().await;
-            nodes.extend(bucket.get_nodes());
-        }
+        let mut nodes = self.nodes().await;
         
         // Sort by distance to target
         nodes.sort_by_key(|n| n.distance(target));
@@ -150,13 +166,24 @@ impl RoutingTable {
         nodes
     }
     
+    /// Every node in the table
+    pub async fn nodes(&self) -> Vec<NodeId> {
+        let mut nodes = Vec::new();
+        for bucket in &self.buckets {
+            nodes.extend(bucket.read().await.get_nodes());
+        }
+        nodes
+    }
+    
+    pub async fn contains(&self, id: &H512) -> bool {
+        match self.bucket_index(id) {
+            Some(bucket_idx) => self.buckets[bucket_idx].read().await.nodes.iter().any(|n| n.id == *id),
+            None => false,
+        }
+    }
+    
     fn bucket_index(&self, id: &H512) -> Option<usize> {
-        let node = NodeId {
-            id: self.local_id,
-            address: "0.0.0.0:0".parse().unwrap(),
-            public_key: PublicKey::from_slice(&[0x04; 65]).unwrap(),
-        };
-        node.log_distance(id)
+        log_distance(&self.local_id, id)
     }
 }
 
@@ -177,11 +204,18 @@ pub enum Message {
         expiration: u64,
     },
     Neighbors {
-        nodes: Vec<NodeEndpoint>,
+        nodes: Vec<NodeRecord>,
         expiration: u64,
     },
 }
 
+/// Node named in a neighbors response
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct NodeRecord {
+    pub id: H512,
+    pub endpoint: NodeEndpoint,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct NodeEndpoint {
     pub address: IpAddr,
@@ -228,10 +262,29 @@ impl Discovery {
         })
     }
     
-    pub async fn bootstrap(&self, nodes: Vec<NodeId>) -> Result<()> {
-        for node in nodes {
-            self.ping_node(node).await?;
+    /// This node as others reach it
+    pub fn local_node(&self) -> Result<NodeId> {
+        Ok(NodeId::new(self.public_key, self.socket.local_addr()?))
+    }
+    
+    pub fn routing_table(&self) -> Arc<RoutingTable> {
+        self.routing_table.clone()
+    }
+    
+    /// Seed the routing table with `bootnodes` and fill the buckets by
+    /// looking up our own id through them
+    ///
+    /// Expects the message handler started by [`Discovery::run`].
+    pub async fn bootstrap(&self, bootnodes: Vec<NodeId>) -> Result<()> {
+        for node in bootnodes {
+            self.routing_table.add_node(node.clone()).await;
+            if let Err(e) = self.ping_node(node.clone()).await {
+                tracing::debug!("Failed to ping bootnode {}: {}", node.address, e);
+            }
         }
+        
+        let found = self.lookup(self.node_id).await?;
+        tracing::info!("Discovery bootstrapped with {} nodes", found.len());
         Ok(())
     }
     
@@ -346,23 +399,28 @@ impl Discovery {
             // Find nearest nodes
             let nodes = self.routing_table.find_nearest(&target, BUCKET_SIZE).await;
             
-            // Convert to endpoints
-            let endpoints: Vec<NodeEndpoint> = nodes
+            // Convert to records
+            let records: Vec<NodeRecord> = nodes
                 .iter()
-                .map(|n| NodeEndpoint {
-                    address: n.address.ip(),
-                    udp_port: n.address.port(),
-                    tcp_port: n.address.port(), // Same as UDP for now
+                .map(|n| NodeRecord {
+                    id: n.id,
+                    endpoint: NodeEndpoint {
+                        address: n.address.ip(),
+                        udp_port: n.address.port(),
+                        tcp_port: n.address.port(), // Same as UDP for now
+                    },
                 })
                 .collect();
             
             // Send neighbors
-            let neighbors = Message::Neighbors {
-                nodes: endpoints,
-                expiration: future_expiration(),
-            };
-            
-            self.send_message(&neighbors, from).await?;
+            for chunk in records.chunks(NEIGHBORS_PER_PACKET) {
+                let neighbors = Message::Neighbors {
+                    nodes: chunk.to_vec(),
+                    expiration: future_expiration(),
+                };
+                
+                self.send_message(&neighbors, from).await?;
+            }
         }
         
         Ok(())
@@ -378,10 +436,14 @@ impl Discovery {
             }
             
             // Process received nodes
-            for endpoint in nodes {
-                // Ping each new node
-                if let Ok(public_key) = endpoint_to_public_key(&endpoint) {
-                    let node = NodeId::new(public_key, endpoint.to_socket_addr());
+            for record in nodes {
+                if record.id == self.node_id || self.routing_table.contains(&record.id).await {
+                    continue;
+                }
+                
+                // Ping each new node, it joins the table once it answers
+                if let Ok(public_key) = node_id_to_public_key(&record.id) {
+                    let node = NodeId::new(public_key, record.endpoint.to_socket_addr());
                     self.ping_node(node).await?;
                 }
             }
@@ -421,22 +483,42 @@ impl Discovery {
         Ok(())
     }
     
-    async fn find_node(&self, target: H512) -> Result<Vec<NodeId>> {
-        let nearest = self.routing_table.find_nearest(&target, ALPHA).await;
-        
-        for node in &nearest {
-            let find = Message::FindNode {
-                target,
-                expiration: future_expiration(),
-            };
+    /// Iterative Kademlia lookup of `target`
+    ///
+    /// Each round asks the `ALPHA` closest nodes not asked yet, until every
+    /// node among the closest `BUCKET_SIZE` has been asked. Returns the
+    /// closest nodes known afterwards.
+    pub async fn lookup(&self, target: H512) -> Result<Vec<NodeId>> {
+        let mut asked = HashSet::new();
+        
+        for _ in 0..MAX_LOOKUP_ROUNDS {
+            let candidates: Vec<NodeId> = self.routing_table
+                .find_nearest(&target, BUCKET_SIZE)
+                .await
+                .into_iter()
+                .filter(|node| !asked.contains(&node.id))
+                .take(ALPHA)
+                .collect();
+            if candidates.is_empty() {
+                break;
+            }
             
-            self.send_message(&find, node.address).await?;
+            for node in candidates {
+                asked.insert(node.id);
+                let find = Message::FindNode {
+                    target,
+                    expiration: future_expiration(),
+                };
+                if let Err(e) = self.send_message(&find, node.address).await {
+                    tracing::debug!("Failed to query {}: {}", node.address, e);
+                }
+            }
+            
+            // Neighbors are pinged as they arrive and join the table on pong
+            tokio::time::sleep(LOOKUP_ROUND_TIMEOUT).await;
         }
         
-        // Wait for responses (simplified)
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        
-        Ok(nearest)
+        Ok(self.routing_table.find_nearest(&target, BUCKET_SIZE).await)
     }
     
     async fn send_message(&self, msg: &Message, to: SocketAddr) -> Result<()> {
@@ -524,26 +606,17 @@ impl Discovery {
             
             // Discover new nodes by searching for random IDs
             let target = random_node_id();
-            if let Err(e) = self.find_node(target).await {
+            if let Err(e) = self.lookup(target).await {
                 tracing::debug!("Failed to find node: {}", e);
             }
         }
     }
 }
 
+/// discv4 node id: the uncompressed public key without its 0x04 prefix
 fn public_key_to_node_id(public_key: &PublicKey) -> H512 {
     let serialized = public_key.serialize_uncompressed();
-    H512::from_slice(&ethereum_crypto::keccak256(&serialized[1..])[..])
-}
-
-fn node_id_to_public_key(_node_id: &H512) -> Result<PublicKey> {
-    // This is a simplified version - in reality, we'd need to store the mapping
-    Err(NetworkError::InvalidMessage("Cannot recover public key from node ID".to_string()))
-}
-
-fn endpoint_to_public_key(_endpoint: &NodeEndpoint) -> Result<PublicKey> {
-    // This is a simplified version - in reality, we'd need to get this from the node
-    Err(NetworkError::InvalidMessage("Cannot get public key from endpoint".to_string()))
+    H512::from_slice(&serialized[1..])
 }
 
 fn encode_message(msg: &Message) -> Result<Vec<u8>> {
@@ -585,4 +658,45 @@ fn random_node_id() -> H512 {
     let mut bytes = [0u8; 64];
     rng.fill(&mut bytes);
     H512::from(bytes)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    async fn start_node() -> Arc<Discovery> {
+        let key = SecretKey::new(&mut rand::thread_rng());
+        let discovery = Arc::new(Discovery::new(key, "127.0.0.1:0".parse().unwrap()).await.unwrap());
+        discovery.clone().run().await;
+        discovery
+    }
+    
+    #[test]
+    fn test_log_distance() {
+        let a = H512::repeat_byte(0x11);
+        let b = H512::repeat_byte(0x22);
+        assert_eq!(log_distance(&a, &a), None);
+        assert_eq!(log_distance(&a, &b), log_distance(&b, &a));
+        assert!(log_distance(&a, &b).unwrap() < 256);
+    }
+    
+    #[tokio::test]
+    async fn test_bootstrap_discovers_peers_through_bootnode() {
+        let bootnode = start_node().await;
+        
+        // A peer the bootnode already knows about
+        let peer = start_node().await;
+        peer.bootstrap(vec![bootnode.local_node().unwrap()]).await.unwrap();
+        assert!(bootnode.routing_table().contains(&peer.node_id).await);
+        
+        let node = start_node().await;
+        node.bootstrap(vec![bootnode.local_node().unwrap()]).await.unwrap();
+        
+        let table = node.routing_table();
+        assert!(table.contains(&bootnode.node_id).await);
+        assert!(table.contains(&peer.node_id).await);
+        assert_eq!(table.nodes().await.len(), 2);
+        
+        // The peer answered our ping and learned about us too
+        assert!(peer.routing_table().contains(&node.node_id).await);
+    }
+}
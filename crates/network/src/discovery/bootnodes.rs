@@ -0,0 +1,104 @@
+use ethereum_types::H512;
+use secp256k1::PublicKey;
+use std::net::SocketAddr;
+
+use super::NodeId;
+use crate::{Result, NetworkError};
+
+/// Ethereum Foundation bootnodes for mainnet
+pub const MAINNET_BOOTNODES: &[&str] = &[
+    "enode://d860a01f9722d78051619d1e2351aba3f43f943f6f00718d1b9baa4101932a1f5011f16bb2b1bb35db20d6fe28fa0bf09636d26a87d31de9ec6203eeedb1f666@18.138.108.67:30303",
+    "enode://22a8232c3abc76a16ae9d6c3b164f98775fe226f0917b0ca871128a74a8e9630b458460865bab457221f1d448dd9791d24c4e5d88786180ac185df813a68d4de@3.209.45.79:30303",
+    "enode://2b252ab6a1d0f971d9722cb839a42cb81db019ba44c08754628ab4a823487071b5695317c8ccd085219c3a03af063495b2f1da8d18218da2d6a82981b45e6ffc@65.108.70.101:30303",
+    "enode://4aeb4ab6c14b23e2c4cfdce879c04b0748a20d8e9b59e25ded2a08143e265c6c25936e74cbc8e641e3312ca288673d91f2f93f8e277de3cfa444ecdaaf982052@157.90.35.166:30303",
+];
+
+/// Ethereum Foundation bootnodes for Sepolia
+pub const SEPOLIA_BOOTNODES: &[&str] = &[
+    "enode://4e5e92199ee224a01932a377160aa432f31d0b351f84ab413a8e0a42f4f36476f8fb1cbe914af0d9aef0d51665c214cf653c651c4bbd9d5550a934f241f1682b@138.197.51.181:30303",
+    "enode://143e11fb766781d22d92a2e33f8f104cddae4411a122295ed1fdb6638de96a6ce65f5b7c964ba3763bba27961738fef7d3ecc739268f3e5e771fb4c87b6234ba@146.190.1.103:30303",
+    "enode://8b61dc2d06c3f96fddcbebb0efb29d60d3598650275dc469c22229d3e5620369b0d3dedafd929835fe7f489618f19f456fe7c0df572bf2d914a9f4e006f783a9@170.64.250.88:30303",
+    "enode://10d62eff032205fcef19497f35ca8477bea0eadfff6d769a147e895d8b2b8f8ae6341630c645c30f5df6e67547c03494ced3d9c5764e8622a26587b083b028e8@139.59.49.206:30303",
+    "enode://9e9492e2e8836114cc75f5b929784f4f46c324ad01daf87d956f98b3b6c5fcba95524d6e5cf9861dc96a2c8a171ea7105bb554a197455058de185fa870970c7c@138.68.123.152:30303",
+];
+
+/// Bootnodes of a named network, empty for networks without defaults
+pub fn default_bootnodes(network: &str) -> &'static [&'static str] {
+    match network {
+        "mainnet" => MAINNET_BOOTNODES,
+        "sepolia" => SEPOLIA_BOOTNODES,
+        _ => &[],
+    }
+}
+
+/// Parse `enode://<hex node id>@<ip>:<tcp port>[?discport=<udp port>]`
+///
+/// The returned node is addressed by its discovery (UDP) endpoint.
+pub fn parse_enode(url: &str) -> Result<NodeId> {
+    let invalid = |reason: &str| NetworkError::InvalidMessage(format!("invalid enode {}: {}", url, reason));
+
+    let rest = url.strip_prefix("enode://").ok_or_else(|| invalid("missing enode:// scheme"))?;
+    let (id, host) = rest.split_once('@').ok_or_else(|| invalid("missing host"))?;
+    let (host, query) = match host.split_once('?') {
+        Some((host, query)) => (host, Some(query)),
+        None => (host, None),
+    };
+
+    let id = hex::decode(id).map_err(|_| invalid("node id is not hex"))?;
+    if id.len() != 64 {
+        return Err(invalid("node id must be 64 bytes"));
+    }
+    let public_key = node_id_to_public_key(&H512::from_slice(&id))
+        .map_err(|_| invalid("node id is not a public key"))?;
+
+    let mut address: SocketAddr = host.parse().map_err(|_| invalid("host must be ip:port"))?;
+    if let Some(query) = query {
+        let port = query.strip_prefix("discport=").ok_or_else(|| invalid("unknown query"))?;
+        address.set_port(port.parse().map_err(|_| invalid("invalid discport"))?);
+    }
+
+    Ok(NodeId::new(public_key, address))
+}
+
+/// Parse every URL in `urls`, failing on the first invalid one
+pub fn parse_bootnodes<S: AsRef<str>>(urls: &[S]) -> Result<Vec<NodeId>> {
+    urls.iter().map(|url| parse_enode(url.as_ref())).collect()
+}
+
+/// Public key behind a discv4 node id, the uncompressed key without its prefix
+pub fn node_id_to_public_key(node_id: &H512) -> Result<PublicKey> {
+    let mut serialized = [0u8; 65];
+    serialized[0] = 0x04;
+    serialized[1..].copy_from_slice(node_id.as_bytes());
+    PublicKey::from_slice(&serialized).map_err(|e| NetworkError::CryptoError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bootnodes_parse() {
+        for network in ["mainnet", "sepolia"] {
+            let nodes = parse_bootnodes(default_bootnodes(network)).unwrap();
+            assert!(!nodes.is_empty());
+        }
+        assert!(default_bootnodes("dev").is_empty());
+
+        let node = parse_enode(MAINNET_BOOTNODES[0]).unwrap();
+        assert_eq!(node.address, "18.138.108.67:30303".parse().unwrap());
+        assert_eq!(&hex::encode(node.id.as_bytes())[..8], "d860a01f");
+    }
+
+    #[test]
+    fn test_parse_enode_discport_and_errors() {
+        let id = &MAINNET_BOOTNODES[0][8..136];
+        let node = parse_enode(&format!("enode://{}@10.0.0.1:30303?discport=30301", id)).unwrap();
+        assert_eq!(node.address, "10.0.0.1:30301".parse().unwrap());
+
+        assert!(parse_enode(&format!("enr://{}@10.0.0.1:30303", id)).is_err());
+        assert!(parse_enode(&format!("enode://{}@bootnode.example:30303", id)).is_err());
+        assert!(parse_enode("enode://abcd@10.0.0.1:30303").is_err());
+        assert!(parse_enode(&format!("enode://{}@10.0.0.1:30303", "00".repeat(64))).is_err());
+    }
+}
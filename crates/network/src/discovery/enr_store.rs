@@ -0,0 +1,232 @@
+use ethereum_storage::{Database, StorageError};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+use crate::discovery_v5::{DiscoveryError, Enr, NodeId, Result};
+
+/// Key prefix of the `NodeId -> (refreshed_at, ENR)` entries
+pub const ENR_PREFIX: &[u8] = b"enr:";
+
+/// ENRs that were not refreshed by a successful Ping/Pong within this
+/// window are dropped during maintenance
+pub const ENR_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Disk-backed store of known ENRs, used to seed the routing table after a
+/// restart instead of re-discovering peers from the bootnodes
+pub struct EnrStore {
+    db: Arc<dyn Database>,
+}
+
+impl EnrStore {
+    pub fn new(db: Arc<dyn Database>) -> Self {
+        Self { db }
+    }
+
+    /// Store an ENR. A record that is already known keeps its refresh time,
+    /// only a successful Ping/Pong (`touch`) extends its lifetime.
+    pub fn put(&self, enr: &Enr) -> Result<()> {
+        let refreshed_at = match self.get_entry(&enr.node_id)? {
+            Some((refreshed_at, _)) => refreshed_at,
+            None => unix_now(),
+        };
+
+        self.write_entry(&enr.node_id, refreshed_at, &enr.encode_rlp())
+    }
+
+    pub fn get(&self, node_id: &NodeId) -> Result<Option<Enr>> {
+        self.get_entry(node_id)?
+            .map(|(_, record)| Enr::decode_rlp(node_id.clone(), &record))
+            .transpose()
+    }
+
+    /// Mark a node as alive after a successful Ping/Pong exchange
+    pub fn touch(&self, node_id: &NodeId) -> Result<bool> {
+        self.touch_at(node_id, unix_now())
+    }
+
+    pub fn touch_at(&self, node_id: &NodeId, now: u64) -> Result<bool> {
+        match self.get_entry(node_id)? {
+            Some((_, record)) => {
+                self.write_entry(node_id, now, &record)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn remove(&self, node_id: &NodeId) -> Result<()> {
+        self.db.delete(&enr_key(node_id))
+            .map_err(storage_error)
+    }
+
+    /// Load every stored ENR. Entries that fail to decode are skipped.
+    pub fn load_all(&self) -> Vec<Enr> {
+        let mut enrs = Vec::new();
+        let mut iter = self.db.iter_prefix(ENR_PREFIX);
+        while let Some(entry) = iter.next() {
+            let (key, value) = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("Failed to read ENR store: {}", e);
+                    break;
+                }
+            };
+            if !key.starts_with(ENR_PREFIX) {
+                break;
+            }
+
+            match decode_entry(&key[ENR_PREFIX.len()..], &value) {
+                Ok((node_id, _, record)) => match Enr::decode_rlp(node_id, &record) {
+                    Ok(enr) => enrs.push(enr),
+                    Err(e) => warn!("Skipping undecodable ENR: {}", e),
+                },
+                Err(e) => warn!("Skipping corrupt ENR entry: {}", e),
+            }
+        }
+
+        enrs
+    }
+
+    /// Remove ENRs whose last refresh is older than `ENR_TTL`
+    pub fn remove_expired(&self) -> Result<Vec<NodeId>> {
+        self.remove_expired_at(unix_now())
+    }
+
+    pub fn remove_expired_at(&self, now: u64) -> Result<Vec<NodeId>> {
+        let cutoff = now.saturating_sub(ENR_TTL.as_secs());
+
+        let mut expired = Vec::new();
+        let mut iter = self.db.iter_prefix(ENR_PREFIX);
+        while let Some(entry) = iter.next() {
+            let (key, value) = entry.map_err(storage_error)?;
+            if !key.starts_with(ENR_PREFIX) {
+                break;
+            }
+            let (node_id, refreshed_at, _) = decode_entry(&key[ENR_PREFIX.len()..], &value)?;
+
+            if refreshed_at < cutoff {
+                expired.push(node_id);
+            }
+        }
+
+        if !expired.is_empty() {
+            let mut batch = self.db.batch();
+            for node_id in &expired {
+                batch.delete(&enr_key(node_id));
+            }
+            self.db.write_batch(batch).map_err(storage_error)?;
+        }
+
+        if !expired.is_empty() {
+            debug!("Expired {} stale ENRs", expired.len());
+        }
+
+        Ok(expired)
+    }
+
+    fn get_entry(&self, node_id: &NodeId) -> Result<Option<(u64, Vec<u8>)>> {
+        let value = self.db.get(&enr_key(node_id))
+            .map_err(storage_error)?;
+
+        value
+            .map(|value| decode_entry(node_id.as_bytes(), &value).map(|(_, refreshed_at, record)| (refreshed_at, record)))
+            .transpose()
+    }
+
+    fn write_entry(&self, node_id: &NodeId, refreshed_at: u64, record: &[u8]) -> Result<()> {
+        // Value layout: 8-byte big-endian refresh time followed by the RLP record
+        let mut value = Vec::with_capacity(8 + record.len());
+        value.extend_from_slice(&refreshed_at.to_be_bytes());
+        value.extend_from_slice(record);
+
+        self.db.put(&enr_key(node_id), &value)
+            .map_err(storage_error)
+    }
+}
+
+fn enr_key(node_id: &NodeId) -> Vec<u8> {
+    [ENR_PREFIX, node_id.as_bytes()].concat()
+}
+
+fn decode_entry(key: &[u8], value: &[u8]) -> Result<(NodeId, u64, Vec<u8>)> {
+    let id: [u8; 32] = key.try_into()
+        .map_err(|_| DiscoveryError::DecodingError("node id key must be 32 bytes".to_string()))?;
+
+    if value.len() < 8 {
+        return Err(DiscoveryError::DecodingError("ENR entry too short".to_string()));
+    }
+
+    let mut refreshed_at = [0u8; 8];
+    refreshed_at.copy_from_slice(&value[..8]);
+
+    Ok((NodeId::new(id), u64::from_be_bytes(refreshed_at), value[8..].to_vec()))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn storage_error(e: StorageError) -> DiscoveryError {
+    DiscoveryError::StorageError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_storage::MemoryDatabase;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn enr(byte: u8) -> Enr {
+        let mut enr = Enr::new(NodeId::new([byte; 32]), 3)
+            .with_ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, byte)), 30303, 30303);
+        enr.signature = vec![byte; 64];
+        enr
+    }
+
+    #[test]
+    fn test_enrs_survive_reopen() {
+        let db: Arc<dyn Database> = Arc::new(MemoryDatabase::new());
+        // Unrelated keys around the ENR prefix are left alone
+        db.put(b"en", b"other").unwrap();
+        db.put(b"header:00", b"other").unwrap();
+
+        {
+            let store = EnrStore::new(db.clone());
+            store.put(&enr(1)).unwrap();
+            store.put(&enr(2)).unwrap();
+        }
+
+        let store = EnrStore::new(db);
+        let mut loaded = store.load_all();
+        loaded.sort_by_key(|e| e.node_id.as_bytes().to_vec());
+
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].node_id, NodeId::new([1; 32]));
+        assert_eq!(loaded[0].ip, Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+        assert_eq!(loaded[0].udp, Some(30303));
+        assert_eq!(loaded[0].seq, 3);
+        assert_eq!(loaded[1].signature, vec![2; 64]);
+    }
+
+    #[test]
+    fn test_unrefreshed_enrs_expire() {
+        let store = EnrStore::new(Arc::new(MemoryDatabase::new()));
+        let now = unix_now();
+
+        store.put(&enr(1)).unwrap();
+        store.put(&enr(2)).unwrap();
+
+        // Only node 2 answered a ping during the last day
+        let later = now + ENR_TTL.as_secs() + 60;
+        assert!(store.touch_at(&NodeId::new([2; 32]), later - 60).unwrap());
+
+        let expired = store.remove_expired_at(later).unwrap();
+        assert_eq!(expired, vec![NodeId::new([1; 32])]);
+        assert!(store.get(&NodeId::new([1; 32])).unwrap().is_none());
+        assert!(store.get(&NodeId::new([2; 32])).unwrap().is_some());
+    }
+}
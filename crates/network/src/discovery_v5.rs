@@ -1,8 +1,8 @@
 use ethereum_types::{H256, H512};
 use ethereum_crypto::keccak256;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::net::{IpAddr, SocketAddr};
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 use thiserror::Error;
@@ -10,6 +10,8 @@ use tokio::net::UdpSocket;
 use tokio::sync::mpsc;
 use tracing::{debug, info, warn, error};
 
+use crate::discovery::EnrStore;
+
 #[derive(Debug, Error)]
 pub enum DiscoveryError {
     #[error("Invalid ENR: {0}")]
@@ -29,6 +31,9 @@ pub enum DiscoveryError {
     
     #[error("Decoding error: {0}")]
     DecodingError(String),
+    
+    #[error("Storage error: {0}")]
+    StorageError(String),
 }
 
 pub type Result<T> = std::result::Result<T, DiscoveryError>;
@@ -144,6 +149,136 @@ impl Enr {
             .map_err(|e| DiscoveryError::DecodingError(e.to_string()))
     }
     
+    /// RLP encoding of the record per EIP-778: `[signature, seq, k, v, ...]`
+    /// with the key/value pairs sorted by key. The node id is not part of
+    /// the record, callers that persist it must keep it alongside.
+    pub fn encode_rlp(&self) -> Vec<u8> {
+        let mut pairs: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        
+        for (key, value) in &self.custom_fields {
+            pairs.insert(key.as_bytes().to_vec(), rlp_bytes(value));
+        }
+        
+        pairs.insert(b"id".to_vec(), rlp_bytes(self.id.as_bytes()));
+        if let Some(ip) = self.ip {
+            pairs.insert(b"ip".to_vec(), rlp_bytes(&ip_octets(&ip)));
+        }
+        if let Some(ip6) = self.ip6 {
+            pairs.insert(b"ip6".to_vec(), rlp_bytes(&ip_octets(&ip6)));
+        }
+        if let Some(tcp) = self.tcp {
+            pairs.insert(b"tcp".to_vec(), rlp_u64(tcp as u64));
+        }
+        if let Some(udp) = self.udp {
+            pairs.insert(b"udp".to_vec(), rlp_u64(udp as u64));
+        }
+        if let Some(tcp6) = self.tcp6 {
+            pairs.insert(b"tcp6".to_vec(), rlp_u64(tcp6 as u64));
+        }
+        if let Some(udp6) = self.udp6 {
+            pairs.insert(b"udp6".to_vec(), rlp_u64(udp6 as u64));
+        }
+        if let Some(pubkey) = &self.secp256k1 {
+            pairs.insert(b"secp256k1".to_vec(), rlp_bytes(pubkey));
+        }
+        if let Some(eth2) = &self.eth2 {
+            // SSZ encoded ENRForkID
+            let mut value = Vec::with_capacity(16);
+            value.extend_from_slice(&eth2.fork_digest);
+            value.extend_from_slice(&eth2.next_fork_version);
+            value.extend_from_slice(&eth2.next_fork_epoch.to_le_bytes());
+            pairs.insert(b"eth2".to_vec(), rlp_bytes(&value));
+        }
+        if let Some(attnets) = &self.attnets {
+            pairs.insert(b"attnets".to_vec(), rlp_bytes(attnets));
+        }
+        if let Some(syncnets) = &self.syncnets {
+            pairs.insert(b"syncnets".to_vec(), rlp_bytes(syncnets));
+        }
+        
+        let mut payload = rlp_bytes(&self.signature);
+        payload.extend_from_slice(&rlp_u64(self.seq));
+        for (key, value) in pairs {
+            payload.extend_from_slice(&rlp_bytes(&key));
+            payload.extend_from_slice(&value);
+        }
+        
+        let mut encoder = ethereum_rlp::Encoder::new();
+        encoder.encode_list_payload(&payload);
+        encoder.finish()
+    }
+    
+    /// Decode a record produced by `encode_rlp` for the given node
+    pub fn decode_rlp(node_id: NodeId, data: &[u8]) -> Result<Self> {
+        let invalid = |msg: &str| DiscoveryError::InvalidEnr(msg.to_string());
+        
+        let mut decoder = ethereum_rlp::Decoder::new(data)
+            .map_err(|e| DiscoveryError::DecodingError(e.to_string()))?;
+        let item = decoder.decode_item()
+            .map_err(|e| DiscoveryError::DecodingError(e.to_string()))?;
+        let items = item.as_list().ok_or_else(|| invalid("record is not a list"))?;
+        
+        if items.len() < 2 || items.len() % 2 != 0 {
+            return Err(invalid("record must hold a signature, seq and key/value pairs"));
+        }
+        
+        let bytes = |item: &ethereum_rlp::RlpItem| -> Result<Vec<u8>> {
+            item.as_bytes()
+                .map(|b| b.to_vec())
+                .ok_or_else(|| invalid("expected a string item"))
+        };
+        
+        let mut enr = Enr::new(node_id, be_u64(&bytes(&items[1])?)?);
+        enr.signature = bytes(&items[0])?;
+        enr.id = String::new();
+        
+        for pair in items[2..].chunks(2) {
+            let key = bytes(&pair[0])?;
+            let value = bytes(&pair[1])?;
+            
+            match key.as_slice() {
+                b"id" => {
+                    enr.id = String::from_utf8(value).map_err(|_| invalid("id is not utf-8"))?;
+                }
+                b"ip" => {
+                    let octets: [u8; 4] = value.as_slice().try_into()
+                        .map_err(|_| invalid("ip must be 4 bytes"))?;
+                    enr.ip = Some(IpAddr::V4(Ipv4Addr::from(octets)));
+                }
+                b"ip6" => {
+                    let octets: [u8; 16] = value.as_slice().try_into()
+                        .map_err(|_| invalid("ip6 must be 16 bytes"))?;
+                    enr.ip6 = Some(IpAddr::V6(Ipv6Addr::from(octets)));
+                }
+                b"tcp" => enr.tcp = Some(be_u16(&value)?),
+                b"udp" => enr.udp = Some(be_u16(&value)?),
+                b"tcp6" => enr.tcp6 = Some(be_u16(&value)?),
+                b"udp6" => enr.udp6 = Some(be_u16(&value)?),
+                b"secp256k1" => enr.secp256k1 = Some(value),
+                b"eth2" => {
+                    if value.len() < 16 {
+                        return Err(invalid("eth2 must be at least 16 bytes"));
+                    }
+                    let mut epoch = [0u8; 8];
+                    epoch.copy_from_slice(&value[8..16]);
+                    enr.eth2 = Some(Eth2Data {
+                        fork_digest: [value[0], value[1], value[2], value[3]],
+                        next_fork_version: [value[4], value[5], value[6], value[7]],
+                        next_fork_epoch: u64::from_le_bytes(epoch),
+                    });
+                }
+                b"attnets" => enr.attnets = Some(value),
+                b"syncnets" => enr.syncnets = Some(value),
+                _ => {
+                    let key = String::from_utf8(key).map_err(|_| invalid("key is not utf-8"))?;
+                    enr.custom_fields.insert(key, value);
+                }
+            }
+        }
+        
+        Ok(enr)
+    }
+    
     pub fn node_address(&self) -> Option<NodeAddress> {
         let ip = self.ip.or(self.ip6)?;
         let udp = self.udp.or(self.udp6)?;
@@ -157,6 +292,37 @@ impl Enr {
     }
 }
 
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = ethereum_rlp::Encoder::new();
+    encoder.encode_bytes(bytes);
+    encoder.finish()
+}
+
+fn rlp_u64(value: u64) -> Vec<u8> {
+    let mut encoder = ethereum_rlp::Encoder::new();
+    encoder.encode_u64(value);
+    encoder.finish()
+}
+
+fn ip_octets(ip: &IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(addr) => addr.octets().to_vec(),
+        IpAddr::V6(addr) => addr.octets().to_vec(),
+    }
+}
+
+fn be_u64(bytes: &[u8]) -> Result<u64> {
+    if bytes.len() > 8 {
+        return Err(DiscoveryError::InvalidEnr("integer overflows u64".to_string()));
+    }
+    Ok(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+}
+
+fn be_u16(bytes: &[u8]) -> Result<u16> {
+    let value = be_u64(bytes)?;
+    u16::try_from(value).map_err(|_| DiscoveryError::InvalidEnr("port overflows u16".to_string()))
+}
+
 /// Node ID (256-bit)
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct NodeId(H256);
@@ -489,6 +655,7 @@ pub struct Discovery {
     pending_requests: Arc<RwLock<HashMap<u64, PendingRequest>>>,
     msg_tx: mpsc::Sender<(Message, SocketAddr)>,
     msg_rx: Arc<tokio::sync::Mutex<mpsc::Receiver<(Message, SocketAddr)>>>,
+    enr_store: Option<Arc<EnrStore>>,
 }
 
 struct PendingRequest {
@@ -520,12 +687,38 @@ impl Discovery {
             pending_requests: Arc::new(RwLock::new(HashMap::new())),
             msg_tx,
             msg_rx: Arc::new(tokio::sync::Mutex::new(msg_rx)),
+            enr_store: None,
         })
     }
     
+    /// Persist discovered ENRs so the routing table can be restored on restart
+    pub fn with_enr_store(mut self, store: Arc<EnrStore>) -> Self {
+        self.enr_store = Some(store);
+        self
+    }
+    
+    fn restore_routing_table(&self) {
+        let Some(store) = &self.enr_store else {
+            return;
+        };
+        
+        let enrs = store.load_all();
+        let mut routing_table = self.routing_table.write().unwrap();
+        let mut restored = 0;
+        for enr in enrs {
+            if routing_table.add_node(enr).is_ok() {
+                restored += 1;
+            }
+        }
+        
+        info!("Restored {} nodes from the ENR store", restored);
+    }
+    
     pub async fn start(&self) {
         info!("Starting Discovery v5 protocol");
         
+        self.restore_routing_table();
+        
         // Start message handler
         let handler = self.clone();
         tokio::spawn(async move {
@@ -610,6 +803,12 @@ impl Discovery {
         
         if let Some(req) = pending.remove(&request_id) {
             debug!("Received pong from {:?} with ENR seq {}", req.node_id, enr_seq);
+            
+            if let Some(store) = &self.enr_store {
+                if let Err(e) = store.touch(&req.node_id) {
+                    warn!("Failed to refresh ENR for {:?}: {}", req.node_id, e);
+                }
+            }
         }
     }
     
@@ -646,6 +845,11 @@ impl Discovery {
         
         for enr in enrs {
             if enr.verify().unwrap_or(false) {
+                if let Some(store) = &self.enr_store {
+                    if let Err(e) = store.put(&enr) {
+                        warn!("Failed to persist ENR for {:?}: {}", enr.node_id, e);
+                    }
+                }
                 let _ = routing_table.add_node(enr);
             }
         }
@@ -721,6 +925,20 @@ impl Discovery {
             pending.retain(|_, req| {
                 now.duration_since(req.sent_at) < req.timeout
             });
+            drop(pending);
+            
+            // Drop ENRs that have not answered a ping within the TTL
+            if let Some(store) = &self.enr_store {
+                match store.remove_expired() {
+                    Ok(expired) => {
+                        let mut routing_table = self.routing_table.write().unwrap();
+                        for node_id in &expired {
+                            routing_table.remove_node(node_id);
+                        }
+                    }
+                    Err(e) => warn!("ENR store maintenance failed: {}", e),
+                }
+            }
             
             // Refresh routing table
             self.refresh_buckets().await;
@@ -783,6 +1001,7 @@ impl Clone for Discovery {
             pending_requests: self.pending_requests.clone(),
             msg_tx: self.msg_tx.clone(),
             msg_rx: self.msg_rx.clone(),
+            enr_store: self.enr_store.clone(),
         }
     }
 }
\ No newline at end of file
@@ -2,15 +2,20 @@ use thiserror::Error;
 
 pub mod rlpx;
 pub mod discovery;
+pub mod discovery_v5;
 pub mod peer;
 pub mod protocol;
 pub mod messages;
+pub mod snap;
+pub mod tx_gossip;
+mod wire;
 
 pub use rlpx::*;
 pub use discovery::*;
 pub use peer::*;
 pub use protocol::*;
 pub use messages::*;
+pub use tx_gossip::{GossipPool, TxGossip};
 
 #[derive(Debug, Error)]
 pub enum NetworkError {
@@ -35,6 +40,9 @@ pub enum NetworkError {
     #[error("Crypto error: {0}")]
     CryptoError(String),
     
+    #[error("State error: {0}")]
+    StateError(String),
+    
     #[error("Timeout")]
     Timeout,
 }
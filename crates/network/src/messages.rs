@@ -1,8 +1,32 @@
-use ethereum_types::{H256, U256};
+use ethereum_types::{H256, H512, U256};
 use ethereum_core::{Block, Header};
 // use ethereum_rlp::{Encode, Decode}; // Unused imports
 use serde::{Serialize, Deserialize};
 
+use crate::wire::{
+    any_list, bytes_of, decode_hashes, decode_item, encode_hashes, list_of, rlp_bytes, rlp_list,
+    rlp_u64, u64_of,
+};
+use crate::protocol::Capability;
+use crate::{NetworkError, Result};
+
+/// Offset of eth message ids on the wire, past the p2p base protocol
+pub const ETH_MSG_ID_OFFSET: u8 = 0x10;
+pub const NEW_POOLED_TRANSACTION_HASHES: u8 = 0x08;
+pub const GET_POOLED_TRANSACTIONS: u8 = 0x09;
+pub const POOLED_TRANSACTIONS: u8 = 0x0a;
+
+/// p2p base protocol Hello, the first message either side sends over a new
+/// RLPx session
+#[derive(Debug, Clone)]
+pub struct HelloMessage {
+    pub protocol_version: u64,
+    pub client_id: String,
+    pub capabilities: Vec<Capability>,
+    pub listen_port: u16,
+    pub node_id: H512,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatusMessage {
     pub protocol_version: u8,
@@ -65,20 +89,23 @@ pub struct NewBlockMessage {
     pub total_difficulty: U256,
 }
 
-#[derive(Debug, Clone)]
+/// eth/68 announcement, `types`, `sizes` and `hashes` run in parallel
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NewPooledTransactionHashesMessage {
     pub types: Vec<u8>,
     pub sizes: Vec<u32>,
     pub hashes: Vec<H256>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GetPooledTransactionsMessage {
+    pub request_id: u64,
     pub hashes: Vec<H256>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PooledTransactionsMessage {
+    pub request_id: u64,
     pub transactions: Vec<Vec<u8>>, // RLP encoded transactions
 }
 
@@ -107,14 +134,128 @@ pub struct Log {
     pub data: Vec<u8>,
 }
 
+impl HelloMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let capabilities: Vec<Vec<u8>> = self.capabilities
+            .iter()
+            .map(|cap| rlp_list(&[rlp_bytes(cap.name.as_bytes()), rlp_u64(cap.version as u64)]))
+            .collect();
+        rlp_list(&[
+            rlp_u64(self.protocol_version),
+            rlp_bytes(self.client_id.as_bytes()),
+            rlp_list(&capabilities),
+            rlp_u64(self.listen_port as u64),
+            rlp_bytes(self.node_id.as_bytes()),
+        ])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 5)?;
+        let capabilities = any_list(&fields[2])?
+            .iter()
+            .map(|cap| {
+                let cap = list_of(cap, 2)?;
+                let name = String::from_utf8(bytes_of(&cap[0])?)
+                    .map_err(|_| NetworkError::InvalidMessage("capability name is not utf-8".to_string()))?;
+                let version = u8::try_from(u64_of(&cap[1])?)
+                    .map_err(|_| NetworkError::InvalidMessage("capability version overflows u8".to_string()))?;
+                Ok(Capability::new(name, version))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let node_id = bytes_of(&fields[4])?;
+        if node_id.len() != 64 {
+            return Err(NetworkError::InvalidMessage(format!(
+                "expected 64-byte node id, got {} bytes", node_id.len()
+            )));
+        }
+
+        Ok(Self {
+            protocol_version: u64_of(&fields[0])?,
+            client_id: String::from_utf8_lossy(&bytes_of(&fields[1])?).into_owned(),
+            capabilities,
+            listen_port: u16::try_from(u64_of(&fields[3])?)
+                .map_err(|_| NetworkError::InvalidMessage("listen port overflows u16".to_string()))?,
+            node_id: H512::from_slice(&node_id),
+        })
+    }
+}
+
 impl StatusMessage {
     pub fn encode(&self) -> Vec<u8> {
         // Simplified encoding - real implementation would use RLP
         bincode::serialize(self).unwrap_or_default()
     }
     
-    pub fn decode(data: &[u8]) -> Result<Self, crate::NetworkError> {
+    pub fn decode(data: &[u8]) -> Result<Self> {
         bincode::deserialize(data)
-            .map_err(|e| crate::NetworkError::InvalidMessage(e.to_string()))
+            .map_err(|e| NetworkError::InvalidMessage(e.to_string()))
     }
-}
\ No newline at end of file
+}
+
+impl NewPooledTransactionHashesMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let sizes: Vec<Vec<u8>> = self.sizes.iter().map(|size| rlp_u64(*size as u64)).collect();
+        rlp_list(&[rlp_bytes(&self.types), rlp_list(&sizes), encode_hashes(&self.hashes)])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 3)?;
+        let sizes = any_list(&fields[1])?
+            .iter()
+            .map(|size| {
+                u32::try_from(u64_of(size)?)
+                    .map_err(|_| NetworkError::InvalidMessage("size overflows u32".to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let message = Self {
+            types: bytes_of(&fields[0])?,
+            sizes,
+            hashes: decode_hashes(&fields[2])?,
+        };
+
+        if message.types.len() != message.hashes.len() || message.sizes.len() != message.hashes.len() {
+            return Err(NetworkError::InvalidMessage(format!(
+                "announcement has {} types, {} sizes and {} hashes",
+                message.types.len(), message.sizes.len(), message.hashes.len()
+            )));
+        }
+        Ok(message)
+    }
+}
+
+impl GetPooledTransactionsMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        rlp_list(&[rlp_u64(self.request_id), encode_hashes(&self.hashes)])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 2)?;
+        Ok(Self {
+            request_id: u64_of(&fields[0])?,
+            hashes: decode_hashes(&fields[1])?,
+        })
+    }
+}
+
+impl PooledTransactionsMessage {
+    /// Each transaction is already an RLP item (a list for legacy, a string
+    /// holding the envelope for typed ones), so they are embedded as they are
+    pub fn encode(&self) -> Vec<u8> {
+        rlp_list(&[rlp_u64(self.request_id), rlp_list(&self.transactions)])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 2)?;
+        Ok(Self {
+            request_id: u64_of(&fields[0])?,
+            transactions: any_list(&fields[1])?
+                .iter()
+                .map(|tx| ethereum_rlp::encode(tx)[..].to_vec())
+                .collect(),
+        })
+    }
+}
@@ -1,13 +1,27 @@
+use std::collections::VecDeque;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{broadcast, Notify, RwLock, mpsc};
 use ethereum_types::H512;
-use secp256k1::PublicKey;
+use secp256k1::{PublicKey, Secp256k1};
 
 use crate::{Result, NetworkError};
-use crate::rlpx::{RLPxHandshake, RLPxSession};
-use crate::protocol::Protocol;
+use crate::discovery::NodeId;
+use crate::rlpx::{RLPxHandshake, RLPxSession, FRAME_HEADER_SIZE, P2P_VERSION};
+use crate::messages::HelloMessage;
+use crate::protocol::{Capability, Protocol};
+
+/// Client id sent in our Hello
+const CLIENT_ID: &str = concat!("ethereum-rust/v", env!("CARGO_PKG_VERSION"));
+
+/// Inbound messages buffered for slow subscribers before they lag
+const INBOUND_CAPACITY: usize = 1024;
+
+/// Longest a dial may take to connect and finish the handshakes
+const DIAL_TIMEOUT: Duration = Duration::from_secs(15);
 
 #[derive(Debug, Clone)]
 pub struct PeerId {
@@ -41,6 +55,14 @@ pub enum PeerMessage {
     Disconnect(DisconnectReason),
 }
 
+/// Message read off a peer's session, with its id as it was on the wire
+#[derive(Debug, Clone)]
+pub struct InboundMessage {
+    pub peer: H512,
+    pub msg_id: u8,
+    pub data: Vec<u8>,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum DisconnectReason {
     DisconnectRequested,
@@ -76,7 +98,7 @@ impl Peer {
         }
     }
     
-    pub async fn connect(&mut self, stream: TcpStream, secret_key: secp256k1::SecretKey) -> Result<()> {
+    pub async fn connect(&mut self, stream: &mut TcpStream, secret_key: secp256k1::SecretKey) -> Result<()> {
         *self.state.write().await = PeerState::Handshaking;
         
         // Perform RLPx handshake
@@ -101,14 +123,71 @@ impl Peer {
         
         // Derive session secrets
         let secrets = handshake.derive_secrets()?;
-        let session = Arc::new(RwLock::new(RLPxSession::new(secrets)));
-        self.session = Some(session);
+        let mut session = RLPxSession::new(secrets);
+        
+        // Hello settles the capabilities and whether frames are compressed
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+        let hello = HelloMessage {
+            protocol_version: P2P_VERSION,
+            client_id: CLIENT_ID.to_string(),
+            capabilities: self.protocols.iter()
+                .map(|protocol| Capability::new(protocol.name.clone(), protocol.version))
+                .collect(),
+            listen_port: 0,
+            node_id: H512::from_slice(&public_key.serialize_uncompressed()[1..]),
+        };
+        let remote = session.exchange_hello(stream, &hello).await?;
+        self.id.client_id = remote.client_id;
+        
+        self.session = Some(Arc::new(RwLock::new(session)));
         
         *self.state.write().await = PeerState::Connected;
         
         Ok(())
     }
     
+    /// Serve the connection: frames queued with `send` are written to
+    /// `stream` while every message read off it goes to `inbound`, until
+    /// either side drops the connection
+    pub async fn run(self: Arc<Self>, stream: TcpStream, inbound: broadcast::Sender<InboundMessage>) -> Result<()> {
+        let session = self.session.clone()
+            .ok_or_else(|| NetworkError::PeerDisconnected("No session established".to_string()))?;
+        let (mut reader, mut writer) = stream.into_split();
+        
+        let outbound = self.clone();
+        let writes = tokio::spawn(async move {
+            while let Some(message) = outbound.recv().await {
+                match message {
+                    PeerMessage::Data(frame) => {
+                        if writer.write_all(&frame).await.is_err() {
+                            break;
+                        }
+                    }
+                    PeerMessage::Disconnect(_) => break,
+                }
+            }
+        });
+        
+        let result: Result<()> = async {
+            loop {
+                // The session is only locked once the bytes are in, so
+                // writers aren't held up by an idle peer
+                let mut frame = vec![0u8; FRAME_HEADER_SIZE];
+                reader.read_exact(&mut frame).await?;
+                frame.resize(session.read().await.frame_len(&frame)?, 0);
+                reader.read_exact(&mut frame[FRAME_HEADER_SIZE..]).await?;
+                
+                let (msg_id, data) = session.write().await.read_message(&frame)?;
+                // No subscribers just means nobody is interested yet
+                let _ = inbound.send(InboundMessage { peer: self.id.node_id, msg_id, data });
+            }
+        }.await;
+        
+        writes.abort();
+        *self.state.write().await = PeerState::Disconnected;
+        result
+    }
+    
     pub async fn send(&self, data: Vec<u8>) -> Result<()> {
         self.tx.send(PeerMessage::Data(data)).await
             .map_err(|_| NetworkError::PeerDisconnected("Channel closed".to_string()))?;
@@ -138,6 +217,12 @@ impl Peer {
 
 pub struct PeerManager {
     peers: Arc<RwLock<Vec<Arc<Peer>>>>,
+    /// Nodes waiting to be dialed, in the order they were requested
+    dials: Arc<RwLock<VecDeque<NodeId>>>,
+    /// Wakes the dial loop when a node is queued
+    dial_queued: Arc<Notify>,
+    /// Messages read off every connected peer
+    inbound: broadcast::Sender<InboundMessage>,
     max_peers: usize,
 }
 
@@ -145,10 +230,117 @@ impl PeerManager {
     pub fn new(max_peers: usize) -> Self {
         Self {
             peers: Arc::new(RwLock::new(Vec::new())),
+            dials: Arc::new(RwLock::new(VecDeque::new())),
+            dial_queued: Arc::new(Notify::new()),
+            inbound: broadcast::channel(INBOUND_CAPACITY).0,
             max_peers,
         }
     }
     
+    /// Messages read off connected peers from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<InboundMessage> {
+        self.inbound.subscribe()
+    }
+    
+    /// Queue `node` for dialing, false if it is already connected or queued
+    pub async fn add_dial(&self, node: NodeId) -> bool {
+        if self.get_peer(&node.id).await.is_some() {
+            return false;
+        }
+        
+        let mut dials = self.dials.write().await;
+        if dials.iter().any(|queued| queued.id == node.id) {
+            return false;
+        }
+        dials.push_back(node);
+        self.dial_queued.notify_one();
+        true
+    }
+    
+    /// Oldest node waiting to be dialed, waiting until one is queued
+    pub async fn next_dial(&self) -> NodeId {
+        loop {
+            if let Some(node) = self.dials.write().await.pop_front() {
+                return node;
+            }
+            self.dial_queued.notified().await;
+        }
+    }
+    
+    /// Dial queued nodes one at a time, for as long as the manager lives
+    pub async fn run_dials(&self, secret_key: secp256k1::SecretKey) {
+        loop {
+            let node = self.next_dial().await;
+            if let Err(e) = self.dial(&node, secret_key).await {
+                tracing::debug!("Dial to {} failed: {}", node.address, e);
+            }
+        }
+    }
+    
+    /// Open an RLPx session to `node` and add it as an outbound peer, served
+    /// in the background until the connection drops
+    ///
+    /// Fails with `Timeout` if the node doesn't complete the handshakes in
+    /// time, so an unresponsive node can't hold up the dial queue.
+    pub async fn dial(&self, node: &NodeId, secret_key: secp256k1::SecretKey) -> Result<()> {
+        let connecting = async {
+            let mut stream = TcpStream::connect(node.address).await?;
+            let mut peer = Peer::new(
+                PeerId { node_id: node.id, address: node.address, client_id: String::new() },
+                false,
+            );
+            peer.protocols.push(Protocol::eth());
+            peer.connect(&mut stream, secret_key).await?;
+            Ok::<_, NetworkError>((peer, stream))
+        };
+        let (peer, stream) = tokio::time::timeout(DIAL_TIMEOUT, connecting)
+            .await
+            .map_err(|_| NetworkError::Timeout)??;
+        
+        let peer = Arc::new(peer);
+        self.add_peer(peer.clone()).await?;
+        self.serve(peer, stream);
+        Ok(())
+    }
+    
+    /// Run `peer` over `stream`, dropping it once the connection ends
+    fn serve(&self, peer: Arc<Peer>, stream: TcpStream) {
+        let peers = self.peers.clone();
+        let inbound = self.inbound.clone();
+        tokio::spawn(async move {
+            if let Err(e) = peer.clone().run(stream, inbound).await {
+                tracing::debug!("Connection to {} ended: {}", peer.id.address, e);
+            }
+            peers.write().await.retain(|connected| !Arc::ptr_eq(connected, &peer));
+        });
+    }
+    
+    pub async fn pending_dials(&self) -> Vec<NodeId> {
+        self.dials.read().await.iter().cloned().collect()
+    }
+    
+    /// Forget `node_id`, dropping its queued dial and disconnecting it
+    ///
+    /// Returns whether the node was queued or connected.
+    pub async fn drop_peer(&self, node_id: &H512) -> bool {
+        let queued = {
+            let mut dials = self.dials.write().await;
+            let before = dials.len();
+            dials.retain(|node| node.id != *node_id);
+            dials.len() != before
+        };
+        
+        let peer = self.get_peer(node_id).await;
+        if let Some(peer) = &peer {
+            if let Err(e) = peer.disconnect(DisconnectReason::DisconnectRequested).await {
+                tracing::debug!("Failed to disconnect {}: {}", peer.id.address, e);
+            }
+            self.remove_peer(node_id).await;
+        }
+        
+        queued || peer.is_some()
+    }
+    
     pub async fn add_peer(&self, peer: Arc<Peer>) -> Result<()> {
         let mut peers = self.peers.write().await;
         
@@ -193,4 +385,52 @@ impl PeerManager {
         }
         count
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rlpx::Secrets;
+    use ethereum_types::H256;
+    use hmac::{Hmac, Mac};
+    use tokio::net::TcpListener;
+    
+    /// Sessions built from the same secrets read what the other writes
+    fn session() -> RLPxSession {
+        RLPxSession::new(Secrets {
+            aes_secret: H256::repeat_byte(0x11),
+            mac_secret: H256::repeat_byte(0x22),
+            egress_mac: Hmac::new_from_slice(&[0x33; 32]).unwrap(),
+            ingress_mac: Hmac::new_from_slice(&[0x33; 32]).unwrap(),
+        })
+    }
+    
+    #[tokio::test]
+    async fn test_run_carries_messages_both_ways() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (local, accepted) = tokio::join!(TcpStream::connect(address), listener.accept());
+        let (local, (mut remote, _)) = (local.unwrap(), accepted.unwrap());
+        
+        let node_id = H512::repeat_byte(0x01);
+        let mut peer = Peer::new(PeerId { node_id, address, client_id: String::new() }, false);
+        peer.session = Some(Arc::new(RwLock::new(session())));
+        let peer = Arc::new(peer);
+        let (inbound, mut messages) = broadcast::channel(16);
+        let serving = tokio::spawn(peer.clone().run(local, inbound));
+        
+        let mut remote_session = session();
+        let frame = remote_session.write_message(0x13, b"request").unwrap();
+        remote.write_all(&frame).await.unwrap();
+        let message = messages.recv().await.unwrap();
+        assert_eq!((message.peer, message.msg_id, message.data), (node_id, 0x13, b"request".to_vec()));
+        
+        let frame = peer.session.as_ref().unwrap().write().await.write_message(0x14, b"response").unwrap();
+        peer.send(frame).await.unwrap();
+        assert_eq!(remote_session.read_message_from(&mut remote).await.unwrap(), (0x14, b"response".to_vec()));
+        
+        // The peer is done once the remote hangs up
+        drop(remote);
+        assert!(serving.await.unwrap().is_err());
+        assert_eq!(*peer.state.read().await, PeerState::Disconnected);
+    }
+}
@@ -1,7 +1,11 @@
 // use ethereum_types::{H256, U256}; // Unused imports
 // use ethereum_rlp::{Encode, Decode}; // Unused imports
+use ethereum_types::H512;
 use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+use crate::tx_gossip::{GossipPool, TxGossip};
 use crate::Result;
 
 pub const ETH_PROTOCOL_VERSION: u8 = 68;
@@ -60,22 +64,32 @@ impl fmt::Display for Capability {
 
 pub struct ProtocolHandler {
     pub protocol: Protocol,
+    tx_gossip: Option<(Arc<Mutex<TxGossip>>, Arc<dyn GossipPool>)>,
 }
 
 impl ProtocolHandler {
     pub fn new(protocol: Protocol) -> Self {
-        Self { protocol }
+        Self { protocol, tx_gossip: None }
     }
     
-    pub fn handle_message(&self, msg_id: u8, data: &[u8]) -> Result<()> {
+    /// Hand transaction announcements, requests and responses to `gossip`,
+    /// fetching for and serving from `pool`
+    pub fn with_tx_gossip(mut self, gossip: Arc<Mutex<TxGossip>>, pool: Arc<dyn GossipPool>) -> Self {
+        self.tx_gossip = Some((gossip, pool));
+        self
+    }
+    
+    /// Handle a message from `peer`, returning the response id and payload
+    /// to send back, if any
+    pub fn handle_message(&self, peer: H512, msg_id: u8, data: &[u8]) -> Result<Option<(u8, Vec<u8>)>> {
         match self.protocol.name.as_str() {
-            "eth" => self.handle_eth_message(msg_id, data),
+            "eth" => self.handle_eth_message(peer, msg_id, data),
             "snap" => self.handle_snap_message(msg_id, data),
-            _ => Ok(()),
+            _ => Ok(None),
         }
     }
     
-    fn handle_eth_message(&self, msg_id: u8, _data: &[u8]) -> Result<()> {
+    fn handle_eth_message(&self, peer: H512, msg_id: u8, data: &[u8]) -> Result<Option<(u8, Vec<u8>)>> {
         match msg_id {
             0x00 => {}, // Status
             0x01 => {}, // NewBlockHashes
@@ -85,19 +99,24 @@ impl ProtocolHandler {
             0x05 => {}, // GetBlockBodies
             0x06 => {}, // BlockBodies
             0x07 => {}, // NewBlock
-            0x08 => {}, // NewPooledTransactionHashes
-            0x09 => {}, // GetPooledTransactions
-            0x0a => {}, // PooledTransactions
+            // NewPooledTransactionHashes, GetPooledTransactions, PooledTransactions
+            0x08..=0x0a => return self.handle_tx_gossip(peer, msg_id, data),
             0x0d => {}, // GetNodeData
             0x0e => {}, // NodeData
             0x0f => {}, // GetReceipts
             0x10 => {}, // Receipts
             _ => {},
         }
-        Ok(())
+        Ok(None)
+    }
+    
+    fn handle_tx_gossip(&self, peer: H512, msg_id: u8, data: &[u8]) -> Result<Option<(u8, Vec<u8>)>> {
+        let Some((gossip, pool)) = &self.tx_gossip else { return Ok(None) };
+        let mut gossip = gossip.lock().unwrap();
+        gossip.handle_message(peer, msg_id, data, pool.as_ref(), Instant::now())
     }
     
-    fn handle_snap_message(&self, msg_id: u8, _data: &[u8]) -> Result<()> {
+    fn handle_snap_message(&self, msg_id: u8, _data: &[u8]) -> Result<Option<(u8, Vec<u8>)>> {
         match msg_id {
             0x00 => {}, // GetAccountRange
             0x01 => {}, // AccountRange
@@ -109,6 +128,6 @@ impl ProtocolHandler {
             0x07 => {}, // TrieNodes
             _ => {},
         }
-        Ok(())
+        Ok(None)
     }
 }
\ No newline at end of file
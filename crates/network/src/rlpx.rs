@@ -7,16 +7,36 @@ use secp256k1::{PublicKey, SecretKey, Secp256k1, ecdsa::RecoverableSignature};
 use ethereum_types::H256;
 // use ethereum_rlp::{Encode, Decode, Encoder, Decoder}; // Unused imports
 use rand::Rng;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 // use bytes::{Bytes, BytesMut, BufMut}; // Unused imports
 // use std::io; // Unused import
 
 use crate::{Result, NetworkError};
+use crate::messages::HelloMessage;
 
 type Aes256Ctr = Ctr128BE<Aes256>;
 
 const MAC_SIZE: usize = 16;
+
+/// Encrypted frame header followed by its MAC
+pub const FRAME_HEADER_SIZE: usize = 32;
 const PROTOCOL_VERSION: u8 = 5;
 
+/// Largest message payload accepted once decompressed
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// p2p base protocol version advertised in Hello
+pub const P2P_VERSION: u64 = 5;
+
+/// First p2p version that snappy-compresses message payloads
+const SNAPPY_P2P_VERSION: u64 = 5;
+
+/// p2p Hello, exchanged before compression is negotiated and never compressed
+const HELLO_MSG_ID: u8 = 0x00;
+
+/// p2p Disconnect, a peer's answer to a Hello it rejects
+const DISCONNECT_MSG_ID: u8 = 0x01;
+
 pub struct RLPxHandshake {
     pub static_key: SecretKey,
     pub ephemeral_key: SecretKey,
@@ -235,6 +255,7 @@ pub struct RLPxSession {
     secrets: Secrets,
     ingress_aes: Aes256Ctr,
     egress_aes: Aes256Ctr,
+    compression: bool,
 }
 
 impl RLPxSession {
@@ -247,7 +268,131 @@ impl RLPxSession {
             secrets,
             ingress_aes,
             egress_aes,
+            compression: false,
+        }
+    }
+    
+    /// Snappy-compress message payloads from now on
+    ///
+    /// Called once both Hello messages advertise p2p version 5 or later.
+    pub fn enable_compression(&mut self) {
+        self.compression = true;
+    }
+    
+    pub fn compression_enabled(&self) -> bool {
+        self.compression
+    }
+    
+    /// Apply the outcome of the Hello exchange, compressing from now on when
+    /// both sides advertise p2p version 5 or later
+    pub fn handle_hello(&mut self, local: &HelloMessage, remote: &HelloMessage) {
+        if local.protocol_version >= SNAPPY_P2P_VERSION && remote.protocol_version >= SNAPPY_P2P_VERSION {
+            self.enable_compression();
+        }
+    }
+    
+    /// Send `local` over `stream`, then read and apply the peer's Hello
+    pub async fn exchange_hello<S>(&mut self, stream: &mut S, local: &HelloMessage) -> Result<HelloMessage>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let frame = self.write_message(HELLO_MSG_ID, &local.encode())?;
+        stream.write_all(&frame).await?;
+        
+        let (msg_id, payload) = self.read_message_from(stream).await?;
+        let remote = match msg_id {
+            HELLO_MSG_ID => HelloMessage::decode(&payload)?,
+            DISCONNECT_MSG_ID => {
+                return Err(NetworkError::PeerDisconnected("Disconnected during Hello".to_string()));
+            }
+            id => {
+                return Err(NetworkError::ProtocolError(format!("Expected Hello, got message {:#x}", id)));
+            }
+        };
+        
+        self.handle_hello(local, &remote);
+        Ok(remote)
+    }
+    
+    /// Read one whole frame off `stream` and decode it as `read_message` does
+    pub async fn read_message_from<R>(&mut self, stream: &mut R) -> Result<(u8, Vec<u8>)>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut frame = vec![0u8; FRAME_HEADER_SIZE];
+        stream.read_exact(&mut frame).await?;
+        frame.resize(self.frame_len(&frame)?, 0);
+        stream.read_exact(&mut frame[FRAME_HEADER_SIZE..]).await?;
+        self.read_message(&frame)
+    }
+    
+    /// Length of the whole frame starting with the `FRAME_HEADER_SIZE` bytes
+    /// of `header`, read without advancing the ingress cipher
+    pub fn frame_len(&self, header: &[u8]) -> Result<usize> {
+        if header.len() < FRAME_HEADER_SIZE {
+            return Err(NetworkError::InvalidMessage("Frame too short".to_string()));
+        }
+        
+        let mut size = [0u8; 16];
+        size.copy_from_slice(&header[..16]);
+        self.ingress_aes.clone().apply_keystream(&mut size);
+        let frame_size = u32::from_be_bytes([0, size[0], size[1], size[2]]) as usize;
+        if frame_size > MAX_MESSAGE_SIZE {
+            return Err(NetworkError::InvalidMessage(format!(
+                "Frame of {} bytes exceeds limit", frame_size
+            )));
+        }
+        
+        Ok(FRAME_HEADER_SIZE + frame_size + MAC_SIZE)
+    }
+    
+    /// Frame a message: RLP message id followed by the payload, which is
+    /// compressed when compression is enabled
+    pub fn write_message(&mut self, msg_id: u8, payload: &[u8]) -> Result<Vec<u8>> {
+        if payload.len() > MAX_MESSAGE_SIZE {
+            return Err(NetworkError::InvalidMessage(format!(
+                "Message of {} bytes exceeds limit", payload.len()
+            )));
+        }
+        
+        let mut data = encode_msg_id(msg_id);
+        if self.compresses(msg_id) {
+            let compressed = snap::raw::Encoder::new().compress_vec(payload)
+                .map_err(|e| NetworkError::InvalidMessage(format!("Snappy compression failed: {}", e)))?;
+            data.extend_from_slice(&compressed);
+        } else {
+            data.extend_from_slice(payload);
+        }
+        
+        self.write_frame(&data)
+    }
+    
+    /// Read a frame written by `write_message`, returning the message id and
+    /// decompressed payload
+    pub fn read_message(&mut self, frame: &[u8]) -> Result<(u8, Vec<u8>)> {
+        let data = self.read_frame(frame)?;
+        let (msg_id, payload) = decode_msg_id(&data)?;
+        
+        if !self.compresses(msg_id) {
+            return Ok((msg_id, payload.to_vec()));
+        }
+        
+        // The claimed size is checked before allocating anything for it
+        let size = snap::raw::decompress_len(payload)
+            .map_err(|e| NetworkError::InvalidMessage(format!("Invalid snappy payload: {}", e)))?;
+        if size > MAX_MESSAGE_SIZE {
+            return Err(NetworkError::InvalidMessage(format!(
+                "Decompressed size {} exceeds limit", size
+            )));
         }
+        
+        let payload = snap::raw::Decoder::new().decompress_vec(payload)
+            .map_err(|e| NetworkError::InvalidMessage(format!("Snappy decompression failed: {}", e)))?;
+        Ok((msg_id, payload))
+    }
+    
+    fn compresses(&self, msg_id: u8) -> bool {
+        self.compression && msg_id != HELLO_MSG_ID
     }
     
     pub fn write_frame(&mut self, data: &[u8]) -> Result<Vec<u8>> {
@@ -393,10 +538,145 @@ fn ecies_decrypt(private_key: &SecretKey, data: &[u8]) -> Result<Vec<u8>> {
     Ok(decrypted)
 }
 
+/// Message ids are RLP integers: zero is the empty string, ids below 0x80
+/// are a single byte
+fn encode_msg_id(msg_id: u8) -> Vec<u8> {
+    match msg_id {
+        0 => vec![0x80],
+        id if id < 0x80 => vec![id],
+        id => vec![0x81, id],
+    }
+}
+
+fn decode_msg_id(data: &[u8]) -> Result<(u8, &[u8])> {
+    match data {
+        [0x80, rest @ ..] => Ok((0, rest)),
+        [id, rest @ ..] if *id < 0x80 => Ok((*id, rest)),
+        [0x81, id, rest @ ..] if *id >= 0x80 => Ok((*id, rest)),
+        _ => Err(NetworkError::InvalidMessage("Invalid message id".to_string())),
+    }
+}
+
 fn xor_bytes(a: &[u8], b: &[u8]) -> Vec<u8> {
     a.iter().zip(b.iter()).map(|(x, y)| x ^ y).collect()
 }
 
 fn keccak256(data: &[u8]) -> [u8; 32] {
     ethereum_crypto::keccak256(data).into()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_rlp::Encoder;
+    
+    /// BlockBodies in eth/66+, offset past the p2p message ids
+    const BLOCK_BODIES_MSG_ID: u8 = 0x16;
+    
+    /// Two sessions whose egress and ingress state mirror each other, before
+    /// Hello
+    fn uncompressed_pair() -> (RLPxSession, RLPxSession) {
+        let secrets = || Secrets {
+            aes_secret: H256::repeat_byte(0x11),
+            mac_secret: H256::repeat_byte(0x22),
+            egress_mac: Hmac::new_from_slice(&[0x33; 32]).unwrap(),
+            ingress_mac: Hmac::new_from_slice(&[0x33; 32]).unwrap(),
+        };
+        (RLPxSession::new(secrets()), RLPxSession::new(secrets()))
+    }
+    
+    fn session_pair() -> (RLPxSession, RLPxSession) {
+        let (mut sender, mut receiver) = uncompressed_pair();
+        sender.enable_compression();
+        receiver.enable_compression();
+        (sender, receiver)
+    }
+    
+    fn block_bodies(count: usize) -> Vec<u8> {
+        // Each body is [transactions, uncles], both empty here
+        let mut bodies = Encoder::new();
+        bodies.encode_list_payload(&[0xc2, 0xc0, 0xc0].repeat(count));
+        
+        let mut fields = Encoder::new();
+        fields.encode_u64(7); // request id
+        let mut payload = fields.finish();
+        payload.extend_from_slice(&bodies.finish());
+        
+        let mut message = Encoder::new();
+        message.encode_list_payload(&payload);
+        message.finish()
+    }
+    
+    #[test]
+    fn test_compressed_message_roundtrip() {
+        let (mut sender, mut receiver) = session_pair();
+        let payload = block_bodies(256);
+        
+        let frame = sender.write_message(BLOCK_BODIES_MSG_ID, &payload).unwrap();
+        assert!(frame.len() < payload.len());
+        
+        let (msg_id, decoded) = receiver.read_message(&frame).unwrap();
+        assert_eq!(msg_id, BLOCK_BODIES_MSG_ID);
+        assert_eq!(decoded, payload);
+        
+        // Hello goes out uncompressed even once compression is on
+        let hello = vec![0xc5, 0x05, 0x83, b'a', b'b', b'c'];
+        let frame = sender.write_message(HELLO_MSG_ID, &hello).unwrap();
+        assert_eq!(receiver.read_message(&frame).unwrap(), (HELLO_MSG_ID, hello));
+    }
+    
+    fn hello(protocol_version: u64, client_id: &str) -> HelloMessage {
+        HelloMessage {
+            protocol_version,
+            client_id: client_id.to_string(),
+            capabilities: vec![crate::protocol::Capability::eth()],
+            listen_port: 30303,
+            node_id: ethereum_types::H512::repeat_byte(0x44),
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_hello_negotiates_compression() {
+        for (local_version, remote_version, compressed) in [(5, 5, true), (5, 6, true), (5, 4, false)] {
+            let (mut local, mut remote) = uncompressed_pair();
+            let (mut local_io, mut remote_io) = tokio::io::duplex(4096);
+            let local_hello = hello(local_version, "local");
+            let remote_hello = hello(remote_version, "remote");
+            
+            let (from_remote, from_local) = tokio::join!(
+                local.exchange_hello(&mut local_io, &local_hello),
+                remote.exchange_hello(&mut remote_io, &remote_hello),
+            );
+            let (from_remote, from_local) = (from_remote.unwrap(), from_local.unwrap());
+            assert_eq!(from_remote.client_id, "remote");
+            assert_eq!(from_local.protocol_version, local_version);
+            assert_eq!(from_local.node_id, local_hello.node_id);
+            assert_eq!(local.compression_enabled(), compressed);
+            assert_eq!(remote.compression_enabled(), compressed);
+            
+            // Later messages use whatever was negotiated
+            let payload = block_bodies(64);
+            let frame = local.write_message(BLOCK_BODIES_MSG_ID, &payload).unwrap();
+            assert_eq!(frame.len() < payload.len(), compressed);
+            tokio::io::AsyncWriteExt::write_all(&mut local_io, &frame).await.unwrap();
+            let (msg_id, decoded) = remote.read_message_from(&mut remote_io).await.unwrap();
+            assert_eq!((msg_id, decoded), (BLOCK_BODIES_MSG_ID, payload));
+        }
+    }
+    
+    #[test]
+    fn test_rejects_decompression_bomb() {
+        let (mut sender, mut receiver) = session_pair();
+        
+        // Snappy preamble claiming 1 GiB of output
+        let mut data = encode_msg_id(BLOCK_BODIES_MSG_ID);
+        data.extend_from_slice(&[0x80, 0x80, 0x80, 0x80, 0x04]);
+        data.extend_from_slice(&[0u8; 16]);
+        let frame = sender.write_frame(&data).unwrap();
+        
+        assert!(matches!(
+            receiver.read_message(&frame),
+            Err(NetworkError::InvalidMessage(_))
+        ));
+    }
+}
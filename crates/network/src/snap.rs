@@ -0,0 +1,585 @@
+use ethereum_crypto::keccak256;
+use ethereum_types::H256;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::wire::{
+    any_list, bytes_of, decode_hashes, decode_item, decode_proof, encode_hashes, encode_proof,
+    hash_of, list_of, rlp_bytes, rlp_list, rlp_u64, u64_of,
+};
+use crate::{NetworkError, Result};
+
+pub const GET_ACCOUNT_RANGE: u8 = 0x00;
+pub const ACCOUNT_RANGE: u8 = 0x01;
+pub const GET_STORAGE_RANGES: u8 = 0x02;
+pub const STORAGE_RANGES: u8 = 0x03;
+pub const GET_BYTE_CODES: u8 = 0x04;
+pub const BYTE_CODES: u8 = 0x05;
+pub const GET_TRIE_NODES: u8 = 0x06;
+pub const TRIE_NODES: u8 = 0x07;
+
+/// Upper bound on a response regardless of what the peer asks for
+pub const MAX_RESPONSE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Highest possible account or slot hash
+const MAX_HASH: [u8; 32] = [0xff; 32];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetAccountRange {
+    pub request_id: u64,
+    pub root_hash: H256,
+    pub origin: H256,
+    pub limit: H256,
+    pub response_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountData {
+    pub hash: H256,
+    /// Account RLP as stored in the state trie
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountRange {
+    pub request_id: u64,
+    pub accounts: Vec<AccountData>,
+    pub proof: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetStorageRanges {
+    pub request_id: u64,
+    pub root_hash: H256,
+    pub account_hashes: Vec<H256>,
+    /// Only applies to the first account, empty means from the start
+    pub origin: Vec<u8>,
+    /// Only applies to the first account, empty means up to the end
+    pub limit: Vec<u8>,
+    pub response_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageData {
+    pub hash: H256,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageRanges {
+    pub request_id: u64,
+    pub slots: Vec<Vec<StorageData>>,
+    /// Boundary proof for the last account, only when its range is partial
+    pub proof: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetByteCodes {
+    pub request_id: u64,
+    pub hashes: Vec<H256>,
+    pub response_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteCodes {
+    pub request_id: u64,
+    pub codes: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetTrieNodes {
+    pub request_id: u64,
+    pub root_hash: H256,
+    /// Compact encoded paths: an account trie path on its own, or an account
+    /// hash followed by paths into that account's storage trie
+    pub paths: Vec<Vec<Vec<u8>>>,
+    pub response_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrieNodes {
+    pub request_id: u64,
+    pub nodes: Vec<Vec<u8>>,
+}
+
+impl GetAccountRange {
+    pub fn encode(&self) -> Vec<u8> {
+        rlp_list(&[
+            rlp_u64(self.request_id),
+            rlp_bytes(self.root_hash.as_bytes()),
+            rlp_bytes(self.origin.as_bytes()),
+            rlp_bytes(self.limit.as_bytes()),
+            rlp_u64(self.response_bytes),
+        ])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 5)?;
+        Ok(Self {
+            request_id: u64_of(&fields[0])?,
+            root_hash: hash_of(&fields[1])?,
+            origin: hash_of(&fields[2])?,
+            limit: hash_of(&fields[3])?,
+            response_bytes: u64_of(&fields[4])?,
+        })
+    }
+}
+
+impl AccountRange {
+    pub fn encode(&self) -> Vec<u8> {
+        let accounts: Vec<Vec<u8>> = self.accounts
+            .iter()
+            .map(|account| rlp_list(&[rlp_bytes(account.hash.as_bytes()), rlp_bytes(&account.body)]))
+            .collect();
+        rlp_list(&[rlp_u64(self.request_id), rlp_list(&accounts), encode_proof(&self.proof)])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 3)?;
+        let accounts = any_list(&fields[1])?
+            .iter()
+            .map(|account| {
+                let pair = list_of(account, 2)?;
+                Ok(AccountData { hash: hash_of(&pair[0])?, body: bytes_of(&pair[1])? })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            request_id: u64_of(&fields[0])?,
+            accounts,
+            proof: decode_proof(&fields[2])?,
+        })
+    }
+}
+
+impl GetStorageRanges {
+    pub fn encode(&self) -> Vec<u8> {
+        rlp_list(&[
+            rlp_u64(self.request_id),
+            rlp_bytes(self.root_hash.as_bytes()),
+            encode_hashes(&self.account_hashes),
+            rlp_bytes(&self.origin),
+            rlp_bytes(&self.limit),
+            rlp_u64(self.response_bytes),
+        ])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 6)?;
+        Ok(Self {
+            request_id: u64_of(&fields[0])?,
+            root_hash: hash_of(&fields[1])?,
+            account_hashes: decode_hashes(&fields[2])?,
+            origin: bytes_of(&fields[3])?,
+            limit: bytes_of(&fields[4])?,
+            response_bytes: u64_of(&fields[5])?,
+        })
+    }
+}
+
+impl StorageRanges {
+    pub fn encode(&self) -> Vec<u8> {
+        let slots: Vec<Vec<u8>> = self.slots
+            .iter()
+            .map(|account_slots| {
+                let slots: Vec<Vec<u8>> = account_slots
+                    .iter()
+                    .map(|slot| rlp_list(&[rlp_bytes(slot.hash.as_bytes()), rlp_bytes(&slot.data)]))
+                    .collect();
+                rlp_list(&slots)
+            })
+            .collect();
+        rlp_list(&[rlp_u64(self.request_id), rlp_list(&slots), encode_proof(&self.proof)])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 3)?;
+        let slots = any_list(&fields[1])?
+            .iter()
+            .map(|account_slots| {
+                any_list(account_slots)?
+                    .iter()
+                    .map(|slot| {
+                        let pair = list_of(slot, 2)?;
+                        Ok(StorageData { hash: hash_of(&pair[0])?, data: bytes_of(&pair[1])? })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            request_id: u64_of(&fields[0])?,
+            slots,
+            proof: decode_proof(&fields[2])?,
+        })
+    }
+}
+
+impl GetByteCodes {
+    pub fn encode(&self) -> Vec<u8> {
+        rlp_list(&[rlp_u64(self.request_id), encode_hashes(&self.hashes), rlp_u64(self.response_bytes)])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 3)?;
+        Ok(Self {
+            request_id: u64_of(&fields[0])?,
+            hashes: decode_hashes(&fields[1])?,
+            response_bytes: u64_of(&fields[2])?,
+        })
+    }
+}
+
+impl ByteCodes {
+    pub fn encode(&self) -> Vec<u8> {
+        rlp_list(&[rlp_u64(self.request_id), encode_proof(&self.codes)])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 2)?;
+        Ok(Self { request_id: u64_of(&fields[0])?, codes: decode_proof(&fields[1])? })
+    }
+}
+
+impl GetTrieNodes {
+    pub fn encode(&self) -> Vec<u8> {
+        let paths: Vec<Vec<u8>> = self.paths.iter().map(|set| encode_proof(set)).collect();
+        rlp_list(&[
+            rlp_u64(self.request_id),
+            rlp_bytes(self.root_hash.as_bytes()),
+            rlp_list(&paths),
+            rlp_u64(self.response_bytes),
+        ])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 4)?;
+        Ok(Self {
+            request_id: u64_of(&fields[0])?,
+            root_hash: hash_of(&fields[1])?,
+            paths: any_list(&fields[2])?.iter().map(decode_proof).collect::<Result<_>>()?,
+            response_bytes: u64_of(&fields[3])?,
+        })
+    }
+}
+
+impl TrieNodes {
+    pub fn encode(&self) -> Vec<u8> {
+        rlp_list(&[rlp_u64(self.request_id), encode_proof(&self.nodes)])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = decode_item(data)?;
+        let fields = list_of(&item, 2)?;
+        Ok(Self { request_id: u64_of(&fields[0])?, nodes: decode_proof(&fields[1])? })
+    }
+}
+
+/// Local state the snap server answers from
+///
+/// Implemented by the node over its trie database, so the network layer
+/// doesn't depend on storage or trie internals. Tries are addressed by
+/// their root hash.
+pub trait SnapState: Send + Sync {
+    /// Whether the trie rooted at `root` is available
+    fn has_root(&self, root: H256) -> Result<bool>;
+
+    /// Value stored under `key` in the trie at `root`
+    fn get(&self, root: H256, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Entries of the trie at `root` from `origin` up to `limit`, in key
+    /// order, stopping once `byte_limit` bytes are collected
+    fn range(&self, root: H256, origin: &[u8], limit: &[u8], byte_limit: usize) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    /// Nodes proving `key` or its absence in the trie at `root`
+    fn prove(&self, root: H256, key: &[u8]) -> Result<Vec<Vec<u8>>>;
+
+    /// Encoded node reached by the compact encoded `path` in the trie at
+    /// `root`, `None` if the path ends inside a leaf, an extension or an
+    /// empty slot
+    fn node_at_path(&self, root: H256, path: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Contract code by hash
+    fn code(&self, hash: H256) -> Result<Option<Vec<u8>>>;
+}
+
+/// Answers snap/1 requests from the local state
+///
+/// Ranges are cut at the requested response size (capped at
+/// `MAX_RESPONSE_BYTES`) and carry boundary proofs so the peer can check
+/// them against the state root. Requests for a root we don't have get an
+/// empty response.
+pub struct SnapServer {
+    state: Arc<dyn SnapState>,
+}
+
+impl SnapServer {
+    pub fn new(state: Arc<dyn SnapState>) -> Self {
+        Self { state }
+    }
+
+    /// Handle a snap request message, returning the response id and payload
+    ///
+    /// Response messages are not handled here and yield `None`.
+    pub fn handle_message(&self, msg_id: u8, data: &[u8]) -> Result<Option<(u8, Vec<u8>)>> {
+        let response = match msg_id {
+            GET_ACCOUNT_RANGE => {
+                let request = GetAccountRange::decode(data)?;
+                (ACCOUNT_RANGE, self.get_account_range(&request)?.encode())
+            }
+            GET_STORAGE_RANGES => {
+                let request = GetStorageRanges::decode(data)?;
+                (STORAGE_RANGES, self.get_storage_ranges(&request)?.encode())
+            }
+            GET_BYTE_CODES => {
+                let request = GetByteCodes::decode(data)?;
+                (BYTE_CODES, self.get_byte_codes(&request)?.encode())
+            }
+            GET_TRIE_NODES => {
+                let request = GetTrieNodes::decode(data)?;
+                (TRIE_NODES, self.get_trie_nodes(&request)?.encode())
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(response))
+    }
+
+    pub fn get_account_range(&self, request: &GetAccountRange) -> Result<AccountRange> {
+        let mut response = AccountRange { request_id: request.request_id, accounts: Vec::new(), proof: Vec::new() };
+
+        let root = match self.open(request.root_hash)? {
+            Some(root) => root,
+            None => return Ok(response),
+        };
+
+        let entries = self.serve_range(
+            root,
+            request.origin.as_bytes(),
+            request.limit.as_bytes(),
+            response_limit(request.response_bytes),
+        )?;
+        response.proof = self.boundary_proof(root, request.origin.as_bytes(), entries.last().map(|(key, _)| key.as_slice()))?;
+        response.accounts = entries
+            .into_iter()
+            .map(|(key, body)| AccountData { hash: H256::from_slice(&key), body })
+            .collect();
+
+        debug!("Serving {} accounts from {:?}", response.accounts.len(), request.origin);
+        Ok(response)
+    }
+
+    pub fn get_storage_ranges(&self, request: &GetStorageRanges) -> Result<StorageRanges> {
+        let mut response = StorageRanges { request_id: request.request_id, slots: Vec::new(), proof: Vec::new() };
+
+        let accounts = match self.open(request.root_hash)? {
+            Some(root) => root,
+            None => return Ok(response),
+        };
+
+        let budget = response_limit(request.response_bytes);
+        let mut served = 0;
+
+        for (index, account_hash) in request.account_hashes.iter().enumerate() {
+            if served >= budget {
+                break;
+            }
+
+            let storage = match self.storage_root(accounts, account_hash)? {
+                Some(root) => root,
+                None => {
+                    response.slots.push(Vec::new());
+                    continue;
+                }
+            };
+
+            // The requested bounds only apply to the first account
+            let origin = match index {
+                0 => pad_hash(&request.origin, 0x00),
+                _ => [0u8; 32],
+            };
+            let limit = match index {
+                0 if !request.limit.is_empty() => pad_hash(&request.limit, 0xff),
+                _ => MAX_HASH,
+            };
+
+            let entries = self.serve_range(storage, &origin, &limit, budget - served)?;
+            served += entries.iter().map(|(key, value)| key.len() + value.len()).sum::<usize>();
+
+            // A range that may not reach the end of the storage trie is proven,
+            // and nothing is served after it
+            let partial = origin != [0u8; 32] || served >= budget || limit != MAX_HASH;
+            if partial {
+                response.proof = self.boundary_proof(storage, &origin, entries.last().map(|(key, _)| key.as_slice()))?;
+            }
+
+            response.slots.push(
+                entries
+                    .into_iter()
+                    .map(|(key, data)| StorageData { hash: H256::from_slice(&key), data })
+                    .collect(),
+            );
+
+            if partial {
+                break;
+            }
+        }
+
+        Ok(response)
+    }
+
+    pub fn get_byte_codes(&self, request: &GetByteCodes) -> Result<ByteCodes> {
+        let budget = response_limit(request.response_bytes);
+        let mut codes = Vec::new();
+        let mut served = 0;
+
+        for hash in &request.hashes {
+            if served >= budget {
+                break;
+            }
+            // Unknown codes are left out, the peer rerequests them elsewhere
+            if let Some(code) = self.state.code(*hash)? {
+                served += code.len();
+                codes.push(code);
+            }
+        }
+
+        Ok(ByteCodes { request_id: request.request_id, codes })
+    }
+
+    pub fn get_trie_nodes(&self, request: &GetTrieNodes) -> Result<TrieNodes> {
+        let mut response = TrieNodes { request_id: request.request_id, nodes: Vec::new() };
+
+        let accounts = match self.open(request.root_hash)? {
+            Some(root) => root,
+            None => return Ok(response),
+        };
+
+        let budget = response_limit(request.response_bytes);
+        let mut served = 0;
+
+        // Nodes are matched to paths by position, so serving stops at the
+        // first one that isn't available
+        'paths: for set in &request.paths {
+            let (account_path, storage_paths) = match set.split_first() {
+                Some(split) => split,
+                None => continue,
+            };
+
+            let nodes = if storage_paths.is_empty() {
+                vec![self.state.node_at_path(accounts, account_path)?]
+            } else {
+                let account_hash = match full_path_hash(account_path) {
+                    Some(hash) => hash,
+                    None => break,
+                };
+                let storage = match self.storage_root(accounts, &account_hash)? {
+                    Some(root) => root,
+                    None => break,
+                };
+                storage_paths
+                    .iter()
+                    .map(|path| self.state.node_at_path(storage, path))
+                    .collect::<Result<Vec<_>>>()?
+            };
+
+            for node in nodes {
+                match node {
+                    Some(node) if served < budget => {
+                        served += node.len();
+                        response.nodes.push(node);
+                    }
+                    _ => break 'paths,
+                }
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// `root` if its trie is available
+    fn open(&self, root: H256) -> Result<Option<H256>> {
+        if self.state.has_root(root)? {
+            Ok(Some(root))
+        } else {
+            debug!("Snap request for unavailable state root {:?}", root);
+            Ok(None)
+        }
+    }
+
+    /// Storage root of an account, `None` for unknown accounts and empty storage
+    fn storage_root(&self, accounts: H256, account_hash: &H256) -> Result<Option<H256>> {
+        let body = match self.state.get(accounts, account_hash.as_bytes())? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+
+        // Account RLP: [nonce, balance, storage_root, code_hash]
+        let item = decode_item(&body)?;
+        let storage_root = hash_of(&list_of(&item, 4)?[2])?;
+        if storage_root == empty_root() {
+            return Ok(None);
+        }
+
+        self.open(storage_root)
+    }
+
+    /// Entries from `origin` up to and including the first key at or past
+    /// `limit`, within `byte_limit`
+    fn serve_range(&self, root: H256, origin: &[u8], limit: &[u8], byte_limit: usize) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = self.state.range(root, origin, &MAX_HASH, byte_limit)?;
+        if let Some(past_limit) = entries.iter().position(|(key, _)| key.as_slice() >= limit) {
+            entries.truncate(past_limit + 1);
+        }
+        Ok(entries)
+    }
+
+    /// Proof nodes for the origin and the last served key, without duplicates
+    fn boundary_proof(&self, root: H256, origin: &[u8], last: Option<&[u8]>) -> Result<Vec<Vec<u8>>> {
+        let mut seen = HashSet::new();
+        let mut proof = Vec::new();
+
+        for key in std::iter::once(origin).chain(last) {
+            for node in self.state.prove(root, key)? {
+                if seen.insert(node.clone()) {
+                    proof.push(node);
+                }
+            }
+        }
+
+        Ok(proof)
+    }
+}
+
+fn response_limit(requested: u64) -> usize {
+    requested.min(MAX_RESPONSE_BYTES) as usize
+}
+
+/// Extend a possibly short bound to a full 32-byte hash
+fn pad_hash(bytes: &[u8], fill: u8) -> [u8; 32] {
+    let mut hash = [fill; 32];
+    let len = bytes.len().min(32);
+    hash[..len].copy_from_slice(&bytes[..len]);
+    hash
+}
+
+/// Hash spelled out by a compact encoded path of all 64 nibbles
+///
+/// An even-length path is a flag byte with a zero low nibble followed by
+/// the packed nibbles.
+fn full_path_hash(path: &[u8]) -> Option<H256> {
+    match path.split_first() {
+        Some((flag, hash)) if flag & 0x1f == 0 && hash.len() == 32 => Some(H256::from_slice(hash)),
+        _ => None,
+    }
+}
+
+/// Root of the empty trie, `keccak256(rlp(""))`
+fn empty_root() -> H256 {
+    keccak256(&[0x80])
+}
@@ -0,0 +1,433 @@
+use ethereum_core::Transaction;
+use ethereum_types::{H256, H512};
+use rand::seq::SliceRandom;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::messages::{
+    GetPooledTransactionsMessage, NewPooledTransactionHashesMessage, PooledTransactionsMessage,
+    GET_POOLED_TRANSACTIONS, NEW_POOLED_TRANSACTION_HASHES, POOLED_TRANSACTIONS,
+};
+use crate::{NetworkError, Result};
+
+/// Hashes remembered per peer, the oldest are forgotten first
+pub const MAX_KNOWN_TXS: usize = 32_768;
+
+/// Hashes asked for in a single `GetPooledTransactions`
+pub const MAX_TX_REQUEST: usize = 256;
+
+/// Soft limit on a `PooledTransactions` response, the transaction that
+/// crosses it is still included
+pub const SOFT_RESPONSE_LIMIT: usize = 2 * 1024 * 1024;
+
+/// How long a request may go unanswered before its hashes can be fetched
+/// from another peer that announced them
+pub const TX_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Transaction pool as seen by the gossip
+pub trait GossipPool: Send + Sync {
+    /// Whether the pool already holds `hash`
+    fn contains(&self, hash: &H256) -> bool;
+
+    /// Pooled transaction by hash, to serve to peers
+    fn transaction(&self, hash: &H256) -> Option<Transaction>;
+
+    /// Hand transactions fetched from peers to the pool
+    fn import(&self, txs: Vec<Transaction>);
+}
+
+/// Transaction propagation over eth/68
+///
+/// New pool transactions are announced by hash to a random subset of the
+/// peers that don't know them yet. Announced hashes the pool doesn't have are
+/// fetched with `GetPooledTransactions`, one request in flight per hash, and
+/// only transactions that were asked for are accepted from the responses.
+///
+/// The gossip only decides what to send to whom; framing and delivery are
+/// left to the caller.
+#[derive(Debug)]
+pub struct TxGossip {
+    known: HashMap<H512, KnownHashes>,
+    /// Hashes with a request in flight
+    fetching: HashSet<H256>,
+    requests: HashMap<u64, PendingRequest>,
+    next_request_id: u64,
+}
+
+#[derive(Debug, Default)]
+struct KnownHashes {
+    hashes: HashSet<H256>,
+    order: VecDeque<H256>,
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    peer: H512,
+    hashes: Vec<H256>,
+    sent_at: Instant,
+}
+
+impl TxGossip {
+    pub fn new() -> Self {
+        Self {
+            known: HashMap::new(),
+            fetching: HashSet::new(),
+            requests: HashMap::new(),
+            next_request_id: 0,
+        }
+    }
+
+    /// Announcements of `txs` for a subset of `peers`, at most one message per peer
+    ///
+    /// Roughly the square root of the peers that don't know a transaction
+    /// hear about it, the rest learn of it from those peers in turn.
+    pub fn announce(&mut self, txs: &[Transaction], peers: &[H512]) -> Vec<(H512, NewPooledTransactionHashesMessage)> {
+        let mut announcements: HashMap<H512, NewPooledTransactionHashesMessage> = HashMap::new();
+
+        for tx in txs {
+            let hash = tx.hash();
+            let unaware: Vec<H512> = peers
+                .iter()
+                .filter(|peer| !self.knows(peer, &hash))
+                .copied()
+                .collect();
+            let count = (unaware.len() as f64).sqrt().ceil() as usize;
+
+            for peer in unaware.choose_multiple(&mut rand::thread_rng(), count) {
+                self.mark_known(*peer, hash);
+                let message = announcements.entry(*peer).or_insert_with(|| NewPooledTransactionHashesMessage {
+                    types: Vec::new(),
+                    sizes: Vec::new(),
+                    hashes: Vec::new(),
+                });
+                message.types.push(tx.tx_type());
+                message.sizes.push(tx.size() as u32);
+                message.hashes.push(hash);
+            }
+        }
+
+        announcements.into_iter().collect()
+    }
+
+    /// Handle an announcement from `peer`, returning the request for the
+    /// hashes neither the pool (`is_known`) nor an earlier request covers
+    pub fn on_announcement<F>(
+        &mut self,
+        peer: H512,
+        message: &NewPooledTransactionHashesMessage,
+        is_known: F,
+        now: Instant,
+    ) -> Option<GetPooledTransactionsMessage>
+    where
+        F: Fn(&H256) -> bool,
+    {
+        let mut wanted = Vec::new();
+        for hash in &message.hashes {
+            self.mark_known(peer, *hash);
+            if wanted.len() < MAX_TX_REQUEST && !is_known(hash) && !self.fetching.contains(hash) {
+                wanted.push(*hash);
+            }
+        }
+        if wanted.is_empty() {
+            return None;
+        }
+
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        for hash in &wanted {
+            self.fetching.insert(*hash);
+        }
+        self.requests.insert(request_id, PendingRequest { peer, hashes: wanted.clone(), sent_at: now });
+
+        Some(GetPooledTransactionsMessage { request_id, hashes: wanted })
+    }
+
+    /// Handle a response from `peer`, returning the transactions it was asked for
+    ///
+    /// Hashes the peer left out can be fetched from other announcers again.
+    pub fn on_pooled_transactions(&mut self, peer: H512, message: &PooledTransactionsMessage) -> Result<Vec<Transaction>> {
+        if self.requests.get(&message.request_id).map(|request| request.peer) != Some(peer) {
+            return Err(NetworkError::ProtocolError(format!(
+                "unsolicited PooledTransactions with request id {}", message.request_id
+            )));
+        }
+        let request = self.requests.remove(&message.request_id).unwrap();
+        for hash in &request.hashes {
+            self.fetching.remove(hash);
+        }
+
+        let requested: HashSet<H256> = request.hashes.into_iter().collect();
+        let mut transactions = Vec::new();
+        for encoded in &message.transactions {
+            let tx: Transaction = ethereum_rlp::decode(encoded)?;
+            let hash = tx.hash();
+            if requested.contains(&hash) {
+                self.mark_known(peer, hash);
+                transactions.push(tx);
+            }
+        }
+        Ok(transactions)
+    }
+
+    /// Handle an inbound eth message from `peer`, returning the response id
+    /// and payload to send back
+    ///
+    /// Announcements are answered with a request for the hashes `pool`
+    /// lacks, requests with the transactions it holds, and fetched
+    /// transactions are imported into it. Other messages yield `None`.
+    pub fn handle_message(
+        &mut self,
+        peer: H512,
+        msg_id: u8,
+        data: &[u8],
+        pool: &dyn GossipPool,
+        now: Instant,
+    ) -> Result<Option<(u8, Vec<u8>)>> {
+        match msg_id {
+            NEW_POOLED_TRANSACTION_HASHES => {
+                let message = NewPooledTransactionHashesMessage::decode(data)?;
+                let request = self.on_announcement(peer, &message, |hash| pool.contains(hash), now);
+                Ok(request.map(|request| (GET_POOLED_TRANSACTIONS, request.encode())))
+            }
+            GET_POOLED_TRANSACTIONS => {
+                let request = GetPooledTransactionsMessage::decode(data)?;
+                let response = pooled_transactions(&request, |hash| pool.transaction(hash));
+                Ok(Some((POOLED_TRANSACTIONS, response.encode())))
+            }
+            POOLED_TRANSACTIONS => {
+                let message = PooledTransactionsMessage::decode(data)?;
+                let transactions = self.on_pooled_transactions(peer, &message)?;
+                if !transactions.is_empty() {
+                    pool.import(transactions);
+                }
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Give up on requests older than `TX_FETCH_TIMEOUT`, returning how many expired
+    pub fn expire_requests(&mut self, now: Instant) -> usize {
+        let expired: Vec<u64> = self.requests
+            .iter()
+            .filter(|(_, request)| now.saturating_duration_since(request.sent_at) >= TX_FETCH_TIMEOUT)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &expired {
+            self.release(*id);
+        }
+        expired.len()
+    }
+
+    /// Forget a disconnected peer and release the hashes it was asked for
+    pub fn remove_peer(&mut self, peer: &H512) {
+        self.known.remove(peer);
+        let requests: Vec<u64> = self.requests
+            .iter()
+            .filter(|(_, request)| request.peer == *peer)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in requests {
+            self.release(id);
+        }
+    }
+
+    /// Whether `peer` announced `hash` to us or was told about it
+    pub fn knows(&self, peer: &H512, hash: &H256) -> bool {
+        self.known.get(peer).map_or(false, |known| known.hashes.contains(hash))
+    }
+
+    fn mark_known(&mut self, peer: H512, hash: H256) {
+        let known = self.known.entry(peer).or_default();
+        if known.hashes.insert(hash) {
+            known.order.push_back(hash);
+            if known.order.len() > MAX_KNOWN_TXS {
+                if let Some(oldest) = known.order.pop_front() {
+                    known.hashes.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    fn release(&mut self, request_id: u64) {
+        if let Some(request) = self.requests.remove(&request_id) {
+            for hash in &request.hashes {
+                self.fetching.remove(hash);
+            }
+        }
+    }
+}
+
+impl Default for TxGossip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Answer a `GetPooledTransactions`, skipping hashes `lookup` doesn't find
+pub fn pooled_transactions<F>(request: &GetPooledTransactionsMessage, lookup: F) -> PooledTransactionsMessage
+where
+    F: Fn(&H256) -> Option<Transaction>,
+{
+    let mut transactions = Vec::new();
+    let mut size = 0;
+    for hash in &request.hashes {
+        if size >= SOFT_RESPONSE_LIMIT {
+            break;
+        }
+        if let Some(tx) = lookup(hash) {
+            let encoded = ethereum_rlp::encode(&tx)[..].to_vec();
+            size += encoded.len();
+            transactions.push(encoded);
+        }
+    }
+    PooledTransactionsMessage { request_id: request.request_id, transactions }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::LegacyTransaction;
+    use ethereum_types::{Address, Bytes, U256};
+    use std::sync::Mutex;
+
+    /// Pool holding transactions by hash
+    #[derive(Default)]
+    struct MapPool(Mutex<HashMap<H256, Transaction>>);
+
+    impl GossipPool for MapPool {
+        fn contains(&self, hash: &H256) -> bool {
+            self.0.lock().unwrap().contains_key(hash)
+        }
+
+        fn transaction(&self, hash: &H256) -> Option<Transaction> {
+            self.0.lock().unwrap().get(hash).cloned()
+        }
+
+        fn import(&self, txs: Vec<Transaction>) {
+            self.0.lock().unwrap().extend(txs.into_iter().map(|tx| (tx.hash(), tx)));
+        }
+    }
+
+    fn transfer(nonce: u64) -> Transaction {
+        Transaction::Legacy(LegacyTransaction {
+            nonce: U256::from(nonce),
+            gas_price: U256::from(1_000_000_000u64),
+            gas_limit: U256::from(21_000),
+            to: Some(Address::from_bytes([0x42; 20])),
+            value: U256::from(1),
+            data: Bytes::default(),
+            v: 37,
+            r: U256::one(),
+            s: U256::one(),
+        })
+    }
+
+    #[test]
+    fn test_messages_round_trip() {
+        let tx = transfer(0);
+        let announcement = NewPooledTransactionHashesMessage {
+            types: vec![tx.tx_type()],
+            sizes: vec![tx.size() as u32],
+            hashes: vec![tx.hash()],
+        };
+        assert_eq!(NewPooledTransactionHashesMessage::decode(&announcement.encode()).unwrap(), announcement);
+
+        let request = GetPooledTransactionsMessage { request_id: 7, hashes: vec![tx.hash(), H256::repeat_byte(1)] };
+        assert_eq!(GetPooledTransactionsMessage::decode(&request.encode()).unwrap(), request);
+
+        let response = pooled_transactions(&request, |hash| (*hash == tx.hash()).then(|| tx.clone()));
+        assert_eq!(response.request_id, 7);
+        assert_eq!(response.transactions.len(), 1);
+        let decoded = PooledTransactionsMessage::decode(&response.encode()).unwrap();
+        assert_eq!(decoded, response);
+
+        let mismatched = NewPooledTransactionHashesMessage { types: vec![], ..announcement };
+        assert!(NewPooledTransactionHashesMessage::decode(&mismatched.encode()).is_err());
+    }
+
+    #[test]
+    fn test_unknown_announcement_is_fetched_once() {
+        let (alice, bob) = (H512::repeat_byte(1), H512::repeat_byte(2));
+        let (known, unknown) = (transfer(0), transfer(1));
+        let announcement = NewPooledTransactionHashesMessage {
+            types: vec![0, 0],
+            sizes: vec![known.size() as u32, unknown.size() as u32],
+            hashes: vec![known.hash(), unknown.hash()],
+        };
+        let now = Instant::now();
+        let mut gossip = TxGossip::new();
+
+        let request = gossip.on_announcement(alice, &announcement, |hash| *hash == known.hash(), now).unwrap();
+        assert_eq!(request.hashes, vec![unknown.hash()]);
+        assert!(gossip.knows(&alice, &known.hash()));
+
+        // Already being fetched from alice
+        assert!(gossip.on_announcement(bob, &announcement, |hash| *hash == known.hash(), now).is_none());
+
+        let response = pooled_transactions(&request, |_| Some(unknown.clone()));
+        assert!(gossip.on_pooled_transactions(bob, &response).is_err());
+        assert_eq!(gossip.on_pooled_transactions(alice, &response).unwrap(), vec![unknown.clone()]);
+        assert!(gossip.on_pooled_transactions(alice, &response).is_err());
+
+        // Peers that know a transaction don't hear about it again
+        assert!(gossip.announce(&[unknown.clone()], &[alice]).is_empty());
+        let announced = gossip.announce(&[unknown], &[bob]);
+        assert_eq!(announced.len(), 1);
+        assert_eq!(announced[0].0, bob);
+    }
+
+    #[test]
+    fn test_expired_request_can_be_retried() {
+        let (alice, bob) = (H512::repeat_byte(1), H512::repeat_byte(2));
+        let tx = transfer(0);
+        let announcement = NewPooledTransactionHashesMessage {
+            types: vec![0],
+            sizes: vec![tx.size() as u32],
+            hashes: vec![tx.hash()],
+        };
+        let now = Instant::now();
+        let mut gossip = TxGossip::new();
+
+        assert!(gossip.on_announcement(alice, &announcement, |_| false, now).is_some());
+        assert_eq!(gossip.expire_requests(now + Duration::from_secs(1)), 0);
+        assert_eq!(gossip.expire_requests(now + TX_FETCH_TIMEOUT), 1);
+
+        let retry = gossip.on_announcement(bob, &announcement, |_| false, now + TX_FETCH_TIMEOUT).unwrap();
+        assert_eq!(retry.hashes, vec![tx.hash()]);
+    }
+
+    #[test]
+    fn test_inbound_messages_are_dispatched() {
+        let (alice, bob) = (H512::repeat_byte(1), H512::repeat_byte(2));
+        let tx = transfer(0);
+        let (ours, theirs) = (MapPool::default(), MapPool::default());
+        theirs.import(vec![tx.clone()]);
+        let now = Instant::now();
+        let (mut local, mut remote) = (TxGossip::new(), TxGossip::new());
+
+        // Bob announces, we ask for what we lack
+        let announcement = NewPooledTransactionHashesMessage {
+            types: vec![tx.tx_type()],
+            sizes: vec![tx.size() as u32],
+            hashes: vec![tx.hash()],
+        };
+        let (msg_id, request) = local
+            .handle_message(bob, NEW_POOLED_TRANSACTION_HASHES, &announcement.encode(), &ours, now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg_id, GET_POOLED_TRANSACTIONS);
+
+        // Bob serves the request from his pool
+        let (msg_id, response) = remote.handle_message(alice, msg_id, &request, &theirs, now).unwrap().unwrap();
+        assert_eq!(msg_id, POOLED_TRANSACTIONS);
+
+        // The response lands in our pool
+        assert!(local.handle_message(bob, msg_id, &response, &ours, now).unwrap().is_none());
+        assert!(ours.contains(&tx.hash()));
+        assert!(local.handle_message(bob, NEW_POOLED_TRANSACTION_HASHES, &announcement.encode(), &ours, now).unwrap().is_none());
+
+        // Other messages aren't the gossip's
+        assert!(local.handle_message(bob, 0x02, &[], &ours, now).unwrap().is_none());
+    }
+}
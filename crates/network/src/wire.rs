@@ -0,0 +1,81 @@
+use ethereum_rlp::{Decoder, Encoder, RlpItem};
+use ethereum_types::H256;
+
+use crate::{NetworkError, Result};
+
+pub(crate) fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    encoder.encode_bytes(bytes);
+    encoder.finish()
+}
+
+pub(crate) fn rlp_u64(value: u64) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    encoder.encode_u64(value);
+    encoder.finish()
+}
+
+pub(crate) fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    encoder.encode_list_payload(&items.concat());
+    encoder.finish()
+}
+
+pub(crate) fn encode_hashes(hashes: &[H256]) -> Vec<u8> {
+    let items: Vec<Vec<u8>> = hashes.iter().map(|hash| rlp_bytes(hash.as_bytes())).collect();
+    rlp_list(&items)
+}
+
+pub(crate) fn encode_proof(nodes: &[Vec<u8>]) -> Vec<u8> {
+    let items: Vec<Vec<u8>> = nodes.iter().map(|node| rlp_bytes(node)).collect();
+    rlp_list(&items)
+}
+
+pub(crate) fn decode_item(data: &[u8]) -> Result<RlpItem> {
+    Ok(Decoder::new(data)?.decode_item()?)
+}
+
+pub(crate) fn any_list(item: &RlpItem) -> Result<&[RlpItem]> {
+    item.as_list()
+        .ok_or_else(|| NetworkError::InvalidMessage("expected a list".to_string()))
+}
+
+pub(crate) fn list_of(item: &RlpItem, len: usize) -> Result<&[RlpItem]> {
+    let items = any_list(item)?;
+    if items.len() < len {
+        return Err(NetworkError::InvalidMessage(format!(
+            "expected {} fields, got {}", len, items.len()
+        )));
+    }
+    Ok(items)
+}
+
+pub(crate) fn bytes_of(item: &RlpItem) -> Result<Vec<u8>> {
+    item.as_bytes()
+        .map(|bytes| bytes.to_vec())
+        .ok_or_else(|| NetworkError::InvalidMessage("expected a string".to_string()))
+}
+
+pub(crate) fn u64_of(item: &RlpItem) -> Result<u64> {
+    let bytes = bytes_of(item)?;
+    if bytes.len() > 8 {
+        return Err(NetworkError::InvalidMessage("integer overflows u64".to_string()));
+    }
+    Ok(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+}
+
+pub(crate) fn hash_of(item: &RlpItem) -> Result<H256> {
+    let bytes = bytes_of(item)?;
+    if bytes.len() != 32 {
+        return Err(NetworkError::InvalidMessage(format!("expected 32-byte hash, got {} bytes", bytes.len())));
+    }
+    Ok(H256::from_slice(&bytes))
+}
+
+pub(crate) fn decode_hashes(item: &RlpItem) -> Result<Vec<H256>> {
+    any_list(item)?.iter().map(hash_of).collect()
+}
+
+pub(crate) fn decode_proof(item: &RlpItem) -> Result<Vec<Vec<u8>>> {
+    any_list(item)?.iter().map(bytes_of).collect()
+}
@@ -46,16 +46,21 @@ impl Encoder {
         }
         let list_bytes = list_encoder.finish();
         
-        match list_bytes.len() {
+        self.encode_list_payload(&list_bytes);
+    }
+    
+    /// Wrap already-encoded items in a list header
+    pub fn encode_list_payload(&mut self, payload: &[u8]) {
+        match payload.len() {
             len if len < 56 => {
                 self.buffer.extend_from_slice(&[0xc0 + len as u8]);
-                self.buffer.extend_from_slice(&list_bytes);
+                self.buffer.extend_from_slice(payload);
             }
             len => {
                 let len_bytes = encode_length(len);
                 self.buffer.extend_from_slice(&[0xf7 + len_bytes.len() as u8]);
                 self.buffer.extend_from_slice(&len_bytes);
-                self.buffer.extend_from_slice(&list_bytes);
+                self.buffer.extend_from_slice(payload);
             }
         }
     }
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use ethereum_network::discovery::{parse_enode, NodeId};
+use ethereum_network::{Capability, PeerManager};
+use ethereum_types::H512;
+use serde::Serialize;
+
+use crate::{Result, RpcError};
+
+/// `admin_nodeInfo` result
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeInfo {
+    pub id: String,
+    pub name: String,
+    pub enode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enr: Option<String>,
+    pub ip: String,
+    pub ports: NodePorts,
+    pub listen_addr: String,
+    pub protocols: BTreeMap<String, ProtocolInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodePorts {
+    pub discovery: u16,
+    pub listener: u16,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtocolInfo {
+    pub version: u8,
+}
+
+impl NodeInfo {
+    /// Info of `local` accepting RLPx connections on `listener_port`
+    pub fn new(name: String, local: &NodeId, listener_port: u16) -> Self {
+        let ip = local.address.ip();
+        let discovery = local.address.port();
+        let enode = if discovery == listener_port {
+            format!("enode://{}@{}", node_id_hex(&local.id), SocketAddr::new(ip, listener_port))
+        } else {
+            format!(
+                "enode://{}@{}?discport={}",
+                node_id_hex(&local.id),
+                SocketAddr::new(ip, listener_port),
+                discovery
+            )
+        };
+        let protocols = [Capability::eth(), Capability::snap()]
+            .into_iter()
+            .map(|cap| (cap.name, ProtocolInfo { version: cap.version }))
+            .collect();
+
+        Self {
+            id: node_id_hex(&local.id),
+            name,
+            enode,
+            enr: None,
+            ip: ip.to_string(),
+            ports: NodePorts { discovery, listener: listener_port },
+            listen_addr: SocketAddr::new(ip, listener_port).to_string(),
+            protocols,
+        }
+    }
+
+    /// Advertise `enr`, the textual `enr:` form of our record
+    pub fn with_enr(mut self, enr: String) -> Self {
+        self.enr = Some(enr);
+        self
+    }
+}
+
+/// `admin_peers` entry
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerInfo {
+    pub id: String,
+    pub name: String,
+    pub enode: String,
+    pub caps: Vec<String>,
+    pub network: PeerNetworkInfo,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerNetworkInfo {
+    pub remote_address: String,
+    pub inbound: bool,
+}
+
+/// `admin_` namespace over the local node and its peers
+pub struct AdminApi {
+    node_info: NodeInfo,
+    peer_manager: Arc<PeerManager>,
+}
+
+impl AdminApi {
+    pub fn new(node_info: NodeInfo, peer_manager: Arc<PeerManager>) -> Self {
+        Self { node_info, peer_manager }
+    }
+
+    pub async fn node_info(&self) -> Result<NodeInfo> {
+        Ok(self.node_info.clone())
+    }
+
+    /// Connected peers with their capabilities and connection direction
+    pub async fn peers(&self) -> Result<Vec<PeerInfo>> {
+        let mut peers = Vec::new();
+        for peer in self.peer_manager.get_all_peers().await {
+            if !peer.is_connected().await {
+                continue;
+            }
+            peers.push(PeerInfo {
+                id: node_id_hex(&peer.id.node_id),
+                name: peer.id.client_id.clone(),
+                enode: format!("enode://{}@{}", node_id_hex(&peer.id.node_id), peer.id.address),
+                caps: peer.protocols.iter()
+                    .map(|protocol| format!("{}/{}", protocol.name, protocol.version))
+                    .collect(),
+                network: PeerNetworkInfo {
+                    remote_address: peer.id.address.to_string(),
+                    inbound: peer.inbound,
+                },
+            });
+        }
+        Ok(peers)
+    }
+
+    /// Queue a dial to the node behind `url`, true if it is connected or queued
+    pub async fn add_peer(&self, url: String) -> Result<bool> {
+        let node = parse_dial_target(&url)?;
+        // Already connected or queued nodes need no new dial
+        self.peer_manager.add_dial(node).await;
+        Ok(true)
+    }
+
+    /// Drop the node behind `url`, false if it was neither connected nor queued
+    pub async fn remove_peer(&self, url: String) -> Result<bool> {
+        let node = parse_dial_target(&url)?;
+        Ok(self.peer_manager.drop_peer(&node.id).await)
+    }
+}
+
+/// Node behind an enode URL, addressed by its RLPx (TCP) endpoint
+///
+/// `parse_enode` addresses nodes for discovery and prefers `discport`, which
+/// is the wrong port to dial.
+fn parse_dial_target(url: &str) -> Result<NodeId> {
+    let url = url.split('?').next().unwrap_or(url);
+    parse_enode(url).map_err(|e| RpcError::InvalidParams(e.to_string()))
+}
+
+/// Node id as written in enode URLs, hex without a prefix
+fn node_id_hex(id: &H512) -> String {
+    hex::encode(id.as_bytes())
+}
@@ -0,0 +1,290 @@
+use ethereum_types::{Address, Bytes, H256, U256};
+use ethereum_core::{LegacyTransaction, Transaction};
+use ethereum_evm::execution::{BlockContext, ExecutionStatus, Log};
+use ethereum_evm::opcodes::Opcode;
+use ethereum_evm::state::StateDB;
+use ethereum_evm::{run_interpreter, Account, Checkpoint, ExecutionResult, Frame, Host};
+use ethereum_state::{apply_transaction_with, intrinsic_gas};
+use ethereum_txpool::PooledTransaction;
+use tracing::debug;
+
+use crate::{Result, RpcError};
+use crate::types::CallRequest;
+
+pub use ethereum_state::call::{apply_transaction, bump_nonce, Call, TX_BASE_GAS};
+pub use ethereum_state::call_intrinsic_gas;
+pub use ethereum_state::{apply_account_override, apply_state_override, CallState, EmptyState, StateProvider};
+
+/// Block context for the pending block built on top of `head`
+pub fn pending_block_context(head: &BlockContext) -> BlockContext {
+    let mut block = head.clone();
+    block.number = head.number + U256::one();
+    block
+}
+
+/// Layer the pool's pending transactions on top of the state
+///
+/// Transactions are applied per sender in nonce order. One that doesn't fit
+/// the state built so far (wrong nonce, unaffordable) is skipped, as the
+/// block builder would.
+pub fn apply_pending(state: &mut CallState<'_>, block: &BlockContext, mut pending: Vec<PooledTransaction>) -> usize {
+    pending.sort_by(|a, b| a.from.cmp(&b.from).then_with(|| a.tx.nonce().cmp(&b.tx.nonce())));
+
+    let mut applied = 0;
+    for pooled in &pending {
+        if apply_transaction(state, block, pooled.from, &pooled.tx) {
+            applied += 1;
+        } else {
+            debug!("Skipping pending transaction {:?} in pending state", pooled.hash);
+        }
+    }
+    applied
+}
+
+/// Execute a call request against the state without charging for gas
+pub fn execute_call(state: &mut CallState<'_>, block: &BlockContext, request: &CallRequest) -> Result<ExecutionResult> {
+    Ok(ethereum_state::execute_call(state, block, &request.to_call()?)?)
+}
+
+/// Pseudo-contract emitting the `Transfer` logs of traced value transfers
+const TRANSFER_LOG_ADDRESS: [u8; 20] = [0xee; 20];
+
+/// `keccak256("Transfer(address,address,uint256)")`
+const TRANSFER_TOPIC: [u8; 32] = [
+    0xdd, 0xf2, 0x52, 0xad, 0x1b, 0xe2, 0xc8, 0x9b, 0x69, 0xc2, 0xb0, 0x68, 0xfc, 0x37, 0x8d, 0xaa,
+    0x95, 0x2b, 0xa7, 0xf1, 0x63, 0xc4, 0xa1, 0x16, 0x28, 0xf5, 0x5a, 0x4d, 0xf5, 0x23, 0xb3, 0xef,
+];
+
+/// What became of one call of a simulated block
+#[derive(Debug)]
+pub enum SimulatedExecution {
+    /// Included like a transaction
+    Included {
+        result: ExecutionResult,
+        /// Logs of the frames that succeeded in the order they were emitted,
+        /// traced transfers among them
+        logs: Vec<Log>,
+        /// Gas of the call, intrinsic gas included
+        gas_used: u64,
+    },
+    /// Left out because the sender can't pay for its gas and value
+    InsufficientFunds { have: U256, want: U256 },
+}
+
+/// Execute one call of a simulated block, keeping its changes in the overlay
+///
+/// The call is applied as a transaction of its sender: it consumes a nonce,
+/// pays for its intrinsic gas and must fit in `gas_available`, the gas left
+/// in the block, which is also its gas limit unless it names one. With
+/// `validation` the call must match the sender's nonce if it names one and
+/// pay at least the base fee, and is charged for the gas it uses; without
+/// it gas is free. A call whose sender can't afford it is left out on its
+/// own, the block goes on without it.
+pub fn simulate_call(
+    state: &mut CallState<'_>,
+    block: &BlockContext,
+    request: &CallRequest,
+    validation: bool,
+    trace_transfers: bool,
+    gas_available: u64,
+) -> Result<SimulatedExecution> {
+    let call = request.to_call()?;
+    let sender = StateDB::get_account(&*state, &call.from).unwrap_or_default();
+    let base_fee = block.base_fee.unwrap_or_default();
+    let gas_price = match (request.gas_price, request.max_fee_per_gas) {
+        _ if !validation => U256::zero(),
+        (Some(gas_price), _) => gas_price,
+        (None, Some(max_fee)) => max_fee.min(base_fee.saturating_add(request.max_priority_fee_per_gas.unwrap_or_default())),
+        (None, None) => U256::zero(),
+    };
+
+    if validation {
+        if let Some(nonce) = request.nonce {
+            if nonce != U256::from(sender.nonce) {
+                return Err(RpcError::InvalidParams(format!(
+                    "nonce {} does not match account nonce {}", nonce, sender.nonce
+                )));
+            }
+        }
+        let max_fee = request.gas_price.or(request.max_fee_per_gas).unwrap_or_default();
+        if max_fee < base_fee {
+            return Err(RpcError::InvalidParams(format!(
+                "max fee per gas {} less than block base fee {}", max_fee, base_fee
+            )));
+        }
+    }
+
+    let gas_limit = call.gas.unwrap_or(U256::from(gas_available));
+    if gas_limit > U256::from(gas_available) {
+        return Err(RpcError::InvalidParams(format!(
+            "block gas limit reached: call needs {} with {} left", gas_limit, gas_available
+        )));
+    }
+    let tx = Transaction::Legacy(LegacyTransaction {
+        nonce: U256::from(sender.nonce),
+        gas_price,
+        gas_limit,
+        to: call.to,
+        value: call.value,
+        data: Bytes::from_vec(call.data),
+        v: 0,
+        r: U256::zero(),
+        s: U256::zero(),
+    });
+    let intrinsic = intrinsic_gas(&tx);
+    if gas_limit < U256::from(intrinsic) {
+        return Err(RpcError::InvalidParams(format!(
+            "intrinsic gas too low: have {} want {}", gas_limit, intrinsic
+        )));
+    }
+    if sender.balance < tx.upfront_cost() {
+        return Ok(SimulatedExecution::InsufficientFunds { have: sender.balance, want: tx.upfront_cost() });
+    }
+
+    let mut logs = Vec::new();
+    let result = apply_transaction_with(state, block, call.from, &tx, |context, host| {
+        let mut recorder = LogRecorder::new(host, trace_transfers);
+        recorder.enter(context.caller, context.address, context.value, true);
+        let result = run_interpreter(context, &mut recorder);
+        logs = recorder.finish(&result);
+        result
+    });
+    state.finish()?;
+
+    let result = result.ok_or_else(|| RpcError::InternalError("simulated call could not be applied".to_string()))?;
+    Ok(SimulatedExecution::Included { gas_used: intrinsic.saturating_add(result.gas_used), result, logs })
+}
+
+/// Host wrapper collecting the logs of a call tree as they are emitted,
+/// optionally with a `Transfer` log ahead of every frame moving value
+///
+/// Logs are kept per open frame and handed to the caller only when the
+/// frame succeeds, so reverted transfers and logs go with their frame.
+struct LogRecorder<'h, H: Host> {
+    inner: &'h mut H,
+    trace_transfers: bool,
+    /// Logs of the frames still running, innermost frame last
+    open: Vec<Vec<Log>>,
+}
+
+impl<'h, H: Host> LogRecorder<'h, H> {
+    fn new(inner: &'h mut H, trace_transfers: bool) -> Self {
+        Self { inner, trace_transfers, open: Vec::new() }
+    }
+
+    /// Open a frame moving `value` from `from` to `to`
+    fn enter(&mut self, from: Address, to: Address, value: U256, transfers: bool) {
+        let mut logs = Vec::new();
+        if self.trace_transfers && transfers && !value.is_zero() {
+            logs.push(transfer_log(from, to, value));
+        }
+        self.open.push(logs);
+    }
+
+    /// Close the innermost frame, keeping its logs only if it succeeded
+    fn exit(&mut self, result: &ExecutionResult) -> Vec<Log> {
+        let logs = self.open.pop().unwrap_or_default();
+        if result.status == ExecutionStatus::Success {
+            logs
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Logs of the root frame once it returned with `result`
+    fn finish(mut self, result: &ExecutionResult) -> Vec<Log> {
+        self.exit(result)
+    }
+}
+
+impl<'h, H: Host> Host for LogRecorder<'h, H> {
+    fn get_account(&self, address: &Address) -> Option<Account> {
+        self.inner.get_account(address)
+    }
+
+    fn set_account(&mut self, address: Address, account: Account) {
+        self.inner.set_account(address, account)
+    }
+
+    fn remove_account(&mut self, address: &Address) {
+        self.inner.remove_account(address)
+    }
+
+    fn is_empty(&self, address: &Address) -> bool {
+        self.inner.is_empty(address)
+    }
+
+    fn is_created(&self, address: &Address) -> bool {
+        self.inner.is_created(address)
+    }
+
+    fn get_storage(&self, address: &Address, key: &H256) -> H256 {
+        self.inner.get_storage(address, key)
+    }
+
+    fn set_storage(&mut self, address: Address, key: H256, value: H256) {
+        self.inner.set_storage(address, key, value)
+    }
+
+    fn get_transient_storage(&self, address: &Address, key: &H256) -> H256 {
+        self.inner.get_transient_storage(address, key)
+    }
+
+    fn set_transient_storage(&mut self, address: Address, key: H256, value: H256) {
+        self.inner.set_transient_storage(address, key, value)
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.inner.checkpoint()
+    }
+
+    fn revert_to(&mut self, checkpoint: Checkpoint) {
+        self.inner.revert_to(checkpoint)
+    }
+
+    fn commit(&mut self, checkpoint: Checkpoint) {
+        self.inner.commit(checkpoint)
+    }
+
+    fn block_hash(&self, block: &BlockContext, number: U256) -> H256 {
+        self.inner.block_hash(block, number)
+    }
+
+    fn log(&mut self, log: &Log) {
+        if let Some(logs) = self.open.last_mut() {
+            logs.push(log.clone());
+        }
+        self.inner.log(log)
+    }
+
+    fn enter_frame(&mut self, frame: &Frame<'_>) {
+        // Only CALL and CREATE move value
+        let transfers = matches!(frame.opcode, Opcode::CALL | Opcode::CREATE | Opcode::CREATE2);
+        self.enter(frame.from, frame.to, frame.value, transfers);
+        self.inner.enter_frame(frame)
+    }
+
+    fn exit_frame(&mut self, result: &ExecutionResult) {
+        let logs = self.exit(result);
+        if let Some(parent) = self.open.last_mut() {
+            parent.extend(logs);
+        }
+        self.inner.exit_frame(result)
+    }
+}
+
+/// ERC-20 style `Transfer` log for `value` wei sent from `from` to `to`
+fn transfer_log(from: Address, to: Address, value: U256) -> Log {
+    let topic = |address: Address| {
+        let mut word = [0u8; 32];
+        word[12..].copy_from_slice(address.as_bytes());
+        H256::from(word)
+    };
+    let mut data = [0u8; 32];
+    value.to_big_endian(&mut data);
+    Log {
+        address: Address::from_bytes(TRANSFER_LOG_ADDRESS),
+        topics: vec![H256::from(TRANSFER_TOPIC), topic(from), topic(to)],
+        data: data.to_vec(),
+    }
+}
@@ -1,23 +1,89 @@
 use std::sync::Arc;
-use ethereum_types::{H160, H256, U256};
+use ethereum_types::{Address, H160, H256, U256};
 use ethereum_storage::Database;
-use ethereum_core::{Block as CoreBlock, Transaction as CoreTransaction};
+use ethereum_consensus::CheckpointStore;
+use ethereum_core::{Block as CoreBlock, Header, Receipt as CoreReceipt, RpcTransaction, Transaction as CoreTransaction};
+use ethereum_core::eip7691::calculate_blob_base_fee;
+use ethereum_evm::execution::{BlockContext, ExecutionResult, ExecutionStatus};
+use ethereum_evm::ChainConfig;
+use ethereum_evm::interpreter::create_address;
+use ethereum_evm::state::StateDB;
+use ethereum_txpool::TransactionPool;
 
 use crate::{Result, RpcError};
-use crate::types::{Block, Transaction, Receipt, CallRequest, BlockNumber, SyncStatus};
+use crate::call::{self, CallState, SimulatedExecution, StateProvider};
+use crate::state::TrieStateProvider;
+use crate::types::{
+    Block, Transaction, Receipt, Log, CallRequest, BlockId, BlockNumber, SyncStatus,
+    SimulatePayload, SimulatedBlock, SimulatedCall, SimulateCallError, StorageRangeResult,
+    TransactionRequest,
+};
 
+/// Key holding the hash of the current canonical head
+const HEAD_KEY: &[u8] = b"canonical:head";
+
+/// Most blocks a single `eth_simulateV1` request may simulate
+pub const MAX_SIMULATE_BLOCKS: usize = 256;
+
+/// Most slots a single `debug_storageRangeAt` page holds
+pub const MAX_STORAGE_RANGE_RESULTS: usize = 1024;
+
+/// Tip suggested for dynamic fee transactions, 1 gwei
+const DEFAULT_PRIORITY_FEE: u64 = 1_000_000_000;
+
+/// Seconds between simulated blocks without a time override
+const SIMULATED_BLOCK_TIME: u64 = 12;
+
+#[derive(Clone)]
 pub struct EthApi {
     db: Arc<dyn Database>,
-    chain_id: u64,
+    chain_config: ChainConfig,
+    state: Arc<dyn StateProvider>,
+    txpool: Option<Arc<TransactionPool>>,
+    checkpoints: Arc<CheckpointStore>,
 }
 
 impl EthApi {
     pub fn new<D: Database + 'static>(db: Arc<D>) -> Self {
         Self {
+            state: Arc::new(TrieStateProvider::new(db.clone())),
+            checkpoints: Arc::new(CheckpointStore::new(db.clone())),
             db: db as Arc<dyn Database>,
-            chain_id: 1, // Default to mainnet
+            chain_config: ChainConfig::mainnet(),
+            txpool: None,
         }
     }
+
+    /// Account state read by state methods and used to execute calls
+    pub fn with_state_provider(mut self, state: Arc<dyn StateProvider>) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Chain id reported by `eth_chainId` and used when executing calls
+    pub fn with_chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_config.chain_id = chain_id;
+        self
+    }
+
+    /// Forks the executed blocks follow, mainnet's by default
+    pub fn with_chain_config(mut self, chain_config: ChainConfig) -> Self {
+        self.chain_config = chain_config;
+        self
+    }
+
+    /// Pool whose pending transactions make up the "pending" state
+    pub fn with_txpool(mut self, txpool: Arc<TransactionPool>) -> Self {
+        self.txpool = Some(txpool);
+        self
+    }
+
+    /// Checkpoints the "finalized" and "safe" block tags resolve to,
+    /// shared with the consensus engine that records them
+    pub fn with_checkpoint_store(mut self, checkpoints: Arc<CheckpointStore>) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
     
     pub async fn block_number(&self) -> Result<U256> {
         // Get the latest block number from storage
@@ -25,35 +91,48 @@ impl EthApi {
         Ok(U256::from(0))
     }
     
-    pub async fn get_balance(&self, address: H160, block_number: Option<BlockNumber>) -> Result<U256> {
-        // Query state database for account balance
-        // This would interact with the state trie
-        let block = self.resolve_block_number(block_number).await?;
-        
-        // Simplified - would actually query the state trie
-        Ok(U256::zero())
+    pub async fn get_balance(&self, address: H160, block: Option<BlockId>) -> Result<U256> {
+        let (state, _) = self.state_at(&block.unwrap_or_default())?;
+        let balance = state.get_account(&Address::from(address))
+            .map(|account| account.balance)
+            .unwrap_or_default();
+        state.finish()?;
+        Ok(balance)
     }
     
-    pub async fn get_transaction_count(&self, address: H160, block_number: Option<BlockNumber>) -> Result<U256> {
-        // Query state database for account nonce
-        let block = self.resolve_block_number(block_number).await?;
-        
-        // Simplified - would actually query the state trie
-        Ok(U256::zero())
+    pub async fn get_transaction_count(&self, address: H160, block: Option<BlockId>) -> Result<U256> {
+        let (state, _) = self.state_at(&block.unwrap_or_default())?;
+        let nonce = state.get_account(&Address::from(address))
+            .map(|account| account.nonce)
+            .unwrap_or_default();
+        state.finish()?;
+        Ok(U256::from(nonce))
+    }
+    
+    /// Word at `slot` in the storage of `address` as of `block`, zero for
+    /// absent accounts and slots
+    pub async fn get_storage_at(&self, address: H160, slot: U256, block: Option<BlockId>) -> Result<H256> {
+        let mut key = [0u8; 32];
+        slot.to_big_endian(&mut key);
+        let (state, _) = self.state_at(&block.unwrap_or_default())?;
+        let value = state.get_storage(&Address::from(address), &H256::from(key));
+        state.finish()?;
+        Ok(value)
+    }
+    
+    pub async fn get_code(&self, address: H160, block: Option<BlockId>) -> Result<String> {
+        let (state, _) = self.state_at(&block.unwrap_or_default())?;
+        let code = state.get_account(&Address::from(address))
+            .map(|account| account.code)
+            .unwrap_or_default();
+        state.finish()?;
+        Ok(format!("0x{}", hex::encode(code)))
     }
     
     pub async fn get_block_by_hash(&self, hash: H256, full_transactions: bool) -> Result<Option<Block>> {
-        // Query block from database
-        let key = format!("block:{}", hex::encode(hash.as_bytes()));
-        
-        match self.db.get(key.as_bytes()) {
-            Ok(Some(data)) => {
-                // Deserialize block and convert to RPC format
-                let block = self.convert_block(hash, full_transactions)?;
-                Ok(Some(block))
-            }
-            Ok(None) => Ok(None),
-            Err(e) => Err(RpcError::InternalError(e.to_string())),
+        match self.load_block(&hash)? {
+            Some(block) => Ok(Some(self.convert_block(hash, &block, full_transactions)?)),
+            None => Ok(None),
         }
     }
     
@@ -74,48 +153,328 @@ impl EthApi {
         self.get_block_by_hash(hash, full_transactions).await
     }
     
+    /// Mined transaction with the block it was included in
     pub async fn get_transaction_by_hash(&self, hash: H256) -> Result<Option<Transaction>> {
-        // Query transaction from database
-        let key = format!("tx:{}", hex::encode(hash.as_bytes()));
+        let key = format!("tx:block:{}", hex::encode(hash.as_bytes()));
+        let Some(block_hash) = self.read_hash(key.as_bytes())? else {
+            return Ok(None);
+        };
+        let Some(block) = self.load_block(&block_hash)? else {
+            return Ok(None);
+        };
+        let Some(index) = block.transactions.iter().position(|tx| tx.hash() == hash) else {
+            return Ok(None);
+        };
         
-        match self.db.get(key.as_bytes()) {
-            Ok(Some(data)) => {
-                // Deserialize and convert to RPC format
-                let tx = self.convert_transaction(hash)?;
-                Ok(Some(tx))
-            }
-            Ok(None) => Ok(None),
-            Err(e) => Err(RpcError::InternalError(e.to_string())),
+        self.convert_transaction(block_hash, &block, index).map(Some)
+    }
+    
+    /// Number of transactions in the block with `hash`, `None` for unknown blocks
+    pub async fn get_block_transaction_count_by_hash(&self, hash: H256) -> Result<Option<U256>> {
+        Ok(self.load_block(&hash)?.map(|block| U256::from(block.transactions.len())))
+    }
+    
+    /// Number of transactions in the canonical block `number`, `None` for unknown blocks
+    pub async fn get_block_transaction_count_by_number(&self, number: BlockNumber) -> Result<Option<U256>> {
+        Ok(self.block_by_number(&number)?.map(|(_, block)| U256::from(block.transactions.len())))
+    }
+    
+    /// Transaction at `index` in the block with `hash`, `None` if either doesn't exist
+    pub async fn get_transaction_by_block_hash_and_index(&self, hash: H256, index: U256) -> Result<Option<Transaction>> {
+        match self.load_block(&hash)? {
+            Some(block) => self.transaction_at(hash, &block, index),
+            None => Ok(None),
+        }
+    }
+    
+    /// Transaction at `index` in the canonical block `number`, `None` if either doesn't exist
+    pub async fn get_transaction_by_block_number_and_index(&self, number: BlockNumber, index: U256) -> Result<Option<Transaction>> {
+        match self.block_by_number(&number)? {
+            Some((hash, block)) => self.transaction_at(hash, &block, index),
+            None => Ok(None),
         }
     }
     
+    /// Receipt of a mined transaction, joined with the transaction and its
+    /// block for the fields that aren't stored
     pub async fn get_transaction_receipt(&self, hash: H256) -> Result<Option<Receipt>> {
-        // Query receipt from database
-        let key = format!("receipt:{}", hex::encode(hash.as_bytes()));
+        let key = format!("tx:block:{}", hex::encode(hash.as_bytes()));
+        let Some(block_hash) = self.read_hash(key.as_bytes())? else {
+            return Ok(None);
+        };
+        let Some(block) = self.load_block(&block_hash)? else {
+            return Ok(None);
+        };
+        let Some(index) = block.transactions.iter().position(|tx| tx.hash() == hash) else {
+            return Ok(None);
+        };
         
-        match self.db.get(key.as_bytes()) {
-            Ok(Some(data)) => {
-                // Deserialize and convert to RPC format
-                let receipt = self.convert_receipt(hash)?;
-                Ok(Some(receipt))
-            }
-            Ok(None) => Ok(None),
-            Err(e) => Err(RpcError::InternalError(e.to_string())),
+        let receipts = self.load_receipts(&block_hash)?;
+        if receipts.len() <= index {
+            return Ok(None);
         }
+        
+        self.convert_receipt(block_hash, &block, index, &receipts).map(Some)
     }
     
-    pub async fn call(&self, request: CallRequest, block_number: Option<BlockNumber>) -> Result<String> {
-        // Execute EVM call without state changes
-        let block = self.resolve_block_number(block_number).await?;
-        
-        // This would actually execute the call in the EVM
-        Ok("0x".to_string())
+    /// Execute a call without committing any state changes
+    ///
+    /// `"pending"` runs on top of the head with the pool's pending
+    /// transactions applied first, so their effects are visible to the call.
+    pub async fn call(&self, request: CallRequest, block: Option<BlockId>) -> Result<String> {
+        let (mut state, context) = self.state_at(&block.unwrap_or_default())?;
+
+        let result = call::execute_call(&mut state, &context, &request)?;
+        let output = call_output(result)?;
+        Ok(format!("0x{}", hex::encode(output)))
     }
     
+    /// Lowest gas limit the call succeeds with as a transaction on top of
+    /// the latest block, reverts are reported like `call`
+    ///
+    /// The intrinsic gas plus what the call used given all the gas it may
+    /// have is only a first guess: gas held back from nested calls and
+    /// refunds can make a call fail with that much. The guess is checked by
+    /// running the call with it, and searched upwards while it falls short.
     pub async fn estimate_gas(&self, request: CallRequest) -> Result<U256> {
-        // Estimate gas by executing the transaction
-        // This would actually run the transaction to estimate gas
-        Ok(U256::from(21000)) // Basic transfer gas
+        let (state, context) = self.state_at(&BlockId::default())?;
+        let call = request.to_call()?;
+        let cap = call.gas.unwrap_or(context.gas_limit).min(U256::from(u64::MAX)).as_u64();
+        let intrinsic = call::call_intrinsic_gas(&call);
+        if cap < intrinsic {
+            return Err(RpcError::InvalidParams(format!(
+                "intrinsic gas too low: have {} want {}", cap, intrinsic
+            )));
+        }
+
+        let run = |gas: u64| -> Result<ExecutionResult> {
+            let mut state = state.clone();
+            let call = call::Call { gas: Some(U256::from(gas)), ..call.clone() };
+            Ok(ethereum_state::execute_call(&mut state, &context, &call)?)
+        };
+
+        let result = run(cap)?;
+        let estimate = intrinsic.saturating_add(result.gas_used).min(cap);
+        call_output(result)?;
+        if run(estimate)?.status == ExecutionStatus::Success {
+            return Ok(U256::from(estimate));
+        }
+
+        // `low` is known to fail and `high` to succeed
+        let (mut low, mut high) = (estimate, cap);
+        while low + 1 < high {
+            let mid = low + (high - low) / 2;
+            if run(mid)?.status == ExecutionStatus::Success {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        Ok(U256::from(high))
+    }
+    
+    /// Run blocks of calls on top of `block` without committing anything
+    ///
+    /// Every call sees the state left by the calls and blocks before it.
+    /// Simulated blocks follow each other with the overrides of each applied
+    /// before its calls run. Without validation the base fee is zero unless
+    /// overridden and calls aren't charged for gas. Traced transfers cover
+    /// every value transfer of a call, nested calls included. A call its
+    /// sender can't afford fails on its own, the other calls still run.
+    pub async fn simulate_v1(&self, payload: SimulatePayload, block: Option<BlockId>) -> Result<Vec<SimulatedBlock>> {
+        if payload.block_state_calls.len() > MAX_SIMULATE_BLOCKS {
+            return Err(RpcError::LimitExceeded(format!(
+                "too many blocks to simulate, limit is {}", MAX_SIMULATE_BLOCKS
+            )));
+        }
+
+        let (mut state, mut context) = self.state_at(&block.unwrap_or_default())?;
+        if !payload.validation {
+            context.base_fee = Some(U256::zero());
+        }
+
+        let mut blocks = Vec::with_capacity(payload.block_state_calls.len());
+        for block_calls in &payload.block_state_calls {
+            let parent_number = context.number;
+            context.number = parent_number + U256::one();
+            context.timestamp = context.timestamp + U256::from(SIMULATED_BLOCK_TIME);
+
+            if let Some(overrides) = &block_calls.block_overrides {
+                if let Some(number) = overrides.number {
+                    if number <= parent_number {
+                        return Err(RpcError::InvalidParams(format!(
+                            "block number {} must be above {}", number, parent_number
+                        )));
+                    }
+                    context.number = number;
+                }
+                if let Some(time) = overrides.time {
+                    context.timestamp = time;
+                }
+                if let Some(gas_limit) = overrides.gas_limit {
+                    context.gas_limit = gas_limit;
+                }
+                if let Some(fee_recipient) = overrides.fee_recipient {
+                    context.coinbase = Address::from(fee_recipient);
+                }
+                if let Some(prev_randao) = overrides.prev_randao {
+                    context.prev_randao = prev_randao;
+                }
+                if let Some(base_fee) = overrides.base_fee_per_gas {
+                    context.base_fee = Some(base_fee);
+                }
+            }
+            if let Some(overrides) = &block_calls.state_overrides {
+                call::apply_state_override(&mut state, overrides)?;
+            }
+
+            let mut calls = Vec::with_capacity(block_calls.calls.len());
+            let block_gas_limit = context.gas_limit.min(U256::from(u64::MAX)).as_u64();
+            let mut block_gas_used = 0u64;
+            let mut log_index = 0usize;
+            for (index, request) in block_calls.calls.iter().enumerate() {
+                let gas_available = block_gas_limit.saturating_sub(block_gas_used);
+                let simulated = call::simulate_call(
+                    &mut state, &context, request, payload.validation, payload.trace_transfers, gas_available,
+                )?;
+                let (result, logs, gas_used) = match simulated {
+                    SimulatedExecution::Included { result, logs, gas_used } => (result, logs, gas_used),
+                    SimulatedExecution::InsufficientFunds { have, want } => {
+                        calls.push(SimulatedCall {
+                            status: U256::zero(),
+                            return_data: "0x".to_string(),
+                            gas_used: U256::zero(),
+                            logs: Vec::new(),
+                            error: Some(SimulateCallError {
+                                code: -38014,
+                                message: format!("insufficient funds for gas * price + value: have {} want {}", have, want),
+                            }),
+                        });
+                        continue;
+                    }
+                };
+                block_gas_used = block_gas_used.saturating_add(gas_used);
+
+                let logs = logs.into_iter().map(|log| {
+                    let log = Log {
+                        removed: false,
+                        log_index: U256::from(log_index),
+                        transaction_index: U256::from(index),
+                        transaction_hash: H256::zero(),
+                        block_hash: H256::zero(),
+                        block_number: context.number,
+                        address: H160::from_slice(log.address.as_bytes()),
+                        data: format!("0x{}", hex::encode(log.data)),
+                        topics: log.topics,
+                    };
+                    log_index += 1;
+                    log
+                }).collect();
+
+                let error = match &result.status {
+                    ExecutionStatus::Success => None,
+                    ExecutionStatus::Revert => {
+                        let error = RpcError::reverted(result.return_data.clone());
+                        Some(SimulateCallError { code: error.code(), message: error.to_string() })
+                    }
+                    ExecutionStatus::Halt(reason) => Some(SimulateCallError {
+                        code: -32015,
+                        message: format!("execution halted: {:?}", reason),
+                    }),
+                };
+                calls.push(SimulatedCall {
+                    status: if result.status == ExecutionStatus::Success { U256::one() } else { U256::zero() },
+                    return_data: format!("0x{}", hex::encode(&result.return_data)),
+                    gas_used: U256::from(gas_used),
+                    logs,
+                    error,
+                });
+            }
+
+            blocks.push(SimulatedBlock {
+                number: context.number,
+                timestamp: context.timestamp,
+                gas_limit: context.gas_limit,
+                gas_used: U256::from(block_gas_used),
+                fee_recipient: H160::from_slice(context.coinbase.as_bytes()),
+                base_fee_per_gas: context.base_fee,
+                calls,
+            });
+        }
+
+        Ok(blocks)
+    }
+    
+    /// Page of the storage of `address` as it was right before transaction
+    /// `tx_index` of `block` ran, for `debug_storageRangeAt`
+    ///
+    /// Slots are ordered by their hash and the page starts at hashed slot
+    /// `start_key`, holding at most `max_result` slots (capped at
+    /// `MAX_STORAGE_RANGE_RESULTS`). An index one past the last transaction
+    /// reads the block's post-state, earlier ones replay the block's
+    /// transactions up to `tx_index` on top of its parent's state.
+    pub async fn storage_range_at(
+        &self,
+        block: BlockId,
+        tx_index: usize,
+        address: H160,
+        start_key: H256,
+        max_result: usize,
+    ) -> Result<StorageRangeResult> {
+        let header = self.resolve_header(&block)?;
+        let block = self.load_block(&header.hash())?.ok_or(RpcError::ResourceNotFound)?;
+        if tx_index > block.transactions.len() {
+            return Err(RpcError::InvalidParams(format!(
+                "transaction index {} out of range for block {}", tx_index, header.number
+            )));
+        }
+
+        let (state_root, replayed) = if tx_index == block.transactions.len() {
+            (header.state_root, 0)
+        } else {
+            let parent = self.load_block(&header.parent_hash)?.ok_or(RpcError::ResourceNotFound)?;
+            (parent.header.state_root, tx_index)
+        };
+        let mut state = CallState::new(self.state.as_ref(), state_root);
+        let context = BlockContext::from_header(&header, &self.chain_config, Vec::new());
+        for tx in &block.transactions[..replayed] {
+            let sender = tx.sender().map_err(|e| RpcError::InternalError(e.to_string()))?;
+            if !call::apply_transaction(&mut state, &context, sender, tx) {
+                return Err(RpcError::InternalError(format!(
+                    "transaction {:?} does not apply to its parent state", tx.hash()
+                )));
+            }
+        }
+        state.finish()?;
+
+        Ok(state.storage_range(&Address::from(address), &start_key, max_result.min(MAX_STORAGE_RANGE_RESULTS))?)
+    }
+
+    /// Hex-encoded RLP of the header of `block`, for `debug_getRawHeader`
+    pub async fn raw_header(&self, block: BlockId) -> Result<String> {
+        let header = self.resolve_header(&block)?;
+        Ok(format!("0x{}", hex::encode(ethereum_rlp::encode(&header))))
+    }
+
+    /// Hex-encoded RLP of the whole of `block`, for `debug_getRawBlock`
+    pub async fn raw_block(&self, block: BlockId) -> Result<String> {
+        let header = self.resolve_header(&block)?;
+        let block = self.load_block(&header.hash())?.ok_or(RpcError::ResourceNotFound)?;
+        Ok(format!("0x{}", hex::encode(ethereum_rlp::encode(&block))))
+    }
+
+    /// EIP-2718 encoded receipts of `block` in order, for `debug_getRawReceipts`
+    pub async fn raw_receipts(&self, block: BlockId) -> Result<Vec<String>> {
+        let header = self.resolve_header(&block)?;
+        Ok(self.load_receipts(&header.hash())?
+            .iter()
+            .map(|receipt| format!("0x{}", hex::encode(receipt.encoded_2718())))
+            .collect())
+    }
+
+    /// Blocks the node rejected, with why, for `debug_getBadBlocks`
+    pub async fn bad_blocks(&self) -> Result<Vec<ethereum_state::BadBlock>> {
+        Ok(ethereum_state::bad_blocks(self.db.as_ref())?)
     }
     
     pub async fn gas_price(&self) -> Result<U256> {
@@ -124,8 +483,75 @@ impl EthApi {
         Ok(U256::from(20_000_000_000u64)) // 20 gwei
     }
     
+    /// Tip suggested for dynamic fee transactions
+    pub async fn max_priority_fee_per_gas(&self) -> Result<U256> {
+        Ok(U256::from(DEFAULT_PRIORITY_FEE))
+    }
+    
+    /// Fill in the fields a node-signed `request` leaves out
+    ///
+    /// The nonce follows both the latest state and the pending transactions
+    /// `txpool` already holds for the sender. Gas is estimated on the latest state.
+    /// Without an explicit gas price the fees are dynamic once the head has
+    /// a base fee, allowing for it to double, and legacy otherwise.
+    pub async fn fill_transaction(&self, mut request: TransactionRequest, txpool: &TransactionPool) -> Result<TransactionRequest> {
+        if request.nonce.is_none() {
+            // Queued transactions past a gap don't count, the gap is filled first
+            let state_nonce = self.get_transaction_count(request.from, None).await?;
+            let pooled = txpool.get_next_nonce(&Address::from(request.from));
+            request.nonce = Some(state_nonce.max(pooled));
+        }
+        
+        if request.gas.is_none() {
+            let call = CallRequest {
+                from: Some(request.from),
+                to: request.to,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                value: request.value,
+                data: request.data.clone(),
+                nonce: None,
+            };
+            request.gas = Some(self.estimate_gas(call).await?);
+        }
+        
+        if request.gas_price.is_none() {
+            let base_fee = self.resolve_header(&BlockId::default())?.base_fee_per_gas;
+            match base_fee {
+                Some(base_fee) => {
+                    let tip = match request.max_priority_fee_per_gas {
+                        Some(tip) => tip,
+                        None => self.max_priority_fee_per_gas().await?,
+                    };
+                    request.max_priority_fee_per_gas = Some(tip);
+                    request.max_fee_per_gas.get_or_insert(base_fee * 2 + tip);
+                }
+                None if request.max_fee_per_gas.is_none() && request.max_priority_fee_per_gas.is_none() => {
+                    request.gas_price = Some(self.gas_price().await?);
+                }
+                None => {}
+            }
+        }
+        
+        Ok(request)
+    }
+    
+    /// Blob base fee a blob transaction in the next block pays, derived from
+    /// the head's excess blob gas
+    ///
+    /// Errors while the head predates Cancun and carries no excess blob gas.
+    pub async fn blob_base_fee(&self) -> Result<U256> {
+        let head = self.resolve_header(&BlockId::default())?;
+        let excess_blob_gas = head.excess_blob_gas.ok_or_else(|| {
+            RpcError::InvalidParams("blob base fee is not available before Cancun".to_string())
+        })?;
+        Ok(calculate_blob_base_fee(excess_blob_gas, &self.chain_config.blob_config(head.timestamp)))
+    }
+    
     pub async fn chain_id(&self) -> Result<U256> {
-        Ok(U256::from(self.chain_id))
+        Ok(U256::from(self.chain_config.chain_id))
     }
     
     pub async fn syncing(&self) -> Result<SyncStatus> {
@@ -175,79 +601,938 @@ impl EthApi {
             Some(BlockNumber::Earliest) => Ok(U256::zero()),
             Some(BlockNumber::Pending) => self.block_number().await,
             Some(BlockNumber::Number(n)) => Ok(n),
+            Some(tag) => Ok(self.resolve_header(&BlockId::Number(tag))?.number),
         }
     }
     
-    fn convert_block(&self, hash: H256, full_transactions: bool) -> Result<Block> {
+    /// State as of `block` along with the context of the block it belongs to
+    ///
+    /// "pending" is the head state with the pool's pending transactions
+    /// applied, in the context of the next block.
+    fn state_at(&self, block: &BlockId) -> Result<(CallState<'_>, BlockContext)> {
+        let header = self.resolve_header(block)?;
+        let mut state = CallState::new(self.state.as_ref(), header.state_root);
+        let mut context = BlockContext::from_header(&header, &self.chain_config, Vec::new());
+
+        if matches!(block, BlockId::Number(BlockNumber::Pending)) {
+            context = call::pending_block_context(&context);
+            if let Some(pool) = &self.txpool {
+                call::apply_pending(&mut state, &context, pool.get_pending());
+                state.finish()?;
+            }
+        }
+
+        Ok((state, context))
+    }
+    
+    /// Header of the block a state-reading method should run against
+    ///
+    /// "pending" resolves to the head, callers layer the pending
+    /// transactions on top themselves.
+    fn resolve_header(&self, block: &BlockId) -> Result<Header> {
+        let number = match block {
+            BlockId::Hash { block_hash, require_canonical } => {
+                let header = self.load_block(block_hash)?
+                    .ok_or(RpcError::ResourceNotFound)?
+                    .header;
+                if *require_canonical && self.canonical_hash(header.number)? != Some(*block_hash) {
+                    return Err(RpcError::InvalidParams(format!(
+                        "block {:?} is not canonical", block_hash
+                    )));
+                }
+                return Ok(header);
+            }
+            BlockId::Number(number) => number,
+        };
+
+        self.block_by_number(number)?
+            .map(|(_, block)| block.header)
+            .ok_or(RpcError::ResourceNotFound)
+    }
+
+    /// Hash and body of the block `number` refers to, "pending" being the head
+    fn block_by_number(&self, number: &BlockNumber) -> Result<Option<(H256, CoreBlock)>> {
+        let hash = match number {
+            BlockNumber::Latest | BlockNumber::Pending => self.head_hash()?,
+            BlockNumber::Earliest => self.canonical_hash(U256::zero())?,
+            BlockNumber::Finalized => self.checkpoints.finalized()
+                .map_err(|e| RpcError::InternalError(e.to_string()))?
+                .map(|checkpoint| checkpoint.hash),
+            BlockNumber::Safe => self.checkpoints.safe()
+                .map_err(|e| RpcError::InternalError(e.to_string()))?
+                .map(|checkpoint| checkpoint.hash),
+            BlockNumber::Number(number) => self.canonical_hash(*number)?,
+        };
+        let Some(hash) = hash else {
+            return Ok(None);
+        };
+        Ok(self.load_block(&hash)?.map(|block| (hash, block)))
+    }
+
+    fn head_hash(&self) -> Result<Option<H256>> {
+        self.read_hash(HEAD_KEY)
+    }
+
+    fn canonical_hash(&self, number: U256) -> Result<Option<H256>> {
+        self.read_hash(format!("number:{}", number).as_bytes())
+    }
+
+    fn read_hash(&self, key: &[u8]) -> Result<Option<H256>> {
+        match self.db.get(key) {
+            Ok(Some(data)) if data.len() >= 32 => Ok(Some(H256::from_slice(&data[..32]))),
+            Ok(Some(_)) => Err(RpcError::InternalError("corrupt block hash entry".to_string())),
+            Ok(None) => Ok(None),
+            Err(e) => Err(RpcError::InternalError(e.to_string())),
+        }
+    }
+
+    fn load_block(&self, hash: &H256) -> Result<Option<CoreBlock>> {
+        let key = format!("block:{}", hex::encode(hash.as_bytes()));
+        match self.db.get(key.as_bytes()) {
+            Ok(Some(data)) => bincode::deserialize(&data)
+                .map(Some)
+                .map_err(|e| RpcError::InternalError(e.to_string())),
+            Ok(None) => Ok(None),
+            Err(e) => Err(RpcError::InternalError(e.to_string())),
+        }
+    }
+
+    fn load_receipts(&self, block_hash: &H256) -> Result<Vec<CoreReceipt>> {
+        let key = format!("receipts:{}", hex::encode(block_hash.as_bytes()));
+        match self.db.get(key.as_bytes()) {
+            Ok(Some(data)) => bincode::deserialize(&data)
+                .map_err(|e| RpcError::InternalError(e.to_string())),
+            Ok(None) => Ok(Vec::new()),
+            Err(e) => Err(RpcError::InternalError(e.to_string())),
+        }
+    }
+
+    fn convert_block(&self, hash: H256, block: &CoreBlock, full_transactions: bool) -> Result<Block> {
         // Convert core block to RPC block format
         // This is a simplified version
+        let header = &block.header;
         Ok(Block {
-            number: Some(U256::zero()),
+            number: Some(header.number),
             hash: Some(hash),
-            parent_hash: H256::zero(),
-            nonce: Some(U256::zero()),
-            sha3_uncles: H256::zero(),
-            logs_bloom: Some("0x".to_string()),
-            transactions_root: H256::zero(),
-            state_root: H256::zero(),
-            receipts_root: H256::zero(),
-            miner: H160::zero(),
-            difficulty: U256::zero(),
+            parent_hash: header.parent_hash,
+            nonce: Some(U256::from(header.nonce)),
+            sha3_uncles: header.ommers_hash,
+            logs_bloom: Some(format!("0x{}", hex::encode(header.logs_bloom.as_bytes()))),
+            transactions_root: header.transactions_root,
+            state_root: header.state_root,
+            receipts_root: header.receipts_root,
+            miner: H160::from_slice(header.beneficiary.as_bytes()),
+            difficulty: header.difficulty,
             total_difficulty: Some(U256::zero()),
-            extra_data: "0x".to_string(),
+            extra_data: format!("0x{}", hex::encode(&header.extra_data)),
             size: U256::zero(),
-            gas_limit: U256::from(8_000_000),
-            gas_used: U256::zero(),
-            timestamp: U256::zero(),
+            gas_limit: header.gas_limit,
+            gas_used: header.gas_used,
+            timestamp: U256::from(header.timestamp),
             transactions: Vec::new(),
-            uncles: Vec::new(),
-            base_fee_per_gas: Some(U256::from(1_000_000_000)),
+            uncles: block.ommers.iter().map(|ommer| ommer.hash()).collect(),
+            base_fee_per_gas: header.base_fee_per_gas,
+            blob_gas_used: header.blob_gas_used.map(U256::from),
+            excess_blob_gas: header.excess_blob_gas.map(U256::from),
         })
     }
     
-    fn convert_transaction(&self, hash: H256) -> Result<Transaction> {
-        // Convert core transaction to RPC format
-        // This is a simplified version
+    /// Transaction `index` of `block`, `None` past its last transaction
+    fn transaction_at(&self, block_hash: H256, block: &CoreBlock, index: U256) -> Result<Option<Transaction>> {
+        if index >= U256::from(block.transactions.len()) {
+            return Ok(None);
+        }
+        self.convert_transaction(block_hash, block, index.as_usize()).map(Some)
+    }
+    
+    /// RPC transaction `index` of `block`, carrying its block position
+    fn convert_transaction(&self, block_hash: H256, block: &CoreBlock, index: usize) -> Result<Transaction> {
+        let tx = &block.transactions[index];
+        let from = tx.sender().map_err(|e| RpcError::InternalError(e.to_string()))?;
+        let base_fee = block.header.base_fee_per_gas.unwrap_or_default();
+        
+        let mut rpc = RpcTransaction::from(tx);
+        rpc.from = Some(from);
+        // Dynamic fee transactions report the price they paid in the block
+        rpc.gas_price = Some(tx.effective_gas_price(base_fee));
+        
         Ok(Transaction {
-            hash,
-            nonce: U256::zero(),
-            block_hash: None,
-            block_number: None,
-            transaction_index: None,
-            from: H160::zero(),
-            to: None,
-            value: U256::zero(),
-            gas_price: Some(U256::from(20_000_000_000u64)),
-            gas: U256::from(21000),
-            input: "0x".to_string(),
-            v: U256::zero(),
-            r: U256::zero(),
-            s: U256::zero(),
-            tx_type: Some(U256::from(2)),
-            max_fee_per_gas: Some(U256::from(30_000_000_000u64)),
-            max_priority_fee_per_gas: Some(U256::from(1_000_000_000)),
-            access_list: None,
+            tx: rpc,
+            block_hash: Some(block_hash),
+            block_number: Some(block.header.number),
+            transaction_index: Some(U256::from(index)),
         })
     }
     
-    fn convert_receipt(&self, hash: H256) -> Result<Receipt> {
-        // Convert core receipt to RPC format
-        // This is a simplified version
+    /// RPC receipt of transaction `index` in `block`, given all of the
+    /// block's receipts
+    fn convert_receipt(&self, block_hash: H256, block: &CoreBlock, index: usize, receipts: &[CoreReceipt]) -> Result<Receipt> {
+        let header = &block.header;
+        let tx = &block.transactions[index];
+        let receipt = &receipts[index];
+        let hash = tx.hash();
+        
+        let from = tx.sender().map_err(|e| RpcError::InternalError(e.to_string()))?;
+        let contract_address = match receipt.contract_address {
+            Some(address) => Some(address),
+            None if tx.is_create() => Some(create_address(&from, tx.nonce().low_u64())),
+            None => None,
+        };
+        
+        let (blob_gas_used, blob_gas_price) = match (tx.blob_gas(), header.excess_blob_gas) {
+            (0, _) | (_, None) => (None, None),
+            (blob_gas, Some(excess)) => (
+                Some(U256::from(blob_gas)),
+                Some(calculate_blob_base_fee(excess, &self.chain_config.blob_config(header.timestamp))),
+            ),
+        };
+        
+        // Log indexes count from the start of the block
+        let first_log_index: usize = receipts[..index].iter().map(|receipt| receipt.logs.len()).sum();
+        let logs = receipt.logs.iter().enumerate().map(|(i, log)| Log {
+            removed: false,
+            log_index: U256::from(first_log_index + i),
+            transaction_index: U256::from(index),
+            transaction_hash: hash,
+            block_hash,
+            block_number: header.number,
+            address: H160::from_slice(log.address.as_bytes()),
+            data: format!("0x{}", hex::encode(&log.data)),
+            topics: log.topics.clone(),
+        }).collect();
+        
         Ok(Receipt {
             transaction_hash: hash,
-            transaction_index: U256::zero(),
-            block_hash: H256::zero(),
-            block_number: U256::zero(),
-            from: H160::zero(),
-            to: None,
-            cumulative_gas_used: U256::from(21000),
-            gas_used: U256::from(21000),
-            contract_address: None,
-            logs: Vec::new(),
-            logs_bloom: "0x".to_string(),
-            status: U256::one(),
-            effective_gas_price: U256::from(20_000_000_000u64),
-            tx_type: U256::from(2),
+            transaction_index: U256::from(index),
+            block_hash,
+            block_number: header.number,
+            from: H160::from_slice(from.as_bytes()),
+            to: tx.to().map(|to| H160::from_slice(to.as_bytes())),
+            cumulative_gas_used: receipt.cumulative_gas_used,
+            gas_used: receipt.gas_used,
+            contract_address: contract_address.map(|address| H160::from_slice(address.as_bytes())),
+            logs,
+            logs_bloom: format!("0x{}", hex::encode(receipt.logs_bloom.as_bytes())),
+            status: U256::from(receipt.status),
+            effective_gas_price: tx.effective_gas_price(header.base_fee_per_gas.unwrap_or_default()),
+            tx_type: U256::from(tx.tx_type()),
+            blob_gas_used,
+            blob_gas_price,
         })
     }
-}
\ No newline at end of file
+}
+/// Return data of a successful call, reverts carry their output as error data
+fn call_output(result: ExecutionResult) -> Result<Vec<u8>> {
+    match result.status {
+        ExecutionStatus::Success => Ok(result.return_data),
+        ExecutionStatus::Revert => Err(RpcError::reverted(result.return_data)),
+        ExecutionStatus::Halt(reason) => Err(RpcError::InternalError(format!("execution halted: {:?}", reason))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use ethereum_consensus::Checkpoint;
+    use ethereum_core::{Eip1559Transaction, LegacyTransaction};
+    use ethereum_crypto::{generate_private_key, public_key_to_address, sign_message};
+    use ethereum_evm::Account;
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_trie::PatriciaTrie;
+    use ethereum_txpool::{MemoryNonceProvider, TxPoolConfig};
+    use crate::state::StateAccount;
+    use ethereum_types::{Address, Bytes};
+
+    /// Stores the call data word in slot 0 when called with data, returns slot 0 otherwise
+    const SLOT_CONTRACT: &str = "36600f5760005460005260206000f35b60003560005500";
+
+    /// Account state keyed by state root
+    #[derive(Default)]
+    struct MapState(HashMap<H256, HashMap<Address, Account>>);
+
+    impl StateProvider for MapState {
+        fn account(&self, state_root: H256, address: &Address) -> ethereum_state::Result<Option<Account>> {
+            Ok(self.0.get(&state_root).and_then(|accounts| accounts.get(address)).cloned())
+        }
+
+        fn storage(&self, state_root: H256, address: &Address, slot: &H256) -> ethereum_state::Result<H256> {
+            Ok(self.0.get(&state_root)
+                .and_then(|accounts| accounts.get(address))
+                .and_then(|account| account.storage.get(slot))
+                .copied()
+                .unwrap_or_default())
+        }
+    }
+
+    fn contract_address() -> Address {
+        Address::from_bytes([0xcc; 20])
+    }
+
+    fn contract(slot0: u64) -> Account {
+        let mut account = Account {
+            code: hex::decode(SLOT_CONTRACT).unwrap(),
+            ..Default::default()
+        };
+        account.storage.insert(H256::zero(), H256::from_low_u64_be(slot0));
+        account
+    }
+
+    /// Store a canonical block committing to `state_root`
+    fn insert_block(db: &MemoryDatabase, number: u64, state_root: H256) -> H256 {
+        let mut header = Header::new();
+        header.number = U256::from(number);
+        header.state_root = state_root;
+        header.gas_limit = U256::from(30_000_000);
+        let hash = header.hash();
+
+        let block = CoreBlock { header, transactions: Vec::new(), ommers: Vec::new(), withdrawals: None };
+        db.put(format!("block:{}", hex::encode(hash.as_bytes())).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+        db.put(format!("number:{}", number).as_bytes(), hash.as_bytes()).unwrap();
+        db.put(HEAD_KEY, hash.as_bytes()).unwrap();
+        hash
+    }
+
+    /// Store a Cancun block on top of the head with the given blob gas fields
+    fn insert_cancun_block(db: &MemoryDatabase, number: u64, blob_gas_used: u64, excess_blob_gas: u64) -> H256 {
+        let mut header = Header::new();
+        header.number = U256::from(number);
+        header.base_fee_per_gas = Some(U256::from(7));
+        header.blob_gas_used = Some(blob_gas_used);
+        header.excess_blob_gas = Some(excess_blob_gas);
+        let hash = header.hash();
+
+        let block = CoreBlock::new(header);
+        db.put(format!("block:{}", hex::encode(hash.as_bytes())).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+        db.put(format!("number:{}", number).as_bytes(), hash.as_bytes()).unwrap();
+        db.put(HEAD_KEY, hash.as_bytes()).unwrap();
+        hash
+    }
+
+    /// Store a canonical block holding `transactions` and their receipts
+    fn insert_block_with_receipts(db: &MemoryDatabase, number: u64, base_fee: U256, transactions: Vec<CoreTransaction>, receipts: Vec<CoreReceipt>) -> H256 {
+        let mut header = Header::new();
+        header.number = U256::from(number);
+        header.base_fee_per_gas = Some(base_fee);
+        let hash = header.hash();
+
+        for tx in &transactions {
+            db.put(format!("tx:block:{}", hex::encode(tx.hash().as_bytes())).as_bytes(), hash.as_bytes()).unwrap();
+        }
+        let block = CoreBlock { header, transactions, ommers: Vec::new(), withdrawals: None };
+        db.put(format!("block:{}", hex::encode(hash.as_bytes())).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+        db.put(format!("receipts:{}", hex::encode(hash.as_bytes())).as_bytes(), &bincode::serialize(&receipts).unwrap()).unwrap();
+        db.put(format!("number:{}", number).as_bytes(), hash.as_bytes()).unwrap();
+        db.put(HEAD_KEY, hash.as_bytes()).unwrap();
+        hash
+    }
+
+    fn core_receipt(tx: &CoreTransaction, cumulative_gas_used: u64, logs: usize) -> CoreReceipt {
+        CoreReceipt {
+            tx_type: tx.tx_type(),
+            status: 1,
+            cumulative_gas_used: U256::from(cumulative_gas_used),
+            logs_bloom: Default::default(),
+            logs: (0..logs).map(|_| ethereum_core::Log::new(contract_address(), Vec::new(), vec![0xab])).collect(),
+            gas_used: U256::from(21_000),
+            contract_address: None,
+        }
+    }
+
+    fn read_slot_request() -> CallRequest {
+        CallRequest {
+            from: None,
+            to: Some(H160::from_slice(contract_address().as_bytes())),
+            gas: None,
+            gas_price: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            value: None,
+            data: None,
+            nonce: None,
+        }
+    }
+
+    fn word(value: u64) -> String {
+        format!("0x{}", hex::encode(H256::from_low_u64_be(value).as_bytes()))
+    }
+
+    #[tokio::test]
+    async fn test_call_at_block_hash() {
+        let db = Arc::new(MemoryDatabase::new());
+        let (old_root, new_root) = (H256::repeat_byte(1), H256::repeat_byte(2));
+
+        let mut state = MapState::default();
+        state.0.insert(old_root, HashMap::from([(contract_address(), contract(7))]));
+        state.0.insert(new_root, HashMap::from([(contract_address(), contract(9))]));
+
+        let old_hash = insert_block(&db, 1, old_root);
+        insert_block(&db, 2, new_root);
+        let api = EthApi::new(db).with_state_provider(Arc::new(state));
+
+        let at_hash = api.call(read_slot_request(), Some(BlockId::from(old_hash))).await.unwrap();
+        assert_eq!(at_hash, word(7));
+
+        let latest = api.call(read_slot_request(), None).await.unwrap();
+        assert_eq!(latest, word(9));
+
+        let unknown = api.call(read_slot_request(), Some(BlockId::from(H256::repeat_byte(0xee)))).await;
+        assert!(matches!(unknown, Err(RpcError::ResourceNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_calls_see_earlier_writes() {
+        let db = Arc::new(MemoryDatabase::new());
+        let root = H256::repeat_byte(1);
+        let mut state = MapState::default();
+        state.0.insert(root, HashMap::from([(contract_address(), contract(7))]));
+        insert_block(&db, 1, root);
+        let api = EthApi::new(db).with_state_provider(Arc::new(state));
+
+        let write = CallRequest { data: Some(word(42)), ..read_slot_request() };
+        let payload: SimulatePayload = serde_json::from_value(serde_json::json!({
+            "blockStateCalls": [{ "calls": [write, read_slot_request()] }],
+        })).unwrap();
+
+        let blocks = api.simulate_v1(payload, None).await.unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].number, U256::from(2));
+        let calls = &blocks[0].calls;
+        assert_eq!(calls.len(), 2);
+        assert!(calls.iter().all(|call| call.status == U256::one() && call.error.is_none()));
+        assert_eq!(calls[1].return_data, word(42));
+        assert_eq!(blocks[0].gas_used, calls[0].gas_used + calls[1].gas_used);
+
+        // Nothing is committed
+        assert_eq!(api.call(read_slot_request(), None).await.unwrap(), word(7));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_traces_nested_transfers() {
+        // Forwards its call value to 0x77..77 and stops
+        let mut code = hex::decode("60006000600060003473").unwrap();
+        code.extend_from_slice(&[0x77; 20]);
+        code.extend_from_slice(&hex::decode("5af100").unwrap());
+        let forwarder = Address::from_bytes([0xf0; 20]);
+        let rich = Address::from_bytes([0x11; 20]);
+        let poor = Address::from_bytes([0x22; 20]);
+
+        let db = Arc::new(MemoryDatabase::new());
+        let root = H256::repeat_byte(1);
+        let mut state = MapState::default();
+        state.0.insert(root, HashMap::from([
+            (forwarder, Account { code, ..Default::default() }),
+            (rich, Account { balance: U256::from(1_000), ..Default::default() }),
+            (poor, Account { balance: U256::from(1), ..Default::default() }),
+        ]));
+        insert_block(&db, 1, root);
+        let api = EthApi::new(db).with_state_provider(Arc::new(state));
+
+        let send = |from: Address, value: u64| serde_json::json!({
+            "from": H160::from_slice(from.as_bytes()),
+            "to": H160::from_slice(forwarder.as_bytes()),
+            "value": format!("0x{:x}", value),
+        });
+        let payload: SimulatePayload = serde_json::from_value(serde_json::json!({
+            "blockStateCalls": [{ "calls": [send(rich, 5), send(poor, 5), send(rich, 0)] }],
+            "traceTransfers": true,
+        })).unwrap();
+        let blocks = api.simulate_v1(payload, None).await.unwrap();
+        let calls = &blocks[0].calls;
+
+        // The call's own transfer and the one its callee made
+        let topic = |address: Address| H256::from_slice(&[&[0u8; 12][..], address.as_bytes()].concat());
+        assert_eq!(calls[0].status, U256::one());
+        assert_eq!(calls[0].logs.len(), 2);
+        assert_eq!(calls[0].logs[0].topics[1..], [topic(rich), topic(forwarder)]);
+        assert_eq!(calls[0].logs[1].topics[1..], [topic(forwarder), topic(Address::from_bytes([0x77; 20]))]);
+        assert_eq!(calls[0].logs[1].log_index, U256::one());
+
+        // A sender that can't pay fails alone, the next call still runs
+        assert_eq!(calls[1].status, U256::zero());
+        assert_eq!(calls[1].error.as_ref().unwrap().code, -38014);
+        assert_eq!(calls[2].status, U256::one());
+        assert!(calls[2].logs.is_empty());
+        assert_eq!(blocks[0].gas_used, calls[0].gas_used + calls[2].gas_used);
+
+        // Calls share the block's gas, the second doesn't fit in what's left
+        let payload: SimulatePayload = serde_json::from_value(serde_json::json!({
+            "blockStateCalls": [{
+                "blockOverrides": { "gasLimit": "0xc350" },
+                "calls": [
+                    serde_json::json!({ "from": H160::from_slice(rich.as_bytes()), "to": H160::from_slice(&[0x77; 20]), "gas": "0x7530" }),
+                    serde_json::json!({ "from": H160::from_slice(rich.as_bytes()), "to": H160::from_slice(&[0x77; 20]), "gas": "0x7530" }),
+                ],
+            }],
+        })).unwrap();
+        assert!(matches!(api.simulate_v1(payload, None).await, Err(RpcError::InvalidParams(_))));
+    }
+
+    #[tokio::test]
+    async fn test_revert_returns_error_data() {
+        // Error("nope")
+        let mut revert_data = hex::decode("08c379a0").unwrap();
+        revert_data.extend_from_slice(H256::from_low_u64_be(0x20).as_bytes());
+        revert_data.extend_from_slice(H256::from_low_u64_be(4).as_bytes());
+        let mut reason = [0u8; 32];
+        reason[..4].copy_from_slice(b"nope");
+        revert_data.extend_from_slice(&reason);
+
+        // CODECOPY the trailing revert data to memory and REVERT with it
+        let mut code = hex::decode("6064600c60003960646000fd").unwrap();
+        code.extend_from_slice(&revert_data);
+
+        let db = Arc::new(MemoryDatabase::new());
+        let root = H256::repeat_byte(1);
+        let mut state = MapState::default();
+        state.0.insert(root, HashMap::from([(contract_address(), Account { code, ..Default::default() })]));
+        insert_block(&db, 1, root);
+        let api = EthApi::new(db).with_state_provider(Arc::new(state));
+
+        let error = api.call(read_slot_request(), None).await.unwrap_err();
+        assert_eq!(error.code(), 3);
+        assert_eq!(error.to_string(), "execution reverted: nope");
+
+        let response = serde_json::to_value(error.to_response()).unwrap();
+        assert_eq!(response["code"], 3);
+        assert_eq!(response["data"], format!("0x{}", hex::encode(&revert_data)));
+
+        assert_eq!(api.estimate_gas(read_slot_request()).await.unwrap_err().code(), 3);
+
+        // Other errors carry no data member
+        let response = serde_json::to_value(RpcError::ResourceNotFound.to_response()).unwrap();
+        assert!(response.get("data").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_estimate_gas_is_lowest_passing_limit() {
+        let db = Arc::new(MemoryDatabase::new());
+        let root = H256::repeat_byte(1);
+        let forwarder = Address::from_bytes([0xfa; 20]);
+
+        // CALL the slot contract with all but a 64th of the gas left,
+        // reverting if it fails
+        let mut code = hex::decode("60006000600060006000").unwrap();
+        code.push(0x73);
+        code.extend_from_slice(contract_address().as_bytes());
+        code.extend_from_slice(&hex::decode("5af115602657005b600080fd").unwrap());
+        let mut state = MapState::default();
+        state.0.insert(root, HashMap::from([
+            (contract_address(), contract(7)),
+            (forwarder, Account { code, ..Default::default() }),
+        ]));
+        insert_block(&db, 1, root);
+        let api = EthApi::new(db).with_state_provider(Arc::new(state));
+
+        let store = CallRequest { data: Some(word(42)), ..read_slot_request() };
+        let forward = CallRequest { to: Some(H160::from_slice(forwarder.as_bytes())), ..read_slot_request() };
+        for request in [store, forward] {
+            let estimate = api.estimate_gas(request.clone()).await.unwrap();
+            // Calldata is paid for on top of the base cost
+            assert!(estimate > U256::from(call::TX_BASE_GAS));
+
+            let with = |gas: U256| CallRequest { gas: Some(gas), ..request.clone() };
+            assert!(api.call(with(estimate), None).await.is_ok());
+            assert!(api.call(with(estimate - 1), None).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_sees_pending_transactions() {
+        let db = Arc::new(MemoryDatabase::new());
+        let root = H256::repeat_byte(1);
+
+        let key = generate_private_key();
+        let sender = public_key_to_address(&key.public_key(&secp256k1::Secp256k1::new()));
+
+        let mut accounts = HashMap::from([(contract_address(), contract(7))]);
+        accounts.insert(sender, Account { balance: U256::exp10(18), ..Default::default() });
+        let mut state = MapState::default();
+        state.0.insert(root, accounts);
+        insert_block(&db, 1, root);
+
+        // Pending transaction writing 42 into the contract's slot 0
+        let mut tx = LegacyTransaction {
+            nonce: U256::zero(),
+            gas_price: U256::from(1_000_000_000u64),
+            gas_limit: U256::from(100_000),
+            to: Some(contract_address()),
+            value: U256::zero(),
+            data: Bytes::from_slice(H256::from_low_u64_be(42).as_bytes()),
+            v: 0,
+            r: U256::zero(),
+            s: U256::zero(),
+        };
+        let signature = sign_message(&tx.signing_hash(None), &key).unwrap();
+        tx.v = signature.v as u64;
+        tx.r = U256::from_big_endian(signature.r.as_bytes());
+        tx.s = U256::from_big_endian(signature.s.as_bytes());
+
+        let pool = Arc::new(TransactionPool::new(TxPoolConfig::default(), Arc::new(MemoryNonceProvider::new())));
+        pool.add_transaction(CoreTransaction::Legacy(tx)).unwrap();
+
+        let api = EthApi::new(db)
+            .with_state_provider(Arc::new(state))
+            .with_txpool(pool);
+
+        let latest = api.call(read_slot_request(), Some(BlockNumber::Latest.into())).await.unwrap();
+        assert_eq!(latest, word(7));
+
+        let pending = api.call(read_slot_request(), Some(BlockNumber::Pending.into())).await.unwrap();
+        assert_eq!(pending, word(42));
+    }
+
+    #[tokio::test]
+    async fn test_balance_at_historical_block() {
+        let db = Arc::new(MemoryDatabase::new());
+        let sender = Address::from_bytes([0x01; 20]);
+        let recipient = Address::from_bytes([0x02; 20]);
+        let account_key = |address: &Address| ethereum_crypto::keccak256(address.as_bytes());
+
+        let mut trie = PatriciaTrie::new(db.clone());
+        let funded = StateAccount { balance: U256::from(100), ..Default::default() };
+        trie.insert(account_key(&sender).as_bytes(), funded.encode()).unwrap();
+        let old_root = trie.commit().unwrap();
+
+        // Block 2 moves 40 wei from the sender to a new account
+        let spent = StateAccount { nonce: 1, balance: U256::from(60), ..Default::default() };
+        let received = StateAccount { balance: U256::from(40), ..Default::default() };
+        trie.insert(account_key(&sender).as_bytes(), spent.encode()).unwrap();
+        trie.insert(account_key(&recipient).as_bytes(), received.encode()).unwrap();
+        let new_root = trie.commit().unwrap();
+
+        let old_hash = insert_block(&db, 1, old_root);
+        insert_block(&db, 2, new_root);
+        CheckpointStore::new(db.clone()).set_finalized(Checkpoint::new(old_hash, 1)).unwrap();
+
+        let api = EthApi::new(db);
+        let (sender, recipient) = (H160::from_slice(sender.as_bytes()), H160::from_slice(recipient.as_bytes()));
+        let at_block_one = Some(BlockId::from(BlockNumber::Number(U256::one())));
+
+        assert_eq!(api.get_balance(sender, at_block_one.clone()).await.unwrap(), U256::from(100));
+        assert_eq!(api.get_balance(sender, None).await.unwrap(), U256::from(60));
+        assert_eq!(api.get_balance(sender, Some(BlockNumber::Finalized.into())).await.unwrap(), U256::from(100));
+        assert_eq!(api.get_balance(sender, Some(BlockId::from(old_hash))).await.unwrap(), U256::from(100));
+
+        assert_eq!(api.get_transaction_count(sender, at_block_one.clone()).await.unwrap(), U256::zero());
+        assert_eq!(api.get_transaction_count(sender, None).await.unwrap(), U256::one());
+
+        // The recipient did not exist yet at block 1
+        assert_eq!(api.get_balance(recipient, at_block_one.clone()).await.unwrap(), U256::zero());
+        assert_eq!(api.get_balance(recipient, None).await.unwrap(), U256::from(40));
+        assert_eq!(api.get_code(recipient, at_block_one).await.unwrap(), "0x");
+    }
+
+    #[tokio::test]
+    async fn test_storage_at_historical_block() {
+        let db = Arc::new(MemoryDatabase::new());
+        let contract = contract_address();
+        let account_key = ethereum_crypto::keccak256(contract.as_bytes());
+        let slot_key = |slot: u64| ethereum_crypto::keccak256(H256::from_low_u64_be(slot).as_bytes());
+
+        let mut state = PatriciaTrie::new(db.clone());
+        state.insert(account_key.as_bytes(), StateAccount { nonce: 1, ..Default::default() }.encode()).unwrap();
+        let old_root = state.commit().unwrap();
+
+        // Block 2 sets slot 1 to 42
+        let mut storage = PatriciaTrie::new(db.clone());
+        storage.insert(slot_key(1).as_bytes(), ethereum_rlp::encode(&[42u8].as_slice())[..].to_vec()).unwrap();
+        let storage_root = storage.commit().unwrap();
+        state.insert(account_key.as_bytes(), StateAccount { nonce: 1, storage_root, ..Default::default() }.encode()).unwrap();
+        let new_root = state.commit().unwrap();
+
+        insert_block(&db, 1, old_root);
+        insert_block(&db, 2, new_root);
+
+        let api = EthApi::new(db);
+        let contract = H160::from_slice(contract.as_bytes());
+        let at = |number: u64| Some(BlockId::from(BlockNumber::Number(U256::from(number))));
+
+        assert_eq!(api.get_storage_at(contract, U256::one(), at(2)).await.unwrap(), H256::from_low_u64_be(42));
+        assert_eq!(api.get_storage_at(contract, U256::one(), None).await.unwrap(), H256::from_low_u64_be(42));
+        assert_eq!(api.get_storage_at(contract, U256::one(), at(1)).await.unwrap(), H256::zero());
+        assert_eq!(api.get_storage_at(contract, U256::from(2), at(1)).await.unwrap(), H256::zero());
+
+        let absent = H160::from_slice(&[0xdd; 20]);
+        assert_eq!(api.get_storage_at(absent, U256::one(), at(2)).await.unwrap(), H256::zero());
+    }
+
+    #[tokio::test]
+    async fn test_storage_range_pagination() {
+        let db = Arc::new(MemoryDatabase::new());
+        let contract = contract_address();
+        let slot = |slot: u64| H256::from_low_u64_be(slot);
+
+        let mut storage = PatriciaTrie::new(db.clone());
+        for n in 1..=5u64 {
+            let hash = ethereum_crypto::keccak256(slot(n).as_bytes());
+            storage.insert(hash.as_bytes(), ethereum_rlp::encode(&[n as u8 * 10].as_slice())[..].to_vec()).unwrap();
+        }
+        let storage_root = storage.commit().unwrap();
+        let mut state = PatriciaTrie::new(db.clone());
+        state.insert(ethereum_crypto::keccak256(contract.as_bytes()).as_bytes(), StateAccount { nonce: 1, storage_root, ..Default::default() }.encode()).unwrap();
+        let state_root = state.commit().unwrap();
+
+        // Only slot 3 has a recorded preimage
+        let hashed_three = ethereum_crypto::keccak256(slot(3).as_bytes());
+        db.put(crate::state::preimage_key(&hashed_three).as_bytes(), slot(3).as_bytes()).unwrap();
+
+        let block_hash = insert_block(&db, 1, state_root);
+        let api = EthApi::new(db);
+        let contract = H160::from_slice(contract.as_bytes());
+
+        let first = api.storage_range_at(BlockId::from(block_hash), 0, contract, H256::zero(), 3).await.unwrap();
+        assert_eq!(first.storage.len(), 3);
+        let next_key = first.next_key.unwrap();
+        assert!(first.storage.keys().all(|hash| *hash < next_key));
+
+        let second = api.storage_range_at(BlockId::from(block_hash), 0, contract, next_key, 3).await.unwrap();
+        assert_eq!(second.storage.len(), 2);
+        assert_eq!(second.next_key, None);
+        assert_eq!(second.storage.keys().next(), Some(&next_key));
+
+        let mut values: Vec<_> = first.storage.values().chain(second.storage.values()).map(|entry| entry.value).collect();
+        values.sort();
+        assert_eq!(values, (1..=5u64).map(|n| slot(n * 10)).collect::<Vec<_>>());
+
+        let pages = first.storage.iter().chain(second.storage.iter());
+        for (hash, entry) in pages {
+            let expected = (*hash == hashed_three).then(|| slot(3));
+            assert_eq!(entry.key, expected);
+        }
+
+        // A block without transactions only has its post-state
+        assert!(api.storage_range_at(BlockId::from(block_hash), 1, contract, H256::zero(), 3).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blob_base_fee() {
+        let db = Arc::new(MemoryDatabase::new());
+        insert_block(&db, 1, H256::zero());
+        let api = EthApi::new(db.clone());
+
+        // No excess blob gas before Cancun
+        assert!(api.blob_base_fee().await.is_err());
+
+        insert_cancun_block(&db, 2, 0, 0);
+        assert_eq!(api.blob_base_fee().await.unwrap(), U256::one());
+
+        // Excess of one update fraction scales the minimum fee by e, rounded down
+        insert_cancun_block(&db, 3, 0, 3_338_477);
+        assert_eq!(api.blob_base_fee().await.unwrap(), U256::from(2));
+
+        insert_cancun_block(&db, 4, 0, 10 * 3_338_477);
+        assert_eq!(api.blob_base_fee().await.unwrap(), U256::from(22_026));
+
+        // A chain already on Prague prices the excess with EIP-7691's fraction
+        let prague = EthApi::new(db.clone()).with_chain_config(ChainConfig::default());
+        assert_eq!(prague.blob_base_fee().await.unwrap(), U256::from(785));
+    }
+
+    #[tokio::test]
+    async fn test_block_blob_gas_fields() {
+        let db = Arc::new(MemoryDatabase::new());
+        insert_block(&db, 1, H256::zero());
+        let cancun = insert_cancun_block(&db, 2, 262_144, 393_216);
+        let api = EthApi::new(db);
+
+        let block = api.get_block_by_hash(cancun, false).await.unwrap().unwrap();
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(json["blobGasUsed"], serde_json::to_value(U256::from(262_144)).unwrap());
+        assert_eq!(json["excessBlobGas"], serde_json::to_value(U256::from(393_216)).unwrap());
+        assert_eq!(block.number, Some(U256::from(2)));
+
+        let block = api.get_block_by_number(BlockNumber::Number(U256::one()), false).await.unwrap().unwrap();
+        let json = serde_json::to_value(&block).unwrap();
+        assert!(json.get("blobGasUsed").is_none());
+        assert!(json.get("excessBlobGas").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_eip1559_receipt_effective_gas_price() {
+        let db = Arc::new(MemoryDatabase::new());
+        let key = generate_private_key();
+        let sender = public_key_to_address(&key.public_key(&secp256k1::Secp256k1::new()));
+        let gwei = U256::exp10(9);
+
+        let mut tx = Eip1559Transaction {
+            chain_id: 1,
+            nonce: U256::zero(),
+            max_priority_fee_per_gas: gwei * 2,
+            max_fee_per_gas: gwei * 10,
+            gas_limit: U256::from(50_000),
+            to: Some(contract_address()),
+            value: U256::zero(),
+            data: Bytes::new(),
+            access_list: Vec::new(),
+            y_parity: false,
+            r: U256::zero(),
+            s: U256::zero(),
+        };
+        let signature = sign_message(&tx.signing_hash(), &key).unwrap();
+        tx.y_parity = signature.v == 28;
+        tx.r = U256::from_big_endian(signature.r.as_bytes());
+        tx.s = U256::from_big_endian(signature.s.as_bytes());
+        let tx = CoreTransaction::Eip1559(tx);
+
+        // A legacy transaction with two logs comes first in the block
+        let mut first = LegacyTransaction {
+            nonce: U256::from(5),
+            gas_price: gwei * 20,
+            gas_limit: U256::from(21_000),
+            to: Some(contract_address()),
+            value: U256::zero(),
+            data: Bytes::new(),
+            v: 27,
+            r: U256::zero(),
+            s: U256::zero(),
+        };
+        let signature = sign_message(&first.signing_hash(None), &key).unwrap();
+        first.v = signature.v as u64;
+        first.r = U256::from_big_endian(signature.r.as_bytes());
+        first.s = U256::from_big_endian(signature.s.as_bytes());
+        let first = CoreTransaction::Legacy(first);
+
+        let receipts = vec![core_receipt(&first, 21_000, 2), core_receipt(&tx, 42_000, 1)];
+        let block_hash = insert_block_with_receipts(&db, 1, gwei * 7, vec![first.clone(), tx.clone()], receipts);
+        let api = EthApi::new(db);
+
+        let receipt = api.get_transaction_receipt(tx.hash()).await.unwrap().unwrap();
+        // The base fee plus the full tip fits under the max fee
+        assert_eq!(receipt.effective_gas_price, gwei * 9);
+        assert_eq!(receipt.tx_type, U256::from(2));
+        assert_eq!(receipt.from, H160::from_slice(sender.as_bytes()));
+        assert_eq!(receipt.to, Some(H160::from_slice(contract_address().as_bytes())));
+        assert_eq!(receipt.block_hash, block_hash);
+        assert_eq!(receipt.transaction_index, U256::one());
+        assert_eq!(receipt.cumulative_gas_used, U256::from(42_000));
+        assert_eq!(receipt.contract_address, None);
+        assert_eq!(receipt.logs[0].log_index, U256::from(2));
+
+        let json = serde_json::to_value(&receipt).unwrap();
+        assert_eq!(json["type"], "0x2");
+        assert!(json.get("blobGasUsed").is_none());
+
+        // Legacy transactions pay their gas price whatever the base fee
+        let receipt = api.get_transaction_receipt(first.hash()).await.unwrap().unwrap();
+        assert_eq!(receipt.effective_gas_price, gwei * 20);
+        assert_eq!(receipt.tx_type, U256::zero());
+
+        assert!(api.get_transaction_receipt(H256::repeat_byte(0x77)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_contract_creation_receipt_address() {
+        let db = Arc::new(MemoryDatabase::new());
+        let key = generate_private_key();
+        let sender = public_key_to_address(&key.public_key(&secp256k1::Secp256k1::new()));
+
+        let mut tx = LegacyTransaction {
+            nonce: U256::from(3),
+            gas_price: U256::from(1_000_000_000u64),
+            gas_limit: U256::from(100_000),
+            to: None,
+            value: U256::zero(),
+            data: Bytes::from_vec(hex::decode(SLOT_CONTRACT).unwrap()),
+            v: 27,
+            r: U256::zero(),
+            s: U256::zero(),
+        };
+        let signature = sign_message(&tx.signing_hash(None), &key).unwrap();
+        tx.v = signature.v as u64;
+        tx.r = U256::from_big_endian(signature.r.as_bytes());
+        tx.s = U256::from_big_endian(signature.s.as_bytes());
+        let tx = CoreTransaction::Legacy(tx);
+
+        let mut receipt = core_receipt(&tx, 60_000, 0);
+        let deployed = create_address(&sender, 3);
+        receipt.contract_address = Some(deployed);
+        insert_block_with_receipts(&db, 1, U256::from(7), vec![tx.clone()], vec![receipt]);
+        let api = EthApi::new(db);
+
+        let receipt = api.get_transaction_receipt(tx.hash()).await.unwrap().unwrap();
+        assert_eq!(receipt.contract_address, Some(H160::from_slice(deployed.as_bytes())));
+        assert_eq!(receipt.to, None);
+        assert_eq!(receipt.from, H160::from_slice(sender.as_bytes()));
+        assert_ne!(deployed, create_address(&sender, 4));
+    }
+
+    #[tokio::test]
+    async fn test_transaction_by_block_and_index() {
+        let db = Arc::new(MemoryDatabase::new());
+        let key = generate_private_key();
+        let sender = public_key_to_address(&key.public_key(&secp256k1::Secp256k1::new()));
+
+        let transactions: Vec<CoreTransaction> = (0..3u64).map(|nonce| {
+            let mut tx = LegacyTransaction {
+                nonce: U256::from(nonce),
+                gas_price: U256::from(1_000_000_000u64),
+                gas_limit: U256::from(21_000),
+                to: Some(contract_address()),
+                value: U256::from(nonce),
+                data: Bytes::new(),
+                v: 27,
+                r: U256::zero(),
+                s: U256::zero(),
+            };
+            let signature = sign_message(&tx.signing_hash(None), &key).unwrap();
+            tx.v = signature.v as u64;
+            tx.r = U256::from_big_endian(signature.r.as_bytes());
+            tx.s = U256::from_big_endian(signature.s.as_bytes());
+            CoreTransaction::Legacy(tx)
+        }).collect();
+        let receipts = transactions.iter().map(|tx| core_receipt(tx, 21_000, 0)).collect();
+        let block_hash = insert_block_with_receipts(&db, 1, U256::from(7), transactions.clone(), receipts);
+        let api = EthApi::new(db);
+        let number = BlockNumber::Number(U256::one());
+
+        assert_eq!(api.get_block_transaction_count_by_hash(block_hash).await.unwrap(), Some(U256::from(3)));
+        assert_eq!(api.get_block_transaction_count_by_number(number.clone()).await.unwrap(), Some(U256::from(3)));
+        assert_eq!(api.get_block_transaction_count_by_number(BlockNumber::Latest).await.unwrap(), Some(U256::from(3)));
+        assert!(api.get_block_transaction_count_by_hash(H256::repeat_byte(0x77)).await.unwrap().is_none());
+        assert!(api.get_block_transaction_count_by_number(BlockNumber::Number(U256::from(2))).await.unwrap().is_none());
+
+        let third = api.get_transaction_by_block_hash_and_index(block_hash, U256::from(2)).await.unwrap().unwrap();
+        assert_eq!(third.tx.hash, Some(transactions[2].hash()));
+        assert_eq!(third.block_hash, Some(block_hash));
+        assert_eq!(third.block_number, Some(U256::one()));
+        assert_eq!(third.transaction_index, Some(U256::from(2)));
+        assert_eq!(third.tx.from, Some(sender));
+        assert_eq!(third.tx.nonce, U256::from(2));
+        assert_eq!(third.tx.gas_price, Some(U256::from(1_000_000_000u64)));
+
+        // The spec object of the type, with the block position alongside
+        let json = serde_json::to_value(&third).unwrap();
+        assert_eq!(json["type"], "0x0");
+        assert_eq!(json["transactionIndex"], "0x2");
+        assert!(json.get("maxFeePerGas").is_none());
+        assert!(json.get("accessList").is_none());
+
+        let by_number = api.get_transaction_by_block_number_and_index(number.clone(), U256::from(2)).await.unwrap().unwrap();
+        assert_eq!(by_number.tx.hash, third.tx.hash);
+        assert_eq!(api.get_transaction_by_hash(transactions[2].hash()).await.unwrap().unwrap().transaction_index, Some(U256::from(2)));
+
+        assert!(api.get_transaction_by_block_hash_and_index(block_hash, U256::from(3)).await.unwrap().is_none());
+        assert!(api.get_transaction_by_block_number_and_index(number, U256::from(3)).await.unwrap().is_none());
+        assert!(api.get_transaction_by_block_hash_and_index(H256::repeat_byte(0x77), U256::zero()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_tags_follow_shared_store() {
+        let db = Arc::new(MemoryDatabase::new());
+        let hashes: Vec<H256> = (0..3).map(|number| insert_block(&db, number, H256::zero())).collect();
+
+        // Store shared with the consensus engine that records the checkpoints
+        let checkpoints = Arc::new(CheckpointStore::new(db.clone()));
+        let api = EthApi::new(db).with_checkpoint_store(checkpoints.clone());
+        assert!(api.get_block_by_number(BlockNumber::Finalized, false).await.is_err());
+
+        checkpoints.set_finalized(Checkpoint::new(hashes[0], 0)).unwrap();
+        checkpoints.set_safe(Checkpoint::new(hashes[1], 1)).unwrap();
+
+        let finalized = api.get_block_by_number(BlockNumber::Finalized, false).await.unwrap().unwrap();
+        assert_eq!(finalized.hash, Some(hashes[0]));
+        let safe = api.get_block_by_number(BlockNumber::Safe, false).await.unwrap().unwrap();
+        assert_eq!(safe.hash, Some(hashes[1]));
+    }
+}
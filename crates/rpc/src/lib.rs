@@ -1,15 +1,25 @@
+use serde_json::Value;
 use thiserror::Error;
 
 pub mod server;
 pub mod types;
 pub mod methods;
 pub mod eth;
+pub mod call;
 pub mod net;
 pub mod web3;
+pub mod limits;
+pub mod personal;
+pub mod admin;
 
 pub use server::*;
 pub use types::*;
 pub use methods::*;
+pub use limits::{RpcLimits, RateLimiter};
+pub use personal::PersonalApi;
+pub use admin::{AdminApi, NodeInfo};
+pub use ethereum_evm::decode_revert_reason;
+pub use ethereum_state as state;
 
 #[derive(Debug, Error)]
 pub enum RpcError {
@@ -30,6 +40,16 @@ pub enum RpcError {
     
     #[error("Resource not found")]
     ResourceNotFound,
+    
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+    
+    /// Call ended in REVERT, `data` is the raw revert output
+    #[error("{}", revert_message(.reason))]
+    ExecutionReverted {
+        reason: Option<String>,
+        data: Vec<u8>,
+    },
 }
 
 impl RpcError {
@@ -41,8 +61,52 @@ impl RpcError {
             RpcError::InternalError(_) => -32603,
             RpcError::ParseError(_) => -32700,
             RpcError::ResourceNotFound => -32001,
+            RpcError::LimitExceeded(_) => -32005,
+            RpcError::ExecutionReverted { .. } => 3,
+        }
+    }
+    
+    /// Extra `data` member of the JSON-RPC error object
+    pub fn data(&self) -> Option<Value> {
+        match self {
+            RpcError::ExecutionReverted { data, .. } => {
+                Some(Value::String(format!("0x{}", hex::encode(data))))
+            }
+            _ => None,
         }
     }
+    
+    /// Revert with `data` as output, decoding an `Error(string)` reason if present
+    pub fn reverted(data: Vec<u8>) -> Self {
+        RpcError::ExecutionReverted {
+            reason: decode_revert_reason(&data),
+            data,
+        }
+    }
+    
+    pub fn to_response(&self) -> RpcErrorResponse {
+        RpcErrorResponse {
+            code: self.code(),
+            message: self.to_string(),
+            data: self.data(),
+        }
+    }
+}
+
+impl From<ethereum_state::StateError> for RpcError {
+    fn from(e: ethereum_state::StateError) -> Self {
+        match e {
+            ethereum_state::StateError::InvalidInput(message) => RpcError::InvalidParams(message),
+            ethereum_state::StateError::Unavailable(message) => RpcError::InternalError(message),
+        }
+    }
+}
+
+fn revert_message(reason: &Option<String>) -> String {
+    match reason {
+        Some(reason) => format!("execution reverted: {}", reason),
+        None => "execution reverted".to_string(),
+    }
 }
 
 pub type Result<T> = std::result::Result<T, RpcError>;
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::{RpcError, Result};
+
+/// Cost of `eth_getLogs`, which may scan many blocks
+pub const GET_LOGS_COST: u32 = 10;
+
+/// Cost of `debug_trace*`, which re-executes transactions
+pub const TRACE_COST: u32 = 20;
+
+/// Limits enforced on every client of the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RpcLimits {
+    /// Request budget per second and client, 0 disables rate limiting
+    pub requests_per_second: u32,
+    /// Most requests accepted in one batch
+    pub max_batch_size: usize,
+    /// Largest serialized response returned for one request
+    pub max_response_bytes: usize,
+}
+
+impl Default for RpcLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 100,
+            max_batch_size: 100,
+            max_response_bytes: 5 * 1024 * 1024,
+        }
+    }
+}
+
+impl RpcLimits {
+    /// No rate limit and no batch or response size limit
+    pub fn unlimited() -> Self {
+        Self {
+            requests_per_second: 0,
+            max_batch_size: usize::MAX,
+            max_response_bytes: usize::MAX,
+        }
+    }
+}
+
+/// Budget units `method` takes from the rate limit
+pub fn method_cost(method: &str) -> u32 {
+    if method == "eth_getLogs" {
+        GET_LOGS_COST
+    } else if method.starts_with("debug_trace") {
+        TRACE_COST
+    } else {
+        1
+    }
+}
+
+/// Per-client token bucket holding one second worth of requests
+pub struct RateLimiter {
+    requests_per_second: u32,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u32) -> Self {
+        Self {
+            requests_per_second,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take the cost of `method` from the budget of `client`
+    pub fn check(&self, client: IpAddr, method: &str) -> Result<()> {
+        self.check_at(client, method, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, method: &str, now: Instant) -> Result<()> {
+        if self.requests_per_second == 0 {
+            return Ok(());
+        }
+
+        let capacity = self.requests_per_second as f64;
+        // A method costing more than the whole budget can still run on a full bucket
+        let cost = (method_cost(method) as f64).min(capacity);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(client).or_insert(Bucket { tokens: capacity, refilled: now });
+
+        let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * capacity).min(capacity);
+        bucket.refilled = now;
+
+        if bucket.tokens < cost {
+            return Err(RpcError::LimitExceeded(format!(
+                "more than {} requests per second",
+                self.requests_per_second
+            )));
+        }
+        bucket.tokens -= cost;
+        Ok(())
+    }
+
+    /// Forget clients whose bucket has fully refilled
+    pub fn prune(&self) {
+        let now = Instant::now();
+        self.buckets.lock().unwrap().retain(|_, bucket| {
+            now.saturating_duration_since(bucket.refilled).as_secs_f64() < 1.0
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rate_limit_refills() {
+        let limiter = RateLimiter::new(10);
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        for _ in 0..10 {
+            limiter.check_at(client, "eth_blockNumber", start).unwrap();
+        }
+        assert!(matches!(
+            limiter.check_at(client, "eth_blockNumber", start),
+            Err(RpcError::LimitExceeded(_))
+        ));
+        // Budgets are per client
+        limiter.check_at(other, "eth_blockNumber", start).unwrap();
+
+        // A tenth of a second buys one more request
+        let later = start + Duration::from_millis(100);
+        limiter.check_at(client, "eth_blockNumber", later).unwrap();
+        assert!(limiter.check_at(client, "eth_blockNumber", later).is_err());
+    }
+
+    #[test]
+    fn test_expensive_methods_cost_more() {
+        let limiter = RateLimiter::new(20);
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        let start = Instant::now();
+
+        limiter.check_at(client, "eth_getLogs", start).unwrap();
+        limiter.check_at(client, "eth_getLogs", start).unwrap();
+        assert!(limiter.check_at(client, "eth_chainId", start).is_err());
+
+        // Costs are capped at the budget
+        let later = start + Duration::from_secs(1);
+        assert_eq!(method_cost("debug_traceTransaction"), TRACE_COST);
+        limiter.check_at(client, "debug_traceTransaction", later).unwrap();
+        assert!(RateLimiter::new(0).check_at(client, "debug_traceCall", start).is_ok());
+    }
+}
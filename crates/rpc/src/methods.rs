@@ -1,18 +1,24 @@
 use std::sync::Arc;
 use serde_json::Value;
 use ethereum_storage::Database;
+use ethereum_consensus::CheckpointStore;
 use ethereum_core::Block;
 use ethereum_types::{H256, U256};
+use ethereum_txpool::TransactionPool;
 
-use crate::{RpcRequest, RpcError, Result};
+use crate::{BlockId, RpcRequest, RpcError, Result};
+use crate::admin::AdminApi;
 use crate::eth::EthApi;
 use crate::net::NetApi;
+use crate::personal::PersonalApi;
 use crate::web3::Web3Api;
 
 pub struct RpcHandler {
     eth_api: Arc<EthApi>,
     net_api: Arc<NetApi>,
     web3_api: Arc<Web3Api>,
+    personal_api: Option<Arc<PersonalApi>>,
+    admin_api: Option<Arc<AdminApi>>,
 }
 
 impl RpcHandler {
@@ -21,7 +27,7 @@ impl RpcHandler {
         chain_id: u64,
         client_version: String,
     ) -> Self {
-        let eth_api = Arc::new(EthApi::new(db.clone()));
+        let eth_api = Arc::new(EthApi::new(db.clone()).with_chain_id(chain_id));
         let net_api = Arc::new(NetApi::new(chain_id));
         let web3_api = Arc::new(Web3Api::new(client_version));
         
@@ -29,9 +35,35 @@ impl RpcHandler {
             eth_api,
             net_api,
             web3_api,
+            personal_api: None,
+            admin_api: None,
         }
     }
     
+    /// Pool whose pending transactions the "pending" block tag runs on
+    pub fn with_txpool(mut self, txpool: Arc<TransactionPool>) -> Self {
+        self.eth_api = Arc::new(self.eth_api.as_ref().clone().with_txpool(txpool));
+        self
+    }
+    
+    /// Checkpoints the "finalized" and "safe" block tags resolve to
+    pub fn with_checkpoint_store(mut self, checkpoints: Arc<CheckpointStore>) -> Self {
+        self.eth_api = Arc::new(self.eth_api.as_ref().clone().with_checkpoint_store(checkpoints));
+        self
+    }
+    
+    /// Serve the `personal_` namespace, which is unknown until enabled
+    pub fn with_personal(mut self, personal_api: PersonalApi) -> Self {
+        self.personal_api = Some(Arc::new(personal_api));
+        self
+    }
+    
+    /// Serve the `admin_` namespace, which is unknown until enabled
+    pub fn with_admin(mut self, admin_api: AdminApi) -> Self {
+        self.admin_api = Some(Arc::new(admin_api));
+        self
+    }
+    
     pub async fn handle_request(&self, request: RpcRequest) -> Result<Value> {
         let method_parts: Vec<&str> = request.method.split('_').collect();
         
@@ -47,12 +79,25 @@ impl RpcHandler {
             "eth" => self.handle_eth_method(&method, params).await,
             "net" => self.handle_net_method(&method, params).await,
             "web3" => self.handle_web3_method(&method, params).await,
+            "debug" => self.handle_debug_method(&method, params).await,
+            "personal" => match &self.personal_api {
+                Some(personal_api) => Self::handle_personal_method(personal_api, &method, params).await,
+                None => Err(RpcError::MethodNotFound(request.method)),
+            },
+            "admin" => match &self.admin_api {
+                Some(admin_api) => Self::handle_admin_method(admin_api, &method, params).await,
+                None => Err(RpcError::MethodNotFound(request.method)),
+            },
             _ => Err(RpcError::MethodNotFound(request.method)),
         }
     }
     
     async fn handle_eth_method(&self, method: &str, params: Value) -> Result<Value> {
         match method {
+            "chainId" => {
+                let chain_id = self.eth_api.chain_id().await?;
+                Ok(Value::String(format!("{:#x}", chain_id)))
+            }
             "blockNumber" => {
                 let block_number = self.eth_api.block_number().await?;
                 Ok(serde_json::to_value(block_number)
@@ -102,6 +147,52 @@ impl RpcHandler {
                 Ok(serde_json::to_value(count)
                     .map_err(|e| RpcError::InternalError(e.to_string()))?)
             }
+            "getStorageAt" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.len() < 2 {
+                    return Err(RpcError::InvalidParams("Missing parameters".to_string()));
+                }
+                
+                let address = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let slot = serde_json::from_value(params[1].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let block_number = if params.len() > 2 {
+                    Some(serde_json::from_value(params[2].clone())
+                        .map_err(|e| RpcError::InvalidParams(e.to_string()))?)
+                } else {
+                    None
+                };
+                
+                let value = self.eth_api.get_storage_at(address, slot, block_number).await?;
+                Ok(serde_json::to_value(value)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "getCode" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.is_empty() {
+                    return Err(RpcError::InvalidParams("Missing address parameter".to_string()));
+                }
+                
+                let address = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let block_number = if params.len() > 1 {
+                    Some(serde_json::from_value(params[1].clone())
+                        .map_err(|e| RpcError::InvalidParams(e.to_string()))?)
+                } else {
+                    None
+                };
+                
+                let code = self.eth_api.get_code(address, block_number).await?;
+                Ok(serde_json::to_value(code)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
             "getBlockByHash" => {
                 let params: Vec<Value> = serde_json::from_value(params)
                     .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
@@ -151,6 +242,70 @@ impl RpcHandler {
                 Ok(serde_json::to_value(tx)
                     .map_err(|e| RpcError::InternalError(e.to_string()))?)
             }
+            "getBlockTransactionCountByHash" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.is_empty() {
+                    return Err(RpcError::InvalidParams("Missing hash parameter".to_string()));
+                }
+                
+                let hash = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let count = self.eth_api.get_block_transaction_count_by_hash(hash).await?;
+                Ok(serde_json::to_value(count)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "getBlockTransactionCountByNumber" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.is_empty() {
+                    return Err(RpcError::InvalidParams("Missing block number parameter".to_string()));
+                }
+                
+                let number = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let count = self.eth_api.get_block_transaction_count_by_number(number).await?;
+                Ok(serde_json::to_value(count)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "getTransactionByBlockHashAndIndex" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.len() < 2 {
+                    return Err(RpcError::InvalidParams("Missing parameters".to_string()));
+                }
+                
+                let hash = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let index = serde_json::from_value(params[1].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let tx = self.eth_api.get_transaction_by_block_hash_and_index(hash, index).await?;
+                Ok(serde_json::to_value(tx)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "getTransactionByBlockNumberAndIndex" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.len() < 2 {
+                    return Err(RpcError::InvalidParams("Missing parameters".to_string()));
+                }
+                
+                let number = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let index = serde_json::from_value(params[1].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let tx = self.eth_api.get_transaction_by_block_number_and_index(number, index).await?;
+                Ok(serde_json::to_value(tx)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
             "getTransactionReceipt" => {
                 let params: Vec<Value> = serde_json::from_value(params)
                     .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
@@ -188,6 +343,28 @@ impl RpcHandler {
                 Ok(serde_json::to_value(result)
                     .map_err(|e| RpcError::InternalError(e.to_string()))?)
             }
+            "simulateV1" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.is_empty() {
+                    return Err(RpcError::InvalidParams("Missing simulation payload".to_string()));
+                }
+                
+                let payload = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let block_number = if params.len() > 1 {
+                    Some(serde_json::from_value(params[1].clone())
+                        .map_err(|e| RpcError::InvalidParams(e.to_string()))?)
+                } else {
+                    None
+                };
+                
+                let blocks = self.eth_api.simulate_v1(payload, block_number).await?;
+                Ok(serde_json::to_value(blocks)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
             "estimateGas" => {
                 let params: Vec<Value> = serde_json::from_value(params)
                     .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
@@ -208,9 +385,9 @@ impl RpcHandler {
                 Ok(serde_json::to_value(price)
                     .map_err(|e| RpcError::InternalError(e.to_string()))?)
             }
-            "chainId" => {
-                let chain_id = self.eth_api.chain_id().await?;
-                Ok(serde_json::to_value(chain_id)
+            "blobBaseFee" => {
+                let fee = self.eth_api.blob_base_fee().await?;
+                Ok(serde_json::to_value(fee)
                     .map_err(|e| RpcError::InternalError(e.to_string()))?)
             }
             "syncing" => {
@@ -233,6 +410,31 @@ impl RpcHandler {
                 Ok(serde_json::to_value(accounts)
                     .map_err(|e| RpcError::InternalError(e.to_string()))?)
             }
+            "maxPriorityFeePerGas" => {
+                let tip = self.eth_api.max_priority_fee_per_gas().await?;
+                Ok(serde_json::to_value(tip)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "sendTransaction" => {
+                // Node-managed accounts live in the keystore behind `personal_`
+                let personal_api = self.personal_api.as_ref()
+                    .ok_or_else(|| RpcError::InvalidParams("no accounts managed by this node".to_string()))?;
+                
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.is_empty() {
+                    return Err(RpcError::InvalidParams("Missing transaction request".to_string()));
+                }
+                
+                let request = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let request = self.eth_api.fill_transaction(request, personal_api.txpool()).await?;
+                let hash = personal_api.send_transaction(request, None).await?;
+                Ok(serde_json::to_value(hash)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
             "sendRawTransaction" => {
                 let params: Vec<Value> = serde_json::from_value(params)
                     .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
@@ -298,4 +500,513 @@ impl RpcHandler {
             _ => Err(RpcError::MethodNotFound(format!("web3_{}", method))),
         }
     }
-}
\ No newline at end of file
+    
+    async fn handle_debug_method(&self, method: &str, params: Value) -> Result<Value> {
+        match method {
+            "storageRangeAt" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.len() < 5 {
+                    return Err(RpcError::InvalidParams(
+                        "Expected block, transaction index, address, start key and max results".to_string()
+                    ));
+                }
+                
+                // A bare block hash, as go-ethereum takes, or any block selector
+                let block = match serde_json::from_value::<H256>(params[0].clone()) {
+                    Ok(hash) => BlockId::from(hash),
+                    Err(_) => serde_json::from_value(params[0].clone())
+                        .map_err(|e| RpcError::InvalidParams(e.to_string()))?,
+                };
+                let tx_index: u64 = serde_json::from_value(params[1].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let address = serde_json::from_value(params[2].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let start_key: String = serde_json::from_value(params[3].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let max_result: u64 = serde_json::from_value(params[4].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                // Short keys are left-padded, as go-ethereum does
+                let start_key = hex::decode(start_key.trim_start_matches("0x"))
+                    .map_err(|e| RpcError::InvalidParams(format!("invalid start key: {}", e)))?;
+                if start_key.len() > 32 {
+                    return Err(RpcError::InvalidParams("start key longer than 32 bytes".to_string()));
+                }
+                let mut key = [0u8; 32];
+                key[32 - start_key.len()..].copy_from_slice(&start_key);
+                
+                let range = self.eth_api.storage_range_at(
+                    block,
+                    tx_index as usize,
+                    address,
+                    H256::from(key),
+                    max_result as usize,
+                ).await?;
+                Ok(serde_json::to_value(range)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "getRawHeader" | "getRawBlock" | "getRawReceipts" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.is_empty() {
+                    return Err(RpcError::InvalidParams("Missing block parameter".to_string()));
+                }
+                
+                let block = match serde_json::from_value::<H256>(params[0].clone()) {
+                    Ok(hash) => BlockId::from(hash),
+                    Err(_) => serde_json::from_value(params[0].clone())
+                        .map_err(|e| RpcError::InvalidParams(e.to_string()))?,
+                };
+                
+                let raw = match method {
+                    "getRawHeader" => serde_json::to_value(self.eth_api.raw_header(block).await?),
+                    "getRawBlock" => serde_json::to_value(self.eth_api.raw_block(block).await?),
+                    _ => serde_json::to_value(self.eth_api.raw_receipts(block).await?),
+                };
+                Ok(raw.map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "getBadBlocks" => {
+                let bad_blocks = self.eth_api.bad_blocks().await?;
+                Ok(serde_json::to_value(bad_blocks)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            _ => Err(RpcError::MethodNotFound(format!("debug_{}", method))),
+        }
+    }
+    
+    async fn handle_personal_method(personal_api: &PersonalApi, method: &str, params: Value) -> Result<Value> {
+        match method {
+            "newAccount" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.is_empty() {
+                    return Err(RpcError::InvalidParams("Missing password parameter".to_string()));
+                }
+                
+                let password = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let address = personal_api.new_account(password).await?;
+                Ok(serde_json::to_value(address)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "listAccounts" => {
+                let accounts = personal_api.list_accounts().await?;
+                Ok(serde_json::to_value(accounts)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "unlockAccount" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.len() < 2 {
+                    return Err(RpcError::InvalidParams("Missing parameters".to_string()));
+                }
+                
+                let address = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let password = serde_json::from_value(params[1].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let duration = match params.get(2) {
+                    Some(duration) => serde_json::from_value(duration.clone())
+                        .map_err(|e| RpcError::InvalidParams(e.to_string()))?,
+                    None => None,
+                };
+                
+                let unlocked = personal_api.unlock_account(address, password, duration).await?;
+                Ok(Value::Bool(unlocked))
+            }
+            "sign" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.len() < 2 {
+                    return Err(RpcError::InvalidParams("Missing parameters".to_string()));
+                }
+                
+                let data = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let address = serde_json::from_value(params[1].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let password = match params.get(2) {
+                    Some(password) => serde_json::from_value(password.clone())
+                        .map_err(|e| RpcError::InvalidParams(e.to_string()))?,
+                    None => None,
+                };
+                
+                let signature = personal_api.sign(data, address, password).await?;
+                Ok(Value::String(signature))
+            }
+            "sendTransaction" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.is_empty() {
+                    return Err(RpcError::InvalidParams("Missing transaction request".to_string()));
+                }
+                
+                let request = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                let password = match params.get(1) {
+                    Some(password) => serde_json::from_value(password.clone())
+                        .map_err(|e| RpcError::InvalidParams(e.to_string()))?,
+                    None => None,
+                };
+                
+                let hash = personal_api.send_transaction(request, password).await?;
+                Ok(serde_json::to_value(hash)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            _ => Err(RpcError::MethodNotFound(format!("personal_{}", method))),
+        }
+    }
+    
+    async fn handle_admin_method(admin_api: &AdminApi, method: &str, params: Value) -> Result<Value> {
+        match method {
+            "nodeInfo" => {
+                let info = admin_api.node_info().await?;
+                Ok(serde_json::to_value(info)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "peers" => {
+                let peers = admin_api.peers().await?;
+                Ok(serde_json::to_value(peers)
+                    .map_err(|e| RpcError::InternalError(e.to_string()))?)
+            }
+            "addPeer" | "removePeer" => {
+                let params: Vec<Value> = serde_json::from_value(params)
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                if params.is_empty() {
+                    return Err(RpcError::InvalidParams("Missing enode parameter".to_string()));
+                }
+                
+                let url = serde_json::from_value(params[0].clone())
+                    .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+                
+                let done = if method == "addPeer" {
+                    admin_api.add_peer(url).await?
+                } else {
+                    admin_api.remove_peer(url).await?
+                };
+                Ok(Value::Bool(done))
+            }
+            _ => Err(RpcError::MethodNotFound(format!("admin_{}", method))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_storage::MemoryDatabase;
+    
+    fn handler(chain_id: u64) -> RpcHandler {
+        RpcHandler::new(Arc::new(MemoryDatabase::new()), chain_id, "test/v0.1.0".to_string())
+    }
+    
+    fn request(method: &str, params: Value) -> RpcRequest {
+        RpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params: Some(params),
+            id: Some(Value::from(1)),
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_web3_sha3() {
+        let handler = handler(1);
+        
+        let hash = handler.handle_request(request("web3_sha3", serde_json::json!(["0x"]))).await.unwrap();
+        assert_eq!(hash, "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+        
+        let hash = handler.handle_request(request("web3_sha3", serde_json::json!(["0x68656c6c6f20776f726c64"]))).await.unwrap();
+        assert_eq!(hash, "0x47173285a8d7341e5e972fc677286384f802f8ef42a5ec5f03bbfa254cb01fad");
+        
+        for malformed in ["0x123", "0xzz", "68656c6c6f", "0x0x00"] {
+            let result = handler.handle_request(request("web3_sha3", serde_json::json!([malformed]))).await;
+            assert!(matches!(result, Err(RpcError::InvalidParams(_))), "{}", malformed);
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_eth_chain_id_is_minimal_hex() {
+        let chain_id = handler(11155111).handle_request(request("eth_chainId", serde_json::json!([]))).await.unwrap();
+        assert_eq!(chain_id, "0xaa36a7");
+        
+        let chain_id = handler(1).handle_request(request("eth_chainId", serde_json::json!([]))).await.unwrap();
+        assert_eq!(chain_id, "0x1");
+    }
+    
+    #[tokio::test]
+    async fn test_debug_raw_block_data() {
+        use ethereum_core::{Header, Receipt};
+        
+        let db = Arc::new(MemoryDatabase::new());
+        let mut header = Header::new();
+        header.number = U256::from(5);
+        header.gas_limit = U256::from(30_000_000);
+        let block = Block::new(header.clone());
+        let hash = block.hash();
+        let receipt = Receipt {
+            tx_type: 2,
+            status: 1,
+            cumulative_gas_used: U256::from(21_000),
+            logs_bloom: Default::default(),
+            logs: Vec::new(),
+            gas_used: U256::from(21_000),
+            contract_address: None,
+        };
+        db.put(format!("block:{}", hex::encode(hash)).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+        db.put(format!("receipts:{}", hex::encode(hash)).as_bytes(), &bincode::serialize(&vec![receipt.clone()]).unwrap()).unwrap();
+        db.put(b"number:5", hash.as_bytes()).unwrap();
+        db.put(b"canonical:head", hash.as_bytes()).unwrap();
+        let handler = RpcHandler::new(db, 1, "test/v0.1.0".to_string());
+        let hex_of = |bytes: Vec<u8>| Value::from(format!("0x{}", hex::encode(bytes)));
+        
+        for selector in [serde_json::json!("latest"), serde_json::json!(hash), serde_json::json!({ "blockHash": hash })] {
+            let raw = handler.handle_request(request("debug_getRawHeader", serde_json::json!([selector]))).await.unwrap();
+            assert_eq!(raw, hex_of(ethereum_rlp::encode(&header).to_vec()));
+            
+            let raw = handler.handle_request(request("debug_getRawBlock", serde_json::json!([selector]))).await.unwrap();
+            assert_eq!(raw, hex_of(ethereum_rlp::encode(&block).to_vec()));
+            
+            let raw = handler.handle_request(request("debug_getRawReceipts", serde_json::json!([selector]))).await.unwrap();
+            assert_eq!(raw, Value::from(vec![hex_of(receipt.encoded_2718())]));
+        }
+        
+        let missing = handler.handle_request(request("debug_getRawHeader", serde_json::json!([H256::repeat_byte(0x66)]))).await;
+        assert!(matches!(missing, Err(RpcError::ResourceNotFound)));
+    }
+    
+    #[tokio::test]
+    async fn test_personal_disabled_by_default() {
+        let result = handler(1).handle_request(request("personal_listAccounts", serde_json::json!([]))).await;
+        assert!(matches!(result, Err(RpcError::MethodNotFound(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_personal_unlock_and_send_transaction() {
+        use crate::personal::PersonalApi;
+        use ethereum_account::AccountManager;
+        use ethereum_txpool::{MemoryNonceProvider, TransactionPool, TxPoolConfig};
+        use ethereum_types::{Address, H160};
+        
+        let keystore = tempfile::tempdir().unwrap();
+        let accounts = Arc::new(tokio::sync::RwLock::new(AccountManager::new(keystore.path()).unwrap()));
+        let pool = Arc::new(TransactionPool::new(TxPoolConfig::default(), Arc::new(MemoryNonceProvider::new())));
+        let handler = handler(1).with_personal(PersonalApi::new(accounts, pool.clone(), 1));
+        
+        let address = handler.handle_request(request("personal_newAccount", serde_json::json!(["secret"]))).await.unwrap();
+        let listed = handler.handle_request(request("personal_listAccounts", serde_json::json!([]))).await.unwrap();
+        assert_eq!(listed, serde_json::json!([address]));
+        
+        let tx = serde_json::json!({
+            "from": address,
+            "to": "0x4242424242424242424242424242424242424242",
+            "gas": "0x5208",
+            "maxFeePerGas": "0x77359400",
+            "maxPriorityFeePerGas": "0x3b9aca00",
+            "value": "0x1",
+            "nonce": "0x0",
+        });
+        
+        // New accounts start out locked and a wrong password unlocks nothing
+        let locked = handler.handle_request(request("personal_sendTransaction", serde_json::json!([tx]))).await;
+        assert!(matches!(locked, Err(RpcError::InvalidParams(_))));
+        let wrong = handler.handle_request(request("personal_unlockAccount", serde_json::json!([address, "guess", 60]))).await;
+        assert!(wrong.is_err());
+        
+        let unlocked = handler.handle_request(request("personal_unlockAccount", serde_json::json!([address, "secret", 60]))).await.unwrap();
+        assert_eq!(unlocked, Value::Bool(true));
+        
+        let hash = handler.handle_request(request("personal_sendTransaction", serde_json::json!([tx]))).await.unwrap();
+        let pooled = pool.get_transaction(&serde_json::from_value(hash).unwrap()).unwrap();
+        let sender: H160 = serde_json::from_value(address.clone()).unwrap();
+        assert_eq!(pooled.from, Address::from(sender));
+        assert_eq!(pooled.tx.nonce(), U256::zero());
+        
+        // personal_sign follows EIP-191
+        let signature = handler.handle_request(request("personal_sign", serde_json::json!(["0x68656c6c6f", address]))).await.unwrap();
+        let signature = hex::decode(signature.as_str().unwrap().trim_start_matches("0x")).unwrap();
+        let message = ethereum_crypto::keccak256(b"\x19Ethereum Signed Message:\n5hello");
+        let signature = ethereum_crypto::Signature::from_bytes(&signature).unwrap();
+        assert_eq!(ethereum_crypto::recover_address(&message, &signature).unwrap(), Address::from(sender));
+    }
+    
+    fn admin_handler() -> (RpcHandler, Arc<ethereum_network::PeerManager>, String) {
+        use crate::admin::{AdminApi, NodeInfo};
+        use ethereum_network::discovery::NodeId;
+        use secp256k1::{PublicKey, Secp256k1, SecretKey};
+        
+        let key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let local = NodeId::new(PublicKey::from_secret_key(&Secp256k1::new(), &key), "127.0.0.1:30301".parse().unwrap());
+        let peer_manager = Arc::new(ethereum_network::PeerManager::new(25));
+        let info = NodeInfo::new("test/v0.1.0".to_string(), &local, 30303);
+        let handler = handler(1).with_admin(AdminApi::new(info, peer_manager.clone()));
+        (handler, peer_manager, hex::encode(local.id.as_bytes()))
+    }
+    
+    #[tokio::test]
+    async fn test_admin_node_info() {
+        let result = handler(1).handle_request(request("admin_nodeInfo", serde_json::json!([]))).await;
+        assert!(matches!(result, Err(RpcError::MethodNotFound(_))));
+        
+        let (handler, _, id) = admin_handler();
+        let info = handler.handle_request(request("admin_nodeInfo", serde_json::json!([]))).await.unwrap();
+        assert_eq!(info["id"], id.as_str());
+        assert_eq!(info["enode"], format!("enode://{}@127.0.0.1:30303?discport=30301", id).as_str());
+        assert_eq!(info["ports"]["listener"], 30303);
+        assert_eq!(info["protocols"]["eth"]["version"], 68);
+    }
+    
+    #[tokio::test]
+    async fn test_admin_add_peer_queues_dial() {
+        use secp256k1::{PublicKey, Secp256k1, SecretKey};
+        
+        let (handler, peer_manager, _) = admin_handler();
+        let key = SecretKey::from_slice(&[9u8; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &key);
+        let id = hex::encode(&public_key.serialize_uncompressed()[1..]);
+        let enode = format!("enode://{}@10.0.0.1:30303?discport=30301", id);
+        
+        let added = handler.handle_request(request("admin_addPeer", serde_json::json!([enode]))).await.unwrap();
+        assert_eq!(added, Value::Bool(true));
+        
+        // The dial goes to the RLPx port, not the discovery port
+        let dials = peer_manager.pending_dials().await;
+        assert_eq!(dials.len(), 1);
+        assert_eq!(hex::encode(dials[0].id.as_bytes()), id);
+        assert_eq!(dials[0].address, "10.0.0.1:30303".parse().unwrap());
+        
+        // Adding it again does not queue a second dial
+        handler.handle_request(request("admin_addPeer", serde_json::json!([enode]))).await.unwrap();
+        assert_eq!(peer_manager.pending_dials().await.len(), 1);
+        
+        let removed = handler.handle_request(request("admin_removePeer", serde_json::json!([enode]))).await.unwrap();
+        assert_eq!(removed, Value::Bool(true));
+        assert!(peer_manager.pending_dials().await.is_empty());
+        
+        let invalid = handler.handle_request(request("admin_addPeer", serde_json::json!(["enode://abcd@10.0.0.1:30303"]))).await;
+        assert!(matches!(invalid, Err(RpcError::InvalidParams(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_admin_add_peer_is_dialed() {
+        use secp256k1::{PublicKey, Secp256k1, SecretKey};
+        
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (handler, peer_manager, _) = admin_handler();
+        let key = SecretKey::from_slice(&[9u8; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &key);
+        let enode = format!(
+            "enode://{}@{}",
+            hex::encode(&public_key.serialize_uncompressed()[1..]),
+            listener.local_addr().unwrap(),
+        );
+        
+        let dialer = peer_manager.clone();
+        let node_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        tokio::spawn(async move { dialer.run_dials(node_key).await });
+        
+        handler.handle_request(request("admin_addPeer", serde_json::json!([enode]))).await.unwrap();
+        let accepted = tokio::time::timeout(std::time::Duration::from_secs(5), listener.accept()).await;
+        assert!(accepted.unwrap().is_ok());
+        assert!(peer_manager.pending_dials().await.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_eth_send_transaction_fills_missing_fields() {
+        use crate::personal::PersonalApi;
+        use crate::state::StateAccount;
+        use ethereum_account::AccountManager;
+        use ethereum_core::Header;
+        use ethereum_storage::Database;
+        use ethereum_trie::PatriciaTrie;
+        use ethereum_txpool::{MemoryNonceProvider, TransactionPool, TxPoolConfig};
+        use ethereum_types::{Address, H160};
+        
+        let db = Arc::new(MemoryDatabase::new());
+        let keystore = tempfile::tempdir().unwrap();
+        let accounts = Arc::new(tokio::sync::RwLock::new(AccountManager::new(keystore.path()).unwrap()));
+        let nonces = Arc::new(MemoryNonceProvider::new());
+        let pool = Arc::new(TransactionPool::new(TxPoolConfig::default(), nonces.clone()));
+        let handler = RpcHandler::new(db.clone(), 1, "test/v0.1.0".to_string())
+            .with_txpool(pool.clone())
+            .with_personal(PersonalApi::new(accounts, pool.clone(), 1));
+        
+        let address = handler.handle_request(request("personal_newAccount", serde_json::json!(["secret"]))).await.unwrap();
+        let sender: H160 = serde_json::from_value(address.clone()).unwrap();
+        nonces.set_nonce(Address::from(sender), U256::from(3));
+        
+        // Head whose state has the sender funded at nonce 3
+        let mut trie = PatriciaTrie::new(db.clone());
+        let funded = StateAccount { nonce: 3, balance: U256::exp10(18), ..Default::default() };
+        trie.insert(ethereum_crypto::keccak256(sender.as_bytes()).as_bytes(), funded.encode()).unwrap();
+        let mut header = Header::new();
+        header.number = U256::one();
+        header.state_root = trie.commit().unwrap();
+        header.gas_limit = U256::from(30_000_000);
+        header.base_fee_per_gas = Some(U256::from(1_000_000_000u64));
+        let hash = header.hash();
+        let block = Block { header, transactions: Vec::new(), ommers: Vec::new(), withdrawals: None };
+        db.put(format!("block:{}", hex::encode(hash.as_bytes())).as_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+        db.put(b"number:1", hash.as_bytes()).unwrap();
+        db.put(b"canonical:head", hash.as_bytes()).unwrap();
+        
+        let tx = serde_json::json!({
+            "from": address,
+            "to": "0x4242424242424242424242424242424242424242",
+            "value": "0x1",
+        });
+        
+        let locked = handler.handle_request(request("eth_sendTransaction", serde_json::json!([tx]))).await;
+        assert!(matches!(locked, Err(RpcError::InvalidParams(_))));
+        
+        handler.handle_request(request("personal_unlockAccount", serde_json::json!([address, "secret", 60]))).await.unwrap();
+        let hash = handler.handle_request(request("eth_sendTransaction", serde_json::json!([tx]))).await.unwrap();
+        let pooled = pool.get_transaction(&serde_json::from_value(hash).unwrap()).unwrap();
+        assert_eq!(pooled.from, Address::from(sender));
+        assert_eq!(pooled.tx.nonce(), U256::from(3));
+        assert_eq!(pooled.tx.gas_limit(), U256::from(21_000));
+        assert_eq!(pooled.tx.max_priority_fee_per_gas(), U256::from(1_000_000_000u64));
+        
+        // The next one follows the transaction already in the pool
+        let hash = handler.handle_request(request("eth_sendTransaction", serde_json::json!([tx]))).await.unwrap();
+        let pooled = pool.get_transaction(&serde_json::from_value(hash).unwrap()).unwrap();
+        assert_eq!(pooled.tx.nonce(), U256::from(4));
+        
+        // A queued transaction past a gap leaves the gap to be filled
+        let mut gapped = tx.clone();
+        gapped["nonce"] = serde_json::json!("0x7");
+        handler.handle_request(request("eth_sendTransaction", serde_json::json!([gapped]))).await.unwrap();
+        let hash = handler.handle_request(request("eth_sendTransaction", serde_json::json!([tx]))).await.unwrap();
+        let pooled = pool.get_transaction(&serde_json::from_value(hash).unwrap()).unwrap();
+        assert_eq!(pooled.tx.nonce(), U256::from(5));
+        
+        // Calldata is paid for on top of the base cost, 4 per zero byte and 16 otherwise
+        let mut with_data = tx.clone();
+        with_data["data"] = serde_json::json!("0x00ff00ff");
+        let hash = handler.handle_request(request("eth_sendTransaction", serde_json::json!([with_data]))).await.unwrap();
+        let pooled = pool.get_transaction(&serde_json::from_value(hash).unwrap()).unwrap();
+        assert_eq!(pooled.tx.gas_limit(), U256::from(21_000 + 2 * 4 + 2 * 16));
+        assert_eq!(pooled.tx.data().as_slice(), &[0x00, 0xff, 0x00, 0xff]);
+        
+        // "pending" runs the pool's transactions, the one past the gap included now it's filled
+        let count = |tag: &str| request("eth_getTransactionCount", serde_json::json!([address, tag]));
+        assert_eq!(handler.handle_request(count("latest")).await.unwrap(), serde_json::json!("0x3"));
+        assert_eq!(handler.handle_request(count("pending")).await.unwrap(), serde_json::json!("0x8"));
+    }
+    
+    #[tokio::test]
+    async fn test_eth_send_transaction_without_accounts() {
+        let tx = serde_json::json!({"from": "0x4242424242424242424242424242424242424242"});
+        let result = handler(1).handle_request(request("eth_sendTransaction", serde_json::json!([tx]))).await;
+        assert!(matches!(result, Err(RpcError::InvalidParams(_))));
+    }
+}
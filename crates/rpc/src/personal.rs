@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use ethereum_account::{Account, AccountError, AccountManager, LocalSigner, Signer, TxBuildRequest};
+use ethereum_types::{Address, Bytes, H160, H256, U256};
+use ethereum_txpool::TransactionPool;
+use tokio::sync::RwLock;
+
+use crate::{Result, RpcError};
+use crate::types::TransactionRequest;
+
+/// How long `personal_unlockAccount` unlocks an account when no duration is given
+pub const DEFAULT_UNLOCK_DURATION: Duration = Duration::from_secs(300);
+
+/// `personal_` namespace over the node's keystore
+///
+/// Accounts start out locked. Signing either takes the account's password
+/// for that one request or needs the account to be unlocked first.
+pub struct PersonalApi {
+    accounts: Arc<RwLock<AccountManager>>,
+    txpool: Arc<TransactionPool>,
+    chain_id: u64,
+    /// Unlocked accounts and when they lock again, `None` for never
+    unlocked: Mutex<HashMap<Address, Option<Instant>>>,
+}
+
+impl PersonalApi {
+    pub fn new(accounts: Arc<RwLock<AccountManager>>, txpool: Arc<TransactionPool>, chain_id: u64) -> Self {
+        Self {
+            accounts,
+            txpool,
+            chain_id,
+            unlocked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Pool signed transactions are submitted to
+    pub fn txpool(&self) -> &TransactionPool {
+        &self.txpool
+    }
+
+    pub async fn new_account(&self, password: String) -> Result<H160> {
+        let mut accounts = self.accounts.write().await;
+        let address = accounts.new_account(&password).await.map_err(account_error)?;
+        // The manager keeps new accounts in memory, they stay locked until unlocked over RPC
+        accounts.lock_account(address);
+        Ok(H160::from(address.to_bytes()))
+    }
+
+    pub async fn list_accounts(&self) -> Result<Vec<H160>> {
+        let accounts = self.accounts.read().await;
+        Ok(accounts.list_accounts()
+            .into_iter()
+            .map(|address| H160::from(address.to_bytes()))
+            .collect())
+    }
+
+    /// Unlock `address` for `duration` seconds, 0 keeps it unlocked until the node stops
+    pub async fn unlock_account(&self, address: H160, password: String, duration: Option<u64>) -> Result<bool> {
+        let address = Address::from(address);
+        let mut accounts = self.accounts.write().await;
+        accounts.account_with_password(address, &password).await.map_err(account_error)?;
+        accounts.unlock_account(address, &password).await.map_err(account_error)?;
+
+        let until = match duration {
+            Some(0) => None,
+            Some(seconds) => Some(Instant::now() + Duration::from_secs(seconds)),
+            None => Some(Instant::now() + DEFAULT_UNLOCK_DURATION),
+        };
+        self.unlocked.lock().unwrap().insert(address, until);
+        Ok(true)
+    }
+
+    /// EIP-191 signature of `data` as `r || s || v` with v being 27 or 28
+    pub async fn sign(&self, data: String, address: H160, password: Option<String>) -> Result<String> {
+        let data = hex::decode(data.trim_start_matches("0x"))
+            .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
+        let account = self.signing_account(Address::from(address), password.as_deref()).await?;
+
+        let signature = account.sign_message(&eip191_message(&data)).map_err(account_error)?;
+        let mut bytes = signature.to_bytes();
+        bytes[64] += 27;
+        Ok(format!("0x{}", hex::encode(bytes)))
+    }
+
+    /// Sign `request` with its sender and add it to the pool
+    pub async fn send_transaction(&self, request: TransactionRequest, password: Option<String>) -> Result<H256> {
+        let from = Address::from(request.from);
+        let account = self.signing_account(from, password.as_deref()).await?;
+
+        let data = match &request.data {
+            Some(data) => hex::decode(data.trim_start_matches("0x"))
+                .map_err(|e| RpcError::InvalidParams(format!("invalid data: {}", e)))?,
+            None => Vec::new(),
+        };
+        let dynamic_fee = request.max_fee_per_gas.is_some() || request.max_priority_fee_per_gas.is_some();
+        let build = TxBuildRequest {
+            tx_type: if dynamic_fee { 2 } else { 0 },
+            chain_id: Some(self.chain_id),
+            nonce: request.nonce.unwrap_or_else(|| self.txpool.get_next_nonce(&from)),
+            to: request.to.map(Address::from),
+            value: request.value.unwrap_or_default(),
+            data: Bytes::from_vec(data),
+            gas_limit: request.gas.unwrap_or_default(),
+            gas_price: request.gas_price,
+            max_fee_per_gas: request.max_fee_per_gas,
+            max_priority_fee_per_gas: request.max_priority_fee_per_gas,
+            ..Default::default()
+        };
+
+        let signer = LocalSigner::new(account, Some(self.chain_id));
+        let (tx, _) = signer.build_and_sign(build).map_err(account_error)?;
+        self.txpool.add_local_transaction(tx)
+            .map_err(|e| RpcError::InvalidParams(e.to_string()))
+    }
+
+    /// Account able to sign for `address`, decrypted with `password` if one is given
+    async fn signing_account(&self, address: Address, password: Option<&str>) -> Result<Account> {
+        if let Some(password) = password {
+            return self.accounts.read().await
+                .account_with_password(address, password).await
+                .map_err(account_error);
+        }
+
+        let locked = || RpcError::InvalidParams(format!("account {:x} is locked", address));
+        let until = *self.unlocked.lock().unwrap().get(&address).ok_or_else(locked)?;
+        if until.map_or(false, |until| Instant::now() >= until) {
+            self.unlocked.lock().unwrap().remove(&address);
+            self.accounts.write().await.lock_account(address);
+            return Err(locked());
+        }
+
+        self.accounts.read().await
+            .get_account(address)
+            .cloned()
+            .ok_or_else(locked)
+    }
+}
+
+/// `"\x19Ethereum Signed Message:\n" || len(data) || data`, hashed by the signer
+fn eip191_message(data: &[u8]) -> Vec<u8> {
+    let mut message = format!("\x19Ethereum Signed Message:\n{}", data.len()).into_bytes();
+    message.extend_from_slice(data);
+    message
+}
+
+fn account_error(error: AccountError) -> RpcError {
+    match error {
+        AccountError::InvalidPassword
+        | AccountError::AccountNotFound
+        | AccountError::InvalidTransactionRequest(_) => RpcError::InvalidParams(error.to_string()),
+        _ => RpcError::InternalError(error.to_string()),
+    }
+}
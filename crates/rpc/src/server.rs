@@ -1,25 +1,34 @@
 use axum::{
-    extract::State,
+    extract::{ConnectInfo, State},
     response::Json,
     routing::post,
     Router,
 };
 use tower_http::cors::CorsLayer;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use serde_json::Value;
 
-use crate::{RpcRequest, RpcResponse, RpcErrorResponse, RpcError, Result};
+use crate::{RpcRequest, RpcResponse, RpcError, Result};
+use crate::limits::{RateLimiter, RpcLimits};
 use crate::methods::RpcHandler;
 
 pub struct RpcServer {
-    handler: Arc<RpcHandler>,
+    service: Arc<RpcService>,
     addr: SocketAddr,
 }
 
 impl RpcServer {
     pub fn new(addr: SocketAddr, handler: Arc<RpcHandler>) -> Self {
-        Self { handler, addr }
+        Self {
+            service: Arc::new(RpcService::new(handler, RpcLimits::default())),
+            addr,
+        }
+    }
+    
+    pub fn with_limits(mut self, limits: RpcLimits) -> Self {
+        self.service = Arc::new(RpcService::new(self.service.handler.clone(), limits));
+        self
     }
     
     pub async fn run(self) -> std::result::Result<(), Box<dyn std::error::Error>> {
@@ -27,94 +36,148 @@ impl RpcServer {
             .route("/", post(handle_rpc_request))
             .route("/health", axum::routing::get(health_check))
             .layer(CorsLayer::permissive())
-            .with_state(self.handler);
+            .with_state(self.service.clone());
         
         tracing::info!("JSON-RPC server listening on {}", self.addr);
         
+        // Drop budgets of clients that went quiet
+        let service = self.service.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                service.limiter.prune();
+            }
+        });
+        
         let listener = tokio::net::TcpListener::bind(self.addr).await?;
-        axum::serve(listener, app).await?;
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
         
         Ok(())
     }
 }
 
-async fn handle_rpc_request(
-    State(handler): State<Arc<RpcHandler>>,
-    Json(request): Json<Value>,
-) -> Json<Value> {
-    // Handle both single requests and batches
-    if request.is_array() {
-        // Batch request
-        let requests: Vec<RpcRequest> = match serde_json::from_value(request) {
-            Ok(reqs) => reqs,
-            Err(e) => {
-                return Json(serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "error": {
-                        "code": -32700,
-                        "message": format!("Parse error: {}", e)
-                    },
-                    "id": null
-                }));
+/// Request handling behind the server, enforcing its limits
+pub struct RpcService {
+    handler: Arc<RpcHandler>,
+    limits: RpcLimits,
+    limiter: RateLimiter,
+}
+
+impl RpcService {
+    pub fn new(handler: Arc<RpcHandler>, limits: RpcLimits) -> Self {
+        Self {
+            handler,
+            limiter: RateLimiter::new(limits.requests_per_second),
+            limits,
+        }
+    }
+    
+    /// Answer a single request or a batch sent by `client`
+    pub async fn handle(&self, client: IpAddr, request: Value) -> Value {
+        // Handle both single requests and batches
+        if let Value::Array(batch) = request {
+            if batch.len() > self.limits.max_batch_size {
+                let error = RpcError::LimitExceeded(format!(
+                    "batch of {} requests, at most {} allowed",
+                    batch.len(),
+                    self.limits.max_batch_size
+                ));
+                return serde_json::to_value(error_response(error, None)).unwrap_or(Value::Null);
             }
-        };
-        
-        let mut responses = Vec::new();
-        for req in requests {
-            let response = process_single_request(&handler, req).await;
-            if response.id.is_some() {
-                responses.push(response);
+            
+            let requests: Vec<RpcRequest> = match serde_json::from_value(Value::Array(batch)) {
+                Ok(reqs) => reqs,
+                Err(e) => return parse_error(e),
+            };
+            
+            let mut responses = Vec::new();
+            for req in requests {
+                let response = self.process_single_request(client, req).await;
+                if response.id.is_some() {
+                    responses.push(response);
+                }
             }
+            
+            serde_json::to_value(responses).unwrap_or(Value::Null)
+        } else {
+            // Single request
+            let request: RpcRequest = match serde_json::from_value(request) {
+                Ok(req) => req,
+                Err(e) => return parse_error(e),
+            };
+            
+            let response = self.process_single_request(client, request).await;
+            serde_json::to_value(response).unwrap_or(Value::Null)
         }
+    }
+    
+    async fn process_single_request(&self, client: IpAddr, request: RpcRequest) -> RpcResponse {
+        let id = request.id.clone();
         
-        Json(serde_json::to_value(responses).unwrap_or(Value::Null))
-    } else {
-        // Single request
-        let request: RpcRequest = match serde_json::from_value(request) {
-            Ok(req) => req,
-            Err(e) => {
-                return Json(serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "error": {
-                        "code": -32700,
-                        "message": format!("Parse error: {}", e)
-                    },
-                    "id": null
-                }));
-            }
+        if let Err(error) = self.limiter.check(client, &request.method) {
+            return error_response(error, id);
+        }
+        
+        let result = match self.handler.handle_request(request).await {
+            Ok(result) => result,
+            Err(error) => return error_response(error, id),
         };
         
-        let response = process_single_request(&handler, request).await;
-        Json(serde_json::to_value(response).unwrap_or(Value::Null))
+        match self.check_response_size(&result) {
+            Ok(()) => RpcResponse {
+                jsonrpc: "2.0".to_string(),
+                result: Some(result),
+                error: None,
+                id,
+            },
+            Err(error) => error_response(error, id),
+        }
+    }
+    
+    fn check_response_size(&self, result: &Value) -> Result<()> {
+        let size = serde_json::to_vec(result)
+            .map_err(|e| RpcError::InternalError(e.to_string()))?
+            .len();
+        if size > self.limits.max_response_bytes {
+            return Err(RpcError::LimitExceeded(format!(
+                "response of {} bytes, at most {} allowed",
+                size,
+                self.limits.max_response_bytes
+            )));
+        }
+        Ok(())
     }
 }
 
-async fn process_single_request(
-    handler: &Arc<RpcHandler>,
-    request: RpcRequest,
-) -> RpcResponse {
-    let id = request.id.clone();
-    
-    match handler.handle_request(request).await {
-        Ok(result) => RpcResponse {
-            jsonrpc: "2.0".to_string(),
-            result: Some(result),
-            error: None,
-            id,
-        },
-        Err(error) => RpcResponse {
-            jsonrpc: "2.0".to_string(),
-            result: None,
-            error: Some(RpcErrorResponse {
-                code: error.code(),
-                message: error.to_string(),
-                data: None,
-            }),
-            id,
-        },
+async fn handle_rpc_request(
+    State(service): State<Arc<RpcService>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(request): Json<Value>,
+) -> Json<Value> {
+    Json(service.handle(peer.ip(), request).await)
+}
+
+fn error_response(error: RpcError, id: Option<Value>) -> RpcResponse {
+    RpcResponse {
+        jsonrpc: "2.0".to_string(),
+        result: None,
+        error: Some(error.to_response()),
+        id,
     }
 }
 
+fn parse_error(error: serde_json::Error) -> Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": -32700,
+            "message": format!("Parse error: {}", error)
+        },
+        "id": null
+    })
+}
+
 async fn health_check() -> &'static str {
     "OK"
 }
@@ -135,4 +198,56 @@ impl WebSocketServer {
         tracing::info!("WebSocket server would listen on {}", self.addr);
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_storage::MemoryDatabase;
+    
+    fn service(limits: RpcLimits) -> RpcService {
+        let handler = RpcHandler::new(Arc::new(MemoryDatabase::new()), 1, "test/v0.1.0".to_string());
+        RpcService::new(Arc::new(handler), limits)
+    }
+    
+    fn request(id: u64) -> Value {
+        serde_json::json!({"jsonrpc": "2.0", "method": "net_version", "params": [], "id": id})
+    }
+    
+    #[tokio::test]
+    async fn test_requests_over_rate_limit_rejected() {
+        let service = service(RpcLimits { requests_per_second: 3, ..RpcLimits::unlimited() });
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        
+        for id in 0..3 {
+            let response = service.handle(client, request(id)).await;
+            assert_eq!(response["result"], "1");
+        }
+        let response = service.handle(client, request(3)).await;
+        assert_eq!(response["error"]["code"], -32005);
+        assert_eq!(response["id"], 3);
+        
+        // Other clients keep their own budget
+        let response = service.handle("10.0.0.2".parse().unwrap(), request(4)).await;
+        assert_eq!(response["result"], "1");
+    }
+    
+    #[tokio::test]
+    async fn test_oversized_batch_rejected() {
+        let service = service(RpcLimits { max_batch_size: 2, ..RpcLimits::unlimited() });
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        
+        let response = service.handle(client, Value::Array(vec![request(1), request(2)])).await;
+        assert_eq!(response.as_array().unwrap().len(), 2);
+        
+        let response = service.handle(client, Value::Array(vec![request(1), request(2), request(3)])).await;
+        assert_eq!(response["error"]["code"], -32005);
+        assert!(response["id"].is_null());
+    }
+    
+    #[tokio::test]
+    async fn test_oversized_response_rejected() {
+        let service = service(RpcLimits { max_response_bytes: 2, ..RpcLimits::unlimited() });
+        let response = service.handle("10.0.0.1".parse().unwrap(), request(1)).await;
+        assert_eq!(response["error"]["code"], -32005);
+    }
+}
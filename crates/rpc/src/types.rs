@@ -1,6 +1,11 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use ethereum_types::{H160, H256, U256};
+use ethereum_types::{Address, H160, H256, U256};
+use ethereum_core::RpcTransaction;
+pub use ethereum_core::{BlockId, BlockNumber};
+use ethereum_state::Call;
+
+pub use ethereum_state::{AccountOverride, StateOverride, StorageEntry, StorageRangeResult};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RpcRequest {
@@ -51,6 +56,11 @@ pub struct Block {
     pub transactions: Vec<TransactionOrHash>,
     pub uncles: Vec<H256>,
     pub base_fee_per_gas: Option<U256>,
+    /// Post-Cancun only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_gas_used: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excess_blob_gas: Option<U256>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,35 +70,16 @@ pub enum TransactionOrHash {
     Transaction(Transaction),
 }
 
+/// Transaction as served by the eth API: the spec object of its type plus
+/// where it sits in the chain
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Transaction {
-    pub hash: H256,
-    pub nonce: U256,
+    #[serde(flatten)]
+    pub tx: RpcTransaction,
     pub block_hash: Option<H256>,
     pub block_number: Option<U256>,
     pub transaction_index: Option<U256>,
-    pub from: H160,
-    pub to: Option<H160>,
-    pub value: U256,
-    pub gas_price: Option<U256>,
-    pub gas: U256,
-    pub input: String,
-    pub v: U256,
-    pub r: U256,
-    pub s: U256,
-    #[serde(rename = "type")]
-    pub tx_type: Option<U256>,
-    pub max_fee_per_gas: Option<U256>,
-    pub max_priority_fee_per_gas: Option<U256>,
-    pub access_list: Option<Vec<AccessListItem>>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct AccessListItem {
-    pub address: H160,
-    pub storage_keys: Vec<H256>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,6 +100,11 @@ pub struct Receipt {
     pub effective_gas_price: U256,
     #[serde(rename = "type")]
     pub tx_type: U256,
+    /// Blob transactions only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_gas_used: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_gas_price: Option<U256>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -136,6 +132,43 @@ pub struct CallRequest {
     pub max_priority_fee_per_gas: Option<U256>,
     pub value: Option<U256>,
     pub data: Option<String>,
+    /// Only checked by `eth_simulateV1` with validation enabled
+    pub nonce: Option<U256>,
+}
+
+impl CallRequest {
+    /// The call to execute, with its hex call data decoded
+    pub fn to_call(&self) -> crate::Result<Call> {
+        let data = match &self.data {
+            Some(data) => hex::decode(data.trim_start_matches("0x"))
+                .map_err(|e| crate::RpcError::InvalidParams(format!("invalid call data: {}", e)))?,
+            None => Vec::new(),
+        };
+
+        Ok(Call {
+            from: self.from.map(Address::from).unwrap_or_default(),
+            to: self.to.map(Address::from),
+            gas: self.gas,
+            gas_price: self.gas_price.unwrap_or_default(),
+            value: self.value.unwrap_or_default(),
+            data,
+        })
+    }
+}
+
+/// Transaction the node signs with one of its own accounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionRequest {
+    pub from: H160,
+    pub to: Option<H160>,
+    pub gas: Option<U256>,
+    pub gas_price: Option<U256>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub value: Option<U256>,
+    pub data: Option<String>,
+    pub nonce: Option<U256>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -162,21 +195,6 @@ pub enum FilterTopic {
     Multiple(Vec<H256>),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub enum BlockNumber {
-    Latest,
-    Earliest,
-    Pending,
-    Number(U256),
-}
-
-impl Default for BlockNumber {
-    fn default() -> Self {
-        BlockNumber::Latest
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SyncStatus {
@@ -191,4 +209,67 @@ pub struct FeeHistory {
     pub base_fee_per_gas: Vec<U256>,
     pub gas_used_ratio: Vec<f64>,
     pub reward: Option<Vec<Vec<U256>>>,
-}
\ No newline at end of file
+}
+/// Request of `eth_simulateV1`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatePayload {
+    pub block_state_calls: Vec<BlockStateCalls>,
+    /// Report value transfers as ERC-20 `Transfer` logs
+    #[serde(default)]
+    pub trace_transfers: bool,
+    /// Check nonces, fees and balances like a real block would
+    #[serde(default)]
+    pub validation: bool,
+}
+
+/// One simulated block: overrides applied before its calls run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockStateCalls {
+    pub block_overrides: Option<BlockOverrides>,
+    pub state_overrides: Option<StateOverride>,
+    #[serde(default)]
+    pub calls: Vec<CallRequest>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockOverrides {
+    pub number: Option<U256>,
+    pub time: Option<U256>,
+    pub gas_limit: Option<U256>,
+    pub fee_recipient: Option<H160>,
+    pub prev_randao: Option<H256>,
+    pub base_fee_per_gas: Option<U256>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedBlock {
+    pub number: U256,
+    pub timestamp: U256,
+    pub gas_limit: U256,
+    pub gas_used: U256,
+    pub fee_recipient: H160,
+    pub base_fee_per_gas: Option<U256>,
+    pub calls: Vec<SimulatedCall>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedCall {
+    /// 1 for success, 0 for failure
+    pub status: U256,
+    pub return_data: String,
+    pub gas_used: U256,
+    pub logs: Vec<Log>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SimulateCallError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulateCallError {
+    pub code: i32,
+    pub message: String,
+}
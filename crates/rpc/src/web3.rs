@@ -16,7 +16,9 @@ impl Web3Api {
     
     pub async fn sha3(&self, data: String) -> Result<H256> {
         // Decode hex string and compute Keccak256
-        let bytes = hex::decode(data.trim_start_matches("0x"))
+        let hex_data = data.strip_prefix("0x")
+            .ok_or_else(|| RpcError::InvalidParams("data must be 0x-prefixed hex".to_string()))?;
+        let bytes = hex::decode(hex_data)
             .map_err(|e| RpcError::InvalidParams(e.to_string()))?;
         
         let hash = ethereum_crypto::keccak256(&bytes);
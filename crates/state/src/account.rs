@@ -0,0 +1,79 @@
+use ethereum_types::{H256, U256};
+use ethereum_crypto::keccak256;
+use ethereum_rlp::{Decoder, Encoder, RlpItem};
+use ethereum_trie::empty_root;
+
+use crate::{Result, StateError};
+
+/// Account as stored in the state trie: `[nonce, balance, storage_root, code_hash]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateAccount {
+    pub nonce: u64,
+    pub balance: U256,
+    pub storage_root: H256,
+    pub code_hash: H256,
+}
+
+impl Default for StateAccount {
+    fn default() -> Self {
+        Self {
+            nonce: 0,
+            balance: U256::zero(),
+            storage_root: empty_root(),
+            code_hash: keccak256(&[]),
+        }
+    }
+}
+
+impl StateAccount {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut fields = Encoder::new();
+        fields.encode_u64(self.nonce);
+        ethereum_rlp::Encode::encode(&self.balance, &mut fields);
+        fields.encode_bytes(self.storage_root.as_bytes());
+        fields.encode_bytes(self.code_hash.as_bytes());
+
+        let mut list = Encoder::new();
+        list.encode_list_payload(&fields.finish());
+        list.finish()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let item = Decoder::new(data)
+            .and_then(|mut decoder| decoder.decode_item())
+            .map_err(|e| StateError::Unavailable(format!("invalid account encoding: {}", e)))?;
+
+        let fields = match item.as_list() {
+            Some(fields) if fields.len() == 4 => fields,
+            _ => return Err(StateError::Unavailable("invalid account encoding".to_string())),
+        };
+        let nonce = integer_field(&fields[0], 8)?;
+        let balance = integer_field(&fields[1], 32)?;
+
+        Ok(Self {
+            nonce: nonce.as_u64(),
+            balance,
+            storage_root: hash_field(&fields[2])?,
+            code_hash: hash_field(&fields[3])?,
+        })
+    }
+}
+
+fn integer_field(item: &RlpItem, max_len: usize) -> Result<U256> {
+    match item.as_bytes() {
+        Some(bytes) if bytes.len() <= max_len => Ok(U256::from_big_endian(bytes)),
+        _ => Err(StateError::Unavailable("invalid account encoding".to_string())),
+    }
+}
+
+fn hash_field(item: &RlpItem) -> Result<H256> {
+    match item.as_bytes() {
+        Some(bytes) if bytes.len() == 32 => Ok(H256::from_slice(bytes)),
+        _ => Err(StateError::Unavailable("invalid account encoding".to_string())),
+    }
+}
+
+/// Key recording the slot whose hash is `hash`, for nodes that keep preimages
+pub fn preimage_key(hash: &H256) -> String {
+    format!("preimage:{}", hex::encode(hash.as_bytes()))
+}
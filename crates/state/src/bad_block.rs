@@ -0,0 +1,93 @@
+use ethereum_core::Block;
+use ethereum_storage::Database;
+use ethereum_types::H256;
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, StateError};
+
+const BAD_BLOCK_PREFIX: &str = "bad_block:";
+
+/// Block that failed validation, with why it was rejected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BadBlock {
+    pub block: Block,
+    pub reason: String,
+    /// Unix time of the rejection, in seconds
+    pub rejected_at: u64,
+}
+
+/// Record a block that failed validation so it can be inspected later
+pub fn store_bad_block<D: Database + ?Sized>(db: &D, block: &Block, reason: impl Into<String>) -> Result<()> {
+    let rejected_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let bad = BadBlock { block: block.clone(), reason: reason.into(), rejected_at };
+    let data = bincode::serialize(&bad)
+        .map_err(|e| StateError::Unavailable(e.to_string()))?;
+    db.put(bad_block_key(&block.header.hash()).as_bytes(), &data)
+        .map_err(|e| StateError::Unavailable(e.to_string()))
+}
+
+/// Bad block stored under `hash`, `None` if there is none
+pub fn load_bad_block<D: Database + ?Sized>(db: &D, hash: &H256) -> Result<Option<BadBlock>> {
+    let key = bad_block_key(hash);
+    match db.get(key.as_bytes()).map_err(|e| StateError::Unavailable(e.to_string()))? {
+        Some(data) => decode_bad_block(&key, &data).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Every recorded bad block
+///
+/// Entries that don't decode, like blocks stored before rejections carried
+/// a reason, are an error rather than left out.
+pub fn bad_blocks<D: Database + ?Sized>(db: &D) -> Result<Vec<BadBlock>> {
+    let mut iter = db.iter_prefix(BAD_BLOCK_PREFIX.as_bytes());
+    let mut bad_blocks = Vec::new();
+    while let Some(entry) = iter.next() {
+        let (key, value) = entry.map_err(|e| StateError::Unavailable(e.to_string()))?;
+        bad_blocks.push(decode_bad_block(&String::from_utf8_lossy(&key), &value)?);
+    }
+    Ok(bad_blocks)
+}
+
+fn decode_bad_block(key: &str, data: &[u8]) -> Result<BadBlock> {
+    bincode::deserialize(data)
+        .map_err(|e| StateError::Unavailable(format!("undecodable bad block entry {}: {}", key, e)))
+}
+
+fn bad_block_key(hash: &H256) -> String {
+    format!("{}{}", BAD_BLOCK_PREFIX, hex::encode(hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::Header;
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_types::U256;
+
+    #[test]
+    fn test_bad_blocks_round_trip() {
+        let db = MemoryDatabase::new();
+        assert!(bad_blocks(&db).unwrap().is_empty());
+
+        let mut header = Header::new();
+        header.number = U256::from(9);
+        let block = Block::new(header);
+        store_bad_block(&db, &block, "State root mismatch").unwrap();
+
+        let stored = load_bad_block(&db, &block.header.hash()).unwrap().unwrap();
+        assert_eq!(stored.block.header.hash(), block.header.hash());
+        assert_eq!(stored.reason, "State root mismatch");
+        assert!(stored.rejected_at > 0);
+        assert_eq!(bad_blocks(&db).unwrap().len(), 1);
+
+        // A bare block, as stored before rejections had a reason, is reported
+        let old = bincode::serialize(&block).unwrap();
+        db.put(bad_block_key(&H256::repeat_byte(0x01)).as_bytes(), &old).unwrap();
+        assert!(matches!(bad_blocks(&db), Err(StateError::Unavailable(_))));
+    }
+}
@@ -0,0 +1,314 @@
+use ethereum_types::{Address, U256};
+use ethereum_core::Transaction;
+use ethereum_evm::execution::{BlockContext, ExecutionStatus};
+use ethereum_evm::gas::GasCost;
+use ethereum_evm::interpreter::create_address;
+use ethereum_evm::state::StateDB;
+use ethereum_evm::{run_interpreter, ExecutionContext, ExecutionResult, JournaledState};
+
+use crate::intrinsic::{call_intrinsic_gas, intrinsic_gas};
+use crate::overlay::CallState;
+use crate::{Result, StateError};
+
+/// Gas charged for every transaction before any code runs
+pub const TX_BASE_GAS: u64 = 21_000;
+
+/// Message call run against the state on behalf of `from`, without a
+/// signed transaction
+#[derive(Debug, Clone, Default)]
+pub struct Call {
+    pub from: Address,
+    /// `None` runs `data` as init code
+    pub to: Option<Address>,
+    /// Defaults to the block gas limit
+    pub gas: Option<U256>,
+    pub gas_price: U256,
+    pub value: U256,
+    pub data: Vec<u8>,
+}
+
+/// Execute one transaction against the overlay, returning false if it can't be included
+pub fn apply_transaction(state: &mut CallState<'_>, block: &BlockContext, sender: Address, tx: &Transaction) -> bool {
+    apply_transaction_with(state, block, sender, tx, |context, host| run_interpreter(context, host)).is_some()
+}
+
+/// Execute one transaction like `apply_transaction`, with `run` running its
+/// code so a tracer can sit between the interpreter and the state
+///
+/// The sender pays for the gas used less its refund, and the coinbase gets
+/// the priority fee of it. Returns the result of the transaction's frame,
+/// whose gas leaves out the intrinsic gas, `None` if it can't be included.
+pub fn apply_transaction_with<'a, F>(
+    state: &mut CallState<'a>,
+    block: &BlockContext,
+    sender: Address,
+    tx: &Transaction,
+    run: F,
+) -> Option<ExecutionResult>
+where
+    F: FnOnce(ExecutionContext, &mut JournaledState<'_, CallState<'a>>) -> ExecutionResult,
+{
+    let account = state.get_account(&sender).unwrap_or_default();
+    if U256::from(account.nonce) != tx.nonce() {
+        return None;
+    }
+
+    if tx.upfront_cost() > account.balance {
+        return None;
+    }
+
+    let gas_limit = u64_or_max(tx.gas_limit());
+    let intrinsic = intrinsic_gas(tx);
+    if gas_limit < intrinsic {
+        return None;
+    }
+
+    let snapshot = state.clone();
+    let mut context = ExecutionContext::new(
+        sender,
+        Address::zero(),
+        tx.value(),
+        Vec::new(),
+        Vec::new(),
+        gas_limit - intrinsic,
+        block.clone(),
+    );
+    let gas_price = tx.effective_gas_price(block.base_fee.unwrap_or_default());
+    context.gas_price = gas_price;
+    let spec = context.spec;
+    let frame_gas = context.gas_limit;
+
+    bump_nonce(state, &sender);
+    let result = match tx.to() {
+        Some(to) => {
+            context.address = to;
+            context.code = state.get_account(&to).map(|acc| acc.code).unwrap_or_default();
+            context.data = tx.data().as_slice().to_vec();
+            run_frame_with(state, context, run)
+        }
+        None => {
+            let address = create_address(&sender, account.nonce);
+            context.address = address;
+            context.code = tx.data().as_slice().to_vec();
+            let mut result = run_frame_with(state, context, run);
+            result.deposit_code(spec, frame_gas);
+            if result.status == ExecutionStatus::Success {
+                let mut created = state.get_account(&address).unwrap_or_default();
+                created.code = result.return_data.clone();
+                state.set_account(address, created);
+            }
+            result
+        }
+    };
+
+    // A failed transaction still consumes its nonce and pays for its gas
+    if result.status != ExecutionStatus::Success {
+        *state = snapshot;
+        bump_nonce(state, &sender);
+    }
+
+    let mut gas_used = intrinsic.saturating_add(result.gas_used);
+    if result.status == ExecutionStatus::Success {
+        gas_used -= result.gas_refund.min(GasCost::max_refund(spec, gas_used));
+    }
+    let mut payer = state.get_account(&sender).unwrap_or_default();
+    payer.balance = payer.balance.saturating_sub(U256::from(gas_used) * gas_price);
+    state.set_account(sender, payer);
+
+    // The base fee is burnt, the coinbase only gets the priority fee
+    let tip = gas_price.saturating_sub(block.base_fee.unwrap_or_default());
+    let mut coinbase = state.get_account(&block.coinbase).unwrap_or_default();
+    coinbase.balance = coinbase.balance.saturating_add(U256::from(gas_used) * tip);
+    state.set_account(block.coinbase, coinbase);
+
+    Some(result)
+}
+
+/// Execute `call` against the state without charging for gas
+///
+/// The frame gets the call's gas less what it would pay as a transaction
+/// before any code runs.
+pub fn execute_call(state: &mut CallState<'_>, block: &BlockContext, call: &Call) -> Result<ExecutionResult> {
+    execute_call_with(state, block, call, |context, host| run_interpreter(context, host))
+}
+
+/// Execute `call` like `execute_call`, with `run` running its code so a
+/// tracer can sit between the interpreter and the state
+pub fn execute_call_with<'a, F>(
+    state: &mut CallState<'a>,
+    block: &BlockContext,
+    call: &Call,
+    run: F,
+) -> Result<ExecutionResult>
+where
+    F: FnOnce(ExecutionContext, &mut JournaledState<'_, CallState<'a>>) -> ExecutionResult,
+{
+    let gas_limit = u64_or_max(call.gas.unwrap_or(block.gas_limit));
+
+    if !call.value.is_zero() {
+        let balance = state.get_account(&call.from).map(|acc| acc.balance).unwrap_or_default();
+        if balance < call.value {
+            return Err(StateError::InvalidInput("insufficient funds for value transfer".to_string()));
+        }
+    }
+
+    let mut context = ExecutionContext::new(
+        call.from,
+        Address::zero(),
+        call.value,
+        Vec::new(),
+        Vec::new(),
+        gas_limit.saturating_sub(call_intrinsic_gas(call)),
+        block.clone(),
+    );
+    context.gas_price = call.gas_price;
+
+    match call.to {
+        Some(to) => {
+            context.address = to;
+            context.code = state.get_account(&to).map(|acc| acc.code).unwrap_or_default();
+            context.data = call.data.clone();
+        }
+        None => {
+            let nonce = state.get_account(&call.from).map(|acc| acc.nonce).unwrap_or_default();
+            context.address = create_address(&call.from, nonce);
+            context.code = call.data.clone();
+        }
+    }
+
+    let result = run_frame_with(state, context, run);
+    state.finish()?;
+    Ok(result)
+}
+
+/// Move the frame's value to its address and run its code with `run`
+fn run_frame_with<'a, F>(state: &mut CallState<'a>, context: ExecutionContext, run: F) -> ExecutionResult
+where
+    F: FnOnce(ExecutionContext, &mut JournaledState<'_, CallState<'a>>) -> ExecutionResult,
+{
+    transfer(state, context.caller, context.address, context.value);
+    let mut journaled = JournaledState::new(state);
+    run(context, &mut journaled)
+}
+
+/// Move `value` from `from` to `to`, callers check the balance first
+pub fn transfer<S: StateDB>(state: &mut S, from: Address, to: Address, value: U256) {
+    if value.is_zero() {
+        return;
+    }
+
+    let mut sender = state.get_account(&from).unwrap_or_default();
+    sender.balance = sender.balance.saturating_sub(value);
+    state.set_account(from, sender);
+
+    let mut recipient = state.get_account(&to).unwrap_or_default();
+    recipient.balance = recipient.balance.saturating_add(value);
+    state.set_account(to, recipient);
+}
+
+/// Consume a nonce of `address`
+pub fn bump_nonce<S: StateDB>(state: &mut S, address: &Address) {
+    let mut account = state.get_account(address).unwrap_or_default();
+    account.nonce += 1;
+    state.set_account(*address, account);
+}
+
+pub(crate) fn u64_or_max(value: U256) -> u64 {
+    if value > U256::from(u64::MAX) {
+        u64::MAX
+    } else {
+        value.as_u64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::EmptyState;
+    use ethereum_core::{Eip1559Transaction, Header};
+    use ethereum_evm::execution::HaltReason;
+    use ethereum_evm::{Account, ChainConfig};
+    use ethereum_types::{Bytes, H256};
+
+    const SENDER: [u8; 20] = [0x01; 20];
+    const COINBASE: [u8; 20] = [0xcc; 20];
+
+    fn block() -> BlockContext {
+        let mut header = Header::new();
+        header.gas_limit = U256::from(30_000_000);
+        header.base_fee_per_gas = Some(U256::from(10));
+        header.beneficiary = Address::from_bytes(COINBASE);
+        BlockContext::from_header(&header, &ChainConfig::mainnet(), Vec::new())
+    }
+
+    fn tx(to: Option<Address>, data: Vec<u8>) -> Transaction {
+        Transaction::Eip1559(Eip1559Transaction {
+            chain_id: 1,
+            nonce: U256::zero(),
+            max_priority_fee_per_gas: U256::from(2),
+            max_fee_per_gas: U256::from(20),
+            gas_limit: U256::from(100_000),
+            to,
+            value: U256::zero(),
+            data: Bytes::from(data),
+            access_list: Vec::new(),
+            y_parity: false,
+            r: U256::zero(),
+            s: U256::zero(),
+        })
+    }
+
+    fn funded(provider: &EmptyState) -> CallState<'_> {
+        let mut state = CallState::new(provider, H256::zero());
+        let account = Account { balance: U256::from(10_000_000), ..Default::default() };
+        state.set_account(Address::from_bytes(SENDER), account);
+        state
+    }
+
+    #[test]
+    fn test_coinbase_gets_the_priority_fee() {
+        let provider = EmptyState;
+        let mut state = funded(&provider);
+        let sender = Address::from_bytes(SENDER);
+
+        assert!(apply_transaction(&mut state, &block(), sender, &tx(Some(Address::from_bytes([0x02; 20])), Vec::new())));
+
+        // 21000 gas at the base fee of 10 plus a tip of 2
+        let coinbase = state.get_account(&Address::from_bytes(COINBASE)).unwrap();
+        assert_eq!(coinbase.balance, U256::from(21_000 * 2));
+        let sender = state.get_account(&sender).unwrap();
+        assert_eq!(sender.balance, U256::from(10_000_000 - 21_000 * 12));
+    }
+
+    #[test]
+    fn test_create_charges_and_checks_deployed_code() {
+        let provider = EmptyState;
+        let sender = Address::from_bytes(SENDER);
+        let created = create_address(&sender, 0);
+
+        // PUSH1 <byte>, PUSH1 0x00, MSTORE8, PUSH1 0x01, PUSH1 0x00, RETURN
+        let init_code = |byte: u8| vec![0x60, byte, 0x60, 0x00, 0x53, 0x60, 0x01, 0x60, 0x00, 0xf3];
+
+        let mut state = funded(&provider);
+        let result = apply_transaction_with(&mut state, &block(), sender, &tx(None, init_code(0x00)), |context, host| run_interpreter(context, host)).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(state.get_account(&created).unwrap().code, vec![0x00]);
+        let gas_used = result.gas_used;
+
+        // Code starting with 0xEF can't be deployed (EIP-3541)
+        let mut state = funded(&provider);
+        let result = apply_transaction_with(&mut state, &block(), sender, &tx(None, init_code(0xef)), |context, host| run_interpreter(context, host)).unwrap();
+        assert_eq!(result.status, ExecutionStatus::Halt(HaltReason::InvalidCode));
+        assert!(state.get_account(&created).map_or(true, |acc| acc.code.is_empty()));
+        assert_eq!(state.get_account(&sender).unwrap().nonce, 1);
+
+        // The deployed byte costs its deposit on top of the init code
+        let mut state = funded(&provider);
+        let result = apply_transaction_with(&mut state, &block(), sender, &tx(None, init_code(0x00)), |context, host| {
+            let mut result = run_interpreter(context, host);
+            result.return_data.clear();
+            result
+        }).unwrap();
+        assert_eq!(gas_used - result.gas_used, GasCost::CODEDEPOSIT);
+    }
+}
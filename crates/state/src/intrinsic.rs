@@ -0,0 +1,74 @@
+use ethereum_core::{AccessListItem, Transaction};
+
+use crate::call::{Call, TX_BASE_GAS};
+
+/// Base cost of every transaction
+pub const TX_GAS: u64 = TX_BASE_GAS;
+/// Extra base cost of a contract creation
+pub const TX_CREATE_GAS: u64 = 32_000;
+/// Cost per zero byte of calldata
+pub const TX_DATA_ZERO_GAS: u64 = 4;
+/// Cost per non-zero byte of calldata (EIP-2028)
+pub const TX_DATA_NON_ZERO_GAS: u64 = 16;
+/// Cost per access list address (EIP-2930)
+pub const TX_ACCESS_LIST_ADDRESS_GAS: u64 = 2_400;
+/// Cost per access list storage key (EIP-2930)
+pub const TX_ACCESS_LIST_STORAGE_KEY_GAS: u64 = 1_900;
+/// Cost per 32-byte word of init code (EIP-3860)
+pub const INIT_CODE_WORD_GAS: u64 = 2;
+/// Cost per EIP-7702 authorization
+pub const PER_AUTH_BASE_GAS: u64 = 25_000;
+
+/// Gas a transaction is charged before any code runs
+pub fn intrinsic_gas(tx: &Transaction) -> u64 {
+    let mut gas = payload_gas(tx.data().as_slice(), tx.to().is_none());
+
+    if let Transaction::Eip7702(tx) = tx {
+        gas += PER_AUTH_BASE_GAS * tx.authorization_list.len() as u64;
+    }
+
+    gas + access_list_gas(access_list(tx))
+}
+
+/// Intrinsic gas of `call` sent as a transaction without an access list
+pub fn call_intrinsic_gas(call: &Call) -> u64 {
+    payload_gas(&call.data, call.to.is_none())
+}
+
+/// Base cost of a transaction carrying `data`, as init code if it `creates`
+fn payload_gas(data: &[u8], creates: bool) -> u64 {
+    let zeros = data.iter().filter(|byte| **byte == 0).count() as u64;
+    let non_zeros = data.len() as u64 - zeros;
+
+    let mut gas = TX_GAS
+        + zeros * TX_DATA_ZERO_GAS
+        + non_zeros * TX_DATA_NON_ZERO_GAS;
+
+    if creates {
+        gas += TX_CREATE_GAS + INIT_CODE_WORD_GAS * (data.len() as u64).div_ceil(32);
+    }
+
+    gas
+}
+
+/// Gas charged for `access_list` (EIP-2930)
+///
+/// Duplicate addresses and storage keys are valid and every entry is
+/// charged, only the accessed set they warm is deduplicated.
+pub fn access_list_gas(access_list: &[AccessListItem]) -> u64 {
+    access_list.iter()
+        .map(|item| TX_ACCESS_LIST_ADDRESS_GAS
+            + TX_ACCESS_LIST_STORAGE_KEY_GAS * item.storage_keys.len() as u64)
+        .sum()
+}
+
+/// Access list of `tx`, empty for legacy transactions
+fn access_list(tx: &Transaction) -> &[AccessListItem] {
+    match tx {
+        Transaction::Legacy(_) => &[],
+        Transaction::Eip2930(tx) => &tx.access_list,
+        Transaction::Eip1559(tx) => &tx.access_list,
+        Transaction::Eip4844(tx) => &tx.access_list,
+        Transaction::Eip7702(tx) => &tx.access_list,
+    }
+}
@@ -0,0 +1,31 @@
+use thiserror::Error;
+
+pub mod account;
+pub mod provider;
+pub mod overlay;
+pub mod call;
+pub mod overrides;
+pub mod bad_block;
+pub mod intrinsic;
+
+pub use account::{preimage_key, StateAccount};
+pub use provider::{EmptyState, StateDbProvider, StateProvider, TrieStateProvider};
+pub use overlay::{CallState, StorageEntry, StorageRangeResult};
+pub use call::{apply_transaction, apply_transaction_with, bump_nonce, execute_call, execute_call_with, transfer, Call, TX_BASE_GAS};
+pub use overrides::{apply_account_override, apply_state_override, AccountOverride, StateOverride};
+pub use bad_block::{bad_blocks, load_bad_block, store_bad_block, BadBlock};
+pub use intrinsic::{call_intrinsic_gas, intrinsic_gas};
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    /// The caller asked for something the state can't do, like a malformed
+    /// override or a call its sender can't pay for
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    /// Committed state is missing or unreadable
+    #[error("State unavailable: {0}")]
+    Unavailable(String),
+}
+
+pub type Result<T> = std::result::Result<T, StateError>;
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use ethereum_types::{Address, H256};
+use ethereum_crypto::keccak256;
+use ethereum_evm::state::StateDB;
+use ethereum_evm::Account;
+use serde::{Deserialize, Serialize};
+
+use crate::provider::StateProvider;
+use crate::{Result, StateError};
+
+/// Throwaway overlay on top of committed state used to execute calls
+///
+/// Reads fall through to the provider, writes stay in the overlay and are
+/// dropped with it. Provider errors can't be returned through `StateDB`, so
+/// the first one is kept and reported by `finish`.
+#[derive(Clone)]
+pub struct CallState<'a> {
+    provider: &'a dyn StateProvider,
+    state_root: H256,
+    /// Modified accounts, `None` marks a removed account
+    accounts: HashMap<Address, Option<Account>>,
+    storage: HashMap<(Address, H256), H256>,
+    /// Accounts whose committed storage must no longer be read
+    cleared: HashSet<Address>,
+    error: RefCell<Option<String>>,
+}
+
+impl<'a> CallState<'a> {
+    pub fn new(provider: &'a dyn StateProvider, state_root: H256) -> Self {
+        Self {
+            provider,
+            state_root,
+            accounts: HashMap::new(),
+            storage: HashMap::new(),
+            cleared: HashSet::new(),
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Surface the first error hit while reading committed state
+    pub fn finish(&self) -> Result<()> {
+        match self.error.borrow_mut().take() {
+            Some(e) => Err(StateError::Unavailable(e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Accounts written through the overlay, `None` for removed ones
+    pub fn changed_accounts(&self) -> impl Iterator<Item = (&Address, Option<&Account>)> {
+        self.accounts.iter().map(|(address, account)| (address, account.as_ref()))
+    }
+
+    /// Storage slots written through the overlay
+    pub fn changed_storage(&self) -> impl Iterator<Item = (&Address, &H256, &H256)> {
+        self.storage.iter().map(|((address, slot), value)| (address, slot, value))
+    }
+
+    /// Up to `max_results` slots of `address` from hashed slot `start` on,
+    /// with the overlay's writes layered over the committed storage
+    ///
+    /// `next_key` is the hashed slot the following page starts at.
+    pub fn storage_range(&self, address: &Address, start: &H256, max_results: usize) -> Result<StorageRangeResult> {
+        let written: Vec<_> = self.storage.iter()
+            .filter(|((owner, _), _)| owner == address)
+            .map(|((_, slot), value)| (keccak256(slot.as_bytes()), *slot, *value))
+            .filter(|(hash, _, _)| hash >= start)
+            .collect();
+
+        let mut slots = BTreeMap::new();
+        if !self.cleared.contains(address) {
+            // Each write may shadow a committed slot, so read enough of them
+            // to fill the page and find the next key either way
+            let wanted = max_results.saturating_add(written.len()).saturating_add(1);
+            for (hash, value) in self.provider.storage_range(self.state_root, address, start, wanted)? {
+                slots.insert(hash, StorageEntry { key: None, value });
+            }
+        }
+        for (hash, slot, value) in written {
+            if value.is_zero() {
+                slots.remove(&hash);
+            } else {
+                slots.insert(hash, StorageEntry { key: Some(slot), value });
+            }
+        }
+
+        let mut slots = slots.into_iter();
+        let mut storage = BTreeMap::new();
+        for (hash, mut entry) in slots.by_ref().take(max_results) {
+            if entry.key.is_none() {
+                entry.key = self.provider.preimage(&hash)?;
+            }
+            storage.insert(hash, entry);
+        }
+
+        Ok(StorageRangeResult {
+            storage,
+            next_key: slots.next().map(|(hash, _)| hash),
+        })
+    }
+
+    fn record_error(&self, e: StateError) {
+        self.error.borrow_mut().get_or_insert_with(|| e.to_string());
+    }
+}
+
+impl<'a> StateDB for CallState<'a> {
+    fn get_account(&self, address: &Address) -> Option<Account> {
+        if let Some(account) = self.accounts.get(address) {
+            return account.clone();
+        }
+
+        self.provider.account(self.state_root, address).unwrap_or_else(|e| {
+            self.record_error(e);
+            None
+        })
+    }
+
+    fn set_account(&mut self, address: Address, account: Account) {
+        self.accounts.insert(address, Some(account));
+    }
+
+    fn get_storage(&self, address: &Address, key: &H256) -> H256 {
+        if let Some(value) = self.storage.get(&(*address, *key)) {
+            return *value;
+        }
+        if self.cleared.contains(address) {
+            return H256::zero();
+        }
+
+        self.provider.storage(self.state_root, address, key).unwrap_or_else(|e| {
+            self.record_error(e);
+            H256::zero()
+        })
+    }
+
+    fn set_storage(&mut self, address: Address, key: H256, value: H256) {
+        self.storage.insert((address, key), value);
+    }
+
+    fn exists(&self, address: &Address) -> bool {
+        self.get_account(address).is_some()
+    }
+
+    fn is_empty(&self, address: &Address) -> bool {
+        self.get_account(address)
+            .map(|account| {
+                account.balance.is_zero()
+                && account.nonce == 0
+                && account.code.is_empty()
+            })
+            .unwrap_or(true)
+    }
+
+    fn remove_account(&mut self, address: &Address) {
+        self.accounts.insert(*address, None);
+        self.storage.retain(|(owner, _), _| owner != address);
+        self.cleared.insert(*address);
+    }
+}
+
+/// Page of `debug_storageRangeAt`, slots keyed by the hash of the slot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageRangeResult {
+    pub storage: BTreeMap<H256, StorageEntry>,
+    /// Hashed slot the next page starts at, `None` on the last page
+    pub next_key: Option<H256>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageEntry {
+    /// The slot itself, `None` when its preimage isn't known
+    pub key: Option<H256>,
+    pub value: H256,
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use ethereum_types::{Address, H160, H256, U256};
+use ethereum_evm::state::StateDB;
+use serde::{Deserialize, Serialize};
+
+use crate::call::u64_or_max;
+use crate::overlay::CallState;
+use crate::{Result, StateError};
+
+/// Account overrides layered over the state a call runs against
+pub type StateOverride = HashMap<H160, AccountOverride>;
+
+/// Replacement account fields, `state` replaces all storage while
+/// `state_diff` only the given slots
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<U256>,
+    pub code: Option<String>,
+    pub state: Option<HashMap<H256, H256>>,
+    pub state_diff: Option<HashMap<H256, H256>>,
+}
+
+/// Apply every account override of `overrides`
+pub fn apply_state_override(state: &mut CallState<'_>, overrides: &StateOverride) -> Result<()> {
+    for (address, account_override) in overrides {
+        apply_account_override(state, Address::from(*address), account_override)?;
+    }
+    Ok(())
+}
+
+/// Replace the fields of `address` that `account_override` sets
+pub fn apply_account_override(state: &mut CallState<'_>, address: Address, account_override: &AccountOverride) -> Result<()> {
+    if account_override.state.is_some() && account_override.state_diff.is_some() {
+        return Err(StateError::InvalidInput(format!(
+            "account {:x} has both state and stateDiff overrides", address
+        )));
+    }
+
+    let mut account = state.get_account(&address).unwrap_or_default();
+    if let Some(balance) = account_override.balance {
+        account.balance = balance;
+    }
+    if let Some(nonce) = account_override.nonce {
+        account.nonce = u64_or_max(nonce);
+    }
+    if let Some(code) = &account_override.code {
+        account.code = hex::decode(code.trim_start_matches("0x"))
+            .map_err(|e| StateError::InvalidInput(format!("invalid code override: {}", e)))?;
+    }
+
+    if let Some(storage) = &account_override.state {
+        // Drops the committed storage, only the given slots are left
+        state.remove_account(&address);
+        account.storage.clear();
+        state.set_account(address, account);
+        for (slot, value) in storage {
+            state.set_storage(address, *slot, *value);
+        }
+        return Ok(());
+    }
+
+    state.set_account(address, account);
+    for (slot, value) in account_override.state_diff.iter().flatten() {
+        state.set_storage(address, *slot, *value);
+    }
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use std::sync::Arc;
+use ethereum_types::{Address, H256};
+use ethereum_crypto::keccak256;
+use ethereum_evm::state::StateDB;
+use ethereum_evm::Account;
+use ethereum_rlp::Decoder;
+use ethereum_storage::Database;
+use ethereum_trie::{empty_root, TrieError, TrieReadView};
+
+use crate::account::{preimage_key, StateAccount};
+use crate::{Result, StateError};
+
+/// Read access to committed account state at a given state root
+pub trait StateProvider: Send + Sync {
+    /// Balance, nonce and code of `address`, `None` if the account does not exist
+    fn account(&self, state_root: H256, address: &Address) -> Result<Option<Account>>;
+
+    fn storage(&self, state_root: H256, address: &Address, slot: &H256) -> Result<H256>;
+
+    /// Up to `max_results` non-zero slots of `address` keyed by the hash of
+    /// the slot, in hash order starting at `start`
+    fn storage_range(&self, _state_root: H256, _address: &Address, _start: &H256, _max_results: usize) -> Result<Vec<(H256, H256)>> {
+        Err(StateError::Unavailable("storage enumeration is not supported".to_string()))
+    }
+
+    /// Slot whose hash is `hash`, if its preimage was recorded
+    fn preimage(&self, _hash: &H256) -> Result<Option<H256>> {
+        Ok(None)
+    }
+}
+
+/// Provider for a node without state, every account reads as absent
+pub struct EmptyState;
+
+impl StateProvider for EmptyState {
+    fn account(&self, _state_root: H256, _address: &Address) -> Result<Option<Account>> {
+        Ok(None)
+    }
+
+    fn storage(&self, _state_root: H256, _address: &Address, _slot: &H256) -> Result<H256> {
+        Ok(H256::zero())
+    }
+
+    fn storage_range(&self, _state_root: H256, _address: &Address, _start: &H256, _max_results: usize) -> Result<Vec<(H256, H256)>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Serves a `StateDB` the caller already holds as committed state
+///
+/// There is a single state behind it, so the state root is ignored.
+pub struct StateDbProvider<'a, S: StateDB + Sync>(pub &'a S);
+
+impl<'a, S: StateDB + Sync> StateProvider for StateDbProvider<'a, S> {
+    fn account(&self, _state_root: H256, address: &Address) -> Result<Option<Account>> {
+        Ok(self.0.get_account(address))
+    }
+
+    fn storage(&self, _state_root: H256, address: &Address, slot: &H256) -> Result<H256> {
+        Ok(self.0.get_storage(address, slot))
+    }
+}
+
+/// Reads committed state straight from the state trie
+///
+/// Accounts are keyed by the hash of their address, storage slots by the
+/// hash of the slot in the account's storage trie, and code is looked up by
+/// its hash. Any state root whose nodes are still in the database can be
+/// read, so this serves historical blocks as well as the head.
+pub struct TrieStateProvider<D: Database> {
+    db: Arc<D>,
+}
+
+impl<D: Database> TrieStateProvider<D> {
+    pub fn new(db: Arc<D>) -> Self {
+        Self { db }
+    }
+
+    /// Trie-level account record at `state_root`
+    pub fn state_account(&self, state_root: H256, address: &Address) -> Result<Option<StateAccount>> {
+        let view = match self.open(state_root)? {
+            Some(view) => view,
+            None => return Ok(None),
+        };
+
+        view.get(keccak256(address.as_bytes()).as_bytes())
+            .map_err(trie_error)?
+            .map(|data| StateAccount::decode(&data))
+            .transpose()
+    }
+
+    /// Word stored in `slot` of `address` at `state_root`, zero when the
+    /// account or the slot does not exist
+    pub fn storage_at(&self, state_root: H256, address: &Address, slot: &H256) -> Result<H256> {
+        let storage_root = match self.state_account(state_root, address)? {
+            Some(account) => account.storage_root,
+            None => return Ok(H256::zero()),
+        };
+        let view = match self.open(storage_root)? {
+            Some(view) => view,
+            None => return Ok(H256::zero()),
+        };
+
+        match view.get(keccak256(slot.as_bytes()).as_bytes()).map_err(trie_error)? {
+            Some(value) => decode_word(&value),
+            None => Ok(H256::zero()),
+        }
+    }
+
+    /// Up to `max_results` slots of `address` at `state_root` keyed by
+    /// hashed slot, in trie order from `start` on
+    pub fn storage_range_at(&self, state_root: H256, address: &Address, start: &H256, max_results: usize) -> Result<Vec<(H256, H256)>> {
+        let storage_root = match self.state_account(state_root, address)? {
+            Some(account) => account.storage_root,
+            None => return Ok(Vec::new()),
+        };
+        let view = match self.open(storage_root)? {
+            Some(view) if max_results > 0 => view,
+            _ => return Ok(Vec::new()),
+        };
+
+        // An entry is a 32 byte key and at most 33 bytes of value, so this
+        // budget can't run out before `max_results` entries
+        let byte_limit = max_results.saturating_mul(32 + 33);
+        view.range(start.as_bytes(), &[0xff; 32], byte_limit)
+            .map_err(trie_error)?
+            .into_iter()
+            .take(max_results)
+            .map(|(key, value)| Ok((H256::from_slice(&key), decode_word(&value)?)))
+            .collect()
+    }
+
+    fn code(&self, code_hash: &H256) -> Result<Vec<u8>> {
+        if *code_hash == keccak256(&[]) {
+            return Ok(Vec::new());
+        }
+
+        let key = format!("code:{}", hex::encode(code_hash.as_bytes()));
+        self.db.get(key.as_bytes())
+            .map_err(|e| StateError::Unavailable(e.to_string()))?
+            .ok_or_else(|| StateError::Unavailable(format!("missing code {:?}", code_hash)))
+    }
+
+    fn open(&self, root: H256) -> Result<Option<TrieReadView<D>>> {
+        if root == empty_root() {
+            return Ok(None);
+        }
+
+        match TrieReadView::at_root(self.db.clone(), root) {
+            Ok(view) => Ok(Some(view)),
+            Err(TrieError::KeyNotFound) => Err(StateError::Unavailable(format!(
+                "state {:?} is not available", root
+            ))),
+            Err(e) => Err(trie_error(e)),
+        }
+    }
+}
+
+impl<D: Database> StateProvider for TrieStateProvider<D> {
+    fn account(&self, state_root: H256, address: &Address) -> Result<Option<Account>> {
+        let account = match self.state_account(state_root, address)? {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Account {
+            balance: account.balance,
+            nonce: account.nonce,
+            code: self.code(&account.code_hash)?,
+            // Slots are read on demand through `storage`
+            storage: Default::default(),
+        }))
+    }
+
+    fn storage(&self, state_root: H256, address: &Address, slot: &H256) -> Result<H256> {
+        self.storage_at(state_root, address, slot)
+    }
+
+    fn storage_range(&self, state_root: H256, address: &Address, start: &H256, max_results: usize) -> Result<Vec<(H256, H256)>> {
+        self.storage_range_at(state_root, address, start, max_results)
+    }
+
+    fn preimage(&self, hash: &H256) -> Result<Option<H256>> {
+        match self.db.get(preimage_key(hash).as_bytes()) {
+            Ok(Some(slot)) if slot.len() == 32 => Ok(Some(H256::from_slice(&slot))),
+            Ok(Some(_)) => Err(StateError::Unavailable(format!("corrupt preimage of {:?}", hash))),
+            Ok(None) => Ok(None),
+            Err(e) => Err(StateError::Unavailable(e.to_string())),
+        }
+    }
+}
+
+/// Word held by an RLP-encoded storage trie value
+fn decode_word(value: &[u8]) -> Result<H256> {
+    let bytes = Decoder::new(value)
+        .and_then(|mut decoder| decoder.decode_bytes())
+        .map_err(|e| StateError::Unavailable(format!("invalid storage encoding: {}", e)))?;
+    if bytes.len() > 32 {
+        return Err(StateError::Unavailable("invalid storage encoding".to_string()));
+    }
+
+    let mut word = [0u8; 32];
+    word[32 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(H256::from(word))
+}
+
+fn trie_error(e: TrieError) -> StateError {
+    StateError::Unavailable(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_rlp::Encoder;
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_trie::PatriciaTrie;
+    use ethereum_types::U256;
+
+    #[test]
+    fn test_read_account_storage_and_code() {
+        let db = Arc::new(MemoryDatabase::new());
+        let address = Address::from_bytes([0xaa; 20]);
+        let code = vec![0x60, 0x00, 0x54];
+
+        let mut storage = PatriciaTrie::new(db.clone());
+        let mut value = Encoder::new();
+        value.encode_bytes(&[0x2a]);
+        storage.insert(keccak256(H256::from_low_u64_be(1).as_bytes()).as_bytes(), value.finish()).unwrap();
+        let storage_root = storage.commit().unwrap();
+
+        let code_hash = keccak256(&code);
+        db.put(format!("code:{}", hex::encode(code_hash.as_bytes())).as_bytes(), &code).unwrap();
+
+        let account = StateAccount { nonce: 3, balance: U256::from(1000), storage_root, code_hash };
+        assert_eq!(StateAccount::decode(&account.encode()).unwrap(), account);
+
+        let mut state = PatriciaTrie::new(db.clone());
+        state.insert(keccak256(address.as_bytes()).as_bytes(), account.encode()).unwrap();
+        let state_root = state.commit().unwrap();
+
+        let provider = TrieStateProvider::new(db);
+        let read = provider.account(state_root, &address).unwrap().unwrap();
+        assert_eq!(read.balance, U256::from(1000));
+        assert_eq!(read.nonce, 3);
+        assert_eq!(read.code, code);
+
+        assert_eq!(provider.storage(state_root, &address, &H256::from_low_u64_be(1)).unwrap(), H256::from_low_u64_be(42));
+        assert_eq!(provider.storage(state_root, &address, &H256::from_low_u64_be(2)).unwrap(), H256::zero());
+
+        // Absent accounts read as empty, unknown roots are an error
+        let absent = Address::from_bytes([0xbb; 20]);
+        assert!(provider.account(state_root, &absent).unwrap().is_none());
+        assert!(provider.account(H256::repeat_byte(0x11), &address).is_err());
+    }
+}
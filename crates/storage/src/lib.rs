@@ -4,10 +4,12 @@ use ethereum_types::{H256, U256};
 pub mod traits;
 pub mod memory;
 pub mod rocksdb;
+pub mod schema;
 
 pub use traits::*;
 pub use memory::*;
 pub use rocksdb::*;
+pub use schema::{check_schema_version, migrate, CURRENT_SCHEMA_VERSION};
 
 #[derive(Debug, Error)]
 pub enum StorageError {
@@ -25,6 +27,12 @@ pub enum StorageError {
     
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+    
+    #[error("Database schema version {found} is not supported (expected {supported}), migrate or resync the datadir")]
+    SchemaVersionMismatch { found: u32, supported: u32 },
+    
+    #[error("Migration failed: {0}")]
+    MigrationFailed(String),
 }
 
 pub type Result<T> = std::result::Result<T, StorageError>;
@@ -10,7 +10,16 @@ pub struct RocksDatabase {
 }
 
 impl RocksDatabase {
+    /// Open the database, stamping a fresh one with the current schema
+    /// version and rejecting one written with a different key layout
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = Self::open_unchecked(path)?;
+        crate::schema::check_schema_version(&db)?;
+        Ok(db)
+    }
+    
+    /// Open without checking the schema version, for running migrations
+    pub fn open_unchecked<P: AsRef<Path>>(path: P) -> Result<Self> {
         let mut opts = Options::default();
         opts.create_if_missing(true);
         opts.set_compression_type(rocksdb::DBCompressionType::Lz4);
@@ -256,7 +265,8 @@ mod tests {
     #[test]
     fn test_iterator() {
         let temp_dir = TempDir::new().unwrap();
-        let db = RocksDatabase::open(temp_dir.path()).unwrap();
+        // Unchecked so the schema version key does not show up in the iteration
+        let db = RocksDatabase::open_unchecked(temp_dir.path()).unwrap();
         
         // Insert test data
         for i in 0..10 {
@@ -0,0 +1,123 @@
+use crate::{Database, Result, StorageError};
+
+/// Key the on-disk schema version is stored under
+pub const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// Key layout version written by this build
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+/// Read the stored schema version, `None` for a database that was never stamped
+pub fn read_schema_version(db: &dyn Database) -> Result<Option<u32>> {
+    match db.get(SCHEMA_VERSION_KEY)? {
+        Some(bytes) => {
+            let bytes: [u8; 4] = bytes.as_slice().try_into().map_err(|_| {
+                StorageError::InvalidData(format!("schema version must be 4 bytes, got {}", bytes.len()))
+            })?;
+            Ok(Some(u32::from_be_bytes(bytes)))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn write_schema_version(db: &dyn Database, version: u32) -> Result<()> {
+    db.put(SCHEMA_VERSION_KEY, &version.to_be_bytes())
+}
+
+/// Check the database was written with the key layout this build understands
+///
+/// A fresh database is stamped with `CURRENT_SCHEMA_VERSION`. Any other
+/// version is rejected so an old or newer datadir is never read with the
+/// wrong layout.
+pub fn check_schema_version(db: &dyn Database) -> Result<u32> {
+    match read_schema_version(db)? {
+        None => {
+            write_schema_version(db, CURRENT_SCHEMA_VERSION)?;
+            Ok(CURRENT_SCHEMA_VERSION)
+        }
+        Some(version) if version == CURRENT_SCHEMA_VERSION => Ok(version),
+        Some(version) => Err(StorageError::SchemaVersionMismatch {
+            found: version,
+            supported: CURRENT_SCHEMA_VERSION,
+        }),
+    }
+}
+
+/// Upgrade the key layout from schema version `from` to `to`
+///
+/// Each step rewrites the data of one version into the next and bumps the
+/// stored version, so an interrupted migration resumes from the last
+/// completed step.
+pub fn migrate(db: &dyn Database, from: u32, to: u32) -> Result<()> {
+    if from > to {
+        return Err(StorageError::MigrationFailed(format!(
+            "cannot downgrade schema from version {} to {}",
+            from, to
+        )));
+    }
+
+    for version in from..to {
+        migrate_step(db, version)?;
+        write_schema_version(db, version + 1)?;
+    }
+
+    Ok(())
+}
+
+fn migrate_step(_db: &dyn Database, from: u32) -> Result<()> {
+    // No layout changes yet, new migrations are added here as `from => ...`
+    Err(StorageError::MigrationFailed(format!(
+        "no migration from schema version {} to {}",
+        from,
+        from + 1
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryDatabase, RocksDatabase};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_uninitialized_db_is_stamped() {
+        let db = MemoryDatabase::new();
+        assert_eq!(read_schema_version(&db).unwrap(), None);
+
+        assert_eq!(check_schema_version(&db).unwrap(), CURRENT_SCHEMA_VERSION);
+        assert_eq!(read_schema_version(&db).unwrap(), Some(CURRENT_SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn test_matching_version_opens() {
+        let temp_dir = TempDir::new().unwrap();
+        drop(RocksDatabase::open(temp_dir.path()).unwrap());
+
+        let db = RocksDatabase::open(temp_dir.path()).unwrap();
+        assert_eq!(read_schema_version(&db).unwrap(), Some(CURRENT_SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        {
+            let db = RocksDatabase::open_unchecked(temp_dir.path()).unwrap();
+            write_schema_version(&db, CURRENT_SCHEMA_VERSION + 1).unwrap();
+        }
+
+        match RocksDatabase::open(temp_dir.path()) {
+            Err(StorageError::SchemaVersionMismatch { found, supported }) => {
+                assert_eq!(found, CURRENT_SCHEMA_VERSION + 1);
+                assert_eq!(supported, CURRENT_SCHEMA_VERSION);
+            }
+            other => panic!("expected schema version mismatch, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_migrate_without_steps() {
+        let db = MemoryDatabase::new();
+        migrate(&db, CURRENT_SCHEMA_VERSION, CURRENT_SCHEMA_VERSION).unwrap();
+        assert!(migrate(&db, CURRENT_SCHEMA_VERSION, CURRENT_SCHEMA_VERSION + 1).is_err());
+        assert!(migrate(&db, CURRENT_SCHEMA_VERSION + 1, CURRENT_SCHEMA_VERSION).is_err());
+    }
+}
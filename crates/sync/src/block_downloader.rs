@@ -1,3 +1,4 @@
+use async_trait::async_trait;
 use ethereum_types::{H256, U256};
 use ethereum_core::{Block, Header};
 use ethereum_storage::Database;
@@ -8,6 +9,17 @@ use parking_lot::RwLock;
 
 use crate::{Result, SyncError, SyncConfig};
 
+/// Where the full sync loop gets its blocks from
+#[async_trait]
+pub trait BatchSource: Send + Sync {
+    /// Next blocks after the local head in ascending order, empty once synced
+    async fn next_batch(&self) -> Result<Vec<Block>>;
+
+    /// Abandon the requests in flight so the next batch is asked of
+    /// whichever peers are available then
+    fn restart(&self);
+}
+
 pub struct BlockDownloader<D: Database> {
     db: Arc<D>,
     peer_manager: Arc<PeerManager>,
@@ -205,6 +217,18 @@ impl<D: Database + 'static> BlockDownloader<D> {
     }
 }
 
+#[async_trait]
+impl<D: Database + 'static> BatchSource for BlockDownloader<D> {
+    async fn next_batch(&self) -> Result<Vec<Block>> {
+        self.download_next_batch().await
+    }
+
+    fn restart(&self) {
+        self.download_queue.write().clear();
+        self.downloading.write().clear();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DownloadStats {
     pub queued: usize,
@@ -0,0 +1,335 @@
+use async_trait::async_trait;
+use ethereum_types::{H256, U256};
+use ethereum_core::{Block, Header};
+use ethereum_storage::Database;
+use ethereum_trie::{TrieError, TrieReadView};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::{ReorgHandler, Result, SyncConfig, SyncError, SyncEvent};
+
+/// Chain data a checkpoint sync pulls from the network
+#[async_trait]
+pub trait CheckpointProvider: Send + Sync {
+    /// Header with the given hash, `None` if no peer serves it
+    async fn header(&self, hash: H256) -> Result<Option<Header>>;
+
+    /// Download the full state under `header.state_root` into the local
+    /// database, normally over snap
+    async fn sync_state(&self, header: &Header) -> Result<()>;
+
+    /// Up to `limit` canonical blocks following block `number`, empty once
+    /// the tip is reached
+    async fn blocks_after(&self, number: U256, limit: usize) -> Result<Vec<Block>>;
+}
+
+/// How a checkpoint sync finished
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointOutcome {
+    /// Local block the checkpoint was linked to, `None` if it was trusted
+    /// without a known ancestor
+    pub ancestor: Option<H256>,
+    /// Head after syncing forward from the checkpoint
+    pub head: H256,
+}
+
+/// Weak subjectivity sync: start from a trusted block hash instead of
+/// genesis
+///
+/// The header chain is walked backward from the checkpoint until it links
+/// to a block the node already has. If no such block is found within
+/// `max_backfill` headers the checkpoint is trusted as configured. State is
+/// then downloaded at the checkpoint and blocks after it are imported one by
+/// one as in full sync.
+pub struct CheckpointSync<D: Database> {
+    db: Arc<D>,
+    provider: Arc<dyn CheckpointProvider>,
+    reorg: ReorgHandler<D>,
+    events_tx: mpsc::UnboundedSender<SyncEvent>,
+    batch_size: usize,
+    max_backfill: u64,
+}
+
+impl<D: Database + 'static> CheckpointSync<D> {
+    pub fn new(
+        db: Arc<D>,
+        provider: Arc<dyn CheckpointProvider>,
+        config: &SyncConfig,
+        events_tx: mpsc::UnboundedSender<SyncEvent>,
+    ) -> Self {
+        let reorg = ReorgHandler::new(db.clone(), events_tx.clone());
+
+        Self {
+            db,
+            provider,
+            reorg,
+            events_tx,
+            batch_size: config.max_block_request,
+            max_backfill: config.checkpoint_backfill,
+        }
+    }
+
+    pub async fn run(
+        &self,
+        checkpoint: H256,
+        cancel_rx: &mut mpsc::Receiver<()>,
+    ) -> Result<CheckpointOutcome> {
+        let header = self.fetch_header(checkpoint).await?
+            .ok_or_else(|| SyncError::NetworkError(format!("Checkpoint {:?} not available", checkpoint)))?;
+
+        let ancestor = self.verify_ancestry(&header).await?;
+        if ancestor.is_none() {
+            tracing::warn!(
+                "Checkpoint {:?} at #{} does not link to a known block, trusting it as configured",
+                checkpoint,
+                header.number
+            );
+        }
+
+        self.download_state(&header).await?;
+        self.reorg.anchor(&header)?;
+        self.events_tx.send(SyncEvent::BlockImported(checkpoint)).ok();
+
+        let head = self.sync_forward(header, cancel_rx).await?;
+
+        Ok(CheckpointOutcome { ancestor, head })
+    }
+
+    /// Walk parent hashes back from `checkpoint` to a block in the local
+    /// canonical chain
+    ///
+    /// Returns the block linked to, or `None` if the node has no chain yet or
+    /// none was reached within the backfill limit. A header that fails to
+    /// link to its child is an error.
+    pub async fn verify_ancestry(&self, checkpoint: &Header) -> Result<Option<H256>> {
+        if self.reorg.head()?.is_none() {
+            return Ok(None);
+        }
+
+        let mut cursor = checkpoint.clone();
+        for _ in 0..=self.max_backfill {
+            let hash = cursor.hash();
+            if self.reorg.canonical_hash(cursor.number)? == Some(hash) {
+                return Ok(Some(hash));
+            }
+            if cursor.is_genesis() {
+                return Err(SyncError::InvalidBlock(format!(
+                    "Checkpoint {:?} descends from a different genesis", checkpoint.hash()
+                )));
+            }
+
+            let parent = self.fetch_header(cursor.parent_hash).await?
+                .ok_or_else(|| SyncError::NetworkError(format!("Header {:?} not available", cursor.parent_hash)))?;
+            if parent.number + U256::one() != cursor.number {
+                return Err(SyncError::InvalidBlock(format!(
+                    "Header {:?} is not the parent of block #{}", cursor.parent_hash, cursor.number
+                )));
+            }
+            cursor = parent;
+        }
+
+        Ok(None)
+    }
+
+    /// Header by hash, checked against the hash it was requested by
+    async fn fetch_header(&self, hash: H256) -> Result<Option<Header>> {
+        match self.provider.header(hash).await? {
+            Some(header) if header.hash() != hash => Err(SyncError::InvalidBlock(format!(
+                "Peer returned header {:?} for {:?}", header.hash(), hash
+            ))),
+            header => Ok(header),
+        }
+    }
+
+    async fn download_state(&self, header: &Header) -> Result<()> {
+        self.provider.sync_state(header).await?;
+
+        // The state root must resolve locally before the checkpoint is usable
+        match TrieReadView::at_root(self.db.clone(), header.state_root) {
+            Ok(_) => {}
+            Err(TrieError::KeyNotFound) => {
+                return Err(SyncError::InvalidState(format!(
+                    "State {:?} missing after download", header.state_root
+                )));
+            }
+            Err(e) => return Err(SyncError::InvalidState(e.to_string())),
+        }
+
+        self.events_tx.send(SyncEvent::StateImported(header.state_root)).ok();
+        Ok(())
+    }
+
+    /// Import blocks after `from` until the provider runs out, returning
+    /// the new head
+    async fn sync_forward(&self, from: Header, cancel_rx: &mut mpsc::Receiver<()>) -> Result<H256> {
+        let mut head = from;
+
+        loop {
+            if cancel_rx.try_recv().is_ok() {
+                return Err(SyncError::Cancelled);
+            }
+
+            let blocks = self.provider.blocks_after(head.number, self.batch_size).await?;
+            if blocks.is_empty() {
+                break;
+            }
+
+            for block in blocks {
+                if block.header.parent_hash != head.hash() {
+                    return Err(SyncError::InvalidBlock(format!(
+                        "Block #{} does not extend {:?}", block.header.number, head.hash()
+                    )));
+                }
+
+                let hash = block.header.hash();
+                self.reorg.insert_block(&block)?;
+                self.reorg.handle_new_head(&block.header)?;
+                self.events_tx.send(SyncEvent::BlockImported(hash)).ok();
+
+                head = block.header;
+            }
+        }
+
+        Ok(head.hash())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_trie::PatriciaTrie;
+    use std::collections::HashMap;
+
+    const STATE: &[(&[u8], &[u8])] = &[(b"alice", b"100"), (b"bob", b"200")];
+
+    /// Serves a fixed chain and writes the checkpoint state on request
+    struct MockProvider {
+        db: Arc<MemoryDatabase>,
+        chain: Vec<Block>,
+        headers: HashMap<H256, Header>,
+    }
+
+    impl MockProvider {
+        fn new(db: Arc<MemoryDatabase>, chain: Vec<Block>) -> Self {
+            let headers = chain.iter().map(|block| (block.header.hash(), block.header.clone())).collect();
+            Self { db, chain, headers }
+        }
+    }
+
+    #[async_trait]
+    impl CheckpointProvider for MockProvider {
+        async fn header(&self, hash: H256) -> Result<Option<Header>> {
+            Ok(self.headers.get(&hash).cloned())
+        }
+
+        async fn sync_state(&self, header: &Header) -> Result<()> {
+            assert_eq!(write_state(self.db.clone()), header.state_root);
+            Ok(())
+        }
+
+        async fn blocks_after(&self, number: U256, limit: usize) -> Result<Vec<Block>> {
+            Ok(self.chain.iter().filter(|block| block.header.number > number).take(limit).cloned().collect())
+        }
+    }
+
+    fn write_state(db: Arc<MemoryDatabase>) -> H256 {
+        let mut trie = PatriciaTrie::new(db);
+        for (key, value) in STATE {
+            trie.insert(key, value.to_vec()).unwrap();
+        }
+        trie.commit().unwrap()
+    }
+
+    /// Chain of `length` blocks after genesis, every header carrying the
+    /// test state root
+    fn make_chain(length: u64) -> Vec<Block> {
+        let state_root = write_state(Arc::new(MemoryDatabase::new()));
+
+        let mut genesis = Header::new();
+        genesis.difficulty = U256::one();
+        genesis.state_root = state_root;
+
+        let mut chain = vec![Block::new(genesis)];
+        for _ in 0..length {
+            let parent = &chain.last().unwrap().header;
+            let mut header = Header::new();
+            header.parent_hash = parent.hash();
+            header.number = parent.number + U256::one();
+            header.difficulty = U256::one();
+            header.state_root = state_root;
+            chain.push(Block::new(header));
+        }
+        chain
+    }
+
+    fn config() -> SyncConfig {
+        SyncConfig { max_block_request: 2, ..SyncConfig::with_checkpoint(H256::zero()) }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_sync_reaches_checkpoint_then_advances() {
+        let db = Arc::new(MemoryDatabase::new());
+        let chain = make_chain(10);
+        let checkpoint = chain[6].header.hash();
+
+        // The node only knows genesis
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let reorg = ReorgHandler::new(db.clone(), events_tx.clone());
+        reorg.insert_block(&chain[0]).unwrap();
+        reorg.handle_new_head(&chain[0].header).unwrap();
+        while events_rx.try_recv().is_ok() {}
+
+        let provider = Arc::new(MockProvider::new(db.clone(), chain.clone()));
+        let sync = CheckpointSync::new(db.clone(), provider, &config(), events_tx);
+        let (_cancel_tx, mut cancel_rx) = mpsc::channel(1);
+
+        let outcome = sync.run(checkpoint, &mut cancel_rx).await.unwrap();
+        assert_eq!(outcome.ancestor, Some(chain[0].header.hash()));
+        assert_eq!(outcome.head, chain[10].header.hash());
+
+        // State arrives first, then the head moves to the checkpoint and on
+        let mut imported = Vec::new();
+        let mut state_imported = false;
+        while let Ok(event) = events_rx.try_recv() {
+            match event {
+                SyncEvent::StateImported(root) => {
+                    assert!(imported.is_empty());
+                    assert_eq!(root, chain[6].header.state_root);
+                    state_imported = true;
+                }
+                SyncEvent::BlockImported(hash) => imported.push(hash),
+                _ => {}
+            }
+        }
+        assert!(state_imported);
+        let expected: Vec<H256> = chain[6..].iter().map(|block| block.header.hash()).collect();
+        assert_eq!(imported, expected);
+
+        assert_eq!(reorg.head().unwrap(), Some(chain[10].header.hash()));
+        assert_eq!(reorg.canonical_hash(U256::from(6)).unwrap(), Some(checkpoint));
+        assert_eq!(reorg.canonical_hash(U256::from(8)).unwrap(), Some(chain[8].header.hash()));
+        // Blocks between genesis and the checkpoint are skipped
+        assert_eq!(reorg.canonical_hash(U256::from(3)).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_trusted_without_local_chain() {
+        let db = Arc::new(MemoryDatabase::new());
+        let chain = make_chain(5);
+        let checkpoint = chain[3].header.hash();
+
+        let (events_tx, _events_rx) = mpsc::unbounded_channel();
+        let provider = Arc::new(MockProvider::new(db.clone(), chain.clone()));
+        let sync = CheckpointSync::new(db.clone(), provider, &config(), events_tx.clone());
+        let (_cancel_tx, mut cancel_rx) = mpsc::channel(1);
+
+        let outcome = sync.run(checkpoint, &mut cancel_rx).await.unwrap();
+        assert_eq!(outcome.ancestor, None);
+        assert_eq!(outcome.head, chain[5].header.hash());
+
+        // A checkpoint that isn't served at all is an error
+        let sync = CheckpointSync::new(db.clone(), Arc::new(MockProvider::new(db, Vec::new())), &config(), events_tx);
+        assert!(sync.run(H256::repeat_byte(0x11), &mut cancel_rx).await.is_err());
+    }
+}
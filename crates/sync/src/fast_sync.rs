@@ -1,20 +1,30 @@
+use async_trait::async_trait;
 use ethereum_types::{H256, U256};
-use ethereum_core::{Block, Header};
+use ethereum_core::{Block, Header, Receipt};
 use ethereum_storage::Database;
 use ethereum_network::peer::PeerManager;
-use ethereum_trie::PatriciaTrie;
+use ethereum_trie::{ordered_trie_root, PatriciaTrie};
 use std::sync::Arc;
 use std::collections::{HashMap, VecDeque};
 use tokio::sync::mpsc;
 
 use crate::{Result, SyncError, SyncConfig};
 
+/// Source of block receipts, normally peers answering GetReceipts
+#[async_trait]
+pub trait ReceiptProvider: Send + Sync {
+    /// Receipts of the requested blocks, in request order; peers may stop
+    /// short of the full request
+    async fn receipts(&self, blocks: &[H256]) -> Result<Vec<Vec<Receipt>>>;
+}
+
 pub struct FastSync<D: Database> {
     db: Arc<D>,
     peer_manager: Arc<PeerManager>,
     config: SyncConfig,
     headers: VecDeque<Header>,
     pivot_block: Option<U256>,
+    receipt_provider: Option<Arc<dyn ReceiptProvider>>,
 }
 
 impl<D: Database + 'static> FastSync<D> {
@@ -29,8 +39,16 @@ impl<D: Database + 'static> FastSync<D> {
             config,
             headers: VecDeque::new(),
             pivot_block: None,
+            receipt_provider: None,
         }
     }
+
+    /// Source of receipts for downloaded blocks, `download_blocks` fails
+    /// without one
+    pub fn with_receipt_provider(mut self, provider: Arc<dyn ReceiptProvider>) -> Self {
+        self.receipt_provider = Some(provider);
+        self
+    }
     
     pub async fn download_headers(
         &self,
@@ -140,11 +158,9 @@ impl<D: Database + 'static> FastSync<D> {
     ) -> Result<()> {
         tracing::info!("Downloading block bodies and receipts");
         
-        // Download block bodies and receipts in parallel
         let mut block_bodies = HashMap::new();
-        let mut receipts = HashMap::new();
         
-        // In real implementation, would send GetBlockBodies and GetReceipts
+        // In real implementation, would send GetBlockBodies
         
         for header in &self.headers {
             tokio::select! {
@@ -152,9 +168,8 @@ impl<D: Database + 'static> FastSync<D> {
                     return Err(SyncError::Cancelled);
                 }
                 _ = tokio::time::sleep(tokio::time::Duration::from_millis(10)) => {
-                    // Simulate downloading body and receipts
+                    // Simulate downloading body
                     block_bodies.insert(header.hash(), vec![]);
-                    receipts.insert(header.hash(), vec![]);
                 }
             }
         }
@@ -166,17 +181,74 @@ impl<D: Database + 'static> FastSync<D> {
             if let Some(body) = block_bodies.get(&hash) {
                 self.store_block(header.clone(), body.clone()).await?;
             }
-            
-            if let Some(receipt_list) = receipts.get(&hash) {
-                self.store_receipts(&hash, receipt_list.clone()).await?;
-            }
         }
         
+        let headers: Vec<Header> = self.headers.iter().cloned().collect();
+        self.download_receipts(&headers, cancel_rx).await?;
+        
         tracing::info!("Block download completed");
         
         Ok(())
     }
     
+    /// Download and store the receipts of `headers`, `max_receipt_request`
+    /// blocks per request
+    ///
+    /// Each block's receipts must hash to its header's receipts root. A
+    /// response with a mismatching block is rejected as a whole, nothing
+    /// from it is stored.
+    pub async fn download_receipts(
+        &self,
+        headers: &[Header],
+        cancel_rx: &mut mpsc::Receiver<()>,
+    ) -> Result<usize> {
+        let provider = self.receipt_provider.clone().ok_or_else(no_receipt_provider)?;
+        let batch_size = self.config.max_receipt_request.max(1);
+        let mut pending: VecDeque<&Header> = headers.iter().collect();
+        let mut stored = 0;
+        
+        while !pending.is_empty() {
+            if cancel_rx.try_recv().is_ok() {
+                return Err(SyncError::Cancelled);
+            }
+            
+            let batch: Vec<&Header> = pending.iter().take(batch_size).copied().collect();
+            let hashes: Vec<H256> = batch.iter().map(|header| header.hash()).collect();
+            
+            let response = provider.receipts(&hashes).await?;
+            if response.is_empty() {
+                return Err(SyncError::NetworkError(format!(
+                    "No receipts served for block #{}", batch[0].number
+                )));
+            }
+            if response.len() > batch.len() {
+                return Err(SyncError::InvalidBlock(format!(
+                    "Peer served {} receipt lists for {} blocks", response.len(), batch.len()
+                )));
+            }
+            
+            for (header, receipts) in batch.iter().zip(&response) {
+                let root = ordered_trie_root(receipts.iter().map(|receipt| receipt.encoded_2718()));
+                if root != header.receipts_root {
+                    return Err(SyncError::InvalidBlock(format!(
+                        "Receipts root mismatch for block #{}: expected {:?}, got {:?}",
+                        header.number, header.receipts_root, root
+                    )));
+                }
+            }
+            
+            for (hash, receipts) in hashes.iter().zip(&response) {
+                self.store_receipts(hash, receipts).await?;
+                pending.pop_front();
+                stored += 1;
+            }
+        }
+        
+        tracing::info!("Downloaded receipts for {} blocks", stored);
+        
+        Ok(stored)
+    }
+    
     async fn download_accounts(
         &self,
         pivot_header: &Header,
@@ -223,10 +295,124 @@ impl<D: Database + 'static> FastSync<D> {
         Ok(())
     }
     
-    async fn store_receipts(&self, block_hash: &H256, receipts: Vec<u8>) -> Result<()> {
+    async fn store_receipts(&self, block_hash: &H256, receipts: &[Receipt]) -> Result<()> {
         let key = format!("receipts:{}", hex::encode(block_hash));
-        self.db.put(key.as_bytes(), &receipts)?;
+        let encoded = bincode::serialize(receipts)
+            .map_err(|e| SyncError::InvalidState(e.to_string()))?;
+        self.db.put(key.as_bytes(), &encoded)?;
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Fast sync can't complete without receipts, so a missing provider is an
+/// error rather than blocks stored without them
+pub(crate) fn no_receipt_provider() -> SyncError {
+    SyncError::NetworkError("Fast sync needs a receipt provider, none is installed".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReorgHandler;
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_types::Bloom;
+    use parking_lot::Mutex;
+
+    /// Serves fixed receipts per block and records every request
+    struct MockPeer {
+        receipts: HashMap<H256, Vec<Receipt>>,
+        requests: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl ReceiptProvider for MockPeer {
+        async fn receipts(&self, blocks: &[H256]) -> Result<Vec<Vec<Receipt>>> {
+            self.requests.lock().push(blocks.len());
+            Ok(blocks.iter().map_while(|hash| self.receipts.get(hash).cloned()).collect())
+        }
+    }
+
+    fn make_receipt(gas: u64) -> Receipt {
+        Receipt {
+            tx_type: 2,
+            status: 1,
+            cumulative_gas_used: U256::from(gas),
+            logs_bloom: Bloom::default(),
+            logs: vec![],
+            gas_used: U256::from(gas),
+            contract_address: None,
+        }
+    }
+
+    /// `count` headers committing to one receipt each, with the receipts
+    /// a peer serves for them
+    fn make_blocks(count: u64) -> (Vec<Header>, HashMap<H256, Vec<Receipt>>) {
+        let mut headers = Vec::new();
+        let mut receipts = HashMap::new();
+        for number in 0..count {
+            let block_receipts = vec![make_receipt(21_000 + number)];
+            let mut header = Header::new();
+            header.number = U256::from(number);
+            header.receipts_root = ordered_trie_root(block_receipts.iter().map(|receipt| receipt.encoded_2718()));
+            receipts.insert(header.hash(), block_receipts);
+            headers.push(header);
+        }
+        (headers, receipts)
+    }
+
+    fn fast_sync(db: Arc<MemoryDatabase>, peer: Arc<MockPeer>) -> FastSync<MemoryDatabase> {
+        let config = SyncConfig { max_receipt_request: 2, ..SyncConfig::default() };
+        FastSync::new(db, Arc::new(PeerManager::new(1)), config).with_receipt_provider(peer)
+    }
+
+    #[tokio::test]
+    async fn test_download_receipts_in_batches() {
+        let db = Arc::new(MemoryDatabase::new());
+        let (headers, receipts) = make_blocks(5);
+        let peer = Arc::new(MockPeer { receipts: receipts.clone(), requests: Mutex::new(vec![]) });
+        let sync = fast_sync(db.clone(), peer.clone());
+        let (_cancel_tx, mut cancel_rx) = mpsc::channel(1);
+
+        assert_eq!(sync.download_receipts(&headers, &mut cancel_rx).await.unwrap(), 5);
+        assert_eq!(*peer.requests.lock(), vec![2, 2, 1]);
+
+        let (events_tx, _events_rx) = mpsc::unbounded_channel();
+        let reorg = ReorgHandler::new(db, events_tx);
+        for header in &headers {
+            let hash = header.hash();
+            assert_eq!(reorg.receipts(&hash).unwrap(), Some(receipts[&hash].clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_receipts_rejects_mismatched_root() {
+        let db = Arc::new(MemoryDatabase::new());
+        let (headers, mut receipts) = make_blocks(4);
+        // The peer serves the wrong receipts for block #3
+        receipts.insert(headers[3].hash(), vec![make_receipt(1)]);
+        let peer = Arc::new(MockPeer { receipts, requests: Mutex::new(vec![]) });
+        let sync = fast_sync(db.clone(), peer);
+        let (_cancel_tx, mut cancel_rx) = mpsc::channel(1);
+
+        let result = sync.download_receipts(&headers, &mut cancel_rx).await;
+        assert!(matches!(result, Err(SyncError::InvalidBlock(_))));
+
+        // The first batch was valid and kept, the bad one was dropped whole
+        let (events_tx, _events_rx) = mpsc::unbounded_channel();
+        let reorg = ReorgHandler::new(db, events_tx);
+        assert!(reorg.receipts(&headers[1].hash()).unwrap().is_some());
+        assert!(reorg.receipts(&headers[2].hash()).unwrap().is_none());
+        assert!(reorg.receipts(&headers[3].hash()).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_download_receipts_without_provider() {
+        let (headers, _) = make_blocks(1);
+        let sync = FastSync::new(Arc::new(MemoryDatabase::new()), Arc::new(PeerManager::new(1)), SyncConfig::default());
+        let (_cancel_tx, mut cancel_rx) = mpsc::channel(1);
+
+        let result = sync.download_receipts(&headers, &mut cancel_rx).await;
+        assert!(matches!(result, Err(SyncError::NetworkError(_))));
+    }
+}
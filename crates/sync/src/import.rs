@@ -0,0 +1,195 @@
+use async_trait::async_trait;
+use ethereum_core::{Block, Receipt};
+use ethereum_storage::{Database, WriteBatch};
+use ethereum_types::H256;
+use ethereum_verification::{is_rejection, VerificationEngine, VerificationError};
+
+use crate::{ReorgOutcome, Result, SyncError};
+
+/// Verifies and executes blocks ahead of their import
+#[async_trait]
+pub trait BlockProcessor: Send + Sync {
+    /// Verify `block` against its parent and execute it, staging the
+    /// post-state in `batch` and returning the receipts
+    ///
+    /// Rejected blocks are reported as `SyncError::InvalidBlock`, after
+    /// being recorded as bad blocks.
+    async fn process(&self, block: &Block, batch: &mut dyn WriteBatch) -> Result<Vec<Receipt>>;
+}
+
+#[async_trait]
+impl<D: Database + 'static> BlockProcessor for VerificationEngine<D> {
+    async fn process(&self, block: &Block, batch: &mut dyn WriteBatch) -> Result<Vec<Receipt>> {
+        let error = match self.execute_block(block, batch).await {
+            Ok(receipts) => return Ok(receipts),
+            Err(e) => e,
+        };
+        if is_rejection(&error) {
+            self.record_bad_block(block, error.to_string())
+                .map_err(|e| SyncError::InvalidState(e.to_string()))?;
+        }
+        Err(match error {
+            VerificationError::StorageError(e) => SyncError::StorageError(e),
+            e => SyncError::InvalidBlock(e.to_string()),
+        })
+    }
+}
+
+/// What importing a block of a segment did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockImportStatus {
+    /// Stored and made the head, possibly through a reorg
+    Canonical(ReorgOutcome),
+    /// Stored on a branch lighter than the current head
+    SideChain,
+    /// Already stored, nothing was written
+    Known,
+    /// Rejected, nothing was written for it
+    Invalid(String),
+    /// Not looked at because an earlier block of the segment was rejected
+    Skipped,
+}
+
+/// Result of `Synchronizer::import_blocks`, one status per block in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportOutcome {
+    pub blocks: Vec<(H256, BlockImportStatus)>,
+    /// Canonical head once the import finished
+    pub head: Option<H256>,
+}
+
+impl ImportOutcome {
+    /// Number of blocks written by the import
+    pub fn imported(&self) -> usize {
+        self.blocks
+            .iter()
+            .filter(|(_, status)| matches!(status, BlockImportStatus::Canonical(_) | BlockImportStatus::SideChain))
+            .count()
+    }
+
+    /// The first rejected block and why it was rejected
+    pub fn invalid(&self) -> Option<(H256, &str)> {
+        self.blocks.iter().find_map(|(hash, status)| match status {
+            BlockImportStatus::Invalid(reason) => Some((*hash, reason.as_str())),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_consensus::{BlockAssembler, BlockExecutor, Clique, Consensus, ConsensusConfig, EngineType};
+    use ethereum_core::Header;
+    use ethereum_crypto::{generate_private_key, public_key_to_address};
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_trie::empty_root;
+    use ethereum_types::U256;
+    use ethereum_verification::{compute_withdrawals_root, ExecutedBlock, StateExecutor, VerificationConfig};
+    use secp256k1::{PublicKey, Secp256k1};
+    use std::sync::Arc;
+    
+    /// Seals empty blocks without touching the state
+    struct EmptyBlockExecutor;
+    
+    impl BlockExecutor for EmptyBlockExecutor {
+        fn execute(&self, mut block: Block) -> ethereum_consensus::Result<Block> {
+            block.header.transactions_root = empty_root();
+            block.header.receipts_root = empty_root();
+            block.header.withdrawals_root = block.withdrawals.as_deref().map(compute_withdrawals_root);
+            Ok(block)
+        }
+    }
+    
+    /// Stages a marker node and reports `state_root` as the post-state
+    struct FixedRootExecutor {
+        state_root: H256,
+    }
+    
+    impl StateExecutor for FixedRootExecutor {
+        fn execute(&self, _block: &Block, _parent_state_root: H256, batch: &mut dyn WriteBatch) -> ethereum_verification::Result<ExecutedBlock> {
+            batch.put(b"node", b"post-state");
+            Ok(ExecutedBlock { receipts: Vec::new(), state_root: self.state_root })
+        }
+    }
+    
+    /// Empty block sealed by a single Clique signer on top of a stored parent
+    async fn produce_block(db: &Arc<MemoryDatabase>) -> (ConsensusConfig, Block) {
+        let signer_key = generate_private_key();
+        let signer = public_key_to_address(&PublicKey::from_secret_key(&Secp256k1::new(), &signer_key));
+        let config = ConsensusConfig {
+            engine_type: EngineType::Clique,
+            epoch_length: 30_000,
+            block_period: 5,
+            validators: vec![signer],
+            genesis_validators: vec![signer],
+        };
+        
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut parent = Header::new();
+        parent.number = U256::one();
+        parent.gas_limit = U256::from(30_000_000);
+        parent.gas_used = U256::from(15_000_000);
+        parent.base_fee_per_gas = Some(U256::from(1_000_000_000u64));
+        parent.timestamp = now - 60;
+        db.put(
+            format!("header:{}", hex::encode(parent.hash())).as_bytes(),
+            &bincode::serialize(&parent).unwrap(),
+        ).unwrap();
+        
+        let clique = Clique::new(config.clone())
+            .with_assembler(Arc::new(BlockAssembler::new(Arc::new(EmptyBlockExecutor))))
+            .with_signer_key(signer_key);
+        let consensus = Consensus::new(config.clone(), db.clone()).with_engine(Box::new(clique));
+        let block = consensus.produce_block(&parent, Vec::new(), signer).await.unwrap();
+        (config, block)
+    }
+    
+    #[tokio::test]
+    async fn test_verification_engine_processes_blocks() {
+        let db = Arc::new(MemoryDatabase::new());
+        let (config, block) = produce_block(&db).await;
+        
+        let executor = Arc::new(FixedRootExecutor { state_root: block.header.state_root });
+        let engine = VerificationEngine::new(db.clone(), config, VerificationConfig::default())
+            .with_executor(executor);
+        
+        let mut batch = db.batch();
+        let receipts = engine.process(&block, batch.as_mut()).await.unwrap();
+        assert!(receipts.is_empty());
+        // The post-state is staged, not written
+        assert_eq!(batch.len(), 1);
+        assert!(db.get(b"node").unwrap().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_verification_engine_rejects_wrong_state_root() {
+        let db = Arc::new(MemoryDatabase::new());
+        let (config, block) = produce_block(&db).await;
+        
+        let executor = Arc::new(FixedRootExecutor { state_root: H256::repeat_byte(0xab) });
+        let engine = VerificationEngine::new(db.clone(), config.clone(), VerificationConfig::default())
+            .with_executor(executor);
+        let mut batch = db.batch();
+        let result = engine.process(&block, batch.as_mut()).await;
+        assert!(matches!(result, Err(SyncError::InvalidBlock(_))));
+        
+        // The rejection is kept with its reason
+        let bad_blocks = ethereum_state::bad_blocks(&*db).unwrap();
+        assert_eq!(bad_blocks.len(), 1);
+        assert_eq!(bad_blocks[0].block.header.hash(), block.header.hash());
+        assert_eq!(bad_blocks[0].reason, VerificationError::StateRootMismatch.to_string());
+        
+        // Without an executor the post-state can't be checked at all, which
+        // says nothing about the block
+        let db = Arc::new(MemoryDatabase::new());
+        let (config, block) = produce_block(&db).await;
+        let engine = VerificationEngine::new(db.clone(), config, VerificationConfig::default());
+        let result = engine.process(&block, db.batch().as_mut()).await;
+        assert!(matches!(result, Err(SyncError::InvalidBlock(reason)) if reason.contains("No state executor")));
+        assert!(ethereum_state::bad_blocks(&*db).unwrap().is_empty());
+    }
+}
@@ -2,6 +2,7 @@ use ethereum_types::{H256, U256};
 use ethereum_core::{Block, Header};
 use ethereum_storage::Database;
 use ethereum_network::peer::{Peer, PeerManager};
+use ethereum_txpool::TransactionPool;
 use parking_lot::RwLock;
 use std::collections::{HashMap, VecDeque, HashSet};
 use std::sync::Arc;
@@ -14,11 +15,21 @@ pub mod fast_sync;
 pub mod snap_sync;
 pub mod state_sync;
 pub mod block_downloader;
+pub mod reorg;
+pub mod checkpoint_sync;
+pub mod stall;
+pub mod import;
+pub mod nonces;
 
-pub use fast_sync::FastSync;
+pub use fast_sync::{FastSync, ReceiptProvider};
 pub use snap_sync::SnapSync;
 pub use state_sync::StateSync;
-pub use block_downloader::BlockDownloader;
+pub use block_downloader::{BatchSource, BlockDownloader};
+pub use reorg::{ReorgHandler, ReorgOutcome};
+pub use checkpoint_sync::{CheckpointOutcome, CheckpointProvider, CheckpointSync};
+pub use stall::StallDetector;
+pub use import::{BlockImportStatus, BlockProcessor, ImportOutcome};
+pub use nonces::StateNonceProvider;
 
 #[derive(Debug, Error)]
 pub enum SyncError {
@@ -45,6 +56,9 @@ pub enum SyncError {
     
     #[error("Sync cancelled")]
     Cancelled,
+    
+    #[error("Sync stalled, no progress after {0} restarts")]
+    Stalled(usize),
 }
 
 pub type Result<T> = std::result::Result<T, SyncError>;
@@ -55,6 +69,8 @@ pub enum SyncMode {
     Full,
     Snap,
     Light,
+    /// Start from a trusted block hash instead of genesis
+    Checkpoint(H256),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -75,6 +91,7 @@ pub struct SyncProgress {
     pub known_states: u64,
 }
 
+#[derive(Clone)]
 pub struct SyncConfig {
     pub mode: SyncMode,
     pub max_peers: usize,
@@ -85,6 +102,13 @@ pub struct SyncConfig {
     pub max_state_request: usize,
     pub timeout: Duration,
     pub retry_limit: usize,
+    /// Headers walked back from a checkpoint looking for a known block
+    /// before the checkpoint is trusted outright
+    pub checkpoint_backfill: u64,
+    /// Time without the head advancing before downloads are restarted
+    pub stall_timeout: Duration,
+    /// Consecutive restarts without progress before sync fails
+    pub max_stall_restarts: usize,
 }
 
 impl Default for SyncConfig {
@@ -99,6 +123,27 @@ impl Default for SyncConfig {
             max_state_request: 384,
             timeout: Duration::from_secs(10),
             retry_limit: 3,
+            checkpoint_backfill: 8192,
+            stall_timeout: Duration::from_secs(60),
+            max_stall_restarts: 5,
+        }
+    }
+}
+
+impl SyncConfig {
+    /// Checkpoint sync from `checkpoint` with otherwise default settings
+    pub fn with_checkpoint(checkpoint: H256) -> Self {
+        Self {
+            mode: SyncMode::Checkpoint(checkpoint),
+            ..Self::default()
+        }
+    }
+
+    /// Trusted block hash when running a checkpoint sync
+    pub fn checkpoint(&self) -> Option<H256> {
+        match self.mode {
+            SyncMode::Checkpoint(hash) => Some(hash),
+            _ => None,
         }
     }
 }
@@ -111,6 +156,11 @@ pub struct Synchronizer<D: Database> {
     progress: Arc<RwLock<SyncProgress>>,
     events_tx: mpsc::UnboundedSender<SyncEvent>,
     cancel_tx: Option<mpsc::Sender<()>>,
+    reorg: ReorgHandler<D>,
+    checkpoint_provider: Option<Arc<dyn CheckpointProvider>>,
+    receipt_provider: Option<Arc<dyn ReceiptProvider>>,
+    block_processor: Option<Arc<dyn BlockProcessor>>,
+    txpool: Option<Arc<TransactionPool>>,
 }
 
 #[derive(Debug, Clone)]
@@ -118,6 +168,8 @@ pub enum SyncEvent {
     Started,
     Progress(SyncProgress),
     BlockImported(H256),
+    BlockReverted(H256),
+    BlockApplied(H256),
     StateImported(H256),
     Completed,
     Error(String),
@@ -130,6 +182,7 @@ impl<D: Database + 'static> Synchronizer<D> {
         peer_manager: Arc<PeerManager>,
     ) -> Self {
         let (events_tx, _) = mpsc::unbounded_channel();
+        let reorg = ReorgHandler::new(db.clone(), events_tx.clone());
         
         Self {
             config,
@@ -145,8 +198,41 @@ impl<D: Database + 'static> Synchronizer<D> {
             })),
             events_tx,
             cancel_tx: None,
+            reorg,
+            checkpoint_provider: None,
+            receipt_provider: None,
+            block_processor: None,
+            txpool: None,
         }
     }
+
+    /// Source of headers, state and blocks for `SyncMode::Checkpoint`
+    pub fn with_checkpoint_provider(mut self, provider: Arc<dyn CheckpointProvider>) -> Self {
+        self.checkpoint_provider = Some(provider);
+        self
+    }
+    
+    /// Source of receipts for blocks downloaded by `SyncMode::Fast`, which
+    /// fails to start without one
+    pub fn with_receipt_provider(mut self, provider: Arc<dyn ReceiptProvider>) -> Self {
+        self.receipt_provider = Some(provider);
+        self
+    }
+    
+    /// Verification and execution for downloaded and imported blocks
+    ///
+    /// Without one, downloaded blocks are only checked for basic sanity and
+    /// `import_blocks` can't be used.
+    pub fn with_block_processor(mut self, processor: Arc<dyn BlockProcessor>) -> Self {
+        self.block_processor = Some(processor);
+        self
+    }
+    
+    /// Transaction pool to re-price whenever an import moves the head
+    pub fn with_txpool(mut self, pool: Arc<TransactionPool>) -> Self {
+        self.txpool = Some(pool);
+        self
+    }
     
     pub async fn start(&mut self) -> Result<()> {
         *self.status.write() = SyncStatus::Downloading;
@@ -168,6 +254,9 @@ impl<D: Database + 'static> Synchronizer<D> {
             SyncMode::Light => {
                 self.run_light_sync(&mut cancel_rx).await?;
             }
+            SyncMode::Checkpoint(checkpoint) => {
+                self.run_checkpoint_sync(checkpoint, &mut cancel_rx).await?;
+            }
         }
         
         *self.status.write() = SyncStatus::Synced;
@@ -184,11 +273,13 @@ impl<D: Database + 'static> Synchronizer<D> {
     }
     
     async fn run_fast_sync(&self, cancel_rx: &mut mpsc::Receiver<()>) -> Result<()> {
+        // Refuse before downloading anything rather than after the state
+        let provider = self.receipt_provider.clone().ok_or_else(fast_sync::no_receipt_provider)?;
         let fast_sync = FastSync::new(
             self.db.clone(),
             self.peer_manager.clone(),
             self.config.clone(),
-        );
+        ).with_receipt_provider(provider);
         
         // Download headers first
         let pivot_header = fast_sync.download_headers(cancel_rx).await?;
@@ -212,28 +303,59 @@ impl<D: Database + 'static> Synchronizer<D> {
             self.config.clone(),
         );
         
+        self.download_loop(&downloader, cancel_rx).await
+    }
+    
+    /// Import batches from `source` until it runs dry, restarting its
+    /// requests whenever the head stops advancing
+    async fn download_loop<S: BatchSource + ?Sized>(
+        &self,
+        source: &S,
+        cancel_rx: &mut mpsc::Receiver<()>,
+    ) -> Result<()> {
+        let mut watchdog = StallDetector::new(
+            self.config.stall_timeout,
+            self.config.max_stall_restarts,
+            self.progress.read().current_block,
+            Instant::now(),
+        );
+        
         loop {
+            let idle = watchdog.remaining(Instant::now());
+            
             tokio::select! {
                 _ = cancel_rx.recv() => {
                     return Err(SyncError::Cancelled);
                 }
-                result = downloader.download_next_batch() => {
+                result = time::timeout(idle, source.next_batch()) => {
                     match result {
-                        Ok(blocks) if blocks.is_empty() => {
+                        Ok(Ok(blocks)) if blocks.is_empty() => {
                             // No more blocks to download
                             break;
                         }
-                        Ok(blocks) => {
+                        Ok(Ok(blocks)) => {
                             self.process_blocks(blocks).await?;
                         }
-                        Err(e) => {
+                        Ok(Err(e)) => {
                             tracing::error!("Failed to download blocks: {}", e);
                             return Err(e);
                         }
+                        Err(_) => {
+                            let restart = watchdog.stalled(Instant::now())?;
+                            tracing::warn!(
+                                "No sync progress for {:?}, restarting downloads (attempt {})",
+                                self.config.stall_timeout,
+                                restart
+                            );
+                            self.events_tx.send(SyncEvent::Error("stalled, retrying".to_string())).ok();
+                            source.restart();
+                        }
                     }
                 }
             }
             
+            watchdog.record(self.progress.read().current_block, Instant::now());
+            
             // Update progress
             self.update_progress().await;
         }
@@ -266,6 +388,21 @@ impl<D: Database + 'static> Synchronizer<D> {
         Ok(())
     }
     
+    async fn run_checkpoint_sync(&self, checkpoint: H256, cancel_rx: &mut mpsc::Receiver<()>) -> Result<()> {
+        let provider = self.checkpoint_provider.clone().ok_or(SyncError::NoPeers)?;
+        let checkpoint_sync = CheckpointSync::new(
+            self.db.clone(),
+            provider,
+            &self.config,
+            self.events_tx.clone(),
+        );
+
+        let outcome = checkpoint_sync.run(checkpoint, cancel_rx).await?;
+        tracing::info!("Checkpoint sync from {:?} reached head {:?}", checkpoint, outcome.head);
+
+        Ok(())
+    }
+    
     async fn run_light_sync(&self, _cancel_rx: &mut mpsc::Receiver<()>) -> Result<()> {
         // Light sync only downloads headers and verifies using CHT (Canonical Hash Trie)
         // This is a simplified implementation
@@ -274,6 +411,14 @@ impl<D: Database + 'static> Synchronizer<D> {
     }
     
     async fn process_blocks(&self, blocks: Vec<Block>) -> Result<()> {
+        if self.block_processor.is_some() {
+            let outcome = self.import_blocks(blocks).await?;
+            return match outcome.invalid() {
+                Some((hash, reason)) => Err(SyncError::InvalidBlock(format!("{:?}: {}", hash, reason))),
+                None => Ok(()),
+            };
+        }
+        
         for block in blocks {
             // Validate block
             self.validate_block(&block)?;
@@ -306,23 +451,18 @@ impl<D: Database + 'static> Synchronizer<D> {
     async fn import_block(&self, block: Block) -> Result<()> {
         let hash = block.header.hash();
         
-        // Store block header
-        let header_key = format!("header:{}", hex::encode(hash));
-        self.db.put(
-            header_key.as_bytes(),
-            &bincode::serialize(&block.header).unwrap(),
-        )?;
+        // Store header, body and total difficulty
+        self.reorg.insert_block(&block)?;
         
-        // Store block body
-        let body_key = format!("body:{}", hex::encode(hash));
-        self.db.put(
-            body_key.as_bytes(),
-            &bincode::serialize(&block.body).unwrap(),
-        )?;
+        // Update canonical chain, unwinding the old branch if this one is heavier
+        if let Some(outcome) = self.reorg.handle_new_head(&block.header)? {
+            self.head_changed(&block.header, &outcome)?;
+        }
         
-        // Update canonical chain
-        let number_key = format!("number:{}", block.header.number);
-        self.db.put(number_key.as_bytes(), hash.as_bytes())?;
+        {
+            let mut progress = self.progress.write();
+            progress.current_block = progress.current_block.max(block.header.number);
+        }
         
         // Send event
         self.events_tx.send(SyncEvent::BlockImported(hash)).ok();
@@ -330,6 +470,78 @@ impl<D: Database + 'static> Synchronizer<D> {
         Ok(())
     }
     
+    /// Import a chain segment, parents first
+    ///
+    /// Each block is verified and executed by the block processor, then its
+    /// header, body, receipts, post-state and, if it becomes the head, the
+    /// canonical and transaction indices are written in a single batch. A
+    /// heavier branch replaces the canonical chain.
+    ///
+    /// The import stops at the first rejected block, leaving the blocks
+    /// before it imported and nothing of it or the blocks after it.
+    pub async fn import_blocks(&self, blocks: Vec<Block>) -> Result<ImportOutcome> {
+        let processor = self.block_processor.clone()
+            .ok_or_else(|| SyncError::InvalidState("no block processor configured".to_string()))?;
+        
+        let mut statuses = Vec::with_capacity(blocks.len());
+        let mut rejected = false;
+        for block in blocks {
+            let hash = block.header.hash();
+            if rejected {
+                statuses.push((hash, BlockImportStatus::Skipped));
+                continue;
+            }
+            if self.reorg.total_difficulty(&hash)?.is_some() {
+                statuses.push((hash, BlockImportStatus::Known));
+                continue;
+            }
+            
+            let mut batch = self.db.batch();
+            let imported = match processor.process(&block, &mut *batch).await {
+                Ok(receipts) => self.reorg.import_block(&block, &receipts, batch),
+                Err(e) => Err(e),
+            };
+            let status = match imported {
+                Ok(Some(outcome)) => BlockImportStatus::Canonical(outcome),
+                Ok(None) => BlockImportStatus::SideChain,
+                Err(SyncError::InvalidBlock(reason)) => {
+                    tracing::warn!("Rejected block {} {:?}: {}", block.header.number, hash, reason);
+                    rejected = true;
+                    statuses.push((hash, BlockImportStatus::Invalid(reason)));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            
+            if let BlockImportStatus::Canonical(outcome) = &status {
+                let mut progress = self.progress.write();
+                progress.current_block = progress.current_block.max(block.header.number);
+                drop(progress);
+                self.head_changed(&block.header, outcome)?;
+            }
+            self.events_tx.send(SyncEvent::BlockImported(hash)).ok();
+            statuses.push((hash, status));
+        }
+        
+        Ok(ImportOutcome {
+            blocks: statuses,
+            head: self.reorg.head()?,
+        })
+    }
+    
+    /// Bring the transaction pool up to date with the new canonical `head`,
+    /// reached through `outcome`
+    fn head_changed(&self, head: &Header, outcome: &ReorgOutcome) -> Result<()> {
+        if let Some(pool) = &self.txpool {
+            let senders = self.reorg.senders(outcome)?;
+            let dropped = pool.on_new_head(head, &senders);
+            if dropped > 0 {
+                tracing::debug!("Dropped {} pooled transactions at head {}", dropped, head.number);
+            }
+        }
+        Ok(())
+    }
+    
     async fn update_progress(&self) {
         let progress = self.progress.read().clone();
         self.events_tx.send(SyncEvent::Progress(progress)).ok();
@@ -359,11 +571,295 @@ impl<D: Database + 'static> Synchronizer<D> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use async_trait::async_trait;
+    use ethereum_core::{LegacyTransaction, Receipt, Transaction};
+    use ethereum_storage::{MemoryDatabase, WriteBatch};
+    use ethereum_types::{Address, Bytes};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    
+    /// Serves a fixed chain two blocks at a time from a peer that goes
+    /// quiet after `answers` batches
+    struct MockSource {
+        chain: Vec<Block>,
+        served: RwLock<usize>,
+        answers: RwLock<Option<usize>>,
+        /// Whether a responsive peer shows up when requests are restarted
+        fresh_peer: AtomicBool,
+        restarts: AtomicUsize,
+    }
+    
+    impl MockSource {
+        fn new(chain: Vec<Block>, answers: usize, fresh_peer: bool) -> Self {
+            Self {
+                chain,
+                served: RwLock::new(1),
+                answers: RwLock::new(Some(answers)),
+                fresh_peer: AtomicBool::new(fresh_peer),
+                restarts: AtomicUsize::new(0),
+            }
+        }
+    }
+    
+    #[async_trait]
+    impl BatchSource for MockSource {
+        async fn next_batch(&self) -> Result<Vec<Block>> {
+            let quiet = {
+                let mut answers = self.answers.write();
+                match answers.as_mut() {
+                    Some(0) => true,
+                    Some(left) => {
+                        *left -= 1;
+                        false
+                    }
+                    None => false,
+                }
+            };
+            if quiet {
+                return std::future::pending().await;
+            }
+            
+            let mut served = self.served.write();
+            let end = (*served + 2).min(self.chain.len());
+            let batch = self.chain[*served..end].to_vec();
+            *served = end;
+            Ok(batch)
+        }
+        
+        fn restart(&self) {
+            self.restarts.fetch_add(1, Ordering::SeqCst);
+            if self.fresh_peer.load(Ordering::SeqCst) {
+                *self.answers.write() = None;
+            }
+        }
+    }
+    
+    fn make_chain(length: u64) -> Vec<Block> {
+        let mut genesis = Header::new();
+        genesis.difficulty = U256::one();
+        
+        let mut chain = vec![Block::new(genesis)];
+        for _ in 0..length {
+            let parent = &chain.last().unwrap().header;
+            let mut header = Header::new();
+            header.parent_hash = parent.hash();
+            header.number = parent.number + U256::one();
+            header.difficulty = U256::one();
+            chain.push(Block::new(header));
+        }
+        chain
+    }
+    
+    /// Extra data marking a block the processor rejects
+    const REJECT: &[u8] = b"reject";
+    
+    /// Accepts blocks unless their extra data is `REJECT`, staging a state
+    /// entry per block and returning a receipt per transaction
+    struct MockProcessor;
+    
+    #[async_trait]
+    impl BlockProcessor for MockProcessor {
+        async fn process(&self, block: &Block, batch: &mut dyn WriteBatch) -> Result<Vec<Receipt>> {
+            batch.put(state_key(block).as_bytes(), b"state");
+            if block.header.extra_data == REJECT {
+                return Err(SyncError::InvalidBlock("state root mismatch".to_string()));
+            }
+            
+            Ok(block.transactions.iter().enumerate().map(|(i, tx)| Receipt {
+                tx_type: tx.tx_type(),
+                status: 1,
+                cumulative_gas_used: U256::from(21_000 * (i as u64 + 1)),
+                logs_bloom: Default::default(),
+                logs: Vec::new(),
+                gas_used: U256::from(21_000),
+                contract_address: None,
+            }).collect())
+        }
+    }
+    
+    fn state_key(block: &Block) -> String {
+        format!("state:{}", hex::encode(block.header.hash()))
+    }
+    
+    /// Child of `parent` holding a single transfer with nonce `nonce`
+    fn child_with_tx(parent: &Header, nonce: u64, extra_data: &[u8]) -> Block {
+        let mut header = Header::new();
+        header.parent_hash = parent.hash();
+        header.number = parent.number + U256::one();
+        header.difficulty = U256::one();
+        header.extra_data = extra_data.to_vec();
+        
+        let mut block = Block::new(header);
+        block.transactions = vec![Transaction::Legacy(LegacyTransaction {
+            nonce: U256::from(nonce),
+            gas_price: U256::from(1),
+            gas_limit: U256::from(21_000),
+            to: None,
+            value: U256::zero(),
+            data: Bytes::new(),
+            v: 27,
+            r: U256::one(),
+            s: U256::one(),
+        })];
+        block
+    }
+    
+    /// Synchronizer over a database holding only `genesis`
+    fn synchronizer(genesis: &Block, max_stall_restarts: usize) -> (Synchronizer<MemoryDatabase>, ReorgHandler<MemoryDatabase>) {
+        let db = Arc::new(MemoryDatabase::new());
+        let (events_tx, _) = mpsc::unbounded_channel();
+        let reorg = ReorgHandler::new(db.clone(), events_tx);
+        reorg.insert_block(genesis).unwrap();
+        reorg.handle_new_head(&genesis.header).unwrap();
+        
+        let config = SyncConfig {
+            mode: SyncMode::Full,
+            stall_timeout: Duration::from_millis(50),
+            max_stall_restarts,
+            ..SyncConfig::default()
+        };
+        let sync = Synchronizer::new(config, db, Arc::new(PeerManager::new(1)));
+        (sync, reorg)
+    }
     
     #[test]
     fn test_sync_config_default() {
         let config = SyncConfig::default();
         assert_eq!(config.mode, SyncMode::Fast);
         assert_eq!(config.max_peers, 25);
+        assert_eq!(config.checkpoint(), None);
+
+        let checkpoint = H256::repeat_byte(0xcc);
+        let config = SyncConfig::with_checkpoint(checkpoint);
+        assert_eq!(config.mode, SyncMode::Checkpoint(checkpoint));
+        assert_eq!(config.checkpoint(), Some(checkpoint));
+    }
+    
+    #[tokio::test]
+    async fn test_stalled_download_restarts_with_fresh_peer() {
+        let chain = make_chain(6);
+        let (sync, reorg) = synchronizer(&chain[0], 3);
+        let (_cancel_tx, mut cancel_rx) = mpsc::channel(1);
+        
+        // The first peer answers once and then goes quiet
+        let source = MockSource::new(chain.clone(), 1, true);
+        sync.download_loop(&source, &mut cancel_rx).await.unwrap();
+        
+        assert_eq!(source.restarts.load(Ordering::SeqCst), 1);
+        assert_eq!(reorg.head().unwrap(), Some(chain[6].header.hash()));
+        assert_eq!(sync.progress().current_block, U256::from(6));
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_stall_without_peers_gives_up() {
+        let chain = make_chain(6);
+        let (sync, reorg) = synchronizer(&chain[0], 2);
+        let (_cancel_tx, mut cancel_rx) = mpsc::channel(1);
+        
+        let source = MockSource::new(chain.clone(), 1, false);
+        let result = sync.download_loop(&source, &mut cancel_rx).await;
+        
+        assert!(matches!(result, Err(SyncError::Stalled(2))));
+        assert_eq!(source.restarts.load(Ordering::SeqCst), 2);
+        assert_eq!(reorg.head().unwrap(), Some(chain[2].header.hash()));
+    }
+    
+    #[tokio::test]
+    async fn test_import_blocks_advances_head_and_indexes_receipts() {
+        let genesis = make_chain(0).remove(0);
+        let (sync, reorg) = synchronizer(&genesis, 1);
+        let sync = sync.with_block_processor(Arc::new(MockProcessor));
+        
+        let b1 = child_with_tx(&genesis.header, 0, &[]);
+        let b2 = child_with_tx(&b1.header, 1, &[]);
+        let b3 = child_with_tx(&b2.header, 2, &[]);
+        let outcome = sync.import_blocks(vec![b1.clone(), b2.clone(), b3.clone()]).await.unwrap();
+        
+        assert_eq!(outcome.imported(), 3);
+        assert!(outcome.invalid().is_none());
+        assert!(outcome.blocks.iter().all(|(_, status)| matches!(status, BlockImportStatus::Canonical(_))));
+        assert_eq!(outcome.head, Some(b3.header.hash()));
+        assert_eq!(reorg.head().unwrap(), Some(b3.header.hash()));
+        assert_eq!(sync.progress().current_block, U256::from(3));
+        
+        for block in [&b1, &b2, &b3] {
+            let hash = block.header.hash();
+            assert_eq!(reorg.canonical_hash(block.header.number).unwrap(), Some(hash));
+            assert_eq!(reorg.receipts(&hash).unwrap().unwrap().len(), 1);
+            assert_eq!(reorg.transaction_block(&block.transactions[0].hash()).unwrap(), Some(hash));
+            assert!(sync.db.get(state_key(block).as_bytes()).unwrap().is_some());
+        }
+        
+        // Importing the same segment again writes nothing
+        let again = sync.import_blocks(vec![b3.clone()]).await.unwrap();
+        assert_eq!(again.blocks, vec![(b3.header.hash(), BlockImportStatus::Known)]);
+    }
+    
+    #[tokio::test]
+    async fn test_import_blocks_resets_pool_senders() {
+        use ethereum_crypto::{generate_private_key, public_key_to_address, sign_message};
+        use ethereum_txpool::{MemoryNonceProvider, TxPoolConfig};
+        
+        let genesis = make_chain(0).remove(0);
+        let (sync, _reorg) = synchronizer(&genesis, 1);
+        let nonces = Arc::new(MemoryNonceProvider::new());
+        let pool = Arc::new(TransactionPool::new(TxPoolConfig::default(), nonces.clone()));
+        let sync = sync.with_block_processor(Arc::new(MockProcessor)).with_txpool(pool.clone());
+        
+        let key = generate_private_key();
+        let sender = public_key_to_address(&key.public_key(&secp256k1::Secp256k1::new()));
+        let mut tx = LegacyTransaction {
+            nonce: U256::zero(),
+            gas_price: U256::from(1_000_000_000u64),
+            gas_limit: U256::from(21_000),
+            to: Some(Address::from_bytes([0x42; 20])),
+            value: U256::one(),
+            data: Bytes::new(),
+            v: 27,
+            r: U256::zero(),
+            s: U256::zero(),
+        };
+        let signature = sign_message(&tx.signing_hash(None), &key).unwrap();
+        tx.v = signature.v as u64;
+        tx.r = U256::from_big_endian(signature.r.as_bytes());
+        tx.s = U256::from_big_endian(signature.s.as_bytes());
+        let tx = Transaction::Legacy(tx);
+        pool.add_transaction(tx.clone()).unwrap();
+        assert_eq!(pool.pending_count(), 1);
+        
+        // The block including it moves the sender's nonce, the pooled copy goes
+        let mut b1 = child_with_tx(&genesis.header, 0, &[]);
+        b1.transactions = vec![tx];
+        nonces.set_nonce(sender, U256::one());
+        sync.import_blocks(vec![b1]).await.unwrap();
+        assert_eq!(pool.pending_count(), 0);
+    }
+    
+    #[tokio::test]
+    async fn test_import_blocks_stops_at_invalid_block() {
+        let genesis = make_chain(0).remove(0);
+        let (sync, reorg) = synchronizer(&genesis, 1);
+        let sync = sync.with_block_processor(Arc::new(MockProcessor));
+        
+        let b1 = child_with_tx(&genesis.header, 0, &[]);
+        let b2 = child_with_tx(&b1.header, 1, REJECT);
+        let b3 = child_with_tx(&b2.header, 2, &[]);
+        let outcome = sync.import_blocks(vec![b1.clone(), b2.clone(), b3.clone()]).await.unwrap();
+        
+        assert_eq!(outcome.imported(), 1);
+        assert!(matches!(outcome.blocks[0].1, BlockImportStatus::Canonical(_)));
+        assert_eq!(outcome.invalid(), Some((b2.header.hash(), "state root mismatch")));
+        assert_eq!(outcome.blocks[2], (b3.header.hash(), BlockImportStatus::Skipped));
+        assert_eq!(outcome.head, Some(b1.header.hash()));
+        assert_eq!(reorg.head().unwrap(), Some(b1.header.hash()));
+        
+        // Nothing of the rejected block or the one after it was written
+        for block in [&b2, &b3] {
+            let hash = block.header.hash();
+            assert_eq!(reorg.total_difficulty(&hash).unwrap(), None);
+            assert_eq!(reorg.receipts(&hash).unwrap(), None);
+            assert_eq!(reorg.canonical_hash(block.header.number).unwrap(), None);
+            assert_eq!(reorg.transaction_block(&block.transactions[0].hash()).unwrap(), None);
+            assert!(sync.db.get(state_key(block).as_bytes()).unwrap().is_none());
+        }
+    }
+}
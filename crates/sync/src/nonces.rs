@@ -0,0 +1,80 @@
+use ethereum_types::{Address, U256};
+use ethereum_state::TrieStateProvider;
+use ethereum_storage::Database;
+use ethereum_txpool::NonceProvider;
+use std::sync::Arc;
+
+use crate::reorg::head_header;
+use crate::{Result, SyncError};
+
+/// Account nonces read from the state of the canonical head
+///
+/// The head is looked up on every read, so the pool sees a new head as soon
+/// as the import that made it is written.
+pub struct StateNonceProvider<D: Database> {
+    db: Arc<D>,
+    state: TrieStateProvider<D>,
+}
+
+impl<D: Database> StateNonceProvider<D> {
+    pub fn new(db: Arc<D>) -> Self {
+        Self { state: TrieStateProvider::new(db.clone()), db }
+    }
+
+    fn head_nonce(&self, address: &Address) -> Result<u64> {
+        let head = match head_header(&*self.db)? {
+            Some(head) => head,
+            None => return Ok(0),
+        };
+        let account = self.state.state_account(head.state_root, address)
+            .map_err(|e| SyncError::InvalidState(e.to_string()))?;
+        Ok(account.map(|account| account.nonce).unwrap_or_default())
+    }
+}
+
+impl<D: Database> NonceProvider for StateNonceProvider<D> {
+    fn account_nonce(&self, address: &Address) -> U256 {
+        match self.head_nonce(address) {
+            Ok(nonce) => U256::from(nonce),
+            Err(e) => {
+                tracing::warn!("Failed to read the nonce of {:?}: {}", address, e);
+                U256::zero()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::Header;
+    use ethereum_crypto::keccak256;
+    use ethereum_state::StateAccount;
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_trie::PatriciaTrie;
+    use ethereum_types::H256;
+
+    #[test]
+    fn test_nonce_follows_canonical_head() {
+        let db = Arc::new(MemoryDatabase::new());
+        let provider = StateNonceProvider::new(db.clone());
+        let sender = Address::from_bytes([0x11; 20]);
+        assert_eq!(provider.account_nonce(&sender), U256::zero());
+
+        let mut trie = PatriciaTrie::new(db.clone());
+        let account = StateAccount { nonce: 7, ..Default::default() };
+        trie.insert(keccak256(sender.as_bytes()).as_bytes(), account.encode()).unwrap();
+        let mut header = Header::new();
+        header.state_root = trie.commit().unwrap();
+        let hash = header.hash();
+        db.put(format!("header:{}", hex::encode(hash)).as_bytes(), &bincode::serialize(&header).unwrap()).unwrap();
+        db.put(b"canonical:head", hash.as_bytes()).unwrap();
+
+        assert_eq!(provider.account_nonce(&sender), U256::from(7));
+        assert_eq!(provider.account_nonce(&Address::from_bytes([0x22; 20])), U256::zero());
+
+        // A head whose header is gone reads as a fresh account
+        db.put(b"canonical:head", H256::repeat_byte(0x33).as_bytes()).unwrap();
+        assert_eq!(provider.account_nonce(&sender), U256::zero());
+    }
+}
@@ -0,0 +1,436 @@
+use ethereum_types::{Address, H256, U256};
+use ethereum_core::{Block, Header, Receipt, Transaction};
+use ethereum_storage::{Database, WriteBatch};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::{Result, SyncError, SyncEvent};
+
+/// Key holding the hash of the current canonical head
+const HEAD_KEY: &[u8] = b"canonical:head";
+
+/// Result of switching the canonical chain to a new head
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReorgOutcome {
+    pub common_ancestor: H256,
+    /// Blocks removed from the canonical chain, highest first
+    pub reverted: Vec<H256>,
+    /// Blocks added to the canonical chain, lowest first
+    pub applied: Vec<H256>,
+}
+
+/// Maintains the canonical chain index (number → hash, tx → block)
+/// and unwinds it when a heavier branch shows up
+pub struct ReorgHandler<D: Database> {
+    db: Arc<D>,
+    events_tx: mpsc::UnboundedSender<SyncEvent>,
+}
+
+impl<D: Database> ReorgHandler<D> {
+    pub fn new(db: Arc<D>, events_tx: mpsc::UnboundedSender<SyncEvent>) -> Self {
+        Self { db, events_tx }
+    }
+
+    /// Store a block's header, body and total difficulty without touching
+    /// the canonical index
+    pub fn insert_block(&self, block: &Block) -> Result<()> {
+        let mut batch = self.db.batch();
+        self.stage_block(block, &mut *batch)?;
+        self.db.write_batch(batch)?;
+        Ok(())
+    }
+
+    /// Store `block` with its receipts and make it the head if it is heavier,
+    /// everything staged so far in `batch` included in the same write
+    ///
+    /// Returns `None` when the block only extends a side branch.
+    pub fn import_block(
+        &self,
+        block: &Block,
+        receipts: &[Receipt],
+        mut batch: Box<dyn WriteBatch>,
+    ) -> Result<Option<ReorgOutcome>> {
+        let hash = block.header.hash();
+        let td = self.stage_block(block, &mut *batch)?;
+        batch.put(receipts_key(&hash).as_bytes(), &encode(&receipts.to_vec())?);
+
+        let outcome = self.stage_new_head(&block.header, td, &block.transactions, &mut *batch)?;
+        self.db.write_batch(batch)?;
+
+        if let Some(outcome) = &outcome {
+            self.announce(outcome);
+        }
+        Ok(outcome)
+    }
+
+    /// Stage the header, body and total difficulty of `block`, returning the latter
+    fn stage_block(&self, block: &Block, batch: &mut dyn WriteBatch) -> Result<U256> {
+        let hash = block.header.hash();
+
+        let parent_td = if block.header.is_genesis() {
+            U256::zero()
+        } else {
+            self.total_difficulty(&block.header.parent_hash)?
+                .ok_or_else(|| SyncError::InvalidBlock(format!(
+                    "Unknown parent {:?}", block.header.parent_hash
+                )))?
+        };
+        let td = parent_td + block.header.difficulty;
+
+        batch.put(header_key(&hash).as_bytes(), &encode(&block.header)?);
+        batch.put(body_key(&hash).as_bytes(), &encode(&block.transactions)?);
+        batch.put(td_key(&hash).as_bytes(), &encode(&td)?);
+
+        Ok(td)
+    }
+
+    /// Start the canonical chain at `header` without its ancestors
+    ///
+    /// Used by checkpoint sync: the header becomes the head and descendants
+    /// can be imported on top of it. The difficulty of the skipped ancestors
+    /// is unknown, so the anchor's own difficulty seeds the total.
+    pub fn anchor(&self, header: &Header) -> Result<()> {
+        let hash = header.hash();
+
+        self.put_encoded(&header_key(&hash), header)?;
+        self.put_encoded(&body_key(&hash), &Vec::<Transaction>::new())?;
+        self.put_encoded(&td_key(&hash), &header.difficulty)?;
+        self.db.put(number_key(header.number).as_bytes(), hash.as_bytes())?;
+        self.db.put(HEAD_KEY, hash.as_bytes())?;
+
+        self.events_tx.send(SyncEvent::BlockApplied(hash)).ok();
+        Ok(())
+    }
+
+    /// Make `new_head` canonical if its total difficulty beats the current head
+    ///
+    /// Returns `None` when the current head is kept.
+    pub fn handle_new_head(&self, new_head: &Header) -> Result<Option<ReorgOutcome>> {
+        let new_hash = new_head.hash();
+        let new_td = self.total_difficulty(&new_hash)?
+            .ok_or_else(|| SyncError::InvalidState(format!("Missing total difficulty for {:?}", new_hash)))?;
+        let transactions = self.transactions(&new_hash)?;
+
+        let mut batch = self.db.batch();
+        let outcome = self.stage_new_head(new_head, new_td, &transactions, &mut *batch)?;
+        self.db.write_batch(batch)?;
+
+        if let Some(outcome) = &outcome {
+            self.announce(outcome);
+        }
+        Ok(outcome)
+    }
+
+    /// Stage the canonical index changes that make `new_head` the head
+    ///
+    /// `transactions` is the body of `new_head`, which may only be staged
+    /// itself. The rest of the new branch must already be stored.
+    fn stage_new_head(
+        &self,
+        new_head: &Header,
+        new_td: U256,
+        transactions: &[Transaction],
+        batch: &mut dyn WriteBatch,
+    ) -> Result<Option<ReorgOutcome>> {
+        let new_hash = new_head.hash();
+
+        let old_head = match self.head()? {
+            Some(head) => {
+                let head_td = self.total_difficulty(&head)?.unwrap_or_default();
+                if head == new_hash || new_td <= head_td {
+                    return Ok(None);
+                }
+                Some(self.header(&head)?)
+            }
+            None => None,
+        };
+
+        // Walk the new branch back until it meets the canonical chain
+        let mut new_branch = Vec::new();
+        let mut cursor = new_head.clone();
+        let ancestor = loop {
+            let hash = cursor.hash();
+            if self.canonical_hash(cursor.number)? == Some(hash) {
+                break Some(cursor);
+            }
+            if cursor.is_genesis() {
+                new_branch.push(cursor);
+                break None;
+            }
+            let parent = self.header(&cursor.parent_hash)?;
+            new_branch.push(cursor);
+            cursor = parent;
+        };
+
+        let (common_ancestor, first_replaced) = match &ancestor {
+            Some(header) => (header.hash(), header.number + U256::one()),
+            None => (H256::zero(), U256::zero()),
+        };
+
+        // Unwind the old branch from its tip down to the ancestor
+        let mut reverted = Vec::new();
+        if let Some(old_head) = old_head {
+            let mut number = old_head.number;
+            while number >= first_replaced {
+                if let Some(hash) = self.canonical_hash(number)? {
+                    for tx in self.transactions(&hash)? {
+                        batch.delete(tx_lookup_key(&tx.hash()).as_bytes());
+                    }
+                    batch.delete(number_key(number).as_bytes());
+                    reverted.push(hash);
+                }
+                if number.is_zero() {
+                    break;
+                }
+                number = number - U256::one();
+            }
+        }
+
+        // Install the new branch from the ancestor up to the new head
+        let mut applied = Vec::new();
+        for header in new_branch.iter().rev() {
+            let hash = header.hash();
+            batch.put(number_key(header.number).as_bytes(), hash.as_bytes());
+            let body = if hash == new_hash {
+                transactions.to_vec()
+            } else {
+                self.transactions(&hash)?
+            };
+            for tx in body {
+                batch.put(tx_lookup_key(&tx.hash()).as_bytes(), hash.as_bytes());
+            }
+            applied.push(hash);
+        }
+
+        batch.put(HEAD_KEY, new_hash.as_bytes());
+
+        Ok(Some(ReorgOutcome {
+            common_ancestor,
+            reverted,
+            applied,
+        }))
+    }
+
+    /// Report a head switch once it has been written
+    fn announce(&self, outcome: &ReorgOutcome) {
+        for hash in &outcome.reverted {
+            self.events_tx.send(SyncEvent::BlockReverted(*hash)).ok();
+        }
+        for hash in &outcome.applied {
+            self.events_tx.send(SyncEvent::BlockApplied(*hash)).ok();
+        }
+
+        if !outcome.reverted.is_empty() {
+            tracing::info!(
+                "Chain reorg at ancestor {:?}: reverted {} blocks, applied {} blocks",
+                outcome.common_ancestor,
+                outcome.reverted.len(),
+                outcome.applied.len()
+            );
+        }
+    }
+
+    /// Hash of the current canonical head
+    pub fn head(&self) -> Result<Option<H256>> {
+        Ok(self.db.get(HEAD_KEY)?.map(|data| H256::from_slice(&data)))
+    }
+
+    /// Senders of the transactions in the blocks `outcome` reverted and
+    /// applied, each once
+    pub fn senders(&self, outcome: &ReorgOutcome) -> Result<Vec<Address>> {
+        let mut senders = BTreeSet::new();
+        for hash in outcome.reverted.iter().chain(&outcome.applied) {
+            senders.extend(self.transactions(hash)?.iter().filter_map(|tx| tx.sender().ok()));
+        }
+        Ok(senders.into_iter().collect())
+    }
+
+    /// Canonical block hash at `number`
+    pub fn canonical_hash(&self, number: U256) -> Result<Option<H256>> {
+        Ok(self.db.get(number_key(number).as_bytes())?.map(|data| H256::from_slice(&data)))
+    }
+
+    /// Block containing the canonical transaction `tx_hash`
+    pub fn transaction_block(&self, tx_hash: &H256) -> Result<Option<H256>> {
+        Ok(self.db.get(tx_lookup_key(tx_hash).as_bytes())?.map(|data| H256::from_slice(&data)))
+    }
+
+    /// Receipts stored for block `hash`
+    pub fn receipts(&self, hash: &H256) -> Result<Option<Vec<Receipt>>> {
+        self.get_decoded(&receipts_key(hash))
+    }
+
+    /// Total difficulty of the chain ending at `hash`
+    pub fn total_difficulty(&self, hash: &H256) -> Result<Option<U256>> {
+        self.get_decoded(&td_key(hash))
+    }
+
+    fn header(&self, hash: &H256) -> Result<Header> {
+        self.get_decoded(&header_key(hash))?
+            .ok_or_else(|| SyncError::InvalidState(format!("Missing header {:?}", hash)))
+    }
+
+    fn transactions(&self, hash: &H256) -> Result<Vec<Transaction>> {
+        Ok(self.get_decoded(&body_key(hash))?.unwrap_or_default())
+    }
+
+    fn put_encoded<T: serde::Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        self.db.put(key.as_bytes(), &encode(value)?)?;
+        Ok(())
+    }
+
+    fn get_decoded<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        match self.db.get(key.as_bytes())? {
+            Some(data) => bincode::deserialize(&data)
+                .map(Some)
+                .map_err(|e| SyncError::InvalidState(e.to_string())),
+            None => Ok(None),
+        }
+    }
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> Result<Vec<u8>> {
+    bincode::serialize(value).map_err(|e| SyncError::InvalidState(e.to_string()))
+}
+
+/// Header of the canonical head, `None` before the first block is imported
+pub(crate) fn head_header<D: Database + ?Sized>(db: &D) -> Result<Option<Header>> {
+    let hash = match db.get(HEAD_KEY)? {
+        Some(data) => H256::from_slice(&data),
+        None => return Ok(None),
+    };
+    match db.get(header_key(&hash).as_bytes())? {
+        Some(data) => bincode::deserialize(&data)
+            .map(Some)
+            .map_err(|e| SyncError::InvalidState(e.to_string())),
+        None => Err(SyncError::InvalidState(format!("Missing header {:?}", hash))),
+    }
+}
+
+fn header_key(hash: &H256) -> String {
+    format!("header:{}", hex::encode(hash))
+}
+
+fn body_key(hash: &H256) -> String {
+    format!("body:{}", hex::encode(hash))
+}
+
+fn receipts_key(hash: &H256) -> String {
+    format!("receipts:{}", hex::encode(hash))
+}
+
+fn td_key(hash: &H256) -> String {
+    format!("td:{}", hex::encode(hash))
+}
+
+fn number_key(number: U256) -> String {
+    format!("number:{}", number)
+}
+
+fn tx_lookup_key(tx_hash: &H256) -> String {
+    format!("tx:block:{}", hex::encode(tx_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::LegacyTransaction;
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_types::Bytes;
+
+    fn make_tx(nonce: u64) -> Transaction {
+        Transaction::Legacy(LegacyTransaction {
+            nonce: U256::from(nonce),
+            gas_price: U256::from(1),
+            gas_limit: U256::from(21000),
+            to: None,
+            value: U256::zero(),
+            data: Bytes::new(),
+            v: 27,
+            r: U256::one(),
+            s: U256::one(),
+        })
+    }
+
+    fn make_block(parent: &Header, difficulty: u64, extra: u8, txs: Vec<Transaction>) -> Block {
+        let mut header = Header::new();
+        header.parent_hash = parent.hash();
+        header.number = parent.number + U256::one();
+        header.difficulty = U256::from(difficulty);
+        header.extra_data = vec![extra];
+
+        let mut block = Block::new(header);
+        block.transactions = txs;
+        block
+    }
+
+    fn import(handler: &ReorgHandler<MemoryDatabase>, block: &Block) -> Option<ReorgOutcome> {
+        handler.insert_block(block).unwrap();
+        handler.handle_new_head(&block.header).unwrap()
+    }
+
+    #[test]
+    fn test_reorg_to_heavier_branch() {
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let handler = ReorgHandler::new(Arc::new(MemoryDatabase::new()), events_tx);
+
+        let genesis = Block::new(Header::new());
+        import(&handler, &genesis);
+
+        // Old branch: two light blocks
+        let old_tx1 = make_tx(1);
+        let old_tx2 = make_tx(2);
+        let a1 = make_block(&genesis.header, 1, 0xa, vec![old_tx1.clone()]);
+        let a2 = make_block(&a1.header, 1, 0xa, vec![old_tx2.clone()]);
+        import(&handler, &a1);
+        import(&handler, &a2);
+        assert_eq!(handler.head().unwrap(), Some(a2.hash()));
+
+        // New branch: a single heavier block at height 1
+        let new_tx = make_tx(3);
+        let b1 = make_block(&genesis.header, 5, 0xb, vec![new_tx.clone()]);
+
+        while events_rx.try_recv().is_ok() {}
+        let outcome = import(&handler, &b1).expect("heavier branch must be adopted");
+
+        assert_eq!(outcome.common_ancestor, genesis.hash());
+        assert_eq!(outcome.reverted, vec![a2.hash(), a1.hash()]);
+        assert_eq!(outcome.applied, vec![b1.hash()]);
+
+        assert_eq!(handler.head().unwrap(), Some(b1.hash()));
+        assert_eq!(handler.canonical_hash(U256::from(1)).unwrap(), Some(b1.hash()));
+        assert_eq!(handler.canonical_hash(U256::from(2)).unwrap(), None);
+
+        assert_eq!(handler.transaction_block(&old_tx1.hash()).unwrap(), None);
+        assert_eq!(handler.transaction_block(&old_tx2.hash()).unwrap(), None);
+        assert_eq!(handler.transaction_block(&new_tx.hash()).unwrap(), Some(b1.hash()));
+
+        let mut reverted = 0;
+        let mut applied = 0;
+        while let Ok(event) = events_rx.try_recv() {
+            match event {
+                SyncEvent::BlockReverted(_) => reverted += 1,
+                SyncEvent::BlockApplied(_) => applied += 1,
+                _ => {}
+            }
+        }
+        assert_eq!((reverted, applied), (2, 1));
+    }
+
+    #[test]
+    fn test_lighter_branch_is_ignored() {
+        let (events_tx, _events_rx) = mpsc::unbounded_channel();
+        let handler = ReorgHandler::new(Arc::new(MemoryDatabase::new()), events_tx);
+
+        let genesis = Block::new(Header::new());
+        import(&handler, &genesis);
+
+        let a1 = make_block(&genesis.header, 5, 0xa, vec![]);
+        let b1 = make_block(&genesis.header, 1, 0xb, vec![]);
+        import(&handler, &a1);
+
+        assert!(import(&handler, &b1).is_none());
+        assert_eq!(handler.canonical_hash(U256::from(1)).unwrap(), Some(a1.hash()));
+    }
+}
@@ -0,0 +1,88 @@
+use ethereum_types::U256;
+use std::time::{Duration, Instant};
+
+use crate::{Result, SyncError};
+
+/// Watchdog over the local head during a download
+///
+/// The download counts as stalled once `idle_timeout` passes without the
+/// head advancing. Every stall allows one restart; after `max_restarts`
+/// consecutive stalls without progress in between the sync gives up.
+#[derive(Debug, Clone)]
+pub struct StallDetector {
+    idle_timeout: Duration,
+    max_restarts: usize,
+    current_block: U256,
+    last_progress: Instant,
+    restarts: usize,
+}
+
+impl StallDetector {
+    pub fn new(idle_timeout: Duration, max_restarts: usize, current_block: U256, now: Instant) -> Self {
+        Self {
+            idle_timeout,
+            max_restarts,
+            current_block,
+            last_progress: now,
+            restarts: 0,
+        }
+    }
+
+    /// Record the local head, restarting the idle clock if it advanced
+    pub fn record(&mut self, current_block: U256, now: Instant) {
+        if current_block > self.current_block {
+            self.current_block = current_block;
+            self.last_progress = now;
+            self.restarts = 0;
+        }
+    }
+
+    /// Time left before the download counts as stalled
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.idle_timeout.saturating_sub(now.saturating_duration_since(self.last_progress))
+    }
+
+    /// Register a stall, returning the number of this restart or an error
+    /// once the restarts are used up
+    pub fn stalled(&mut self, now: Instant) -> Result<usize> {
+        if self.restarts >= self.max_restarts {
+            return Err(SyncError::Stalled(self.restarts));
+        }
+        self.restarts += 1;
+        // The restarted requests get a full timeout of their own
+        self.last_progress = now;
+        Ok(self.restarts)
+    }
+
+    /// Consecutive restarts since the head last advanced
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restarts_are_capped_and_reset_by_progress() {
+        let start = Instant::now();
+        let mut detector = StallDetector::new(Duration::from_secs(30), 2, U256::from(10), start);
+        assert_eq!(detector.remaining(start + Duration::from_secs(10)), Duration::from_secs(20));
+
+        // No progress, the clock keeps running
+        detector.record(U256::from(10), start + Duration::from_secs(20));
+        assert_eq!(detector.remaining(start + Duration::from_secs(40)), Duration::ZERO);
+
+        let stalled_at = start + Duration::from_secs(40);
+        assert_eq!(detector.stalled(stalled_at).unwrap(), 1);
+        assert_eq!(detector.remaining(stalled_at), Duration::from_secs(30));
+        assert_eq!(detector.stalled(stalled_at).unwrap(), 2);
+        assert!(matches!(detector.stalled(stalled_at), Err(SyncError::Stalled(2))));
+
+        // Progress earns a fresh set of restarts
+        detector.record(U256::from(11), stalled_at);
+        assert_eq!(detector.restarts(), 0);
+        assert_eq!(detector.stalled(stalled_at).unwrap(), 1);
+    }
+}
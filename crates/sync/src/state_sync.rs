@@ -1,55 +1,311 @@
+use async_trait::async_trait;
 use ethereum_types::{H256, U256};
-use ethereum_storage::Database;
-use ethereum_trie::{PatriciaTrie, MerkleProof};
+use ethereum_storage::{Database, WriteBatch};
+use ethereum_trie::{node_key, MerkleProof, Node, NodeRef, TrieReadView};
 use std::sync::Arc;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use crate::{Result, SyncError};
+use crate::{Result, SyncConfig, SyncError};
+
+/// Source of trie nodes, normally peers answering GetTrieNodes
+#[async_trait]
+pub trait NodeProvider: Send + Sync {
+    /// RLP of the requested nodes; peers may return a subset in any order
+    async fn nodes(&self, hashes: &[H256]) -> Result<Vec<Vec<u8>>>;
+}
+
+/// Tracks which trie nodes still have to be fetched
+///
+/// Every hash is queued, in flight or completed, and only ever in one of
+/// those. At most `window` hashes are in flight at a time.
+#[derive(Debug)]
+pub struct NodeScheduler {
+    queue: VecDeque<H256>,
+    queued: HashSet<H256>,
+    in_flight: HashSet<H256>,
+    completed: HashSet<H256>,
+    window: usize,
+}
+
+impl NodeScheduler {
+    pub fn new(root: H256, window: usize) -> Self {
+        let mut scheduler = Self {
+            queue: VecDeque::new(),
+            queued: HashSet::new(),
+            in_flight: HashSet::new(),
+            completed: HashSet::new(),
+            window: window.max(1),
+        };
+        scheduler.enqueue(root);
+        scheduler
+    }
+
+    /// Queue `hash` unless it's already known
+    pub fn enqueue(&mut self, hash: H256) {
+        if self.completed.contains(&hash) || self.in_flight.contains(&hash) {
+            return;
+        }
+        if self.queued.insert(hash) {
+            self.queue.push_back(hash);
+        }
+    }
+
+    /// Mark `hash` as present without fetching it, e.g. found locally
+    pub fn skip(&mut self, hash: H256) {
+        self.completed.insert(hash);
+    }
+
+    /// Next hashes to request, filling the window
+    pub fn next_batch(&mut self) -> Vec<H256> {
+        let free = self.window.saturating_sub(self.in_flight.len());
+        let mut batch = Vec::with_capacity(free.min(self.queue.len()));
+
+        while batch.len() < free {
+            let Some(hash) = self.queue.pop_front() else { break };
+            self.queued.remove(&hash);
+            self.in_flight.insert(hash);
+            batch.push(hash);
+        }
+
+        batch
+    }
+
+    /// Accept the RLP of a requested node, returning the hashes of its
+    /// children
+    pub fn deliver(&mut self, data: &[u8]) -> Result<Vec<H256>> {
+        let hash = ethereum_crypto::keccak256(data);
+        if !self.in_flight.remove(&hash) {
+            return Err(SyncError::InvalidState(format!("Unrequested trie node {:?}", hash)));
+        }
+
+        let node = Node::decode_raw(data)
+            .map_err(|e| SyncError::InvalidState(format!("Trie node {:?}: {}", hash, e)))?;
+        self.completed.insert(hash);
+
+        let mut children = Vec::new();
+        child_hashes(&node, &mut children);
+        Ok(children)
+    }
+
+    /// Put requested but undelivered hashes back in the queue
+    pub fn retry(&mut self, hashes: impl IntoIterator<Item = H256>) {
+        for hash in hashes {
+            if self.in_flight.remove(&hash) && self.queued.insert(hash) {
+                self.queue.push_front(hash);
+            }
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed.len()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.queue.is_empty() && self.in_flight.is_empty()
+    }
+}
+
+/// Hashed nodes `node` refers to, looking through inline children
+fn child_hashes(node: &Node, out: &mut Vec<H256>) {
+    let visit = |child: &NodeRef, out: &mut Vec<H256>| match child {
+        NodeRef::Hash(hash) => out.push(*hash),
+        NodeRef::Inline(inline) => child_hashes(inline, out),
+    };
+
+    match node {
+        Node::Extension { node, .. } => visit(node, out),
+        Node::Branch { children, .. } => {
+            for child in children.iter().flatten() {
+                visit(child, out);
+            }
+        }
+        Node::Empty | Node::Leaf { .. } => {}
+    }
+}
+
+/// Delivered trie nodes held back until everything below them is stored
+///
+/// A node is only written once all of its children are, so a stored node
+/// always comes with its whole subtree. An interrupted sync leaves no
+/// partial subtrees behind and the next run can skip whatever is stored.
+#[derive(Debug, Default)]
+struct PendingNodes {
+    nodes: HashMap<H256, PendingNode>,
+    /// Pending parents of each child that isn't stored yet
+    parents: HashMap<H256, Vec<H256>>,
+    /// Nodes staged in the current batch but not written yet
+    staged: HashSet<H256>,
+}
+
+#[derive(Debug)]
+struct PendingNode {
+    data: Vec<u8>,
+    missing: usize,
+}
+
+impl PendingNodes {
+    fn is_staged(&self, hash: &H256) -> bool {
+        self.staged.contains(hash)
+    }
+
+    /// Hold the node `hash` until its `missing` children are stored, staging
+    /// it in `batch` right away if there are none
+    fn insert(&mut self, hash: H256, data: Vec<u8>, missing: HashSet<H256>, batch: &mut dyn WriteBatch) {
+        if missing.is_empty() {
+            self.stage(hash, data, batch);
+            return;
+        }
+        for child in &missing {
+            self.parents.entry(*child).or_default().push(hash);
+        }
+        self.nodes.insert(hash, PendingNode { data, missing: missing.len() });
+    }
+
+    /// Stage `hash` and every pending parent that was only waiting on it
+    fn stage(&mut self, hash: H256, data: Vec<u8>, batch: &mut dyn WriteBatch) {
+        let mut ready = vec![(hash, data)];
+        while let Some((hash, data)) = ready.pop() {
+            batch.put(&node_key(&hash), &data);
+            self.staged.insert(hash);
+
+            for parent in self.parents.remove(&hash).unwrap_or_default() {
+                let Some(node) = self.nodes.get_mut(&parent) else { continue };
+                node.missing -= 1;
+                if node.missing == 0 {
+                    if let Some(node) = self.nodes.remove(&parent) {
+                        ready.push((parent, node.data));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The staged batch was written
+    fn flushed(&mut self) {
+        self.staged.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
 
 pub struct StateSync<D: Database> {
     db: Arc<D>,
-    state_trie: PatriciaTrie<D>,
-    pending_nodes: VecDeque<H256>,
-    downloaded_nodes: HashMap<H256, Vec<u8>>,
+    provider: Option<Arc<dyn NodeProvider>>,
+    max_state_request: usize,
 }
 
 impl<D: Database + 'static> StateSync<D> {
     pub fn new(db: Arc<D>) -> Self {
-        let state_trie = PatriciaTrie::new(db.clone());
-        
         Self {
             db,
-            state_trie,
-            pending_nodes: VecDeque::new(),
-            downloaded_nodes: HashMap::new(),
+            provider: None,
+            max_state_request: SyncConfig::default().max_state_request,
         }
     }
     
+    pub fn with_node_provider(mut self, provider: Arc<dyn NodeProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+    
+    /// Limit on trie nodes requested but not yet delivered
+    pub fn with_max_state_request(mut self, max_state_request: usize) -> Self {
+        self.max_state_request = max_state_request;
+        self
+    }
+    
     pub async fn sync_state_root(&mut self, state_root: H256) -> Result<()> {
         tracing::info!("Syncing state root: {:?}", state_root);
         
-        // Start with root node
-        self.pending_nodes.push_back(state_root);
+        let fetched = self.sync_trie(state_root).await?;
+        tracing::info!("State root {:?} complete, fetched {} nodes", state_root, fetched);
+        
+        Ok(())
+    }
+    
+    /// Fetch every node of the trie under `root` that isn't stored locally
+    ///
+    /// Nodes are requested `max_state_request` at a time, each hash at most
+    /// once while it's in flight. The children of delivered nodes are
+    /// queued, subtrees already present are skipped. Nodes are written
+    /// bottom-up, each one only after all of its children, so a sync that
+    /// stops halfway resumes from what it stored. Returns the number of
+    /// nodes fetched.
+    pub async fn sync_trie(&self, root: H256) -> Result<usize> {
+        let provider = self.provider.as_ref()
+            .ok_or_else(|| SyncError::NetworkError("No trie node provider".to_string()))?;
+        
+        // A stored root means the whole trie was synced before
+        if self.has_node(&root)? {
+            return Ok(0);
+        }
         
-        while let Some(node_hash) = self.pending_nodes.pop_front() {
-            if self.downloaded_nodes.contains_key(&node_hash) {
-                continue;
+        let mut scheduler = NodeScheduler::new(root, self.max_state_request);
+        let mut pending = PendingNodes::default();
+        let mut fetched = 0;
+        loop {
+            let batch = scheduler.next_batch();
+            if batch.is_empty() {
+                break;
             }
             
-            // Download node from network
-            let node_data = self.download_node(node_hash).await?;
+            let nodes = provider.nodes(&batch).await?;
+            if nodes.is_empty() {
+                return Err(SyncError::NetworkError(format!(
+                    "No trie nodes returned for {} requested", batch.len()
+                )));
+            }
             
-            // Parse node and add children to pending
-            self.process_node(&node_data)?;
+            let mut write = self.db.batch();
+            let mut delivered = HashSet::new();
+            for data in nodes {
+                let children = scheduler.deliver(&data)?;
+                let hash = ethereum_crypto::keccak256(&data);
+                
+                let mut missing = HashSet::new();
+                for child in children {
+                    if pending.is_staged(&child) || self.has_node(&child)? {
+                        scheduler.skip(child);
+                    } else {
+                        scheduler.enqueue(child);
+                        missing.insert(child);
+                    }
+                }
+                pending.insert(hash, data, missing, &mut *write);
+                delivered.insert(hash);
+            }
+            self.db.write_batch(write)?;
+            pending.flushed();
+            fetched += delivered.len();
             
-            // Store node
-            self.downloaded_nodes.insert(node_hash, node_data);
+            scheduler.retry(batch.into_iter().filter(|hash| !delivered.contains(hash)));
+        }
+        
+        if !pending.is_empty() {
+            return Err(SyncError::InvalidState(format!(
+                "State {:?} incomplete: {} nodes still miss children", root, pending.len()
+            )));
         }
         
-        // Verify complete state
-        self.verify_state(state_root)?;
+        // The root must resolve locally once every node arrived
+        TrieReadView::at_root(self.db.clone(), root)
+            .map_err(|e| SyncError::InvalidState(format!("State {:?} incomplete: {}", root, e)))?;
         
-        Ok(())
+        Ok(fetched)
+    }
+    
+    fn has_node(&self, hash: &H256) -> Result<bool> {
+        Ok(self.db.contains(&node_key(hash))?)
     }
     
     pub async fn verify_account_proof(
@@ -97,21 +353,8 @@ impl<D: Database + 'static> StateSync<D> {
     ) -> Result<()> {
         tracing::debug!("Syncing storage for account {:?}", account);
         
-        // Similar to state sync but for storage trie
-        let mut storage_trie = PatriciaTrie::new(self.db.clone());
-        
-        // Download storage trie nodes
-        self.pending_nodes.clear();
-        self.pending_nodes.push_back(storage_root);
-        
-        while let Some(node_hash) = self.pending_nodes.pop_front() {
-            let node_data = self.download_node(node_hash).await?;
-            self.process_node(&node_data)?;
-            
-            // Store in storage trie
-            let key = format!("storage:{}:{}", hex::encode(account), hex::encode(node_hash));
-            self.db.put(key.as_bytes(), &node_data)?;
-        }
+        // Storage tries share the node store with the account trie
+        self.sync_trie(storage_root).await?;
         
         Ok(())
     }
@@ -135,33 +378,10 @@ impl<D: Database + 'static> StateSync<D> {
         Ok(())
     }
     
-    async fn download_node(&self, hash: H256) -> Result<Vec<u8>> {
-        // In real implementation, would download from network
-        // For now, return empty data
-        Ok(vec![])
-    }
-    
     async fn download_code(&self, hash: H256) -> Result<Vec<u8>> {
         // In real implementation, would download from network
         Ok(vec![])
     }
-    
-    fn process_node(&mut self, node_data: &[u8]) -> Result<()> {
-        // Parse node and extract child references
-        // This would use RLP decoding to parse the node
-        
-        // For branch nodes, add all non-empty children to pending
-        // For extension nodes, add the child node to pending
-        
-        Ok(())
-    }
-    
-    fn verify_state(&self, state_root: H256) -> Result<()> {
-        // Verify that all downloaded nodes form a complete trie
-        // with the given state root
-        
-        Ok(())
-    }
 }
 
 #[derive(Debug, Clone)]
@@ -170,4 +390,164 @@ pub struct AccountState {
     pub balance: U256,
     pub storage_root: H256,
     pub code_hash: H256,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_trie::PatriciaTrie;
+    use parking_lot::Mutex;
+
+    const WINDOW: usize = 4;
+
+    /// Serves nodes from a source database, answering at most half of each
+    /// request so the scheduler has to retry
+    struct MockNodes {
+        db: Arc<MemoryDatabase>,
+        served: Mutex<HashMap<H256, usize>>,
+    }
+
+    #[async_trait]
+    impl NodeProvider for MockNodes {
+        async fn nodes(&self, hashes: &[H256]) -> Result<Vec<Vec<u8>>> {
+            assert!(hashes.len() <= WINDOW);
+            let unique: HashSet<_> = hashes.iter().collect();
+            assert_eq!(unique.len(), hashes.len());
+
+            let mut served = self.served.lock();
+            let mut nodes = Vec::new();
+            for hash in hashes.iter().take((hashes.len() + 1) / 2) {
+                nodes.push(self.db.get(&node_key(hash)).unwrap().unwrap());
+                *served.entry(*hash).or_default() += 1;
+            }
+            Ok(nodes)
+        }
+    }
+
+    fn entry(i: u64) -> (H256, Vec<u8>) {
+        (ethereum_crypto::keccak256(&i.to_be_bytes()), vec![i as u8; 40])
+    }
+
+    #[tokio::test]
+    async fn test_sync_trie_fetches_each_node_once() {
+        let source = Arc::new(MemoryDatabase::new());
+        let mut trie = PatriciaTrie::new(source.clone());
+        for i in 0..64 {
+            let (key, value) = entry(i);
+            trie.insert(key.as_bytes(), value).unwrap();
+        }
+        let root = trie.commit().unwrap();
+
+        let provider = Arc::new(MockNodes { db: source, served: Mutex::new(HashMap::new()) });
+        let db = Arc::new(MemoryDatabase::new());
+        let sync = StateSync::new(db.clone())
+            .with_node_provider(provider.clone())
+            .with_max_state_request(WINDOW);
+
+        let fetched = sync.sync_trie(root).await.unwrap();
+
+        let served = provider.served.lock().clone();
+        assert!(served.contains_key(&root));
+        assert_eq!(fetched, served.len());
+        assert!(served.values().all(|&count| count == 1));
+
+        let view = TrieReadView::at_root(db, root).unwrap();
+        for i in 0..64 {
+            let (key, value) = entry(i);
+            assert_eq!(view.get(key.as_bytes()).unwrap(), Some(value));
+        }
+
+        // Nothing left to fetch the second time
+        assert_eq!(sync.sync_trie(root).await.unwrap(), 0);
+    }
+
+    /// Fails every request after the first `limit`
+    struct FlakyNodes {
+        inner: MockNodes,
+        limit: usize,
+        calls: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl NodeProvider for FlakyNodes {
+        async fn nodes(&self, hashes: &[H256]) -> Result<Vec<Vec<u8>>> {
+            {
+                let mut calls = self.calls.lock();
+                *calls += 1;
+                if *calls > self.limit {
+                    return Err(SyncError::PeerDisconnected);
+                }
+            }
+            self.inner.nodes(hashes).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_sync_resumes() {
+        let source = Arc::new(MemoryDatabase::new());
+        let mut trie = PatriciaTrie::new(source.clone());
+        for i in 0..64 {
+            let (key, value) = entry(i);
+            trie.insert(key.as_bytes(), value).unwrap();
+        }
+        let root = trie.commit().unwrap();
+
+        let db = Arc::new(MemoryDatabase::new());
+        let flaky = FlakyNodes {
+            inner: MockNodes { db: source.clone(), served: Mutex::new(HashMap::new()) },
+            limit: 12,
+            calls: Mutex::new(0),
+        };
+        let sync = StateSync::new(db.clone())
+            .with_node_provider(Arc::new(flaky))
+            .with_max_state_request(WINDOW);
+        assert!(matches!(sync.sync_trie(root).await, Err(SyncError::PeerDisconnected)));
+
+        // Some nodes made it, but none is stored without its children
+        assert!(!db.contains(&node_key(&root)).unwrap());
+        let mut stored = HashSet::new();
+        let mut iter = db.iter_prefix(b"t");
+        while let Some(entry) = iter.next() {
+            let (key, data) = entry.unwrap();
+            let mut children = Vec::new();
+            child_hashes(&Node::decode_raw(&data).unwrap(), &mut children);
+            assert!(children.iter().all(|child| db.contains(&node_key(child)).unwrap()));
+            stored.insert(H256::from_slice(&key[1..]));
+        }
+        drop(iter);
+        assert!(!stored.is_empty());
+
+        // The next run only fetches what's missing
+        let provider = Arc::new(MockNodes { db: source, served: Mutex::new(HashMap::new()) });
+        let sync = StateSync::new(db.clone())
+            .with_node_provider(provider.clone())
+            .with_max_state_request(WINDOW);
+        sync.sync_trie(root).await.unwrap();
+        assert!(provider.served.lock().keys().all(|hash| !stored.contains(hash)));
+
+        let view = TrieReadView::at_root(db, root).unwrap();
+        for i in 0..64 {
+            let (key, value) = entry(i);
+            assert_eq!(view.get(key.as_bytes()).unwrap(), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_scheduler_window_and_dedup() {
+        let mut scheduler = NodeScheduler::new(H256::repeat_byte(1), 2);
+        scheduler.enqueue(H256::repeat_byte(1));
+        scheduler.enqueue(H256::repeat_byte(2));
+        scheduler.enqueue(H256::repeat_byte(3));
+
+        let batch = scheduler.next_batch();
+        assert_eq!(batch, vec![H256::repeat_byte(1), H256::repeat_byte(2)]);
+        assert!(scheduler.next_batch().is_empty());
+
+        // In flight hashes aren't queued again
+        scheduler.enqueue(H256::repeat_byte(2));
+        scheduler.retry(vec![H256::repeat_byte(2)]);
+        assert_eq!(scheduler.in_flight(), 1);
+        assert_eq!(scheduler.next_batch(), vec![H256::repeat_byte(2)]);
+        assert!(!scheduler.is_done());
+    }
+}
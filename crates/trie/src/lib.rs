@@ -4,11 +4,19 @@ pub mod node;
 pub mod nibbles;
 pub mod trie;
 pub mod proof;
+pub mod ordered;
+pub mod view;
+pub mod range;
+pub mod prune;
 
 pub use node::*;
 pub use nibbles::*;
 pub use trie::*;
 pub use proof::*;
+pub use ordered::{empty_root, ordered_trie_root, trie_root};
+pub use view::{node_key, TrieReadView};
+pub use range::{collect_range, verify_range_proof};
+pub use prune::{prune_unreachable, TriePruner, DEFAULT_RETAINED_ROOTS};
 
 #[derive(Debug, Error)]
 pub enum TrieError {
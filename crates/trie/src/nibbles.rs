@@ -133,11 +133,7 @@ impl Nibbles {
             nibbles.push(byte >> 4);
             nibbles.push(byte & 0x0f);
         }
-        
-        // Remove padding if necessary
-        if !odd_len && nibbles.len() > 0 && nibbles[nibbles.len() - 1] == 0 {
-            nibbles.pop();
-        }
+
         
         Ok((Self { data: nibbles }, is_leaf))
     }
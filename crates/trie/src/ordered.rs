@@ -0,0 +1,190 @@
+use ethereum_types::H256;
+use ethereum_rlp::Encoder;
+use crate::Nibbles;
+
+const EMPTY_ROOT_BYTES: [u8; 32] = [
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6,
+    0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0,
+    0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+];
+
+/// Root of an empty trie: keccak256(rlp(""))
+pub fn empty_root() -> H256 {
+    H256::from(EMPTY_ROOT_BYTES)
+}
+
+/// Root of a trie keyed by `rlp(index)` of each item, as used for the
+/// transactions, receipts and withdrawals roots of a block
+pub fn ordered_trie_root<I>(items: I) -> H256
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let pairs = items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let key = ethereum_rlp::encode(&(index as u64));
+            (key.as_slice().to_vec(), item.as_ref().to_vec())
+        })
+        .collect();
+
+    trie_root(pairs)
+}
+
+/// Root of an in-memory trie built from the given key/value pairs
+///
+/// Computed directly from the sorted pairs without going through the
+/// database, so it is cheap enough to run on every imported block.
+pub fn trie_root(pairs: Vec<(Vec<u8>, Vec<u8>)>) -> H256 {
+    if pairs.is_empty() {
+        return empty_root();
+    }
+
+    let mut entries: Vec<(Nibbles, Vec<u8>)> = pairs
+        .into_iter()
+        .map(|(key, value)| (Nibbles::from_bytes(&key), value))
+        .collect();
+    entries.sort_by(|a, b| a.0.as_slice().cmp(b.0.as_slice()));
+    entries.dedup_by(|later, earlier| {
+        // Keep the last value written for a key
+        if later.0 == earlier.0 {
+            std::mem::swap(&mut earlier.1, &mut later.1);
+            true
+        } else {
+            false
+        }
+    });
+
+    ethereum_crypto::keccak256(&encode_node(&entries, 0))
+}
+
+pub(crate) fn encode_node(entries: &[(Nibbles, Vec<u8>)], depth: usize) -> Vec<u8> {
+    if entries.len() == 1 {
+        let (key, value) = &entries[0];
+        return encode_list(&[
+            encode_string(&key.slice_from(depth).encode_compact(true)),
+            encode_string(value),
+        ]);
+    }
+
+    // Entries are sorted, so the first and last key bound the shared prefix
+    let first = entries[0].0.slice_from(depth);
+    let last = entries[entries.len() - 1].0.slice_from(depth);
+    let shared = first.common_prefix_len(&last);
+
+    if shared > 0 {
+        let child = encode_node(entries, depth + shared);
+        return encode_list(&[
+            encode_string(&first.slice(0, shared).encode_compact(false)),
+            child_reference(child),
+        ]);
+    }
+
+    let mut items = Vec::with_capacity(17);
+    let mut value = Vec::new();
+    let mut start = 0;
+
+    // A key ending exactly at this depth sorts first and becomes the branch value
+    if entries[0].0.len() == depth {
+        value = entries[0].1.clone();
+        start = 1;
+    }
+
+    for nibble in 0..16u8 {
+        let end = start + entries[start..]
+            .iter()
+            .take_while(|(key, _)| key.get(depth) == Some(nibble))
+            .count();
+
+        if end == start {
+            items.push(encode_string(&[]));
+        } else {
+            items.push(child_reference(encode_node(&entries[start..end], depth + 1)));
+        }
+        start = end;
+    }
+    items.push(encode_string(&value));
+
+    encode_list(&items)
+}
+
+/// Children shorter than 32 bytes are embedded, larger ones referenced by hash
+pub(crate) fn child_reference(encoded: Vec<u8>) -> Vec<u8> {
+    if encoded.len() < 32 {
+        encoded
+    } else {
+        encode_string(ethereum_crypto::keccak256(&encoded).as_bytes())
+    }
+}
+
+pub(crate) fn encode_string(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    encoder.encode_bytes(bytes);
+    encoder.finish()
+}
+
+fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: Vec<u8> = items.concat();
+    let mut encoded = Vec::with_capacity(payload.len() + 9);
+
+    if payload.len() < 56 {
+        encoded.push(0xc0 + payload.len() as u8);
+    } else {
+        let len_bytes: Vec<u8> = (payload.len() as u64)
+            .to_be_bytes()
+            .iter()
+            .skip_while(|b| **b == 0)
+            .copied()
+            .collect();
+        encoded.push(0xf7 + len_bytes.len() as u8);
+        encoded.extend_from_slice(&len_bytes);
+    }
+
+    encoded.extend_from_slice(&payload);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(hex_str: &str) -> H256 {
+        H256::from_slice(&hex::decode(hex_str).unwrap())
+    }
+
+    #[test]
+    fn test_empty_root() {
+        assert_eq!(ordered_trie_root(Vec::<Vec<u8>>::new()), empty_root());
+        assert_eq!(empty_root(), ethereum_crypto::keccak256(&[0x80]));
+    }
+
+    #[test]
+    fn test_trie_root_known_vector() {
+        let pairs = vec![
+            (b"do".to_vec(), b"verb".to_vec()),
+            (b"horse".to_vec(), b"stallion".to_vec()),
+            (b"doge".to_vec(), b"coin".to_vec()),
+            (b"dog".to_vec(), b"puppy".to_vec()),
+        ];
+
+        assert_eq!(
+            trie_root(pairs),
+            hash("5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84")
+        );
+    }
+
+    #[test]
+    fn test_ordered_root_uses_rlp_index_keys() {
+        let items: Vec<Vec<u8>> = (0..200u32).map(|i| i.to_be_bytes().to_vec()).collect();
+
+        let pairs = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (ethereum_rlp::encode(&(i as u64)).as_slice().to_vec(), item.clone()))
+            .collect();
+
+        assert_eq!(ordered_trie_root(&items), trie_root(pairs));
+    }
+}
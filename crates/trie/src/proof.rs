@@ -155,12 +155,12 @@ where
                 if let Some(child_ref) = &children[nibble] {
                     match child_ref {
                         NodeRef::Inline(child) => {
-                            collect_proof_nodes(child, key, key_index + 1, proof, load_node)
+                            collect_proof_nodes(child, key, key_index + 1, proof, load_node)?
                         }
                         NodeRef::Hash(hash) => {
                             let child_data = load_node(hash)?;
                             let child = Node::decode_raw(&child_data)?;
-                            collect_proof_nodes(&child, key, key_index + 1, proof, load_node)
+                            collect_proof_nodes(&child, key, key_index + 1, proof, load_node)?
                         }
                     }
                 }
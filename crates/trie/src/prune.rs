@@ -0,0 +1,250 @@
+use ethereum_types::H256;
+use ethereum_rlp::Decoder;
+use ethereum_storage::Database;
+use std::collections::{HashSet, VecDeque};
+use crate::{empty_root, Node, NodeRef, Result};
+
+/// Default number of committed roots kept readable, matching the minimum
+/// number of blocks the history expiry keeps around
+pub const DEFAULT_RETAINED_ROOTS: usize = 128;
+
+/// Length of a node key, the `t` prefix followed by the node hash
+const NODE_KEY_LEN: usize = 33;
+
+/// Window of recently committed state roots whose nodes must survive pruning
+///
+/// Updating a trie writes new nodes next to the ones they replace. Recording
+/// a root is cheap; the sweep in [`TriePruner::prune`] walks every retained
+/// state and is meant to run on the history expiry's schedule rather than
+/// after each commit. It deletes every node that is not reachable from a
+/// root still inside the window, so overwritten values stop taking up space
+/// while recent states stay readable.
+#[derive(Debug, Clone)]
+pub struct TriePruner {
+    retained: usize,
+    roots: VecDeque<H256>,
+}
+
+impl TriePruner {
+    pub fn new(retained: usize) -> Self {
+        Self {
+            retained: retained.max(1),
+            roots: VecDeque::new(),
+        }
+    }
+
+    /// Record a committed root, dropping the oldest one once the window is full
+    pub fn retain_root(&mut self, root: H256) {
+        if self.roots.back() != Some(&root) {
+            self.roots.push_back(root);
+        }
+        while self.roots.len() > self.retained {
+            self.roots.pop_front();
+        }
+    }
+
+    /// Delete the nodes no retained root reaches, returning how many were deleted
+    pub fn prune<D: Database + ?Sized>(&self, db: &D) -> Result<usize> {
+        prune_unreachable(db, self.roots.iter())
+    }
+
+    /// Roots that are still readable, oldest first
+    pub fn retained_roots(&self) -> impl Iterator<Item = &H256> {
+        self.roots.iter()
+    }
+}
+
+impl Default for TriePruner {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETAINED_ROOTS)
+    }
+}
+
+/// Delete every stored node that is not reachable from one of the state
+/// `roots`, returning the number of nodes deleted
+///
+/// Leaves of a state trie are accounts, so the storage trie each of them
+/// points at is kept along with it.
+pub fn prune_unreachable<'a, D, I>(db: &D, roots: I) -> Result<usize>
+where
+    D: Database + ?Sized,
+    I: IntoIterator<Item = &'a H256>,
+{
+    let mut live = HashSet::new();
+    for root in roots {
+        mark(db, root, TrieKind::State, &mut live)?;
+    }
+
+    let mut batch = db.batch();
+    let mut iter = db.iter_prefix(b"t");
+    while let Some(entry) = iter.next() {
+        let (key, _) = entry?;
+        // Other tables may share the prefix, node keys are the only ones of this length
+        if key.len() == NODE_KEY_LEN && !live.contains(&H256::from_slice(&key[1..])) {
+            batch.delete(&key);
+        }
+    }
+    drop(iter);
+
+    let deleted = batch.len();
+    if deleted > 0 {
+        db.write_batch(batch)?;
+    }
+    Ok(deleted)
+}
+
+/// Whether the leaves being walked are accounts or storage slots
+#[derive(Debug, Clone, Copy)]
+enum TrieKind {
+    State,
+    Storage,
+}
+
+fn mark<D: Database + ?Sized>(db: &D, hash: &H256, kind: TrieKind, live: &mut HashSet<H256>) -> Result<()> {
+    if !live.insert(*hash) {
+        return Ok(());
+    }
+    // Read the database directly, the node cache may still hold deleted nodes.
+    // A node that was never written has nothing below it to keep.
+    match db.get(&crate::node_key(hash))? {
+        Some(data) => mark_children(db, &Node::decode_raw(&data)?, kind, live),
+        None => Ok(()),
+    }
+}
+
+fn mark_children<D: Database + ?Sized>(db: &D, node: &Node, kind: TrieKind, live: &mut HashSet<H256>) -> Result<()> {
+    let mark_ref = |node_ref: &NodeRef, live: &mut HashSet<H256>| match node_ref {
+        NodeRef::Hash(hash) => mark(db, hash, kind, live),
+        NodeRef::Inline(child) => mark_children(db, child, kind, live),
+    };
+
+    match node {
+        Node::Extension { node: child_ref, .. } => mark_ref(child_ref, live),
+        Node::Branch { children, .. } => {
+            for child_ref in children.iter().flatten() {
+                mark_ref(child_ref, live)?;
+            }
+            Ok(())
+        }
+        Node::Leaf { value, .. } => match (kind, account_storage_root(value)) {
+            (TrieKind::State, Some(storage_root)) if storage_root != empty_root() => {
+                mark(db, &storage_root, TrieKind::Storage, live)
+            }
+            _ => Ok(()),
+        },
+        Node::Empty => Ok(()),
+    }
+}
+
+/// Storage root of an account encoded as `[nonce, balance, storage_root, code_hash]`
+fn account_storage_root(value: &[u8]) -> Option<H256> {
+    let item = Decoder::new(value).and_then(|mut decoder| decoder.decode_item()).ok()?;
+    match item.as_list()? {
+        [_, _, storage_root, _] => match storage_root.as_bytes()? {
+            bytes if bytes.len() == 32 => Some(H256::from_slice(bytes)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PatriciaTrie;
+    use ethereum_storage::MemoryDatabase;
+    use std::sync::Arc;
+
+    fn stored_nodes(db: &MemoryDatabase) -> usize {
+        let mut iter = db.iter_prefix(b"t");
+        let mut count = 0;
+        while let Some(entry) = iter.next() {
+            entry.unwrap();
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn test_pruning_removes_orphaned_nodes() {
+        let db = Arc::new(MemoryDatabase::new());
+        let mut trie = PatriciaTrie::new(db.clone());
+        let mut pruner = TriePruner::new(2);
+
+        trie.insert(b"other", vec![0xaa; 40]).unwrap();
+        let mut roots = Vec::new();
+        for i in 0..20u8 {
+            trie.insert(b"counter", vec![i; 40]).unwrap();
+            roots.push(trie.commit_pruned(&mut pruner).unwrap());
+        }
+
+        // Committing only records roots, nothing is deleted until the sweep
+        assert!(db.contains(&crate::node_key(&roots[0])).unwrap());
+        assert!(pruner.prune(&*db).unwrap() > 0);
+
+        // Only the two most recent states are left
+        let latest = *roots.last().unwrap();
+        assert_eq!(pruner.retained_roots().copied().collect::<Vec<_>>(), roots[18..].to_vec());
+        assert!(!db.contains(&crate::node_key(&roots[0])).unwrap());
+        assert!(db.contains(&crate::node_key(&roots[18])).unwrap());
+
+        let reloaded = PatriciaTrie::new_with_root(db.clone(), latest).unwrap();
+        assert_eq!(reloaded.get(b"counter").unwrap(), Some(vec![19; 40]));
+        assert_eq!(reloaded.get(b"other").unwrap(), Some(vec![0xaa; 40]));
+
+        // Pruning to the latest root drops everything the older one needed
+        let before = stored_nodes(&db);
+        let deleted = prune_unreachable(&*db, [&latest]).unwrap();
+        assert!(deleted > 0);
+        assert_eq!(stored_nodes(&db), before - deleted);
+        assert!(!db.contains(&crate::node_key(&roots[18])).unwrap());
+
+        let reloaded = PatriciaTrie::new_with_root(db, latest).unwrap();
+        assert_eq!(reloaded.get(b"counter").unwrap(), Some(vec![19; 40]));
+        assert_eq!(reloaded.get(b"other").unwrap(), Some(vec![0xaa; 40]));
+    }
+
+    fn account(storage_root: H256) -> Vec<u8> {
+        let mut fields = ethereum_rlp::Encoder::new();
+        fields.encode_u64(1);
+        fields.encode_bytes(&[0x0f, 0x42, 0x40]);
+        fields.encode_bytes(storage_root.as_bytes());
+        fields.encode_bytes(ethereum_crypto::keccak256(&[]).as_bytes());
+
+        let mut encoder = ethereum_rlp::Encoder::new();
+        encoder.encode_list_payload(&fields.finish());
+        encoder.finish()
+    }
+
+    #[test]
+    fn test_pruning_keeps_account_storage() {
+        let db = Arc::new(MemoryDatabase::new());
+        let mut storage = PatriciaTrie::new(db.clone());
+        for slot in 0..8u8 {
+            storage.insert(&[slot; 32], vec![slot + 1; 33]).unwrap();
+        }
+        let storage_root = storage.commit().unwrap();
+
+        let mut state = PatriciaTrie::new(db.clone());
+        state.insert(&[0x11; 32], account(storage_root)).unwrap();
+        state.insert(&[0x22; 32], account(empty_root())).unwrap();
+        let mut pruner = TriePruner::new(1);
+        let root = state.commit_pruned(&mut pruner).unwrap();
+
+        // A storage trie nothing references anymore is swept
+        let mut orphan = PatriciaTrie::new(db.clone());
+        orphan.insert(&[0x33; 32], vec![0xee; 40]).unwrap();
+        let orphan_root = orphan.commit().unwrap();
+
+        assert!(pruner.prune(&*db).unwrap() > 0);
+        assert!(!db.contains(&crate::node_key(&orphan_root)).unwrap());
+        assert!(db.contains(&crate::node_key(&storage_root)).unwrap());
+
+        let storage = PatriciaTrie::new_with_root(db.clone(), storage_root).unwrap();
+        for slot in 0..8u8 {
+            assert_eq!(storage.get(&[slot; 32]).unwrap(), Some(vec![slot + 1; 33]));
+        }
+        let state = PatriciaTrie::new_with_root(db, root).unwrap();
+        assert_eq!(state.get(&[0x11; 32]).unwrap(), Some(account(storage_root)));
+    }
+}
@@ -0,0 +1,277 @@
+use ethereum_types::H256;
+use std::collections::HashMap;
+use crate::ordered::{child_reference, encode_node, encode_string};
+use crate::{trie_root, Node, NodeRef, Nibbles, Result};
+
+/// Entries with keys in `[origin, limit]`, in key order
+///
+/// Keys are fixed length, as in the state and storage tries, and `origin`
+/// and `limit` have that same length. Collection stops after the entry that
+/// brings the returned keys and values to `byte_limit`, so a non-empty range
+/// always yields at least one entry.
+pub fn collect_range<F>(
+    root: &Node,
+    origin: &[u8],
+    limit: &[u8],
+    byte_limit: usize,
+    load_node: F,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>>
+where
+    F: Fn(&H256) -> Result<Node>,
+{
+    let mut collector = RangeCollector {
+        bounds: Bounds::new(origin, limit),
+        byte_limit,
+        bytes: 0,
+        entries: Vec::new(),
+        load_node,
+    };
+    collector.collect(root, Vec::new())?;
+
+    Ok(collector.entries)
+}
+
+/// Check a range proof as served by snap: `keys`/`values` must be exactly
+/// the trie entries from `origin` up to the last returned key
+///
+/// `proof` holds the nodes on the paths to `origin` and to the last key.
+/// Without a proof the range has to be the whole trie. An empty range with a
+/// proof claims there are no keys at or after `origin`.
+pub fn verify_range_proof(
+    root: H256,
+    origin: &[u8],
+    keys: &[Vec<u8>],
+    values: &[Vec<u8>],
+    proof: &[Vec<u8>],
+) -> Result<bool> {
+    if keys.len() != values.len() {
+        return Ok(false);
+    }
+    let ordered = keys.windows(2).all(|pair| pair[0] < pair[1]);
+    let in_bounds = keys.iter().all(|key| key.len() == origin.len() && key.as_slice() >= origin);
+    if !ordered || !in_bounds {
+        return Ok(false);
+    }
+
+    if proof.is_empty() {
+        let pairs = keys.iter().cloned().zip(values.iter().cloned()).collect();
+        return Ok(trie_root(pairs) == root);
+    }
+
+    let mut nodes = HashMap::with_capacity(proof.len());
+    for encoded in proof {
+        nodes.insert(ethereum_crypto::keccak256(encoded), Node::decode_raw(encoded)?);
+    }
+    let root_node = match nodes.get(&root) {
+        Some(node) => node.clone(),
+        None => return Ok(false),
+    };
+
+    let last = match keys.last() {
+        Some(key) => key.clone(),
+        None => vec![0xff; origin.len()],
+    };
+    let verifier = RangeVerifier {
+        bounds: Bounds::new(origin, &last),
+        entries: keys
+            .iter()
+            .zip(values)
+            .map(|(key, value)| (Nibbles::from_bytes(key), value.clone()))
+            .collect(),
+        nodes,
+    };
+
+    Ok(verifier.verify_node(&root_node, Vec::new())? == Some(keys.len()))
+}
+
+/// Inclusive key range in nibbles
+struct Bounds {
+    origin: Vec<u8>,
+    limit: Vec<u8>,
+}
+
+impl Bounds {
+    fn new(origin: &[u8], limit: &[u8]) -> Self {
+        Self {
+            origin: Nibbles::from_bytes(origin).as_slice().to_vec(),
+            limit: Nibbles::from_bytes(limit).as_slice().to_vec(),
+        }
+    }
+
+    /// Smallest and largest full key below `path`
+    fn span(&self, path: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let pad = |fill: u8| {
+            let mut key = path.to_vec();
+            key.resize(self.origin.len().max(path.len()), fill);
+            key
+        };
+        (pad(0), pad(0x0f))
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        key >= self.origin.as_slice() && key <= self.limit.as_slice()
+    }
+
+    /// No key below `path` falls in the range
+    fn excludes(&self, path: &[u8]) -> bool {
+        let (lowest, highest) = self.span(path);
+        highest < self.origin || lowest > self.limit
+    }
+
+    /// Every key below `path` falls in the range
+    fn covers(&self, path: &[u8]) -> bool {
+        let (lowest, highest) = self.span(path);
+        lowest >= self.origin && highest <= self.limit
+    }
+}
+
+struct RangeCollector<F> {
+    bounds: Bounds,
+    byte_limit: usize,
+    bytes: usize,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    load_node: F,
+}
+
+impl<F> RangeCollector<F>
+where
+    F: Fn(&H256) -> Result<Node>,
+{
+    /// Walk the subtrie at `path` in key order, false once the byte limit is hit
+    fn collect(&mut self, node: &Node, path: Vec<u8>) -> Result<bool> {
+        match node {
+            Node::Empty => Ok(true),
+
+            Node::Leaf { key, value } => {
+                let mut full = path;
+                full.extend_from_slice(key.as_slice());
+                if !self.bounds.contains(&full) {
+                    return Ok(true);
+                }
+
+                let key = Nibbles::new(full).to_bytes();
+                self.bytes += key.len() + value.len();
+                self.entries.push((key, value.clone()));
+                Ok(self.bytes < self.byte_limit)
+            }
+
+            Node::Extension { key, node: child } => {
+                let mut child_path = path;
+                child_path.extend_from_slice(key.as_slice());
+                self.collect_child(child, child_path)
+            }
+
+            Node::Branch { children, .. } => {
+                for (nibble, child) in children.iter().enumerate() {
+                    if let Some(child) = child {
+                        let mut child_path = path.clone();
+                        child_path.push(nibble as u8);
+                        if !self.collect_child(child, child_path)? {
+                            return Ok(false);
+                        }
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    fn collect_child(&mut self, child: &NodeRef, path: Vec<u8>) -> Result<bool> {
+        if self.bounds.excludes(&path) {
+            return Ok(true);
+        }
+
+        let node = match child {
+            NodeRef::Inline(node) => (**node).clone(),
+            NodeRef::Hash(hash) => (self.load_node)(hash)?,
+        };
+        self.collect(&node, path)
+    }
+}
+
+struct RangeVerifier {
+    bounds: Bounds,
+    entries: Vec<(Nibbles, Vec<u8>)>,
+    nodes: HashMap<H256, Node>,
+}
+
+impl RangeVerifier {
+    /// Number of range entries accounted for below `path`, `None` if the
+    /// subtrie contradicts them
+    fn verify_node(&self, node: &Node, path: Vec<u8>) -> Result<Option<usize>> {
+        match node {
+            Node::Empty => Ok(Some(0)),
+
+            Node::Leaf { key, value } => {
+                let mut full = path;
+                full.extend_from_slice(key.as_slice());
+                if !self.bounds.contains(&full) {
+                    return Ok(Some(0));
+                }
+
+                let found = self.entries
+                    .binary_search_by(|(entry_key, _)| entry_key.as_slice().cmp(&full))
+                    .ok()
+                    .filter(|index| self.entries[*index].1 == *value);
+                Ok(found.map(|_| 1))
+            }
+
+            Node::Extension { key, node: child } => {
+                let mut child_path = path;
+                child_path.extend_from_slice(key.as_slice());
+                self.verify_child(child, child_path)
+            }
+
+            Node::Branch { children, .. } => {
+                let mut accounted = 0;
+                for (nibble, child) in children.iter().enumerate() {
+                    if let Some(child) = child {
+                        let mut child_path = path.clone();
+                        child_path.push(nibble as u8);
+                        match self.verify_child(child, child_path)? {
+                            Some(count) => accounted += count,
+                            None => return Ok(None),
+                        }
+                    }
+                }
+                Ok(Some(accounted))
+            }
+        }
+    }
+
+    fn verify_child(&self, child: &NodeRef, path: Vec<u8>) -> Result<Option<usize>> {
+        if self.bounds.excludes(&path) {
+            return Ok(Some(0));
+        }
+
+        if self.bounds.covers(&path) {
+            // Subtries fully inside the range aren't in the proof, they must
+            // hash to exactly the entries returned under their path
+            let start = self.entries.partition_point(|(key, _)| key.as_slice() < path.as_slice());
+            let end = start + self.entries[start..]
+                .iter()
+                .take_while(|(key, _)| key.as_slice().starts_with(&path))
+                .count();
+            if start == end {
+                return Ok(None);
+            }
+
+            let expected = child_reference(encode_node(&self.entries[start..end], path.len()));
+            let actual = match child {
+                NodeRef::Hash(hash) => encode_string(hash.as_bytes()),
+                NodeRef::Inline(node) => node.encode_raw(),
+            };
+            return Ok((expected == actual).then_some(end - start));
+        }
+
+        // Subtries straddling a range boundary lie on a proven path
+        let node = match child {
+            NodeRef::Inline(node) => (**node).clone(),
+            NodeRef::Hash(hash) => match self.nodes.get(hash) {
+                Some(node) => node.clone(),
+                None => return Ok(None),
+            },
+        };
+        self.verify_node(&node, path)
+    }
+}
@@ -1,12 +1,16 @@
 use ethereum_types::H256;
 use ethereum_storage::{Database, WriteBatch};
+use std::collections::HashMap;
 use std::sync::Arc;
-use crate::{Node, NodeRef, Nibbles, Result, TrieError};
+use crate::{Node, NodeRef, Nibbles, Result, TriePruner, TrieReadView};
+use crate::view;
 
 pub struct PatriciaTrie<D: Database> {
     db: Arc<D>,
     root: Node,
     root_hash: Option<H256>,
+    /// Hashed nodes created since the last commit
+    dirty: HashMap<H256, Node>,
 }
 
 impl<D: Database> PatriciaTrie<D> {
@@ -15,6 +19,7 @@ impl<D: Database> PatriciaTrie<D> {
             db,
             root: Node::Empty,
             root_hash: None,
+            dirty: HashMap::new(),
         }
     }
     
@@ -24,6 +29,7 @@ impl<D: Database> PatriciaTrie<D> {
             db,
             root,
             root_hash: Some(root_hash),
+            dirty: HashMap::new(),
         })
     }
     
@@ -34,6 +40,12 @@ impl<D: Database> PatriciaTrie<D> {
         self.root_hash.unwrap()
     }
     
+    /// Snapshot of the current root that can serve concurrent reads
+    pub fn read_only_view(&self) -> TrieReadView<D> {
+        let root_hash = self.root_hash.unwrap_or_else(|| self.root.hash());
+        TrieReadView::new(self.db.clone(), self.root.clone(), root_hash)
+    }
+    
     pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let nibbles = Nibbles::from_bytes(key);
         self.get_at_node(&self.root, &nibbles, 0)
@@ -120,7 +132,7 @@ impl<D: Database> PatriciaTrie<D> {
                                 key: leaf_key.slice_from(1),
                                 value: leaf_value,
                             };
-                            children[nibble] = Some(NodeRef::from_node(new_leaf));
+                            children[nibble] = Some(self.node_ref(new_leaf));
                         }
                         
                         // Insert new value
@@ -132,7 +144,7 @@ impl<D: Database> PatriciaTrie<D> {
                                 key: remaining_key.slice_from(1),
                                 value,
                             };
-                            children[nibble] = Some(NodeRef::from_node(new_leaf));
+                            children[nibble] = Some(self.node_ref(new_leaf));
                         }
                     }
                     Ok(branch)
@@ -149,7 +161,7 @@ impl<D: Database> PatriciaTrie<D> {
                                 key: leaf_key.slice_from(common_len + 1),
                                 value: leaf_value,
                             };
-                            children[nibble] = Some(NodeRef::from_node(new_leaf));
+                            children[nibble] = Some(self.node_ref(new_leaf));
                         } else {
                             *branch_value = Some(leaf_value);
                         }
@@ -161,7 +173,7 @@ impl<D: Database> PatriciaTrie<D> {
                                 key: remaining_key.slice_from(common_len + 1),
                                 value,
                             };
-                            children[nibble] = Some(NodeRef::from_node(new_leaf));
+                            children[nibble] = Some(self.node_ref(new_leaf));
                         } else {
                             *branch_value = Some(value);
                         }
@@ -170,7 +182,7 @@ impl<D: Database> PatriciaTrie<D> {
                     if common_len > 0 {
                         Ok(Node::Extension {
                             key: common_prefix,
-                            node: NodeRef::from_node(branch),
+                            node: self.node_ref(branch),
                         })
                     } else {
                         Ok(branch)
@@ -188,7 +200,7 @@ impl<D: Database> PatriciaTrie<D> {
                     let new_child = self.insert_at_node(child, key, key_index + common_len, value)?;
                     Ok(Node::Extension {
                         key: ext_key,
-                        node: NodeRef::from_node(new_child),
+                        node: self.node_ref(new_child),
                     })
                 } else {
                     // Partial match, split extension
@@ -208,7 +220,7 @@ impl<D: Database> PatriciaTrie<D> {
                                     key: ext_remainder.slice_from(1),
                                     node: child_ref,
                                 };
-                                children[nibble] = Some(NodeRef::from_node(new_ext));
+                                children[nibble] = Some(self.node_ref(new_ext));
                             }
                         }
                         
@@ -221,14 +233,14 @@ impl<D: Database> PatriciaTrie<D> {
                                 key: key_remainder.slice_from(1),
                                 value,
                             };
-                            children[nibble] = Some(NodeRef::from_node(new_leaf));
+                            children[nibble] = Some(self.node_ref(new_leaf));
                         }
                     }
                     
                     if common_len > 0 {
                         Ok(Node::Extension {
                             key: common_prefix,
-                            node: NodeRef::from_node(branch),
+                            node: self.node_ref(branch),
                         })
                     } else {
                         Ok(branch)
@@ -247,7 +259,7 @@ impl<D: Database> PatriciaTrie<D> {
                         Some(ref child_ref) => self.resolve_node_ref(child_ref)?,
                     };
                     let new_child = self.insert_at_node(child, key, key_index + 1, value)?;
-                    children[nibble] = Some(NodeRef::from_node(new_child));
+                    children[nibble] = Some(self.node_ref(new_child));
                 }
                 Ok(Node::Branch { children, value: branch_value })
             }
@@ -299,7 +311,7 @@ impl<D: Database> PatriciaTrie<D> {
                             }
                             _ => Ok((Node::Extension {
                                 key: ext_key,
-                                node: NodeRef::from_node(new_child),
+                                node: self.node_ref(new_child),
                             }, true))
                         }
                     } else {
@@ -329,7 +341,7 @@ impl<D: Database> PatriciaTrie<D> {
                             if matches!(new_child, Node::Empty) {
                                 children[nibble] = None;
                             } else {
-                                children[nibble] = Some(NodeRef::from_node(new_child));
+                                children[nibble] = Some(self.node_ref(new_child));
                             }
                             let compacted = self.try_compact_branch(children, value)?;
                             Ok((compacted, true))
@@ -384,30 +396,54 @@ impl<D: Database> PatriciaTrie<D> {
     fn resolve_node_ref(&self, node_ref: &NodeRef) -> Result<Node> {
         match node_ref {
             NodeRef::Inline(node) => Ok((**node).clone()),
-            NodeRef::Hash(hash) => Self::load_node(&*self.db, hash),
+            NodeRef::Hash(hash) => match self.dirty.get(hash) {
+                Some(node) => Ok(node.clone()),
+                None => Self::load_node(&*self.db, hash),
+            },
+        }
+    }
+    
+    /// Reference `node` from its parent, keeping hashed nodes until the next commit
+    fn node_ref(&mut self, node: Node) -> NodeRef {
+        let node_ref = NodeRef::from_node(node.clone());
+        if let NodeRef::Hash(hash) = node_ref {
+            self.dirty.insert(hash, node);
         }
+        node_ref
     }
     
     fn load_node(db: &D, hash: &H256) -> Result<Node> {
-        let key = Self::node_key(hash);
-        let data = db.get(&key)?
-            .ok_or(TrieError::KeyNotFound)?;
-        Node::decode_raw(&data)
+        view::load_node(db, hash)
     }
     
     fn node_key(hash: &H256) -> Vec<u8> {
-        let mut key = vec![b't']; // 't' for trie node
-        key.extend_from_slice(hash.as_bytes());
-        key
+        view::node_key(hash)
     }
     
     pub fn commit(&mut self) -> Result<H256> {
         let mut batch = self.db.batch();
-        self.commit_node(&self.root, &mut *batch)?;
+        let root_hash = self.commit_to(&mut *batch)?;
         self.db.write_batch(batch)?;
+        Ok(root_hash)
+    }
+    
+    /// Stage the new nodes in `batch` instead of writing them, so they can
+    /// be stored atomically with other data
+    pub fn commit_to(&mut self, batch: &mut dyn WriteBatch) -> Result<H256> {
+        self.commit_node(&self.root, batch)?;
+        // Nodes replaced before they were committed are simply dropped
+        self.dirty.clear();
         Ok(self.root_hash())
     }
     
+    /// Commit and record the new root with `pruner`, whose next sweep keeps
+    /// the nodes it needs
+    pub fn commit_pruned(&mut self, pruner: &mut TriePruner) -> Result<H256> {
+        let root_hash = self.commit()?;
+        pruner.retain_root(root_hash);
+        Ok(root_hash)
+    }
+    
     fn commit_node(&self, node: &Node, batch: &mut dyn WriteBatch) -> Result<()> {
         let encoded = node.encode_raw();
         if encoded.len() >= 32 {
@@ -418,15 +454,11 @@ impl<D: Database> PatriciaTrie<D> {
         
         match node {
             Node::Extension { node: child_ref, .. } => {
-                if let NodeRef::Inline(child) = child_ref {
-                    self.commit_node(child, batch)?;
-                }
+                self.commit_node_ref(child_ref, batch)?;
             }
             Node::Branch { children, .. } => {
                 for child_ref in children.iter().flatten() {
-                    if let NodeRef::Inline(child) = child_ref {
-                        self.commit_node(child, batch)?;
-                    }
+                    self.commit_node_ref(child_ref, batch)?;
                 }
             }
             _ => {}
@@ -434,6 +466,17 @@ impl<D: Database> PatriciaTrie<D> {
         
         Ok(())
     }
+    
+    fn commit_node_ref(&self, node_ref: &NodeRef, batch: &mut dyn WriteBatch) -> Result<()> {
+        match node_ref {
+            NodeRef::Inline(child) => self.commit_node(child, batch),
+            // Hashed children that are not dirty are already stored
+            NodeRef::Hash(hash) => match self.dirty.get(hash) {
+                Some(child) => self.commit_node(child, batch),
+                None => Ok(()),
+            },
+        }
+    }
 }
 
 #[cfg(test)]
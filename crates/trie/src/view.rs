@@ -0,0 +1,233 @@
+use ethereum_types::H256;
+use ethereum_storage::Database;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::{collect_range, generate_proof, MerkleProof, Node, NodeRef, Nibbles, Result, TrieError};
+
+/// Maximum number of decoded nodes kept per thread before the cache is reset
+const NODE_CACHE_LIMIT: usize = 16_384;
+
+thread_local! {
+    // Nodes are content addressed, so one cache per thread can be shared by
+    // every trie and view running on it without any locking
+    static NODE_CACHE: RefCell<HashMap<H256, Node>> = RefCell::new(HashMap::new());
+}
+
+/// Load a hashed node from the database through the calling thread's cache
+pub(crate) fn load_node<D: Database + ?Sized>(db: &D, hash: &H256) -> Result<Node> {
+    if let Some(node) = NODE_CACHE.with(|cache| cache.borrow().get(hash).cloned()) {
+        return Ok(node);
+    }
+
+    let data = db.get(&node_key(hash))?
+        .ok_or(TrieError::KeyNotFound)?;
+    let node = Node::decode_raw(&data)?;
+
+    NODE_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= NODE_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(*hash, node.clone());
+    });
+
+    Ok(node)
+}
+
+/// Database key a hashed trie node is stored under
+pub fn node_key(hash: &H256) -> Vec<u8> {
+    let mut key = vec![b't']; // 't' for trie node
+    key.extend_from_slice(hash.as_bytes());
+    key
+}
+
+/// Read-only snapshot of a trie at a fixed root
+///
+/// The view owns its root and resolves hashed children independently, so it
+/// can be shared between threads and tokio tasks while the originating trie
+/// keeps being modified.
+#[derive(Clone)]
+pub struct TrieReadView<D: Database> {
+    db: Arc<D>,
+    root: Node,
+    root_hash: H256,
+}
+
+impl<D: Database> TrieReadView<D> {
+    pub(crate) fn new(db: Arc<D>, root: Node, root_hash: H256) -> Self {
+        Self { db, root, root_hash }
+    }
+
+    /// Open a view directly on a committed root
+    pub fn at_root(db: Arc<D>, root_hash: H256) -> Result<Self> {
+        let root = load_node(&*db, &root_hash)?;
+        Ok(Self::new(db, root, root_hash))
+    }
+
+    /// Root hash captured when the view was created
+    pub fn root_hash(&self) -> H256 {
+        self.root_hash
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let key = Nibbles::from_bytes(key);
+        let mut key_index = 0;
+        let mut current = self.root.clone();
+
+        loop {
+            let next = match &current {
+                Node::Empty => return Ok(None),
+
+                Node::Leaf { key: leaf_key, value } => {
+                    return if key.slice_from(key_index) == *leaf_key {
+                        Ok(Some(value.clone()))
+                    } else {
+                        Ok(None)
+                    };
+                }
+
+                Node::Extension { key: ext_key, node: child_ref } => {
+                    let remaining_key = key.slice_from(key_index);
+                    if ext_key.common_prefix_len(&remaining_key) != ext_key.len() {
+                        return Ok(None);
+                    }
+                    key_index += ext_key.len();
+                    self.resolve(child_ref)?
+                }
+
+                Node::Branch { children, value } => {
+                    if key_index == key.len() {
+                        return Ok(value.clone());
+                    }
+                    let nibble = key.get(key_index).unwrap() as usize;
+                    key_index += 1;
+                    match &children[nibble] {
+                        None => return Ok(None),
+                        Some(child_ref) => self.resolve(child_ref)?,
+                    }
+                }
+            };
+            current = next;
+        }
+    }
+
+    /// Entries with keys in `[origin, limit]` up to roughly `byte_limit` bytes,
+    /// see `collect_range`
+    pub fn range(&self, origin: &[u8], limit: &[u8], byte_limit: usize) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        collect_range(&self.root, origin, limit, byte_limit, |hash| load_node(&*self.db, hash))
+    }
+
+    /// Proof of the value, or the absence of a value, stored under `key`
+    pub fn prove(&self, key: &[u8]) -> Result<MerkleProof> {
+        generate_proof(&self.root, key, |hash| {
+            self.db.get(&node_key(hash))?.ok_or(TrieError::KeyNotFound)
+        })
+    }
+
+    /// Encoded node found by following `path` from the root, `None` if the
+    /// path ends inside a leaf, an extension or an empty slot
+    pub fn node_at_path(&self, path: &Nibbles) -> Result<Option<Vec<u8>>> {
+        let mut index = 0;
+        let mut current = self.root.clone();
+
+        loop {
+            if index == path.len() {
+                return Ok(Some(current.encode_raw()));
+            }
+
+            current = match &current {
+                Node::Empty | Node::Leaf { .. } => return Ok(None),
+
+                Node::Extension { key: ext_key, node: child_ref } => {
+                    let remaining = path.slice_from(index);
+                    if remaining.len() < ext_key.len()
+                        || ext_key.common_prefix_len(&remaining) != ext_key.len()
+                    {
+                        return Ok(None);
+                    }
+                    index += ext_key.len();
+                    self.resolve(child_ref)?
+                }
+
+                Node::Branch { children, .. } => {
+                    let nibble = path.get(index).unwrap() as usize;
+                    index += 1;
+                    match &children[nibble] {
+                        None => return Ok(None),
+                        Some(child_ref) => self.resolve(child_ref)?,
+                    }
+                }
+            };
+        }
+    }
+
+    fn resolve(&self, node_ref: &NodeRef) -> Result<Node> {
+        match node_ref {
+            NodeRef::Inline(node) => Ok((**node).clone()),
+            NodeRef::Hash(hash) => load_node(&*self.db, hash),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PatriciaTrie;
+    use ethereum_storage::MemoryDatabase;
+
+    fn assert_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_view_is_sync() {
+        assert_sync::<TrieReadView<MemoryDatabase>>();
+    }
+
+    #[test]
+    fn test_view_is_isolated_from_later_writes() {
+        let db = Arc::new(MemoryDatabase::new());
+        let mut trie = PatriciaTrie::new(db);
+
+        trie.insert(b"test", vec![1, 2, 3]).unwrap();
+        let view = trie.read_only_view();
+
+        trie.insert(b"test", vec![4, 5, 6]).unwrap();
+        trie.insert(b"test2", vec![7, 8, 9]).unwrap();
+
+        assert_eq!(view.get(b"test").unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(view.get(b"test2").unwrap(), None);
+        assert_eq!(trie.get(b"test").unwrap(), Some(vec![4, 5, 6]));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_reads() {
+        let db = Arc::new(MemoryDatabase::new());
+        let root_hash = {
+            let mut trie = PatriciaTrie::new(db.clone());
+            trie.insert(b"test", vec![1, 2, 3]).unwrap();
+            trie.insert(b"test2", vec![4, 5, 6]).unwrap();
+            trie.insert(b"test3", vec![7, 8, 9]).unwrap();
+            trie.commit().unwrap()
+        };
+
+        let view = Arc::new(TrieReadView::at_root(db, root_hash).unwrap());
+        let mut tasks = Vec::new();
+        for _ in 0..8 {
+            let view = view.clone();
+            tasks.push(tokio::spawn(async move {
+                (
+                    view.get(b"test").unwrap(),
+                    view.get(b"test2").unwrap(),
+                    view.get(b"test3").unwrap(),
+                )
+            }));
+        }
+
+        for task in tasks {
+            let (a, b, c) = task.await.unwrap();
+            assert_eq!(a, Some(vec![1, 2, 3]));
+            assert_eq!(b, Some(vec![4, 5, 6]));
+            assert_eq!(c, Some(vec![7, 8, 9]));
+        }
+    }
+}
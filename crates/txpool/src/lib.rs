@@ -1,12 +1,19 @@
 use ethereum_types::{H256, U256, Address};
-use ethereum_core::Transaction;
+use ethereum_core::{
+    next_base_fee, BlobAndProof, BlobGasConfig, BlobPool, BlobSidecar, BlobTransactionData, Header, Transaction,
+    MAX_INIT_CODE_SIZE,
+};
+use ethereum_core::eip7691::Eip7691Error;
 use parking_lot::RwLock;
 use priority_queue::PriorityQueue;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 use std::sync::Arc;
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
+use std::time::Instant;
 use thiserror::Error;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
 use tokio::time::{self, Duration};
 
 #[derive(Debug, Error)]
@@ -23,6 +30,9 @@ pub enum TxPoolError {
     #[error("Transaction pool is full")]
     PoolFull,
     
+    #[error("Transaction underpriced, the pool's cheapest pays {floor}")]
+    Underpriced { floor: U256 },
+    
     #[error("Invalid transaction: {0}")]
     InvalidTransaction(String),
     
@@ -31,10 +41,19 @@ pub enum TxPoolError {
     
     #[error("Gas limit exceeded")]
     GasLimitExceeded,
+    
+    #[error("Replacement transaction underpriced")]
+    ReplacementUnderpriced,
+    
+    #[error("Account transaction limit exceeded")]
+    AccountLimitExceeded,
 }
 
 pub type Result<T> = std::result::Result<T, TxPoolError>;
 
+/// Largest transaction accepted by default, 128KB as in geth
+pub const DEFAULT_MAX_TX_SIZE: usize = 128 * 1024;
+
 #[derive(Debug, Clone)]
 pub struct TxPoolConfig {
     pub max_size: usize,
@@ -44,6 +63,13 @@ pub struct TxPoolConfig {
     pub account_queue: usize,
     pub global_queue: usize,
     pub lifetime: Duration,
+    /// Largest encoded transaction admitted, in bytes
+    pub max_tx_size: usize,
+    /// Senders whose transactions are kept until mined, they never expire
+    pub locals: Vec<Address>,
+    /// Base fee transactions are priced against until `set_base_fee` is
+    /// called for the next block
+    pub base_fee: U256,
 }
 
 impl Default for TxPoolConfig {
@@ -56,17 +82,52 @@ impl Default for TxPoolConfig {
             account_queue: 64,
             global_queue: 1024,
             lifetime: Duration::from_secs(3 * 60 * 60), // 3 hours
+            max_tx_size: DEFAULT_MAX_TX_SIZE,
+            locals: Vec::new(),
+            base_fee: U256::zero(),
         }
     }
 }
 
+/// Account nonces as of the chain head
+pub trait NonceProvider: Send + Sync {
+    /// Nonce of the next transaction `address` can get included, zero for
+    /// unknown accounts
+    fn account_nonce(&self, address: &Address) -> U256;
+}
+
+/// Nonces kept in memory, for tests and nodes without state
+#[derive(Default)]
+pub struct MemoryNonceProvider {
+    nonces: RwLock<HashMap<Address, U256>>,
+}
+
+impl MemoryNonceProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn set_nonce(&self, address: Address, nonce: U256) {
+        self.nonces.write().insert(address, nonce);
+    }
+}
+
+impl NonceProvider for MemoryNonceProvider {
+    fn account_nonce(&self, address: &Address) -> U256 {
+        self.nonces.read().get(address).copied().unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PooledTransaction {
     pub tx: Transaction,
     pub hash: H256,
     pub gas_price: U256,
     pub from: Address,
-    pub timestamp: std::time::Instant,
+    pub timestamp: Instant,
+    /// Submitted through this node rather than gossiped, exempt from the
+    /// price limit, eviction and expiry
+    pub is_local: bool,
 }
 
 impl PooledTransaction {
@@ -80,12 +141,15 @@ impl PooledTransaction {
             hash,
             gas_price,
             from,
-            timestamp: std::time::Instant::now(),
+            timestamp: Instant::now(),
+            is_local: false,
         }
     }
     
-    pub fn effective_gas_price(&self) -> U256 {
-        self.gas_price
+    /// Price per gas paid in a block with `base_fee`, below `base_fee`
+    /// when the transaction can't be included in it
+    pub fn effective_gas_price(&self, base_fee: U256) -> U256 {
+        self.tx.effective_gas_price(base_fee)
     }
 }
 
@@ -112,13 +176,70 @@ impl PartialEq for TxPriority {
     }
 }
 
+/// Counters updated by each expiry pass
+#[derive(Default)]
+struct MaintenanceMetrics {
+    runs: AtomicU64,
+    expired_total: AtomicU64,
+    last_expired: AtomicU64,
+    last_scanned: AtomicU64,
+    last_scan_micros: AtomicU64,
+}
+
+impl MaintenanceMetrics {
+    fn record(&self, expired: usize, scanned: usize, duration: Duration) {
+        self.runs.fetch_add(1, AtomicOrdering::Relaxed);
+        self.expired_total.fetch_add(expired as u64, AtomicOrdering::Relaxed);
+        self.last_expired.store(expired as u64, AtomicOrdering::Relaxed);
+        self.last_scanned.store(scanned as u64, AtomicOrdering::Relaxed);
+        self.last_scan_micros.store(duration.as_micros() as u64, AtomicOrdering::Relaxed);
+    }
+}
+
+/// Snapshot of the pool maintenance metrics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceStats {
+    pub runs: u64,
+    pub expired_total: u64,
+    /// Transactions removed by the latest pass
+    pub last_expired: u64,
+    /// Index entries the latest pass looked at
+    pub last_scanned: u64,
+    pub last_scan_duration: Duration,
+}
+
+/// Pool contents per sender keyed by nonce, what `txpool_content` and
+/// `txpool_inspect` serve
+#[derive(Debug, Clone, Default)]
+pub struct TxPoolContent {
+    pub pending: HashMap<Address, BTreeMap<U256, PooledTransaction>>,
+    pub queued: HashMap<Address, BTreeMap<U256, PooledTransaction>>,
+}
+
+/// Code holding more than one of the pool's locks takes them in the order
+/// `all`, `price_heap`, `by_time`, `pending`, `queued`, `blobs`. `base_fee`
+/// is only ever held on its own.
 pub struct TransactionPool {
     config: TxPoolConfig,
+    /// Source of the on-chain nonce pending transactions continue from
+    nonces: Arc<dyn NonceProvider>,
     pending: Arc<RwLock<HashMap<Address, VecDeque<PooledTransaction>>>>,
     queued: Arc<RwLock<HashMap<Address, VecDeque<PooledTransaction>>>>,
     all: Arc<RwLock<HashMap<H256, PooledTransaction>>>,
-    price_heap: Arc<RwLock<PriorityQueue<H256, TxPriority>>>,
+    /// Base fee of the next block, what effective prices are taken at
+    base_fee: Arc<RwLock<U256>>,
+    /// Min-heap on effective gas price of non-local transactions, the peek
+    /// is the cheapest one that can be evicted
+    price_heap: Arc<RwLock<PriorityQueue<H256, Reverse<TxPriority>>>>,
+    /// Non-local transactions ordered by arrival, oldest first
+    by_time: Arc<RwLock<BTreeSet<(Instant, H256)>>>,
+    /// Sidecars of the blob transactions in the pool
+    blobs: Arc<RwLock<BlobPool>>,
+    metrics: Arc<MaintenanceMetrics>,
     events_tx: broadcast::Sender<TxPoolEvent>,
+    /// Whole transactions as they are added, for subscribers that would
+    /// otherwise look each hash up and race with eviction
+    full_tx: broadcast::Sender<Arc<PooledTransaction>>,
 }
 
 #[derive(Debug, Clone)]
@@ -129,21 +250,47 @@ pub enum TxPoolEvent {
 }
 
 impl TransactionPool {
-    pub fn new(config: TxPoolConfig) -> Self {
+    pub fn new(config: TxPoolConfig, nonces: Arc<dyn NonceProvider>) -> Self {
         let (events_tx, _) = broadcast::channel(1000); // Buffer size of 1000 events
+        let (full_tx, _) = broadcast::channel(1000);
+        // Every blob transaction is also in the main pool, which stays within
+        // `max_size`, so the blob pool never has to evict
+        let blobs = BlobPool::new(BlobGasConfig::post_7691(), config.max_size);
+        
+        let base_fee = config.base_fee;
         
         Self {
             config,
+            nonces,
+            base_fee: Arc::new(RwLock::new(base_fee)),
             pending: Arc::new(RwLock::new(HashMap::new())),
             queued: Arc::new(RwLock::new(HashMap::new())),
             all: Arc::new(RwLock::new(HashMap::new())),
             price_heap: Arc::new(RwLock::new(PriorityQueue::new())),
+            by_time: Arc::new(RwLock::new(BTreeSet::new())),
+            blobs: Arc::new(RwLock::new(blobs)),
+            metrics: Arc::new(MaintenanceMetrics::default()),
             events_tx,
+            full_tx,
         }
     }
     
     pub fn add_transaction(&self, tx: Transaction) -> Result<H256> {
-        let pooled = PooledTransaction::new(tx);
+        self.insert(PooledTransaction::new(tx))
+    }
+    
+    /// Add a transaction submitted through this node's own RPC
+    ///
+    /// Local transactions skip the price limit and are never evicted or
+    /// expired, they stay until mined or replaced.
+    pub fn add_local_transaction(&self, tx: Transaction) -> Result<H256> {
+        let mut pooled = PooledTransaction::new(tx);
+        pooled.is_local = true;
+        self.insert(pooled)
+    }
+    
+    fn insert(&self, mut pooled: PooledTransaction) -> Result<H256> {
+        pooled.is_local |= self.config.locals.contains(&pooled.from);
         let hash = pooled.hash;
         
         // Check if transaction already exists
@@ -151,11 +298,26 @@ impl TransactionPool {
             return Err(TxPoolError::AlreadyExists);
         }
         
+        self.validate_size(&pooled.tx)?;
+        
         // Validate gas price
-        if pooled.gas_price < self.config.price_limit {
+        if !pooled.is_local && pooled.gas_price < self.config.price_limit {
             return Err(TxPoolError::GasPriceTooLow);
         }
         
+        // A transaction with a known sender and nonce replaces the pooled one
+        if let Some(old) = self.find_by_nonce(&pooled.from, pooled.tx.nonce()) {
+            return self.replace_transaction(old, pooled);
+        }
+        
+        // Future transactions only get the global queue's slots
+        let queued = pooled.tx.nonce() > self.get_next_nonce(&pooled.from);
+        if queued && self.queued_count() >= self.config.global_queue {
+            return Err(TxPoolError::PoolFull);
+        }
+        
+        self.make_account_room(&pooled)?;
+        
         // Check pool size
         if self.all.read().len() >= self.config.max_size {
             // Try to evict lower priced transaction
@@ -164,20 +326,192 @@ impl TransactionPool {
             }
         }
         
+        // Only pay for the copy when someone wants the whole transaction
+        let full = (self.full_tx.receiver_count() > 0).then(|| Arc::new(pooled.clone()));
+        
         // Add to pool
         self.add_to_pool(pooled)?;
         
         // Send event
         let _ = self.events_tx.send(TxPoolEvent::NewTransaction(hash));
+        if let Some(full) = full {
+            let _ = self.full_tx.send(full);
+        }
         
         Ok(hash)
     }
     
+    /// Pooled transaction of `from` with `nonce`, pending or queued
+    fn find_by_nonce(&self, from: &Address, nonce: U256) -> Option<PooledTransaction> {
+        let find = |lists: &HashMap<Address, VecDeque<PooledTransaction>>| {
+            lists.get(from)?.iter().find(|tx| tx.tx.nonce() == nonce).cloned()
+        };
+        find(&self.pending.read()).or_else(|| find(&self.queued.read()))
+    }
+    
+    /// Swap `old` for `new` in its pending or queued slot if `new` pays at
+    /// least `price_bump` percent more
+    fn replace_transaction(&self, old: PooledTransaction, mut new: PooledTransaction) -> Result<H256> {
+        let base_fee = self.base_fee();
+        let old_price = old.effective_gas_price(base_fee);
+        let new_price = new.effective_gas_price(base_fee);
+        let threshold = old_price.saturating_mul(U256::from(100 + self.config.price_bump)) / U256::from(100);
+        if new_price < threshold || new_price <= old_price {
+            return Err(TxPoolError::ReplacementUnderpriced);
+        }
+        
+        new.is_local |= old.is_local;
+        let hash = new.hash;
+        let full = (self.full_tx.receiver_count() > 0).then(|| Arc::new(new.clone()));
+        
+        self.all.write().remove(&old.hash);
+        self.price_heap.write().remove(&old.hash);
+        self.by_time.write().remove(&(old.timestamp, old.hash));
+        self.blobs.write().remove_transaction(&old.hash);
+        
+        for lists in [&self.pending, &self.queued] {
+            if let Some(slot) = lists.write()
+                .get_mut(&new.from)
+                .and_then(|txs| txs.iter_mut().find(|tx| tx.hash == old.hash))
+            {
+                *slot = new.clone();
+            }
+        }
+        
+        self.all.write().insert(hash, new.clone());
+        if !self.is_local(&new) {
+            self.price_heap.write().push(hash, Reverse(TxPriority(new_price)));
+            self.by_time.write().insert((new.timestamp, hash));
+        }
+        
+        let _ = self.events_tx.send(TxPoolEvent::Removed(old.hash));
+        let _ = self.events_tx.send(TxPoolEvent::NewTransaction(hash));
+        if let Some(full) = full {
+            let _ = self.full_tx.send(full);
+        }
+        
+        Ok(hash)
+    }
+    
+    /// Keep the sender of `new_tx` within `max_account_slots`, evicting its
+    /// cheapest transaction if `new_tx` pays more
+    ///
+    /// Among equally cheap transactions the highest nonce goes, anything
+    /// after an evicted nonce is no longer executable and moves to queued.
+    /// Local senders are not limited.
+    fn make_account_room(&self, new_tx: &PooledTransaction) -> Result<()> {
+        let from = new_tx.from;
+        if self.is_local(new_tx) {
+            return Ok(());
+        }
+        
+        let mut account = self.get_pending_by_address(&from);
+        account.extend(self.get_queued_by_address(&from));
+        if account.len() < self.config.max_account_slots {
+            return Ok(());
+        }
+        
+        let base_fee = self.base_fee();
+        let cheapest = account.iter()
+            .min_by_key(|tx| (tx.effective_gas_price(base_fee), Reverse(tx.tx.nonce())));
+        match cheapest {
+            Some(cheapest) if cheapest.effective_gas_price(base_fee) < new_tx.effective_gas_price(base_fee) => {
+                self.remove_transaction(&cheapest.hash);
+                Ok(())
+            }
+            _ => Err(TxPoolError::AccountLimitExceeded),
+        }
+    }
+    
+    /// Move pending transactions of `address` above `nonce` back to queued
+    fn demote_after(&self, address: &Address, nonce: U256) {
+        let mut pending = self.pending.write();
+        let Some(txs) = pending.get_mut(address) else { return };
+        
+        let demoted: Vec<PooledTransaction> = txs.iter().filter(|tx| tx.tx.nonce() > nonce).cloned().collect();
+        txs.retain(|tx| tx.tx.nonce() < nonce);
+        if txs.is_empty() {
+            pending.remove(address);
+        }
+        drop(pending);
+        
+        if !demoted.is_empty() {
+            self.queued.write().entry(*address).or_insert_with(VecDeque::new).extend(demoted);
+        }
+    }
+    
+    /// Add a 4844 transaction along with the blobs, commitments and proofs
+    /// it commits to
+    ///
+    /// The sidecar must match the transaction's versioned hashes. It is kept
+    /// for as long as the transaction stays in the pool.
+    ///
+    /// The sidecar is stored first, checking for one already pooled under
+    /// the same lock, so concurrent imports of a transaction can't both
+    /// store it, and the transaction is never pooled without its blobs.
+    pub fn add_blob_transaction(&self, tx: Transaction, sidecar: BlobSidecar) -> Result<H256> {
+        let blob_data = match &tx {
+            Transaction::Eip4844(blob_tx) => BlobTransactionData::new(
+                blob_tx.blob_versioned_hashes.clone(),
+                blob_tx.max_fee_per_blob_gas,
+            ).map_err(|e| TxPoolError::InvalidTransaction(e.to_string()))?,
+            _ => return Err(TxPoolError::InvalidTransaction("sidecar for a non-blob transaction".to_string())),
+        };
+        
+        let hash = tx.hash();
+        let priority_fee = tx.max_priority_fee_per_gas();
+        self.blobs.write()
+            .add_transaction_with_sidecar(hash, blob_data, priority_fee, sidecar)
+            .map_err(|e| match e {
+                Eip7691Error::AlreadyPooled(_) => TxPoolError::AlreadyExists,
+                e => TxPoolError::InvalidTransaction(e.to_string()),
+            })?;
+        
+        if let Err(e) = self.add_transaction(tx) {
+            self.blobs.write().remove_transaction(&hash);
+            return Err(e);
+        }
+        
+        Ok(hash)
+    }
+    
+    /// Sidecar of pooled blob transaction `hash`
+    pub fn get_blob_sidecar(&self, hash: &H256) -> Option<BlobSidecar> {
+        self.blobs.read().get_sidecar(hash)
+    }
+    
+    /// Blob with its commitment and proof, by versioned hash
+    pub fn get_blob(&self, versioned_hash: &H256) -> Option<BlobAndProof> {
+        self.blobs.read().get_blob(versioned_hash).cloned()
+    }
+    
+    /// Reject transactions too large to gossip and creations whose init
+    /// code exceeds the EIP-3860 limit
+    fn validate_size(&self, tx: &Transaction) -> Result<()> {
+        let size = tx.size();
+        if size > self.config.max_tx_size {
+            return Err(TxPoolError::InvalidTransaction(format!(
+                "transaction size {} exceeds limit {}",
+                size, self.config.max_tx_size
+            )));
+        }
+        
+        if tx.is_create() && tx.data().len() > MAX_INIT_CODE_SIZE {
+            return Err(TxPoolError::InvalidTransaction(format!(
+                "init code size {} exceeds limit {}",
+                tx.data().len(), MAX_INIT_CODE_SIZE
+            )));
+        }
+        
+        Ok(())
+    }
+    
     fn add_to_pool(&self, tx: PooledTransaction) -> Result<()> {
         let from = tx.from;
         let nonce = tx.tx.nonce();
         let hash = tx.hash;
-        let gas_price = tx.gas_price;
+        let gas_price = tx.effective_gas_price(self.base_fee());
+        let timestamp = tx.timestamp;
         
         // Get expected nonce for account
         let expected_nonce = self.get_next_nonce(&from);
@@ -185,8 +519,11 @@ impl TransactionPool {
         // Add to all transactions
         self.all.write().insert(hash, tx.clone());
         
-        // Add to price heap
-        self.price_heap.write().push(hash, TxPriority(gas_price));
+        // Local transactions are neither priced for eviction nor timed out
+        if !self.is_local(&tx) {
+            self.price_heap.write().push(hash, Reverse(TxPriority(gas_price)));
+            self.by_time.write().insert((timestamp, hash));
+        }
         
         if nonce == expected_nonce {
             // Add to pending
@@ -207,28 +544,40 @@ impl TransactionPool {
             // Nonce too low
             self.all.write().remove(&hash);
             self.price_heap.write().remove(&hash);
+            self.by_time.write().remove(&(timestamp, hash));
             return Err(TxPoolError::NonceTooLow);
         }
         
         Ok(())
     }
     
+    /// Make room for `new_tx` by dropping the cheapest pooled transaction
+    ///
+    /// Fails with `Underpriced` if `new_tx` pays no more than it, false if
+    /// there is nothing to evict. Transactions of the same sender after the
+    /// evicted one move to queued.
     fn evict_transaction(&self, new_tx: &PooledTransaction) -> Result<bool> {
+        let new_price = new_tx.effective_gas_price(self.base_fee());
+        let mut all = self.all.write();
         let mut heap = self.price_heap.write();
         
-        // Find transaction with lowest gas price
-        if let Some((hash, priority)) = heap.peek() {
-            if priority.0 < new_tx.gas_price {
-                let hash = *hash;
-                heap.remove(&hash);
-                
-                // Remove from pool
-                if let Some(old_tx) = self.all.write().remove(&hash) {
-                    self.remove_from_lists(&old_tx);
-                    let _ = self.events_tx.send(TxPoolEvent::Removed(hash));
-                    return Ok(true);
-                }
-            }
+        // Find transaction with lowest effective gas price
+        let Some((hash, Reverse(priority))) = heap.peek() else { return Ok(false) };
+        if priority.0 >= new_price {
+            return Err(TxPoolError::Underpriced { floor: priority.0 });
+        }
+        
+        let hash = *hash;
+        heap.remove(&hash);
+        drop(heap);
+        
+        // Remove from pool
+        if let Some(old_tx) = all.remove(&hash) {
+            drop(all);
+            self.remove_from_lists(&old_tx);
+            self.demote_after(&old_tx.from, old_tx.tx.nonce());
+            let _ = self.events_tx.send(TxPoolEvent::Removed(hash));
+            return Ok(true);
         }
         
         Ok(false)
@@ -238,6 +587,8 @@ impl TransactionPool {
         let from = tx.from;
         let hash = tx.hash;
         
+        self.by_time.write().remove(&(tx.timestamp, hash));
+        
         // Remove from pending
         if let Some(txs) = self.pending.write().get_mut(&from) {
             txs.retain(|t| t.hash != hash);
@@ -247,57 +598,109 @@ impl TransactionPool {
         if let Some(txs) = self.queued.write().get_mut(&from) {
             txs.retain(|t| t.hash != hash);
         }
+        
+        self.blobs.write().remove_transaction(&hash);
     }
     
+    fn is_local(&self, tx: &PooledTransaction) -> bool {
+        tx.is_local || self.config.locals.contains(&tx.from)
+    }
+    
+    /// Move queued transactions of `address` that continue its pending run
+    /// to pending, in nonce order
     fn promote_queued(&self, address: &Address) {
-        let expected_nonce = self.get_next_nonce(address);
+        let mut next_nonce = self.get_next_nonce(address);
         
         let mut queued = self.queued.write();
-        if let Some(txs) = queued.get_mut(address) {
-            let mut promoted = Vec::new();
-            
-            // Find transactions that can be promoted
-            txs.retain(|tx| {
-                if tx.tx.nonce() == expected_nonce + U256::from(promoted.len()) {
-                    promoted.push(tx.clone());
-                    false
-                } else {
-                    true
-                }
-            });
-            
-            // Add promoted transactions to pending
-            if !promoted.is_empty() {
-                let mut pending = self.pending.write();
-                let pending_txs = pending.entry(*address).or_insert_with(VecDeque::new);
-                for tx in promoted {
-                    let hash = tx.hash;
-                    pending_txs.push_back(tx);
-                    let _ = self.events_tx.send(TxPoolEvent::Promoted(hash));
-                }
+        let Some(txs) = queued.get_mut(address) else { return };
+        
+        let mut promoted = Vec::new();
+        while let Some(index) = txs.iter().position(|tx| tx.tx.nonce() == next_nonce) {
+            promoted.extend(txs.remove(index));
+            next_nonce += U256::one();
+        }
+        if txs.is_empty() {
+            queued.remove(address);
+        }
+        drop(queued);
+        
+        if !promoted.is_empty() {
+            let mut pending = self.pending.write();
+            let pending_txs = pending.entry(*address).or_insert_with(VecDeque::new);
+            for tx in promoted {
+                let hash = tx.hash;
+                pending_txs.push_back(tx);
+                let _ = self.events_tx.send(TxPoolEvent::Promoted(hash));
             }
         }
     }
     
-    fn get_next_nonce(&self, address: &Address) -> U256 {
-        // This should query the blockchain state
-        // For now, return the next nonce based on pending transactions
+    /// Nonce the next transaction of `address` needs to become pending: its
+    /// on-chain nonce plus the run of pending transactions continuing it
+    pub fn get_next_nonce(&self, address: &Address) -> U256 {
+        let mut next_nonce = self.nonces.account_nonce(address);
         if let Some(txs) = self.pending.read().get(address) {
-            if let Some(last_tx) = txs.back() {
-                return last_tx.tx.nonce() + U256::one();
+            for tx in txs {
+                if tx.tx.nonce() == next_nonce {
+                    next_nonce += U256::one();
+                }
             }
         }
-        U256::zero()
+        next_nonce
+    }
+    
+    /// Re-check `accounts` against their on-chain nonces, normally after a
+    /// block import
+    ///
+    /// Transactions below the new nonce were included or can't be anymore
+    /// and are dropped. Pending transactions that no longer continue the
+    /// on-chain nonce, e.g. after a reorg, go back to queued, then queued
+    /// ones that now fit are promoted. Returns the number dropped.
+    pub fn reset_accounts(&self, accounts: &[Address]) -> usize {
+        let mut dropped = 0;
+        
+        for address in accounts {
+            let base = self.nonces.account_nonce(address);
+            
+            let stale: Vec<H256> = self.pending.read().get(address).into_iter()
+                .chain(self.queued.read().get(address))
+                .flatten()
+                .filter(|tx| tx.tx.nonce() < base)
+                .map(|tx| tx.hash)
+                .collect();
+            for hash in &stale {
+                self.remove_transaction(hash);
+            }
+            dropped += stale.len();
+            
+            let gapped = self.pending.read().get(address)
+                .and_then(|txs| txs.front())
+                .map_or(false, |tx| tx.tx.nonce() != base);
+            if gapped {
+                if let Some(demoted) = self.pending.write().remove(address) {
+                    self.queued.write().entry(*address).or_insert_with(VecDeque::new).extend(demoted);
+                }
+            }
+            
+            self.pending.write().retain(|_, txs| !txs.is_empty());
+            self.promote_queued(address);
+        }
+        
+        dropped
     }
     
     pub fn get_transaction(&self, hash: &H256) -> Option<PooledTransaction> {
         self.all.read().get(hash).cloned()
     }
     
+    /// Drop `hash` from the pool, the sender's transactions after it are no
+    /// longer executable and move to queued
     pub fn remove_transaction(&self, hash: &H256) -> Option<PooledTransaction> {
-        if let Some(tx) = self.all.write().remove(hash) {
+        let removed = self.all.write().remove(hash);
+        if let Some(tx) = removed {
             self.price_heap.write().remove(hash);
             self.remove_from_lists(&tx);
+            self.demote_after(&tx.from, tx.tx.nonce());
             let _ = self.events_tx.send(TxPoolEvent::Removed(*hash));
             Some(tx)
         } else {
@@ -335,6 +738,30 @@ impl TransactionPool {
             .unwrap_or_default()
     }
     
+    /// Snapshot of the pending and queued transactions, in nonce order per
+    /// sender
+    pub fn content(&self) -> TxPoolContent {
+        let by_nonce = |lists: &HashMap<Address, VecDeque<PooledTransaction>>| {
+            lists.iter()
+                .filter(|(_, txs)| !txs.is_empty())
+                .map(|(address, txs)| {
+                    let txs = txs.iter().map(|tx| (tx.tx.nonce(), tx.clone())).collect();
+                    (*address, txs)
+                })
+                .collect()
+        };
+        
+        TxPoolContent {
+            pending: by_nonce(&self.pending.read()),
+            queued: by_nonce(&self.queued.read()),
+        }
+    }
+    
+    /// Pending and queued transaction counts, as in `txpool_status`
+    pub fn status(&self) -> (usize, usize) {
+        (self.pending_count(), self.queued_count())
+    }
+    
     pub fn pending_count(&self) -> usize {
         self.pending.read()
             .values()
@@ -353,79 +780,295 @@ impl TransactionPool {
         self.all.read().len()
     }
     
+    /// Lowest effective gas price in the pool, the price a new transaction
+    /// has to beat to displace another once the pool is full
+    pub fn min_gas_price(&self) -> Option<U256> {
+        self.price_heap.read().peek().map(|(_, Reverse(priority))| priority.0)
+    }
+    
+    /// Base fee of the next block
+    pub fn base_fee(&self) -> U256 {
+        *self.base_fee.read()
+    }
+    
+    /// Price transactions against `base_fee` from now on, normally the base
+    /// fee of the block after a new head
+    pub fn set_base_fee(&self, base_fee: U256) {
+        *self.base_fee.write() = base_fee;
+        
+        let all = self.all.read();
+        let mut heap = self.price_heap.write();
+        for (hash, tx) in all.iter() {
+            heap.change_priority(hash, Reverse(TxPriority(tx.effective_gas_price(base_fee))));
+        }
+    }
+    
+    /// Bring the pool up to date once `head` became the canonical head
+    ///
+    /// The pool is re-priced for the block after `head`, and `senders`, the
+    /// senders of the blocks applied and reverted to get there, are checked
+    /// against their new nonces. Returns the number of transactions dropped.
+    pub fn on_new_head(&self, head: &Header, senders: &[Address]) -> usize {
+        self.set_base_fee(next_base_fee(head).unwrap_or_default());
+        self.reset_accounts(senders)
+    }
+    
+    pub fn is_full(&self) -> bool {
+        self.total_count() >= self.config.max_size
+    }
+    
+    /// Occupancy of the pool, from 0.0 when empty to 1.0 when full
+    pub fn pressure(&self) -> f64 {
+        if self.config.max_size == 0 {
+            return 1.0;
+        }
+        self.total_count() as f64 / self.config.max_size as f64
+    }
+    
     pub fn clear(&self) {
         self.pending.write().clear();
         self.queued.write().clear();
         self.all.write().clear();
         self.price_heap.write().clear();
+        self.by_time.write().clear();
+        self.blobs.write().clear();
     }
     
-    pub async fn run_maintenance(&self) {
+    /// Expire old transactions every minute until `shutdown` turns true or
+    /// its sender is dropped
+    pub async fn run_maintenance(&self, mut shutdown: watch::Receiver<bool>) {
         let mut interval = time::interval(Duration::from_secs(60));
         
-        loop {
-            interval.tick().await;
-            
-            // Remove expired transactions
-            let now = std::time::Instant::now();
-            let mut expired = Vec::new();
-            
-            for (hash, tx) in self.all.read().iter() {
-                if now.duration_since(tx.timestamp) > self.config.lifetime {
-                    expired.push(*hash);
+        while !*shutdown.borrow() {
+            tokio::select! {
+                _ = interval.tick() => {}
+                changed = shutdown.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    continue;
                 }
             }
             
-            for hash in expired {
-                self.remove_transaction(&hash);
-            }
+            let expired = self.remove_expired(Instant::now());
             
             tracing::debug!(
-                "Transaction pool maintenance: {} pending, {} queued, {} total",
+                "Transaction pool maintenance: {} expired, {} pending, {} queued, {} total",
+                expired,
                 self.pending_count(),
                 self.queued_count(),
                 self.total_count()
             );
         }
+        
+        let stats = self.maintenance_stats();
+        tracing::debug!(
+            "Transaction pool maintenance stopped after {} runs, {} expired in total",
+            stats.runs,
+            stats.expired_total
+        );
+    }
+    
+    /// Run `run_maintenance` on its own task
+    pub fn spawn_maintenance(self: &Arc<Self>, shutdown: watch::Receiver<bool>) -> JoinHandle<()> {
+        let pool = self.clone();
+        tokio::spawn(async move { pool.run_maintenance(shutdown).await })
+    }
+    
+    /// Drop transactions older than the configured lifetime as of `now`
+    ///
+    /// Only the expired front of the time index is visited, so the work is
+    /// proportional to what gets removed rather than to the pool size.
+    /// Transactions from local senders are never indexed and never expire.
+    /// Later transactions of an expired one's sender move to queued.
+    pub fn remove_expired(&self, now: Instant) -> usize {
+        let started = Instant::now();
+        
+        let expired: Vec<H256> = self.by_time.read()
+            .iter()
+            .take_while(|(timestamp, _)| now.saturating_duration_since(*timestamp) > self.config.lifetime)
+            .map(|(_, hash)| *hash)
+            .collect();
+        
+        for hash in &expired {
+            self.remove_transaction(hash);
+        }
+        
+        // The scan stops at the first entry that is still live
+        let scanned = expired.len() + usize::from(!self.by_time.read().is_empty());
+        self.metrics.record(expired.len(), scanned, started.elapsed());
+        expired.len()
+    }
+    
+    pub fn maintenance_stats(&self) -> MaintenanceStats {
+        MaintenanceStats {
+            runs: self.metrics.runs.load(AtomicOrdering::Relaxed),
+            expired_total: self.metrics.expired_total.load(AtomicOrdering::Relaxed),
+            last_expired: self.metrics.last_expired.load(AtomicOrdering::Relaxed),
+            last_scanned: self.metrics.last_scanned.load(AtomicOrdering::Relaxed),
+            last_scan_duration: Duration::from_micros(self.metrics.last_scan_micros.load(AtomicOrdering::Relaxed)),
+        }
     }
     
     pub fn subscribe(&self) -> broadcast::Receiver<TxPoolEvent> {
         self.events_tx.subscribe()
     }
     
-    pub fn get_transactions_for_block(&self, gas_limit: U256) -> Vec<PooledTransaction> {
+    /// Every transaction added to the pool, in full
+    pub fn subscribe_full_transactions(&self) -> broadcast::Receiver<Arc<PooledTransaction>> {
+        self.full_tx.subscribe()
+    }
+    
+    /// Pending transactions for a block of `gas_limit` with `base_fee`, best
+    /// paying first
+    ///
+    /// Accounts are merged by the effective gas price of their next
+    /// transaction, so each account's transactions stay in nonce order. A
+    /// transaction that can't pay the base fee or doesn't fit holds back the
+    /// rest of its account.
+    ///
+    /// Blob gas is budgeted separately against `max_blob_gas`, so running out
+    /// of blob space still leaves room for transactions without blobs. Returns
+    /// the selected transactions and the blob gas they use.
+    pub fn get_transactions_for_block(
+        &self,
+        gas_limit: U256,
+        base_fee: U256,
+        max_blob_gas: u64,
+    ) -> (Vec<PooledTransaction>, u64) {
         let mut result = Vec::new();
         let mut total_gas = U256::zero();
+        let mut total_blob_gas = 0u64;
         
-        // Get transactions sorted by gas price
-        let mut txs_by_price: Vec<_> = self.pending.read()
-            .values()
-            .flat_map(|txs| txs.iter().cloned())
+        let mut accounts = self.pending.read().clone();
+        let mut heads: BinaryHeap<(U256, Address)> = accounts.iter()
+            .filter_map(|(address, txs)| Some((txs.front()?.effective_gas_price(base_fee), *address)))
             .collect();
         
-        txs_by_price.sort_by(|a, b| b.gas_price.cmp(&a.gas_price));
-        
-        for tx in txs_by_price {
+        while let Some((_, address)) = heads.pop() {
+            let Some(txs) = accounts.get_mut(&address) else { continue };
+            let Some(tx) = txs.pop_front() else { continue };
+            
+            if tx.tx.gas_price() < base_fee {
+                continue;
+            }
             let gas = tx.tx.gas_limit();
-            if total_gas + gas <= gas_limit {
-                total_gas += gas;
-                result.push(tx);
-            } else {
-                break;
+            if total_gas + gas > gas_limit {
+                continue;
+            }
+            let blob_gas = tx.tx.blob_gas();
+            if total_blob_gas + blob_gas > max_blob_gas {
+                continue;
+            }
+            total_gas += gas;
+            total_blob_gas += blob_gas;
+            result.push(tx);
+            
+            if let Some(next) = txs.front() {
+                heads.push((next.effective_gas_price(base_fee), address));
             }
         }
         
-        result
+        (result, total_blob_gas)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ethereum_core::eip7691::{
+        kzg_to_versioned_hash, BLOB_GAS_PER_BLOB, BYTES_PER_BLOB, BYTES_PER_COMMITMENT, BYTES_PER_PROOF,
+    };
+    use ethereum_core::{Eip1559Transaction, Eip4844Transaction, LegacyTransaction};
+    use ethereum_types::Bytes;
+    
+    fn legacy_tx(nonce: u64, gas_price: U256) -> Transaction {
+        unsigned_tx(nonce, gas_price, Some(Address::zero()), Vec::new())
+    }
+    
+    /// Unsigned transaction, the pool attributes it to the zero address
+    fn unsigned_tx(nonce: u64, gas_price: U256, to: Option<Address>, data: Vec<u8>) -> Transaction {
+        Transaction::Legacy(LegacyTransaction {
+            nonce: U256::from(nonce),
+            gas_price,
+            gas_limit: U256::from(21_000),
+            to,
+            value: U256::zero(),
+            data: Bytes::from_vec(data),
+            v: 27,
+            r: U256::zero(),
+            s: U256::zero(),
+        })
+    }
+    
+    /// Unsigned blob transaction committing to `commitments`, with a
+    /// sidecar of zero blobs for them
+    fn blob_tx(nonce: u64, commitments: &[[u8; BYTES_PER_COMMITMENT]]) -> (Transaction, BlobSidecar) {
+        let tx = Transaction::Eip4844(Eip4844Transaction {
+            chain_id: 1,
+            nonce: U256::from(nonce),
+            max_priority_fee_per_gas: gwei(1),
+            max_fee_per_gas: gwei(2),
+            gas_limit: U256::from(21_000),
+            to: Address::zero(),
+            value: U256::zero(),
+            data: Bytes::new(),
+            access_list: Vec::new(),
+            max_fee_per_blob_gas: gwei(1),
+            blob_versioned_hashes: commitments.iter().map(|c| kzg_to_versioned_hash(c)).collect(),
+            y_parity: false,
+            r: U256::zero(),
+            s: U256::zero(),
+        });
+        let sidecar = BlobSidecar {
+            blobs: commitments.iter().map(|_| Bytes::from_vec(vec![0; BYTES_PER_BLOB])).collect(),
+            commitments: commitments.iter().map(|c| Bytes::from_slice(c)).collect(),
+            proofs: commitments.iter().map(|_| Bytes::from_vec(vec![0xc0; BYTES_PER_PROOF])).collect(),
+        };
+        (tx, sidecar)
+    }
+    
+    fn dynamic_fee_tx(nonce: u64, max_fee: U256, tip: U256) -> Transaction {
+        Transaction::Eip1559(Eip1559Transaction {
+            chain_id: 1,
+            nonce: U256::from(nonce),
+            max_priority_fee_per_gas: tip,
+            max_fee_per_gas: max_fee,
+            gas_limit: U256::from(21_000),
+            to: Some(Address::zero()),
+            value: U256::zero(),
+            data: Bytes::new(),
+            access_list: Vec::new(),
+            y_parity: false,
+            r: U256::zero(),
+            s: U256::zero(),
+        })
+    }
+    
+    /// `tx` attributed to the sender with every address byte `sender`
+    fn pooled_from(tx: Transaction, sender: u8) -> PooledTransaction {
+        let mut pooled = PooledTransaction::new(tx);
+        pooled.from = Address::from_bytes([sender; 20]);
+        pooled
+    }
+    
+    fn new_pool(config: TxPoolConfig) -> TransactionPool {
+        TransactionPool::new(config, Arc::new(MemoryNonceProvider::new()))
+    }
+    
+    fn gwei(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(9)
+    }
+    
+    fn pooled_at(nonce: u64, timestamp: Instant) -> PooledTransaction {
+        let mut pooled = PooledTransaction::new(legacy_tx(nonce, gwei(1)));
+        pooled.timestamp = timestamp;
+        pooled
+    }
     
     #[test]
     fn test_transaction_pool_basic() {
-        let pool = TransactionPool::new(TxPoolConfig::default());
+        let pool = new_pool(TxPoolConfig::default());
         
         // Create a test transaction
         // This would need proper transaction creation
@@ -441,4 +1084,564 @@ mod tests {
         assert!(p1 < p2);
         assert!(p2 > p1);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_remove_expired_scans_only_expired() {
+        let config = TxPoolConfig {
+            lifetime: Duration::from_secs(100),
+            ..Default::default()
+        };
+        let pool = new_pool(config);
+        
+        // One transaction per second
+        let base = Instant::now();
+        let mut hashes = Vec::new();
+        for nonce in 0..10 {
+            let pooled = pooled_at(nonce, base + Duration::from_secs(nonce));
+            hashes.push(pooled.hash);
+            pool.add_to_pool(pooled).unwrap();
+        }
+        assert_eq!(pool.total_count(), 10);
+        
+        // At base + 103s the first three are older than the lifetime
+        assert_eq!(pool.remove_expired(base + Duration::from_secs(103)), 3);
+        assert_eq!(pool.total_count(), 7);
+        for hash in &hashes[..3] {
+            assert!(pool.get_transaction(hash).is_none());
+        }
+        for hash in &hashes[3..] {
+            assert!(pool.get_transaction(hash).is_some());
+        }
+        assert_eq!(pool.price_heap.read().len(), 7);
+        
+        let stats = pool.maintenance_stats();
+        assert_eq!(stats.runs, 1);
+        assert_eq!(stats.last_expired, 3);
+        // The expired entries plus the first live one that ended the scan
+        assert_eq!(stats.last_scanned, 4);
+        
+        // Nothing else has expired yet
+        assert_eq!(pool.remove_expired(base + Duration::from_secs(103)), 0);
+        assert_eq!(pool.maintenance_stats().last_scanned, 1);
+        assert_eq!(pool.maintenance_stats().expired_total, 3);
+    }
+    
+    #[tokio::test]
+    async fn test_maintenance_stops_on_shutdown() {
+        let pool = Arc::new(new_pool(TxPoolConfig::default()));
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handle = pool.spawn_maintenance(shutdown_rx);
+        
+        // The first pass runs straight away
+        time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(pool.maintenance_stats().runs, 1);
+        
+        shutdown_tx.send(true).unwrap();
+        time::timeout(Duration::from_secs(1), handle).await
+            .expect("maintenance did not stop")
+            .unwrap();
+        
+        // Dropping the sender stops it as well
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handle = pool.spawn_maintenance(shutdown_rx);
+        drop(shutdown_tx);
+        time::timeout(Duration::from_secs(1), handle).await
+            .expect("maintenance did not stop")
+            .unwrap();
+    }
+    
+    #[test]
+    fn test_local_transactions_never_expire() {
+        let config = TxPoolConfig {
+            lifetime: Duration::from_secs(100),
+            locals: vec![Address::zero()],
+            ..Default::default()
+        };
+        let pool = new_pool(config);
+        
+        let base = Instant::now();
+        for nonce in 0..3 {
+            pool.add_to_pool(pooled_at(nonce, base)).unwrap();
+        }
+        
+        assert_eq!(pool.remove_expired(base + Duration::from_secs(1000)), 0);
+        assert_eq!(pool.total_count(), 3);
+        assert_eq!(pool.maintenance_stats().last_scanned, 0);
+    }
+    
+    #[test]
+    fn test_min_gas_price_and_pressure() {
+        let config = TxPoolConfig {
+            max_size: 2,
+            ..Default::default()
+        };
+        let pool = new_pool(config);
+        assert_eq!(pool.min_gas_price(), None);
+        assert_eq!(pool.pressure(), 0.0);
+        
+        pool.add_transaction(legacy_tx(0, gwei(3))).unwrap();
+        assert_eq!(pool.min_gas_price(), Some(gwei(3)));
+        assert!(!pool.is_full());
+        assert_eq!(pool.pressure(), 0.5);
+        
+        let cheapest = pool.add_transaction(legacy_tx(1, gwei(2))).unwrap();
+        assert_eq!(pool.min_gas_price(), Some(gwei(2)));
+        assert!(pool.is_full());
+        assert_eq!(pool.pressure(), 1.0);
+        
+        // A full pool only admits transactions paying more than its cheapest
+        assert!(matches!(
+            pool.add_transaction(legacy_tx(2, gwei(2))),
+            Err(TxPoolError::Underpriced { floor }) if floor == gwei(2)
+        ));
+        pool.add_transaction(legacy_tx(2, gwei(5))).unwrap();
+        assert!(pool.get_transaction(&cheapest).is_none());
+        assert_eq!(pool.min_gas_price(), Some(gwei(3)));
+        assert!(pool.is_full());
+        
+        pool.clear();
+        assert_eq!(pool.min_gas_price(), None);
+        assert!(!pool.is_full());
+    }
+    
+    #[test]
+    fn test_size_limit() {
+        let pool = new_pool(TxPoolConfig::default());
+        
+        // Pad the calldata until the envelope is exactly `size` bytes, the
+        // rest of the encoding depends on the nonce
+        let padded = |nonce: u64, size: usize| {
+            let mut data = vec![0u8; size - 200];
+            let unpadded = unsigned_tx(nonce, gwei(1), Some(Address::zero()), data.clone()).size();
+            data.resize(data.len() + size - unpadded, 0);
+            unsigned_tx(nonce, gwei(1), Some(Address::zero()), data)
+        };
+        
+        let at_limit = padded(0, DEFAULT_MAX_TX_SIZE);
+        assert_eq!(at_limit.size(), DEFAULT_MAX_TX_SIZE);
+        pool.add_transaction(at_limit).unwrap();
+        
+        let oversized = padded(1, DEFAULT_MAX_TX_SIZE + 1);
+        assert_eq!(oversized.size(), DEFAULT_MAX_TX_SIZE + 1);
+        assert!(matches!(pool.add_transaction(oversized), Err(TxPoolError::InvalidTransaction(_))));
+        assert_eq!(pool.total_count(), 1);
+    }
+    
+    #[test]
+    fn test_init_code_limit() {
+        let pool = new_pool(TxPoolConfig::default());
+        
+        let oversized = unsigned_tx(0, gwei(1), None, vec![0u8; MAX_INIT_CODE_SIZE + 1]);
+        assert!(oversized.size() < DEFAULT_MAX_TX_SIZE);
+        assert!(matches!(pool.add_transaction(oversized), Err(TxPoolError::InvalidTransaction(_))));
+        
+        pool.add_transaction(unsigned_tx(0, gwei(1), None, vec![0u8; MAX_INIT_CODE_SIZE])).unwrap();
+        
+        // The limit only applies to creations
+        pool.add_transaction(unsigned_tx(1, gwei(1), Some(Address::zero()), vec![0u8; MAX_INIT_CODE_SIZE + 1])).unwrap();
+    }
+    
+    #[test]
+    fn test_blob_transaction_with_sidecar() {
+        let pool = new_pool(TxPoolConfig::default());
+        let (tx, sidecar) = blob_tx(0, &[[0xc0; BYTES_PER_COMMITMENT], [0xc1; BYTES_PER_COMMITMENT]]);
+        let versioned_hashes = sidecar.versioned_hashes();
+        
+        let hash = pool.add_blob_transaction(tx, sidecar.clone()).unwrap();
+        assert!(pool.get_transaction(&hash).is_some());
+        assert_eq!(pool.get_blob_sidecar(&hash), Some(sidecar));
+        assert!(pool.get_blob(&versioned_hashes[1]).is_some());
+        
+        // Importing it again leaves the pooled copy and its blobs alone
+        let (tx, sidecar) = blob_tx(0, &[[0xc0; BYTES_PER_COMMITMENT], [0xc1; BYTES_PER_COMMITMENT]]);
+        assert!(matches!(pool.add_blob_transaction(tx, sidecar.clone()), Err(TxPoolError::AlreadyExists)));
+        assert_eq!(pool.get_blob_sidecar(&hash), Some(sidecar));
+        
+        // The sidecar leaves the pool with its transaction
+        pool.remove_transaction(&hash).unwrap();
+        assert!(pool.get_blob_sidecar(&hash).is_none());
+        assert!(pool.get_blob(&versioned_hashes[0]).is_none());
+    }
+    
+    #[test]
+    fn test_blob_sidecar_commitment_mismatch() {
+        let pool = new_pool(TxPoolConfig::default());
+        let (tx, mut sidecar) = blob_tx(0, &[[0xc0; BYTES_PER_COMMITMENT]]);
+        sidecar.commitments[0] = Bytes::from_vec(vec![0xc1; BYTES_PER_COMMITMENT]);
+        
+        let result = pool.add_blob_transaction(tx, sidecar);
+        assert!(matches!(result, Err(TxPoolError::InvalidTransaction(_))));
+        assert_eq!(pool.total_count(), 0);
+        
+        // Sidecars only go with blob transactions
+        let (_, sidecar) = blob_tx(0, &[[0xc0; BYTES_PER_COMMITMENT]]);
+        assert!(pool.add_blob_transaction(legacy_tx(0, gwei(1)), sidecar).is_err());
+    }
+    
+    #[test]
+    fn test_full_transaction_subscription() {
+        let pool = new_pool(TxPoolConfig::default());
+        let mut hashes = pool.subscribe();
+        let mut full = pool.subscribe_full_transactions();
+        
+        let tx = legacy_tx(0, gwei(2));
+        let hash = pool.add_transaction(tx.clone()).unwrap();
+        
+        assert!(matches!(hashes.try_recv().unwrap(), TxPoolEvent::NewTransaction(h) if h == hash));
+        let delivered = full.try_recv().unwrap();
+        assert_eq!(delivered.hash, hash);
+        assert_eq!(delivered.tx, tx);
+        assert_eq!(delivered.from, tx.from());
+        assert_eq!(delivered.gas_price, gwei(2));
+        assert!(full.try_recv().is_err());
+    }
+    
+    #[test]
+    fn test_next_nonce_continues_state_nonce() {
+        let nonces = Arc::new(MemoryNonceProvider::new());
+        nonces.set_nonce(Address::zero(), U256::from(3));
+        let pool = TransactionPool::new(TxPoolConfig::default(), nonces.clone());
+        assert_eq!(pool.get_next_nonce(&Address::zero()), U256::from(3));
+        
+        // The first transaction of an account with history is pending
+        pool.add_transaction(legacy_tx(3, gwei(1))).unwrap();
+        pool.add_transaction(legacy_tx(5, gwei(1))).unwrap();
+        assert_eq!(pool.pending_count(), 1);
+        assert_eq!(pool.queued_count(), 1);
+        assert_eq!(pool.get_next_nonce(&Address::zero()), U256::from(4));
+        assert!(matches!(pool.add_transaction(legacy_tx(2, gwei(1))), Err(TxPoolError::NonceTooLow)));
+        
+        // Nonces 3 and 4 were mined elsewhere, 5 continues the new state nonce
+        nonces.set_nonce(Address::zero(), U256::from(5));
+        assert_eq!(pool.reset_accounts(&[Address::zero()]), 1);
+        assert_eq!(pool.queued_count(), 0);
+        let pending = pool.get_pending_by_address(&Address::zero());
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].tx.nonce(), U256::from(5));
+        assert_eq!(pool.get_next_nonce(&Address::zero()), U256::from(6));
+    }
+    
+    #[test]
+    fn test_state_nonce_buckets_transactions() {
+        let nonces = Arc::new(MemoryNonceProvider::new());
+        nonces.set_nonce(Address::zero(), U256::from(5));
+        let pool = TransactionPool::new(TxPoolConfig::default(), nonces);
+        
+        let ready = pool.add_transaction(legacy_tx(5, gwei(1))).unwrap();
+        let future = pool.add_transaction(legacy_tx(7, gwei(1))).unwrap();
+        assert_eq!(pool.get_pending_by_address(&Address::zero())[0].hash, ready);
+        assert_eq!(pool.get_queued_by_address(&Address::zero())[0].hash, future);
+        
+        // Nonces the chain already used are refused even with an empty pool
+        // for that nonce
+        assert!(matches!(pool.add_transaction(legacy_tx(0, gwei(1))), Err(TxPoolError::NonceTooLow)));
+        
+        // Filling the gap promotes the queued transaction
+        pool.add_transaction(legacy_tx(6, gwei(1))).unwrap();
+        assert_eq!(pool.pending_count(), 3);
+        assert_eq!(pool.queued_count(), 0);
+    }
+    
+    #[test]
+    fn test_replace_pending_transaction() {
+        let pool = new_pool(TxPoolConfig::default());
+        let old = pool.add_transaction(legacy_tx(0, gwei(10))).unwrap();
+        let mut events = pool.subscribe();
+        
+        // Less than a 10% bump is refused
+        let underpriced = legacy_tx(0, gwei(10) + gwei(1) - U256::one());
+        assert!(matches!(pool.add_transaction(underpriced), Err(TxPoolError::ReplacementUnderpriced)));
+        assert!(pool.get_transaction(&old).is_some());
+        
+        let new = pool.add_transaction(legacy_tx(0, gwei(11))).unwrap();
+        assert!(pool.get_transaction(&old).is_none());
+        assert_eq!(pool.total_count(), 1);
+        assert_eq!(pool.get_pending_by_address(&Address::zero())[0].hash, new);
+        assert_eq!(pool.min_gas_price(), Some(gwei(11)));
+        
+        assert!(matches!(events.try_recv().unwrap(), TxPoolEvent::Removed(h) if h == old));
+        assert!(matches!(events.try_recv().unwrap(), TxPoolEvent::NewTransaction(h) if h == new));
+    }
+    
+    #[test]
+    fn test_replace_queued_transaction() {
+        let pool = new_pool(TxPoolConfig::default());
+        pool.add_transaction(legacy_tx(0, gwei(1))).unwrap();
+        let old = pool.add_transaction(legacy_tx(2, gwei(2))).unwrap();
+        
+        let new = pool.add_transaction(legacy_tx(2, gwei(3))).unwrap();
+        assert!(pool.get_transaction(&old).is_none());
+        let queued = pool.get_queued_by_address(&Address::zero());
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].hash, new);
+        assert_eq!(pool.pending_count(), 1);
+        
+        // The replacement is promoted like the original would have been
+        pool.add_transaction(legacy_tx(1, gwei(1))).unwrap();
+        assert_eq!(pool.pending_count(), 3);
+        assert_eq!(pool.queued_count(), 0);
+    }
+    
+    #[test]
+    fn test_block_ordering_by_effective_gas_price() {
+        let config = TxPoolConfig { base_fee: gwei(5), ..Default::default() };
+        let pool = new_pool(config);
+        
+        let legacy = pooled_from(legacy_tx(0, gwei(7)), 1);
+        // Pays 6 gwei at a 5 gwei base fee despite its 20 gwei cap
+        let capped = pooled_from(dynamic_fee_tx(0, gwei(20), gwei(1)), 2);
+        let capped_next = pooled_from(dynamic_fee_tx(1, gwei(20), gwei(3)), 2);
+        // Can't pay the base fee at all
+        let too_low = pooled_from(dynamic_fee_tx(0, gwei(4), gwei(4)), 3);
+        let legacy_low = pooled_from(legacy_tx(0, gwei(5) + U256::one()), 4);
+        for pooled in [&legacy, &capped, &capped_next, &too_low, &legacy_low] {
+            pool.add_to_pool(pooled.clone()).unwrap();
+        }
+        
+        let block_hashes = |base_fee| -> Vec<H256> {
+            pool.get_transactions_for_block(U256::from(1_000_000), base_fee, 0)
+                .0
+                .iter()
+                .map(|pooled| pooled.hash)
+                .collect()
+        };
+        assert_eq!(block_hashes(gwei(5)), vec![legacy.hash, capped.hash, capped_next.hash, legacy_low.hash]);
+        
+        // At a 15 gwei base fee only the dynamic fee account can pay
+        assert_eq!(block_hashes(gwei(15)), vec![capped.hash, capped_next.hash]);
+        
+        // Without one the tips decide, legacy transactions pay in full
+        assert_eq!(
+            block_hashes(U256::zero()),
+            vec![legacy.hash, legacy_low.hash, too_low.hash, capped.hash, capped_next.hash]
+        );
+        
+        pool.set_base_fee(gwei(15));
+        assert_eq!(pool.min_gas_price(), Some(gwei(4)));
+    }
+    
+    #[test]
+    fn test_block_blob_gas_budget() {
+        let pool = new_pool(TxPoolConfig::default());
+        let mut blob_hashes = Vec::new();
+        for nonce in 0..7u8 {
+            let (tx, sidecar) = blob_tx(nonce as u64, &[[0xc0 + nonce; BYTES_PER_COMMITMENT]]);
+            blob_hashes.push(pool.add_blob_transaction(tx, sidecar).unwrap());
+        }
+        let plain = pooled_from(legacy_tx(0, gwei(1)), 1);
+        pool.add_to_pool(plain.clone()).unwrap();
+        
+        // Only six of the blobs fit, the account without blobs is unaffected
+        let (selected, blob_gas) =
+            pool.get_transactions_for_block(U256::from(1_000_000), U256::zero(), 6 * BLOB_GAS_PER_BLOB);
+        let mut selected: Vec<H256> = selected.iter().map(|pooled| pooled.hash).collect();
+        assert_eq!(blob_gas, 6 * BLOB_GAS_PER_BLOB);
+        assert!(selected.contains(&plain.hash));
+        selected.retain(|hash| *hash != plain.hash);
+        assert_eq!(selected, blob_hashes[..6]);
+        
+        // No blob budget at all still fills the block with the rest
+        let (selected, blob_gas) = pool.get_transactions_for_block(U256::from(1_000_000), U256::zero(), 0);
+        assert_eq!(blob_gas, 0);
+        assert_eq!(selected.iter().map(|pooled| pooled.hash).collect::<Vec<_>>(), vec![plain.hash]);
+    }
+    
+    #[test]
+    fn test_eviction_by_effective_gas_price() {
+        let config = TxPoolConfig { max_size: 2, base_fee: gwei(5), ..Default::default() };
+        let pool = new_pool(config);
+        
+        let legacy = pooled_from(legacy_tx(0, gwei(7)), 1);
+        let capped = pooled_from(dynamic_fee_tx(0, gwei(20), gwei(1)), 2);
+        pool.add_to_pool(legacy.clone()).unwrap();
+        pool.add_to_pool(capped.clone()).unwrap();
+        assert_eq!(pool.min_gas_price(), Some(gwei(6)));
+        
+        // 6.5 gwei beats the dynamic fee transaction's effective 6 gwei
+        let incoming = pooled_from(legacy_tx(0, gwei(13) / 2), 3);
+        assert!(pool.evict_transaction(&incoming).unwrap());
+        assert!(pool.get_transaction(&capped.hash).is_none());
+        assert!(pool.get_transaction(&legacy.hash).is_some());
+    }
+    
+    #[test]
+    fn test_removal_demotes_later_nonces() {
+        let config = TxPoolConfig { max_size: 4, ..Default::default() };
+        let pool = new_pool(config);
+        let sender = Address::from_bytes([1; 20]);
+        
+        let txs: Vec<PooledTransaction> = [gwei(3), gwei(1), gwei(3)].into_iter().enumerate()
+            .map(|(nonce, price)| pooled_from(legacy_tx(nonce as u64, price), 1))
+            .collect();
+        for tx in &txs {
+            pool.add_to_pool(tx.clone()).unwrap();
+        }
+        pool.add_to_pool(pooled_from(legacy_tx(0, gwei(4)), 2)).unwrap();
+        assert_eq!(pool.get_pending_by_address(&sender).len(), 3);
+        
+        // Evicting nonce 1 leaves nonce 2 with a gap in front of it
+        let incoming = pooled_from(legacy_tx(0, gwei(2)), 3);
+        assert!(pool.evict_transaction(&incoming).unwrap());
+        assert_eq!(pool.get_pending_by_address(&sender).iter().map(|tx| tx.hash).collect::<Vec<_>>(), vec![txs[0].hash]);
+        assert_eq!(pool.get_queued_by_address(&sender).iter().map(|tx| tx.hash).collect::<Vec<_>>(), vec![txs[2].hash]);
+        
+        // So does dropping the first one outright
+        pool.remove_transaction(&txs[0].hash).unwrap();
+        assert!(pool.get_pending_by_address(&sender).is_empty());
+        assert_eq!(pool.get_queued_by_address(&sender).len(), 1);
+    }
+    
+    #[test]
+    fn test_new_head_reprices_pool() {
+        let config = TxPoolConfig { base_fee: gwei(5), ..Default::default() };
+        let pool = new_pool(config);
+        pool.add_to_pool(pooled_from(dynamic_fee_tx(0, gwei(20), gwei(1)), 1)).unwrap();
+        assert_eq!(pool.min_gas_price(), Some(gwei(6)));
+        
+        // A full parent raises the next base fee by an eighth
+        let mut head = Header::new();
+        head.base_fee_per_gas = Some(gwei(8));
+        head.gas_limit = U256::from(30_000_000);
+        head.gas_used = head.gas_limit;
+        pool.on_new_head(&head, &[]);
+        assert_eq!(pool.base_fee(), gwei(9));
+        assert_eq!(pool.min_gas_price(), Some(gwei(10)));
+    }
+    
+    #[test]
+    fn test_replacement_price_bump_boundary() {
+        let pool = new_pool(TxPoolConfig::default());
+        
+        // A bump of exactly 10% is enough
+        pool.add_transaction(legacy_tx(0, gwei(20))).unwrap();
+        pool.add_transaction(legacy_tx(0, gwei(22))).unwrap();
+        
+        // 110% of 1.000000005 gwei is 1.1000000055 gwei, rounded down
+        let old = gwei(1) + U256::from(5);
+        pool.add_transaction(legacy_tx(1, old)).unwrap();
+        let threshold = U256::from(1_100_000_005u64);
+        assert!(matches!(
+            pool.add_transaction(legacy_tx(1, threshold - U256::one())),
+            Err(TxPoolError::ReplacementUnderpriced)
+        ));
+        let new = pool.add_transaction(legacy_tx(1, threshold)).unwrap();
+        assert_eq!(pool.get_transaction(&new).unwrap().gas_price, threshold);
+        assert_eq!(pool.total_count(), 2);
+    }
+    
+    #[test]
+    fn test_account_slot_limit() {
+        let pool = new_pool(TxPoolConfig::default());
+        
+        for nonce in 0..20 {
+            let result = pool.add_transaction(legacy_tx(nonce, gwei(1)));
+            if nonce < 16 {
+                result.unwrap();
+            } else {
+                assert!(matches!(result, Err(TxPoolError::AccountLimitExceeded)));
+            }
+        }
+        assert_eq!(pool.total_count(), 16);
+        assert_eq!(pool.pending_count(), 16);
+        
+        // Replacements don't take another slot
+        pool.add_transaction(legacy_tx(15, gwei(2))).unwrap();
+        assert_eq!(pool.total_count(), 16);
+    }
+    
+    #[test]
+    fn test_account_limit_evicts_cheapest() {
+        let pool = new_pool(TxPoolConfig::default());
+        
+        let mut cheapest = H256::zero();
+        for nonce in 0..16 {
+            let price = if nonce == 3 { gwei(1) } else { gwei(2) };
+            let hash = pool.add_transaction(legacy_tx(nonce, price)).unwrap();
+            if nonce == 3 {
+                cheapest = hash;
+            }
+        }
+        
+        // Paying no more than the cheapest isn't enough
+        assert!(matches!(pool.add_transaction(legacy_tx(16, gwei(1))), Err(TxPoolError::AccountLimitExceeded)));
+        
+        pool.add_transaction(legacy_tx(16, gwei(3))).unwrap();
+        assert!(pool.get_transaction(&cheapest).is_none());
+        assert_eq!(pool.total_count(), 16);
+        // Nonces after the gap wait in the queue for nonce 3 to return
+        assert_eq!(pool.pending_count(), 3);
+        assert_eq!(pool.queued_count(), 13);
+    }
+    
+    #[test]
+    fn test_global_queue_limit() {
+        let config = TxPoolConfig { global_queue: 2, ..Default::default() };
+        let pool = new_pool(config);
+        
+        pool.add_transaction(legacy_tx(5, gwei(1))).unwrap();
+        pool.add_to_pool(pooled_from(legacy_tx(5, gwei(1)), 1)).unwrap();
+        assert!(matches!(pool.add_transaction(legacy_tx(6, gwei(1))), Err(TxPoolError::PoolFull)));
+        
+        // Executable transactions don't count against it
+        pool.add_transaction(legacy_tx(0, gwei(1))).unwrap();
+        assert_eq!(pool.queued_count(), 2);
+        assert_eq!(pool.pending_count(), 1);
+    }
+    
+    #[test]
+    fn test_full_pool_reports_underpriced_floor() {
+        let config = TxPoolConfig { max_size: 2, ..Default::default() };
+        let pool = new_pool(config);
+        pool.add_to_pool(pooled_from(legacy_tx(0, gwei(4)), 1)).unwrap();
+        pool.add_to_pool(pooled_from(legacy_tx(0, gwei(3)), 2)).unwrap();
+        
+        let result = pool.add_transaction(legacy_tx(0, gwei(1)));
+        assert!(matches!(result, Err(TxPoolError::Underpriced { floor }) if floor == gwei(3)));
+        assert_eq!(pool.total_count(), 2);
+        
+        // With nothing to evict the pool is simply full
+        let empty = new_pool(TxPoolConfig { max_size: 0, ..Default::default() });
+        assert!(matches!(empty.add_transaction(legacy_tx(0, gwei(1))), Err(TxPoolError::PoolFull)));
+    }
+    
+    #[test]
+    fn test_content_sorted_by_nonce() {
+        let pool = new_pool(TxPoolConfig::default());
+        for nonce in [4, 2, 0, 3, 1, 7, 5] {
+            pool.add_transaction(legacy_tx(nonce, gwei(1))).unwrap();
+        }
+        // Replacing a queued transaction keeps it in its nonce slot
+        pool.add_transaction(legacy_tx(7, gwei(2))).unwrap();
+        assert_eq!(pool.status(), (6, 1));
+        
+        let content = pool.content();
+        let pending: Vec<U256> = content.pending[&Address::zero()].keys().copied().collect();
+        assert_eq!(pending, (0..6).map(U256::from).collect::<Vec<_>>());
+        let queued = &content.queued[&Address::zero()];
+        assert_eq!(queued.keys().copied().collect::<Vec<_>>(), vec![U256::from(7)]);
+        assert_eq!(queued[&U256::from(7)].gas_price, gwei(2));
+    }
+    
+    #[test]
+    fn test_local_transactions_skip_price_limit_and_eviction() {
+        let config = TxPoolConfig { max_size: 2, ..Default::default() };
+        let pool = new_pool(config);
+        let cheap = legacy_tx(0, gwei(1) / 2);
+        
+        assert!(matches!(pool.add_transaction(cheap.clone()), Err(TxPoolError::GasPriceTooLow)));
+        let local = pool.add_local_transaction(cheap).unwrap();
+        assert!(pool.get_transaction(&local).unwrap().is_local);
+        
+        // The local transaction is not what a full pool evicts
+        let remote = pool.add_transaction(legacy_tx(1, gwei(2))).unwrap();
+        assert!(pool.is_full());
+        assert_eq!(pool.min_gas_price(), Some(gwei(2)));
+        pool.add_transaction(legacy_tx(2, gwei(5))).unwrap();
+        assert!(pool.get_transaction(&local).is_some());
+        assert!(pool.get_transaction(&remote).is_none());
+        
+        // Nor does it expire
+        assert_eq!(pool.remove_expired(Instant::now() + Duration::from_secs(24 * 60 * 60)), 1);
+        assert!(pool.get_transaction(&local).is_some());
+    }
+}
@@ -1,11 +1,33 @@
-use ethereum_types::{H256, U256};
-use ethereum_core::{Block, Header};
+use ethereum_types::H256;
+use ethereum_core::{Block, Header, Transaction, Withdrawal};
 use ethereum_storage::Database;
 use ethereum_crypto::keccak256;
+use ethereum_rlp::Encoder;
 use std::sync::Arc;
 
 use crate::{Result, VerificationError};
 
+/// Root of the withdrawals trie a header commits to in `withdrawals_root`
+/// (EIP-4895), keyed by each withdrawal's position in the block
+pub fn compute_withdrawals_root(withdrawals: &[Withdrawal]) -> H256 {
+    ethereum_trie::ordered_trie_root(
+        withdrawals.iter().map(|withdrawal| ethereum_rlp::encode(withdrawal).as_slice().to_vec()),
+    )
+}
+
+/// Root of the transactions trie, keyed by position with each transaction
+/// in its EIP-2718 envelope
+pub fn compute_transactions_root(transactions: &[Transaction]) -> H256 {
+    ethereum_trie::ordered_trie_root(transactions.iter().map(|tx| tx.encoded_2718()))
+}
+
+/// Hash of the RLP list of a block's ommer headers
+pub fn compute_ommers_hash(ommers: &[Header]) -> H256 {
+    let mut encoder = Encoder::new();
+    encoder.encode_list(ommers);
+    keccak256(&encoder.finish())
+}
+
 /// Block structure verifier
 pub struct BlockVerifier<D: Database> {
     db: Arc<D>,
@@ -19,28 +41,31 @@ impl<D: Database> BlockVerifier<D> {
     /// Verify block structure
     pub fn verify_structure(&self, block: &Block) -> Result<()> {
         // Verify transactions root
-        let computed_tx_root = self.compute_transaction_root(&block.body.transactions);
+        let computed_tx_root = compute_transactions_root(&block.transactions);
         if computed_tx_root != block.header.transactions_root {
             return Err(VerificationError::InvalidBlock(
                 "Transaction root mismatch".to_string()
             ));
         }
         
-        // Verify uncles hash
-        let computed_uncles_hash = self.compute_uncles_hash(&block.body.uncles);
-        if computed_uncles_hash != block.header.uncles_hash {
+        // Verify ommers hash
+        let computed_ommers_hash = compute_ommers_hash(&block.ommers);
+        if computed_ommers_hash != block.header.ommers_hash {
             return Err(VerificationError::InvalidBlock(
-                "Uncles hash mismatch".to_string()
+                "Ommers hash mismatch".to_string()
             ));
         }
         
-        // Verify block hash
-        let computed_hash = block.header.hash();
+        // Verify withdrawals root
+        let computed_withdrawals_root = block.withdrawals.as_deref().map(compute_withdrawals_root);
+        if computed_withdrawals_root != block.header.withdrawals_root {
+            return Err(VerificationError::InvalidBlock(
+                "Withdrawals root mismatch".to_string()
+            ));
+        }
         
         // Check block size limits
-        let block_size = bincode::serialize(block)
-            .map_err(|_| VerificationError::InvalidBlock("Failed to serialize block".to_string()))?
-            .len();
+        let block_size = ethereum_rlp::encode(block).len();
         
         const MAX_BLOCK_SIZE: usize = 1_000_000; // 1MB limit
         if block_size > MAX_BLOCK_SIZE {
@@ -51,9 +76,9 @@ impl<D: Database> BlockVerifier<D> {
         
         // Verify uncle count
         const MAX_UNCLES: usize = 2;
-        if block.body.uncles.len() > MAX_UNCLES {
+        if block.ommers.len() > MAX_UNCLES {
             return Err(VerificationError::InvalidBlock(
-                format!("Too many uncles: {} > {}", block.body.uncles.len(), MAX_UNCLES)
+                format!("Too many uncles: {} > {}", block.ommers.len(), MAX_UNCLES)
             ));
         }
         
@@ -62,13 +87,13 @@ impl<D: Database> BlockVerifier<D> {
     
     /// Verify uncle blocks
     pub fn verify_uncles(&self, block: &Block) -> Result<()> {
-        for uncle in &block.body.uncles {
+        for uncle in &block.ommers {
             self.verify_uncle(uncle, &block.header)?;
         }
         
         // Check for duplicate uncles
         let mut uncle_hashes = Vec::new();
-        for uncle in &block.body.uncles {
+        for uncle in &block.ommers {
             let hash = uncle.hash();
             if uncle_hashes.contains(&hash) {
                 return Err(VerificationError::InvalidBlock(
@@ -135,42 +160,50 @@ impl<D: Database> BlockVerifier<D> {
         
         Ok(false)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::Address;
     
-    /// Compute transaction root
-    fn compute_transaction_root(&self, transactions: &[ethereum_core::Transaction]) -> H256 {
-        if transactions.is_empty() {
-            // Empty transactions trie root
-            return H256::from([0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6,
-                              0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
-                              0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0,
-                              0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21]);
-        }
-        
-        // Build Merkle Patricia Trie of transactions
-        let mut data = Vec::new();
-        for tx in transactions {
-            data.extend_from_slice(&tx.hash().0);
-        }
-        
-        H256(keccak256(&data))
+    #[test]
+    fn test_empty_body_roots() {
+        assert_eq!(
+            compute_transactions_root(&[]),
+            H256::from_slice(&hex::decode("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421").unwrap())
+        );
+        assert_eq!(
+            compute_ommers_hash(&[]),
+            H256::from_slice(&hex::decode("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347").unwrap())
+        );
     }
     
-    /// Compute uncles hash
-    fn compute_uncles_hash(&self, uncles: &[Header]) -> H256 {
-        if uncles.is_empty() {
-            // Empty uncles hash
-            return H256::from([0x1d, 0xcc, 0x4d, 0xe8, 0xde, 0xc7, 0x5d, 0x7a,
-                              0xab, 0x85, 0xb5, 0x67, 0xb6, 0xcc, 0xd4, 0x1a,
-                              0xd3, 0x12, 0x45, 0x1b, 0x94, 0x8a, 0x74, 0x13,
-                              0xf0, 0xa1, 0x42, 0xfd, 0x40, 0xd4, 0x93, 0x47]);
-        }
-        
-        // Hash of RLP-encoded uncles
-        let mut data = Vec::new();
-        for uncle in uncles {
-            data.extend_from_slice(&uncle.hash().0);
-        }
-        
-        H256(keccak256(&data))
+    #[test]
+    fn test_withdrawals_root() {
+        let withdrawals = vec![
+            Withdrawal {
+                index: 0,
+                validator_index: 1,
+                address: Address::from_bytes([0x11; 20]),
+                amount: 1_000_000_000,
+            },
+            Withdrawal {
+                index: 1,
+                validator_index: 2,
+                address: Address::from_bytes([0x22; 20]),
+                amount: 32_000_000_000,
+            },
+        ];
+        
+        assert_eq!(compute_withdrawals_root(&[]), ethereum_trie::empty_root());
+        assert_eq!(
+            compute_withdrawals_root(&withdrawals[..1]),
+            H256::from_slice(&hex::decode("e752c1df3d849f80bdb84703239e3cff5f878fc1e86f8d803108be93252f9f8b").unwrap())
+        );
+        assert_eq!(
+            compute_withdrawals_root(&withdrawals),
+            H256::from_slice(&hex::decode("14cf24f963f924c48b58effea953378e2cb34c0d27bb99b1d061ddcb070fa5a3").unwrap())
+        );
     }
 }
\ No newline at end of file
@@ -0,0 +1,169 @@
+use ethereum_types::{H256, Address};
+use ethereum_core::Transaction;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::transaction::intrinsic_gas;
+use crate::{Result, VerificationError};
+
+/// Default number of transactions remembered by a [`TxCache`]
+pub const DEFAULT_TX_CACHE_SIZE: usize = 16_384;
+
+/// Per-transaction results that only depend on the transaction itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedTx {
+    pub sender: Address,
+    pub intrinsic_gas: u64,
+}
+
+/// Bounded LRU of recovered senders and intrinsic gas keyed by transaction hash
+///
+/// Shared between block verification and execution so a block that is
+/// verified and then imported pays for each ECDSA recovery once. Entries are
+/// content-addressed and never go stale, so they are only dropped to make
+/// room.
+pub struct TxCache {
+    capacity: usize,
+    inner: Mutex<LruMap>,
+    recoveries: AtomicU64,
+}
+
+#[derive(Default)]
+struct LruMap {
+    entries: HashMap<H256, (CachedTx, u64)>,
+    /// Last use of each entry, oldest first
+    order: BTreeMap<u64, H256>,
+    tick: u64,
+}
+
+impl TxCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: Mutex::new(LruMap::default()),
+            recoveries: AtomicU64::new(0),
+        }
+    }
+
+    /// Cached results for `tx`, recovering its sender on a miss
+    pub fn get(&self, tx: &Transaction) -> Result<CachedTx> {
+        let hash = tx.hash();
+        if let Some(cached) = self.inner.lock().touch(&hash) {
+            return Ok(cached);
+        }
+
+        // Recover outside the lock, concurrent misses on one hash are harmless
+        self.recoveries.fetch_add(1, Ordering::Relaxed);
+        let sender = tx.sender().map_err(|_| VerificationError::InvalidTransaction(
+            "Failed to recover sender".to_string()
+        ))?;
+        let cached = CachedTx { sender, intrinsic_gas: intrinsic_gas(tx) };
+
+        self.inner.lock().insert(hash, cached, self.capacity);
+        Ok(cached)
+    }
+
+    pub fn sender(&self, tx: &Transaction) -> Result<Address> {
+        Ok(self.get(tx)?.sender)
+    }
+
+    pub fn intrinsic_gas(&self, tx: &Transaction) -> Result<u64> {
+        Ok(self.get(tx)?.intrinsic_gas)
+    }
+
+    /// Number of signature recoveries performed so far
+    pub fn recoveries(&self) -> u64 {
+        self.recoveries.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for TxCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TX_CACHE_SIZE)
+    }
+}
+
+impl LruMap {
+    fn touch(&mut self, hash: &H256) -> Option<CachedTx> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        let (cached, last_used) = self.entries.get_mut(hash)?;
+        self.order.remove(last_used);
+        *last_used = tick;
+        self.order.insert(tick, *hash);
+        Some(*cached)
+    }
+
+    fn insert(&mut self, hash: H256, cached: CachedTx, capacity: usize) {
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.insert(hash, (cached, self.tick)) {
+            self.order.remove(&last_used);
+        }
+        self.order.insert(self.tick, hash);
+
+        while self.entries.len() > capacity {
+            let Some((_, oldest)) = self.order.pop_first() else { break };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::LegacyTransaction;
+    use ethereum_crypto::{generate_private_key, sign_message};
+    use ethereum_types::{Bytes, U256};
+
+    fn signed_transfer(nonce: u64) -> Transaction {
+        let mut tx = LegacyTransaction {
+            nonce: U256::from(nonce),
+            gas_price: U256::from(1_000_000_000u64),
+            gas_limit: U256::from(21_000),
+            to: Some(Address::from_bytes([0x42; 20])),
+            value: U256::from(1),
+            data: Bytes::default(),
+            v: 27,
+            r: U256::one(),
+            s: U256::one(),
+        };
+        let signature = sign_message(&tx.signing_hash(None), &generate_private_key()).unwrap();
+        tx.v = signature.v as u64;
+        tx.r = U256::from_big_endian(signature.r.as_bytes());
+        tx.s = U256::from_big_endian(signature.s.as_bytes());
+        Transaction::Legacy(tx)
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = TxCache::new(2);
+        let (a, b, c) = (signed_transfer(0), signed_transfer(1), signed_transfer(2));
+
+        let sender = cache.sender(&a).unwrap();
+        assert_eq!(sender, a.sender().unwrap());
+        assert_eq!(cache.intrinsic_gas(&a).unwrap(), 21_000);
+        cache.get(&b).unwrap();
+        assert_eq!(cache.recoveries(), 2);
+
+        // `a` was used more recently than `b`, so `b` makes room for `c`
+        cache.get(&a).unwrap();
+        cache.get(&c).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.recoveries(), 3);
+
+        cache.get(&a).unwrap();
+        assert_eq!(cache.recoveries(), 3);
+        cache.get(&b).unwrap();
+        assert_eq!(cache.recoveries(), 4);
+    }
+}
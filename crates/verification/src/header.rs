@@ -1,5 +1,6 @@
-use ethereum_types::{H256, U256};
+use ethereum_types::U256;
 use ethereum_core::Header;
+use ethereum_consensus::header_rules::GAS_LIMIT_BOUND_DIVISOR;
 use ethereum_storage::Database;
 use std::sync::Arc;
 
@@ -157,26 +158,25 @@ impl<D: Database> HeaderVerifier<D> {
     }
     
     /// Verify gas limit adjustment
+    ///
+    /// The gas limit must change by strictly less than 1/1024 of the
+    /// parent's, the bound the consensus header rules apply as well.
     fn verify_gas_limit_adjustment(&self, header: &Header, parent: &Header) -> Result<()> {
-        // Gas limit can only change by 1/1024 of parent gas limit
-        let parent_gas = parent.gas_limit.as_u64();
-        let adjustment_limit = parent_gas / 1024;
+        let adjustment_limit = parent.gas_limit / U256::from(GAS_LIMIT_BOUND_DIVISOR);
         
-        let current_gas = header.gas_limit.as_u64();
-        
-        if current_gas > parent_gas {
-            let increase = current_gas - parent_gas;
-            if increase > adjustment_limit {
+        if header.gas_limit > parent.gas_limit {
+            let increase = header.gas_limit - parent.gas_limit;
+            if increase >= adjustment_limit {
                 return Err(VerificationError::InvalidHeader(
-                    format!("Gas limit increase too large: {} > {}", 
+                    format!("Gas limit increase too large: {} >= {}", 
                             increase, adjustment_limit)
                 ));
             }
         } else {
-            let decrease = parent_gas - current_gas;
-            if decrease > adjustment_limit {
+            let decrease = parent.gas_limit - header.gas_limit;
+            if decrease >= adjustment_limit {
                 return Err(VerificationError::InvalidHeader(
-                    format!("Gas limit decrease too large: {} > {}", 
+                    format!("Gas limit decrease too large: {} >= {}", 
                             decrease, adjustment_limit)
                 ));
             }
@@ -231,4 +231,36 @@ impl<D: Database> HeaderVerifier<D> {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_storage::MemoryDatabase;
+    
+    #[test]
+    fn test_gas_limit_adjustment_bound() {
+        let verifier = HeaderVerifier::new(Arc::new(MemoryDatabase::new()));
+        let mut parent = Header::new();
+        parent.gas_limit = U256::from(30_000_000u64);
+        let bound = parent.gas_limit / U256::from(GAS_LIMIT_BOUND_DIVISOR);
+        let child = |gas_limit: U256| {
+            let mut header = Header::new();
+            header.gas_limit = gas_limit;
+            header
+        };
+        
+        for gas_limit in [parent.gas_limit + bound - 1, parent.gas_limit - bound + 1] {
+            assert!(verifier.verify_gas_limit_adjustment(&child(gas_limit), &parent).is_ok());
+        }
+        // A change of exactly the bound is rejected, as by the consensus rules
+        for gas_limit in [parent.gas_limit + bound, parent.gas_limit - bound] {
+            assert!(verifier.verify_gas_limit_adjustment(&child(gas_limit), &parent).is_err());
+            assert!(ethereum_consensus::header_rules::validate_against_parent(
+                &Header { timestamp: 1, ..child(gas_limit) },
+                &parent,
+                0,
+            ).is_err());
+        }
+    }
+}
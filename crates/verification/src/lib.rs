@@ -1,20 +1,19 @@
-use ethereum_types::{H256, U256, Address};
-use ethereum_core::{Block, Header, Transaction, Receipt};
-use ethereum_storage::Database;
-use ethereum_consensus::{Consensus, ConsensusConfig, EngineType};
-use ethereum_evm::EVM;
-use ethereum_trie::PatriciaTrie;
+use ethereum_types::{H256, U256};
+use ethereum_core::{Block, Header, Receipt};
+use ethereum_storage::{Database, WriteBatch};
+use ethereum_consensus::{Consensus, ConsensusConfig};
 use std::sync::Arc;
-use std::collections::HashMap;
 use thiserror::Error;
 
 pub mod block;
 pub mod transaction;
 pub mod header;
 pub mod state;
+pub mod cache;
 
-pub use block::BlockVerifier;
-pub use transaction::TransactionVerifier;
+pub use block::{compute_ommers_hash, compute_transactions_root, compute_withdrawals_root, BlockVerifier};
+pub use transaction::{intrinsic_gas, TransactionVerifier};
+pub use cache::{CachedTx, TxCache};
 pub use header::HeaderVerifier;
 pub use state::StateVerifier;
 
@@ -41,6 +40,9 @@ pub enum VerificationError {
     #[error("Gas limit exceeded")]
     GasLimitExceeded,
     
+    #[error("No state executor configured")]
+    NoStateExecutor,
+    
     #[error("Consensus error: {0}")]
     ConsensusError(#[from] ethereum_consensus::ConsensusError),
     
@@ -50,11 +52,37 @@ pub enum VerificationError {
 
 pub type Result<T> = std::result::Result<T, VerificationError>;
 
+/// Whether `error` rejects the block itself, rather than saying the node
+/// can't check it: storage failures, unknown parents and a missing executor
+/// don't
+pub fn is_rejection(error: &VerificationError) -> bool {
+    !matches!(
+        error,
+        VerificationError::StorageError(_) | VerificationError::ParentNotFound | VerificationError::NoStateExecutor
+    )
+}
+
+/// Executes a block on top of its parent's state
+pub trait StateExecutor: Send + Sync {
+    /// Run the transactions of `block`, its rewards and withdrawals on the
+    /// state at `parent_state_root`, staging the post-state trie nodes in
+    /// `batch`
+    fn execute(&self, block: &Block, parent_state_root: H256, batch: &mut dyn WriteBatch) -> Result<ExecutedBlock>;
+}
+
+/// What executing a block produced
+#[derive(Debug, Clone)]
+pub struct ExecutedBlock {
+    pub receipts: Vec<Receipt>,
+    pub state_root: H256,
+}
+
 /// Main verification engine
 pub struct VerificationEngine<D: Database> {
     db: Arc<D>,
     consensus: Arc<Consensus<D>>,
-    evm: Arc<EVM<D>>,
+    executor: Option<Arc<dyn StateExecutor>>,
+    tx_cache: Arc<TxCache>,
     config: VerificationConfig,
 }
 
@@ -86,18 +114,79 @@ impl<D: Database + 'static> VerificationEngine<D> {
         verification_config: VerificationConfig,
     ) -> Self {
         let consensus = Arc::new(Consensus::new(consensus_config, db.clone()));
-        let evm = Arc::new(EVM::new(db.clone()));
         
         Self {
             db,
             consensus,
-            evm,
+            executor: None,
+            tx_cache: Arc::new(TxCache::default()),
             config: verification_config,
         }
     }
     
+    /// Execute blocks with `executor`
+    ///
+    /// Without one, every check that needs the post-state fails.
+    pub fn with_executor(mut self, executor: Arc<dyn StateExecutor>) -> Self {
+        self.executor = Some(executor);
+        self
+    }
+    
+    /// Share recovered senders with the execution path through `cache`
+    pub fn with_tx_cache(mut self, cache: Arc<TxCache>) -> Self {
+        self.tx_cache = cache;
+        self
+    }
+    
+    /// Cache of recovered senders and intrinsic gas filled by verification
+    pub fn tx_cache(&self) -> Arc<TxCache> {
+        self.tx_cache.clone()
+    }
+    
     /// Verify a complete block
+    ///
+    /// A rejected block is recorded as a bad block with the reason it failed.
     pub async fn verify_block(&self, block: &Block) -> Result<()> {
+        let result = self.verify_complete(block).await;
+        if let Err(e) = &result {
+            if is_rejection(e) {
+                self.record_bad_block(block, e.to_string())?;
+            }
+        }
+        result
+    }
+    
+    async fn verify_complete(&self, block: &Block) -> Result<()> {
+        self.verify_without_state(block).await?;
+        
+        // 5. Verify state transition
+        if self.config.validate_state_root {
+            self.verify_state_transition(block).await?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Keep a rejected block for `debug_getBadBlocks` with the reason it
+    /// was rejected for
+    pub fn record_bad_block(&self, block: &Block, reason: impl Into<String>) -> Result<()> {
+        ethereum_state::store_bad_block(&*self.db, block, reason)
+            .map_err(|e| VerificationError::InvalidState(e.to_string()))
+    }
+    
+    /// Verify `block` and execute it on its parent's state
+    ///
+    /// Nothing is written: the post-state trie nodes are staged in `batch`
+    /// and the receipts returned, so the caller can store both together
+    /// with the block itself. A rejected block is left to the caller to
+    /// record.
+    pub async fn execute_block(&self, block: &Block, batch: &mut dyn WriteBatch) -> Result<Vec<Receipt>> {
+        self.verify_without_state(block).await?;
+        self.execute_on_parent(block, batch)
+    }
+    
+    /// Header, consensus, transaction and structure checks
+    async fn verify_without_state(&self, block: &Block) -> Result<()> {
         // 1. Verify header
         let header_verifier = HeaderVerifier::new(self.db.clone());
         header_verifier.verify(&block.header).await?;
@@ -107,8 +196,9 @@ impl<D: Database + 'static> VerificationEngine<D> {
             .map_err(|e| VerificationError::ConsensusError(e))?;
         
         // 3. Verify transactions
-        let tx_verifier = TransactionVerifier::new(self.config.chain_id);
-        for tx in &block.body.transactions {
+        let tx_verifier = TransactionVerifier::new(self.config.chain_id)
+            .with_cache(self.tx_cache.clone());
+        for tx in &block.transactions {
             tx_verifier.verify(tx)?;
         }
         
@@ -116,51 +206,33 @@ impl<D: Database + 'static> VerificationEngine<D> {
         let block_verifier = BlockVerifier::new(self.db.clone());
         block_verifier.verify_structure(block)?;
         
-        // 5. Verify state transition
-        if self.config.validate_state_root {
-            self.verify_state_transition(block).await?;
-        }
-        
         Ok(())
     }
     
     /// Verify state transition by executing block
     async fn verify_state_transition(&self, block: &Block) -> Result<()> {
-        // Get parent state
-        let parent_state_root = self.get_parent_state_root(&block.header)?;
-        
-        // Initialize state from parent
-        let mut state = PatriciaTrie::new_with_root(self.db.clone(), parent_state_root);
-        
-        // Execute transactions
-        let mut receipts = Vec::new();
-        let mut cumulative_gas = U256::zero();
-        
-        for tx in &block.body.transactions {
-            // Execute transaction
-            let receipt = self.execute_transaction(
-                tx,
-                &mut state,
-                &block.header,
-                cumulative_gas,
-            ).await?;
-            
-            cumulative_gas = cumulative_gas + receipt.gas_used;
-            receipts.push(receipt);
-        }
+        let mut batch = self.db.batch();
+        self.execute_on_parent(block, batch.as_mut())?;
+        self.db.write_batch(batch)?;
+        Ok(())
+    }
+    
+    /// Execute `block` on its parent's state and check the results against
+    /// its header, returning the receipts
+    fn execute_on_parent(&self, block: &Block, batch: &mut dyn WriteBatch) -> Result<Vec<Receipt>> {
+        let executor = self.executor.as_ref().ok_or(VerificationError::NoStateExecutor)?;
         
-        // Apply block rewards
-        self.apply_block_rewards(&mut state, &block.header).await?;
+        let parent_state_root = self.get_parent_state_root(&block.header)?;
+        let executed = executor.execute(block, parent_state_root, batch)?;
         
         // Verify final state root
-        let computed_state_root = state.commit().await?;
-        if computed_state_root != block.header.state_root {
+        if executed.state_root != block.header.state_root {
             return Err(VerificationError::StateRootMismatch);
         }
         
         // Verify receipts root
         if self.config.validate_receipts_root {
-            let computed_receipts_root = self.compute_receipts_root(&receipts);
+            let computed_receipts_root = self.compute_receipts_root(&executed.receipts);
             if computed_receipts_root != block.header.receipts_root {
                 return Err(VerificationError::InvalidBlock(
                     "Receipts root mismatch".to_string()
@@ -169,6 +241,8 @@ impl<D: Database + 'static> VerificationEngine<D> {
         }
         
         // Verify gas used
+        let cumulative_gas = executed.receipts.last()
+            .map_or(U256::zero(), |receipt| receipt.cumulative_gas_used);
         if cumulative_gas != block.header.gas_used {
             return Err(VerificationError::InvalidBlock(
                 format!("Gas used mismatch: expected {}, got {}", 
@@ -176,73 +250,14 @@ impl<D: Database + 'static> VerificationEngine<D> {
             ));
         }
         
-        Ok(())
-    }
-    
-    /// Execute a single transaction
-    async fn execute_transaction(
-        &self,
-        tx: &Transaction,
-        state: &mut PatriciaTrie<D>,
-        header: &Header,
-        cumulative_gas: U256,
-    ) -> Result<Receipt> {
-        // Create EVM context
-        let context = ethereum_evm::Context {
-            block_number: header.number,
-            timestamp: header.timestamp,
-            gas_limit: header.gas_limit,
-            coinbase: header.author,
-            difficulty: header.difficulty,
-            chain_id: self.config.chain_id,
-        };
-        
-        // Execute transaction in EVM
-        let result = self.evm.execute_transaction(tx, state, &context).await
-            .map_err(|e| VerificationError::InvalidTransaction(e.to_string()))?;
-        
-        // Create receipt
-        let receipt = Receipt {
-            status: if result.success { 1 } else { 0 },
-            cumulative_gas_used: cumulative_gas + result.gas_used,
-            logs_bloom: result.logs_bloom,
-            logs: result.logs,
-            gas_used: result.gas_used,
-            contract_address: result.contract_address,
-        };
-        
-        Ok(receipt)
-    }
-    
-    /// Apply block rewards
-    async fn apply_block_rewards(
-        &self,
-        state: &mut PatriciaTrie<D>,
-        header: &Header,
-    ) -> Result<()> {
-        let reward = self.consensus.get_engine().block_reward(header.number);
-        
-        // Add reward to coinbase account
-        let coinbase_key = header.author.as_bytes();
-        let mut account = state.get(coinbase_key).await?
-            .map(|data| bincode::deserialize(&data).unwrap())
-            .unwrap_or_else(|| ethereum_core::Account::default());
-        
-        account.balance = account.balance + reward;
-        
-        state.insert(
-            coinbase_key,
-            bincode::serialize(&account).unwrap(),
-        ).await?;
-        
-        Ok(())
+        Ok(executed.receipts)
     }
     
     /// Get parent state root
     fn get_parent_state_root(&self, header: &Header) -> Result<H256> {
         if header.number == U256::zero() {
             // Genesis block
-            return Ok(H256::zero());
+            return Ok(ethereum_trie::empty_root());
         }
         
         let parent_key = format!("header:{}", hex::encode(header.parent_hash));
@@ -256,23 +271,11 @@ impl<D: Database + 'static> VerificationEngine<D> {
     }
     
     /// Compute receipts root
+    ///
+    /// Receipts are keyed by `rlp(index)` and stored in their EIP-2718
+    /// envelope, so typed receipts carry their transaction type prefix.
     fn compute_receipts_root(&self, receipts: &[Receipt]) -> H256 {
-        if receipts.is_empty() {
-            return H256::from([0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6,
-                              0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
-                              0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0,
-                              0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21]);
-        }
-        
-        // Build Merkle Patricia Trie of receipts
-        let mut data = Vec::new();
-        for receipt in receipts {
-            data.extend_from_slice(&ethereum_crypto::keccak256(
-                &bincode::serialize(receipt).unwrap()
-            ));
-        }
-        
-        H256(ethereum_crypto::keccak256(&data))
+        ethereum_trie::ordered_trie_root(receipts.iter().map(|receipt| receipt.encoded_2718()))
     }
     
     /// Verify a batch of blocks
@@ -301,6 +304,67 @@ impl<D: Database + 'static> VerificationEngine<D> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ethereum_consensus::{BlockAssembler, BlockExecutor, Clique, EngineType};
+    use ethereum_core::{LegacyTransaction, Transaction};
+    use ethereum_crypto::{generate_private_key, public_key_to_address, sign_message};
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_trie::ordered_trie_root;
+    use ethereum_txpool::{MemoryNonceProvider, TransactionPool, TxPoolConfig};
+    use ethereum_types::{Address, Bytes};
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+    
+    /// Charges every transaction as a plain transfer and leaves the state
+    /// untouched
+    struct TransferExecutor;
+    
+    impl BlockExecutor for TransferExecutor {
+        fn execute(&self, mut block: Block) -> ethereum_consensus::Result<Block> {
+            let mut receipts = Vec::new();
+            let mut cumulative_gas_used = U256::zero();
+            for tx in &block.transactions {
+                cumulative_gas_used += U256::from(21_000);
+                receipts.push(Receipt {
+                    tx_type: tx.tx_type(),
+                    status: 1,
+                    cumulative_gas_used,
+                    logs_bloom: Default::default(),
+                    logs: Vec::new(),
+                    gas_used: U256::from(21_000),
+                    contract_address: None,
+                });
+            }
+            
+            let header = &mut block.header;
+            header.gas_used = cumulative_gas_used;
+            header.transactions_root = ordered_trie_root(block.transactions.iter().map(|tx| tx.encoded_2718()));
+            header.receipts_root = ordered_trie_root(receipts.iter().map(|receipt| receipt.encoded_2718()));
+            header.withdrawals_root = block.withdrawals.as_deref().map(compute_withdrawals_root);
+            Ok(block)
+        }
+    }
+    
+    fn address_of(key: &SecretKey) -> Address {
+        public_key_to_address(&PublicKey::from_secret_key(&Secp256k1::new(), key))
+    }
+    
+    fn transfer(key: &SecretKey, nonce: u64) -> Transaction {
+        let mut tx = LegacyTransaction {
+            nonce: U256::from(nonce),
+            gas_price: U256::from(2_000_000_000u64),
+            gas_limit: U256::from(21_000),
+            to: Some(Address::from_bytes([0x42; 20])),
+            value: U256::from(1_000),
+            data: Bytes::default(),
+            v: 27,
+            r: U256::one(),
+            s: U256::one(),
+        };
+        let signature = sign_message(&tx.signing_hash(None), key).unwrap();
+        tx.v = signature.v as u64;
+        tx.r = U256::from_big_endian(signature.r.as_bytes());
+        tx.s = U256::from_big_endian(signature.s.as_bytes());
+        Transaction::Legacy(tx)
+    }
     
     #[test]
     fn test_verification_config_default() {
@@ -308,4 +372,91 @@ mod tests {
         assert_eq!(config.chain_id, 1);
         assert_eq!(config.max_block_gas, U256::from(30_000_000));
     }
-}
\ No newline at end of file
+    
+    /// Block with two signed transfers, sealed by a single Clique signer
+    async fn produce_clique_block(db: &Arc<MemoryDatabase>) -> (ConsensusConfig, Block) {
+        let signer_key = generate_private_key();
+        let signer = address_of(&signer_key);
+        let config = ConsensusConfig {
+            engine_type: EngineType::Clique,
+            epoch_length: 30_000,
+            block_period: 5,
+            validators: vec![signer],
+            genesis_validators: vec![signer],
+        };
+        
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut parent = Header::new();
+        parent.number = U256::one();
+        parent.gas_limit = U256::from(30_000_000);
+        parent.gas_used = U256::from(15_000_000);
+        parent.base_fee_per_gas = Some(U256::from(1_000_000_000u64));
+        parent.timestamp = now - 60;
+        db.put(
+            format!("header:{}", hex::encode(parent.hash())).as_bytes(),
+            &bincode::serialize(&parent).unwrap(),
+        ).unwrap();
+        
+        let sender_key = generate_private_key();
+        let pool = Arc::new(TransactionPool::new(TxPoolConfig::default(), Arc::new(MemoryNonceProvider::new())));
+        pool.add_transaction(transfer(&sender_key, 0)).unwrap();
+        pool.add_transaction(transfer(&sender_key, 1)).unwrap();
+        
+        let assembler = Arc::new(BlockAssembler::new(Arc::new(TransferExecutor)).with_pool(pool));
+        let clique = Clique::new(config.clone())
+            .with_assembler(assembler)
+            .with_signer_key(signer_key);
+        let consensus = Consensus::new(config.clone(), db.clone()).with_engine(Box::new(clique));
+        
+        let block = consensus.produce_block(&parent, Vec::new(), signer).await.unwrap();
+        (config, block)
+    }
+    
+    #[tokio::test]
+    async fn test_produced_block_verifies() {
+        let db = Arc::new(MemoryDatabase::new());
+        let (config, block) = produce_clique_block(&db).await;
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(block.header.number, U256::from(2));
+        assert_eq!(block.header.gas_used, U256::from(42_000));
+        // The parent hit its gas target exactly
+        assert_eq!(block.header.base_fee_per_gas, Some(U256::from(1_000_000_000u64)));
+        
+        // State is left to the executor, everything else is checked
+        let verification_config = VerificationConfig {
+            validate_state_root: false,
+            ..Default::default()
+        };
+        let engine = VerificationEngine::new(db, config, verification_config);
+        engine.verify_block(&block).await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_reverification_reuses_recovered_senders() {
+        let db = Arc::new(MemoryDatabase::new());
+        let (config, block) = produce_clique_block(&db).await;
+        
+        let verification_config = VerificationConfig {
+            validate_state_root: false,
+            ..Default::default()
+        };
+        let cache = Arc::new(TxCache::default());
+        let engine = VerificationEngine::new(db, config, verification_config)
+            .with_tx_cache(cache.clone());
+        
+        engine.verify_block(&block).await.unwrap();
+        assert_eq!(cache.recoveries(), 2);
+        
+        engine.verify_block(&block).await.unwrap();
+        assert_eq!(cache.recoveries(), 2);
+        
+        // Execution finds the senders already recovered
+        let sender = cache.sender(&block.transactions[0]).unwrap();
+        assert_eq!(sender, block.transactions[0].sender().unwrap());
+        assert_eq!(cache.intrinsic_gas(&block.transactions[1]).unwrap(), 21_000);
+        assert_eq!(cache.recoveries(), 2);
+    }
+}
@@ -1,42 +1,50 @@
 use ethereum_types::{H256, U256, Address};
-use ethereum_core::{Block, Account};
+use ethereum_core::Block;
+use ethereum_crypto::keccak256;
+use ethereum_state::StateAccount;
 use ethereum_storage::Database;
 use ethereum_trie::{PatriciaTrie, MerkleProof};
 use std::sync::Arc;
 use std::collections::HashMap;
 
-use crate::{Result, VerificationError};
+use crate::{Result, TxCache, VerificationError};
 
 /// State verifier
 pub struct StateVerifier<D: Database> {
     db: Arc<D>,
+    cache: Arc<TxCache>,
 }
 
 impl<D: Database> StateVerifier<D> {
     pub fn new(db: Arc<D>) -> Self {
-        Self { db }
+        Self {
+            db,
+            cache: Arc::new(TxCache::default()),
+        }
+    }
+    
+    /// Reuse senders recovered by transaction verification through `cache`
+    pub fn with_cache(mut self, cache: Arc<TxCache>) -> Self {
+        self.cache = cache;
+        self
     }
     
     /// Verify state root
-    pub async fn verify_state_root(
+    pub fn verify_state_root(
         &self,
         state_root: H256,
-        accounts: &HashMap<Address, Account>,
+        accounts: &HashMap<Address, StateAccount>,
     ) -> Result<()> {
-        // Build state trie from accounts
+        // Build state trie from accounts, keyed by the hash of the address
         let mut trie = PatriciaTrie::new(self.db.clone());
         
         for (address, account) in accounts {
-            let key = address.as_bytes();
-            let value = bincode::serialize(account)
-                .map_err(|_| VerificationError::InvalidState("Failed to serialize account".to_string()))?;
-            
-            trie.insert(key, value).await
+            trie.insert(keccak256(address.as_bytes()).as_bytes(), account.encode())
                 .map_err(|_| VerificationError::InvalidState("Failed to insert account".to_string()))?;
         }
         
         // Commit and get root
-        let computed_root = trie.commit().await
+        let computed_root = trie.commit()
             .map_err(|_| VerificationError::InvalidState("Failed to commit trie".to_string()))?;
         
         if computed_root != state_root {
@@ -47,18 +55,17 @@ impl<D: Database> StateVerifier<D> {
     }
     
     /// Verify account proof
-    pub async fn verify_account_proof(
+    pub fn verify_account_proof(
         &self,
         state_root: H256,
         address: Address,
-        account: &Account,
+        account: &StateAccount,
         proof: &MerkleProof,
     ) -> Result<()> {
-        let key = address.as_bytes();
-        let value = bincode::serialize(account)
-            .map_err(|_| VerificationError::InvalidState("Failed to serialize account".to_string()))?;
+        let key = keccak256(address.as_bytes());
+        let value = account.encode();
         
-        let valid = proof.verify(&state_root, key, Some(&value))
+        let valid = proof.verify(&state_root, key.as_bytes(), Some(&value))
             .map_err(|_| VerificationError::InvalidState("Failed to verify proof".to_string()))?;
         
         if !valid {
@@ -71,17 +78,20 @@ impl<D: Database> StateVerifier<D> {
     }
     
     /// Verify storage proof
-    pub async fn verify_storage_proof(
+    pub fn verify_storage_proof(
         &self,
         storage_root: H256,
         key: H256,
         value: H256,
         proof: &MerkleProof,
     ) -> Result<()> {
+        // Slots are keyed by the hash of the slot and hold the RLP of the
+        // value with its leading zeros stripped
+        let value = ethereum_rlp::encode(&U256::from_big_endian(value.as_bytes()));
         let valid = proof.verify(
             &storage_root,
-            key.as_bytes(),
-            Some(value.as_bytes()),
+            keccak256(key.as_bytes()).as_bytes(),
+            Some(&value[..]),
         ).map_err(|_| VerificationError::InvalidState("Failed to verify storage proof".to_string()))?;
         
         if !valid {
@@ -94,17 +104,17 @@ impl<D: Database> StateVerifier<D> {
     }
     
     /// Verify state transition
-    pub async fn verify_state_transition(
+    pub fn verify_state_transition(
         &self,
-        pre_state: &HashMap<Address, Account>,
-        post_state: &HashMap<Address, Account>,
+        pre_state: &HashMap<Address, StateAccount>,
+        post_state: &HashMap<Address, StateAccount>,
         block: &Block,
     ) -> Result<()> {
         // Clone pre-state to working state
         let mut working_state = pre_state.clone();
         
         // Apply transactions
-        for tx in &block.body.transactions {
+        for tx in &block.transactions {
             self.apply_transaction(&mut working_state, tx)?;
         }
         
@@ -124,31 +134,38 @@ impl<D: Database> StateVerifier<D> {
     /// Apply transaction to state (simplified)
     fn apply_transaction(
         &self,
-        state: &mut HashMap<Address, Account>,
+        state: &mut HashMap<Address, StateAccount>,
         tx: &ethereum_core::Transaction,
     ) -> Result<()> {
         // Get sender
         let sender = self.recover_sender(tx)?;
         
+        let intrinsic = self.cache.intrinsic_gas(tx)?;
+        if tx.gas_limit() < U256::from(intrinsic) {
+            return Err(VerificationError::InvalidState(
+                format!("Intrinsic gas too low: {} < {}", tx.gas_limit(), intrinsic)
+            ));
+        }
+        
         // Deduct gas cost and value from sender
         let sender_account = state.get_mut(&sender)
             .ok_or_else(|| VerificationError::InvalidState("Sender account not found".to_string()))?;
         
-        let gas_cost = tx.gas_limit * tx.gas_price.unwrap_or(U256::zero());
-        if sender_account.balance < gas_cost + tx.value {
+        let gas_cost = tx.gas_limit() * tx.gas_price();
+        if sender_account.balance < gas_cost + tx.value() {
             return Err(VerificationError::InvalidState(
                 "Insufficient balance".to_string()
             ));
         }
         
-        sender_account.balance = sender_account.balance - gas_cost - tx.value;
+        sender_account.balance = sender_account.balance - gas_cost - tx.value();
         sender_account.nonce += 1;
         
         // Add value to recipient
-        if let Some(to) = tx.to {
+        if let Some(to) = tx.to() {
             let recipient_account = state.entry(to)
-                .or_insert_with(Account::default);
-            recipient_account.balance = recipient_account.balance + tx.value;
+                .or_insert_with(StateAccount::default);
+            recipient_account.balance = recipient_account.balance + tx.value();
         } else {
             // Contract creation
             // Would need to deploy contract and set code
@@ -160,23 +177,23 @@ impl<D: Database> StateVerifier<D> {
     /// Apply block rewards
     fn apply_block_rewards(
         &self,
-        state: &mut HashMap<Address, Account>,
+        state: &mut HashMap<Address, StateAccount>,
         block: &Block,
     ) -> Result<()> {
         // Base block reward (simplified)
         let base_reward = U256::from(2_000_000_000_000_000_000u128); // 2 ETH
         
         // Reward to miner
-        let miner_account = state.entry(block.header.author)
-            .or_insert_with(Account::default);
+        let miner_account = state.entry(block.header.beneficiary)
+            .or_insert_with(StateAccount::default);
         
         miner_account.balance = miner_account.balance + base_reward;
         
         // Uncle rewards
-        for uncle in &block.body.uncles {
+        for uncle in &block.ommers {
             let uncle_reward = base_reward / U256::from(8);
-            let uncle_account = state.entry(uncle.author)
-                .or_insert_with(Account::default);
+            let uncle_account = state.entry(uncle.beneficiary)
+                .or_insert_with(StateAccount::default);
             uncle_account.balance = uncle_account.balance + uncle_reward;
         }
         
@@ -185,14 +202,13 @@ impl<D: Database> StateVerifier<D> {
     
     /// Recover sender from transaction
     fn recover_sender(&self, tx: &ethereum_core::Transaction) -> Result<Address> {
-        // Simplified - would use proper signature recovery
-        Ok(Address::from([1u8; 20]))
+        self.cache.sender(tx)
     }
     
     /// Verify account balance
     pub fn verify_balance(
         &self,
-        account: &Account,
+        account: &StateAccount,
         expected_balance: U256,
     ) -> Result<()> {
         if account.balance != expected_balance {
@@ -207,7 +223,7 @@ impl<D: Database> StateVerifier<D> {
     /// Verify account nonce
     pub fn verify_nonce(
         &self,
-        account: &Account,
+        account: &StateAccount,
         expected_nonce: u64,
     ) -> Result<()> {
         if account.nonce != expected_nonce {
@@ -218,4 +234,65 @@ impl<D: Database> StateVerifier<D> {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::{Header, LegacyTransaction, Transaction};
+    use ethereum_crypto::{generate_private_key, public_key_to_address, sign_message};
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_types::Bytes;
+    use secp256k1::{PublicKey, Secp256k1};
+    
+    #[test]
+    fn test_state_transition_charges_recovered_sender() {
+        let key = generate_private_key();
+        let sender = public_key_to_address(&PublicKey::from_secret_key(&Secp256k1::new(), &key));
+        let recipient = Address::from_bytes([0x42; 20]);
+        
+        let mut tx = LegacyTransaction {
+            nonce: U256::zero(),
+            gas_price: U256::from(10),
+            gas_limit: U256::from(21_000),
+            to: Some(recipient),
+            value: U256::from(1_000),
+            data: Bytes::default(),
+            v: 27,
+            r: U256::one(),
+            s: U256::one(),
+        };
+        let signature = sign_message(&tx.signing_hash(None), &key).unwrap();
+        tx.v = signature.v as u64;
+        tx.r = U256::from_big_endian(signature.r.as_bytes());
+        tx.s = U256::from_big_endian(signature.s.as_bytes());
+        
+        let mut block = Block::new(Header::new());
+        block.transactions = vec![Transaction::Legacy(tx)];
+        
+        let funded = StateAccount { balance: U256::from(1_000_000), ..Default::default() };
+        let pre_state = HashMap::from([(sender, funded)]);
+        
+        let mut post_state = HashMap::new();
+        post_state.insert(sender, StateAccount {
+            nonce: 1,
+            balance: U256::from(1_000_000 - 210_000 - 1_000),
+            ..Default::default()
+        });
+        post_state.insert(recipient, StateAccount { balance: U256::from(1_000), ..Default::default() });
+        post_state.insert(block.header.beneficiary, StateAccount {
+            balance: U256::from(2_000_000_000_000_000_000u128),
+            ..Default::default()
+        });
+        
+        let verifier = StateVerifier::new(Arc::new(MemoryDatabase::new()));
+        verifier.verify_state_transition(&pre_state, &post_state, &block).unwrap();
+        
+        // A gas limit below the intrinsic gas of a transfer is rejected
+        if let Transaction::Legacy(tx) = &mut block.transactions[0] {
+            tx.gas_limit = U256::from(20_999);
+        }
+        let err = verifier.verify_state_transition(&pre_state, &post_state, &block).unwrap_err();
+        assert!(err.to_string().contains("Intrinsic gas too low"));
+    }
+}
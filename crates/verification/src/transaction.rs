@@ -1,17 +1,33 @@
-use ethereum_types::{H256, U256, Address};
+use ethereum_types::{U256, Address};
 use ethereum_core::Transaction;
-use ethereum_crypto::{recover_address, keccak256};
+use std::sync::Arc;
 
+use crate::cache::TxCache;
 use crate::{Result, VerificationError};
 
+pub use ethereum_state::intrinsic::{
+    access_list_gas, intrinsic_gas, INIT_CODE_WORD_GAS, PER_AUTH_BASE_GAS, TX_ACCESS_LIST_ADDRESS_GAS,
+    TX_ACCESS_LIST_STORAGE_KEY_GAS, TX_CREATE_GAS, TX_DATA_NON_ZERO_GAS, TX_DATA_ZERO_GAS, TX_GAS,
+};
+
 /// Transaction verifier
 pub struct TransactionVerifier {
     chain_id: u64,
+    cache: Arc<TxCache>,
 }
 
 impl TransactionVerifier {
     pub fn new(chain_id: u64) -> Self {
-        Self { chain_id }
+        Self {
+            chain_id,
+            cache: Arc::new(TxCache::default()),
+        }
+    }
+    
+    /// Share recovered senders and intrinsic gas with other users of `cache`
+    pub fn with_cache(mut self, cache: Arc<TxCache>) -> Self {
+        self.cache = cache;
+        self
     }
     
     /// Verify transaction
@@ -24,6 +40,7 @@ impl TransactionVerifier {
         
         // Verify gas parameters
         self.verify_gas_parameters(tx)?;
+        self.verify_intrinsic_gas(tx)?;
         
         // Verify transaction type
         self.verify_transaction_type(tx)?;
@@ -36,12 +53,6 @@ impl TransactionVerifier {
     
     /// Verify transaction signature
     fn verify_signature(&self, tx: &Transaction) -> Result<()> {
-        if !tx.signature.is_valid() {
-            return Err(VerificationError::InvalidTransaction(
-                "Invalid signature".to_string()
-            ));
-        }
-        
         // Verify we can recover sender
         let sender = self.recover_sender(tx)?;
         
@@ -57,66 +68,28 @@ impl TransactionVerifier {
     
     /// Recover transaction sender
     fn recover_sender(&self, tx: &Transaction) -> Result<Address> {
-        let message = self.signing_hash(tx);
-        
-        recover_address(&message, &tx.signature)
-            .map_err(|_| VerificationError::InvalidTransaction(
-                "Failed to recover sender".to_string()
-            ))
-    }
-    
-    /// Calculate signing hash for transaction
-    fn signing_hash(&self, tx: &Transaction) -> [u8; 32] {
-        // Build message based on transaction type
-        let mut data = Vec::new();
-        
-        // Add transaction fields
-        data.extend_from_slice(&tx.nonce.to_le_bytes());
-        
-        if let Some(gas_price) = tx.gas_price {
-            data.extend_from_slice(&gas_price.to_le_bytes());
-        } else if let Some(max_fee) = tx.max_fee_per_gas {
-            data.extend_from_slice(&max_fee.to_le_bytes());
-            if let Some(priority_fee) = tx.max_priority_fee_per_gas {
-                data.extend_from_slice(&priority_fee.to_le_bytes());
-            }
-        }
-        
-        data.extend_from_slice(&tx.gas_limit.to_le_bytes());
-        
-        if let Some(to) = tx.to {
-            data.extend_from_slice(to.as_bytes());
-        }
-        
-        data.extend_from_slice(&tx.value.to_le_bytes());
-        data.extend_from_slice(&tx.input);
-        
-        // Add chain ID for EIP-155
-        if let Some(chain_id) = tx.chain_id {
-            data.extend_from_slice(&chain_id.to_le_bytes());
-        }
-        
-        keccak256(&data)
+        self.cache.sender(tx)
     }
     
     /// Verify chain ID
     fn verify_chain_id(&self, tx: &Transaction) -> Result<()> {
-        // For legacy transactions, chain ID is optional
-        if tx.transaction_type == 0 {
-            return Ok(());
-        }
+        let tx_chain_id = match tx {
+            // Legacy transactions sign the chain into `v` (EIP-155), older
+            // ones without it are valid on every chain
+            Transaction::Legacy(tx) => match tx.chain_id() {
+                Some(chain_id) => chain_id,
+                None => return Ok(()),
+            },
+            Transaction::Eip2930(tx) => tx.chain_id,
+            Transaction::Eip1559(tx) => tx.chain_id,
+            Transaction::Eip4844(tx) => tx.chain_id,
+            Transaction::Eip7702(tx) => tx.chain_id,
+        };
         
-        // For EIP-155 and later, chain ID must match
-        if let Some(tx_chain_id) = tx.chain_id {
-            if tx_chain_id != self.chain_id {
-                return Err(VerificationError::InvalidTransaction(
-                    format!("Wrong chain ID: expected {}, got {}", 
-                            self.chain_id, tx_chain_id)
-                ));
-            }
-        } else if tx.transaction_type > 0 {
+        if tx_chain_id != self.chain_id {
             return Err(VerificationError::InvalidTransaction(
-                "Missing chain ID for typed transaction".to_string()
+                format!("Wrong chain ID: expected {}, got {}", 
+                        self.chain_id, tx_chain_id)
             ));
         }
         
@@ -129,82 +102,77 @@ impl TransactionVerifier {
         const MIN_GAS: u64 = 21000; // Minimum gas for simple transfer
         const MAX_GAS: u64 = 30_000_000; // Maximum block gas limit
         
-        if tx.gas_limit < U256::from(MIN_GAS) {
+        if tx.gas_limit() < U256::from(MIN_GAS) {
             return Err(VerificationError::InvalidTransaction(
-                format!("Gas limit too low: {} < {}", tx.gas_limit, MIN_GAS)
+                format!("Gas limit too low: {} < {}", tx.gas_limit(), MIN_GAS)
             ));
         }
         
-        if tx.gas_limit > U256::from(MAX_GAS) {
+        if tx.gas_limit() > U256::from(MAX_GAS) {
             return Err(VerificationError::InvalidTransaction(
-                format!("Gas limit too high: {} > {}", tx.gas_limit, MAX_GAS)
+                format!("Gas limit too high: {} > {}", tx.gas_limit(), MAX_GAS)
             ));
         }
         
         // Check gas price parameters based on transaction type
-        match tx.transaction_type {
-            0 | 1 => {
-                // Legacy or EIP-2930
-                if tx.gas_price.is_none() || tx.gas_price == Some(U256::zero()) {
+        match tx {
+            Transaction::Legacy(_) | Transaction::Eip2930(_) => {
+                if tx.gas_price().is_zero() {
                     return Err(VerificationError::InvalidTransaction(
                         "Gas price cannot be zero".to_string()
                     ));
                 }
             }
-            2 => {
-                // EIP-1559
-                if tx.max_fee_per_gas.is_none() || tx.max_priority_fee_per_gas.is_none() {
-                    return Err(VerificationError::InvalidTransaction(
-                        "Missing EIP-1559 gas parameters".to_string()
-                    ));
-                }
-                
-                let max_fee = tx.max_fee_per_gas.unwrap();
-                let priority_fee = tx.max_priority_fee_per_gas.unwrap();
-                
-                if max_fee < priority_fee {
+            _ => {
+                if tx.gas_price() < tx.max_priority_fee_per_gas() {
                     return Err(VerificationError::InvalidTransaction(
                         "Max fee less than priority fee".to_string()
                     ));
                 }
             }
-            _ => {
-                return Err(VerificationError::InvalidTransaction(
-                    format!("Unknown transaction type: {}", tx.transaction_type)
-                ));
-            }
         }
         
         Ok(())
     }
     
-    /// Verify transaction type
-    fn verify_transaction_type(&self, tx: &Transaction) -> Result<()> {
-        // Currently support types 0 (legacy), 1 (EIP-2930), 2 (EIP-1559)
-        if tx.transaction_type > 2 {
+    /// Verify the gas limit covers the intrinsic gas, access list included
+    ///
+    /// Storage keys are 32 bytes by construction, decoding rejects keys of
+    /// any other length.
+    fn verify_intrinsic_gas(&self, tx: &Transaction) -> Result<()> {
+        let intrinsic = self.cache.intrinsic_gas(tx)?;
+        if tx.gas_limit() < U256::from(intrinsic) {
             return Err(VerificationError::InvalidTransaction(
-                format!("Unsupported transaction type: {}", tx.transaction_type)
-            ));
-        }
-        
-        // Verify access list for type 1 and 2
-        if tx.transaction_type >= 1 && tx.access_list.is_none() {
-            return Err(VerificationError::InvalidTransaction(
-                "Missing access list for typed transaction".to_string()
+                format!("Intrinsic gas too low: {} < {}", tx.gas_limit(), intrinsic)
             ));
         }
         
         Ok(())
     }
     
+    /// Verify the type-specific payload
+    fn verify_transaction_type(&self, tx: &Transaction) -> Result<()> {
+        match tx {
+            Transaction::Eip4844(tx) if tx.blob_versioned_hashes.is_empty() => {
+                Err(VerificationError::InvalidTransaction(
+                    "Blob transaction without blobs".to_string()
+                ))
+            }
+            Transaction::Eip7702(tx) if tx.authorization_list.is_empty() => {
+                Err(VerificationError::InvalidTransaction(
+                    "Set code transaction without authorizations".to_string()
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+    
     /// Verify nonce
     fn verify_nonce(&self, tx: &Transaction) -> Result<()> {
-        // Basic check - nonce should not be unreasonably high
-        const MAX_NONCE: u64 = u64::MAX / 2;
-        
-        if tx.nonce > MAX_NONCE {
+        // EIP-2681: the nonce can't reach 2^64 - 1
+        if tx.nonce() >= U256::from(u64::MAX) {
             return Err(VerificationError::InvalidTransaction(
-                format!("Nonce too high: {} > {}", tx.nonce, MAX_NONCE)
+                format!("Nonce too high: {}", tx.nonce())
             ));
         }
         
@@ -221,9 +189,7 @@ impl TransactionVerifier {
         // Additional mempool-specific checks
         
         // Check transaction is not too large
-        let tx_size = bincode::serialize(tx)
-            .map_err(|_| VerificationError::InvalidTransaction("Failed to serialize".to_string()))?
-            .len();
+        let tx_size = tx.size();
         
         const MAX_TX_SIZE: usize = 128 * 1024; // 128KB
         if tx_size > MAX_TX_SIZE {
@@ -234,4 +200,71 @@ impl TransactionVerifier {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_core::{AccessListItem, Eip2930Transaction, LegacyTransaction};
+    use ethereum_types::{Bytes, H256};
+
+    fn access_list() -> Vec<AccessListItem> {
+        let a = Address::from_bytes([0xaa; 20]);
+        let b = Address::from_bytes([0xbb; 20]);
+        vec![
+            AccessListItem { address: a, storage_keys: vec![H256::repeat_byte(1), H256::repeat_byte(2)] },
+            AccessListItem { address: b, storage_keys: Vec::new() },
+            // Repeated entries are valid and charged again
+            AccessListItem { address: a, storage_keys: vec![H256::repeat_byte(1)] },
+        ]
+    }
+
+    #[test]
+    fn test_access_list_gas() {
+        assert_eq!(access_list_gas(&[]), 0);
+        assert_eq!(access_list_gas(&access_list()), 3 * 2_400 + 3 * 1_900);
+    }
+
+    #[test]
+    fn test_intrinsic_gas_with_access_list() {
+        let tx = Transaction::Eip2930(Eip2930Transaction {
+            chain_id: 1,
+            nonce: U256::zero(),
+            gas_price: U256::from(1_000_000_000u64),
+            gas_limit: U256::from(100_000),
+            to: Some(Address::from_bytes([0x42; 20])),
+            value: U256::zero(),
+            data: Bytes::from_vec(vec![0x00, 0x01]),
+            access_list: access_list(),
+            y_parity: false,
+            r: U256::zero(),
+            s: U256::zero(),
+        });
+
+        assert_eq!(intrinsic_gas(&tx), 21_000 + 4 + 16 + 12_900);
+    }
+
+    #[test]
+    fn test_legacy_chain_id_from_v() {
+        let legacy = |v: u64| Transaction::Legacy(LegacyTransaction {
+            nonce: U256::zero(),
+            gas_price: U256::from(1_000_000_000u64),
+            gas_limit: U256::from(21_000),
+            to: Some(Address::from_bytes([0x42; 20])),
+            value: U256::zero(),
+            data: Bytes::new(),
+            v,
+            r: U256::one(),
+            s: U256::one(),
+        });
+        let verifier = TransactionVerifier::new(5);
+
+        // EIP-155 signatures commit to `(v - 35) / 2`
+        assert!(verifier.verify_chain_id(&legacy(5 * 2 + 35)).is_ok());
+        assert!(verifier.verify_chain_id(&legacy(5 * 2 + 36)).is_ok());
+        assert!(verifier.verify_chain_id(&legacy(37)).is_err());
+
+        // Unprotected signatures are valid on every chain
+        assert!(verifier.verify_chain_id(&legacy(27)).is_ok());
+    }
+}
@@ -3,6 +3,7 @@ use std::fs;
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
 use ethereum_types::U256;
+use ethereum_rpc::RpcLimits;
 
 /// Complete node configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,6 +84,14 @@ pub struct RpcConfig {
     pub apis: Vec<String>,
     /// Gas price oracle configuration
     pub gpo: GasPriceOracleConfig,
+    /// Per-client rate limit, batch and response size limits
+    pub limits: RpcLimits,
+    /// Serve the personal_ namespace from the local keystore
+    pub enable_personal: bool,
+    /// Also serve personal_ when HTTP listens on a non-loopback address
+    pub allow_public_personal: bool,
+    /// Serve the admin_ namespace for managing peers
+    pub enable_admin: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -291,6 +300,10 @@ impl Default for RpcConfig {
                 "web3".to_string(),
             ],
             gpo: GasPriceOracleConfig::default(),
+            limits: RpcLimits::default(),
+            enable_personal: false,
+            allow_public_personal: false,
+            enable_admin: false,
         }
     }
 }
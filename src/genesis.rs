@@ -3,10 +3,13 @@ use std::path::Path;
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
 use ethereum_types::{H256, U256, Address, Bloom};
-use ethereum_core::{Block, Header, BlockBody, Account};
-use ethereum_storage::Database;
+use ethereum_core::{Block, Header};
+use ethereum_rlp::{Encode, Encoder};
+use ethereum_state::StateAccount;
+use ethereum_storage::{Database, MemoryDatabase};
 use ethereum_crypto::keccak256;
-use ethereum_trie::PatriciaTrie;
+use ethereum_evm::ChainConfig;
+use ethereum_trie::{empty_root, PatriciaTrie};
 use std::sync::Arc;
 
 /// Genesis configuration
@@ -35,30 +38,6 @@ pub struct GenesisConfig {
     pub parent_hash: Option<String>,
 }
 
-/// Chain configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ChainConfig {
-    pub chain_id: u64,
-    pub homestead_block: Option<u64>,
-    pub eip150_block: Option<u64>,
-    pub eip155_block: Option<u64>,
-    pub eip158_block: Option<u64>,
-    pub byzantium_block: Option<u64>,
-    pub constantinople_block: Option<u64>,
-    pub petersburg_block: Option<u64>,
-    pub istanbul_block: Option<u64>,
-    pub berlin_block: Option<u64>,
-    pub london_block: Option<u64>,
-    pub arrow_glacier_block: Option<u64>,
-    pub gray_glacier_block: Option<u64>,
-    pub merge_netsplit_block: Option<u64>,
-    pub shanghai_time: Option<u64>,
-    pub cancun_time: Option<u64>,
-    pub terminal_total_difficulty: Option<String>,
-    pub terminal_total_difficulty_passed: Option<bool>,
-}
-
 /// Genesis account
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenesisAccount {
@@ -75,6 +54,34 @@ pub struct GenesisAccount {
     pub storage: Option<HashMap<String, String>>,
 }
 
+/// Initial base fee of a chain with London active from genesis (EIP-1559)
+pub const INITIAL_BASE_FEE: u64 = 1_000_000_000;
+
+/// RLP of an empty list, hashed into the ommers hash of a block without ommers
+const EMPTY_LIST: u8 = 0xc0;
+
+/// Allocated account with its fields parsed
+struct AllocAccount {
+    balance: U256,
+    nonce: u64,
+    code: Vec<u8>,
+    storage: Vec<(H256, H256)>,
+}
+
+impl AllocAccount {
+    /// Storage trie entries: hashed slot to RLP of the value, zero slots omitted
+    fn storage_entries(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.storage.iter()
+            .filter(|(_, value)| !value.is_zero())
+            .map(|(key, value)| {
+                let mut encoder = Encoder::new();
+                U256::from_big_endian(value.as_bytes()).encode(&mut encoder);
+                (keccak256(key.as_bytes()).as_bytes().to_vec(), encoder.finish())
+            })
+            .collect()
+    }
+}
+
 /// Genesis block builder
 pub struct Genesis {
     config: GenesisConfig,
@@ -104,26 +111,7 @@ impl Genesis {
     pub fn mainnet() -> Self {
         Self {
             config: GenesisConfig {
-                config: ChainConfig {
-                    chain_id: 1,
-                    homestead_block: Some(1150000),
-                    eip150_block: Some(2463000),
-                    eip155_block: Some(2675000),
-                    eip158_block: Some(2675000),
-                    byzantium_block: Some(4370000),
-                    constantinople_block: Some(7280000),
-                    petersburg_block: Some(7280000),
-                    istanbul_block: Some(9069000),
-                    berlin_block: Some(12244000),
-                    london_block: Some(12965000),
-                    arrow_glacier_block: Some(13773000),
-                    gray_glacier_block: Some(15050000),
-                    merge_netsplit_block: Some(15537394),
-                    shanghai_time: Some(1681338455),
-                    cancun_time: None,
-                    terminal_total_difficulty: Some("58750000000000000000000".to_string()),
-                    terminal_total_difficulty_passed: Some(true),
-                },
+                config: ChainConfig::mainnet(),
                 nonce: "0x42".to_string(),
                 timestamp: "0x0".to_string(),
                 extra_data: "0x11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa".to_string(),
@@ -144,6 +132,7 @@ impl Genesis {
                 config: ChainConfig {
                     chain_id: 5,
                     homestead_block: Some(0),
+                    dao_fork_block: None,
                     eip150_block: Some(0),
                     eip155_block: Some(0),
                     eip158_block: Some(0),
@@ -158,8 +147,10 @@ impl Genesis {
                     merge_netsplit_block: None,
                     shanghai_time: Some(1678832736),
                     cancun_time: None,
+                    prague_time: None,
                     terminal_total_difficulty: Some("10790000".to_string()),
                     terminal_total_difficulty_passed: Some(true),
+                    rip7212_time: None,
                 },
                 nonce: "0x0".to_string(),
                 timestamp: "0x5c51a607".to_string(),
@@ -174,123 +165,124 @@ impl Genesis {
         }
     }
     
-    /// Build genesis block
-    pub async fn build_block<D: Database>(&self, db: Arc<D>) -> Result<Block> {
-        // Parse values
-        let nonce = self.parse_u64(&self.config.nonce)?;
+    /// Compute the genesis header and state root without touching the node's
+    /// database, the state is built in memory and dropped
+    pub fn build(&self) -> Result<(Header, H256)> {
+        let state_root = self.build_state(Arc::new(MemoryDatabase::new()))?;
+        Ok((self.header(state_root)?, state_root))
+    }
+    
+    /// Genesis header committing to `state_root`
+    ///
+    /// Forks active from genesis on add their header fields: the initial
+    /// base fee for London, an empty withdrawals root for Shanghai and zero
+    /// blob gas for Cancun.
+    pub fn header(&self, state_root: H256) -> Result<Header> {
+        let chain = &self.config.config;
         let timestamp = self.parse_u64(&self.config.timestamp)?;
-        let gas_limit = self.parse_u256(&self.config.gas_limit)?;
-        let difficulty = self.parse_u256(&self.config.difficulty)?;
-        let extra_data = self.parse_bytes(&self.config.extra_data)?;
-        let mix_hash = self.parse_h256(&self.config.mix_hash)?;
-        let coinbase = self.parse_address(&self.config.coinbase)?;
-        
-        // Create state trie with pre-allocated accounts
-        let state_root = self.build_state(db.clone()).await?;
+        let active_at_genesis = |time: Option<u64>| time.map_or(false, |time| time <= timestamp);
+        let cancun = active_at_genesis(chain.cancun_time);
         
-        // Create genesis header
-        let header = Header {
-            parent_hash: H256::zero(),
-            uncles_hash: H256::from([0x1d, 0xcc, 0x4d, 0xe8, 0xde, 0xc7, 0x5d, 0x7a,
-                                     0xab, 0x85, 0xb5, 0x67, 0xb6, 0xcc, 0xd4, 0x1a,
-                                     0xd3, 0x12, 0x45, 0x1b, 0x94, 0x8a, 0x74, 0x13,
-                                     0xf0, 0xa1, 0x42, 0xfd, 0x40, 0xd4, 0x93, 0x47]),
-            author: coinbase,
+        Ok(Header {
+            parent_hash: match &self.config.parent_hash {
+                Some(parent_hash) => self.parse_h256(parent_hash)?,
+                None => H256::zero(),
+            },
+            ommers_hash: keccak256(&[EMPTY_LIST]),
+            beneficiary: self.parse_address(&self.config.coinbase)?,
             state_root,
-            transactions_root: H256::from([0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6,
-                                           0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
-                                           0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0,
-                                           0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21]),
-            receipts_root: H256::from([0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6,
-                                       0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
-                                       0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0,
-                                       0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21]),
-            bloom: Bloom::default(),
-            difficulty,
+            transactions_root: empty_root(),
+            receipts_root: empty_root(),
+            logs_bloom: Bloom::default(),
+            difficulty: self.parse_u256(&self.config.difficulty)?,
             number: U256::zero(),
-            gas_limit,
+            gas_limit: self.parse_u256(&self.config.gas_limit)?,
             gas_used: U256::zero(),
             timestamp,
-            extra_data,
-            mix_hash,
-            nonce,
-        };
+            extra_data: self.parse_bytes(&self.config.extra_data)?,
+            mix_hash: self.parse_h256(&self.config.mix_hash)?,
+            nonce: self.parse_u64(&self.config.nonce)?,
+            base_fee_per_gas: (chain.london_block == Some(0)).then(|| U256::from(INITIAL_BASE_FEE)),
+            blob_gas_used: cancun.then_some(0),
+            excess_blob_gas: cancun.then_some(0),
+            parent_beacon_block_root: cancun.then(H256::zero),
+            withdrawals_root: active_at_genesis(chain.shanghai_time).then(empty_root),
+        })
+    }
+    
+    /// Build genesis block, writing its state into `db`
+    pub async fn build_block<D: Database>(&self, db: Arc<D>) -> Result<Block> {
+        let state_root = self.build_state(db)?;
+        let header = self.header(state_root)?;
+        let withdrawals = header.withdrawals_root.map(|_| Vec::new());
         
-        // Create genesis block
-        let block = Block {
+        Ok(Block {
             header,
-            body: BlockBody {
-                transactions: vec![],
-                uncles: vec![],
-            },
-        };
-        
-        Ok(block)
+            transactions: vec![],
+            ommers: vec![],
+            withdrawals,
+        })
     }
     
-    /// Build state trie with pre-allocated accounts
-    async fn build_state<D: Database>(&self, db: Arc<D>) -> Result<H256> {
+    /// Write the allocated accounts, their storage and code into `db`
+    fn build_state<D: Database>(&self, db: Arc<D>) -> Result<H256> {
         let mut state = PatriciaTrie::new(db.clone());
         
-        for (address_str, genesis_account) in &self.config.alloc {
-            let address = self.parse_address(address_str)?;
-            let balance = self.parse_u256(&genesis_account.balance)?;
-            
-            // Create account
-            let mut account = Account {
-                nonce: genesis_account.nonce.unwrap_or(0),
-                balance,
-                storage_root: H256::zero(),
-                code_hash: H256::zero(),
-                code: vec![],
-            };
-            
-            // Set code if provided
-            if let Some(ref code_str) = genesis_account.code {
-                let code = self.parse_bytes(code_str)?;
-                account.code_hash = H256(keccak256(&code));
-                account.code = code;
+        for (address, account) in self.alloc()? {
+            let mut storage = PatriciaTrie::new(db.clone());
+            for (key, value) in account.storage_entries() {
+                storage.insert(&key, value)
+                    .context("Failed to insert storage value")?;
             }
+            let storage_root = storage.commit()
+                .context("Failed to commit storage trie")?;
             
-            // Set storage if provided
-            if let Some(ref storage) = genesis_account.storage {
-                let storage_root = self.build_storage(db.clone(), storage).await?;
-                account.storage_root = storage_root;
+            let code_hash = keccak256(&account.code);
+            if !account.code.is_empty() {
+                db.put(format!("code:{}", hex::encode(code_hash.as_bytes())).as_bytes(), &account.code)?;
             }
             
-            // Insert account into state trie
-            let account_bytes = bincode::serialize(&account)?;
-            state.insert(address.as_bytes(), account_bytes).await
+            let state_account = StateAccount {
+                nonce: account.nonce,
+                balance: account.balance,
+                storage_root,
+                code_hash,
+            };
+            state.insert(keccak256(address.as_bytes()).as_bytes(), state_account.encode())
                 .context("Failed to insert account into state trie")?;
         }
         
-        // Commit state trie and get root
-        let state_root = state.commit().await
+        let state_root = state.commit()
             .context("Failed to commit state trie")?;
         
         Ok(state_root)
     }
     
-    /// Build storage trie for an account
-    async fn build_storage<D: Database>(
-        &self,
-        db: Arc<D>,
-        storage: &HashMap<String, String>,
-    ) -> Result<H256> {
-        let mut storage_trie = PatriciaTrie::new(db);
+    /// Parsed pre-allocated accounts
+    fn alloc(&self) -> Result<Vec<(Address, AllocAccount)>> {
+        let mut alloc = Vec::with_capacity(self.config.alloc.len());
         
-        for (key_str, value_str) in storage {
-            let key = self.parse_h256(key_str)?;
-            let value = self.parse_h256(value_str)?;
+        for (address_str, genesis_account) in &self.config.alloc {
+            let address = self.parse_address(address_str)
+                .with_context(|| format!("Invalid alloc address {}", address_str))?;
+            
+            let mut storage = Vec::new();
+            for (key_str, value_str) in genesis_account.storage.iter().flatten() {
+                storage.push((self.parse_h256(key_str)?, self.parse_h256(value_str)?));
+            }
             
-            storage_trie.insert(key.as_bytes(), value.as_bytes().to_vec()).await
-                .context("Failed to insert storage value")?;
+            alloc.push((address, AllocAccount {
+                balance: self.parse_u256(&genesis_account.balance)?,
+                nonce: genesis_account.nonce.unwrap_or(0),
+                code: match &genesis_account.code {
+                    Some(code) => self.parse_bytes(code)?,
+                    None => Vec::new(),
+                },
+                storage,
+            }));
         }
         
-        let storage_root = storage_trie.commit().await
-            .context("Failed to commit storage trie")?;
-        
-        Ok(storage_root)
+        Ok(alloc)
     }
     
     /// Initialize database with genesis block
@@ -306,7 +298,7 @@ impl Genesis {
         )?;
         
         // Store block number -> hash mapping
-        let number_key = b"block:number:0";
+        let number_key = b"number:0";
         db.put(number_key, genesis_hash.as_bytes())?;
         
         // Store genesis hash
@@ -357,7 +349,7 @@ impl Genesis {
         if bytes.len() != 20 {
             anyhow::bail!("Invalid address length: {}", bytes.len());
         }
-        Ok(Address::from_slice(&bytes))
+        Ok(Address::from_slice(&bytes)?)
     }
     
     fn parse_bytes(&self, s: &str) -> Result<Vec<u8>> {
@@ -395,6 +387,112 @@ mod tests {
         assert_eq!(block.header.number, U256::zero());
     }
     
+    fn h256(hex_str: &str) -> H256 {
+        H256::from_slice(&hex::decode(hex_str).unwrap())
+    }
+    
+    #[test]
+    fn test_mainnet_genesis_hash() {
+        // The mainnet alloc isn't bundled, so start from its known state root
+        let state_root = h256("d7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544");
+        let header = Genesis::mainnet().header(state_root).unwrap();
+        
+        assert_eq!(header.base_fee_per_gas, None);
+        assert_eq!(header.withdrawals_root, None);
+        assert_eq!(
+            header.hash(),
+            h256("d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3")
+        );
+        
+        // Without an alloc the state is empty
+        let (header, root) = Genesis::mainnet().build().unwrap();
+        assert_eq!(root, empty_root());
+        assert_eq!(header.state_root, root);
+    }
+    
+    #[tokio::test]
+    async fn test_custom_alloc_state_root() {
+        let genesis = Genesis::from_json(r#"{
+            "config": { "chainId": 1337, "londonBlock": 0, "shanghaiTime": 0 },
+            "nonce": "0x0",
+            "timestamp": "0x0",
+            "extraData": "0x",
+            "gasLimit": "0x1c9c380",
+            "difficulty": "0x0",
+            "mixHash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "coinbase": "0x0000000000000000000000000000000000000000",
+            "alloc": {
+                "0x00000000000000000000000000000000000000aa": { "balance": "1000000000000000000" },
+                "0x00000000000000000000000000000000000000bb": {
+                    "balance": "0x0",
+                    "nonce": 1,
+                    "code": "0x600054",
+                    "storage": {
+                        "0x0000000000000000000000000000000000000000000000000000000000000000": "0x000000000000000000000000000000000000000000000000000000000000002a",
+                        "0x0000000000000000000000000000000000000000000000000000000000000001": "0x0000000000000000000000000000000000000000000000000000000000000000"
+                    }
+                }
+            }
+        }"#).unwrap();
+        
+        let (header, state_root) = genesis.build().unwrap();
+        assert_ne!(state_root, empty_root());
+        assert_eq!(header.state_root, state_root);
+        assert_eq!(header.gas_limit, U256::from(30_000_000));
+        assert_eq!(header.base_fee_per_gas, Some(U256::from(INITIAL_BASE_FEE)));
+        assert_eq!(header.withdrawals_root, Some(empty_root()));
+        assert_eq!(header.blob_gas_used, None);
+        
+        // Deterministic regardless of the alloc's iteration order
+        assert_eq!(genesis.build().unwrap().1, state_root);
+        
+        // Writing the state produces the same root and block
+        let db = Arc::new(MemoryDatabase::new());
+        let block = genesis.build_block(db.clone()).await.unwrap();
+        assert_eq!(block.header, header);
+        assert_eq!(block.withdrawals, Some(vec![]));
+        
+        let code_hash = keccak256(&[0x60, 0x00, 0x54]);
+        let code_key = format!("code:{}", hex::encode(code_hash.as_bytes()));
+        assert_eq!(db.get(code_key.as_bytes()).unwrap(), Some(vec![0x60, 0x00, 0x54]));
+    }
+    
+    #[test]
+    fn test_rinkeby_genesis_state_root() {
+        // Rinkeby's alloc is small enough to spell out: one wei for each of
+        // the first 256 addresses and the faucet's balance
+        let mut alloc = serde_json::Map::new();
+        for i in 0..=255u8 {
+            alloc.insert(format!("0x{}", hex::encode([&[0u8; 19][..], &[i]].concat())), serde_json::json!({ "balance": "0x1" }));
+        }
+        alloc.insert(
+            "0x31b98d14007bdee637298086988a0bbd31184523".to_string(),
+            serde_json::json!({ "balance": "0x200000000000000000000000000000000000000000000000000000000000000" }),
+        );
+        let genesis: GenesisConfig = serde_json::from_value(serde_json::json!({
+            "config": { "chainId": 4, "homesteadBlock": 1, "eip150Block": 2, "eip155Block": 3, "eip158Block": 3 },
+            "nonce": "0x0",
+            "timestamp": "0x58ee40ba",
+            "extraData": format!(
+                "0x{}{}",
+                "52657370656374206d7920617574686f7269746168207e452e436172746d616e42eb768f2244c8811c63729a21a3569731535f067ffc57839b00206d1ad20c69a1981b489f772031b279182d99e65703f0076e4812653aab85fca0f0",
+                "00".repeat(65),
+            ),
+            "gasLimit": "0x47b760",
+            "difficulty": "0x1",
+            "mixHash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "coinbase": "0x0000000000000000000000000000000000000000",
+            "alloc": alloc,
+        })).unwrap();
+        
+        let (header, state_root) = Genesis { config: genesis }.build().unwrap();
+        assert_eq!(state_root, h256("53580584816f617295ea26c0e17641e0120cab2f0a8ffb53a866fd53aa8e8c2d"));
+        assert_eq!(
+            header.hash(),
+            h256("6341fd3daf94b748c72ced5a5b26028f2474f5f00d824504e4fa37a75767e177")
+        );
+    }
+    
     #[test]
     fn test_parse_u256() {
         let genesis = Genesis::mainnet();
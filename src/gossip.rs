@@ -0,0 +1,35 @@
+use ethereum_core::Transaction;
+use ethereum_network::GossipPool;
+use ethereum_txpool::TransactionPool;
+use ethereum_types::H256;
+use std::sync::Arc;
+
+/// Transaction gossip backed by the node's transaction pool
+pub struct TxPoolGossip {
+    txpool: Arc<TransactionPool>,
+}
+
+impl TxPoolGossip {
+    pub fn new(txpool: Arc<TransactionPool>) -> Self {
+        Self { txpool }
+    }
+}
+
+impl GossipPool for TxPoolGossip {
+    fn contains(&self, hash: &H256) -> bool {
+        self.txpool.get_transaction(hash).is_some()
+    }
+
+    fn transaction(&self, hash: &H256) -> Option<Transaction> {
+        self.txpool.get_transaction(hash).map(|pooled| pooled.tx)
+    }
+
+    fn import(&self, txs: Vec<Transaction>) {
+        for tx in txs {
+            let hash = tx.hash();
+            if let Err(e) = self.txpool.add_transaction(tx) {
+                tracing::debug!("Dropped fetched transaction {:?}: {}", hash, e);
+            }
+        }
+    }
+}
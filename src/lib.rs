@@ -1,12 +1,17 @@
 // Core modules
 pub mod config;
 pub mod genesis;
+pub mod gossip;
 pub mod node;
+pub mod snap;
 
 // Re-export commonly used types
 pub use config::{Config, NodeConfig, NetworkConfig, RpcConfig};
-pub use genesis::{Genesis, GenesisConfig, ChainConfig};
+pub use genesis::{Genesis, GenesisConfig};
+pub use gossip::TxPoolGossip;
+pub use ethereum_evm::ChainConfig;
 pub use node::{Node, NodeInfo};
+pub use snap::TrieSnapState;
 
 // Re-export crate modules
 pub use ethereum_core as core;
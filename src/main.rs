@@ -1,15 +1,16 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use tracing::info;
-use tracing_subscriber::{self, EnvFilter};
+use tracing_subscriber::{self, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use std::path::PathBuf;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 use ethereum_storage::{RocksDatabase, MemoryDatabase};
 use ethereum_rpc::{RpcServer, RpcHandler};
-use ethereum_network::discovery::Discovery;
-use secp256k1::SecretKey;
+use ethereum_monitor::{MetricsConfig, MetricsServer, MetricsServerConfig, Monitor};
+use ethereum_network::discovery::{default_bootnodes, parse_bootnodes, Discovery};
+use ethereum_rust::node::load_node_key;
 
 #[derive(Parser)]
 #[command(name = "ethereum-rust")]
@@ -45,6 +46,14 @@ enum Commands {
         /// P2P port
         #[arg(long, default_value = "30303")]
         p2p_port: u16,
+        
+        /// Prometheus metrics port
+        #[arg(long, default_value = "9090")]
+        metrics_port: u16,
+        
+        /// Comma separated enode URLs, replacing the network's default bootnodes
+        #[arg(long, value_delimiter = ',')]
+        bootnodes: Vec<String>,
     },
     
     /// Initialize a new genesis block
@@ -124,8 +133,12 @@ async fn main() -> Result<()> {
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(&cli.log_level));
     
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
+    // Logged warnings and errors are counted in the monitor's metrics
+    let monitor = Arc::new(Monitor::new(MetricsConfig::default())?);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(monitor.log_metrics_layer())
         .init();
     
     match cli.command {
@@ -135,6 +148,8 @@ async fn main() -> Result<()> {
             http_port,
             ws_port,
             p2p_port,
+            metrics_port,
+            bootnodes,
         } => {
             info!(
                 "Starting Ethereum Rust node on {} network",
@@ -144,6 +159,18 @@ async fn main() -> Result<()> {
             info!("HTTP RPC port: {}", http_port);
             info!("WebSocket RPC port: {}", ws_port);
             info!("P2P port: {}", p2p_port);
+            info!("Metrics port: {}", metrics_port);
+            
+            // Serve the monitor's metrics, logged warnings and errors included
+            let metrics_server = MetricsServer::new(
+                MetricsServerConfig {
+                    host: "127.0.0.1".to_string(),
+                    port: metrics_port,
+                    ..MetricsServerConfig::default()
+                },
+                monitor,
+            );
+            metrics_server.start().await?;
             
             run_node(
                 PathBuf::from(datadir),
@@ -151,6 +178,7 @@ async fn main() -> Result<()> {
                 http_port,
                 ws_port,
                 p2p_port,
+                bootnodes,
             ).await?;
         }
         
@@ -205,6 +233,7 @@ async fn run_node(
     http_port: u16,
     ws_port: u16,
     p2p_port: u16,
+    bootnodes: Vec<String>,
 ) -> Result<()> {
     // Initialize database
     let db_path = datadir.join("chaindata");
@@ -228,14 +257,25 @@ async fn run_node(
     // Initialize P2P networking
     let p2p_addr: SocketAddr = format!("0.0.0.0:{}", p2p_port).parse()?;
     
-    // Generate or load node key
-    let node_key = SecretKey::new(&mut rand::thread_rng());
+    // Load the node key, generating it on first start
+    let node_key = load_node_key(&datadir)?;
     
     // Start discovery protocol
     let discovery = Arc::new(Discovery::new(node_key, p2p_addr).await?);
+    discovery.clone().run().await;
+    
+    // Seed the routing table from the bootnodes
+    let bootnodes = if bootnodes.is_empty() {
+        parse_bootnodes(default_bootnodes(&network))?
+    } else {
+        parse_bootnodes(&bootnodes)?
+    };
+    info!("Bootstrapping discovery from {} bootnodes", bootnodes.len());
     let discovery_handle = discovery.clone();
     tokio::spawn(async move {
-        discovery_handle.run().await;
+        if let Err(e) = discovery_handle.bootstrap(bootnodes).await {
+            tracing::warn!("Discovery bootstrap failed: {}", e);
+        }
     });
     
     // Initialize JSON-RPC server
@@ -1,21 +1,29 @@
 use std::sync::Arc;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::sync::{RwLock, mpsc, broadcast};
 use tokio::task::JoinHandle;
 use anyhow::{Result, Context};
 use tracing::{info, error, warn};
 
-use ethereum_types::{H256, U256};
+use ethereum_types::{H256, H512, U256};
 use ethereum_core::{Block, Transaction};
 use ethereum_storage::{Database, RocksDatabase};
-use ethereum_network::{NetworkManager, PeerManager};
-use ethereum_rpc::{RpcServer, RpcHandler};
+use ethereum_network::{
+    NetworkManager, PeerManager, Protocol, ProtocolHandler, TxGossip, ETH_MSG_ID_OFFSET,
+    NEW_POOLED_TRANSACTION_HASHES,
+};
+use ethereum_network::discovery::NodeId;
+use ethereum_rpc::{AdminApi, NodeInfo as AdminNodeInfo, PersonalApi, RpcServer, RpcHandler, RpcLimits};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use ethereum_account::AccountManager;
 use ethereum_consensus::{Consensus, ConsensusConfig, EngineType};
-use ethereum_sync::{Synchronizer, SyncConfig, SyncMode};
-use ethereum_txpool::TransactionPool;
+use ethereum_sync::{StateNonceProvider, Synchronizer, SyncConfig, SyncMode};
+use ethereum_txpool::{TransactionPool, TxPoolConfig as PoolConfig, TxPoolEvent};
 use ethereum_filter::FilterSystem;
 use ethereum_verification::{VerificationEngine, VerificationConfig};
 
+use crate::gossip::TxPoolGossip;
+
 /// Node configuration
 #[derive(Debug, Clone)]
 pub struct NodeConfig {
@@ -46,6 +54,31 @@ pub struct RpcConfig {
     pub port: u16,
     pub apis: Vec<String>,
     pub cors: Vec<String>,
+    pub limits: RpcLimits,
+    /// Serve the personal_ namespace from the keystore in the data directory
+    pub enable_personal: bool,
+    /// Also serve it when listening on a non-loopback address
+    pub allow_public_personal: bool,
+    /// Serve the admin_ namespace for managing peers
+    pub enable_admin: bool,
+}
+
+impl RpcConfig {
+    /// Whether the personal_ namespace is enabled and safe to serve on `host`
+    pub fn serves_personal(&self) -> bool {
+        self.enable_personal && (self.is_loopback() || self.allow_public_personal)
+    }
+
+    /// Whether the admin_ namespace is enabled and `host` is loopback, it is
+    /// never served on a public address
+    pub fn serves_admin(&self) -> bool {
+        self.enable_admin && self.is_loopback()
+    }
+
+    fn is_loopback(&self) -> bool {
+        self.host == "localhost"
+            || self.host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -55,6 +88,28 @@ pub struct P2pConfig {
     pub port: u16,
     pub max_peers: usize,
     pub bootnodes: Vec<String>,
+    /// Key behind our node id, `None` to use the one kept in the data
+    /// directory
+    pub node_key: Option<SecretKey>,
+}
+
+/// File in the data directory holding the node key
+const NODE_KEY_FILE: &str = "nodekey";
+
+/// Node key kept in `data_dir`, generated and stored on first use so
+/// discovery, RLPx and admin_nodeInfo all present the same node id
+pub fn load_node_key(data_dir: &Path) -> Result<SecretKey> {
+    let path = data_dir.join(NODE_KEY_FILE);
+    if path.exists() {
+        let hex_key = std::fs::read_to_string(&path).context("Failed to read node key")?;
+        let bytes = hex::decode(hex_key.trim()).context("Node key is not hex")?;
+        return SecretKey::from_slice(&bytes).context("Invalid node key");
+    }
+
+    let key = SecretKey::new(&mut rand::thread_rng());
+    std::fs::create_dir_all(data_dir)?;
+    std::fs::write(&path, hex::encode(key.secret_bytes())).context("Failed to store node key")?;
+    Ok(key)
 }
 
 #[derive(Debug, Clone)]
@@ -76,6 +131,10 @@ impl Default for NodeConfig {
                 port: 8545,
                 apis: vec!["eth".to_string(), "net".to_string(), "web3".to_string()],
                 cors: vec!["*".to_string()],
+                limits: RpcLimits::default(),
+                enable_personal: false,
+                allow_public_personal: false,
+                enable_admin: false,
             },
             ws_rpc: RpcConfig {
                 enabled: true,
@@ -83,6 +142,10 @@ impl Default for NodeConfig {
                 port: 8546,
                 apis: vec!["eth".to_string(), "net".to_string(), "web3".to_string()],
                 cors: vec!["*".to_string()],
+                limits: RpcLimits::default(),
+                enable_personal: false,
+                allow_public_personal: false,
+                enable_admin: false,
             },
             p2p: P2pConfig {
                 enabled: true,
@@ -90,6 +153,7 @@ impl Default for NodeConfig {
                 port: 30303,
                 max_peers: 25,
                 bootnodes: vec![],
+                node_key: None,
             },
             consensus: ConsensusConfig {
                 engine_type: EngineType::ProofOfStake,
@@ -108,6 +172,9 @@ impl Default for NodeConfig {
                 max_state_request: 384,
                 timeout: std::time::Duration::from_secs(10),
                 retry_limit: 3,
+                checkpoint_backfill: 8192,
+                stall_timeout: std::time::Duration::from_secs(60),
+                max_stall_restarts: 5,
             },
             txpool: TxPoolConfig {
                 max_pending: 4096,
@@ -133,6 +200,10 @@ pub struct Node<D: Database> {
     // Network components
     network_manager: Option<Arc<NetworkManager>>,
     peer_manager: Arc<PeerManager>,
+    /// Announcement and fetch state shared by outbound and inbound gossip
+    tx_gossip: Arc<std::sync::Mutex<TxGossip>>,
+    /// Handler for inbound eth messages
+    eth_protocol: Arc<ProtocolHandler>,
     
     // RPC servers
     http_server: Option<Arc<RpcServer>>,
@@ -175,18 +246,30 @@ impl<D: Database + 'static> Node<D> {
         // Initialize peer manager
         let peer_manager = Arc::new(PeerManager::new());
         
+        // Initialize transaction pool, nonces come from the head state
+        let txpool = Arc::new(TransactionPool::new(
+            PoolConfig {
+                max_size: config.txpool.max_pending,
+                global_queue: config.txpool.max_queued,
+                price_limit: config.txpool.gas_price_floor,
+                ..PoolConfig::default()
+            },
+            Arc::new(StateNonceProvider::new(db.clone())),
+        ));
+        
+        // Inbound transaction gossip fetches into and serves from the pool
+        let tx_gossip = Arc::new(std::sync::Mutex::new(TxGossip::new()));
+        let eth_protocol = Arc::new(
+            ProtocolHandler::new(Protocol::eth())
+                .with_tx_gossip(tx_gossip.clone(), Arc::new(TxPoolGossip::new(txpool.clone()))),
+        );
+        
         // Initialize synchronizer
         let sync = Arc::new(RwLock::new(Synchronizer::new(
             config.sync.clone(),
             db.clone(),
             peer_manager.clone(),
-        )));
-        
-        // Initialize transaction pool
-        let txpool = Arc::new(TransactionPool::new(
-            config.txpool.max_pending,
-            config.txpool.max_queued,
-        ));
+        ).with_txpool(txpool.clone())));
         
         // Initialize filter system
         let filter_system = Arc::new(FilterSystem::new(db.clone()));
@@ -216,6 +299,8 @@ impl<D: Database + 'static> Node<D> {
             verification,
             network_manager: None,
             peer_manager,
+            tx_gossip,
+            eth_protocol,
             http_server: None,
             ws_server: None,
             block_events,
@@ -242,6 +327,9 @@ impl<D: Database + 'static> Node<D> {
         
         // Start transaction pool
         self.start_txpool().await?;
+        if self.config.p2p.enabled {
+            self.start_tx_gossip().await?;
+        }
         
         // Start RPC servers
         if self.config.http_rpc.enabled {
@@ -317,9 +405,58 @@ impl<D: Database + 'static> Node<D> {
         self.tasks.write().await.push(handle);
         self.network_manager = Some(network_manager);
         
+        // Dial the nodes queued through admin_addPeer
+        let dialer = self.peer_manager.clone();
+        let node_key = self.node_key()?;
+        let handle = tokio::spawn(async move {
+            dialer.run_dials(node_key).await;
+        });
+        self.tasks.write().await.push(handle);
+        
+        // Answer the eth messages peers send us
+        let eth_protocol = self.eth_protocol.clone();
+        let peer_manager = self.peer_manager.clone();
+        let mut inbound = self.peer_manager.subscribe();
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        let handle = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    message = inbound.recv() => {
+                        let message = match message {
+                            Ok(message) => message,
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                                warn!("Dropped {} inbound peer messages", skipped);
+                                continue;
+                            }
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        };
+                        // Ids below the offset belong to the p2p base protocol
+                        let Some(msg_id) = message.msg_id.checked_sub(ETH_MSG_ID_OFFSET) else { continue };
+                        
+                        if let Err(e) = handle_eth_message(&eth_protocol, &peer_manager, message.peer, msg_id, &message.data).await {
+                            warn!("Failed to handle eth message {:#x} from {:?}: {}", msg_id, message.peer, e);
+                        }
+                    }
+                    _ = shutdown_rx.recv() => {
+                        info!("Peer message handling shutting down");
+                        break;
+                    }
+                }
+            }
+        });
+        self.tasks.write().await.push(handle);
+        
         Ok(())
     }
     
+    /// Key behind our node id
+    fn node_key(&self) -> Result<SecretKey> {
+        match self.config.p2p.node_key {
+            Some(key) => Ok(key),
+            None => load_node_key(&self.config.data_dir),
+        }
+    }
+    
     /// Start synchronization
     async fn start_sync(&self) -> Result<()> {
         info!("Starting blockchain synchronization");
@@ -380,6 +517,62 @@ impl<D: Database + 'static> Node<D> {
         Ok(())
     }
     
+    /// Announce transactions entering the pool to peers
+    async fn start_tx_gossip(&self) -> Result<()> {
+        let txpool = self.txpool.clone();
+        let peer_manager = self.peer_manager.clone();
+        let gossip = self.tx_gossip.clone();
+        let mut pool_events = txpool.subscribe();
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        
+        let handle = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    event = pool_events.recv() => {
+                        let hash = match event {
+                            Ok(TxPoolEvent::NewTransaction(hash)) => hash,
+                            Ok(_) => continue,
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                                warn!("Transaction gossip skipped {} pool events", skipped);
+                                continue;
+                            }
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        };
+                        let Some(pooled) = txpool.get_transaction(&hash) else { continue };
+                        
+                        let peers = peer_manager.get_all_peers().await;
+                        let node_ids: Vec<_> = peers.iter().map(|peer| peer.id.node_id).collect();
+                        let announcements = gossip.lock().unwrap().announce(&[pooled.tx], &node_ids);
+                        for (node_id, announcement) in announcements {
+                            let Some(peer) = peers.iter().find(|peer| peer.id.node_id == node_id) else { continue };
+                            let Some(session) = &peer.session else { continue };
+                            
+                            let frame = session.write().await.write_message(
+                                ETH_MSG_ID_OFFSET + NEW_POOLED_TRANSACTION_HASHES,
+                                &announcement.encode(),
+                            );
+                            match frame {
+                                Ok(frame) => {
+                                    if let Err(e) = peer.send(frame).await {
+                                        warn!("Failed to announce transactions to {:?}: {}", node_id, e);
+                                    }
+                                }
+                                Err(e) => warn!("Failed to frame transaction announcement: {}", e),
+                            }
+                        }
+                    }
+                    _ = shutdown_rx.recv() => {
+                        info!("Transaction gossip shutting down");
+                        break;
+                    }
+                }
+            }
+        });
+        
+        self.tasks.write().await.push(handle);
+        Ok(())
+    }
+    
     /// Start HTTP RPC server
     async fn start_http_rpc(&mut self) -> Result<()> {
         let addr = format!("{}:{}", self.config.http_rpc.host, self.config.http_rpc.port);
@@ -387,16 +580,41 @@ impl<D: Database + 'static> Node<D> {
         
         let client_version = format!("ethereum-rust/v{}/rust", env!("CARGO_PKG_VERSION"));
         
-        let rpc_handler = Arc::new(RpcHandler::new(
+        let mut rpc_handler = RpcHandler::new(
             self.db.clone(),
             self.config.chain_id,
-            client_version,
-        ));
+            client_version.clone(),
+        )
+        .with_txpool(self.txpool.clone())
+        .with_checkpoint_store(self.consensus.checkpoints());
+        if self.config.http_rpc.serves_personal() {
+            let accounts = AccountManager::new(self.config.data_dir.join("keystore"))
+                .context("Failed to open keystore")?;
+            rpc_handler = rpc_handler.with_personal(PersonalApi::new(
+                Arc::new(RwLock::new(accounts)),
+                self.txpool.clone(),
+                self.config.chain_id,
+            ));
+        } else if self.config.http_rpc.enable_personal {
+            warn!("Not serving personal_ on public address {}, set allow_public_personal to opt in", addr);
+        }
+        if self.config.http_rpc.serves_admin() {
+            let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &self.node_key()?);
+            let local = NodeId::new(
+                public_key,
+                format!("{}:{}", self.config.p2p.listen_addr, self.config.p2p.port).parse()?,
+            );
+            let info = AdminNodeInfo::new(client_version.clone(), &local, self.config.p2p.port);
+            rpc_handler = rpc_handler.with_admin(AdminApi::new(info, self.peer_manager.clone()));
+        } else if self.config.http_rpc.enable_admin {
+            warn!("Not serving admin_ on public address {}", addr);
+        }
+        let rpc_handler = Arc::new(rpc_handler);
         
-        let server = Arc::new(RpcServer::new(
-            addr.parse()?,
-            rpc_handler,
-        ));
+        let server = Arc::new(
+            RpcServer::new(addr.parse()?, rpc_handler)
+                .with_limits(self.config.http_rpc.limits.clone())
+        );
         
         let srv = server.clone();
         let mut shutdown_rx = self.shutdown_tx.subscribe();
@@ -476,7 +694,7 @@ impl<D: Database + 'static> Node<D> {
         )?;
         
         // Update chain head
-        let number_key = format!("block:number:{}", block.header.number);
+        let number_key = format!("number:{}", block.header.number);
         self.db.put(
             number_key.as_bytes(),
             block.header.hash().as_bytes(),
@@ -528,6 +746,26 @@ impl<D: Database + 'static> Node<D> {
     }
 }
 
+/// Handle an eth message received from `peer`, with its id relative to the
+/// eth offset, and send back the response if there is one
+async fn handle_eth_message(
+    eth_protocol: &ProtocolHandler,
+    peer_manager: &PeerManager,
+    peer: H512,
+    msg_id: u8,
+    data: &[u8],
+) -> Result<()> {
+    let Some((response_id, payload)) = eth_protocol.handle_message(peer, msg_id, data)? else {
+        return Ok(());
+    };
+    let Some(peer) = peer_manager.get_peer(&peer).await else { return Ok(()) };
+    let Some(session) = &peer.session else { return Ok(()) };
+    
+    let frame = session.write().await.write_message(ETH_MSG_ID_OFFSET + response_id, &payload)?;
+    peer.send(frame).await?;
+    Ok(())
+}
+
 /// Node information
 #[derive(Debug, Clone)]
 pub struct NodeInfo {
@@ -552,4 +790,23 @@ mod tests {
         let node = Node::with_database(config, db).await;
         assert!(node.is_ok());
     }
+    
+    #[test]
+    fn test_admin_only_on_loopback() {
+        let mut config = NodeConfig::default().http_rpc;
+        config.enable_admin = true;
+        assert!(config.serves_admin());
+        
+        // Unlike personal_, there is no opting in to a public address
+        config.host = "0.0.0.0".to_string();
+        config.allow_public_personal = true;
+        assert!(!config.serves_admin());
+    }
+    
+    #[test]
+    fn test_node_key_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = load_node_key(dir.path()).unwrap();
+        assert_eq!(load_node_key(dir.path()).unwrap(), key);
+    }
 }
\ No newline at end of file
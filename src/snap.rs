@@ -0,0 +1,169 @@
+use ethereum_network::snap::SnapState;
+use ethereum_network::{NetworkError, Result};
+use ethereum_storage::Database;
+use ethereum_trie::{Nibbles, TrieError, TrieReadView};
+use ethereum_types::H256;
+use std::sync::Arc;
+
+/// Snap server state read from the node's trie database
+pub struct TrieSnapState<D: Database> {
+    db: Arc<D>,
+}
+
+impl<D: Database> TrieSnapState<D> {
+    pub fn new(db: Arc<D>) -> Self {
+        Self { db }
+    }
+
+    fn view(&self, root: H256) -> Result<TrieReadView<D>> {
+        TrieReadView::at_root(self.db.clone(), root).map_err(state_error)
+    }
+}
+
+impl<D: Database> SnapState for TrieSnapState<D> {
+    fn has_root(&self, root: H256) -> Result<bool> {
+        match TrieReadView::at_root(self.db.clone(), root) {
+            Ok(_) => Ok(true),
+            Err(TrieError::KeyNotFound) => Ok(false),
+            Err(e) => Err(state_error(e)),
+        }
+    }
+
+    fn get(&self, root: H256, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.view(root)?.get(key).map_err(state_error)
+    }
+
+    fn range(&self, root: H256, origin: &[u8], limit: &[u8], byte_limit: usize) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.view(root)?.range(origin, limit, byte_limit).map_err(state_error)
+    }
+
+    fn prove(&self, root: H256, key: &[u8]) -> Result<Vec<Vec<u8>>> {
+        Ok(self.view(root)?.prove(key).map_err(state_error)?.nodes)
+    }
+
+    fn node_at_path(&self, root: H256, path: &[u8]) -> Result<Option<Vec<u8>>> {
+        let (path, _) = Nibbles::decode_compact(path)
+            .map_err(|e| NetworkError::InvalidMessage(format!("invalid trie path: {}", e)))?;
+        self.view(root)?.node_at_path(&path).map_err(state_error)
+    }
+
+    fn code(&self, hash: H256) -> Result<Option<Vec<u8>>> {
+        let key = format!("code:{}", hex::encode(hash));
+        self.db.get(key.as_bytes())
+            .map_err(|e| NetworkError::StateError(e.to_string()))
+    }
+}
+
+fn state_error(e: TrieError) -> NetworkError {
+    NetworkError::StateError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_crypto::keccak256;
+    use ethereum_network::snap::{
+        AccountRange, GetAccountRange, SnapServer, ACCOUNT_RANGE, GET_ACCOUNT_RANGE, MAX_RESPONSE_BYTES,
+    };
+    use ethereum_rlp::Encoder;
+    use ethereum_storage::MemoryDatabase;
+    use ethereum_trie::{empty_root, verify_range_proof, PatriciaTrie};
+    use ethereum_types::U256;
+
+    const MAX_HASH: [u8; 32] = [0xff; 32];
+
+    fn account_body(nonce: u64, balance: u64) -> Vec<u8> {
+        let mut balance_bytes = [0u8; 32];
+        U256::from(balance).to_big_endian(&mut balance_bytes);
+        let balance_start = balance_bytes.iter().position(|b| *b != 0).unwrap_or(32);
+
+        let mut fields = Encoder::new();
+        fields.encode_u64(nonce);
+        fields.encode_bytes(&balance_bytes[balance_start..]);
+        fields.encode_bytes(empty_root().as_bytes());
+        fields.encode_bytes(keccak256(&[]).as_bytes());
+
+        let mut encoder = Encoder::new();
+        encoder.encode_list_payload(&fields.finish());
+        encoder.finish()
+    }
+
+    /// State with 32 accounts keyed by the hash of their index
+    fn small_state() -> (SnapServer, H256, Vec<(Vec<u8>, Vec<u8>)>) {
+        let db = Arc::new(MemoryDatabase::new());
+        let mut trie = PatriciaTrie::new(db.clone());
+        let mut accounts = Vec::new();
+
+        for i in 0..32u64 {
+            let key = keccak256(&i.to_be_bytes()).as_bytes().to_vec();
+            let body = account_body(i, 1_000_000 + i);
+            trie.insert(&key, body.clone()).unwrap();
+            accounts.push((key, body));
+        }
+        accounts.sort();
+
+        let root = trie.commit().unwrap();
+        (SnapServer::new(Arc::new(TrieSnapState::new(db))), root, accounts)
+    }
+
+    fn verify(root: H256, origin: H256, response: &AccountRange) -> bool {
+        let keys: Vec<Vec<u8>> = response.accounts.iter().map(|a| a.hash.as_bytes().to_vec()).collect();
+        let values: Vec<Vec<u8>> = response.accounts.iter().map(|a| a.body.clone()).collect();
+        verify_range_proof(root, origin.as_bytes(), &keys, &values, &response.proof).unwrap()
+    }
+
+    #[test]
+    fn test_serve_account_range_with_proof() {
+        let (server, root, accounts) = small_state();
+
+        let request = GetAccountRange {
+            request_id: 7,
+            root_hash: root,
+            origin: H256::zero(),
+            limit: H256::from(MAX_HASH),
+            response_bytes: MAX_RESPONSE_BYTES,
+        };
+        let response = server.get_account_range(&request).unwrap();
+
+        assert_eq!(response.request_id, 7);
+        assert_eq!(response.accounts.len(), accounts.len());
+        assert_eq!(response.accounts[0].body, accounts[0].1);
+        assert!(verify(root, request.origin, &response));
+
+        // Dropping an account from the middle breaks the proof
+        let mut tampered = response.clone();
+        tampered.accounts.remove(10);
+        assert!(!verify(root, request.origin, &tampered));
+
+        // The response survives the wire
+        assert_eq!(AccountRange::decode(&response.encode()).unwrap(), response);
+    }
+
+    #[test]
+    fn test_account_range_respects_byte_limit() {
+        let (server, root, accounts) = small_state();
+
+        // Start in the middle of the key space with room for a few accounts
+        let origin = H256::from_slice(&accounts[5].0);
+        let request = GetAccountRange {
+            request_id: 1,
+            root_hash: root,
+            origin,
+            limit: H256::from(MAX_HASH),
+            response_bytes: 300,
+        };
+        let encoded = request.encode();
+        let (msg_id, payload) = server.handle_message(GET_ACCOUNT_RANGE, &encoded).unwrap().unwrap();
+        assert_eq!(msg_id, ACCOUNT_RANGE);
+
+        let response = AccountRange::decode(&payload).unwrap();
+        assert!(!response.accounts.is_empty());
+        assert!(response.accounts.len() < accounts.len() - 5);
+        assert_eq!(response.accounts[0].hash, origin);
+        assert!(verify(root, origin, &response));
+
+        // Unknown roots get an empty answer
+        let unknown = GetAccountRange { root_hash: H256::repeat_byte(0xab), ..request };
+        assert!(server.get_account_range(&unknown).unwrap().accounts.is_empty());
+    }
+}
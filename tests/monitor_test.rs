@@ -144,6 +144,36 @@ async fn test_collector_metrics_update() {
     assert!(system_metrics.disk_usage.len() > 0);
 }
 
+#[tokio::test]
+async fn test_collection_tick_samples_resources() {
+    let datadir = std::env::temp_dir().join(format!("monitor-datadir-{}", std::process::id()));
+    std::fs::create_dir_all(datadir.join("chaindata")).unwrap();
+    std::fs::write(datadir.join("chaindata").join("000001.log"), vec![0u8; 1000]).unwrap();
+    std::fs::write(datadir.join("nodekey"), vec![0u8; 64]).unwrap();
+    
+    let config = MetricsConfig {
+        collection_interval_secs: 1,
+        datadir: Some(datadir.clone()),
+        ..Default::default()
+    };
+    let monitor = Monitor::new(config).unwrap();
+    monitor.collector().write().await.collect_once();
+    
+    let metrics = monitor.metrics();
+    assert!(metrics.process_cpu_usage.get() >= 0.0);
+    assert!(metrics.process_memory_usage.get() > 0);
+    assert!(metrics.system_memory_usage.get() > 0);
+    #[cfg(target_os = "linux")]
+    assert!(metrics.process_open_fds.get() > 0);
+    assert_eq!(metrics.datadir_size.get(), 1064);
+    assert!(monitor.get_metrics().unwrap().contains("ethereum_datadir_size_bytes 1064"));
+    
+    // A datadir that disappears is skipped, the last value stays
+    std::fs::remove_dir_all(&datadir).unwrap();
+    monitor.collector().write().await.collect_once();
+    assert_eq!(metrics.datadir_size.get(), 1064);
+}
+
 #[tokio::test]
 async fn test_alert_thresholds() {
     use ethereum_monitor::alerts::AlertConfig;